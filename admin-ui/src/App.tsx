@@ -1,5 +1,7 @@
 import { useState, useEffect } from 'react'
 import { storage } from '@/lib/storage'
+import { isSsoEnabled } from '@/lib/config'
+import { getSession } from '@/api/credentials'
 import { LoginPage } from '@/components/login-page'
 import { Dashboard } from '@/components/dashboard'
 import { Toaster } from '@/components/ui/sonner'
@@ -11,6 +13,11 @@ function App() {
     // 检查是否已经有保存的 API Key
     if (storage.getApiKey()) {
       setIsLoggedIn(true)
+    } else if (isSsoEnabled()) {
+      // 检查是否已通过单点登录
+      getSession()
+        .then((session) => setIsLoggedIn(session.authenticated))
+        .catch(() => undefined)
     }
   }, [])
 
@@ -9,6 +9,7 @@ import type {
   SetPriorityRequest,
   AddCredentialRequest,
   AddCredentialResponse,
+  SessionResponse,
 } from '@/types/api'
 
 // 创建 axios 实例
@@ -85,3 +86,21 @@ export async function deleteCredential(id: number): Promise<SuccessResponse> {
   const { data } = await api.delete<SuccessResponse>(`/credentials/${id}`)
   return data
 }
+
+// 查询单点登录会话
+export async function getSession(): Promise<SessionResponse> {
+  const { data } = await api.get<SessionResponse>('/oidc/session')
+  return data
+}
+
+// 退出单点登录
+export async function logoutSession(): Promise<SuccessResponse> {
+  const { data } = await api.post<SuccessResponse>('/oidc/logout')
+  return data
+}
+
+// 注销全部单点登录会话
+export async function revokeAllSessions(): Promise<SuccessResponse> {
+  const { data } = await api.delete<SuccessResponse>('/sessions')
+  return data
+}
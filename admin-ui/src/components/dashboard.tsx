@@ -1,11 +1,21 @@
 import { useState } from 'react'
-import { RefreshCw, LogOut, Moon, Sun, Server, Plus } from 'lucide-react'
+import { RefreshCw, LogOut, Moon, Sun, Server, Plus, ShieldOff } from 'lucide-react'
 import { useQueryClient } from '@tanstack/react-query'
 import { toast } from 'sonner'
 import { storage } from '@/lib/storage'
+import { isSsoEnabled } from '@/lib/config'
+import { logoutSession, revokeAllSessions } from '@/api/credentials'
 import { Card, CardContent, CardHeader, CardTitle } from '@/components/ui/card'
 import { Button } from '@/components/ui/button'
 import { Badge } from '@/components/ui/badge'
+import {
+  Dialog,
+  DialogContent,
+  DialogDescription,
+  DialogFooter,
+  DialogHeader,
+  DialogTitle,
+} from '@/components/ui/dialog'
 import { CredentialCard } from '@/components/credential-card'
 import { BalanceDialog } from '@/components/balance-dialog'
 import { AddCredentialDialog } from '@/components/add-credential-dialog'
@@ -19,6 +29,7 @@ export function Dashboard({ onLogout }: DashboardProps) {
   const [selectedCredentialId, setSelectedCredentialId] = useState<number | null>(null)
   const [balanceDialogOpen, setBalanceDialogOpen] = useState(false)
   const [addDialogOpen, setAddDialogOpen] = useState(false)
+  const [revokeDialogOpen, setRevokeDialogOpen] = useState(false)
   const [darkMode, setDarkMode] = useState(() => {
     if (typeof window !== 'undefined') {
       return document.documentElement.classList.contains('dark')
@@ -44,12 +55,26 @@ export function Dashboard({ onLogout }: DashboardProps) {
     toast.success('已刷新凭据列表')
   }
 
-  const handleLogout = () => {
+  const handleLogout = async () => {
     storage.removeApiKey()
+    if (isSsoEnabled()) {
+      // 同时注销单点登录会话（未通过单点登录时无影响）
+      await logoutSession().catch(() => undefined)
+    }
     queryClient.clear()
     onLogout()
   }
 
+  const handleRevokeSessions = async () => {
+    try {
+      const result = await revokeAllSessions()
+      toast.success(result.message)
+      setRevokeDialogOpen(false)
+    } catch {
+      toast.error('注销会话失败')
+    }
+  }
+
   if (isLoading) {
     return (
       <div className="min-h-screen flex items-center justify-center bg-background">
@@ -94,6 +119,11 @@ export function Dashboard({ onLogout }: DashboardProps) {
             <Button variant="ghost" size="icon" onClick={handleRefresh}>
               <RefreshCw className="h-5 w-5" />
             </Button>
+            {isSsoEnabled() && (
+              <Button variant="ghost" size="icon" onClick={() => setRevokeDialogOpen(true)} title="注销全部会话">
+                <ShieldOff className="h-5 w-5" />
+              </Button>
+            )}
             <Button variant="ghost" size="icon" onClick={handleLogout}>
               <LogOut className="h-5 w-5" />
             </Button>
@@ -181,6 +211,26 @@ export function Dashboard({ onLogout }: DashboardProps) {
         open={addDialogOpen}
         onOpenChange={setAddDialogOpen}
       />
+
+      {/* 注销全部会话确认对话框 */}
+      <Dialog open={revokeDialogOpen} onOpenChange={setRevokeDialogOpen}>
+        <DialogContent>
+          <DialogHeader>
+            <DialogTitle>注销全部会话</DialogTitle>
+            <DialogDescription>
+              所有浏览器中的单点登录会话（包括当前会话）都将失效，需要重新登录。
+            </DialogDescription>
+          </DialogHeader>
+          <DialogFooter>
+            <Button variant="outline" onClick={() => setRevokeDialogOpen(false)}>
+              取消
+            </Button>
+            <Button variant="destructive" onClick={handleRevokeSessions}>
+              确认注销
+            </Button>
+          </DialogFooter>
+        </DialogContent>
+      </Dialog>
     </div>
   )
 }
@@ -1,6 +1,7 @@
 import { useState, useEffect } from 'react'
 import { KeyRound } from 'lucide-react'
 import { storage } from '@/lib/storage'
+import { getSsoLoginUrl, isSsoEnabled } from '@/lib/config'
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '@/components/ui/card'
 import { Input } from '@/components/ui/input'
 import { Button } from '@/components/ui/button'
@@ -55,6 +56,11 @@ export function LoginPage({ onLogin }: LoginPageProps) {
               登录
             </Button>
           </form>
+          {isSsoEnabled() && (
+            <Button asChild variant="outline" className="mt-4 w-full">
+              <a href={getSsoLoginUrl()}>使用单点登录</a>
+            </Button>
+          )}
         </CardContent>
       </Card>
     </div>
@@ -1,5 +1,7 @@
 interface KiroConfig {
   basePath: string
+  // 是否启用了 OIDC 单点登录
+  sso?: boolean
 }
 
 declare global {
@@ -16,3 +18,11 @@ export function getApiBaseUrl(): string {
   const { basePath } = getConfig()
   return `${basePath}/api/admin`
 }
+
+export function isSsoEnabled(): boolean {
+  return getConfig().sso === true
+}
+
+export function getSsoLoginUrl(): string {
+  return `${getApiBaseUrl()}/oidc/login`
+}
@@ -35,6 +35,12 @@ export interface SuccessResponse {
   message: string
 }
 
+// 单点登录会话
+export interface SessionResponse {
+  authenticated: boolean
+  subject?: string
+}
+
 // 错误响应
 export interface AdminErrorResponse {
   error: {
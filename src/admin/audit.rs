@@ -0,0 +1,262 @@
+//! Admin 操作审计日志
+//!
+//! Admin API 的每个写操作（非 GET / HEAD 请求，含认证失败的尝试）结束时追加一行 JSON：
+//! 序号、时间、方法、路径、状态码、客户端 IP 与操作者。每行的 `mac` 字段是以 `hmacKey`
+//! 对「上一行的 `mac` + 本行内容」计算的 HMAC-SHA256，形成链式校验：修改、删除或插入任意一行
+//! 都会使之后的校验失败，可用 `verify-audit-log` 子命令检查。重启后从文件最后一行继续链接。
+//!
+//! 只截掉文件末尾若干行无法仅凭文件本身发现，需要对照外部保存的最新 `seq` 与 `mac`。
+//!
+//! 配置了 `databasePath` 时每条记录（含 `mac`）同时写入数据库的 `audit` 表，便于查询。
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chrono::{SecondsFormat, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::database::Database;
+use crate::model::config::AuditLogConfig;
+
+/// 一次 Admin 写操作
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    /// 序号（从 1 开始连续递增）
+    pub seq: u64,
+    /// 时间（RFC3339）
+    pub timestamp: String,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+    /// 操作者：`admin`、`readOnly`（带 `adminReadOnly` 的 API Key）、`oidc:<用户>`（单点登录）或 `anonymous`（认证失败）
+    pub actor: String,
+}
+
+/// 文件中的一行
+#[derive(Serialize, Deserialize)]
+struct AuditLine {
+    #[serde(flatten)]
+    entry: AuditEntry,
+    mac: String,
+}
+
+/// 审计日志文件
+pub struct AuditLog {
+    path: PathBuf,
+    key: Vec<u8>,
+    state: Mutex<State>,
+    /// 同时写入的数据库（可选）
+    database: Option<Arc<Database>>,
+}
+
+/// 链的末端
+struct State {
+    file: File,
+    seq: u64,
+    mac: String,
+}
+
+impl AuditLog {
+    /// 打开审计日志，文件不存在时自动创建；已有文件的最后一行无法解析时返回错误
+    pub fn open(config: &AuditLogConfig) -> anyhow::Result<Self> {
+        let path = PathBuf::from(&config.path);
+        let (seq, mac) = match fs::read_to_string(&path) {
+            Ok(content) => match content.lines().rfind(|line| !line.trim().is_empty()) {
+                Some(line) => {
+                    let last: AuditLine = serde_json::from_str(line).map_err(|e| {
+                        anyhow::anyhow!(
+                            "审计日志 {} 的最后一行无法解析（{}），请使用 verify-audit-log 检查",
+                            path.display(),
+                            e
+                        )
+                    })?;
+                    (last.entry.seq, last.mac)
+                }
+                None => (0, String::new()),
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (0, String::new()),
+            Err(e) => anyhow::bail!("读取审计日志 {} 失败: {}", path.display(), e),
+        };
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            key: config.hmac_key.as_bytes().to_vec(),
+            state: Mutex::new(State { file, seq, mac }),
+            database: None,
+        })
+    }
+
+    /// 同时将每条记录写入数据库
+    pub fn with_database(mut self, database: Arc<Database>) -> Self {
+        self.database = Some(database);
+        self
+    }
+
+    /// 追加一条记录（序号与时间自动填写）
+    pub fn record(
+        &self,
+        method: &str,
+        path: &str,
+        status: u16,
+        client_ip: Option<String>,
+        actor: &str,
+    ) {
+        let mut state = self.state.lock();
+        let entry = AuditEntry {
+            seq: state.seq + 1,
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            method: method.to_string(),
+            path: path.to_string(),
+            status,
+            client_ip,
+            actor: actor.to_string(),
+        };
+        let result = chain_mac(&self.key, &state.mac, &entry).and_then(|mac| {
+            let line = serde_json::to_string(&AuditLine {
+                entry: entry.clone(),
+                mac: mac.clone(),
+            })?;
+            state.file.write_all(format!("{}\n", line).as_bytes())?;
+            Ok(mac)
+        });
+        match result {
+            Ok(mac) => {
+                if let Some(database) = &self.database
+                    && let Err(e) = database.insert_audit(&entry, &mac)
+                {
+                    tracing::warn!("写入审计记录到数据库失败: {}", e);
+                }
+                state.seq = entry.seq;
+                state.mac = mac;
+            }
+            Err(e) => tracing::warn!("写入审计日志失败 ({}): {}", self.path.display(), e),
+        }
+    }
+}
+
+/// 校验审计日志内容，返回记录数；校验失败时返回出错的行号与原因
+pub fn verify(content: &str, key: &str) -> Result<u64, String> {
+    let mut seq = 0;
+    let mut mac = String::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let number = index + 1;
+        let parsed: AuditLine =
+            serde_json::from_str(line).map_err(|e| format!("第 {} 行无法解析: {}", number, e))?;
+        if parsed.entry.seq != seq + 1 {
+            return Err(format!(
+                "第 {} 行序号为 {}，应为 {}（记录被删除或插入）",
+                number,
+                parsed.entry.seq,
+                seq + 1
+            ));
+        }
+        let expected = chain_mac(key.as_bytes(), &mac, &parsed.entry)
+            .map_err(|e| format!("第 {} 行: {}", number, e))?;
+        if expected != parsed.mac {
+            return Err(format!(
+                "第 {} 行（seq {}）校验失败，该行或之前的记录已被修改",
+                number, parsed.entry.seq
+            ));
+        }
+        seq = parsed.entry.seq;
+        mac = parsed.mac;
+    }
+    Ok(seq)
+}
+
+/// 本行的 mac：HMAC-SHA256(key, 上一行 mac + 本行 JSON)
+fn chain_mac(key: &[u8], previous: &str, entry: &AuditEntry) -> anyhow::Result<String> {
+    let json = serde_json::to_string(entry)?;
+    Ok(hex::encode(hmac_sha256(
+        key,
+        &[previous.as_bytes(), json.as_bytes()],
+    )))
+}
+
+/// HMAC-SHA256（RFC 2104）
+pub(crate) fn hmac_sha256(key: &[u8], message: &[&[u8]]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    for part in message {
+        inner.update(part);
+    }
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_sha256_rfc4231() {
+        assert_eq!(
+            hex::encode(hmac_sha256(
+                b"Jefe",
+                &[b"what do ya ", b"want for nothing?"]
+            )),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_chain_detects_tampering() {
+        let dir = std::env::temp_dir().join(format!("kiro-audit-{}", uuid::Uuid::new_v4()));
+        let config = AuditLogConfig {
+            path: dir.join("audit.log").to_string_lossy().into_owned(),
+            hmac_key: "audit-secret-key".to_string(),
+        };
+        let log = AuditLog::open(&config).unwrap();
+        log.record("POST", "/api/admin/credentials", 200, None, "admin");
+        log.record("DELETE", "/api/admin/credentials/2", 401, None, "anonymous");
+        drop(log);
+        // 重启后继续链接
+        let log = AuditLog::open(&config).unwrap();
+        log.record(
+            "PUT",
+            "/api/admin/routes",
+            200,
+            Some("203.0.113.9".into()),
+            "admin",
+        );
+
+        let content = std::fs::read_to_string(&config.path).unwrap();
+        assert_eq!(verify(&content, "audit-secret-key"), Ok(3));
+        assert!(verify(&content, "wrong-key").is_err());
+
+        let lines: Vec<&str> = content.lines().collect();
+        let modified = content.replace("\"status\":401", "\"status\":200");
+        assert!(
+            verify(&modified, "audit-secret-key")
+                .unwrap_err()
+                .contains("第 2 行")
+        );
+        let deleted = format!("{}\n{}\n", lines[0], lines[2]);
+        assert!(verify(&deleted, "audit-secret-key").is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -20,6 +20,15 @@ pub enum AdminServiceError {
 
     /// 凭据无效（验证失败）
     InvalidCredential(String),
+
+    /// 路由规则无效
+    InvalidRoute(String),
+
+    /// 查询参数无效
+    InvalidQuery(String),
+
+    /// 配置文件无效（重新加载失败）
+    InvalidConfig(String),
 }
 
 impl fmt::Display for AdminServiceError {
@@ -31,6 +40,11 @@ impl fmt::Display for AdminServiceError {
             AdminServiceError::UpstreamError(msg) => write!(f, "上游服务错误: {}", msg),
             AdminServiceError::InternalError(msg) => write!(f, "内部错误: {}", msg),
             AdminServiceError::InvalidCredential(msg) => write!(f, "凭据无效: {}", msg),
+            AdminServiceError::InvalidRoute(msg) => write!(f, "路由规则无效: {}", msg),
+            AdminServiceError::InvalidQuery(msg) => write!(f, "查询参数无效: {}", msg),
+            AdminServiceError::InvalidConfig(msg) => {
+                write!(f, "重新加载配置失败，继续使用当前配置: {}", msg)
+            }
         }
     }
 }
@@ -44,7 +58,10 @@ impl AdminServiceError {
             AdminServiceError::NotFound { .. } => StatusCode::NOT_FOUND,
             AdminServiceError::UpstreamError(_) => StatusCode::BAD_GATEWAY,
             AdminServiceError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            AdminServiceError::InvalidCredential(_) => StatusCode::BAD_REQUEST,
+            AdminServiceError::InvalidCredential(_)
+            | AdminServiceError::InvalidRoute(_)
+            | AdminServiceError::InvalidQuery(_)
+            | AdminServiceError::InvalidConfig(_) => StatusCode::BAD_REQUEST,
         }
     }
 
@@ -56,7 +73,10 @@ impl AdminServiceError {
             AdminServiceError::InternalError(_) => {
                 AdminErrorResponse::internal_error(self.to_string())
             }
-            AdminServiceError::InvalidCredential(_) => {
+            AdminServiceError::InvalidCredential(_)
+            | AdminServiceError::InvalidRoute(_)
+            | AdminServiceError::InvalidQuery(_)
+            | AdminServiceError::InvalidConfig(_) => {
                 AdminErrorResponse::invalid_request(self.to_string())
             }
         }
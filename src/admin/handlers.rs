@@ -1,14 +1,29 @@
 //! Admin API HTTP 处理器
 
+use std::convert::Infallible;
+use std::time::Duration;
+
 use axum::{
     Json,
-    extract::{Path, State},
-    response::IntoResponse,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{
+        AppendHeaders, IntoResponse, Redirect, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
 };
+use futures::{Stream, StreamExt, stream};
+use tokio::sync::broadcast::error::RecvError;
 
 use super::{
     middleware::AdminState,
-    types::{AddCredentialRequest, SetDisabledRequest, SetPriorityRequest, SuccessResponse},
+    oidc::{self, OidcError},
+    session::{self, SESSION_COOKIE},
+    types::{
+        AddCredentialRequest, ModelRoutesBody, OidcCallbackQuery, SessionResponse,
+        SetDisabledRequest, SetPriorityRequest, SetTagsRequest, SuccessResponse, TimeseriesQuery,
+        UsageQuery,
+    },
 };
 
 /// GET /api/admin/credentials
@@ -51,6 +66,46 @@ pub async fn set_credential_priority(
     }
 }
 
+/// POST /api/admin/credentials/:id/tags
+/// 设置凭据标签
+pub async fn set_credential_tags(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+    Json(payload): Json<SetTagsRequest>,
+) -> impl IntoResponse {
+    match state.service.set_tags(id, payload.tags) {
+        Ok(_) => Json(SuccessResponse::new(format!("凭据 #{} 标签已更新", id))).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// GET /api/admin/routes
+/// 获取模型路由规则
+pub async fn get_model_routes(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_routes())
+}
+
+/// PUT /api/admin/routes
+/// 替换模型路由规则
+pub async fn set_model_routes(
+    State(state): State<AdminState>,
+    Json(payload): Json<ModelRoutesBody>,
+) -> impl IntoResponse {
+    match state.service.set_routes(payload) {
+        Ok(_) => Json(SuccessResponse::new("模型路由规则已更新")).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// POST /api/admin/reload
+/// 重新加载配置与凭证文件（如轮换 Admin Key）
+pub async fn reload_config(State(state): State<AdminState>) -> impl IntoResponse {
+    match state.service.reload_config() {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
 /// POST /api/admin/credentials/:id/reset
 /// 重置失败计数并重新启用
 pub async fn reset_failure_count(
@@ -102,3 +157,219 @@ pub async fn delete_credential(
         Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
     }
 }
+
+/// GET /api/admin/usage
+/// 获取最近的请求用量记录
+pub async fn get_usage(
+    State(state): State<AdminState>,
+    Query(query): Query<UsageQuery>,
+) -> impl IntoResponse {
+    Json(state.service.get_usage(query.limit))
+}
+
+/// GET /api/admin/usage/summary
+/// 按 API Key 与终端用户汇总用量
+pub async fn get_usage_summary(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_usage_summary())
+}
+
+/// GET /api/admin/timeseries?metric=requests&window=24h&step=5m
+/// 获取按步长分桶的时间序列，供图表使用
+pub async fn get_timeseries(
+    State(state): State<AdminState>,
+    Query(query): Query<TimeseriesQuery>,
+) -> impl IntoResponse {
+    match state.service.get_timeseries(query) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// GET /api/admin/usage/reports
+/// 获取已生成的每日用量报告
+pub async fn get_usage_reports(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_usage_reports())
+}
+
+/// GET /api/admin/alerts
+/// 获取当前处于触发状态的错误率告警
+pub async fn get_alerts(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_alerts())
+}
+
+/// GET /api/admin/connections
+/// 获取上游连接复用统计（新建连接与复用已有连接的请求数）
+pub async fn get_connections(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_connections())
+}
+
+/// GET /api/admin/events
+/// 以 SSE 推送事件：连接时先发送一次当前告警（`alerts`），之后推送 `alert_fired` / `alert_resolved`
+pub async fn get_events(
+    State(state): State<AdminState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = state.service.subscribe_alerts();
+    let initial = Event::default()
+        .event("alerts")
+        .json_data(state.service.get_alerts())
+        .unwrap_or_default();
+
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let data = Event::default()
+                        .event(event.kind)
+                        .json_data(&event.alert)
+                        .unwrap_or_default();
+                    return Some((Ok(data), receiver));
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Admin 事件流落后，跳过 {} 个事件", skipped);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream::iter([Ok(initial)]).chain(events))
+        .keep_alive(KeepAlive::new().interval(Duration::from_secs(15)))
+}
+
+/// GET /api/admin/oidc/login
+/// 跳转到身份提供方登录（无需认证）
+pub async fn oidc_login(State(state): State<AdminState>) -> Response {
+    let Some(oidc) = &state.oidc else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match oidc.begin().await {
+        Ok((url, login_state)) => {
+            // 回调是从身份提供方发起的跨站跳转，state Cookie 需使用 SameSite=Lax
+            let cookie = format!(
+                "{}={}; {}",
+                oidc::STATE_COOKIE,
+                login_state,
+                oidc.cookie_attributes("/api/admin/oidc", oidc::LOGIN_TTL, "Lax")
+            );
+            (
+                AppendHeaders([(header::SET_COOKIE, cookie)]),
+                Redirect::to(&url),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::warn!("OIDC 登录失败: {}", e);
+            (StatusCode::BAD_GATEWAY, e.to_string()).into_response()
+        }
+    }
+}
+
+/// GET /api/admin/oidc/callback
+/// 身份提供方回调：校验通过后创建会话并跳转到 Admin UI（无需认证）
+pub async fn oidc_callback(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    Query(query): Query<OidcCallbackQuery>,
+) -> Response {
+    let Some(oidc) = &state.oidc else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if let Some(error) = query.error {
+        let description = query.error_description.unwrap_or_default();
+        tracing::warn!("OIDC 登录被身份提供方拒绝: {} {}", error, description);
+        return (
+            StatusCode::FORBIDDEN,
+            format!("身份提供方返回错误: {} {}", error, description),
+        )
+            .into_response();
+    }
+    let (Some(code), Some(login_state)) = (query.code, query.state) else {
+        return (StatusCode::BAD_REQUEST, "缺少 code 或 state 参数").into_response();
+    };
+    // state 必须与发起登录的浏览器中保存的一致，防止登录 CSRF
+    let result = if session::cookie(&headers, oidc::STATE_COOKIE) == Some(login_state.as_str()) {
+        oidc.complete(&login_state, &code).await
+    } else {
+        Err(OidcError::InvalidState)
+    };
+    let clear_state = format!(
+        "{}=; {}",
+        oidc::STATE_COOKIE,
+        oidc.cookie_attributes("/api/admin/oidc", Duration::ZERO, "Lax")
+    );
+    match result {
+        Ok(subject) => {
+            tracing::info!("Admin 单点登录: {}", subject);
+            let session_cookie = format!(
+                "{}={}; {}",
+                SESSION_COOKIE,
+                state.sessions.create(subject),
+                oidc.cookie_attributes("/api/admin", state.sessions.max_age(), "Strict")
+            );
+            (
+                AppendHeaders([
+                    (header::SET_COOKIE, clear_state),
+                    (header::SET_COOKIE, session_cookie),
+                ]),
+                Redirect::to(&oidc.ui_path()),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::warn!("OIDC 登录失败: {}", e);
+            let status = match e {
+                OidcError::InvalidState => StatusCode::BAD_REQUEST,
+                OidcError::Provider(_) => StatusCode::BAD_GATEWAY,
+                OidcError::Denied(_) => StatusCode::FORBIDDEN,
+            };
+            (
+                status,
+                AppendHeaders([(header::SET_COOKIE, clear_state)]),
+                e.to_string(),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// GET /api/admin/oidc/session
+/// 查询当前浏览器是否已通过单点登录（无需认证）
+pub async fn oidc_session(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let subject = session::cookie(&headers, SESSION_COOKIE).and_then(|id| state.sessions.get(id));
+    Json(SessionResponse {
+        authenticated: subject.is_some(),
+        subject,
+    })
+}
+
+/// POST /api/admin/oidc/logout
+/// 注销当前会话（无需认证）
+pub async fn oidc_logout(State(state): State<AdminState>, headers: HeaderMap) -> Response {
+    let Some(oidc) = &state.oidc else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if let Some(id) = session::cookie(&headers, SESSION_COOKIE) {
+        state.sessions.remove(id);
+    }
+    let cookie = format!(
+        "{}=; {}",
+        SESSION_COOKIE,
+        oidc.cookie_attributes("/api/admin", Duration::ZERO, "Strict")
+    );
+    (
+        AppendHeaders([(header::SET_COOKIE, cookie)]),
+        Json(SuccessResponse::new("已退出登录")),
+    )
+        .into_response()
+}
+
+/// DELETE /api/admin/sessions
+/// 注销全部单点登录会话（包括当前会话），所有浏览器都需重新登录
+pub async fn revoke_sessions(State(state): State<AdminState>) -> impl IntoResponse {
+    let count = state.sessions.revoke_all();
+    tracing::warn!("已注销全部 {} 个 Admin 会话", count);
+    Json(SuccessResponse::new(format!("已注销 {} 个会话", count)))
+}
@@ -4,23 +4,40 @@ use std::sync::Arc;
 
 use axum::{
     body::Body,
-    extract::State,
-    http::{Request, StatusCode},
+    extract::{OriginalUri, State},
+    http::{Method, Request, StatusCode, header},
     middleware::Next,
     response::{IntoResponse, Json, Response},
 };
 
+use super::audit::AuditLog;
+use super::oidc::OidcLogin;
 use super::service::AdminService;
+use super::session::{self, AdminSessions};
 use super::types::AdminErrorResponse;
 use crate::common::auth;
+use crate::common::client_ip::ClientIp;
+use crate::common::live::Live;
+use crate::limit::AuthLockout;
+use crate::model::config::Config;
 
 /// Admin API 共享状态
 #[derive(Clone)]
 pub struct AdminState {
-    /// Admin API 密钥
+    /// Admin API 密钥（未设置 `config` 时使用；设置后以热加载的配置为准）
     pub admin_api_key: String,
     /// Admin 服务
     pub service: Arc<AdminService>,
+    /// 应用配置（用于读取热加载后的 Admin Key 与带 `adminReadOnly` 的下游 API Key）
+    pub config: Option<Live<Config>>,
+    /// 认证失败锁定器（与代理 API 共用）
+    pub auth_lockout: Arc<AuthLockout>,
+    /// 写操作审计日志（可选）
+    pub audit_log: Option<Arc<AuditLog>>,
+    /// OIDC 单点登录（可选）
+    pub oidc: Option<Arc<OidcLogin>>,
+    /// 单点登录后的会话
+    pub sessions: Arc<AdminSessions>,
 }
 
 impl AdminState {
@@ -28,23 +45,178 @@ impl AdminState {
         Self {
             admin_api_key: admin_api_key.into(),
             service: Arc::new(service),
+            config: None,
+            auth_lockout: Arc::new(AuthLockout::default()),
+            audit_log: None,
+            oidc: None,
+            sessions: Arc::new(AdminSessions::default()),
         }
     }
+
+    /// 允许 `apiKeys` 中带 `adminReadOnly` 的 Key 只读访问
+    pub fn with_config(mut self, config: Live<Config>) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// 与代理 API 共用认证失败锁定器，任一端的失败都计入同一 IP
+    pub fn with_auth_lockout(mut self, auth_lockout: Arc<AuthLockout>) -> Self {
+        self.auth_lockout = auth_lockout;
+        self
+    }
+
+    /// 将写操作记录到审计日志
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = Some(Arc::new(audit_log));
+        self
+    }
+
+    /// 启用 OIDC 单点登录，登录后的会话可访问全部端点
+    pub fn with_oidc(mut self, oidc: OidcLogin) -> Self {
+        let (idle_timeout, max_age) = oidc.session_timeouts();
+        self.sessions = Arc::new(AdminSessions::new(idle_timeout, max_age));
+        self.oidc = Some(Arc::new(oidc));
+        self
+    }
+
+    /// 请求 Cookie 中有效会话的登录用户
+    fn session_subject(&self, request: &Request<Body>) -> Option<String> {
+        let id = session::cookie(request.headers(), session::SESSION_COOKIE)?;
+        self.sessions.get(id)
+    }
+
+    /// 是否为有效的 Admin Key
+    ///
+    /// 配置热加载后 `adminApiKey` 与 `adminApiKeys` 立即生效，新旧 Key 可同时有效以便不停机轮换。
+    /// 所有候选都会完整校验，空的 Key 不匹配任何请求
+    fn is_admin_key(&self, key: &str) -> bool {
+        let config = self.config.as_ref().map(Live::load);
+        let (primary, extra) = match &config {
+            Some(config) => (
+                config.admin_api_key.as_deref().unwrap_or_default(),
+                config.admin_api_keys.as_slice(),
+            ),
+            None => (self.admin_api_key.as_str(), &[][..]),
+        };
+        let primary = !primary.trim().is_empty() && auth::verify_api_key(primary, key);
+        let extra =
+            auth::find_api_key(extra, String::as_str, key).is_some_and(|k| !k.trim().is_empty());
+        primary | extra
+    }
+
+    /// 是否为带 `adminReadOnly` 的下游 API Key
+    fn is_read_only_key(&self, key: &str) -> bool {
+        self.config.as_ref().is_some_and(|config| {
+            let config = config.load();
+            auth::find_api_key(&config.api_keys, |k| &k.key, key).is_some_and(|k| k.admin_read_only)
+        })
+    }
 }
 
 /// Admin API 认证中间件
+///
+/// Admin API Key（`adminApiKey` 或 `adminApiKeys` 中任一项）与单点登录的会话可访问全部端点；带 `adminReadOnly` 的下游 API Key 只能发起 GET 请求。
+/// 客户端 IP 处于认证失败锁定期时返回 429
 pub async fn admin_auth_middleware(
     State(state): State<AdminState>,
     request: Request<Body>,
     next: Next,
 ) -> Response {
+    let ip = request.extensions().get::<ClientIp>().map(|ip| ip.0);
+    if let Some(ip) = ip
+        && let Err(remaining) = state.auth_lockout.check(ip)
+    {
+        let retry_after = auth::audit_locked_out(ip, remaining);
+        let error = AdminErrorResponse::new(
+            "rate_limit_error",
+            "Too many failed authentication attempts from this IP address",
+        );
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+            Json(error),
+        )
+            .into_response();
+    }
+
     let api_key = auth::extract_api_key(&request);
+    // 未携带 API Key 时才读取单点登录的会话 Cookie
+    let session = match api_key {
+        Some(_) => None,
+        None => state.session_subject(&request),
+    };
+    let is_admin = session.is_some()
+        || api_key
+            .as_deref()
+            .is_some_and(|key| state.is_admin_key(key));
+    let is_read_only = api_key
+        .as_deref()
+        .is_some_and(|key| state.is_read_only_key(key));
+    if let Some(ip) = ip {
+        if is_admin || is_read_only {
+            state.auth_lockout.record_success(ip);
+        } else if api_key.as_deref().is_some_and(|key| !key.is_empty()) {
+            auth::audit_failure(&state.auth_lockout, ip, request.uri().path());
+        }
+    }
 
-    match api_key {
-        Some(key) if auth::constant_time_eq(&key, &state.admin_api_key) => next.run(request).await,
-        _ => {
-            let error = AdminErrorResponse::authentication_error();
-            (StatusCode::UNAUTHORIZED, Json(error)).into_response()
+    let actor = match session {
+        Some(subject) => format!("oidc:{}", subject),
+        None if is_admin => "admin".to_string(),
+        None if is_read_only => "readOnly".to_string(),
+        None => "anonymous".to_string(),
+    };
+    let mut response = if is_admin {
+        next.run(request).await
+    } else if is_read_only {
+        if matches!(*request.method(), Method::GET | Method::HEAD) {
+            next.run(request).await
+        } else {
+            let error = AdminErrorResponse::new("permission_error", "This API key is read-only");
+            (StatusCode::FORBIDDEN, Json(error)).into_response()
         }
+    } else {
+        let error = AdminErrorResponse::authentication_error();
+        (StatusCode::UNAUTHORIZED, Json(error)).into_response()
+    };
+    response.extensions_mut().insert(AdminActor(actor));
+    response
+}
+
+/// 认证中间件判定的操作者，供审计日志记录
+#[derive(Debug, Clone)]
+struct AdminActor(String);
+
+/// Admin 审计中间件
+///
+/// 配置了 `auditLog` 时，将每个写操作（非 GET / HEAD 请求，含认证失败的尝试）的结果追加到审计日志
+pub async fn admin_audit_middleware(
+    State(state): State<AdminState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(audit_log) = state.audit_log.clone() else {
+        return next.run(request).await;
+    };
+    if matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
     }
+
+    let method = request.method().to_string();
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| request.uri().path(), |uri| uri.path())
+        .to_string();
+    let ip = request
+        .extensions()
+        .get::<ClientIp>()
+        .map(|ip| ip.0.to_string());
+    let response = next.run(request).await;
+    let actor = response
+        .extensions()
+        .get::<AdminActor>()
+        .map_or("anonymous", |actor| actor.0.as_str());
+    audit_log.record(&method, &path, response.status().as_u16(), ip, actor);
+    response
 }
@@ -8,21 +8,29 @@
 //! - 修改凭据优先级
 //! - 重置失败计数
 //! - 查询凭据余额
+//! - 查询请求用量记录
+//! - 写操作审计日志（HMAC 链式校验）
+//! - Admin UI 的 OIDC 单点登录
 //!
 //! # 使用
 //! ```ignore
-//! let admin_service = AdminService::new(token_manager.clone());
+//! let admin_service = AdminService::new(token_manager.clone(), usage_store.clone());
 //! let admin_state = AdminState::new(admin_api_key, admin_service);
 //! let admin_router = create_admin_router(admin_state);
 //! ```
 
+pub mod audit;
 mod error;
 mod handlers;
 mod middleware;
+mod oidc;
 mod router;
 mod service;
+mod session;
 pub mod types;
 
+pub use audit::{AuditEntry, AuditLog};
 pub use middleware::AdminState;
+pub use oidc::OidcLogin;
 pub use router::create_admin_router;
 pub use service::AdminService;
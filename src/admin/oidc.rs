@@ -0,0 +1,397 @@
+//! Admin UI 的 OIDC 单点登录
+//!
+//! 授权码流程 + PKCE（S256）：
+//! 1. `GET /oidc/login` 生成 state、nonce 与 code verifier，跳转到身份提供方的授权端点
+//! 2. `GET /oidc/callback` 校验 state，用授权码与 code verifier 换取 ID Token
+//! 3. 校验 ID Token 的 `iss` / `aud` / `exp` / `nonce`，`claim` 的取值包含 `allowedValues` 中任一项时创建 Admin 会话
+//!
+//! ID Token 经 TLS 直接从令牌端点获取，按 OIDC Core 3.1.3.7 以 TLS 校验代替签名校验，不验证 JWS 签名。
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+
+use super::session::random_token;
+use crate::http_client;
+use crate::model::config::AdminOidcConfig;
+
+/// 登录 state 的 Cookie 名称（把回调绑定到发起登录的浏览器）
+pub const STATE_COOKIE: &str = "kiro_oidc_state";
+
+/// 发起登录到回调之间的最长时间
+pub const LOGIN_TTL: Duration = Duration::from_secs(600);
+
+/// 同时进行中的登录数上限，超出时丢弃最早发起的
+const MAX_PENDING: usize = 1024;
+
+/// 校验 `exp` 时允许的时钟偏差（秒）
+const CLOCK_SKEW_SECS: i64 = 60;
+
+/// 身份提供方元数据（`/.well-known/openid-configuration` 中用到的字段）
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+/// 令牌端点的响应
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+/// 已发起、尚未回调的登录
+struct PendingLogin {
+    verifier: String,
+    nonce: String,
+    created: Instant,
+}
+
+/// 登录失败的原因
+#[derive(Debug)]
+pub enum OidcError {
+    /// state 无效或已过期（需重新登录）
+    InvalidState,
+    /// 身份提供方不可用或返回了无效的响应
+    Provider(String),
+    /// ID Token 无效或用户无权访问
+    Denied(String),
+}
+
+impl std::fmt::Display for OidcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OidcError::InvalidState => write!(f, "登录已过期或无效，请重新登录"),
+            OidcError::Provider(msg) => write!(f, "身份提供方错误: {}", msg),
+            OidcError::Denied(msg) => write!(f, "拒绝访问: {}", msg),
+        }
+    }
+}
+
+/// OIDC 登录客户端
+pub struct OidcLogin {
+    config: AdminOidcConfig,
+    /// Admin UI 在浏览器中的访问前缀（Cookie 路径与登录后的跳转地址）
+    public_prefix: String,
+    http: reqwest::Client,
+    /// 首次登录时发现的端点
+    provider: OnceCell<ProviderMetadata>,
+    pending: Mutex<HashMap<String, PendingLogin>>,
+}
+
+impl OidcLogin {
+    pub fn new(config: AdminOidcConfig, public_prefix: String) -> anyhow::Result<Self> {
+        let http = http_client::build_integration_client(15)?;
+        Ok(Self {
+            config,
+            public_prefix,
+            http,
+            provider: OnceCell::new(),
+            pending: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// 会话的空闲超时与最长有效期
+    pub fn session_timeouts(&self) -> (Duration, Duration) {
+        (
+            Duration::from_secs(self.config.session_idle_timeout_secs),
+            Duration::from_secs(self.config.session_max_age_secs),
+        )
+    }
+
+    /// Admin UI 的地址（登录后跳转）
+    pub fn ui_path(&self) -> String {
+        format!("{}/admin", self.public_prefix)
+    }
+
+    /// Cookie 的附加属性：回调地址为 HTTPS 时带 `Secure`
+    pub fn cookie_attributes(&self, path: &str, max_age: Duration, same_site: &str) -> String {
+        let secure = if self.config.redirect_url.starts_with("https://") {
+            "; Secure"
+        } else {
+            ""
+        };
+        format!(
+            "Path={}{}; Max-Age={}; HttpOnly; SameSite={}{}",
+            self.public_prefix,
+            path,
+            max_age.as_secs(),
+            same_site,
+            secure
+        )
+    }
+
+    /// 发起登录，返回授权端点地址与 state
+    pub async fn begin(&self) -> Result<(String, String), OidcError> {
+        let provider = self.provider().await?;
+        let state = random_token();
+        let nonce = random_token();
+        let verifier = random_token();
+        let url = reqwest::Url::parse_with_params(
+            &provider.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("scope", self.config.scopes.join(" ").as_str()),
+                ("state", state.as_str()),
+                ("nonce", nonce.as_str()),
+                ("code_challenge", pkce_challenge(&verifier).as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| OidcError::Provider(format!("授权端点地址无效: {}", e)))?;
+
+        let mut pending = self.pending.lock();
+        pending.retain(|_, login| login.created.elapsed() < LOGIN_TTL);
+        if pending.len() >= MAX_PENDING
+            && let Some(oldest) = pending
+                .iter()
+                .min_by_key(|(_, login)| login.created)
+                .map(|(state, _)| state.clone())
+        {
+            pending.remove(&oldest);
+        }
+        pending.insert(
+            state.clone(),
+            PendingLogin {
+                verifier,
+                nonce,
+                created: Instant::now(),
+            },
+        );
+        Ok((url.into(), state))
+    }
+
+    /// 处理回调：换取并校验 ID Token，返回登录用户
+    pub async fn complete(&self, state: &str, code: &str) -> Result<String, OidcError> {
+        let login = self
+            .pending
+            .lock()
+            .remove(state)
+            .filter(|login| login.created.elapsed() < LOGIN_TTL)
+            .ok_or(OidcError::InvalidState)?;
+        let provider = self.provider().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_url.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", login.verifier.as_str()),
+        ];
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+        let response = self
+            .http
+            .post(&provider.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| OidcError::Provider(format!("请求令牌端点失败: {}", e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(OidcError::Provider(format!(
+                "令牌端点返回 {}: {}",
+                status, body
+            )));
+        }
+        let tokens: TokenResponse = response
+            .json()
+            .await
+            .map_err(|e| OidcError::Provider(format!("令牌端点的响应无效: {}", e)))?;
+        let id_token = tokens
+            .id_token
+            .ok_or_else(|| OidcError::Provider("令牌端点未返回 id_token".to_string()))?;
+
+        let claims = validate_id_token(
+            &id_token,
+            &provider.issuer,
+            &self.config.client_id,
+            &login.nonce,
+            chrono::Utc::now().timestamp(),
+        )
+        .map_err(OidcError::Denied)?;
+        let subject = subject(&claims);
+        if !claim_allows(&claims, &self.config.claim, &self.config.allowed_values) {
+            return Err(OidcError::Denied(format!(
+                "用户 {} 的 {} 声明不包含允许的值",
+                subject, self.config.claim
+            )));
+        }
+        Ok(subject)
+    }
+
+    /// 获取身份提供方元数据（失败时不缓存，下次登录重试）
+    async fn provider(&self) -> Result<&ProviderMetadata, OidcError> {
+        self.provider
+            .get_or_try_init(|| async {
+                let issuer = self.config.issuer.trim_end_matches('/');
+                let url = format!("{}/.well-known/openid-configuration", issuer);
+                let metadata: ProviderMetadata = self
+                    .http
+                    .get(&url)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map_err(|e| OidcError::Provider(format!("获取 {} 失败: {}", url, e)))?
+                    .json()
+                    .await
+                    .map_err(|e| OidcError::Provider(format!("{} 的内容无效: {}", url, e)))?;
+                if metadata.issuer.trim_end_matches('/') != issuer {
+                    return Err(OidcError::Provider(format!(
+                        "发现文档中的 issuer {} 与配置的 {} 不一致",
+                        metadata.issuer, self.config.issuer
+                    )));
+                }
+                Ok(metadata)
+            })
+            .await
+    }
+}
+
+/// PKCE S256 challenge：BASE64URL(SHA256(verifier))
+fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// 解码 ID Token 并校验 `iss`、`aud`（及 `azp`）、`exp` 与 `nonce`，返回其中的声明
+fn validate_id_token(
+    id_token: &str,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+    now: i64,
+) -> Result<Value, String> {
+    let payload = id_token.split('.').nth(1).ok_or("ID Token 格式无效")?;
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|e| format!("ID Token 无法解码: {}", e))?;
+    let claims: Value =
+        serde_json::from_slice(&payload).map_err(|e| format!("ID Token 无法解析: {}", e))?;
+
+    if claims["iss"].as_str() != Some(issuer) {
+        return Err(format!("ID Token 的 iss 不是 {}", issuer));
+    }
+    let audience_ok = match &claims["aud"] {
+        Value::String(aud) => aud == client_id,
+        Value::Array(auds) => auds.iter().any(|aud| aud.as_str() == Some(client_id)),
+        _ => false,
+    };
+    if !audience_ok {
+        return Err("ID Token 的 aud 不包含当前 Client ID".to_string());
+    }
+    if let Some(azp) = claims["azp"].as_str()
+        && azp != client_id
+    {
+        return Err("ID Token 的 azp 不是当前 Client ID".to_string());
+    }
+    match claims["exp"].as_i64() {
+        Some(exp) if exp + CLOCK_SKEW_SECS > now => {}
+        _ => return Err("ID Token 已过期".to_string()),
+    }
+    if claims["nonce"].as_str() != Some(nonce) {
+        return Err("ID Token 的 nonce 不匹配".to_string());
+    }
+    Ok(claims)
+}
+
+/// `claim`（可用 `.` 访问嵌套字段）的取值是否包含 `allowed` 中任一项
+fn claim_allows(claims: &Value, claim: &str, allowed: &[String]) -> bool {
+    let value = claim
+        .split('.')
+        .try_fold(claims, |value, key| value.get(key));
+    let is_allowed = |value: &Value| {
+        value
+            .as_str()
+            .is_some_and(|value| allowed.iter().any(|a| a == value))
+    };
+    match value {
+        Some(Value::Array(values)) => values.iter().any(is_allowed),
+        Some(value) => is_allowed(value),
+        None => false,
+    }
+}
+
+/// 用于显示与审计的用户标识
+fn subject(claims: &Value) -> String {
+    ["email", "preferred_username", "sub"]
+        .iter()
+        .find_map(|key| claims[*key].as_str())
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn id_token(claims: Value) -> String {
+        format!(
+            "eyJhbGciOiJSUzI1NiJ9.{}.c2lnbmF0dXJl",
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        )
+    }
+
+    #[test]
+    fn test_pkce_challenge() {
+        // 与 `openssl dgst -sha256 -binary | base64` 转为 URL 安全字符并去掉填充的结果一致
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mJ92K1wRWlTgH4TgwNTbq2jpgsoT1Y"),
+            "7GPwlEvfGNmqIL3ihs_lqhFNRlSyt2FDPVo5DtYa_o8"
+        );
+    }
+
+    #[test]
+    fn test_validate_id_token() {
+        let claims = json!({
+            "iss": "https://idp.example.com",
+            "aud": ["kiro", "other"],
+            "azp": "kiro",
+            "exp": 1_000,
+            "nonce": "n-1",
+            "email": "alice@example.com",
+        });
+        let token = id_token(claims.clone());
+        let validate = |issuer, client_id, nonce, now| {
+            validate_id_token(&token, issuer, client_id, nonce, now)
+        };
+        assert!(validate("https://idp.example.com", "kiro", "n-1", 900).is_ok());
+        assert!(validate("https://evil.example.com", "kiro", "n-1", 900).is_err());
+        assert!(validate("https://idp.example.com", "other", "n-1", 900).is_err());
+        assert!(validate("https://idp.example.com", "kiro", "n-2", 900).is_err());
+        assert!(validate("https://idp.example.com", "kiro", "n-1", 2_000).is_err());
+        assert!(validate_id_token("not-a-jwt", "", "", "", 0).is_err());
+        assert_eq!(subject(&claims), "alice@example.com");
+    }
+
+    #[test]
+    fn test_claim_allows() {
+        let allowed = vec!["kiro-admins".to_string()];
+        let claims = json!({
+            "groups": ["staff", "kiro-admins"],
+            "role": "kiro-admins",
+            "realm_access": {"roles": ["kiro-admins"]},
+        });
+        assert!(claim_allows(&claims, "groups", &allowed));
+        assert!(claim_allows(&claims, "role", &allowed));
+        assert!(claim_allows(&claims, "realm_access.roles", &allowed));
+        assert!(!claim_allows(&claims, "missing", &allowed));
+        assert!(!claim_allows(
+            &json!({"groups": ["staff"]}),
+            "groups",
+            &allowed
+        ));
+    }
+}
@@ -7,10 +7,13 @@ use axum::{
 
 use super::{
     handlers::{
-        add_credential, delete_credential, get_all_credentials, get_credential_balance,
-        reset_failure_count, set_credential_disabled, set_credential_priority,
+        add_credential, delete_credential, get_alerts, get_all_credentials, get_connections,
+        get_credential_balance, get_events, get_model_routes, get_timeseries, get_usage,
+        get_usage_reports, get_usage_summary, oidc_callback, oidc_login, oidc_logout, oidc_session,
+        reload_config, reset_failure_count, revoke_sessions, set_credential_disabled,
+        set_credential_priority, set_credential_tags, set_model_routes,
     },
-    middleware::{AdminState, admin_auth_middleware},
+    middleware::{AdminState, admin_audit_middleware, admin_auth_middleware},
 };
 
 /// 创建 Admin API 路由
@@ -21,15 +24,38 @@ use super::{
 /// - `DELETE /credentials/:id` - 删除凭据
 /// - `POST /credentials/:id/disabled` - 设置凭据禁用状态
 /// - `POST /credentials/:id/priority` - 设置凭据优先级
+/// - `POST /credentials/:id/tags` - 设置凭据标签
 /// - `POST /credentials/:id/reset` - 重置失败计数
 /// - `GET /credentials/:id/balance` - 获取凭据余额
+/// - `GET /usage` - 获取最近的请求用量记录
+/// - `GET /usage/summary` - 按 API Key 与终端用户汇总用量
+/// - `GET /usage/reports` - 获取每日用量报告
+/// - `GET /timeseries` - 请求数 / 错误数 / tokens / 延迟的时间序列
+/// - `GET /routes` - 获取模型路由规则
+/// - `PUT /routes` - 替换模型路由规则
+/// - `GET /alerts` - 获取当前的错误率告警
+/// - `GET /events` - 告警事件流（SSE）
+/// - `GET /connections` - 上游连接复用统计
+/// - `POST /reload` - 重新加载配置与凭证文件
+/// - `DELETE /sessions` - 注销全部单点登录会话
+///
+/// 配置了 `adminOidc` 时另有以下无需认证的单点登录端点：
+/// - `GET /oidc/login` - 跳转到身份提供方登录
+/// - `GET /oidc/callback` - 登录回调，创建会话
+/// - `GET /oidc/session` - 查询当前会话
+/// - `POST /oidc/logout` - 注销当前会话
 ///
 /// # 认证
-/// 需要 Admin API Key 认证，支持：
+/// 需要 Admin API Key（`adminApiKey` 或 `adminApiKeys` 中任一项）认证，支持：
 /// - `x-api-key` header
 /// - `Authorization: Bearer <token>` header
+///
+/// 或单点登录后的会话 Cookie
+///
+/// # 审计
+/// 配置了 `auditLog` 时，写操作（含认证失败的尝试）记录到审计日志
 pub fn create_admin_router(state: AdminState) -> Router {
-    Router::new()
+    let router = Router::new()
         .route(
             "/credentials",
             get(get_all_credentials).post(add_credential),
@@ -37,11 +63,36 @@ pub fn create_admin_router(state: AdminState) -> Router {
         .route("/credentials/{id}", delete(delete_credential))
         .route("/credentials/{id}/disabled", post(set_credential_disabled))
         .route("/credentials/{id}/priority", post(set_credential_priority))
+        .route("/credentials/{id}/tags", post(set_credential_tags))
         .route("/credentials/{id}/reset", post(reset_failure_count))
         .route("/credentials/{id}/balance", get(get_credential_balance))
+        .route("/usage", get(get_usage))
+        .route("/usage/summary", get(get_usage_summary))
+        .route("/usage/reports", get(get_usage_reports))
+        .route("/timeseries", get(get_timeseries))
+        .route("/routes", get(get_model_routes).put(set_model_routes))
+        .route("/alerts", get(get_alerts))
+        .route("/events", get(get_events))
+        .route("/connections", get(get_connections))
+        .route("/reload", post(reload_config))
+        .route("/sessions", delete(revoke_sessions))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             admin_auth_middleware,
         ))
-        .with_state(state)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            admin_audit_middleware,
+        ));
+    // 在认证中间件之后添加，不经过认证
+    let router = if state.oidc.is_some() {
+        router
+            .route("/oidc/login", get(oidc_login))
+            .route("/oidc/callback", get(oidc_callback))
+            .route("/oidc/session", get(oidc_session))
+            .route("/oidc/logout", post(oidc_logout))
+    } else {
+        router
+    };
+    router.with_state(state)
 }
@@ -4,23 +4,158 @@ use std::sync::Arc;
 
 use crate::kiro::model::credentials::KiroCredentials;
 use crate::kiro::token_manager::MultiTokenManager;
+use crate::metrics;
+use crate::metrics::alerts::AlertEvent;
+use crate::reload::Reloader;
+use crate::usage::UsageStore;
+use crate::usage::report::ReportStore;
+use crate::usage::timeseries;
 
 use super::error::AdminServiceError;
 use super::types::{
-    AddCredentialRequest, AddCredentialResponse, BalanceResponse, CredentialStatusItem,
-    CredentialsStatusResponse,
+    AddCredentialRequest, AddCredentialResponse, AlertsResponse, BalanceResponse,
+    ConnectionsResponse, CredentialStatusItem, CredentialsStatusResponse, ModelRoutesBody,
+    ReloadResponse, TimeseriesQuery, TimeseriesResponse, UsageReportsResponse, UsageResponse,
+    UsageSummaryResponse,
 };
 
+/// 用量记录查询的默认条数
+const DEFAULT_USAGE_LIMIT: usize = 100;
+
 /// Admin 服务
 ///
 /// 封装所有 Admin API 的业务逻辑
 pub struct AdminService {
     token_manager: Arc<MultiTokenManager>,
+    usage_store: Arc<UsageStore>,
+    reports: Option<Arc<ReportStore>>,
+    reloader: Option<Arc<Reloader>>,
 }
 
 impl AdminService {
-    pub fn new(token_manager: Arc<MultiTokenManager>) -> Self {
-        Self { token_manager }
+    pub fn new(token_manager: Arc<MultiTokenManager>, usage_store: Arc<UsageStore>) -> Self {
+        Self {
+            token_manager,
+            usage_store,
+            reports: None,
+            reloader: None,
+        }
+    }
+
+    /// 启用每日用量报告查询
+    pub fn with_reports(mut self, reports: Arc<ReportStore>) -> Self {
+        self.reports = Some(reports);
+        self
+    }
+
+    /// 启用通过 Admin API 重新加载配置
+    pub fn with_reloader(mut self, reloader: Arc<Reloader>) -> Self {
+        self.reloader = Some(reloader);
+        self
+    }
+
+    /// 重新加载配置与凭证文件（与 SIGHUP 相同）
+    pub fn reload_config(&self) -> Result<ReloadResponse, AdminServiceError> {
+        let reloader = self
+            .reloader
+            .as_ref()
+            .ok_or_else(|| AdminServiceError::InternalError("配置热加载未启用".to_string()))?;
+        let changes = reloader
+            .reload()
+            .map_err(|e| AdminServiceError::InvalidConfig(e.to_string()))?;
+        Ok(ReloadResponse {
+            applied: changes.applied,
+            restart_required: changes.restart_required,
+        })
+    }
+
+    /// 获取最近的用量记录
+    pub fn get_usage(&self, limit: Option<usize>) -> UsageResponse {
+        UsageResponse {
+            total: self.usage_store.len(),
+            records: self
+                .usage_store
+                .recent(limit.unwrap_or(DEFAULT_USAGE_LIMIT)),
+        }
+    }
+
+    /// 按 API Key 与终端用户汇总用量
+    pub fn get_usage_summary(&self) -> UsageSummaryResponse {
+        UsageSummaryResponse {
+            total: self.usage_store.len(),
+            keys: self.usage_store.summary_by_key(),
+        }
+    }
+
+    /// 获取按步长分桶的时间序列（基于内存中的用量记录）
+    pub fn get_timeseries(
+        &self,
+        query: TimeseriesQuery,
+    ) -> Result<TimeseriesResponse, AdminServiceError> {
+        let parse = |value: Option<&str>, default: &str, name: &str| {
+            let value = value.unwrap_or(default);
+            timeseries::parse_duration(value).ok_or_else(|| {
+                AdminServiceError::InvalidQuery(format!("{} 格式无效: {}", name, value))
+            })
+        };
+        let window = parse(query.window.as_deref(), "24h", "window")?;
+        let step = parse(query.step.as_deref(), "5m", "step")?;
+        if window > timeseries::MAX_WINDOW {
+            return Err(AdminServiceError::InvalidQuery(format!(
+                "window 过大（最多 {} 天）",
+                timeseries::MAX_WINDOW.num_days()
+            )));
+        }
+        if step > window {
+            return Err(AdminServiceError::InvalidQuery(
+                "step 不能大于 window".to_string(),
+            ));
+        }
+        if window.num_seconds() / step.num_seconds() > timeseries::MAX_POINTS {
+            return Err(AdminServiceError::InvalidQuery(format!(
+                "数据点过多（最多 {} 个），请增大 step",
+                timeseries::MAX_POINTS
+            )));
+        }
+
+        let (start, buckets) = timeseries::range(chrono::Utc::now(), window, step);
+        Ok(TimeseriesResponse {
+            metric: query.metric,
+            step_secs: step.num_seconds(),
+            points: self.usage_store.series(query.metric, start, step, buckets),
+        })
+    }
+
+    /// 获取已生成的每日用量报告（未启用时为空）
+    pub fn get_usage_reports(&self) -> UsageReportsResponse {
+        UsageReportsResponse {
+            reports: self
+                .reports
+                .as_ref()
+                .map(|reports| reports.list())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// 获取当前的错误率告警
+    pub fn get_alerts(&self) -> AlertsResponse {
+        AlertsResponse {
+            alerts: metrics::alerts().active_alerts(),
+        }
+    }
+
+    /// 获取上游连接复用统计
+    pub fn get_connections(&self) -> ConnectionsResponse {
+        let stats = metrics::connections();
+        ConnectionsResponse {
+            total: stats.total(),
+            last_minute: stats.recent(),
+        }
+    }
+
+    /// 订阅告警触发 / 解除事件
+    pub fn subscribe_alerts(&self) -> tokio::sync::broadcast::Receiver<AlertEvent> {
+        metrics::alerts().subscribe()
     }
 
     /// 获取所有凭据状态
@@ -39,6 +174,8 @@ impl AdminService {
                 expires_at: entry.expires_at,
                 auth_method: entry.auth_method,
                 has_profile_arn: entry.has_profile_arn,
+                tags: entry.tags,
+                upstream_statuses: metrics::global().upstream_statuses(entry.id),
             })
             .collect();
 
@@ -77,6 +214,36 @@ impl AdminService {
             .map_err(|e| self.classify_error(e, id))
     }
 
+    /// 设置凭据标签
+    pub fn set_tags(&self, id: u64, tags: Vec<String>) -> Result<(), AdminServiceError> {
+        self.token_manager
+            .set_tags(id, tags)
+            .map_err(|e| self.classify_error(e, id))
+    }
+
+    /// 获取模型路由规则
+    pub fn get_routes(&self) -> ModelRoutesBody {
+        ModelRoutesBody {
+            routes: self.token_manager.routes(),
+        }
+    }
+
+    /// 替换模型路由规则
+    pub fn set_routes(&self, body: ModelRoutesBody) -> Result<(), AdminServiceError> {
+        if let Some(route) = body
+            .routes
+            .iter()
+            .find(|r| r.model.is_empty() || r.tags.is_empty())
+        {
+            return Err(AdminServiceError::InvalidRoute(format!(
+                "路由规则的 model 与 tags 不能为空: {:?}",
+                route
+            )));
+        }
+        self.token_manager.set_routes(body.routes);
+        Ok(())
+    }
+
     /// 重置失败计数并重新启用
     pub fn reset_and_enable(&self, id: u64) -> Result<(), AdminServiceError> {
         self.token_manager
@@ -128,6 +295,8 @@ impl AdminService {
             client_id: req.client_id,
             client_secret: req.client_secret,
             priority: req.priority,
+            tags: req.tags,
+            region: req.region,
         };
 
         // 调用 token_manager 添加凭据
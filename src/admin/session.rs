@@ -0,0 +1,189 @@
+//! Admin 会话
+//!
+//! 通过 OIDC 单点登录的用户获得一个随机会话 ID，以 HttpOnly Cookie 保存在浏览器中，之后的 Admin API
+//! 请求凭 Cookie 认证。会话只保存在内存中，重启后需重新登录。
+//!
+//! 会话在超过空闲时间未使用或自登录起超过最长有效期后失效，也可通过 `DELETE /sessions` 一次注销全部会话，
+//! 被盗用的浏览器会话因此不会永久有效。
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use axum::http::{HeaderMap, header};
+use parking_lot::Mutex;
+
+use crate::model::config::{default_session_idle_timeout_secs, default_session_max_age_secs};
+
+/// 会话 Cookie 名称
+pub const SESSION_COOKIE: &str = "kiro_admin_session";
+
+/// 一个已登录的会话
+struct Session {
+    /// 登录用户（ID Token 中的 email / preferred_username / sub）
+    subject: String,
+    created: Instant,
+    /// 最近一次使用的时间
+    last_seen: Instant,
+}
+
+/// 内存中的会话表
+pub struct AdminSessions {
+    sessions: Mutex<HashMap<String, Session>>,
+    /// 空闲超时
+    idle_timeout: Duration,
+    /// 自登录起的最长有效期
+    max_age: Duration,
+}
+
+impl Default for AdminSessions {
+    fn default() -> Self {
+        Self::new(
+            Duration::from_secs(default_session_idle_timeout_secs()),
+            Duration::from_secs(default_session_max_age_secs()),
+        )
+    }
+}
+
+impl AdminSessions {
+    pub fn new(idle_timeout: Duration, max_age: Duration) -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            idle_timeout,
+            max_age,
+        }
+    }
+
+    /// 自登录起的最长有效期（会话 Cookie 的 `Max-Age`）
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    fn is_expired(&self, session: &Session, now: Instant) -> bool {
+        now.duration_since(session.created) >= self.max_age
+            || now.duration_since(session.last_seen) >= self.idle_timeout
+    }
+
+    /// 创建会话并返回会话 ID（同时清理已过期的会话）
+    pub fn create(&self, subject: impl Into<String>) -> String {
+        let id = random_token();
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock();
+        sessions.retain(|_, session| !self.is_expired(session, now));
+        sessions.insert(
+            id.clone(),
+            Session {
+                subject: subject.into(),
+                created: now,
+                last_seen: now,
+            },
+        );
+        id
+    }
+
+    /// 查找有效的会话并刷新其空闲计时，返回登录用户
+    pub fn get(&self, id: &str) -> Option<String> {
+        self.get_at(id, Instant::now())
+    }
+
+    fn get_at(&self, id: &str, now: Instant) -> Option<String> {
+        let mut sessions = self.sessions.lock();
+        let session = sessions.get_mut(id)?;
+        if self.is_expired(session, now) {
+            sessions.remove(id);
+            return None;
+        }
+        session.last_seen = now;
+        Some(session.subject.clone())
+    }
+
+    /// 注销会话
+    pub fn remove(&self, id: &str) {
+        self.sessions.lock().remove(id);
+    }
+
+    /// 注销全部会话，返回注销的有效会话数
+    pub fn revoke_all(&self) -> usize {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock();
+        let count = sessions
+            .values()
+            .filter(|session| !self.is_expired(session, now))
+            .count();
+        sessions.clear();
+        count
+    }
+}
+
+/// 生成含 244 位随机数的 URL 安全令牌（会话 ID、OIDC state / nonce / PKCE verifier）
+pub fn random_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// 读取请求中的 Cookie
+pub fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| {
+            let (key, value) = pair.trim().split_once('=')?;
+            (key == name).then_some(value)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions() {
+        let sessions = AdminSessions::default();
+        let id = sessions.create("alice@example.com");
+        assert_eq!(id.len(), 64);
+        assert_eq!(sessions.get(&id).as_deref(), Some("alice@example.com"));
+        assert_eq!(sessions.get("unknown"), None);
+        sessions.remove(&id);
+        assert_eq!(sessions.get(&id), None);
+
+        sessions.create("bob@example.com");
+        sessions.create("carol@example.com");
+        assert_eq!(sessions.revoke_all(), 2);
+        assert_eq!(sessions.revoke_all(), 0);
+    }
+
+    #[test]
+    fn test_session_expiry() {
+        let sessions = AdminSessions::new(Duration::from_secs(60), Duration::from_secs(300));
+        let id = sessions.create("alice@example.com");
+        let start = Instant::now();
+        // 持续使用时空闲计时不断刷新
+        for secs in [50, 100, 150, 200, 250] {
+            let now = start + Duration::from_secs(secs);
+            assert!(sessions.get_at(&id, now).is_some(), "{}s", secs);
+        }
+        // 超过最长有效期后即使一直在使用也会失效
+        assert_eq!(sessions.get_at(&id, start + Duration::from_secs(301)), None);
+
+        let id = sessions.create("alice@example.com");
+        assert_eq!(
+            sessions.get_at(&id, Instant::now() + Duration::from_secs(61)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            "theme=dark; kiro_admin_session=abc123".parse().unwrap(),
+        );
+        assert_eq!(cookie(&headers, SESSION_COOKIE), Some("abc123"));
+        assert_eq!(cookie(&headers, "missing"), None);
+    }
+}
@@ -1,7 +1,16 @@
 //! Admin API 类型定义
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::metrics::alerts::Alert;
+use crate::metrics::connections::ConnectionCounts;
+use crate::model::config::ModelRoute;
+use crate::usage::report::DailyReport;
+use crate::usage::timeseries::{SeriesMetric, SeriesPoint};
+use crate::usage::{KeyUsageSummary, UsageRecord};
+
 // ============ 凭据状态 ============
 
 /// 所有凭据状态响应
@@ -38,6 +47,10 @@ pub struct CredentialStatusItem {
     pub auth_method: Option<String>,
     /// 是否有 Profile ARN
     pub has_profile_arn: bool,
+    /// 凭据标签
+    pub tags: Vec<String>,
+    /// 启动以来的上游调用次数，按状态码（网络错误为 `network`）分组
+    pub upstream_statuses: BTreeMap<String, u64>,
 }
 
 // ============ 操作请求 ============
@@ -58,6 +71,14 @@ pub struct SetPriorityRequest {
     pub priority: u32,
 }
 
+/// 修改标签请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetTagsRequest {
+    /// 新标签列表（覆盖原有标签）
+    pub tags: Vec<String>,
+}
+
 /// 添加凭据请求
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -78,6 +99,13 @@ pub struct AddCredentialRequest {
     /// 优先级（可选，默认 0）
     #[serde(default)]
     pub priority: u32,
+
+    /// 标签（可选，用于模型路由）
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// 区域（可选，默认使用全局 region）
+    pub region: Option<String>,
 }
 
 fn default_auth_method() -> String {
@@ -94,6 +122,26 @@ pub struct AddCredentialResponse {
     pub credential_id: u64,
 }
 
+// ============ 模型路由 ============
+
+/// 模型路由规则（查询响应与替换请求共用）
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelRoutesBody {
+    /// 路由规则列表（按顺序匹配第一条）
+    pub routes: Vec<ModelRoute>,
+}
+
+/// 重新加载配置的结果
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReloadResponse {
+    /// 已在运行时生效的配置项
+    pub applied: Vec<String>,
+    /// 有变化但需重启后生效的配置项
+    pub restart_required: Vec<String>,
+}
+
 // ============ 余额查询 ============
 
 /// 余额查询响应
@@ -116,6 +164,111 @@ pub struct BalanceResponse {
     pub next_reset_at: Option<f64>,
 }
 
+// ============ 用量记录 ============
+
+/// 用量记录查询参数
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    /// 返回的最大记录数（默认 100）
+    pub limit: Option<usize>,
+}
+
+/// 用量记录查询响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageResponse {
+    /// 内存中的记录总数
+    pub total: usize,
+    /// 最近的记录（最新的在前）
+    pub records: Vec<UsageRecord>,
+}
+
+/// 按 API Key 汇总的用量响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageSummaryResponse {
+    /// 参与汇总的记录数（内存中的记录）
+    pub total: usize,
+    /// 各 API Key 的用量（请求数从多到少）
+    pub keys: Vec<KeyUsageSummary>,
+}
+
+/// 每日用量报告响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageReportsResponse {
+    /// 已生成的报告（最新的在前）
+    pub reports: Vec<DailyReport>,
+}
+
+/// 时间序列查询参数
+#[derive(Debug, Deserialize)]
+pub struct TimeseriesQuery {
+    /// 指标：`requests`（默认）/ `errors` / `tokens` / `latency`
+    #[serde(default)]
+    pub metric: SeriesMetric,
+    /// 时间窗口，如 `24h`（默认）
+    pub window: Option<String>,
+    /// 步长，如 `5m`（默认）
+    pub step: Option<String>,
+}
+
+/// 时间序列响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeseriesResponse {
+    pub metric: SeriesMetric,
+    /// 步长（秒）
+    pub step_secs: i64,
+    /// 各桶的值（按时间顺序，无数据的桶为 0）
+    pub points: Vec<SeriesPoint>,
+}
+
+// ============ 告警 ============
+
+/// 当前告警响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertsResponse {
+    /// 处于触发状态的告警
+    pub alerts: Vec<Alert>,
+}
+
+// ============ 上游连接 ============
+
+/// 上游连接复用统计响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionsResponse {
+    /// 自启动以来的统计
+    pub total: ConnectionCounts,
+    /// 最近一分钟的统计
+    pub last_minute: ConnectionCounts,
+}
+
+// ============ 单点登录 ============
+
+/// OIDC 回调参数
+#[derive(Debug, Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    /// 身份提供方返回的错误（如用户取消授权）
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+/// 当前会话响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionResponse {
+    /// 是否已通过单点登录
+    pub authenticated: bool,
+    /// 登录用户
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+}
+
 // ============ 通用响应 ============
 
 /// 操作成功响应
@@ -15,21 +15,30 @@ use rust_embed::Embed;
 #[folder = "admin-ui/dist"]
 struct Asset;
 
+/// 注入前端的运行时配置
+#[derive(Clone)]
+struct UiConfig {
+    /// 外部访问前缀
+    base_path: String,
+    /// 是否启用了 OIDC 单点登录
+    sso: bool,
+}
+
 /// 创建 Admin UI 路由
-pub fn create_admin_ui_router(base_path: String) -> Router {
+pub fn create_admin_ui_router(base_path: String, sso: bool) -> Router {
     Router::new()
         .route("/", get(index_handler))
         .route("/{*file}", get(static_handler))
-        .with_state(base_path)
+        .with_state(UiConfig { base_path, sso })
 }
 
 /// 处理首页请求
-async fn index_handler(State(base_path): State<String>) -> impl IntoResponse {
-    serve_index(&base_path)
+async fn index_handler(State(config): State<UiConfig>) -> impl IntoResponse {
+    serve_index(&config)
 }
 
 /// 处理静态文件请求
-async fn static_handler(State(base_path): State<String>, uri: Uri) -> impl IntoResponse {
+async fn static_handler(State(config): State<UiConfig>, uri: Uri) -> impl IntoResponse {
     let path = uri.path().trim_start_matches('/');
 
     // 安全检查：拒绝包含 .. 的路径
@@ -59,7 +68,7 @@ async fn static_handler(State(base_path): State<String>, uri: Uri) -> impl IntoR
 
     // SPA fallback: 如果文件不存在且不是资源文件，返回 index.html
     if !is_asset_path(path) {
-        return serve_index(&base_path);
+        return serve_index(&config);
     }
 
     // 404
@@ -70,17 +79,24 @@ async fn static_handler(State(base_path): State<String>, uri: Uri) -> impl IntoR
 }
 
 /// 提供 index.html（注入运行时配置）
-fn serve_index(base_path: &str) -> Response<Body> {
+fn serve_index(config: &UiConfig) -> Response<Body> {
+    let base_path = config.base_path.as_str();
     match Asset::get("index.html") {
         Some(content) => {
             let html = String::from_utf8_lossy(&content.data);
 
             // 注入运行时配置
             let config_script = format!(
-                r#"<script>window.__KIRO_CONFIG__={{basePath:"{}"}}</script>"#,
-                base_path
+                r#"<script>window.__KIRO_CONFIG__={{basePath:"{}",sso:{}}}</script>"#,
+                base_path, config.sso
             );
             let modified_html = html.replace("</head>", &format!("{}</head>", config_script));
+            // 构建产物中的资源地址以 /admin/ 开头，挂载在前缀下时需要补上前缀
+            let modified_html = if base_path.is_empty() {
+                modified_html
+            } else {
+                modified_html.replace("\"/admin/", &format!("\"{}/admin/", base_path))
+            };
 
             Response::builder()
                 .status(StatusCode::OK)
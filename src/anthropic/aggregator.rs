@@ -0,0 +1,256 @@
+//! 非流式响应聚合模块
+//!
+//! 上游只提供流式响应。非流式请求同样复用 [`StreamContext`](super::stream::StreamContext)
+//! 生成的 SSE 事件序列，再由 [`MessageAggregator`] 折叠为一个完整的 Anthropic 消息，
+//! 保证 thinking / 文本 / 工具调用块的解析规则与流式路径完全一致。
+
+use std::collections::BTreeMap;
+
+use serde_json::json;
+
+use super::stream::SseEvent;
+
+/// 聚合中的内容块
+#[derive(Debug)]
+enum AggregatedBlock {
+    Text(String),
+    Thinking(String),
+    ToolUse {
+        id: String,
+        name: String,
+        /// 累积的 input_json_delta 片段
+        partial_json: String,
+    },
+}
+
+/// SSE 事件聚合器
+///
+/// 按块索引收集 content_block_start / content_block_delta，
+/// 并从 message_delta 中提取 stop_reason 和 usage。
+#[derive(Debug, Default)]
+pub struct MessageAggregator {
+    /// 按索引排序的内容块
+    blocks: BTreeMap<i64, AggregatedBlock>,
+    /// message_delta 中的 stop_reason
+    stop_reason: Option<String>,
+    /// message_delta 中的输入 tokens
+    input_tokens: Option<i32>,
+}
+
+impl MessageAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 处理单个 SSE 事件
+    pub fn push(&mut self, event: &SseEvent) {
+        let data = &event.data;
+        match event.event.as_str() {
+            "content_block_start" => {
+                let Some(index) = data["index"].as_i64() else {
+                    return;
+                };
+                let block = &data["content_block"];
+                let aggregated = match block["type"].as_str() {
+                    Some("text") => AggregatedBlock::Text(String::new()),
+                    Some("thinking") => AggregatedBlock::Thinking(String::new()),
+                    Some("tool_use") => AggregatedBlock::ToolUse {
+                        id: block["id"].as_str().unwrap_or_default().to_string(),
+                        name: block["name"].as_str().unwrap_or_default().to_string(),
+                        partial_json: String::new(),
+                    },
+                    _ => return,
+                };
+                self.blocks.entry(index).or_insert(aggregated);
+            }
+            "content_block_delta" => {
+                let Some(index) = data["index"].as_i64() else {
+                    return;
+                };
+                let Some(block) = self.blocks.get_mut(&index) else {
+                    tracing::warn!("聚合时收到未知块 {} 的 delta 事件", index);
+                    return;
+                };
+                let delta = &data["delta"];
+                match (block, delta["type"].as_str()) {
+                    (AggregatedBlock::Text(text), Some("text_delta")) => {
+                        text.push_str(delta["text"].as_str().unwrap_or_default());
+                    }
+                    (AggregatedBlock::Thinking(thinking), Some("thinking_delta")) => {
+                        thinking.push_str(delta["thinking"].as_str().unwrap_or_default());
+                    }
+                    (AggregatedBlock::ToolUse { partial_json, .. }, Some("input_json_delta")) => {
+                        partial_json.push_str(delta["partial_json"].as_str().unwrap_or_default());
+                    }
+                    _ => {}
+                }
+            }
+            "message_delta" => {
+                if let Some(reason) = data["delta"]["stop_reason"].as_str() {
+                    self.stop_reason = Some(reason.to_string());
+                }
+                if let Some(tokens) = data["usage"]["input_tokens"].as_i64() {
+                    self.input_tokens = Some(tokens as i32);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 批量处理 SSE 事件
+    pub fn extend<'a>(&mut self, events: impl IntoIterator<Item = &'a SseEvent>) {
+        for event in events {
+            self.push(event);
+        }
+    }
+
+    /// 聚合后的 stop_reason（未收到 message_delta 时为 None）
+    pub fn stop_reason(&self) -> Option<&str> {
+        self.stop_reason.as_deref()
+    }
+
+    /// 聚合后的输入 tokens
+    pub fn input_tokens(&self) -> Option<i32> {
+        self.input_tokens
+    }
+
+    /// 生成 Anthropic 响应的 content 数组
+    ///
+    /// 空的文本/thinking 块会被丢弃；工具输入 JSON 解析失败时回退为空对象。
+    pub fn content(&self) -> Vec<serde_json::Value> {
+        let mut content = Vec::with_capacity(self.blocks.len());
+
+        for block in self.blocks.values() {
+            match block {
+                AggregatedBlock::Text(text) => {
+                    if !text.is_empty() {
+                        content.push(json!({
+                            "type": "text",
+                            "text": text
+                        }));
+                    }
+                }
+                AggregatedBlock::Thinking(thinking) => {
+                    if !thinking.is_empty() {
+                        content.push(json!({
+                            "type": "thinking",
+                            "thinking": thinking
+                        }));
+                    }
+                }
+                AggregatedBlock::ToolUse {
+                    id,
+                    name,
+                    partial_json,
+                } => {
+                    let input: serde_json::Value = if partial_json.trim().is_empty() {
+                        json!({})
+                    } else {
+                        serde_json::from_str(partial_json).unwrap_or_else(|e| {
+                            tracing::warn!(
+                                "工具输入 JSON 解析失败: {}, tool_use_id: {}, 原始内容: {}",
+                                e,
+                                id,
+                                partial_json
+                            );
+                            json!({})
+                        })
+                    };
+                    content.push(json!({
+                        "type": "tool_use",
+                        "id": id,
+                        "name": name,
+                        "input": input
+                    }));
+                }
+            }
+        }
+
+        content
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anthropic::stream::StreamContext;
+    use crate::kiro::model::events::{AssistantResponseEvent, Event, ToolUseEvent};
+
+    fn assistant(content: &str) -> Event {
+        let event: AssistantResponseEvent =
+            serde_json::from_value(json!({ "content": content })).unwrap();
+        Event::AssistantResponse(event)
+    }
+
+    fn tool_use(id: &str, input: &str, stop: bool) -> Event {
+        Event::ToolUse(ToolUseEvent {
+            name: "get_weather".to_string(),
+            tool_use_id: id.to_string(),
+            input: input.to_string(),
+            stop,
+        })
+    }
+
+    fn aggregate(thinking_enabled: bool, events: &[Event]) -> MessageAggregator {
+        let mut ctx = StreamContext::new_with_thinking("test-model", 10, thinking_enabled);
+        let mut aggregator = MessageAggregator::new();
+        aggregator.extend(&ctx.generate_initial_events());
+        for event in events {
+            aggregator.extend(&ctx.process_kiro_event(event));
+        }
+        aggregator.extend(&ctx.generate_final_events());
+        aggregator
+    }
+
+    #[test]
+    fn test_aggregate_text_and_tool_use() {
+        let aggregator = aggregate(
+            false,
+            &[
+                assistant("Let me check. "),
+                assistant("One moment."),
+                tool_use("tool_1", "{\"city\":", false),
+                tool_use("tool_1", "\"Paris\"}", true),
+            ],
+        );
+
+        let content = aggregator.content();
+        assert_eq!(content.len(), 2);
+        assert_eq!(content[0]["type"], "text");
+        assert_eq!(content[0]["text"], "Let me check. One moment.");
+        assert_eq!(content[1]["type"], "tool_use");
+        assert_eq!(content[1]["id"], "tool_1");
+        assert_eq!(content[1]["input"]["city"], "Paris");
+        assert_eq!(aggregator.stop_reason(), Some("tool_use"));
+        assert_eq!(aggregator.input_tokens(), Some(10));
+    }
+
+    #[test]
+    fn test_aggregate_thinking_block() {
+        let aggregator = aggregate(
+            true,
+            &[
+                assistant("<thinking>reasoning here</thinking>\n\n"),
+                assistant("final answer"),
+            ],
+        );
+
+        let content = aggregator.content();
+        assert_eq!(content.len(), 2);
+        assert_eq!(content[0]["type"], "thinking");
+        assert_eq!(content[0]["thinking"], "reasoning here");
+        assert_eq!(content[1]["type"], "text");
+        // 与流式路径一致：结束标签后的双换行保留在文本块中
+        assert_eq!(content[1]["text"], "\n\nfinal answer");
+        assert_eq!(aggregator.stop_reason(), Some("end_turn"));
+    }
+
+    #[test]
+    fn test_aggregate_invalid_tool_json_falls_back_to_empty_object() {
+        let aggregator = aggregate(false, &[tool_use("tool_1", "{\"city\":", true)]);
+
+        let content = aggregator.content();
+        assert_eq!(content.len(), 1);
+        assert_eq!(content[0]["input"], json!({}));
+    }
+}
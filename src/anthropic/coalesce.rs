@@ -0,0 +1,247 @@
+//! 流式增量合并
+//!
+//! 上游经常以极小的片段（几个字符）返回文本，逐条转发会产生大量 SSE 事件。
+//! 启用后，同一内容块的连续增量会在时间窗口内合并为一个事件，
+//! 达到字符上限、窗口到期或遇到其他事件时立即输出，事件顺序保持不变。
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::time::Duration;
+
+use futures::{Stream, StreamExt, stream};
+use tokio::time::Instant;
+
+use crate::model::config::Config;
+
+use super::stream::SseEvent;
+
+/// 合并参数
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CoalesceOptions {
+    /// 首个增量到达后最多等待的时间
+    pub window: Duration,
+    /// 合并后的增量达到该字符数时立即输出
+    pub max_chars: usize,
+}
+
+impl CoalesceOptions {
+    /// 从配置读取，未配置 `streamCoalesceMs` 时不启用
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let window_ms = config.stream_coalesce_ms.filter(|ms| *ms > 0)?;
+        Some(Self {
+            window: Duration::from_millis(window_ms),
+            max_chars: config.stream_coalesce_chars.max(1),
+        })
+    }
+}
+
+/// 可合并增量的文本字段名
+fn delta_field(event: &SseEvent) -> Option<&'static str> {
+    if event.event != "content_block_delta" {
+        return None;
+    }
+    match event.data["delta"]["type"].as_str()? {
+        "text_delta" => Some("text"),
+        "thinking_delta" => Some("thinking"),
+        "input_json_delta" => Some("partial_json"),
+        _ => None,
+    }
+}
+
+/// 正在合并中的增量
+struct Pending {
+    event: SseEvent,
+    field: &'static str,
+    chars: usize,
+    deadline: Instant,
+}
+
+impl Pending {
+    /// 同一内容块、同一类型的增量才能合并
+    fn accepts(&self, event: &SseEvent) -> bool {
+        delta_field(event) == Some(self.field) && event.data["index"] == self.event.data["index"]
+    }
+
+    fn append(&mut self, event: &SseEvent) {
+        let text = event.data["delta"][self.field].as_str().unwrap_or_default();
+        self.chars += text.chars().count();
+        if let Some(serde_json::Value::String(merged)) =
+            self.event.data["delta"].get_mut(self.field)
+        {
+            merged.push_str(text);
+        }
+    }
+}
+
+struct State<S> {
+    inner: Pin<Box<S>>,
+    options: CoalesceOptions,
+    pending: Option<Pending>,
+    ready: VecDeque<SseEvent>,
+    finished: bool,
+}
+
+impl<S: Stream<Item = SseEvent>> State<S> {
+    fn flush(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            self.ready.push_back(pending.event);
+        }
+    }
+
+    fn push(&mut self, event: SseEvent) {
+        if let Some(pending) = &mut self.pending
+            && pending.accepts(&event)
+        {
+            pending.append(&event);
+            if pending.chars >= self.options.max_chars {
+                self.flush();
+            }
+            return;
+        }
+
+        self.flush();
+        match delta_field(&event) {
+            Some(field) => {
+                let chars = event.data["delta"][field]
+                    .as_str()
+                    .map_or(0, |t| t.chars().count());
+                if chars >= self.options.max_chars {
+                    self.ready.push_back(event);
+                } else {
+                    self.pending = Some(Pending {
+                        event,
+                        field,
+                        chars,
+                        deadline: Instant::now() + self.options.window,
+                    });
+                }
+            }
+            None => self.ready.push_back(event),
+        }
+    }
+
+    async fn next(&mut self) -> Option<SseEvent> {
+        loop {
+            if let Some(event) = self.ready.pop_front() {
+                return Some(event);
+            }
+            if self.finished {
+                return None;
+            }
+
+            let next = match &self.pending {
+                Some(pending) => {
+                    match tokio::time::timeout_at(pending.deadline, self.inner.next()).await {
+                        Ok(next) => next,
+                        // 窗口到期，输出已合并的部分
+                        Err(_) => {
+                            self.flush();
+                            continue;
+                        }
+                    }
+                }
+                None => self.inner.next().await,
+            };
+
+            match next {
+                Some(event) => self.push(event),
+                None => {
+                    self.flush();
+                    self.finished = true;
+                }
+            }
+        }
+    }
+}
+
+/// 按参数合并事件流中的小增量，`options` 为 None 时原样透传
+pub(crate) fn coalesce<S>(
+    events: S,
+    options: Option<CoalesceOptions>,
+) -> impl Stream<Item = SseEvent> + Send + 'static
+where
+    S: Stream<Item = SseEvent> + Send + 'static,
+{
+    match options {
+        None => events.left_stream(),
+        Some(options) => {
+            let state = State {
+                inner: Box::pin(events),
+                options,
+                pending: None,
+                ready: VecDeque::new(),
+                finished: false,
+            };
+            stream::unfold(state, |mut state| async move {
+                let event = state.next().await?;
+                Some((event, state))
+            })
+            .right_stream()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn text(index: i64, text: &str) -> SseEvent {
+        SseEvent::new(
+            "content_block_delta",
+            json!({"index": index, "delta": {"type": "text_delta", "text": text}}),
+        )
+    }
+
+    fn options(max_chars: usize) -> Option<CoalesceOptions> {
+        Some(CoalesceOptions {
+            window: Duration::from_secs(60),
+            max_chars,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_merges_same_block_and_keeps_order() {
+        let events = stream::iter(vec![
+            text(0, "He"),
+            text(0, "llo"),
+            text(1, "a"),
+            SseEvent::new("content_block_stop", json!({"index": 1})),
+            text(1, "b"),
+        ]);
+        let out: Vec<SseEvent> = coalesce(events, options(100)).collect().await;
+
+        assert_eq!(out.len(), 4);
+        assert_eq!(out[0].data["delta"]["text"], "Hello");
+        assert_eq!(out[1].data["delta"]["text"], "a");
+        assert_eq!(out[2].event, "content_block_stop");
+        assert_eq!(out[3].data["delta"]["text"], "b");
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_flushes_at_max_chars() {
+        let events = stream::iter(vec![text(0, "ab"), text(0, "cd"), text(0, "e")]);
+        let out: Vec<SseEvent> = coalesce(events, options(4)).collect().await;
+
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].data["delta"]["text"], "abcd");
+        assert_eq!(out[1].data["delta"]["text"], "e");
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_flushes_when_window_expires() {
+        let events = stream::iter(vec![text(0, "a")]).chain(stream::once(async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            text(0, "b")
+        }));
+        let options = Some(CoalesceOptions {
+            window: Duration::from_millis(20),
+            max_chars: 100,
+        });
+        let out: Vec<SseEvent> = coalesce(events, options).collect().await;
+
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].data["delta"]["text"], "a");
+        assert_eq!(out[1].data["delta"]["text"], "b");
+    }
+}
@@ -0,0 +1,260 @@
+//! 历史消息压缩模块
+//!
+//! 当估算的输入 tokens 超过上下文窗口时，按配置的策略从最早的对话轮次开始丢弃，
+//! 而不是直接把请求发往上游后失败。
+//!
+//! 只在真实的用户轮次（不含 tool_result 的 user 消息）处截断，
+//! 保证保留下来的 tool_result 总能找到对应的 tool_use。
+
+use crate::model::config::CompactionStrategy;
+use crate::token;
+
+use super::types::{Message, MessagesRequest};
+
+/// 摘要中每条被丢弃消息保留的最大字符数
+const SUMMARY_EXCERPT_CHARS: usize = 200;
+
+/// 摘要的最大字符数
+const SUMMARY_MAX_CHARS: usize = 4000;
+
+/// 按策略压缩请求的历史消息，返回被移除的消息数（未压缩时为 0）
+///
+/// 使用本地估算的 token 数判断是否超限，不调用远程 count_tokens API。
+/// 即使丢弃到只剩最后一轮仍然超限，也返回已压缩的结果，交由上游判断。
+pub fn compact(req: &mut MessagesRequest, strategy: CompactionStrategy, limit: u64) -> usize {
+    if strategy == CompactionStrategy::Off {
+        return 0;
+    }
+
+    let fixed = fixed_tokens(req);
+    let message_tokens: Vec<u64> = req.messages.iter().map(estimate_message_tokens).collect();
+    let total: u64 = fixed + message_tokens.iter().sum::<u64>();
+    if total <= limit {
+        return 0;
+    }
+
+    // 候选截断点：第一条之后、最后一条之前（含）的真实用户轮次
+    let cuts: Vec<usize> = (1..req.messages.len())
+        .filter(|&i| is_turn_start(&req.messages[i]))
+        .collect();
+    let Some(&last_cut) = cuts.last() else {
+        return 0;
+    };
+
+    let mut cut = last_cut;
+    let mut summary = None;
+    for &candidate in &cuts {
+        let kept: u64 = message_tokens[candidate..].iter().sum();
+        let candidate_summary = (strategy == CompactionStrategy::Summarize)
+            .then(|| build_summary(&req.messages[..candidate]));
+        let summary_tokens = candidate_summary
+            .as_ref()
+            .map(|s| token::count_tokens(s))
+            .unwrap_or(0);
+
+        if fixed + kept + summary_tokens <= limit {
+            cut = candidate;
+            summary = candidate_summary;
+            break;
+        }
+        if candidate == last_cut {
+            summary = candidate_summary;
+        }
+    }
+
+    let dropped = req.messages.drain(..cut).count();
+    if let Some(summary) = summary {
+        req.messages.insert(
+            0,
+            Message {
+                role: "user".to_string(),
+                content: serde_json::json!(summary),
+            },
+        );
+    }
+
+    tracing::warn!(
+        "输入约 {} tokens，超过上下文窗口 {}，已移除最早的 {} 条历史消息",
+        total,
+        limit,
+        dropped
+    );
+    dropped
+}
+
+/// 系统提示与工具定义的 tokens（不随历史压缩变化）
+fn fixed_tokens(req: &MessagesRequest) -> u64 {
+    let system: u64 = req
+        .system
+        .iter()
+        .flatten()
+        .map(|s| token::count_tokens(&s.text))
+        .sum();
+    let tools: u64 = req
+        .tools
+        .iter()
+        .flatten()
+        .map(|t| {
+            token::count_tokens(&t.name)
+                + token::count_tokens(&t.description)
+                + token::count_tokens(&serde_json::to_string(&t.input_schema).unwrap_or_default())
+        })
+        .sum();
+    system + tools
+}
+
+/// 估算单条消息的 tokens
+///
+/// 与 count_tokens 端点不同，这里会计入 tool_use 输入和 tool_result 内容，
+/// 它们往往是长对话中体积最大的部分
+fn estimate_message_tokens(message: &Message) -> u64 {
+    match &message.content {
+        serde_json::Value::String(s) => token::count_tokens(s),
+        serde_json::Value::Array(blocks) => blocks
+            .iter()
+            .map(|block| match block.get("text").and_then(|v| v.as_str()) {
+                Some(text) => token::count_tokens(text),
+                None => token::count_tokens(&block.to_string()),
+            })
+            .sum(),
+        other => token::count_tokens(&other.to_string()),
+    }
+}
+
+/// 是否为新对话轮次的开始（不含 tool_result 的 user 消息）
+fn is_turn_start(message: &Message) -> bool {
+    if message.role != "user" {
+        return false;
+    }
+    !message
+        .content
+        .as_array()
+        .is_some_and(|blocks| blocks.iter().any(|b| b["type"] == "tool_result"))
+}
+
+/// 将被丢弃的消息整理为简短摘录
+fn build_summary(messages: &[Message]) -> String {
+    let mut summary =
+        String::from("[Earlier conversation was truncated to fit the context window. Excerpts:]\n");
+    for message in messages {
+        let text = message_text(message);
+        if text.is_empty() {
+            continue;
+        }
+        let excerpt: String = text.chars().take(SUMMARY_EXCERPT_CHARS).collect();
+        let line = format!("- {}: {}\n", message.role, excerpt.replace('\n', " "));
+        if summary.len() + line.len() > SUMMARY_MAX_CHARS {
+            break;
+        }
+        summary.push_str(&line);
+    }
+    summary
+}
+
+/// 提取消息中的纯文本内容
+fn message_text(message: &Message) -> String {
+    match &message.content {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|b| b.get("text").and_then(|v| v.as_str()))
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn message(role: &str, content: serde_json::Value) -> Message {
+        Message {
+            role: role.to_string(),
+            content,
+        }
+    }
+
+    fn request(messages: Vec<Message>) -> MessagesRequest {
+        MessagesRequest {
+            model: "claude-sonnet-4".to_string(),
+            max_tokens: 1024,
+            temperature: None,
+            messages,
+            stream: false,
+            system: None,
+            tools: None,
+            tool_choice: None,
+            thinking: None,
+            metadata: None,
+        }
+    }
+
+    fn long_conversation() -> Vec<Message> {
+        let filler = "word ".repeat(400);
+        vec![
+            message("user", json!(filler)),
+            message("assistant", json!(filler)),
+            message("user", json!(filler)),
+            message(
+                "assistant",
+                json!([{"type": "tool_use", "id": "t1", "name": "read", "input": {}}]),
+            ),
+            message(
+                "user",
+                json!([{"type": "tool_result", "tool_use_id": "t1", "content": filler}]),
+            ),
+            message("assistant", json!("done")),
+            message("user", json!("latest question")),
+        ]
+    }
+
+    #[test]
+    fn test_off_strategy_keeps_history() {
+        let mut req = request(long_conversation());
+        assert_eq!(compact(&mut req, CompactionStrategy::Off, 10), 0);
+        assert_eq!(req.messages.len(), 7);
+    }
+
+    #[test]
+    fn test_within_limit_keeps_history() {
+        let mut req = request(long_conversation());
+        assert_eq!(
+            compact(&mut req, CompactionStrategy::DropOldest, 1_000_000),
+            0
+        );
+        assert_eq!(req.messages.len(), 7);
+    }
+
+    #[test]
+    fn test_drop_oldest_never_splits_tool_pairs() {
+        let mut req = request(long_conversation());
+        let dropped = compact(&mut req, CompactionStrategy::DropOldest, 2000);
+
+        // 第二轮包含 tool_use/tool_result 配对，只能整轮保留或整轮丢弃
+        assert_eq!(dropped, 2);
+        assert_eq!(req.messages[0].content, json!("word ".repeat(400)));
+        assert_eq!(req.messages.len(), 5);
+    }
+
+    #[test]
+    fn test_drop_oldest_falls_back_to_last_turn() {
+        let mut req = request(long_conversation());
+        let dropped = compact(&mut req, CompactionStrategy::DropOldest, 1);
+        assert_eq!(dropped, 6);
+        assert_eq!(req.messages[0].content, json!("latest question"));
+    }
+
+    #[test]
+    fn test_summarize_inserts_excerpt() {
+        let mut req = request(long_conversation());
+        let dropped = compact(&mut req, CompactionStrategy::Summarize, 1);
+        assert_eq!(dropped, 6);
+        assert_eq!(req.messages.len(), 2);
+        assert_eq!(req.messages[0].role, "user");
+        let summary = req.messages[0].content.as_str().unwrap();
+        assert!(summary.starts_with("[Earlier conversation was truncated"));
+        assert!(summary.contains("- assistant: done"));
+    }
+}
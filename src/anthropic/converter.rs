@@ -240,7 +240,8 @@ fn process_message_content(
                         }
                         "tool_result" => {
                             if let Some(tool_use_id) = block.tool_use_id {
-                                let result_content = extract_tool_result_content(&block.content);
+                                let result_content =
+                                    extract_tool_result_content(&block.content, &mut images);
                                 let is_error = block.is_error.unwrap_or(false);
 
                                 let mut result = if is_error {
@@ -279,22 +280,46 @@ fn get_image_format(media_type: &str) -> Option<String> {
     }
 }
 
+/// 工具结果中图片所在位置的文本占位（图片本身随消息附带）
+const TOOL_RESULT_IMAGE_PLACEHOLDER: &str = "[image]";
+
 /// 提取工具结果内容
-fn extract_tool_result_content(content: &Option<serde_json::Value>) -> String {
-    match content {
-        Some(serde_json::Value::String(s)) => s.clone(),
-        Some(serde_json::Value::Array(arr)) => {
-            let mut parts = Vec::new();
-            for item in arr {
-                if let Some(text) = item.get("text").and_then(|v| v.as_str()) {
-                    parts.push(text.to_string());
+///
+/// 上游的工具结果只支持文本：其中的图片块转为当前消息的附带图片，
+/// 其余非文本块序列化为 JSON 文本，避免被静默丢弃
+fn extract_tool_result_content(
+    content: &Option<serde_json::Value>,
+    images: &mut Vec<KiroImage>,
+) -> String {
+    let items = match content {
+        Some(serde_json::Value::String(s)) => return s.clone(),
+        Some(serde_json::Value::Array(arr)) => arr.as_slice(),
+        Some(v @ serde_json::Value::Object(_)) => std::slice::from_ref(v),
+        Some(v) => return v.to_string(),
+        None => return String::new(),
+    };
+
+    let mut parts = Vec::new();
+    for item in items {
+        match item["type"].as_str() {
+            Some("text") => parts.push(item["text"].as_str().unwrap_or_default().to_string()),
+            Some("image") => {
+                let media_type = item["source"]["media_type"].as_str().unwrap_or_default();
+                match (
+                    get_image_format(media_type),
+                    item["source"]["data"].as_str(),
+                ) {
+                    (Some(format), Some(data)) => {
+                        images.push(KiroImage::from_base64(format, data));
+                        parts.push(TOOL_RESULT_IMAGE_PLACEHOLDER.to_string());
+                    }
+                    _ => tracing::warn!("跳过工具结果中不支持的图片: {}", media_type),
                 }
             }
-            parts.join("\n")
+            _ => parts.push(item.to_string()),
         }
-        Some(v) => v.to_string(),
-        None => String::new(),
     }
+    parts.join("\n")
 }
 
 /// 验证并过滤 tool_use/tool_result 配对
@@ -655,6 +680,7 @@ mod tests {
         let req = MessagesRequest {
             model: "claude-sonnet-4".to_string(),
             max_tokens: 1024,
+            temperature: None,
             messages: vec![],
             stream: false,
             system: None,
@@ -715,6 +741,22 @@ mod tests {
         assert!(json.contains("\"name\":\"my_custom_tool\""));
     }
 
+    #[test]
+    fn test_generate_thinking_prefix_clamps_budget() {
+        let thinking: Thinking =
+            serde_json::from_str(r#"{"type": "enabled", "budget_tokens": 100}"#).unwrap();
+        let prefix = generate_thinking_prefix(&Some(thinking)).unwrap();
+        assert!(prefix.contains("<max_thinking_length>1024</max_thinking_length>"));
+
+        let thinking: Thinking =
+            serde_json::from_str(r#"{"type": "enabled", "budget_tokens": 99999}"#).unwrap();
+        let prefix = generate_thinking_prefix(&Some(thinking)).unwrap();
+        assert!(prefix.contains("<max_thinking_length>24576</max_thinking_length>"));
+
+        let disabled: Thinking = serde_json::from_str(r#"{"type": "disabled"}"#).unwrap();
+        assert!(generate_thinking_prefix(&Some(disabled)).is_none());
+    }
+
     #[test]
     fn test_history_tools_added_to_tools_list() {
         use super::super::types::Message as AnthropicMessage;
@@ -723,6 +765,7 @@ mod tests {
         let req = MessagesRequest {
             model: "claude-sonnet-4".to_string(),
             max_tokens: 1024,
+            temperature: None,
             messages: vec![
                 AnthropicMessage {
                     role: "user".to_string(),
@@ -802,6 +845,7 @@ mod tests {
         let req = MessagesRequest {
             model: "claude-sonnet-4".to_string(),
             max_tokens: 1024,
+            temperature: None,
             messages: vec![AnthropicMessage {
                 role: "user".to_string(),
                 content: serde_json::json!("Hello"),
@@ -833,6 +877,7 @@ mod tests {
         let req = MessagesRequest {
             model: "claude-sonnet-4".to_string(),
             max_tokens: 1024,
+            temperature: None,
             messages: vec![AnthropicMessage {
                 role: "user".to_string(),
                 content: serde_json::json!("Hello"),
@@ -882,8 +927,10 @@ mod tests {
 
         // 测试孤立的 tool_use（有 tool_use 但没有对应的 tool_result）
         let mut assistant_msg = AssistantMessage::new("I'll read the file.");
-        assistant_msg = assistant_msg.with_tool_uses(vec![ToolUseEntry::new("tool-orphan", "read")
-            .with_input(serde_json::json!({"path": "/test.txt"}))]);
+        assistant_msg = assistant_msg.with_tool_uses(vec![
+            ToolUseEntry::new("tool-orphan", "read")
+                .with_input(serde_json::json!({"path": "/test.txt"})),
+        ]);
 
         let history = vec![
             Message::User(HistoryUserMessage::new(
@@ -911,8 +958,10 @@ mod tests {
 
         // 测试正常配对的情况
         let mut assistant_msg = AssistantMessage::new("I'll read the file.");
-        assistant_msg = assistant_msg.with_tool_uses(vec![ToolUseEntry::new("tool-1", "read")
-            .with_input(serde_json::json!({"path": "/test.txt"}))]);
+        assistant_msg = assistant_msg.with_tool_uses(vec![
+            ToolUseEntry::new("tool-1", "read")
+                .with_input(serde_json::json!({"path": "/test.txt"})),
+        ]);
 
         let history = vec![
             Message::User(HistoryUserMessage::new(
@@ -964,4 +1013,26 @@ mod tests {
         assert_eq!(filtered[0].tool_use_id, "tool-1");
         // tool-2 是孤立的 tool_use（无 result），tool-3 是孤立的 tool_result
     }
+
+    #[test]
+    fn test_tool_result_images_become_message_images() {
+        let content = serde_json::json!([
+            {"type": "tool_result", "tool_use_id": "tool-1", "content": [
+                {"type": "text", "text": "screenshot:"},
+                {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "AAAA"}}
+            ]},
+            {"type": "tool_result", "tool_use_id": "tool-2", "is_error": true,
+             "content": {"type": "text", "text": "boom"}}
+        ]);
+
+        let (_, images, tool_results) = process_message_content(&content).unwrap();
+
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].format, "png");
+        assert_eq!(images[0].source.bytes, "AAAA");
+        assert_eq!(tool_results.len(), 2);
+        assert_eq!(tool_results[0].content[0]["text"], "screenshot:\n[image]");
+        assert_eq!(tool_results[1].content[0]["text"], "boom");
+        assert!(tool_results[1].is_error);
+    }
 }
@@ -0,0 +1,135 @@
+//! 相同并发请求合并
+//!
+//! 重试频繁的客户端经常同时发出多个完全相同的非流式请求。开启 `dedupeConcurrentRequests` 后，
+//! 第一个请求（leader）实际调用上游，其余相同请求（follower）等待并共享它的结果，避免重复消耗配额。
+//! leader 中途被取消（如客户端断开）时，follower 各自回退为独立调用上游。
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::http::HeaderMap;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use tokio::sync::watch;
+
+use super::handlers::HandlerError;
+
+/// leader 共享给 follower 的结果（含筛选后的上游响应头）
+pub(crate) type SharedResult = Result<(serde_json::Value, HeaderMap), HandlerError>;
+
+type Slot = watch::Receiver<Option<SharedResult>>;
+
+/// 进行中的请求表
+#[derive(Default)]
+pub(crate) struct InflightRequests {
+    slots: Mutex<HashMap<String, Slot>>,
+}
+
+/// 加入进行中请求表的结果
+pub(crate) enum Role {
+    /// 没有相同的请求在进行，由当前请求调用上游
+    Leader(Leader),
+    /// 已有相同的请求在进行，等待它的结果
+    Follower(Slot),
+}
+
+/// 调用上游的请求，完成后通过 [`Leader::finish`] 广播结果，drop 时从请求表中移除
+pub(crate) struct Leader {
+    inflight: Arc<InflightRequests>,
+    key: String,
+    sender: watch::Sender<Option<SharedResult>>,
+}
+
+impl InflightRequests {
+    /// 按请求键加入：已有相同请求时成为 follower，否则成为 leader
+    pub fn join(self: &Arc<Self>, key: &str) -> Role {
+        let mut slots = self.slots.lock();
+        if let Some(slot) = slots.get(key) {
+            return Role::Follower(slot.clone());
+        }
+        let (sender, receiver) = watch::channel(None);
+        slots.insert(key.to_string(), receiver);
+        Role::Leader(Leader {
+            inflight: self.clone(),
+            key: key.to_string(),
+            sender,
+        })
+    }
+
+    /// 进行中的请求数
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.slots.lock().len()
+    }
+}
+
+impl Leader {
+    /// 将结果广播给等待中的 follower
+    pub fn finish(self, result: &SharedResult) {
+        self.sender.send_replace(Some(result.clone()));
+    }
+}
+
+impl Drop for Leader {
+    fn drop(&mut self) {
+        self.inflight.slots.lock().remove(&self.key);
+    }
+}
+
+/// 等待 leader 的结果，leader 未完成就被取消时返回 None
+pub(crate) async fn wait(mut slot: Slot) -> Option<SharedResult> {
+    slot.wait_for(Option::is_some).await.ok()?.clone()
+}
+
+/// 请求键：对决定上游输出的全部内容取 SHA-256（十六进制）
+pub(crate) fn request_key(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_request_key_separates_parts() {
+        assert_eq!(request_key(&["a", "bc"]), request_key(&["a", "bc"]));
+        assert_ne!(request_key(&["a", "bc"]), request_key(&["ab", "c"]));
+    }
+
+    #[tokio::test]
+    async fn test_follower_receives_leader_result() {
+        let inflight = Arc::new(InflightRequests::default());
+        let Role::Leader(leader) = inflight.join("k") else {
+            panic!("first request should lead");
+        };
+        let Role::Follower(slot) = inflight.join("k") else {
+            panic!("identical request should follow");
+        };
+        assert!(matches!(inflight.join("other"), Role::Leader(_)));
+
+        let waiter = tokio::spawn(wait(slot));
+        leader.finish(&Ok((json!({"id": "msg_1"}), HeaderMap::new())));
+        let (message, _) = waiter.await.unwrap().unwrap().unwrap();
+        assert_eq!(message["id"], "msg_1");
+        assert_eq!(inflight.len(), 0);
+        assert!(matches!(inflight.join("k"), Role::Leader(_)));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_leader_releases_followers() {
+        let inflight = Arc::new(InflightRequests::default());
+        let leader = inflight.join("k");
+        let Role::Follower(slot) = inflight.join("k") else {
+            panic!("identical request should follow");
+        };
+        drop(leader);
+        assert!(wait(slot).await.is_none());
+        assert_eq!(inflight.len(), 0);
+    }
+}
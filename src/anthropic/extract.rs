@@ -0,0 +1,86 @@
+//! 返回 Anthropic 格式错误的 JSON 请求体提取器
+
+use axum::{
+    Json,
+    extract::{FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::de::DeserializeOwned;
+
+use crate::common::json::{REQUEST_TOO_LARGE, classify_rejection};
+
+use super::types::ErrorResponse;
+
+/// 与 `axum::Json` 相同，但解析失败时返回 Anthropic 格式的 400 / 413 错误
+pub struct AnthropicJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for AnthropicJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(Self(value)),
+            Err(rejection) => {
+                let (status, message) = classify_rejection(&rejection);
+                tracing::warn!("请求体解析失败 ({}): {}", status, message);
+                let error_type = if status == StatusCode::PAYLOAD_TOO_LARGE {
+                    REQUEST_TOO_LARGE
+                } else {
+                    "invalid_request_error"
+                };
+                Err((status, Json(ErrorResponse::new(error_type, message))).into_response())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{Body, to_bytes};
+    use axum::http::header;
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Payload {
+        #[allow(dead_code)]
+        model: String,
+    }
+
+    async fn extract(body: impl Into<Body>) -> Response {
+        let request = Request::builder()
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.into())
+            .unwrap();
+        match AnthropicJson::<Payload>::from_request(request, &()).await {
+            Ok(_) => StatusCode::OK.into_response(),
+            Err(response) => response,
+        }
+    }
+
+    async fn error_type(response: Response) -> String {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        value["error"]["type"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_missing_field_is_structured_400() {
+        let response = extract("{}").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(error_type(response).await, "invalid_request_error");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_is_structured_413() {
+        // 未设置 DefaultBodyLimit 时 axum 默认限制为 2MB
+        let body = format!("{{\"model\":\"{}\"}}", "a".repeat(3 * 1024 * 1024));
+        let response = extract(body).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(error_type(response).await, REQUEST_TOO_LARGE);
+    }
+}
@@ -0,0 +1,332 @@
+//! 关键词护栏
+//!
+//! 按配置的屏蔽规则检查请求中的提示词，命中时直接拒绝；
+//! 启用 `scanOutput` 后同时检查模型输出，按 `outputAction` 截断（以 `refusal` 结束消息）或仅记录。
+//! 命中的规则写入用量记录的 `guardrail` 字段，并输出警告日志。
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use regex_automata::meta::Regex;
+use serde_json::{Value, json};
+
+use crate::common::rewrite::for_each_text;
+use crate::model::config::{GuardrailAction, GuardrailConfig};
+use crate::usage::GuardrailFlag;
+
+use super::pipeline::EventStage;
+use super::stream::SseEvent;
+
+/// 截断输出时使用的 stop_reason
+pub const REFUSAL_STOP_REASON: &str = "refusal";
+
+/// 流式检查时回看的字节数，用于匹配跨越多个增量的内容
+const SCAN_OVERLAP_BYTES: usize = 256;
+
+/// 编译后的护栏规则
+#[derive(Default)]
+pub(crate) struct Guardrail {
+    patterns: Vec<(String, Regex)>,
+    scan_output: bool,
+    output_action: GuardrailAction,
+}
+
+impl Guardrail {
+    /// 编译配置中的屏蔽规则，无效的正则会被忽略并记录警告
+    pub fn new(config: &GuardrailConfig) -> Self {
+        let patterns = config
+            .blocked_patterns
+            .iter()
+            .filter_map(|pattern| match Regex::new(pattern) {
+                Ok(regex) => Some((pattern.clone(), regex)),
+                Err(e) => {
+                    tracing::warn!("忽略无效的护栏屏蔽规则 {:?}: {}", pattern, e);
+                    None
+                }
+            })
+            .collect();
+        Self {
+            patterns,
+            scan_output: config.scan_output,
+            output_action: config.output_action,
+        }
+    }
+
+    /// 是否配置了屏蔽规则
+    pub fn is_enabled(&self) -> bool {
+        !self.patterns.is_empty()
+    }
+
+    /// 是否需要检查模型输出
+    pub fn scans_output(&self) -> bool {
+        self.is_enabled() && self.scan_output
+    }
+
+    /// 在文本中查找最早的命中，返回 (命中起始位置, 屏蔽规则)
+    pub fn find(&self, text: &str) -> Option<(usize, &str)> {
+        self.patterns
+            .iter()
+            .filter_map(|(pattern, regex)| regex.find(text).map(|m| (m.start(), pattern.as_str())))
+            .min_by_key(|(start, _)| *start)
+    }
+
+    /// 检查原始请求体中的消息、系统提示与提示词，返回命中的屏蔽规则
+    pub fn check_request(&self, body: &Value) -> Option<&str> {
+        let mut hit = None;
+        for_each_text(body, &mut |text| {
+            if hit.is_none() {
+                hit = self.find(text).map(|(_, pattern)| pattern);
+            }
+        });
+        hit
+    }
+
+    /// 检查非流式响应中的文本与 thinking 块，返回命中的屏蔽规则
+    ///
+    /// 处理方式为截断时，保留命中位置之前的内容，移除后续内容块并将 stop_reason 改为 `refusal`
+    pub fn check_message(&self, message: &mut Value) -> Option<String> {
+        let content = message["content"].as_array_mut()?;
+        for i in 0..content.len() {
+            let field = match content[i]["type"].as_str() {
+                Some("text") => "text",
+                Some("thinking") => "thinking",
+                _ => continue,
+            };
+            let Some(text) = content[i][field].as_str() else {
+                continue;
+            };
+            let Some((start, pattern)) = self.find(text) else {
+                continue;
+            };
+            tracing::warn!(pattern, "模型输出命中护栏屏蔽规则");
+            if self.output_action == GuardrailAction::Halt {
+                let kept = text[..start].to_string();
+                content[i][field] = json!(kept);
+                content.truncate(i + 1);
+                message["stop_reason"] = json!(REFUSAL_STOP_REASON);
+            }
+            return Some(pattern.to_string());
+        }
+        None
+    }
+}
+
+/// 流式输出检查阶段
+///
+/// 按内容块累积文本并检查新增部分（含少量回看），命中后写入采集器的护栏标记。
+/// 截断时丢弃后续的内容块与增量，只保留已开始块的结束事件与最终的 message_delta / message_stop
+pub(crate) struct OutputGuard {
+    guardrail: Arc<Guardrail>,
+    flag: GuardrailFlag,
+    buffers: HashMap<i64, String>,
+    open_blocks: HashSet<i64>,
+    /// 已命中（仅记录模式下不再检查）
+    matched: bool,
+}
+
+impl OutputGuard {
+    pub fn new(guardrail: Arc<Guardrail>, flag: GuardrailFlag) -> Self {
+        Self {
+            guardrail,
+            flag,
+            buffers: HashMap::new(),
+            open_blocks: HashSet::new(),
+            matched: false,
+        }
+    }
+
+    fn halted(&self) -> bool {
+        self.matched && self.guardrail.output_action == GuardrailAction::Halt
+    }
+
+    fn after_halt(&mut self, mut event: SseEvent) -> Option<SseEvent> {
+        match event.event.as_str() {
+            "content_block_start" | "content_block_delta" => None,
+            "content_block_stop" => {
+                let index = event.data["index"].as_i64().unwrap_or(0);
+                self.open_blocks.remove(&index).then_some(event)
+            }
+            "message_delta" => {
+                event.data["delta"]["stop_reason"] = json!(REFUSAL_STOP_REASON);
+                Some(event)
+            }
+            _ => Some(event),
+        }
+    }
+
+    fn scan(&mut self, mut event: SseEvent, field: &str) -> Option<SseEvent> {
+        let index = event.data["index"].as_i64().unwrap_or(0);
+        let delta = event.data["delta"][field]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let buffer = self.buffers.entry(index).or_default();
+        let previous = buffer.len();
+        buffer.push_str(&delta);
+
+        let mut from = previous.saturating_sub(SCAN_OVERLAP_BYTES);
+        while !buffer.is_char_boundary(from) {
+            from -= 1;
+        }
+        let Some((start, pattern)) = self.guardrail.find(&buffer[from..]) else {
+            return Some(event);
+        };
+        let start = from + start;
+        tracing::warn!(pattern, "模型输出命中护栏屏蔽规则");
+        self.flag.set(pattern);
+        self.matched = true;
+
+        if self.guardrail.output_action == GuardrailAction::Flag {
+            return Some(event);
+        }
+        // 保留本次增量中命中位置之前的部分
+        if start > previous {
+            event.data["delta"][field] = json!(delta[..start - previous].to_string());
+            Some(event)
+        } else {
+            None
+        }
+    }
+}
+
+impl EventStage for OutputGuard {
+    fn process(&mut self, event: SseEvent) -> Option<SseEvent> {
+        if self.halted() {
+            return self.after_halt(event);
+        }
+        let index = event.data["index"].as_i64().unwrap_or(0);
+        match event.event.as_str() {
+            "content_block_start" => {
+                self.open_blocks.insert(index);
+            }
+            "content_block_stop" => {
+                self.open_blocks.remove(&index);
+                self.buffers.remove(&index);
+            }
+            "content_block_delta" if !self.matched => {
+                let field = match event.data["delta"]["type"].as_str() {
+                    Some("text_delta") => "text",
+                    Some("thinking_delta") => "thinking",
+                    _ => return Some(event),
+                };
+                return self.scan(event, field);
+            }
+            _ => {}
+        }
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anthropic::pipeline::Stages;
+    use futures::{StreamExt, stream};
+
+    fn guardrail(action: GuardrailAction) -> Arc<Guardrail> {
+        Arc::new(Guardrail::new(&GuardrailConfig {
+            blocked_patterns: vec!["(?i)project falcon".to_string(), "(".to_string()],
+            scan_output: true,
+            output_action: action,
+        }))
+    }
+
+    fn text(index: i64, text: &str) -> SseEvent {
+        SseEvent::new(
+            "content_block_delta",
+            json!({"index": index, "delta": {"type": "text_delta", "text": text}}),
+        )
+    }
+
+    fn events() -> Vec<SseEvent> {
+        vec![
+            SseEvent::new("content_block_start", json!({"index": 0})),
+            text(0, "About Project "),
+            text(0, "Falcon: it is"),
+            text(0, " secret"),
+            SseEvent::new("content_block_stop", json!({"index": 0})),
+            SseEvent::new("content_block_start", json!({"index": 1})),
+            SseEvent::new("content_block_stop", json!({"index": 1})),
+            SseEvent::new(
+                "message_delta",
+                json!({"delta": {"stop_reason": "end_turn"}}),
+            ),
+            SseEvent::new("message_stop", json!({})),
+        ]
+    }
+
+    #[test]
+    fn test_check_request_scans_message_text() {
+        let guardrail = guardrail(GuardrailAction::Halt);
+        let blocked = json!({
+            "model": "project falcon",
+            "messages": [{"role": "user", "content": [{"type": "text", "text": "tell me about PROJECT FALCON"}]}]
+        });
+        let allowed =
+            json!({"model": "project falcon", "messages": [{"role": "user", "content": "hi"}]});
+
+        assert_eq!(
+            guardrail.check_request(&blocked),
+            Some("(?i)project falcon")
+        );
+        assert_eq!(guardrail.check_request(&allowed), None);
+    }
+
+    #[tokio::test]
+    async fn test_output_guard_halts_across_deltas() {
+        let flag = GuardrailFlag::default();
+        let stages = Stages::default().with(OutputGuard::new(
+            guardrail(GuardrailAction::Halt),
+            flag.clone(),
+        ));
+        let out: Vec<SseEvent> = stages.apply(stream::iter(events())).collect().await;
+
+        let kinds: Vec<&str> = out.iter().map(|e| e.event.as_str()).collect();
+        assert_eq!(
+            kinds,
+            [
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop"
+            ]
+        );
+        assert_eq!(out[1].data["delta"]["text"], "About Project ");
+        assert_eq!(out[3].data["delta"]["stop_reason"], REFUSAL_STOP_REASON);
+        assert_eq!(flag.get().as_deref(), Some("(?i)project falcon"));
+    }
+
+    #[tokio::test]
+    async fn test_output_guard_flag_keeps_output() {
+        let flag = GuardrailFlag::default();
+        let stages = Stages::default().with(OutputGuard::new(
+            guardrail(GuardrailAction::Flag),
+            flag.clone(),
+        ));
+        let out: Vec<SseEvent> = stages.apply(stream::iter(events())).collect().await;
+
+        assert_eq!(out.len(), events().len());
+        assert_eq!(out[7].data["delta"]["stop_reason"], "end_turn");
+        assert!(flag.get().is_some());
+    }
+
+    #[test]
+    fn test_check_message_truncates_on_halt() {
+        let mut message = json!({
+            "content": [
+                {"type": "text", "text": "Sure. project falcon is"},
+                {"type": "tool_use", "id": "t", "name": "x", "input": {}}
+            ],
+            "stop_reason": "tool_use"
+        });
+        let pattern = guardrail(GuardrailAction::Halt).check_message(&mut message);
+
+        assert_eq!(pattern.as_deref(), Some("(?i)project falcon"));
+        assert_eq!(
+            message["content"],
+            json!([{"type": "text", "text": "Sure. "}])
+        );
+        assert_eq!(message["stop_reason"], REFUSAL_STOP_REASON);
+    }
+}
@@ -1,104 +1,330 @@
 //! Anthropic API Handler 函数
 
 use std::convert::Infallible;
+use std::sync::Arc;
 
+use crate::common::headers;
+use crate::kiro::circuit::CircuitOpen;
 use crate::kiro::model::events::Event;
 use crate::kiro::model::requests::kiro::KiroRequest;
 use crate::kiro::parser::decoder::EventStreamDecoder;
+use crate::kiro::provider::{
+    ContextLengthExceeded, CredentialId, KiroProvider, ResponseTooLarge, UpstreamAttempts,
+};
+use crate::kiro::retry_budget;
+use crate::model::config::HedgingConfig;
 use crate::token;
+use crate::usage::UsageRecorder;
 use axum::{
-    Json as JsonExtractor,
+    Extension,
     body::Body,
     extract::State,
-    http::{StatusCode, header},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
     response::{IntoResponse, Json, Response},
 };
 use bytes::Bytes;
-use futures::{Stream, StreamExt, stream};
+use futures::{Stream, StreamExt, stream, stream::BoxStream};
+use parking_lot::Mutex;
 use serde_json::json;
-use std::time::Duration;
+use sha2::{Digest, Sha256};
+use std::time::{Duration, Instant};
 use tokio::time::interval;
-use uuid::Uuid;
 
+use super::aggregator::MessageAggregator;
+use super::coalesce::{CoalesceOptions, coalesce};
+use super::compaction;
 use super::converter::{ConversionError, convert_request};
-use super::middleware::AppState;
+use super::dedupe::{self, InflightRequests, Role};
+use super::extract::AnthropicJson;
+use super::guardrail::{Guardrail, OutputGuard};
+use super::middleware::{ApiKeyLabel, AppState};
+use super::moderation::{self, MODERATION_HEADER, ModerationStage, Moderator, Verdict};
+use super::pipeline::{AnthropicEncoder, EventStage, Stages, sse_stream};
+use super::preset;
+use super::reasoning::ReasoningFilter;
 use super::stream::{SseEvent, StreamContext};
 use super::types::{
-    CountTokensRequest, CountTokensResponse, ErrorResponse, MessagesRequest, Model, ModelsResponse,
+    CountTokensRequest, CountTokensResponse, ErrorResponse, MessagesRequest, ModelsResponse,
 };
 
 /// GET /v1/models
 ///
 /// 返回可用的模型列表
-pub async fn get_models() -> impl IntoResponse {
+pub async fn get_models(State(state): State<AppState>) -> impl IntoResponse {
     tracing::info!("Received GET /v1/models request");
 
-    let models = vec![
-        Model {
-            id: "claude-sonnet-4-5-20250929".to_string(),
-            object: "model".to_string(),
-            created: 1727568000,
-            owned_by: "anthropic".to_string(),
-            display_name: "Claude Sonnet 4.5".to_string(),
-            model_type: "chat".to_string(),
-            max_tokens: 32000,
-        },
-        Model {
-            id: "claude-opus-4-5-20251101".to_string(),
-            object: "model".to_string(),
-            created: 1730419200,
-            owned_by: "anthropic".to_string(),
-            display_name: "Claude Opus 4.5".to_string(),
-            model_type: "chat".to_string(),
-            max_tokens: 32000,
-        },
-        Model {
-            id: "claude-haiku-4-5-20251001".to_string(),
-            object: "model".to_string(),
-            created: 1727740800,
-            owned_by: "anthropic".to_string(),
-            display_name: "Claude Haiku 4.5".to_string(),
-            model_type: "chat".to_string(),
-            max_tokens: 32000,
-        },
-    ];
-
     Json(ModelsResponse {
         object: "list".to_string(),
-        data: models,
+        data: state.model_limits.load().models(),
     })
 }
 
+/// 已完成协议转换、可以直接发送到上游的请求
+pub(crate) struct PreparedRequest {
+    /// Kiro Provider
+    pub provider: Arc<KiroProvider>,
+    /// 序列化后的 Kiro 请求体
+    pub request_body: String,
+    /// 客户端请求的模型名称
+    pub model: String,
+    /// 估算的输入 tokens
+    pub input_tokens: i32,
+    /// 是否启用 thinking
+    pub thinking_enabled: bool,
+    /// 是否从响应中移除 thinking 块
+    pub strip_reasoning: bool,
+    /// 因超出上下文窗口被移除的历史消息数
+    pub truncated_messages: usize,
+    /// 模型的上下文窗口大小（用于错误提示）
+    pub context_window_tokens: u64,
+    /// 是否在响应头中返回性能指标
+    pub performance_headers: bool,
+    /// 流式增量合并参数（未启用时为 None）
+    pub coalesce: Option<CoalesceOptions>,
+    /// 请求开始处理的时间
+    pub started: Instant,
+    /// 上游调用的性能信息，在调用上游后填充
+    pub timings: Mutex<UpstreamTimings>,
+    /// 终端用户标识（OpenAI `user` / Anthropic `metadata.user_id`）
+    pub end_user: Option<String>,
+    /// 按白名单筛选后转发给上游的客户端请求头
+    pub forward_headers: HeaderMap,
+    /// 返回给客户端的上游响应头白名单
+    pub expose_headers: Vec<String>,
+    /// 检查模型输出的护栏（未启用输出检查时为 None）
+    pub guardrail: Option<Arc<Guardrail>>,
+    /// 审核非流式响应输出的外部审核接口（未启用输出审核时为 None）
+    pub moderator: Option<Arc<Moderator>>,
+    /// 按白名单筛选后的上游响应头，在调用上游后填充
+    pub upstream_headers: Mutex<HeaderMap>,
+    /// 进行中的请求表与本请求的键（未启用相同请求合并或为流式请求时为 None）
+    pub dedupe: Option<(Arc<InflightRequests>, String)>,
+    /// 非流式请求的对冲参数（未启用或为流式请求时为 None）
+    pub hedging: Option<HedgingConfig>,
+    /// 非流式请求缓冲上游响应的最大字节数
+    pub max_buffered_bytes: usize,
+}
+
+/// 上游调用的性能信息
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct UpstreamTimings {
+    /// 实际使用的凭据 ID
+    pub credential_id: Option<u64>,
+    /// 上游调用尝试次数（含故障转移）
+    pub attempts: u32,
+    /// 从开始处理到收到上游响应头的耗时
+    pub upstream_latency: Option<Duration>,
+    /// 从开始处理到收到第一块上游数据的耗时（仅非流式请求可在响应头中返回）
+    pub first_token: Option<Duration>,
+}
+
+impl PreparedRequest {
+    /// 记录上游响应的凭据与耗时（因响应异常重试时累加尝试次数）
+    fn record_upstream(&self, response: &reqwest::Response) {
+        let mut timings = self.timings.lock();
+        timings.credential_id = response.extensions().get::<CredentialId>().map(|c| c.0);
+        timings.attempts += response
+            .extensions()
+            .get::<UpstreamAttempts>()
+            .map(|a| a.0)
+            .unwrap_or(1);
+        timings.upstream_latency = Some(self.started.elapsed());
+        *self.upstream_headers.lock() =
+            headers::filter_headers(response.headers(), &self.expose_headers);
+    }
+
+    /// 记录收到第一块上游数据的时间（只记录一次）
+    fn record_first_token(&self) {
+        let mut timings = self.timings.lock();
+        if timings.first_token.is_none() {
+            timings.first_token = Some(self.started.elapsed());
+        }
+    }
+}
+
+/// 历史消息被压缩时返回的响应头，值为被移除的消息数
+pub(crate) const TRUNCATED_MESSAGES_HEADER: &str = "x-kiro-truncated-messages";
+
+/// 性能指标响应头
+const CREDENTIAL_INDEX_HEADER: &str = "x-kiro-credential-index";
+const UPSTREAM_LATENCY_HEADER: &str = "x-kiro-upstream-latency-ms";
+const FIRST_TOKEN_HEADER: &str = "x-kiro-first-token-ms";
+const RETRY_COUNT_HEADER: &str = "x-kiro-retry-count";
+
+/// 附加与请求处理过程相关的响应头
+///
+/// - 历史消息被压缩过时附加 `x-kiro-truncated-messages`
+/// - 启用 `performanceHeaders` 时附加凭据、上游耗时、首 token 耗时与重试次数
+/// - 附加 `exposeResponseHeaders` 白名单中的上游响应头
+pub(crate) fn with_response_headers(
+    mut response: Response,
+    prepared: &PreparedRequest,
+) -> Response {
+    let headers = response.headers_mut();
+    headers::merge_missing(headers, &prepared.upstream_headers.lock());
+    if prepared.truncated_messages > 0 {
+        headers.insert(
+            TRUNCATED_MESSAGES_HEADER,
+            HeaderValue::from(prepared.truncated_messages),
+        );
+    }
+
+    if prepared.performance_headers {
+        let timings = *prepared.timings.lock();
+        if let Some(id) = timings.credential_id {
+            headers.insert(CREDENTIAL_INDEX_HEADER, HeaderValue::from(id));
+        }
+        if let Some(latency) = timings.upstream_latency {
+            headers.insert(
+                UPSTREAM_LATENCY_HEADER,
+                HeaderValue::from(latency.as_millis() as u64),
+            );
+        }
+        if let Some(first_token) = timings.first_token {
+            headers.insert(
+                FIRST_TOKEN_HEADER,
+                HeaderValue::from(first_token.as_millis() as u64),
+            );
+        }
+        if timings.attempts > 0 {
+            headers.insert(RETRY_COUNT_HEADER, HeaderValue::from(timings.attempts - 1));
+        }
+    }
+    response
+}
+
+/// Handler 内部错误（HTTP 状态码 + Anthropic 错误体）
+///
+/// 由各协议的 handler 自行转换为对应格式的错误响应
+pub(crate) type HandlerError = (StatusCode, ErrorResponse);
+
+/// 将 HandlerError 转换为 Anthropic 格式的响应
+fn error_response((status, error): HandlerError) -> Response {
+    (status, Json(error)).into_response()
+}
+
 /// POST /v1/messages
 ///
 /// 创建消息（对话）
 pub async fn post_messages(
     State(state): State<AppState>,
-    JsonExtractor(payload): JsonExtractor<MessagesRequest>,
+    Extension(api_key): Extension<ApiKeyLabel>,
+    request_headers: HeaderMap,
+    AnthropicJson(payload): AnthropicJson<MessagesRequest>,
 ) -> Response {
     tracing::info!(
         model = %payload.model,
         max_tokens = %payload.max_tokens,
+        temperature = ?payload.temperature,
         stream = %payload.stream,
         message_count = %payload.messages.len(),
         "Received POST /v1/messages request"
     );
+
+    let stream = payload.stream;
+    let mut usage = UsageRecorder::new(
+        state.usage_store.clone(),
+        "/v1/messages",
+        api_key.0,
+        &payload.model,
+        stream,
+    )
+    .with_rate_limiter(state.rate_limiter.clone());
+    let prepared = match prepare_request(&state, payload, &request_headers) {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            usage.set_status(e.0.as_u16());
+            return error_response(e);
+        }
+    };
+    retry_budget::budget().record_request();
+
+    let response = if stream {
+        // 流式响应
+        match open_event_stream(&prepared, usage).await {
+            Ok(events) => sse_stream(events, AnthropicEncoder),
+            Err(e) => error_response(e),
+        }
+    } else {
+        // 非流式响应
+        match aggregate_message(&prepared, &mut usage).await {
+            Ok(message) => (StatusCode::OK, Json(message)).into_response(),
+            Err(e) => error_response(e),
+        }
+    };
+    with_response_headers(response, &prepared)
+}
+
+/// 转换 Anthropic 请求并构建 Kiro 请求体
+pub(crate) fn prepare_request(
+    state: &AppState,
+    mut payload: MessagesRequest,
+    request_headers: &HeaderMap,
+) -> Result<PreparedRequest, HandlerError> {
+    let config = state.config.load();
+
+    // 应用服务端预设（可能替换模型、补充系统提示词）
+    preset::apply(&config.presets, &mut payload, request_headers).map_err(|message| {
+        tracing::warn!("应用预设失败: {}", message);
+        (
+            StatusCode::BAD_REQUEST,
+            ErrorResponse::new("invalid_request_error", message),
+        )
+    })?;
+
+    // 在选择凭据、调用上游之前先拒绝明显无效的请求
+    validate_request(&payload).map_err(|message| {
+        tracing::warn!("请求校验失败: {}", message);
+        (
+            StatusCode::BAD_REQUEST,
+            ErrorResponse::new("invalid_request_error", message),
+        )
+    })?;
+
     // 检查 KiroProvider 是否可用
     let provider = match &state.kiro_provider {
         Some(p) => p.clone(),
         None => {
             tracing::error!("KiroProvider 未配置");
-            return (
+            return Err((
                 StatusCode::SERVICE_UNAVAILABLE,
-                Json(ErrorResponse::new(
-                    "service_unavailable",
-                    "Kiro API provider not configured",
-                )),
-            )
-                .into_response();
+                ErrorResponse::new("service_unavailable", "Kiro API provider not configured"),
+            ));
         }
     };
 
+    let end_user = payload
+        .metadata
+        .as_ref()
+        .and_then(|m| m.user_id.clone())
+        .filter(|user| !user.is_empty());
+    let mut forward_headers =
+        headers::filter_headers(request_headers, &config.forward_request_headers);
+    if config.forward_end_user_hash
+        && let Some(user) = &end_user
+    {
+        forward_headers.insert(END_USER_HEADER, hash_end_user(user));
+    }
+
+    // 超出模型的最大输出 tokens 时截断到上限
+    let limits = state.model_limits.load().get(&payload.model);
+    if payload.max_tokens > limits.max_output_tokens {
+        tracing::debug!(
+            "max_tokens {} 超出模型 {} 的上限，截断为 {}",
+            payload.max_tokens,
+            payload.model,
+            limits.max_output_tokens
+        );
+        payload.max_tokens = limits.max_output_tokens;
+    }
+
+    // 超出上下文窗口时按配置压缩历史消息
+    let truncated_messages = compaction::compact(
+        &mut payload,
+        config.compaction_strategy,
+        limits.context_window,
+    );
+
     // 转换请求
     let conversion_result = match convert_request(&payload) {
         Ok(result) => result,
@@ -112,14 +338,29 @@ pub async fn post_messages(
                 }
             };
             tracing::warn!("请求转换失败: {}", e);
-            return (
+            return Err((
                 StatusCode::BAD_REQUEST,
-                Json(ErrorResponse::new(error_type, message)),
-            )
-                .into_response();
+                ErrorResponse::new(error_type, message),
+            ));
         }
     };
 
+    // 会话 ID 每次随机生成，请求键只取决定上游输出的部分
+    let dedupe = if config.dedupe_concurrent_requests && !payload.stream {
+        let conversation = &conversion_result.conversation_state;
+        let key = dedupe::request_key(&[
+            &payload.model,
+            &payload.max_tokens.to_string(),
+            &serde_json::to_string(&conversation.current_message).unwrap_or_default(),
+            &serde_json::to_string(&conversation.history).unwrap_or_default(),
+        ]);
+        Some((state.inflight.clone(), key))
+    } else {
+        None
+    };
+
+    let hedging = config.hedging.clone().filter(|_| !payload.stream);
+
     // 构建 Kiro 请求
     let kiro_request = KiroRequest {
         conversation_state: conversion_result.conversation_state,
@@ -130,19 +371,22 @@ pub async fn post_messages(
         Ok(body) => body,
         Err(e) => {
             tracing::error!("序列化请求失败: {}", e);
-            return (
+            return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse::new(
-                    "internal_error",
-                    format!("序列化请求失败: {}", e),
-                )),
-            )
-                .into_response();
+                ErrorResponse::new("internal_error", format!("序列化请求失败: {}", e)),
+            ));
         }
     };
 
     tracing::debug!("Kiro request body: {}", request_body);
 
+    // 检查是否启用了thinking
+    let thinking_enabled = payload
+        .thinking
+        .as_ref()
+        .map(|t| t.thinking_type == "enabled")
+        .unwrap_or(false);
+
     // 估算输入 tokens
     let input_tokens = token::count_all_tokens(
         payload.model.clone(),
@@ -151,95 +395,304 @@ pub async fn post_messages(
         payload.tools,
     ) as i32;
 
-    // 检查是否启用了thinking
-    let thinking_enabled = payload
-        .thinking
-        .as_ref()
-        .map(|t| t.thinking_type == "enabled")
-        .unwrap_or(false);
+    Ok(PreparedRequest {
+        provider,
+        request_body,
+        model: payload.model,
+        input_tokens,
+        thinking_enabled,
+        strip_reasoning: config.strip_reasoning,
+        truncated_messages,
+        context_window_tokens: limits.context_window,
+        performance_headers: config.performance_headers,
+        coalesce: CoalesceOptions::from_config(&config),
+        started: Instant::now(),
+        timings: Mutex::new(UpstreamTimings::default()),
+        end_user,
+        forward_headers,
+        expose_headers: config.expose_response_headers.clone(),
+        guardrail: Some(state.guardrail.clone()).filter(|g| g.scans_output()),
+        moderator: state.moderator.clone().filter(|m| m.scans_output()),
+        upstream_headers: Mutex::new(HeaderMap::new()),
+        dedupe,
+        hedging,
+        max_buffered_bytes: config.max_buffered_response_bytes,
+    })
+}
 
-    if payload.stream {
-        // 流式响应
-        handle_stream_request(
-            provider,
-            &request_body,
-            &payload.model,
-            input_tokens,
-            thinking_enabled,
-        )
-        .await
-    } else {
-        // 非流式响应
-        handle_non_stream_request(provider, &request_body, &payload.model, input_tokens).await
+/// 校验请求的基本结构（模型、max_tokens、消息角色与内容格式）
+fn validate_request(payload: &MessagesRequest) -> Result<(), String> {
+    if payload.model.trim().is_empty() {
+        return Err("model: 不能为空".to_string());
     }
+    if payload.max_tokens <= 0 {
+        return Err(format!(
+            "max_tokens: 必须大于 0，当前为 {}",
+            payload.max_tokens
+        ));
+    }
+    if payload.messages.is_empty() {
+        return Err("messages: 至少需要一条消息".to_string());
+    }
+    for (i, message) in payload.messages.iter().enumerate() {
+        if message.role != "user" && message.role != "assistant" {
+            return Err(format!(
+                "messages.{}.role: 必须为 user 或 assistant，当前为 {}",
+                i, message.role
+            ));
+        }
+        if !message.content.is_string() && !message.content.is_array() {
+            return Err(format!("messages.{}.content: 必须为字符串或内容块数组", i));
+        }
+    }
+    Ok(())
 }
 
-/// 处理流式请求
-async fn handle_stream_request(
-    provider: std::sync::Arc<crate::kiro::provider::KiroProvider>,
-    request_body: &str,
-    model: &str,
-    input_tokens: i32,
-    thinking_enabled: bool,
-) -> Response {
-    // 调用 Kiro API（支持多凭据故障转移）
-    let response = match provider.call_api_stream(request_body).await {
-        Ok(resp) => resp,
-        Err(e) => {
-            tracing::error!("Kiro API 调用失败: {}", e);
-            return (
-                StatusCode::BAD_GATEWAY,
-                Json(ErrorResponse::new(
-                    "api_error",
-                    format!("上游 API 调用失败: {}", e),
-                )),
-            )
-                .into_response();
-        }
-    };
+/// 转发给上游的终端用户哈希请求头
+const END_USER_HEADER: &str = "x-kiro-end-user";
 
-    // 创建流处理上下文
-    let mut ctx = StreamContext::new_with_thinking(model, input_tokens, thinking_enabled);
+/// 终端用户标识的 SHA-256 哈希（十六进制），避免将原始标识发往上游
+fn hash_end_user(user: &str) -> HeaderValue {
+    let digest = Sha256::digest(user.as_bytes());
+    HeaderValue::from_str(&hex::encode(digest)).expect("十六进制字符串是合法的请求头值")
+}
 
-    // 生成初始事件
-    let initial_events = ctx.generate_initial_events();
+/// 将上游调用失败映射为 HandlerError
+///
+/// 输入过长返回 400 并附带估算的输入 tokens 与上下文窗口大小，其他错误返回 502
+fn upstream_error(prepared: &PreparedRequest, e: anyhow::Error) -> HandlerError {
+    if e.downcast_ref::<ContextLengthExceeded>().is_some() {
+        tracing::warn!(
+            "输入超出上下文长度: 约 {} tokens，窗口 {}",
+            prepared.input_tokens,
+            prepared.context_window_tokens
+        );
+        return (
+            StatusCode::BAD_REQUEST,
+            ErrorResponse::context_length_exceeded(
+                prepared.input_tokens,
+                prepared.context_window_tokens,
+            ),
+        );
+    }
+
+    if let Some(open) = e.downcast_ref::<CircuitOpen>() {
+        tracing::warn!("上游熔断中，拒绝请求");
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            ErrorResponse::new("overloaded_error", open.to_string()),
+        );
+    }
 
-    // 创建 SSE 流
-    let stream = create_sse_stream(response, ctx, initial_events);
+    if let Some(too_large) = e.downcast_ref::<ResponseTooLarge>() {
+        tracing::warn!(model = %prepared.model, "{}", too_large);
+        return (
+            StatusCode::BAD_GATEWAY,
+            ErrorResponse::new("api_error", too_large.to_string()),
+        );
+    }
+
+    tracing::error!(error = %e, "Kiro API 调用失败");
+    (
+        StatusCode::BAD_GATEWAY,
+        ErrorResponse::new("api_error", format!("上游 API 调用失败: {}", e)),
+    )
+}
 
-    // 返回 SSE 响应
+/// 构建 SSE 响应
+///
+/// 不设置 `Connection` 头：HTTP/1.1 默认保持连接，而 HTTP/2 禁止连接级头部。
+pub(crate) fn sse_response(
+    stream: impl Stream<Item = Result<Bytes, Infallible>> + Send + 'static,
+) -> Response {
     Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "text/event-stream")
         .header(header::CACHE_CONTROL, "no-cache")
-        .header(header::CONNECTION, "keep-alive")
         .body(Body::from_stream(stream))
         .unwrap()
 }
 
+/// 调用上游流式 API，返回 Anthropic SSE 事件流
+///
+/// 事件流以 message_start 开始、message_stop 结束，期间每 25 秒插入一个 ping 事件。
+/// `usage` 随事件流一起移动，流结束或客户端断开时写入用量记录。
+/// 客户端断开时事件流被丢弃，其持有的上游响应体随之释放并关闭连接，上游生成立即中止。
+pub(crate) async fn open_event_stream(
+    prepared: &PreparedRequest,
+    mut usage: UsageRecorder,
+) -> Result<impl Stream<Item = SseEvent> + Send + 'static, HandlerError> {
+    let body = call_upstream_stream(prepared, &mut usage).await?;
+
+    // 上游没有返回任何内容事件就结束时换一个凭据重试一次（已发送给客户端的内容无法重试）
+    let body = match wait_for_content(body).await {
+        Some(body) => body,
+        None => {
+            retry_on_next_credential(prepared, &mut usage, "上游返回空响应")?;
+            call_upstream_stream(prepared, &mut usage).await?
+        }
+    };
+
+    // 创建流处理上下文
+    let mut ctx = StreamContext::new_with_thinking(
+        &prepared.model,
+        prepared.input_tokens,
+        prepared.thinking_enabled,
+    );
+    usage.set_user(prepared.end_user.clone());
+
+    // 生成初始事件
+    let initial_events = ctx.generate_initial_events();
+
+    let mut stages = Stages::default().with(ReasoningFilter::new(prepared.strip_reasoning));
+    if let Some(guardrail) = &prepared.guardrail {
+        stages = stages.with(OutputGuard::new(guardrail.clone(), usage.guardrail_flag()));
+    }
+    let stages = stages
+        .with(UsageStage(usage))
+        .with(TraceStage::new(&prepared.model));
+    let events = stages.apply(create_event_stream(body, ctx, initial_events));
+    Ok(coalesce(events, prepared.coalesce))
+}
+
+/// 读取上游事件流直到收到内容事件，返回已读取的数据接上剩余部分的字节流
+///
+/// 流结束时仍没有内容事件（只有计费、上下文使用率等元数据帧或完全为空）时返回 None；
+/// 收到错误或异常事件、读取或解码出错时不再等待，交给后续流程处理
+async fn wait_for_content(
+    mut body: BoxStream<'static, reqwest::Result<Bytes>>,
+) -> Option<BoxStream<'static, reqwest::Result<Bytes>>> {
+    let mut decoder = EventStreamDecoder::new();
+    let mut read = Vec::new();
+    while let Some(chunk) = body.next().await {
+        let settled = match &chunk {
+            Ok(bytes) => {
+                decoder.feed(bytes.clone()).is_err()
+                    || decoder.decode_iter().any(|frame| {
+                        frame.and_then(Event::from_frame).map_or(true, |event| {
+                            event.is_content()
+                                || matches!(event, Event::Error { .. } | Event::Exception { .. })
+                        })
+                    })
+            }
+            Err(_) => true,
+        };
+        read.push(chunk);
+        if settled {
+            return Some(stream::iter(read).chain(body).boxed());
+        }
+    }
+    None
+}
+
+/// 发送流式上游请求，返回响应体字节流
+async fn call_upstream_stream(
+    prepared: &PreparedRequest,
+    usage: &mut UsageRecorder,
+) -> Result<BoxStream<'static, reqwest::Result<Bytes>>, HandlerError> {
+    // 调用 Kiro API（支持多凭据故障转移）
+    let response = match prepared
+        .provider
+        .call_api_stream(
+            &prepared.model,
+            &prepared.request_body,
+            &prepared.forward_headers,
+        )
+        .await
+    {
+        Ok(resp) => resp,
+        Err(e) => {
+            let error = upstream_error(prepared, e);
+            usage.set_status(error.0.as_u16());
+            return Err(error);
+        }
+    };
+
+    prepared.record_upstream(&response);
+    usage.set_credential_id(response.extensions().get::<CredentialId>().map(|c| c.0));
+    Ok(response.bytes_stream().boxed())
+}
+
+/// 从流式事件中采集用量：message_delta 携带最终 token 数，message_stop 表示正常完成
+///
+/// 采集器随事件流一起存活，流结束或客户端断开时写入用量记录
+struct UsageStage(UsageRecorder);
+
+impl EventStage for UsageStage {
+    fn process(&mut self, event: SseEvent) -> Option<SseEvent> {
+        match event.event.as_str() {
+            "message_delta" => self.0.add_tokens(
+                event.data["usage"]["input_tokens"].as_i64().unwrap_or(0) as i32,
+                event.data["usage"]["output_tokens"].as_i64().unwrap_or(0) as i32,
+            ),
+            "content_block_delta" => self.0.mark_first_token(),
+            "message_stop" => self.0.set_status(StatusCode::OK.as_u16()),
+            _ => {}
+        }
+        Some(event)
+    }
+}
+
+/// 流式响应的链路追踪 span，随事件流结束（或客户端断开）而结束
+struct TraceStage {
+    span: tracing::Span,
+    events: u64,
+}
+
+impl TraceStage {
+    fn new(model: &str) -> Self {
+        Self {
+            span: tracing::info_span!(
+                "stream",
+                model,
+                events = tracing::field::Empty,
+                output_tokens = tracing::field::Empty,
+                stop_reason = tracing::field::Empty,
+            ),
+            events: 0,
+        }
+    }
+}
+
+impl EventStage for TraceStage {
+    fn process(&mut self, event: SseEvent) -> Option<SseEvent> {
+        self.events += 1;
+        if event.event == "message_delta" {
+            if let Some(tokens) = event.data["usage"]["output_tokens"].as_i64() {
+                self.span.record("output_tokens", tokens);
+            }
+            if let Some(reason) = event.data["delta"]["stop_reason"].as_str() {
+                self.span.record("stop_reason", reason);
+            }
+        }
+        Some(event)
+    }
+}
+
+impl Drop for TraceStage {
+    fn drop(&mut self) {
+        self.span.record("events", self.events);
+    }
+}
+
 /// Ping 事件间隔（25秒）
 const PING_INTERVAL_SECS: u64 = 25;
 
-/// 创建 ping 事件的 SSE 字符串
-fn create_ping_sse() -> Bytes {
-    Bytes::from("event: ping\ndata: {\"type\": \"ping\"}\n\n")
+/// 创建 ping 事件
+fn create_ping_event() -> SseEvent {
+    SseEvent::new("ping", json!({ "type": "ping" }))
 }
 
 /// 创建 SSE 事件流
-fn create_sse_stream(
-    response: reqwest::Response,
+fn create_event_stream(
+    body_stream: BoxStream<'static, reqwest::Result<Bytes>>,
     ctx: StreamContext,
     initial_events: Vec<SseEvent>,
-) -> impl Stream<Item = Result<Bytes, Infallible>> {
+) -> impl Stream<Item = SseEvent> {
     // 先发送初始事件
-    let initial_stream = stream::iter(
-        initial_events
-            .into_iter()
-            .map(|e| Ok(Bytes::from(e.to_sse_string()))),
-    );
+    let initial_stream = stream::iter(initial_events);
 
     // 然后处理 Kiro 响应流，同时每25秒发送 ping 保活
-    let body_stream = response.bytes_stream();
 
     let processing_stream = stream::unfold(
         (body_stream, ctx, EventStreamDecoder::new(), false, interval(Duration::from_secs(PING_INTERVAL_SECS))),
@@ -255,7 +708,7 @@ fn create_sse_stream(
                     match chunk_result {
                         Some(Ok(chunk)) => {
                             // 解码事件
-                            if let Err(e) = decoder.feed(&chunk) {
+                            if let Err(e) = decoder.feed(chunk) {
                                 tracing::warn!("缓冲区溢出: {}", e);
                             }
 
@@ -274,40 +727,25 @@ fn create_sse_stream(
                                 }
                             }
 
-                            // 转换为 SSE 字节流
-                            let bytes: Vec<Result<Bytes, Infallible>> = events
-                                .into_iter()
-                                .map(|e| Ok(Bytes::from(e.to_sse_string())))
-                                .collect();
-
-                            Some((stream::iter(bytes), (body_stream, ctx, decoder, false, ping_interval)))
+                            Some((stream::iter(events), (body_stream, ctx, decoder, false, ping_interval)))
                         }
                         Some(Err(e)) => {
                             tracing::error!("读取响应流失败: {}", e);
                             // 发送最终事件并结束
                             let final_events = ctx.generate_final_events();
-                            let bytes: Vec<Result<Bytes, Infallible>> = final_events
-                                .into_iter()
-                                .map(|e| Ok(Bytes::from(e.to_sse_string())))
-                                .collect();
-                            Some((stream::iter(bytes), (body_stream, ctx, decoder, true, ping_interval)))
+                            Some((stream::iter(final_events), (body_stream, ctx, decoder, true, ping_interval)))
                         }
                         None => {
                             // 流结束，发送最终事件
                             let final_events = ctx.generate_final_events();
-                            let bytes: Vec<Result<Bytes, Infallible>> = final_events
-                                .into_iter()
-                                .map(|e| Ok(Bytes::from(e.to_sse_string())))
-                                .collect();
-                            Some((stream::iter(bytes), (body_stream, ctx, decoder, true, ping_interval)))
+                            Some((stream::iter(final_events), (body_stream, ctx, decoder, true, ping_interval)))
                         }
                     }
                 }
                 // 发送 ping 保活
                 _ = ping_interval.tick() => {
                     tracing::trace!("发送 ping 保活事件");
-                    let bytes: Vec<Result<Bytes, Infallible>> = vec![Ok(create_ping_sse())];
-                    Some((stream::iter(bytes), (body_stream, ctx, decoder, false, ping_interval)))
+                    Some((stream::iter(vec![create_ping_event()]), (body_stream, ctx, decoder, false, ping_interval)))
                 }
             }
         },
@@ -317,160 +755,211 @@ fn create_sse_stream(
     initial_stream.chain(processing_stream)
 }
 
-/// 上下文窗口大小（200k tokens）
-const CONTEXT_WINDOW_SIZE: i32 = 200_000;
+/// 调用上游 API 并聚合为完整的 Anthropic 消息
+///
+/// 启用相同请求合并时，同时进行的相同请求只调用一次上游并共享结果。
+pub(crate) async fn aggregate_message(
+    prepared: &PreparedRequest,
+    usage: &mut UsageRecorder,
+) -> Result<serde_json::Value, HandlerError> {
+    let Some((inflight, key)) = &prepared.dedupe else {
+        return complete_message(prepared, usage).await;
+    };
 
-/// 处理非流式请求
-async fn handle_non_stream_request(
-    provider: std::sync::Arc<crate::kiro::provider::KiroProvider>,
-    request_body: &str,
-    model: &str,
-    input_tokens: i32,
-) -> Response {
-    // 调用 Kiro API（支持多凭据故障转移）
-    let response = match provider.call_api(request_body).await {
-        Ok(resp) => resp,
-        Err(e) => {
-            tracing::error!("Kiro API 调用失败: {}", e);
-            return (
+    match inflight.join(key) {
+        Role::Leader(leader) => {
+            let result = complete_message(prepared, usage)
+                .await
+                .map(|message| (message, prepared.upstream_headers.lock().clone()));
+            leader.finish(&result);
+            result.map(|(message, _)| message)
+        }
+        Role::Follower(slot) => {
+            tracing::info!(model = %prepared.model, "相同的请求正在进行，等待并共享其结果");
+            match dedupe::wait(slot).await {
+                Some(Ok((message, headers))) => {
+                    prepared.upstream_headers.lock().extend(headers);
+                    usage.set_status(StatusCode::OK.as_u16());
+                    Ok(message)
+                }
+                Some(Err(error)) => {
+                    usage.set_status(error.0.as_u16());
+                    Err(error)
+                }
+                // leader 被取消，自行调用上游
+                None => complete_message(prepared, usage).await,
+            }
+        }
+    }
+}
+
+/// 调用上游 API 并聚合为完整的 Anthropic 消息（不参与相同请求合并）
+///
+/// 上游返回空内容或被截断（收到错误事件、工具调用未结束）时，换一个凭据重试一次，
+/// 仍然异常则返回 502。
+pub(crate) async fn complete_message(
+    prepared: &PreparedRequest,
+    usage: &mut UsageRecorder,
+) -> Result<serde_json::Value, HandlerError> {
+    let (mut message, anomaly) = fetch_message(prepared, usage).await?;
+    if let Some(reason) = anomaly {
+        retry_on_next_credential(prepared, usage, reason)?;
+        let (retried, anomaly) = fetch_message(prepared, usage).await?;
+        if let Some(reason) = anomaly {
+            tracing::error!(model = %prepared.model, "重试后上游响应仍然异常: {}", reason);
+            usage.set_status(StatusCode::BAD_GATEWAY.as_u16());
+            return Err((
                 StatusCode::BAD_GATEWAY,
-                Json(ErrorResponse::new(
-                    "api_error",
-                    format!("上游 API 调用失败: {}", e),
-                )),
-            )
-                .into_response();
+                ErrorResponse::new("api_error", format!("上游响应异常: {}", reason)),
+            ));
         }
-    };
+        message = retried;
+    }
 
-    // 读取响应体
-    let body_bytes = match response.bytes().await {
-        Ok(bytes) => bytes,
+    if let Some(guardrail) = &prepared.guardrail
+        && let Some(pattern) = guardrail.check_message(&mut message)
+    {
+        usage.set_guardrail(&pattern);
+    }
+    if let Some(moderator) = &prepared.moderator {
+        let input = moderation::message_texts(&message);
+        let verdict = moderator
+            .check(ModerationStage::Output, None, &prepared.model, &input)
+            .await;
+        if verdict != Verdict::Allow {
+            tracing::warn!(model = %prepared.model, "模型输出审核结果: {}", verdict.describe());
+            usage.set_guardrail(&verdict.describe());
+            let annotation = if matches!(verdict, Verdict::Block { .. }) {
+                moderation::refuse_message(&mut message);
+                "blocked"
+            } else {
+                "flagged"
+            };
+            prepared
+                .upstream_headers
+                .lock()
+                .insert(MODERATION_HEADER, HeaderValue::from_static(annotation));
+        }
+    }
+    Ok(message)
+}
+
+/// 调用一次上游 API 并聚合为完整的 Anthropic 消息，同时返回响应异常的原因（如有）
+///
+/// 上游只返回事件流，这里逐块解码并复用 StreamContext 的解析逻辑，
+/// 最终由 MessageAggregator 折叠为完整的 Anthropic 消息。
+async fn fetch_message(
+    prepared: &PreparedRequest,
+    usage: &mut UsageRecorder,
+) -> Result<(serde_json::Value, Option<&'static str>), HandlerError> {
+    // 调用 Kiro API（支持多凭据故障转移）
+    let response = match prepared
+        .provider
+        .call_api_hedged(
+            &prepared.model,
+            &prepared.request_body,
+            &prepared.forward_headers,
+            prepared.hedging.as_ref(),
+            prepared.max_buffered_bytes,
+        )
+        .await
+    {
+        Ok(resp) => resp,
         Err(e) => {
-            tracing::error!("读取响应体失败: {}", e);
-            return (
-                StatusCode::BAD_GATEWAY,
-                Json(ErrorResponse::new(
-                    "api_error",
-                    format!("读取响应失败: {}", e),
-                )),
-            )
-                .into_response();
+            let error = upstream_error(prepared, e);
+            usage.set_status(error.0.as_u16());
+            return Err(error);
         }
     };
 
-    // 解析事件流
+    let mut ctx = StreamContext::new_with_thinking(
+        &prepared.model,
+        prepared.input_tokens,
+        prepared.thinking_enabled,
+    );
+    prepared.record_upstream(&response);
+    usage.set_credential_id(response.extensions().get::<CredentialId>().map(|c| c.0));
+    usage.set_user(prepared.end_user.clone());
+
+    let mut aggregator = MessageAggregator::new();
+    let mut filter = ReasoningFilter::new(prepared.strip_reasoning);
+    aggregator.extend(&filter.filter_all(ctx.generate_initial_events()));
+
+    // 逐块读取并解码事件流，避免一次性缓冲整个响应体；
+    // 聚合的消息随读取的数据增长，累计读取量超过缓冲上限时中止
     let mut decoder = EventStreamDecoder::new();
-    if let Err(e) = decoder.feed(&body_bytes) {
-        tracing::warn!("缓冲区溢出: {}", e);
-    }
+    let mut body_stream = response.bytes_stream();
+    let mut received = 0usize;
+    let mut has_content = false;
+    while let Some(chunk_result) = body_stream.next().await {
+        let chunk = match chunk_result {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                // 中途断流时返回错误，而不是返回不完整的消息
+                tracing::error!("读取响应体失败: {}", e);
+                usage.set_status(StatusCode::BAD_GATEWAY.as_u16());
+                return Err((
+                    StatusCode::BAD_GATEWAY,
+                    ErrorResponse::new("api_error", format!("读取响应失败: {}", e)),
+                ));
+            }
+        };
+        prepared.record_first_token();
+        usage.mark_first_token();
 
-    let mut text_content = String::new();
-    let mut tool_uses: Vec<serde_json::Value> = Vec::new();
-    let mut has_tool_use = false;
-    let mut stop_reason = "end_turn".to_string();
-    // 从 contextUsageEvent 计算的实际输入 tokens
-    let mut context_input_tokens: Option<i32> = None;
-
-    // 收集工具调用的增量 JSON
-    let mut tool_json_buffers: std::collections::HashMap<String, String> =
-        std::collections::HashMap::new();
-
-    for result in decoder.decode_iter() {
-        match result {
-            Ok(frame) => {
-                if let Ok(event) = Event::from_frame(frame) {
-                    match event {
-                        Event::AssistantResponse(resp) => {
-                            text_content.push_str(&resp.content);
-                        }
-                        Event::ToolUse(tool_use) => {
-                            has_tool_use = true;
-
-                            // 累积工具的 JSON 输入
-                            let buffer = tool_json_buffers
-                                .entry(tool_use.tool_use_id.clone())
-                                .or_insert_with(String::new);
-                            buffer.push_str(&tool_use.input);
-
-                            // 如果是完整的工具调用，添加到列表
-                            if tool_use.stop {
-                                let input: serde_json::Value = serde_json::from_str(buffer)
-                                    .unwrap_or_else(|e| {
-                                        tracing::warn!(
-                                            "工具输入 JSON 解析失败: {}, tool_use_id: {}, 原始内容: {}",
-                                            e, tool_use.tool_use_id, buffer
-                                        );
-                                        serde_json::json!({})
-                                    });
-
-                                tool_uses.push(json!({
-                                    "type": "tool_use",
-                                    "id": tool_use.tool_use_id,
-                                    "name": tool_use.name,
-                                    "input": input
-                                }));
-                            }
-                        }
-                        Event::ContextUsage(context_usage) => {
-                            // 从上下文使用百分比计算实际的 input_tokens
-                            // 公式: percentage * 200000 / 100 = percentage * 2000
-                            let actual_input_tokens = (context_usage.context_usage_percentage
-                                * (CONTEXT_WINDOW_SIZE as f64)
-                                / 100.0)
-                                as i32;
-                            context_input_tokens = Some(actual_input_tokens);
-                            tracing::debug!(
-                                "收到 contextUsageEvent: {}%, 计算 input_tokens: {}",
-                                context_usage.context_usage_percentage,
-                                actual_input_tokens
-                            );
-                        }
-                        Event::Exception { exception_type, .. } => {
-                            if exception_type == "ContentLengthExceededException" {
-                                stop_reason = "max_tokens".to_string();
-                            }
-                        }
-                        _ => {}
+        received += chunk.len();
+        if received > prepared.max_buffered_bytes {
+            let error = upstream_error(
+                prepared,
+                ResponseTooLarge {
+                    limit: prepared.max_buffered_bytes,
+                }
+                .into(),
+            );
+            usage.set_status(error.0.as_u16());
+            return Err(error);
+        }
+
+        if let Err(e) = decoder.feed(chunk) {
+            tracing::warn!("缓冲区溢出: {}", e);
+        }
+
+        for result in decoder.decode_iter() {
+            match result {
+                Ok(frame) => {
+                    if let Ok(event) = Event::from_frame(frame) {
+                        has_content |= event.is_content();
+                        aggregator.extend(&filter.filter_all(ctx.process_kiro_event(&event)));
                     }
                 }
-            }
-            Err(e) => {
-                tracing::warn!("解码事件失败: {}", e);
+                Err(e) => {
+                    tracing::warn!("解码事件失败: {}", e);
+                }
             }
         }
     }
 
-    // 确定 stop_reason
-    if has_tool_use && stop_reason == "end_turn" {
-        stop_reason = "tool_use".to_string();
-    }
-
-    // 构建响应内容
-    let mut content: Vec<serde_json::Value> = Vec::new();
-
-    if !text_content.is_empty() {
-        content.push(json!({
-            "type": "text",
-            "text": text_content
-        }));
-    }
+    let truncation = ctx.truncation();
+    aggregator.extend(&filter.filter_all(ctx.generate_final_events()));
 
-    content.extend(tool_uses);
+    let content = aggregator.content();
 
     // 估算输出 tokens
     let output_tokens = token::estimate_output_tokens(&content);
 
-    // 使用从 contextUsageEvent 计算的 input_tokens，如果没有则使用估算值
-    let final_input_tokens = context_input_tokens.unwrap_or(input_tokens);
+    // message_delta 中的 input_tokens 已优先使用 contextUsageEvent 的计算值
+    let final_input_tokens = aggregator.input_tokens().unwrap_or(prepared.input_tokens);
+    let stop_reason = aggregator.stop_reason().unwrap_or("end_turn");
+    usage.add_tokens(final_input_tokens, output_tokens);
+    usage.set_status(StatusCode::OK.as_u16());
 
     // 构建 Anthropic 响应
-    let response_body = json!({
-        "id": format!("msg_{}", Uuid::new_v4().to_string().replace('-', "")),
+    let message = json!({
+        "id": ctx.message_id,
         "type": "message",
         "role": "assistant",
         "content": content,
-        "model": model,
+        "model": prepared.model,
         "stop_reason": stop_reason,
         "stop_sequence": null,
         "usage": {
@@ -478,15 +967,44 @@ async fn handle_non_stream_request(
             "output_tokens": output_tokens
         }
     });
+    let anomaly = truncation.or((!has_content).then_some("上游返回空内容"));
+    Ok((message, anomaly))
+}
 
-    (StatusCode::OK, Json(response_body)).into_response()
+/// 上游响应异常时准备重试：当前凭据仍是刚才使用的凭据时切换到下一个
+///
+/// 重试消耗全局重试预算，预算耗尽时返回 502，不再重试
+fn retry_on_next_credential(
+    prepared: &PreparedRequest,
+    usage: &mut UsageRecorder,
+    reason: &str,
+) -> Result<(), HandlerError> {
+    if !retry_budget::budget().try_acquire() {
+        usage.set_status(StatusCode::BAD_GATEWAY.as_u16());
+        return Err((
+            StatusCode::BAD_GATEWAY,
+            ErrorResponse::new("api_error", format!("上游响应异常: {}", reason)),
+        ));
+    }
+    let token_manager = prepared.provider.token_manager();
+    let used = prepared.timings.lock().credential_id;
+    let switched =
+        used == Some(token_manager.snapshot().current_id) && token_manager.switch_to_next();
+    tracing::warn!(
+        model = %prepared.model,
+        credential_id = ?used,
+        switched,
+        "上游响应异常（{}），重试一次",
+        reason
+    );
+    Ok(())
 }
 
 /// POST /v1/messages/count_tokens
 ///
 /// 计算消息的 token 数量
 pub async fn count_tokens(
-    JsonExtractor(payload): JsonExtractor<CountTokensRequest>,
+    AnthropicJson(payload): AnthropicJson<CountTokensRequest>,
 ) -> impl IntoResponse {
     tracing::info!(
         model = %payload.model,
@@ -505,3 +1023,48 @@ pub async fn count_tokens(
         input_tokens: total_tokens.max(1) as i32,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::parser::frame::encode_frame;
+
+    fn frame(event_type: &str, payload: &[u8]) -> reqwest::Result<Bytes> {
+        Ok(Bytes::from(encode_frame(
+            &[
+                (":message-type", "event"),
+                (":event-type", event_type),
+                (":content-type", "application/json"),
+            ],
+            payload,
+        )))
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_content_treats_metadata_only_streams_as_empty() {
+        let metadata = || {
+            vec![
+                frame("meteringEvent", b"{}"),
+                frame("contextUsageEvent", br#"{"contextUsagePercentage":1.5}"#),
+            ]
+        };
+        assert!(
+            wait_for_content(stream::iter(metadata()).boxed())
+                .await
+                .is_none()
+        );
+        assert!(wait_for_content(stream::empty().boxed()).await.is_none());
+
+        let mut chunks = metadata();
+        chunks.push(frame("assistantResponseEvent", br#"{"content":"hi"}"#));
+        let expected: Vec<u8> = chunks
+            .iter()
+            .flat_map(|chunk| chunk.as_ref().unwrap().to_vec())
+            .collect();
+        let body = wait_for_content(stream::iter(chunks).boxed())
+            .await
+            .unwrap();
+        let read: Vec<u8> = body.map(|chunk| chunk.unwrap().to_vec()).concat().await;
+        assert_eq!(read, expected);
+    }
+}
@@ -0,0 +1,241 @@
+//! 健康检查端点
+//!
+//! - `GET /health`、`GET /livez`：存活检查，进程能处理请求即返回 200
+//! - `GET /readyz`：就绪检查，启动预热已完成、已加载凭据、至少一个凭据未被禁用且实例未在排空（收到停止信号）时返回 200，
+//!   供 Kubernetes 在凭据池全部禁用或实例即将退出时停止分配流量
+//! - `GET /health/deep`：深度检查，确认至少有一个凭据能取得有效 Token（必要时刷新），
+//!   且上游端点网络可达；结果缓存 [`DEEP_CHECK_TTL`]，避免负载均衡器频繁探测放大到上游
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use super::middleware::AppState;
+
+/// 实例是否正在排空（收到停止信号后不再就绪）
+static DRAINING: AtomicBool = AtomicBool::new(false);
+
+/// 标记实例开始排空，之后 `/readyz` 返回 503
+pub fn start_draining() {
+    DRAINING.store(true, Ordering::Relaxed);
+}
+
+/// 启动预热是否仍在进行（期间不就绪）
+static WARMING_UP: AtomicBool = AtomicBool::new(false);
+
+/// 标记启动预热开始或结束，进行中 `/readyz` 返回 503
+pub fn set_warming_up(warming_up: bool) {
+    WARMING_UP.store(warming_up, Ordering::Relaxed);
+}
+
+/// 深度检查结果的缓存时长
+pub const DEEP_CHECK_TTL: Duration = Duration::from_secs(30);
+
+/// 上游连通性探测的超时时间
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 深度检查结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeepHealth {
+    /// `ok` 或 `unavailable`
+    pub status: &'static str,
+    pub credential: CheckResult,
+    pub upstream: CheckResult,
+    /// 检查时间
+    pub checked_at: DateTime<Utc>,
+}
+
+/// 单项检查结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckResult {
+    pub ok: bool,
+    /// 检查耗时（毫秒）
+    pub latency_ms: u64,
+    /// 取得有效 Token 的凭据 ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_id: Option<u64>,
+    /// 失败原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DeepHealth {
+    fn healthy(&self) -> bool {
+        self.credential.ok && self.upstream.ok
+    }
+}
+
+/// 深度检查结果缓存（检查期间持有锁，合并并发的探测）
+#[derive(Default)]
+pub struct HealthCache(Mutex<Option<(Instant, DeepHealth)>>);
+
+/// GET /health、GET /livez
+pub async fn get_health() -> impl IntoResponse {
+    Json(json!({ "status": "ok" }))
+}
+
+/// GET /readyz
+pub async fn get_readyz(State(state): State<AppState>) -> impl IntoResponse {
+    let (total, available) = state
+        .kiro_provider
+        .as_ref()
+        .map(|p| {
+            (
+                p.token_manager().total_count(),
+                p.token_manager().available_count(),
+            )
+        })
+        .unwrap_or((0, 0));
+    let reason = if DRAINING.load(Ordering::Relaxed) {
+        Some("draining")
+    } else if WARMING_UP.load(Ordering::Relaxed) {
+        Some("warming up")
+    } else if total == 0 {
+        Some("no credentials loaded")
+    } else if available == 0 {
+        Some("all credentials disabled")
+    } else {
+        None
+    };
+
+    let status = if reason.is_some() {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    (
+        status,
+        Json(json!({
+            "status": if reason.is_some() { "unavailable" } else { "ok" },
+            "reason": reason,
+            "credentials": { "total": total, "available": available },
+        })),
+    )
+}
+
+/// GET /health/deep
+pub async fn get_deep_health(State(state): State<AppState>) -> impl IntoResponse {
+    let health = {
+        let mut cached = state.health.0.lock().await;
+        match &*cached {
+            Some((at, health)) if at.elapsed() < DEEP_CHECK_TTL => health.clone(),
+            _ => {
+                let health = check(&state).await;
+                if !health.healthy() {
+                    tracing::warn!(
+                        credential_error = health.credential.error.as_deref(),
+                        upstream_error = health.upstream.error.as_deref(),
+                        "深度健康检查失败"
+                    );
+                }
+                *cached = Some((Instant::now(), health.clone()));
+                health
+            }
+        }
+    };
+
+    let status = if health.healthy() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(health))
+}
+
+async fn check(state: &AppState) -> DeepHealth {
+    let Some(provider) = &state.kiro_provider else {
+        let missing = CheckResult {
+            ok: false,
+            latency_ms: 0,
+            credential_id: None,
+            error: Some("KiroProvider 未配置".to_string()),
+        };
+        return DeepHealth {
+            status: "unavailable",
+            credential: missing.clone(),
+            upstream: missing,
+            checked_at: Utc::now(),
+        };
+    };
+
+    let started = Instant::now();
+    let credential = match provider.token_manager().acquire_context().await {
+        Ok(ctx) => CheckResult {
+            ok: true,
+            latency_ms: started.elapsed().as_millis() as u64,
+            credential_id: Some(ctx.id),
+            error: None,
+        },
+        Err(e) => CheckResult {
+            ok: false,
+            latency_ms: started.elapsed().as_millis() as u64,
+            credential_id: None,
+            error: Some(e.to_string()),
+        },
+    };
+
+    let started = Instant::now();
+    let upstream = match provider.probe_upstream(PROBE_TIMEOUT).await {
+        Ok(()) => CheckResult {
+            ok: true,
+            latency_ms: started.elapsed().as_millis() as u64,
+            credential_id: None,
+            error: None,
+        },
+        Err(e) => CheckResult {
+            ok: false,
+            latency_ms: started.elapsed().as_millis() as u64,
+            credential_id: None,
+            error: Some(e.to_string()),
+        },
+    };
+
+    let healthy = credential.ok && upstream.ok;
+    DeepHealth {
+        status: if healthy { "ok" } else { "unavailable" },
+        credential,
+        upstream,
+        checked_at: Utc::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+
+    #[tokio::test]
+    async fn test_deep_health_without_provider_is_unavailable() {
+        let state = AppState::new("key");
+        let response = get_deep_health(State(state.clone())).await.into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["status"], "unavailable");
+        assert_eq!(value["credential"]["ok"], false);
+
+        // 结果被缓存
+        assert!(state.health.0.lock().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_readyz_without_credentials_is_unavailable() {
+        let response = get_readyz(State(AppState::new("key")))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["reason"], "no credentials loaded");
+        assert_eq!(value["credentials"]["total"], 0);
+    }
+}
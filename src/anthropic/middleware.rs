@@ -1,20 +1,47 @@
 //! Anthropic API 中间件
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::{
-    body::Body,
-    extract::State,
-    http::{Request, StatusCode},
+    body::{Body, Bytes, to_bytes},
+    extract::{OriginalUri, State},
+    http::{HeaderValue, Request, StatusCode, header},
     middleware::Next,
     response::{IntoResponse, Json, Response},
 };
+use futures::{Stream, StreamExt};
+use tracing::Instrument;
 
+use crate::batch::BatchStore;
 use crate::common::auth;
+use crate::common::client_ip::{ClientIp, IpFilter};
+use crate::common::json::{HEADERS_TOO_LARGE, REQUEST_TOO_LARGE};
+use crate::common::live::Live;
+use crate::common::rewrite::{RequestRewriter, for_each_text};
 use crate::kiro::provider::KiroProvider;
+use crate::limit::{
+    self, AdmissionError, AdmissionPermit, AdmissionQueue, AuthLockout, ConcurrencyLimiter,
+    ConcurrencyPermit, IpRateLimitError, IpRateLimiter, RateLimiter,
+};
+use crate::metrics;
+use crate::model::config::{Config, CorsConfig, Priority, wildcard_match};
+use crate::request_log;
+use crate::telemetry;
+use crate::timing::{self, Phase};
+use crate::usage::{UsageRecorder, UsageStore};
 
+use super::dedupe::InflightRequests;
+use super::guardrail::Guardrail;
+use super::health::HealthCache;
+use super::models::ModelLimits;
+use super::moderation::{MODERATION_HEADER, ModerationStage, Moderator, Verdict};
+use super::preset;
 use super::types::ErrorResponse;
 
+/// 内存中默认保留的用量记录数
+pub const DEFAULT_USAGE_CAPACITY: usize = 10_000;
+
 /// 应用共享状态
 #[derive(Clone)]
 pub struct AppState {
@@ -25,8 +52,42 @@ pub struct AppState {
     pub kiro_provider: Option<Arc<KiroProvider>>,
     /// Profile ARN（可选，用于请求）
     pub profile_arn: Option<String>,
+    /// 应用配置
+    pub config: Live<Config>,
+    /// 用量记录存储
+    pub usage_store: Arc<UsageStore>,
+    /// 按 API Key 的并发限制
+    pub concurrency: Arc<ConcurrencyLimiter>,
+    /// 全局准入队列
+    pub admission: Arc<AdmissionQueue>,
+    /// 全局 RPM/TPM 限流器
+    pub rate_limiter: Arc<RateLimiter>,
+    /// 按客户端 IP 的限流器
+    pub ip_rate_limiter: Arc<IpRateLimiter>,
+    /// 认证失败锁定器（与 Admin API 共用）
+    pub auth_lockout: Arc<AuthLockout>,
+    /// 批处理文件与任务存储
+    pub batches: Arc<BatchStore>,
+    /// 请求改写规则
+    pub rewriter: Arc<RequestRewriter>,
+    /// 关键词护栏
+    pub guardrail: Arc<Guardrail>,
+    /// 外部内容审核（未配置时为 None）
+    pub moderator: Option<Arc<Moderator>>,
+    /// 模型规格表（含配置覆盖）
+    pub model_limits: Live<ModelLimits>,
+    /// 进行中的非流式请求（用于合并相同的并发请求）
+    pub inflight: Arc<InflightRequests>,
+    /// 深度健康检查结果缓存
+    pub health: Arc<HealthCache>,
+    /// 对话端点的客户端 IP 访问规则
+    pub ip_filter: Live<IpFilter>,
 }
 
+/// 通过认证的 API Key（脱敏后），由认证中间件写入请求扩展
+#[derive(Debug, Clone)]
+pub struct ApiKeyLabel(pub String);
+
 impl AppState {
     /// 创建新的应用状态
     pub fn new(api_key: impl Into<String>) -> Self {
@@ -34,6 +95,21 @@ impl AppState {
             api_key: api_key.into(),
             kiro_provider: None,
             profile_arn: None,
+            config: Live::new(Config::default()),
+            usage_store: Arc::new(UsageStore::in_memory(DEFAULT_USAGE_CAPACITY)),
+            concurrency: Arc::new(ConcurrencyLimiter::new(None)),
+            admission: Arc::new(AdmissionQueue::new(0, Duration::ZERO)),
+            rate_limiter: Arc::new(RateLimiter::new(None, None)),
+            ip_rate_limiter: Arc::new(IpRateLimiter::default()),
+            auth_lockout: Arc::new(AuthLockout::default()),
+            batches: Arc::new(BatchStore::default()),
+            rewriter: Arc::new(RequestRewriter::default()),
+            guardrail: Arc::new(Guardrail::default()),
+            moderator: None,
+            model_limits: Live::new(ModelLimits::default()),
+            inflight: Arc::new(InflightRequests::default()),
+            health: Arc::new(HealthCache::default()),
+            ip_filter: Live::new(IpFilter::default()),
         }
     }
 
@@ -48,37 +124,1004 @@ impl AppState {
         self.profile_arn = Some(arn.into());
         self
     }
+
+    /// 设置应用配置（同时按配置创建并发限制、准入队列、全局与按 IP 的限流器、认证失败锁定器、请求改写规则、护栏、内容审核、模型规格表与 IP 访问规则）
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.concurrency = Arc::new(ConcurrencyLimiter::new(config.max_concurrent_per_key));
+        self.admission = Arc::new(AdmissionQueue::new(
+            config.max_queue_depth,
+            Duration::from_secs(config.queue_timeout_secs),
+        ));
+        self.rate_limiter = Arc::new(RateLimiter::new(config.global_rpm, config.global_tpm));
+        self.ip_rate_limiter = Arc::new(IpRateLimiter::new(config.ip_rate_limit.clone()));
+        self.auth_lockout = Arc::new(AuthLockout::new(config.auth_lockout.clone()));
+        self.rewriter = Arc::new(RequestRewriter::new(&config.request_rules));
+        self.guardrail = Arc::new(Guardrail::new(&config.guardrails));
+        self.model_limits = Live::new(ModelLimits::from_config(&config));
+        self.ip_filter = Live::new(IpFilter::parse(&config.ip_filter).unwrap_or_default());
+        self.moderator = config
+            .moderation
+            .clone()
+            .map(|moderation| Arc::new(Moderator::new(moderation)));
+        self.config = Live::new(config);
+        self
+    }
+
+    /// 热加载配置：替换请求处理时读取的配置，并按新配置调整并发限制、准入队列、全局与按 IP 的限流器、认证失败锁定器、模型规格表与 IP 访问规则
+    ///
+    /// 请求改写规则、护栏与内容审核在启动时创建，不随热加载变化
+    pub fn reload_config(&self, config: Config) {
+        let old = self.config.load();
+        self.concurrency.set_limit(config.max_concurrent_per_key);
+        self.admission.set_limits(
+            config.max_queue_depth,
+            Duration::from_secs(config.queue_timeout_secs),
+        );
+        // 限流器按新额度重建桶，额度未变时保留当前余量
+        if (old.global_rpm, old.global_tpm) != (config.global_rpm, config.global_tpm) {
+            self.rate_limiter
+                .set_limits(config.global_rpm, config.global_tpm);
+        }
+        if old.ip_rate_limit != config.ip_rate_limit {
+            self.ip_rate_limiter
+                .set_config(config.ip_rate_limit.clone());
+        }
+        if old.auth_lockout != config.auth_lockout {
+            self.auth_lockout.set_config(config.auth_lockout.clone());
+        }
+        self.model_limits.store(ModelLimits::from_config(&config));
+        self.ip_filter
+            .store(IpFilter::parse(&config.ip_filter).unwrap_or_default());
+        self.config.store(config);
+    }
+
+    /// 设置用量记录存储
+    pub fn with_usage_store(mut self, store: Arc<UsageStore>) -> Self {
+        self.usage_store = store;
+        self
+    }
 }
 
+/// 需要检查请求体中模型与流式参数的对话端点
+const SCOPED_BODY_ROUTES: &[&str] = &[
+    "/v1/messages",
+    "/v1/messages/count_tokens",
+    "/v1/chat/completions",
+    "/v1/completions",
+    "/v1/responses",
+];
+
 /// API Key 认证中间件
+///
+/// 接受主 `apiKey` 或 `apiKeys` 中的任一 Key（明文或加盐哈希），并将该 Key 的调度优先级写入请求扩展。
+/// `apiKeys` 中限定了端点、模型或禁止流式的 Key 在这里检查权限，超出范围时返回 403。
+/// 配置了 `authLockout` 时，连续提供错误 Key 的客户端 IP 在锁定期内返回 429
 pub async fn auth_middleware(
     State(state): State<AppState>,
-    request: Request<Body>,
+    mut request: Request<Body>,
     next: Next,
 ) -> Response {
-    match auth::extract_api_key(&request) {
-        Some(key) if auth::constant_time_eq(&key, &state.api_key) => next.run(request).await,
-        _ => {
+    let ip = request.extensions().get::<ClientIp>().map(|ip| ip.0);
+    if let Some(ip) = ip
+        && let Err(remaining) = state.auth_lockout.check(ip)
+    {
+        return auth_locked_out(ip, remaining);
+    }
+
+    let key = auth::extract_api_key(&request).unwrap_or_default();
+    let config = state.config.load();
+    // 主 Key 与 apiKeys 均完整校验，匹配的位置不影响耗时
+    let is_main = auth::verify_api_key(&state.api_key, &key);
+    let found = auth::find_api_key(&config.api_keys, |k| &k.key, &key);
+    let scoped = match (is_main, found) {
+        (true, _) => None,
+        (false, Some(scoped)) => Some(scoped),
+        (false, None) => {
+            if let Some(ip) = ip
+                && !key.is_empty()
+            {
+                auth::audit_failure(
+                    &state.auth_lockout,
+                    ip,
+                    &request_route(&request, &config.route_prefix()),
+                );
+            }
             let error = ErrorResponse::authentication_error();
-            (StatusCode::UNAUTHORIZED, Json(error)).into_response()
+            return (StatusCode::UNAUTHORIZED, Json(error)).into_response();
+        }
+    };
+    if let Some(ip) = ip {
+        state.auth_lockout.record_success(ip);
+    }
+
+    let priority = scoped.map_or_else(Priority::default, |k| k.priority);
+    if let Some(scoped) = scoped {
+        let route = request_route(&request, &config.route_prefix());
+        if !scoped.allows_endpoint(&route) {
+            return permission_denied(format!("This API key may not access {}", route));
+        }
+        // 批处理文件中的请求不经过这里，限定了模型的 Key 不能创建批处理任务
+        if route == "/v1/batches" && request.method() == axum::http::Method::POST {
+            if !scoped.models.is_empty() {
+                return permission_denied("This API key may not create batches");
+            }
+        } else if scoped.restricts_body() && SCOPED_BODY_ROUTES.contains(&route.as_str()) {
+            let (parts, body) = request.into_parts();
+            let bytes = match to_bytes(body, config.max_request_body_bytes).await {
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    return (
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        Json(ErrorResponse::new(
+                            REQUEST_TOO_LARGE,
+                            "Request body is too large",
+                        )),
+                    )
+                        .into_response();
+                }
+            };
+            // 非 JSON 请求体原样透传，由 handler 返回解析错误
+            if let Ok(value) = serde_json::from_slice::<serde_json::Value>(&bytes) {
+                // 按应用预设（`kiro:` 前缀或 `x-kiro-preset` 请求头）后实际使用的模型检查，
+                // 预设无效时由 handler 返回 400
+                if let Some(model) = value["model"].as_str()
+                    && let Ok(model) = preset::resolve_model(&config.presets, model, &parts.headers)
+                    && !scoped.allows_model(&model)
+                {
+                    return permission_denied(format!("This API key may not use model {}", model));
+                }
+                if !scoped.stream && value["stream"].as_bool() == Some(true) {
+                    return permission_denied("This API key may not use streaming");
+                }
+            }
+            request = Request::from_parts(parts, Body::from(bytes));
         }
     }
+
+    request
+        .extensions_mut()
+        .insert(ApiKeyLabel(auth::mask_api_key(&key)));
+    request.extensions_mut().insert(priority);
+    next.run(request).await
 }
 
-/// CORS 中间件层
+fn auth_locked_out(ip: std::net::IpAddr, remaining: Duration) -> Response {
+    let retry_after = auth::audit_locked_out(ip, remaining);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after.to_string())],
+        Json(ErrorResponse::new(
+            "rate_limit_error",
+            "Too many failed authentication attempts from this IP address",
+        )),
+    )
+        .into_response()
+}
+
+fn permission_denied(message: impl Into<String>) -> Response {
+    let error = ErrorResponse::new("permission_error", message);
+    (StatusCode::FORBIDDEN, Json(error)).into_response()
+}
+
+/// 请求的完整路由（嵌套路由中也包含 `/v1` 等前缀），不含 `pathPrefix`
+fn request_route(request: &Request<Body>, prefix: &str) -> String {
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| request.uri().path(), |uri| uri.path());
+    path.strip_prefix(prefix).unwrap_or(path).to_string()
+}
+
+/// 链路追踪中间件
+///
+/// 为每个请求创建 server span，客户端携带 `traceparent` 时延续其链路；
+/// 流式响应的 span 挂在请求 span 下，请求 span 在响应流结束后才会结束。
+/// 请求 ID 取自 `x-request-id`（未携带时生成），写入 span 字段、响应头与 JSON 错误响应体，
+/// 响应头返回后输出一条带状态码与耗时的完成日志；启用请求体日志时，
+/// 采样选中或失败的请求同时记录请求体与响应体
+pub async fn trace_middleware(request: Request<Body>, next: Next) -> Response {
+    let started = std::time::Instant::now();
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("req_{}", uuid::Uuid::new_v4().simple()));
+    let traceparent = request
+        .headers()
+        .get("traceparent")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let sample = telemetry::sample();
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        otel.kind = "server",
+        otel.sampled = sample.trace,
+        http.method = %request.method(),
+        http.route = %request.uri().path(),
+        client.address = request
+            .extensions()
+            .get::<ClientIp>()
+            .map(|ip| tracing::field::display(ip.0)),
+        http.status_code = tracing::field::Empty,
+        otel.status_code = tracing::field::Empty,
+        traceparent = traceparent.as_deref(),
+    );
+
+    let (request, capture) = request_log::begin(request, &request_id);
+    let mut response = timing::scope(next.run(request).instrument(span.clone())).await;
+    let status = response.status();
+    span.record("http.status_code", status.as_u16());
+    if status.is_server_error() {
+        span.record("otel.status_code", "error");
+    }
+    let failed = status.is_client_error() || status.is_server_error();
+    if failed {
+        span.record("otel.sampled", true);
+    }
+    if failed || sample.log {
+        span.in_scope(|| {
+            tracing::info!(
+                status = status.as_u16(),
+                latency_ms = started.elapsed().as_millis() as u64,
+                "请求完成"
+            )
+        });
+    }
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    if status.is_client_error() || status.is_server_error() {
+        response = attach_request_id(response, &request_id).await;
+    }
+    match capture {
+        Some(capture) if failed || sample.log => capture.finish(response),
+        _ => response,
+    }
+}
+
+/// 请求 ID 请求头 / 响应头
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// 读取错误响应体的上限
+const MAX_ERROR_BODY_BYTES: usize = 64 * 1024;
+
+/// 在 JSON 错误响应体中加入顶层 `request_id` 字段，便于用户反馈问题时引用
+///
+/// 响应体超过 [`MAX_ERROR_BODY_BYTES`]、读取出错或不是 JSON 错误对象时原样转发（只保留 `x-request-id` 响应头）
+async fn attach_request_id(response: Response, request_id: &str) -> Response {
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let mut stream = body.into_data_stream();
+    let mut buffered = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                tracing::warn!("读取错误响应体失败: {}", e);
+                let rest = futures::stream::once(async move { Err(e) }).chain(stream);
+                return Response::from_parts(parts, passthrough(buffered, rest));
+            }
+        };
+        buffered.extend_from_slice(&chunk);
+        if buffered.len() > MAX_ERROR_BODY_BYTES {
+            return Response::from_parts(parts, passthrough(buffered, stream));
+        }
+    }
+    match with_request_id(&buffered, request_id) {
+        Some(body) => {
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(body))
+        }
+        None => Response::from_parts(parts, Body::from(buffered)),
+    }
+}
+
+/// 已读取的部分接上剩余的响应流，还原为原始响应体
+fn passthrough(
+    buffered: Vec<u8>,
+    rest: impl Stream<Item = Result<Bytes, axum::Error>> + Send + 'static,
+) -> Body {
+    let head = futures::stream::once(async move { Ok(Bytes::from(buffered)) });
+    Body::from_stream(head.chain(rest))
+}
+
+/// 为带 `error` 字段的 JSON 对象加入 `request_id`，其他内容返回 None
+fn with_request_id(body: &[u8], request_id: &str) -> Option<Vec<u8>> {
+    let mut value: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let object = value.as_object_mut().filter(|o| o.contains_key("error"))?;
+    object.insert("request_id".to_string(), request_id.into());
+    serde_json::to_vec(&value).ok()
+}
+
+/// 客户端 IP 访问规则中间件
+///
+/// 按 `ipFilter` 检查客户端 IP（经受信任代理转发时取自转发请求头），被拒绝时返回 403
+pub async fn ip_filter_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let ip = request.extensions().get::<ClientIp>().map(|ip| ip.0);
+    if state.ip_filter.load().allows(ip) {
+        return next.run(request).await;
+    }
+    metrics::global()
+        .ip_denied_total
+        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    tracing::warn!(client.address = ?ip, "客户端 IP 不在访问规则允许范围内，拒绝请求");
+    let error = ErrorResponse::new(
+        "permission_error",
+        "Requests from this IP address are not allowed",
+    );
+    (StatusCode::FORBIDDEN, Json(error)).into_response()
+}
+
+/// 并发超限时建议客户端等待的秒数
+const CONCURRENCY_RETRY_AFTER_SECS: u64 = 1;
+
+/// 按 API Key 的并发限制中间件
+pub async fn concurrency_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let key = auth::extract_api_key(&request).unwrap_or_default();
+    let Some(permit) = state.concurrency.try_acquire(&key) else {
+        return concurrency_rejected(&key);
+    };
+
+    hold_until_body_done(next.run(request).await, permit)
+}
+
+/// API Key 并发数超过上限时的响应
+fn concurrency_rejected(key: &str) -> Response {
+    tracing::warn!(
+        api_key = %auth::mask_api_key(key),
+        "并发请求数超过上限，拒绝请求"
+    );
+    let error = ErrorResponse::new(
+        "rate_limit_error",
+        "Too many concurrent requests for this API key",
+    );
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(
+            header::RETRY_AFTER,
+            CONCURRENCY_RETRY_AFTER_SECS.to_string(),
+        )],
+        Json(error),
+    )
+        .into_response()
+}
+
+/// 全局 RPM/TPM 限流中间件
+pub async fn rate_limit_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    match state.rate_limiter.check() {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            metrics::global()
+                .rate_limited_total
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            tracing::warn!("触发全局限流，建议 {} 秒后重试", retry_after);
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                Json(ErrorResponse::new(
+                    "rate_limit_error",
+                    "Global rate limit exceeded",
+                )),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// 按客户端 IP 的限流中间件
+///
+/// 超出额度返回 429 并带 `Retry-After`；被封禁的 IP 在封禁期内同样返回 429，`Retry-After` 为剩余封禁时间
+pub async fn ip_rate_limit_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(ClientIp(ip)) = request.extensions().get::<ClientIp>().copied() else {
+        return next.run(request).await;
+    };
+    let (wait, message) = match state.ip_rate_limiter.check(ip) {
+        Ok(()) => return next.run(request).await,
+        Err(IpRateLimitError::Limited(wait)) => (wait, "Rate limit exceeded for this IP address"),
+        Err(IpRateLimitError::Banned(wait)) => (
+            wait,
+            "This IP address is temporarily banned for exceeding the rate limit",
+        ),
+    };
+    metrics::global()
+        .ip_rate_limited_total
+        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+    tracing::warn!(client.address = %ip, "{}，建议 {} 秒后重试", message, retry_after);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after.to_string())],
+        Json(ErrorResponse::new("rate_limit_error", message)),
+    )
+        .into_response()
+}
+
+/// 请求头大小限制中间件
+///
+/// 请求头名称与值的总字节数超过 `maxRequestHeaderBytes` 时返回 431
+pub async fn header_limit_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let limit = state.config.load().max_request_header_bytes;
+    let size: usize = request
+        .headers()
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .sum();
+    if size <= limit {
+        return next.run(request).await;
+    }
+    tracing::warn!("请求头共 {} 字节，超过上限 {} 字节", size, limit);
+    (
+        StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+        Json(ErrorResponse::new(
+            HEADERS_TOO_LARGE,
+            "Request headers are too large",
+        )),
+    )
+        .into_response()
+}
+
+/// 对话请求中的消息数组（Responses API 为 `input`，可为字符串）
+fn message_count(value: &serde_json::Value) -> Option<usize> {
+    value["messages"]
+        .as_array()
+        .or_else(|| value["input"].as_array())
+        .map(Vec::len)
+}
+
+/// 请求体改写、护栏与内容审核中间件
+///
+/// 配置了 `requestRules`、护栏屏蔽规则、外部审核接口或 `maxMessages` 时读取 JSON 请求体：
+/// 消息数超过 `maxMessages` 时返回 413，
+/// 再按路由与 API Key 应用匹配的改写规则，然后依次检查护栏与外部审核，
+/// 被拒绝时返回 400 并写入带护栏标记的用量记录；审核结论为 `flag` 时在响应头中标注。
+/// 非 JSON 请求体原样透传，由 handler 返回解析错误
+pub async fn request_body_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let max_messages = state.config.load().max_messages;
+    if state.rewriter.is_empty()
+        && !state.guardrail.is_enabled()
+        && state.moderator.is_none()
+        && max_messages.is_none()
+    {
+        return next.run(request).await;
+    }
+
+    let route = request_route(&request, &state.config.load().route_prefix());
+    let key = auth::extract_api_key(&request).unwrap_or_default();
+    let (mut parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, state.config.load().max_request_body_bytes).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            return (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json(ErrorResponse::new(
+                    REQUEST_TOO_LARGE,
+                    "Request body is too large",
+                )),
+            )
+                .into_response();
+        }
+    };
+
+    let Ok(mut value) = serde_json::from_slice::<serde_json::Value>(&bytes) else {
+        return next
+            .run(Request::from_parts(parts, Body::from(bytes)))
+            .await;
+    };
+    let api_key = parts
+        .extensions
+        .get::<ApiKeyLabel>()
+        .map(|label| label.0.clone())
+        .unwrap_or_default();
+    let check = match check_request_body(&state, &route, &key, &api_key, &mut value).await {
+        Ok(check) => check,
+        Err(response) => return response,
+    };
+
+    let body = match check
+        .rewritten
+        .then(|| serde_json::to_vec(&value).ok())
+        .flatten()
+    {
+        Some(rewritten) => {
+            tracing::debug!("已按改写规则修改 {} 的请求体", route);
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Body::from(rewritten)
+        }
+        None => Body::from(bytes),
+    };
+    let mut response = next.run(Request::from_parts(parts, body)).await;
+    if check.flagged {
+        response
+            .headers_mut()
+            .insert(MODERATION_HEADER, HeaderValue::from_static("flagged"));
+    }
+    response
+}
+
+/// 请求体检查的结果
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BodyCheck {
+    /// 请求体是否被改写规则修改
+    pub rewritten: bool,
+    /// 内容审核是否标记了该请求（放行但需要提示）
+    pub flagged: bool,
+}
+
+/// 检查并改写对话请求体：消息数上限、请求改写规则、护栏与内容审核
+///
+/// 交互式请求（[`request_body_middleware`]）与批处理任务中的每条请求共用；
+/// `key` 为原始 API Key（用于匹配改写规则），`api_key` 为脱敏后的 Key（写入用量记录）。
+/// 请求被拒绝时返回错误响应
+pub async fn check_request_body(
+    state: &AppState,
+    route: &str,
+    key: &str,
+    api_key: &str,
+    value: &mut serde_json::Value,
+) -> Result<BodyCheck, Response> {
+    let mut check = BodyCheck::default();
+    if let (Some(max), Some(count)) = (state.config.load().max_messages, message_count(value))
+        && count > max
+    {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(ErrorResponse::new(
+                REQUEST_TOO_LARGE,
+                format!("Too many messages: {} > {} maximum", count, max),
+            )),
+        )
+            .into_response());
+    }
+    check.rewritten = state.rewriter.apply(route, key, value) > 0;
+
+    if let Some(pattern) = state.guardrail.check_request(value) {
+        return Err(reject_request(state, api_key, route, value, pattern));
+    }
+
+    if let Some(moderator) = &state.moderator {
+        let mut input = Vec::new();
+        for_each_text(value, &mut |text| input.push(text.to_string()));
+        let model = value["model"].as_str().unwrap_or_default();
+        let verdict = moderator
+            .check(ModerationStage::Prompt, Some(route), model, &input)
+            .await;
+        match verdict {
+            Verdict::Allow => {}
+            Verdict::Block { .. } => {
+                return Err(reject_request(
+                    state,
+                    api_key,
+                    route,
+                    value,
+                    &verdict.describe(),
+                ));
+            }
+            Verdict::Flag { .. } => {
+                tracing::warn!(route = %route, "{}", verdict.describe());
+                check.flagged = true;
+            }
+        }
+    }
+    Ok(check)
+}
+
+/// 拒绝被护栏或内容审核拦截的请求，并写入带护栏标记的用量记录
+fn reject_request(
+    state: &AppState,
+    api_key: &str,
+    route: &str,
+    body: &serde_json::Value,
+    reason: &str,
+) -> Response {
+    tracing::warn!(
+        api_key = %api_key,
+        route = %route,
+        reason,
+        "请求被护栏拦截，拒绝请求"
+    );
+    let mut usage = UsageRecorder::new(
+        state.usage_store.clone(),
+        route,
+        api_key.to_string(),
+        body["model"].as_str().unwrap_or_default(),
+        body["stream"].as_bool().unwrap_or(false),
+    );
+    usage.set_guardrail(reason);
+    usage.set_status(StatusCode::BAD_REQUEST.as_u16());
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new(
+            "invalid_request_error",
+            "Request blocked by content policy",
+        )),
+    )
+        .into_response()
+}
+
+/// 降载拒绝时建议客户端等待的秒数
+const LOAD_SHED_RETRY_AFTER_SECS: u64 = 5;
+
+/// 资源压力降载中间件
+///
+/// 仅在配置了 `loadShedding` 时生效：事件循环延迟或内存超过阈值时按 API Key 的优先级拒绝新请求，
+/// 已通过的请求（包括进行中的流式响应）不受影响
+pub async fn load_shed_middleware(request: Request<Body>, next: Next) -> Response {
+    let priority = request
+        .extensions()
+        .get::<Priority>()
+        .copied()
+        .unwrap_or_default();
+
+    if limit::shedder().should_shed(priority) {
+        metrics::global()
+            .load_shed_total
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        tracing::warn!("资源压力过高，拒绝 {:?} 优先级的新请求", priority);
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, LOAD_SHED_RETRY_AFTER_SECS.to_string())],
+            Json(ErrorResponse::new(
+                "overloaded_error",
+                "Server is under resource pressure, please retry later",
+            )),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+/// 准入队列等待失败时建议客户端等待的秒数
+const ADMISSION_RETRY_AFTER_SECS: u64 = 5;
+
+/// 全局准入队列中间件
+///
+/// 仅在配置了 `maxConcurrentPerCredential` 或 `adaptiveConcurrency` 时生效：容量为每凭据并发数 × 可用凭据数
+/// 与自适应并发上限中的较小者，
+/// 饱和时请求按 API Key 的优先级排队等待，队列已满或等待超时返回 503
+pub async fn admission_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(capacity) = admission_capacity(&state) else {
+        return next.run(request).await;
+    };
+
+    let priority = request
+        .extensions()
+        .get::<Priority>()
+        .copied()
+        .unwrap_or_default();
+
+    match timing::measure(Phase::Queue, state.admission.acquire(capacity, priority)).await {
+        Ok(permit) => hold_until_body_done(next.run(request).await, permit),
+        Err(e) => admission_rejected(e),
+    }
+}
+
+/// 未能通过准入队列时的响应
+fn admission_rejected(e: AdmissionError) -> Response {
+    tracing::warn!("请求未能通过准入队列: {}", e);
+    let message = match e {
+        AdmissionError::QueueFull => "Server is overloaded, request queue is full",
+        AdmissionError::Timeout => "Server is overloaded, timed out waiting in queue",
+    };
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, ADMISSION_RETRY_AFTER_SECS.to_string())],
+        Json(ErrorResponse::new("overloaded_error", message)),
+    )
+        .into_response()
+}
+
+/// 一个请求中额外的上游调用持有的并发与准入许可，drop 时释放
+pub struct ExtraPermits {
+    _concurrency: Vec<ConcurrencyPermit>,
+    _admission: Vec<AdmissionPermit>,
+}
+
+/// 为一个请求中额外的 `count` 个上游调用（如 `n` > 1 时的多个候选）获取许可
 ///
-/// **安全说明**：当前配置允许所有来源（Any），这是为了支持公开 API 服务。
-/// 如果需要更严格的安全控制，请根据实际需求配置具体的允许来源、方法和头信息。
+/// 与中间件链相同，每个调用占用一个 API Key 并发名额与一个准入名额（按请求的优先级排队），
+/// 无法获取时返回与中间件相同的错误响应；调用总数超过准入容量时直接拒绝，避免持有许可等待自身
+pub async fn acquire_extra_permits(
+    state: &AppState,
+    key: &str,
+    priority: Priority,
+    count: usize,
+) -> Result<ExtraPermits, Response> {
+    let mut concurrency = Vec::with_capacity(count);
+    for _ in 0..count {
+        match state.concurrency.try_acquire(key) {
+            Some(permit) => concurrency.push(permit),
+            None => return Err(concurrency_rejected(key)),
+        }
+    }
+    let mut admission = Vec::with_capacity(count);
+    if let Some(capacity) = admission_capacity(state) {
+        if count >= capacity {
+            return Err(admission_rejected(AdmissionError::QueueFull));
+        }
+        for _ in 0..count {
+            let acquired =
+                timing::measure(Phase::Queue, state.admission.acquire(capacity, priority)).await;
+            match acquired {
+                Ok(permit) => admission.push(permit),
+                Err(e) => return Err(admission_rejected(e)),
+            }
+        }
+    }
+    Ok(ExtraPermits {
+        _concurrency: concurrency,
+        _admission: admission,
+    })
+}
+
+/// 准入队列当前容量（每凭据并发数 × 可用凭据数与自适应并发上限中的较小者，至少为 1），都未配置时为 None
+pub(crate) fn admission_capacity(state: &AppState) -> Option<usize> {
+    let per_credential = state.config.load().max_concurrent_per_credential.map(|n| {
+        let available = state
+            .kiro_provider
+            .as_ref()
+            .map(|p| p.token_manager().available_count())
+            .unwrap_or(0);
+        n * available
+    });
+    let capacity = match (per_credential, limit::adaptive().limit()) {
+        (Some(a), Some(b)) => a.min(b),
+        (a, b) => a.or(b)?,
+    };
+    Some(capacity.max(1))
+}
+
+/// 让 `guard` 随响应体一起存活，流式响应在流结束或客户端断开时才释放
+pub(crate) fn hold_until_body_done<G: Send + Sync + 'static>(
+    response: Response,
+    guard: G,
+) -> Response {
+    response.map(|body| {
+        Body::from_stream(body.into_data_stream().map(move |chunk| {
+            let _guard = &guard;
+            chunk
+        }))
+    })
+}
+
+/// CORS 中间件层
 ///
-/// # 配置说明
-/// - `allow_origin(Any)`: 允许任何来源的请求
-/// - `allow_methods(Any)`: 允许任何 HTTP 方法
-/// - `allow_headers(Any)`: 允许任何请求头
-pub fn cors_layer() -> tower_http::cors::CorsLayer {
-    use tower_http::cors::{Any, CorsLayer};
-
-    CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any)
+/// 未配置 `cors` 时允许任何来源、方法与请求头（不允许携带凭证），以支持公开 API 服务。
+/// 配置后按 `cors` 限定来源与请求头；允许携带凭证时 `*` 来源与未限定的请求头改为回显请求中的值
+/// （浏览器不接受凭证请求的通配符响应）
+pub fn cors_layer(config: Option<&CorsConfig>) -> tower_http::cors::CorsLayer {
+    use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer};
+
+    let Some(config) = config else {
+        return CorsLayer::new()
+            .allow_origin(Any)
+            .allow_methods(Any)
+            .allow_headers(Any);
+    };
+
+    let credentials = config.allow_credentials;
+    let allow_origin = if config.allowed_origins.iter().any(|o| o == "*") {
+        if credentials {
+            AllowOrigin::mirror_request()
+        } else {
+            AllowOrigin::any()
+        }
+    } else {
+        let origins = config.allowed_origins.clone();
+        AllowOrigin::predicate(move |origin, _| {
+            origin
+                .to_str()
+                .is_ok_and(|origin| origins.iter().any(|o| wildcard_match(o, origin)))
+        })
+    };
+    let allow_headers = if !config.allowed_headers.is_empty() {
+        AllowHeaders::list(
+            config
+                .allowed_headers
+                .iter()
+                .filter_map(|h| h.parse::<header::HeaderName>().ok()),
+        )
+    } else if credentials {
+        AllowHeaders::mirror_request()
+    } else {
+        AllowHeaders::any()
+    };
+    let allow_methods = if credentials {
+        AllowMethods::mirror_request()
+    } else {
+        AllowMethods::any()
+    };
+
+    let layer = CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(allow_methods)
+        .allow_headers(allow_headers)
+        .allow_credentials(credentials);
+    match config.max_age_secs {
+        Some(secs) => layer.max_age(Duration::from_secs(secs)),
+        None => layer,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::config::{ApiKeyConfig, PromptPreset};
+    use axum::{Router, middleware, routing::post};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_extra_permits_count_against_limits() {
+        let config = Config {
+            max_concurrent_per_key: Some(3),
+            ..Config::default()
+        };
+        let state = AppState::new("sk-main-key-000").with_config(config);
+        // 中间件为请求本身占用的名额
+        let _request = state.concurrency.try_acquire("sk-a").unwrap();
+
+        let rejected = acquire_extra_permits(&state, "sk-a", Priority::Normal, 3).await;
+        assert_eq!(
+            rejected.err().unwrap().status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        let permits = acquire_extra_permits(&state, "sk-a", Priority::Normal, 2)
+            .await
+            .ok()
+            .unwrap();
+        assert!(state.concurrency.try_acquire("sk-a").is_none());
+        drop(permits);
+        assert!(state.concurrency.try_acquire("sk-a").is_some());
+
+        // 调用总数超过准入容量时直接拒绝
+        let config = Config {
+            max_concurrent_per_credential: Some(1),
+            ..Config::default()
+        };
+        let state = AppState::new("sk-main-key-000").with_config(config);
+        let rejected = acquire_extra_permits(&state, "sk-a", Priority::Normal, 1).await;
+        assert_eq!(
+            rejected.err().unwrap().status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scoped_key_model_checked_after_preset() {
+        let config = Config {
+            api_keys: vec![ApiKeyConfig {
+                key: "sk-cheap-model-key".to_string(),
+                priority: Priority::default(),
+                models: vec!["claude-haiku-*".to_string()],
+                endpoints: Vec::new(),
+                stream: true,
+                admin_read_only: false,
+            }],
+            presets: std::collections::HashMap::from([(
+                "big".to_string(),
+                PromptPreset {
+                    model: Some("claude-opus-4-5".to_string()),
+                    ..Default::default()
+                },
+            )]),
+            ..Config::default()
+        };
+        let state = AppState::new("sk-main-key-000").with_config(config);
+        let app = Router::new()
+            .route("/v1/messages", post(|| async { StatusCode::OK }))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                auth_middleware,
+            ))
+            .with_state(state);
+        let send = |preset: Option<&'static str>| {
+            let mut request =
+                Request::post("/v1/messages").header("x-api-key", "sk-cheap-model-key");
+            if let Some(preset) = preset {
+                request = request.header(preset::PRESET_HEADER, preset);
+            }
+            let body = r#"{"model":"claude-haiku-4-5","max_tokens":1,"messages":[]}"#;
+            app.clone().oneshot(request.body(Body::from(body)).unwrap())
+        };
+
+        assert_eq!(send(None).await.unwrap().status(), StatusCode::OK);
+        // 预设把模型替换为 Key 不允许的模型
+        assert_eq!(
+            send(Some("big")).await.unwrap().status(),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    fn test_message_count() {
+        assert_eq!(
+            message_count(&serde_json::json!({"messages": [{}, {}]})),
+            Some(2)
+        );
+        assert_eq!(message_count(&serde_json::json!({"input": [{}]})), Some(1));
+        assert_eq!(message_count(&serde_json::json!({"input": "hi"})), None);
+        assert_eq!(message_count(&serde_json::json!({"prompt": "hi"})), None);
+    }
+
+    #[tokio::test]
+    async fn test_attach_request_id_passes_large_bodies_through() {
+        let json = |body: Body| {
+            Response::builder()
+                .status(StatusCode::BAD_GATEWAY)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body)
+                .unwrap()
+        };
+        let read = |response: Response| async move {
+            to_bytes(response.into_body(), usize::MAX).await.unwrap()
+        };
+
+        let small = attach_request_id(json(Body::from(r#"{"error":{}}"#)), "req_1").await;
+        let value: serde_json::Value = serde_json::from_slice(&read(small).await).unwrap();
+        assert_eq!(value["request_id"], "req_1");
+
+        let large = format!(r#"{{"error":{{"message":"{}"}}}}"#, "x".repeat(100 * 1024));
+        let chunks = large
+            .as_bytes()
+            .chunks(8 * 1024)
+            .map(|chunk| Ok::<_, std::io::Error>(Bytes::copy_from_slice(chunk)))
+            .collect::<Vec<_>>();
+        let response = attach_request_id(
+            json(Body::from_stream(futures::stream::iter(chunks))),
+            "req_1",
+        )
+        .await;
+        assert_eq!(read(response).await, large.as_bytes());
+    }
+
+    #[test]
+    fn test_with_request_id_adds_field_to_error_bodies() {
+        let body = br#"{"error":{"type":"api_error","message":"boom"}}"#;
+        let value: serde_json::Value =
+            serde_json::from_slice(&with_request_id(body, "req_1").unwrap()).unwrap();
+        assert_eq!(value["request_id"], "req_1");
+        assert_eq!(value["error"]["message"], "boom");
+
+        assert!(with_request_id(br#"{"data":[]}"#, "req_1").is_none());
+        assert!(with_request_id(b"not json", "req_1").is_none());
+    }
 }
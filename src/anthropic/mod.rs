@@ -16,11 +16,26 @@
 //! axum::serve(listener, app).await?;
 //! ```
 
+mod aggregator;
+mod coalesce;
+mod compaction;
 mod converter;
-mod handlers;
-mod middleware;
+mod dedupe;
+pub(crate) mod extract;
+mod guardrail;
+pub(crate) mod handlers;
+mod health;
+pub(crate) mod middleware;
+mod models;
+mod moderation;
+pub(crate) mod pipeline;
+mod preset;
+mod reasoning;
 mod router;
-mod stream;
+pub(crate) mod stream;
 pub mod types;
 
-pub use router::create_router_with_provider;
+pub use converter::convert_request;
+pub use health::{set_warming_up, start_draining};
+pub use middleware::{AppState, DEFAULT_USAGE_CAPACITY, cors_layer};
+pub use router::{create_app_state, create_router};
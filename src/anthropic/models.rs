@@ -0,0 +1,194 @@
+//! 模型规格表
+//!
+//! 内置各上游模型的最大输出 tokens 与上下文窗口，可通过配置 `modelLimits` 按模型名称覆盖。
+//! 用于校验 / 截断 `max_tokens`、判断是否需要压缩历史，以及填充 `/v1/models` 的元数据。
+
+use crate::model::config::{Config, ModelLimit, wildcard_match};
+
+use super::converter::map_model;
+use super::types::Model;
+
+/// 内置模型规格
+pub(crate) struct ModelSpec {
+    /// 对外公开的模型 ID
+    pub id: &'static str,
+    /// 对应的上游模型 ID
+    pub upstream: &'static str,
+    pub display_name: &'static str,
+    pub created: i64,
+    /// 最大输出 tokens
+    pub max_output_tokens: i32,
+    /// 上下文窗口（tokens）
+    pub context_window: u64,
+}
+
+/// 内置模型规格表
+pub(crate) const BUILTIN_MODELS: &[ModelSpec] = &[
+    ModelSpec {
+        id: "claude-sonnet-4-5-20250929",
+        upstream: "claude-sonnet-4.5",
+        display_name: "Claude Sonnet 4.5",
+        created: 1727568000,
+        max_output_tokens: 32000,
+        context_window: 200_000,
+    },
+    ModelSpec {
+        id: "claude-opus-4-5-20251101",
+        upstream: "claude-opus-4.5",
+        display_name: "Claude Opus 4.5",
+        created: 1730419200,
+        max_output_tokens: 32000,
+        context_window: 200_000,
+    },
+    ModelSpec {
+        id: "claude-haiku-4-5-20251001",
+        upstream: "claude-haiku-4.5",
+        display_name: "Claude Haiku 4.5",
+        created: 1727740800,
+        max_output_tokens: 32000,
+        context_window: 200_000,
+    },
+];
+
+/// 未知模型的最大输出 tokens
+const DEFAULT_MAX_OUTPUT_TOKENS: i32 = 32000;
+
+/// 单个模型的生效限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Limits {
+    pub max_output_tokens: i32,
+    pub context_window: u64,
+}
+
+/// 内置规格表与配置覆盖合并后的模型限制
+#[derive(Debug, Clone)]
+pub(crate) struct ModelLimits {
+    overrides: Vec<ModelLimit>,
+    /// 全局上下文窗口上限（`contextWindowTokens`）
+    context_window_cap: u64,
+}
+
+impl Default for ModelLimits {
+    fn default() -> Self {
+        Self::from_config(&Config::default())
+    }
+}
+
+impl ModelLimits {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            overrides: config.model_limits.clone(),
+            context_window_cap: config.context_window_tokens,
+        }
+    }
+
+    /// 查询模型的生效限制
+    ///
+    /// 配置覆盖按顺序匹配（每个字段取第一条设置了该字段的规则），
+    /// 未覆盖时使用内置规格（上下文窗口不超过 `contextWindowTokens`）
+    pub fn get(&self, model: &str) -> Limits {
+        let upstream = map_model(model);
+        let builtin = BUILTIN_MODELS
+            .iter()
+            .find(|spec| spec.id == model || Some(spec.upstream) == upstream.as_deref());
+        let matching = || {
+            self.overrides
+                .iter()
+                .filter(|limit| wildcard_match(&limit.model, model))
+        };
+
+        let max_output_tokens = matching()
+            .find_map(|limit| limit.max_output_tokens)
+            .or(builtin.map(|spec| spec.max_output_tokens))
+            .unwrap_or(DEFAULT_MAX_OUTPUT_TOKENS);
+        let context_window = matching()
+            .find_map(|limit| limit.context_window)
+            .unwrap_or_else(|| {
+                builtin.map_or(self.context_window_cap, |spec| {
+                    spec.context_window.min(self.context_window_cap)
+                })
+            });
+        Limits {
+            max_output_tokens,
+            context_window,
+        }
+    }
+
+    /// `/v1/models` 返回的模型列表
+    pub fn models(&self) -> Vec<Model> {
+        BUILTIN_MODELS
+            .iter()
+            .map(|spec| {
+                let limits = self.get(spec.id);
+                Model {
+                    id: spec.id.to_string(),
+                    object: "model".to_string(),
+                    created: spec.created,
+                    owned_by: "anthropic".to_string(),
+                    display_name: spec.display_name.to_string(),
+                    model_type: "chat".to_string(),
+                    max_tokens: limits.max_output_tokens,
+                    context_window: limits.context_window,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(overrides: Vec<ModelLimit>, cap: u64) -> ModelLimits {
+        ModelLimits {
+            overrides,
+            context_window_cap: cap,
+        }
+    }
+
+    #[test]
+    fn test_builtin_limits_by_family() {
+        let limits = limits(Vec::new(), 200_000);
+        let expected = Limits {
+            max_output_tokens: 32000,
+            context_window: 200_000,
+        };
+        assert_eq!(limits.get("claude-sonnet-4-5-20250929"), expected);
+        assert_eq!(limits.get("claude-sonnet-4.5"), expected);
+        assert_eq!(limits.get("unknown-model").max_output_tokens, 32000);
+    }
+
+    #[test]
+    fn test_overrides_and_global_cap() {
+        let table = limits(
+            vec![
+                ModelLimit {
+                    model: "claude-opus-*".to_string(),
+                    max_output_tokens: Some(64000),
+                    context_window: None,
+                },
+                ModelLimit {
+                    model: "*".to_string(),
+                    max_output_tokens: Some(8000),
+                    context_window: Some(500_000),
+                },
+            ],
+            100_000,
+        );
+
+        let opus = table.get("claude-opus-4-5-20251101");
+        assert_eq!(opus.max_output_tokens, 64000);
+        assert_eq!(opus.context_window, 500_000);
+
+        let sonnet = table.get("claude-sonnet-4.5");
+        assert_eq!(sonnet.max_output_tokens, 8000);
+
+        // 未覆盖上下文窗口时，内置值受 contextWindowTokens 限制
+        let capped = limits(Vec::new(), 100_000);
+        assert_eq!(capped.get("claude-haiku-4.5").context_window, 100_000);
+
+        let models = table.models();
+        assert_eq!(models.len(), BUILTIN_MODELS.len());
+        assert_eq!(models[1].max_tokens, 64000);
+    }
+}
@@ -0,0 +1,234 @@
+//! 外部内容审核
+//!
+//! 配置 `moderation.url` 后，对话请求的提示词（以及可选的非流式响应输出）会发送到审核接口：
+//!
+//! ```json
+//! {"stage": "prompt", "route": "/v1/messages", "model": "claude-sonnet-4.5", "input": ["..."]}
+//! ```
+//!
+//! 输出阶段的 `stage` 为 `"output"`，不携带 `route`；流式响应的输出已发送给客户端，不做审核。
+//!
+//! 审核接口返回 `{"decision": "allow" | "block" | "flag", "reason": "..."}`：
+//! `block` 拒绝请求（输出阶段则截断输出），`flag` 放行并在响应头 `x-kiro-moderation` 中标注。
+//! 超时或出错时按 `failClosed` 决定拒绝还是放行。
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::http_client;
+use crate::model::config::ModerationConfig;
+
+use super::guardrail::REFUSAL_STOP_REASON;
+
+/// 标注审核结果的响应头
+pub const MODERATION_HEADER: &str = "x-kiro-moderation";
+
+/// 审核接口不可用且配置为拒绝时使用的原因
+const UNAVAILABLE_REASON: &str = "moderation unavailable";
+
+/// 审核阶段
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ModerationStage {
+    Prompt,
+    Output,
+}
+
+/// 审核接口的请求体
+#[derive(Serialize)]
+struct ModerationRequest<'a> {
+    stage: ModerationStage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    route: Option<&'a str>,
+    model: &'a str,
+    input: &'a [String],
+}
+
+/// 审核结论
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "decision", rename_all = "lowercase")]
+pub(crate) enum Verdict {
+    Allow,
+    Block {
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    Flag {
+        #[serde(default)]
+        reason: Option<String>,
+    },
+}
+
+impl Verdict {
+    /// 用于日志与用量记录的描述
+    pub fn describe(&self) -> String {
+        let (decision, reason) = match self {
+            Verdict::Allow => return "moderation: allow".to_string(),
+            Verdict::Block { reason } => ("block", reason),
+            Verdict::Flag { reason } => ("flag", reason),
+        };
+        match reason {
+            Some(reason) => format!("moderation: {} ({})", decision, reason),
+            None => format!("moderation: {}", decision),
+        }
+    }
+}
+
+/// 外部审核接口客户端
+pub(crate) struct Moderator {
+    client: reqwest::Client,
+    config: ModerationConfig,
+}
+
+impl Moderator {
+    pub fn new(config: ModerationConfig) -> Self {
+        Self {
+            // 单次审核的超时由 `timeoutMs` 控制，Client 超时只作兜底
+            client: http_client::build_integration_client(config.timeout_ms.div_ceil(1000) + 5)
+                .unwrap_or_default(),
+            config,
+        }
+    }
+
+    /// 是否审核非流式响应的输出
+    pub fn scans_output(&self) -> bool {
+        self.config.scan_output
+    }
+
+    /// 审核一组文本，超时或出错时按配置返回拒绝或放行
+    pub async fn check(
+        &self,
+        stage: ModerationStage,
+        route: Option<&str>,
+        model: &str,
+        input: &[String],
+    ) -> Verdict {
+        if input.is_empty() {
+            return Verdict::Allow;
+        }
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        let result = tokio::time::timeout(timeout, self.call(stage, route, model, input)).await;
+        let error = match result {
+            Ok(Ok(verdict)) => return verdict,
+            Ok(Err(e)) => e,
+            Err(_) => format!("超时（{} ms）", self.config.timeout_ms),
+        };
+
+        if self.config.fail_closed {
+            tracing::warn!("内容审核失败，拒绝请求: {}", error);
+            Verdict::Block {
+                reason: Some(UNAVAILABLE_REASON.to_string()),
+            }
+        } else {
+            tracing::warn!("内容审核失败，放行请求: {}", error);
+            Verdict::Allow
+        }
+    }
+
+    async fn call(
+        &self,
+        stage: ModerationStage,
+        route: Option<&str>,
+        model: &str,
+        input: &[String],
+    ) -> Result<Verdict, String> {
+        let mut request = self.client.post(&self.config.url).json(&ModerationRequest {
+            stage,
+            route,
+            model,
+            input,
+        });
+        if let Some(api_key) = &self.config.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("审核接口返回错误状态: {}", response.status()));
+        }
+        response.json().await.map_err(|e| e.to_string())
+    }
+}
+
+/// 收集非流式响应中文本与 thinking 块的内容
+pub(crate) fn message_texts(message: &Value) -> Vec<String> {
+    message["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|block| match block["type"].as_str() {
+            Some("text") => block["text"].as_str(),
+            Some("thinking") => block["thinking"].as_str(),
+            _ => None,
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// 输出被拒绝时清空内容并以 `refusal` 结束消息
+pub(crate) fn refuse_message(message: &mut Value) {
+    message["content"] = json!([]);
+    message["stop_reason"] = json!(REFUSAL_STOP_REASON);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verdict_parsing() {
+        let allow: Verdict = serde_json::from_value(json!({"decision": "allow"})).unwrap();
+        let block: Verdict =
+            serde_json::from_value(json!({"decision": "block", "reason": "violence"})).unwrap();
+        let flag: Verdict = serde_json::from_value(json!({"decision": "flag"})).unwrap();
+
+        assert_eq!(allow, Verdict::Allow);
+        assert_eq!(block.describe(), "moderation: block (violence)");
+        assert_eq!(flag.describe(), "moderation: flag");
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_endpoint_fails_open_or_closed() {
+        let config = |fail_closed| ModerationConfig {
+            // 保留端口，连接会被立即拒绝
+            url: "http://127.0.0.1:9/moderate".to_string(),
+            api_key: None,
+            timeout_ms: 2000,
+            fail_closed,
+            scan_output: false,
+        };
+        let input = vec!["hello".to_string()];
+
+        let open = Moderator::new(config(false));
+        assert_eq!(
+            open.check(ModerationStage::Prompt, None, "m", &input).await,
+            Verdict::Allow
+        );
+
+        let closed = Moderator::new(config(true));
+        assert!(matches!(
+            closed
+                .check(ModerationStage::Prompt, None, "m", &input)
+                .await,
+            Verdict::Block { .. }
+        ));
+    }
+
+    #[test]
+    fn test_message_texts_and_refusal() {
+        let mut message = json!({
+            "content": [
+                {"type": "thinking", "thinking": "hmm"},
+                {"type": "text", "text": "answer"},
+                {"type": "tool_use", "id": "t", "name": "x", "input": {}}
+            ],
+            "stop_reason": "end_turn"
+        });
+        assert_eq!(message_texts(&message), ["hmm", "answer"]);
+
+        refuse_message(&mut message);
+        assert_eq!(message["content"], json!([]));
+        assert_eq!(message["stop_reason"], REFUSAL_STOP_REASON);
+    }
+}
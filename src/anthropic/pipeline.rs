@@ -0,0 +1,257 @@
+//! 流式响应管道
+//!
+//! 所有流式端点共用同一条管道：
+//!
+//! 1. 上游解析：[`open_event_stream`](super::handlers::open_event_stream) 解码 Kiro 事件流，
+//!    生成 Anthropic SSE 事件；
+//! 2. 转换阶段：按顺序经过若干 [`EventStage`]（推理内容过滤、用量采集等），每个阶段可以修改或丢弃事件；
+//! 3. 下游编码：由 [`SseEncoder`] 将事件编码为目标协议的 SSE 文本。
+//!
+//! 新增输出格式只需实现 [`SseEncoder`]，上游解析与用量统计逻辑保持共用。
+
+use std::convert::Infallible;
+
+use axum::response::Response;
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt, future};
+
+use super::handlers::sse_response;
+use super::stream::SseEvent;
+
+/// 转换阶段：处理单个事件，返回 None 表示丢弃该事件
+pub(crate) trait EventStage: Send + 'static {
+    fn process(&mut self, event: SseEvent) -> Option<SseEvent>;
+}
+
+/// 按顺序执行的转换阶段组合
+#[derive(Default)]
+pub(crate) struct Stages(Vec<Box<dyn EventStage>>);
+
+impl Stages {
+    /// 追加一个阶段
+    pub fn with(mut self, stage: impl EventStage) -> Self {
+        self.0.push(Box::new(stage));
+        self
+    }
+
+    /// 将事件依次交给各阶段处理，任一阶段丢弃时返回 None
+    pub fn process(&mut self, event: SseEvent) -> Option<SseEvent> {
+        self.0
+            .iter_mut()
+            .try_fold(event, |event, stage| stage.process(event))
+    }
+
+    /// 将各阶段应用到事件流
+    pub fn apply(
+        mut self,
+        events: impl Stream<Item = SseEvent> + Send + 'static,
+    ) -> impl Stream<Item = SseEvent> + Send + 'static {
+        events.filter_map(move |event| future::ready(self.process(event)))
+    }
+}
+
+/// 下游编码器：将一个流式条目编码为零个或多个 SSE 字符串
+///
+/// 条目通常是 [`SseEvent`]；多路合并的流（如 n > 1 的候选）可以携带额外信息
+pub(crate) trait SseEncoder<T = SseEvent>: Send + 'static {
+    fn encode(&mut self, item: &T) -> Vec<String>;
+
+    /// 将条目编码后追加到缓冲区；默认逐个复制 [`encode`](Self::encode) 的结果，
+    /// 热路径上的编码器可以覆盖此方法直接写入缓冲区
+    fn encode_into(&mut self, item: &T, out: &mut BytesMut) {
+        for chunk in self.encode(item) {
+            out.extend_from_slice(chunk.as_bytes());
+        }
+    }
+}
+
+/// Anthropic 格式：事件原样输出
+pub(crate) struct AnthropicEncoder;
+
+impl SseEncoder for AnthropicEncoder {
+    fn encode(&mut self, event: &SseEvent) -> Vec<String> {
+        vec![event.to_sse_string()]
+    }
+
+    fn encode_into(&mut self, event: &SseEvent, out: &mut BytesMut) {
+        event.write_sse(out);
+    }
+}
+
+/// 编码缓冲区剩余容量不足时一次预留的大小
+const ENCODE_BUFFER_RESERVE: usize = 8 * 1024;
+
+/// 用编码器将条目流转换为 SSE 字节流
+///
+/// 每个条目的输出合并为一个数据块（没有输出的条目不产生数据块），
+/// 各数据块从同一块复用的缓冲区中切出，避免逐条分配。
+pub(crate) fn encode_stream<T, E>(
+    items: impl Stream<Item = T> + Send + 'static,
+    mut encoder: E,
+) -> impl Stream<Item = Result<Bytes, Infallible>> + Send + 'static
+where
+    T: Send + 'static,
+    E: SseEncoder<T>,
+{
+    let mut buffer = BytesMut::new();
+    items.filter_map(move |item| {
+        if buffer.capacity() - buffer.len() < ENCODE_BUFFER_RESERVE / 4 {
+            buffer.reserve(ENCODE_BUFFER_RESERVE);
+        }
+        encoder.encode_into(&item, &mut buffer);
+        let chunk = (!buffer.is_empty()).then(|| Ok(buffer.split().freeze()));
+        future::ready(chunk)
+    })
+}
+
+/// 用编码器将条目流转换为 SSE 响应
+pub(crate) fn sse_stream<T, E>(
+    items: impl Stream<Item = T> + Send + 'static,
+    encoder: E,
+) -> Response
+where
+    T: Send + 'static,
+    E: SseEncoder<T>,
+{
+    sse_response(encode_stream(items, encoder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use serde_json::json;
+
+    /// 丢弃 ping 事件
+    struct DropPing;
+
+    impl EventStage for DropPing {
+        fn process(&mut self, event: SseEvent) -> Option<SseEvent> {
+            (event.event != "ping").then_some(event)
+        }
+    }
+
+    /// 统计经过的事件数并打标记
+    struct Tag(usize);
+
+    impl EventStage for Tag {
+        fn process(&mut self, mut event: SseEvent) -> Option<SseEvent> {
+            self.0 += 1;
+            event.data["seq"] = json!(self.0);
+            Some(event)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stages_run_in_order_and_encoder_formats_output() {
+        let events = stream::iter(vec![
+            SseEvent::new("message_start", json!({})),
+            SseEvent::new("ping", json!({})),
+            SseEvent::new("message_stop", json!({})),
+        ]);
+        let staged = Stages::default().with(DropPing).with(Tag(0)).apply(events);
+
+        let chunks: Vec<Bytes> = encode_stream(staged, AnthropicEncoder)
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(
+            chunks[0],
+            Bytes::from("event: message_start\ndata: {\"seq\":1}\n\n")
+        );
+        assert_eq!(
+            chunks[1],
+            Bytes::from("event: message_stop\ndata: {\"seq\":2}\n\n")
+        );
+    }
+
+    /// 每个事件输出两段，message_stop 不输出
+    struct Twice;
+
+    impl SseEncoder for Twice {
+        fn encode(&mut self, event: &SseEvent) -> Vec<String> {
+            if event.event == "message_stop" {
+                return Vec::new();
+            }
+            vec![format!("data: {}\n\n", event.event); 2]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encoder_output_is_one_chunk_per_item() {
+        let events = stream::iter(vec![
+            SseEvent::new("message_start", json!({})),
+            SseEvent::new("message_stop", json!({})),
+            SseEvent::new("ping", json!({})),
+        ]);
+
+        let chunks: Vec<Bytes> = encode_stream(events, Twice)
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(
+            chunks,
+            vec![
+                Bytes::from("data: message_start\n\ndata: message_start\n\n"),
+                Bytes::from("data: ping\n\ndata: ping\n\n"),
+            ]
+        );
+    }
+
+    /// 流式转换路径（解码上游事件流 → 转换 → 编码 SSE）的吞吐基准
+    ///
+    /// `cargo test --release bench_stream_pipeline -- --ignored --nocapture`
+    #[tokio::test]
+    #[ignore]
+    async fn bench_stream_pipeline() {
+        use crate::anthropic::stream::StreamContext;
+        use crate::kiro::model::events::Event;
+        use crate::kiro::parser::decoder::EventStreamDecoder;
+        use crate::kiro::parser::frame::encode_frame;
+        use std::time::Instant;
+
+        const FRAMES: usize = 2_000;
+        const ROUNDS: usize = 50;
+        let frame = encode_frame(
+            &[
+                (":message-type", "event"),
+                (":event-type", "assistantResponseEvent"),
+                (":content-type", "application/json"),
+            ],
+            br#"{"content":"Hello, world! ","conversationId":"c-123","messageId":"m-456"}"#,
+        );
+        // 上游数据块与帧边界不对齐
+        let body = frame.repeat(FRAMES);
+        let chunks: Vec<Bytes> = body.chunks(1000).map(Bytes::copy_from_slice).collect();
+
+        let started = Instant::now();
+        let mut output_bytes = 0;
+        for _ in 0..ROUNDS {
+            let mut decoder = EventStreamDecoder::new();
+            let mut ctx = StreamContext::new_with_thinking("claude-sonnet-4", 1, false);
+            let mut events = ctx.generate_initial_events();
+            for chunk in &chunks {
+                decoder.feed(chunk.clone()).unwrap();
+                for frame in decoder.decode_iter() {
+                    let event = Event::from_frame(frame.unwrap()).unwrap();
+                    events.extend(ctx.process_kiro_event(&event));
+                }
+            }
+            events.extend(ctx.generate_final_events());
+            output_bytes += encode_stream(stream::iter(events), AnthropicEncoder)
+                .fold(0, |n, chunk| future::ready(n + chunk.unwrap().len()))
+                .await;
+        }
+        let elapsed = started.elapsed();
+        println!(
+            "{} 帧 / {:?}，{:.0} 帧/秒，输出 {} 字节",
+            FRAMES * ROUNDS,
+            elapsed,
+            (FRAMES * ROUNDS) as f64 / elapsed.as_secs_f64(),
+            output_bytes
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! 服务端提示词预设
+//!
+//! 在配置中按名称定义常用的系统提示词、模型、max_tokens 与 temperature，客户端通过以下任一方式选择：
+//! - 模型名称使用 `kiro:<预设名>`（此时预设必须指定 `model`）
+//! - 请求头 `x-kiro-preset: <预设名>`（未指定 `model` 时沿用客户端请求的模型）
+
+use std::collections::HashMap;
+
+use axum::http::HeaderMap;
+
+use crate::model::config::PromptPreset;
+
+use super::types::{MessagesRequest, SystemMessage};
+
+/// 通过模型名称选择预设时使用的前缀
+pub const PRESET_MODEL_PREFIX: &str = "kiro:";
+
+/// 通过请求头选择预设
+pub const PRESET_HEADER: &str = "x-kiro-preset";
+
+/// 按模型名称或请求头选择预设，返回预设名称与预设；未选择预设时返回 None
+fn select<'a>(
+    presets: &'a HashMap<String, PromptPreset>,
+    model: &str,
+    headers: &HeaderMap,
+) -> Result<Option<(String, &'a PromptPreset)>, String> {
+    let from_model = model.strip_prefix(PRESET_MODEL_PREFIX);
+    let from_header = headers.get(PRESET_HEADER).and_then(|v| v.to_str().ok());
+    let Some(name) = from_model.or(from_header).map(str::to_string) else {
+        return Ok(None);
+    };
+
+    let preset = presets
+        .get(&name)
+        .ok_or_else(|| format!("未知的预设: {}", name))?;
+    if preset.model.is_none() && from_model.is_some() {
+        return Err(format!("预设 {} 未指定 model，无法通过模型名称选择", name));
+    }
+    Ok(Some((name, preset)))
+}
+
+/// 应用预设后实际使用的模型（API Key 的模型限制按该模型检查）
+pub fn resolve_model(
+    presets: &HashMap<String, PromptPreset>,
+    model: &str,
+    headers: &HeaderMap,
+) -> Result<String, String> {
+    Ok(select(presets, model, headers)?
+        .and_then(|(_, preset)| preset.model.clone())
+        .unwrap_or_else(|| model.to_string()))
+}
+
+/// 按模型名称或请求头应用预设，返回应用的预设名称
+///
+/// 预设的系统提示词放在客户端系统提示之前，`model`、`maxTokens` 与 `temperature` 覆盖客户端的值
+pub fn apply(
+    presets: &HashMap<String, PromptPreset>,
+    payload: &mut MessagesRequest,
+    headers: &HeaderMap,
+) -> Result<Option<String>, String> {
+    let Some((name, preset)) = select(presets, &payload.model, headers)? else {
+        return Ok(None);
+    };
+
+    if let Some(model) = &preset.model {
+        payload.model = model.clone();
+    }
+    if let Some(max_tokens) = preset.max_tokens {
+        payload.max_tokens = max_tokens;
+    }
+    if let Some(temperature) = preset.temperature {
+        payload.temperature = Some(temperature);
+    }
+    if let Some(system) = preset.system.as_ref().filter(|s| !s.is_empty()) {
+        payload.system.get_or_insert_with(Vec::new).insert(
+            0,
+            SystemMessage {
+                text: system.clone(),
+            },
+        );
+    }
+
+    tracing::debug!("已应用预设: {}", name);
+    Ok(Some(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anthropic::types::Message;
+    use axum::http::HeaderValue;
+
+    fn presets() -> HashMap<String, PromptPreset> {
+        HashMap::from([
+            (
+                "code-review".to_string(),
+                PromptPreset {
+                    system: Some("You are a strict reviewer.".to_string()),
+                    model: Some("claude-sonnet-4.5".to_string()),
+                    max_tokens: Some(2048),
+                    temperature: Some(0.2),
+                },
+            ),
+            (
+                "terse".to_string(),
+                PromptPreset {
+                    system: Some("Be brief.".to_string()),
+                    model: None,
+                    max_tokens: None,
+                    temperature: None,
+                },
+            ),
+        ])
+    }
+
+    fn request(model: &str) -> MessagesRequest {
+        MessagesRequest {
+            model: model.to_string(),
+            max_tokens: 100,
+            temperature: Some(0.7),
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: serde_json::json!("hi"),
+            }],
+            stream: false,
+            system: Some(vec![SystemMessage {
+                text: "client".to_string(),
+            }]),
+            tools: None,
+            tool_choice: None,
+            thinking: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_preset_by_model_name() {
+        let mut payload = request("kiro:code-review");
+        let applied = apply(&presets(), &mut payload, &HeaderMap::new()).unwrap();
+
+        assert_eq!(applied.as_deref(), Some("code-review"));
+        assert_eq!(payload.model, "claude-sonnet-4.5");
+        assert_eq!(payload.max_tokens, 2048);
+        assert_eq!(payload.temperature, Some(0.2));
+        let system = payload.system.unwrap();
+        assert_eq!(system[0].text, "You are a strict reviewer.");
+        assert_eq!(system[1].text, "client");
+    }
+
+    #[test]
+    fn test_apply_preset_by_header_keeps_model() {
+        let mut headers = HeaderMap::new();
+        headers.insert(PRESET_HEADER, HeaderValue::from_static("terse"));
+        let mut payload = request("claude-opus-4.5");
+        apply(&presets(), &mut payload, &headers).unwrap();
+
+        assert_eq!(payload.model, "claude-opus-4.5");
+        assert_eq!(payload.max_tokens, 100);
+        assert_eq!(payload.temperature, Some(0.7));
+        assert_eq!(payload.system.unwrap()[0].text, "Be brief.");
+    }
+
+    #[test]
+    fn test_apply_preset_errors() {
+        let mut payload = request("kiro:missing");
+        assert!(apply(&presets(), &mut payload, &HeaderMap::new()).is_err());
+
+        let mut payload = request("kiro:terse");
+        assert!(apply(&presets(), &mut payload, &HeaderMap::new()).is_err());
+
+        let mut payload = request("claude-sonnet-4.5");
+        assert_eq!(
+            apply(&presets(), &mut payload, &HeaderMap::new()).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resolve_model() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            resolve_model(&presets(), "kiro:code-review", &headers).unwrap(),
+            "claude-sonnet-4.5"
+        );
+        assert_eq!(
+            resolve_model(&presets(), "claude-haiku-4.5", &headers).unwrap(),
+            "claude-haiku-4.5"
+        );
+
+        headers.insert(PRESET_HEADER, HeaderValue::from_static("code-review"));
+        assert_eq!(
+            resolve_model(&presets(), "claude-haiku-4.5", &headers).unwrap(),
+            "claude-sonnet-4.5"
+        );
+        headers.insert(PRESET_HEADER, HeaderValue::from_static("terse"));
+        assert_eq!(
+            resolve_model(&presets(), "claude-haiku-4.5", &headers).unwrap(),
+            "claude-haiku-4.5"
+        );
+    }
+}
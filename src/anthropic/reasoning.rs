@@ -0,0 +1,116 @@
+//! 推理内容（thinking）过滤模块
+//!
+//! 部分客户端无法处理未知的 thinking 块或 reasoning_content 字段。
+//! 启用 `stripReasoning` 后，[`ReasoningFilter`] 会从 SSE 事件序列中移除所有 thinking 块，
+//! 并重新编号后续块的索引，保证客户端看到的块索引仍然连续。
+
+use std::collections::BTreeSet;
+
+use super::pipeline::EventStage;
+use super::stream::SseEvent;
+
+/// thinking 块过滤器
+#[derive(Debug, Default)]
+pub struct ReasoningFilter {
+    /// 是否启用过滤（未启用时所有事件原样通过）
+    enabled: bool,
+    /// 被移除的 thinking 块的原始索引
+    removed: BTreeSet<i64>,
+}
+
+impl ReasoningFilter {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            removed: BTreeSet::new(),
+        }
+    }
+
+    /// 批量过滤事件
+    pub fn filter_all(&mut self, events: Vec<SseEvent>) -> Vec<SseEvent> {
+        if !self.enabled {
+            return events;
+        }
+        events.into_iter().filter_map(|e| self.filter(e)).collect()
+    }
+
+    /// 过滤单个事件，thinking 块相关事件返回 None，其他事件的块索引按需前移
+    pub fn filter(&mut self, mut event: SseEvent) -> Option<SseEvent> {
+        if !self.enabled {
+            return Some(event);
+        }
+        let Some(index) = event.data["index"].as_i64() else {
+            return Some(event);
+        };
+
+        if event.event == "content_block_start" && event.data["content_block"]["type"] == "thinking"
+        {
+            self.removed.insert(index);
+            return None;
+        }
+        if self.removed.contains(&index) {
+            return None;
+        }
+
+        let shift = self.removed.range(..index).count() as i64;
+        if shift > 0 {
+            event.data["index"] = serde_json::json!(index - shift);
+        }
+        Some(event)
+    }
+}
+
+impl EventStage for ReasoningFilter {
+    fn process(&mut self, event: SseEvent) -> Option<SseEvent> {
+        self.filter(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_filter_removes_thinking_and_reindexes() {
+        let mut filter = ReasoningFilter::new(true);
+        let events = vec![
+            SseEvent::new("message_start", json!({"type": "message_start"})),
+            SseEvent::new(
+                "content_block_start",
+                json!({"index": 0, "content_block": {"type": "thinking", "thinking": ""}}),
+            ),
+            SseEvent::new(
+                "content_block_delta",
+                json!({"index": 0, "delta": {"type": "thinking_delta", "thinking": "hmm"}}),
+            ),
+            SseEvent::new("content_block_stop", json!({"index": 0})),
+            SseEvent::new(
+                "content_block_start",
+                json!({"index": 1, "content_block": {"type": "text", "text": ""}}),
+            ),
+            SseEvent::new(
+                "content_block_delta",
+                json!({"index": 1, "delta": {"type": "text_delta", "text": "hi"}}),
+            ),
+        ];
+
+        let kept = filter.filter_all(events);
+
+        assert_eq!(kept.len(), 3);
+        assert_eq!(kept[1].event, "content_block_start");
+        assert_eq!(kept[1].data["index"], 0);
+        assert_eq!(kept[2].data["index"], 0);
+        assert_eq!(kept[2].data["delta"]["text"], "hi");
+    }
+
+    #[test]
+    fn test_disabled_filter_passes_through() {
+        let mut filter = ReasoningFilter::new(false);
+        let event = SseEvent::new(
+            "content_block_start",
+            json!({"index": 0, "content_block": {"type": "thinking", "thinking": ""}}),
+        );
+        assert!(filter.filter(event).is_some());
+    }
+}
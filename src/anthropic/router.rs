@@ -1,59 +1,175 @@
 //! Anthropic API 路由配置
 
+use std::sync::Arc;
+
 use axum::{
-    Router, middleware,
+    Router,
+    extract::DefaultBodyLimit,
+    middleware,
     routing::{get, post},
 };
 
+use crate::batch::handlers::{
+    MAX_FILE_BYTES, cancel_batch, create_batch, get_batch, get_file, get_file_content,
+    list_batches, upload_file,
+};
 use crate::kiro::provider::KiroProvider;
+use crate::metrics::get_metrics;
+use crate::model::config::{Config, RouteGroup};
+use crate::openai::handlers::{post_chat_completions, post_completions, post_responses};
+use crate::usage::UsageStore;
 
 use super::{
     handlers::{count_tokens, get_models, post_messages},
-    middleware::{AppState, auth_middleware, cors_layer},
+    health::{get_deep_health, get_health, get_readyz},
+    middleware::{
+        AppState, admission_middleware, auth_middleware, concurrency_middleware, cors_layer,
+        header_limit_middleware, ip_filter_middleware, ip_rate_limit_middleware,
+        load_shed_middleware, rate_limit_middleware, request_body_middleware, trace_middleware,
+    },
 };
 
-/// 创建 Anthropic API 路由
-///
-/// # 端点
-/// - `GET /v1/models` - 获取可用模型列表
-/// - `POST /v1/messages` - 创建消息（对话）
-/// - `POST /v1/messages/count_tokens` - 计算 token 数量
-///
-/// # 认证
-/// 所有 `/v1` 路径需要 API Key 认证，支持：
-/// - `x-api-key` header
-/// - `Authorization: Bearer <token>` header
+/// 创建带有 KiroProvider 的应用状态，供各监听地址的路由共享
 ///
 /// # 参数
 /// - `api_key`: API 密钥，用于验证客户端请求
 /// - `kiro_provider`: 可选的 KiroProvider，用于调用上游 API
-
-/// 创建带有 KiroProvider 的 Anthropic API 路由
-pub fn create_router_with_provider(
+/// - `config`: 应用配置，供各 handler 读取功能开关
+/// - `usage_store`: 用量记录存储
+pub fn create_app_state(
     api_key: impl Into<String>,
     kiro_provider: Option<KiroProvider>,
     profile_arn: Option<String>,
-) -> Router {
-    let mut state = AppState::new(api_key);
+    config: Config,
+    usage_store: Arc<UsageStore>,
+) -> AppState {
+    let mut state = AppState::new(api_key)
+        .with_config(config)
+        .with_usage_store(usage_store);
     if let Some(provider) = kiro_provider {
         state = state.with_kiro_provider(provider);
     }
     if let Some(arn) = profile_arn {
         state = state.with_profile_arn(arn);
     }
+    state
+}
 
+/// 创建包含指定路由组的 Anthropic API 路由（`Admin` 路由组由调用方挂载）
+///
+/// # 端点
+/// - `GET /v1/models` - 获取可用模型列表
+/// - `POST /v1/messages` - 创建消息（对话）
+/// - `POST /v1/messages/count_tokens` - 计算 token 数量
+/// - `POST /v1/chat/completions` - OpenAI 兼容的对话补全
+/// - `POST /v1/completions` - OpenAI 兼容的旧版文本补全
+/// - `POST /v1/responses` - OpenAI Responses API
+/// - `POST /v1/files` - 上传批处理文件
+/// - `GET /v1/files/{id}`、`GET /v1/files/{id}/content` - 查询 / 下载文件
+/// - `POST /v1/batches`、`GET /v1/batches` - 创建 / 列出批处理任务
+/// - `GET /v1/batches/{id}`、`POST /v1/batches/{id}/cancel` - 查询 / 取消批处理任务
+/// - `GET /metrics` - Prometheus 格式的运行指标
+/// - `GET /health`、`GET /livez` - 存活检查
+/// - `GET /readyz` - 就绪检查（凭据池可用且未在排空）
+/// - `GET /health/deep` - 深度健康检查
+///
+/// # 认证
+/// 配置了 `ipRateLimit` 时 `/v1` 路径先按客户端 IP 限流。
+/// 所有 `/v1` 路径与 `/metrics` 需要 API Key 认证（健康检查无需认证），支持：
+/// - `x-api-key` header
+/// - `Authorization: Bearer <token>` header
+pub fn create_router(state: AppState, groups: &[RouteGroup]) -> Router {
     // 需要认证的 /v1 路由
+    // 对话端点依次检查客户端 IP 访问规则、资源压力降载、按 API Key 的并发限制、全局限流，再进入全局准入队列
+    let body_limit = DefaultBodyLimit::max(state.config.load().max_request_body_bytes);
+    let completion_routes = Router::new()
+        .route("/messages", post(post_messages))
+        .route("/chat/completions", post(post_chat_completions))
+        .route("/completions", post(post_completions))
+        .route("/responses", post(post_responses))
+        .layer(body_limit)
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            request_body_middleware,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            admission_middleware,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit_middleware,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            concurrency_middleware,
+        ))
+        .route_layer(middleware::from_fn(load_shed_middleware))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            ip_filter_middleware,
+        ));
+
+    // 批处理任务在后台逐条执行，每条请求自行经过全局限流与准入队列
+    let batch_routes = Router::new()
+        .route(
+            "/files",
+            post(upload_file).layer(DefaultBodyLimit::max(MAX_FILE_BYTES)),
+        )
+        .route("/files/{id}", get(get_file))
+        .route("/files/{id}/content", get(get_file_content))
+        .route("/batches", post(create_batch).get(list_batches))
+        .route("/batches/{id}", get(get_batch))
+        .route("/batches/{id}/cancel", post(cancel_batch));
+
     let v1_routes = Router::new()
         .route("/models", get(get_models))
-        .route("/messages", post(post_messages))
-        .route("/messages/count_tokens", post(count_tokens))
+        .route(
+            "/messages/count_tokens",
+            post(count_tokens).layer(body_limit),
+        )
+        .merge(completion_routes)
+        .merge(batch_routes)
         .layer(middleware::from_fn_with_state(
             state.clone(),
             auth_middleware,
+        ))
+        // 按 IP 限流在认证之前，同时限制猜测 API Key 的请求
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            ip_rate_limit_middleware,
         ));
 
-    Router::new()
-        .nest("/v1", v1_routes)
-        .layer(cors_layer())
+    let metrics_routes =
+        Router::new()
+            .route("/metrics", get(get_metrics))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                auth_middleware,
+            ));
+
+    let health_routes = Router::new()
+        .route("/health", get(get_health))
+        .route("/livez", get(get_health))
+        .route("/readyz", get(get_readyz))
+        .route("/health/deep", get(get_deep_health));
+
+    let mut router = Router::new();
+    if groups.contains(&RouteGroup::Api) {
+        router = router.nest("/v1", v1_routes);
+    }
+    if groups.contains(&RouteGroup::Metrics) {
+        router = router.merge(metrics_routes);
+    }
+    if groups.contains(&RouteGroup::Health) {
+        router = router.merge(health_routes);
+    }
+    router
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            header_limit_middleware,
+        ))
+        .layer(cors_layer(state.config.load().cors.as_ref()))
+        .layer(middleware::from_fn(trace_middleware))
         .with_state(state)
 }
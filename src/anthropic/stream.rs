@@ -4,6 +4,8 @@
 
 use std::collections::HashMap;
 
+use bytes::{BufMut, BytesMut};
+
 use serde_json::json;
 use uuid::Uuid;
 
@@ -188,11 +190,21 @@ impl SseEvent {
 
     /// 格式化为 SSE 字符串
     pub fn to_sse_string(&self) -> String {
-        format!(
-            "event: {}\ndata: {}\n\n",
-            self.event,
-            serde_json::to_string(&self.data).unwrap_or_default()
-        )
+        let mut out = BytesMut::new();
+        self.write_sse(&mut out);
+        String::from_utf8(out.to_vec()).unwrap_or_default()
+    }
+
+    /// 以 SSE 格式追加到缓冲区，数据直接序列化进缓冲区而不经过中间字符串
+    pub fn write_sse(&self, out: &mut BytesMut) {
+        out.extend_from_slice(b"event: ");
+        out.extend_from_slice(self.event.as_bytes());
+        out.extend_from_slice(b"\ndata: ");
+        let start = out.len();
+        if serde_json::to_writer(out.writer(), &self.data).is_err() {
+            out.truncate(start);
+        }
+        out.extend_from_slice(b"\n\n");
     }
 }
 
@@ -465,6 +477,11 @@ pub struct StreamContext {
     pub output_tokens: i32,
     /// 工具块索引映射 (tool_id -> block_index)
     pub tool_block_indices: HashMap<String, i32>,
+    /// 等待输出的并行工具调用事件
+    ///
+    /// 上游可能交错发送多个工具调用的增量，而 Anthropic SSE 要求内容块依次 start/delta/stop，
+    /// 因此其他工具块打开期间到达的事件会暂存在这里，待当前工具块结束后按顺序回放。
+    pending_tool_events: Vec<crate::kiro::model::events::ToolUseEvent>,
     /// thinking 是否启用
     pub thinking_enabled: bool,
     /// thinking 内容缓冲区
@@ -477,6 +494,8 @@ pub struct StreamContext {
     pub thinking_block_index: Option<i32>,
     /// 文本块索引（thinking 启用时动态分配）
     pub text_block_index: Option<i32>,
+    /// 是否收到上游的错误 / 异常事件
+    pub upstream_error: bool,
 }
 
 impl StreamContext {
@@ -494,12 +513,14 @@ impl StreamContext {
             context_input_tokens: None,
             output_tokens: 0,
             tool_block_indices: HashMap::new(),
+            pending_tool_events: Vec::new(),
             thinking_enabled,
             thinking_buffer: String::new(),
             in_thinking_block: false,
             thinking_extracted: false,
             thinking_block_index: None,
             text_block_index: None,
+            upstream_error: false,
         }
     }
 
@@ -586,6 +607,7 @@ impl StreamContext {
                 error_message,
             } => {
                 tracing::error!("收到错误事件: {} - {}", error_code, error_message);
+                self.upstream_error = true;
                 Vec::new()
             }
             Event::Exception {
@@ -595,6 +617,8 @@ impl StreamContext {
                 // 处理 ContentLengthExceededException
                 if exception_type == "ContentLengthExceededException" {
                     self.state_manager.set_stop_reason("max_tokens");
+                } else {
+                    self.upstream_error = true;
                 }
                 tracing::warn!("收到异常事件: {} - {}", exception_type, message);
                 Vec::new()
@@ -879,6 +903,15 @@ impl StreamContext {
             events.extend(self.create_text_delta_events(&buffered));
         }
 
+        // 其他工具块仍在输出时，暂存当前事件，待其结束后再回放
+        if self
+            .open_tool_use_id()
+            .is_some_and(|id| id != tool_use.tool_use_id)
+        {
+            self.pending_tool_events.push(tool_use.clone());
+            return events;
+        }
+
         // 获取或分配块索引
         let block_index = if let Some(&idx) = self.tool_block_indices.get(&tool_use.tool_use_id) {
             idx
@@ -930,8 +963,40 @@ impl StreamContext {
             if let Some(stop_event) = self.state_manager.handle_content_block_stop(block_index) {
                 events.push(stop_event);
             }
+            events.extend(self.flush_pending_tool_events());
+        }
+
+        events
+    }
+
+    /// 当前处于打开状态的工具块对应的 tool_use_id
+    /// 上游响应是否异常结束（需在生成最终事件之前调用）
+    ///
+    /// 收到错误 / 异常事件，或仍有工具调用未收到结束标记时返回原因
+    pub fn truncation(&self) -> Option<&'static str> {
+        if self.upstream_error {
+            Some("上游返回错误事件")
+        } else if self.open_tool_use_id().is_some() || !self.pending_tool_events.is_empty() {
+            Some("工具调用未结束")
+        } else {
+            None
         }
+    }
+
+    fn open_tool_use_id(&self) -> Option<&str> {
+        self.tool_block_indices
+            .iter()
+            .find(|(_, idx)| self.state_manager.is_block_open_of_type(**idx, "tool_use"))
+            .map(|(id, _)| id.as_str())
+    }
 
+    /// 按到达顺序回放暂存的并行工具调用事件
+    fn flush_pending_tool_events(&mut self) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        let pending = std::mem::take(&mut self.pending_tool_events);
+        for tool_use in &pending {
+            events.extend(self.process_tool_use(tool_use));
+        }
         events
     }
 
@@ -939,6 +1004,19 @@ impl StreamContext {
     pub fn generate_final_events(&mut self) -> Vec<SseEvent> {
         let mut events = Vec::new();
 
+        // 输出仍在暂存的并行工具调用（上游未发送 stop 时，由下方统一关闭）
+        while !self.pending_tool_events.is_empty() {
+            if let Some(idx) = self
+                .open_tool_use_id()
+                .and_then(|id| self.tool_block_indices.get(id).copied())
+            {
+                if let Some(stop_event) = self.state_manager.handle_content_block_stop(idx) {
+                    events.push(stop_event);
+                }
+            }
+            events.extend(self.flush_pending_tool_events());
+        }
+
         // Flush thinking_buffer 中的剩余内容
         if self.thinking_enabled && !self.thinking_buffer.is_empty() {
             if self.in_thinking_block {
@@ -1220,6 +1298,79 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_interleaved_parallel_tool_uses_are_serialized_into_blocks() {
+        let mut ctx = StreamContext::new_with_thinking("test-model", 1, false);
+        let _ = ctx.generate_initial_events();
+
+        let tool = |id: &str, input: &str, stop: bool| crate::kiro::model::events::ToolUseEvent {
+            name: "Read".to_string(),
+            tool_use_id: id.to_string(),
+            input: input.to_string(),
+            stop,
+        };
+
+        let mut events = Vec::new();
+        events.extend(ctx.process_tool_use(&tool("tool_a", "{\"path\":", false)));
+        events.extend(ctx.process_tool_use(&tool("tool_b", "{\"path\":", false)));
+        events.extend(ctx.process_tool_use(&tool("tool_a", "\"a.rs\"}", true)));
+        events.extend(ctx.process_tool_use(&tool("tool_b", "\"b.rs\"}", true)));
+        events.extend(ctx.generate_final_events());
+
+        let index_a = ctx.tool_block_indices["tool_a"] as i64;
+        let index_b = ctx.tool_block_indices["tool_b"] as i64;
+        assert_ne!(index_a, index_b);
+
+        // 工具块必须依次 start/delta/stop，不能交错
+        let block_events: Vec<(String, i64)> = events
+            .iter()
+            .filter(|e| e.event.starts_with("content_block_"))
+            .filter_map(|e| e.data["index"].as_i64().map(|i| (e.event.clone(), i)))
+            .filter(|(_, i)| *i == index_a || *i == index_b)
+            .collect();
+        let stop_a = block_events
+            .iter()
+            .position(|(e, i)| e == "content_block_stop" && *i == index_a)
+            .unwrap();
+        let start_b = block_events
+            .iter()
+            .position(|(e, i)| e == "content_block_start" && *i == index_b)
+            .unwrap();
+        assert!(
+            stop_a < start_b,
+            "second tool block must start after the first one stops"
+        );
+
+        let args_b: String = events
+            .iter()
+            .filter(|e| e.data["index"].as_i64() == Some(index_b))
+            .filter_map(|e| e.data["delta"]["partial_json"].as_str())
+            .collect();
+        assert_eq!(args_b, "{\"path\":\"b.rs\"}");
+
+        let message_delta = events.iter().find(|e| e.event == "message_delta").unwrap();
+        assert_eq!(message_delta.data["delta"]["stop_reason"], "tool_use");
+    }
+
+    #[test]
+    fn test_truncation_detects_unterminated_tool_use() {
+        let mut ctx = StreamContext::new_with_thinking("test-model", 1, false);
+        let _ = ctx.generate_initial_events();
+        assert_eq!(ctx.truncation(), None);
+
+        let tool = |stop: bool| crate::kiro::model::events::ToolUseEvent {
+            name: "Read".to_string(),
+            tool_use_id: "tool_a".to_string(),
+            input: "{}".to_string(),
+            stop,
+        };
+        let _ = ctx.process_tool_use(&tool(false));
+        assert_eq!(ctx.truncation(), Some("工具调用未结束"));
+
+        let _ = ctx.process_tool_use(&tool(true));
+        assert_eq!(ctx.truncation(), None);
+    }
+
     #[test]
     fn test_estimate_tokens() {
         assert!(estimate_tokens("Hello") > 0);
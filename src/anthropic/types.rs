@@ -6,17 +6,20 @@ use std::collections::HashMap;
 // === 错误响应 ===
 
 /// API 错误响应
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ErrorResponse {
     pub error: ErrorDetail,
 }
 
 /// 错误详情
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ErrorDetail {
     #[serde(rename = "type")]
     pub error_type: String,
     pub message: String,
+    /// 机器可读的错误码（如 `context_length_exceeded`），Anthropic 原生错误不携带
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
 }
 
 impl ErrorResponse {
@@ -26,6 +29,7 @@ impl ErrorResponse {
             error: ErrorDetail {
                 error_type: error_type.into(),
                 message: message.into(),
+                code: None,
             },
         }
     }
@@ -34,6 +38,19 @@ impl ErrorResponse {
     pub fn authentication_error() -> Self {
         Self::new("authentication_error", "Invalid API key")
     }
+
+    /// 创建输入超出上下文长度的错误响应
+    pub fn context_length_exceeded(input_tokens: i32, context_window_tokens: u64) -> Self {
+        let mut error = Self::new(
+            "invalid_request_error",
+            format!(
+                "prompt is too long: {} tokens > {} maximum",
+                input_tokens, context_window_tokens
+            ),
+        );
+        error.error.code = Some("context_length_exceeded".to_string());
+        error
+    }
 }
 
 // === Models 端点类型 ===
@@ -48,7 +65,10 @@ pub struct Model {
     pub display_name: String,
     #[serde(rename = "type")]
     pub model_type: String,
+    /// 最大输出 tokens
     pub max_tokens: i32,
+    /// 上下文窗口（tokens）
+    pub context_window: u64,
 }
 
 /// 模型列表响应
@@ -61,7 +81,10 @@ pub struct ModelsResponse {
 // === Messages 端点类型 ===
 
 /// 最大思考预算 tokens
-const MAX_BUDGET_TOKENS: i32 = 24576;
+pub const MAX_BUDGET_TOKENS: i32 = 24576;
+
+/// 最小思考预算 tokens（与 Anthropic API 的下限一致）
+pub const MIN_BUDGET_TOKENS: i32 = 1024;
 
 /// Thinking 配置
 #[derive(Debug, Deserialize, Clone)]
@@ -75,6 +98,16 @@ pub struct Thinking {
     pub budget_tokens: i32,
 }
 
+impl Thinking {
+    /// 创建启用状态的 Thinking 配置，预算限制在上游支持的范围内
+    pub fn enabled(budget_tokens: i32) -> Self {
+        Self {
+            thinking_type: "enabled".to_string(),
+            budget_tokens: budget_tokens.clamp(MIN_BUDGET_TOKENS, MAX_BUDGET_TOKENS),
+        }
+    }
+}
+
 fn default_budget_tokens() -> i32 {
     20000
 }
@@ -83,7 +116,7 @@ where
     D: serde::Deserializer<'de>,
 {
     let value = i32::deserialize(deserializer)?;
-    Ok(value.min(MAX_BUDGET_TOKENS))
+    Ok(value.clamp(MIN_BUDGET_TOKENS, MAX_BUDGET_TOKENS))
 }
 
 /// Claude Code 请求中的 metadata
@@ -98,6 +131,8 @@ pub struct Metadata {
 pub struct MessagesRequest {
     pub model: String,
     pub max_tokens: i32,
+    /// 采样温度（0~1，可由预设覆盖）
+    pub temperature: Option<f32>,
     pub messages: Vec<Message>,
     #[serde(default)]
     pub stream: bool,
@@ -0,0 +1,279 @@
+//! `/v1/files` 与 `/v1/batches` 端点处理器
+
+use axum::{
+    Extension,
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Json, Response},
+};
+use serde::Deserialize;
+
+use crate::anthropic::middleware::{ApiKeyLabel, AppState};
+use crate::common::auth;
+use crate::openai::extract::OpenAiJson;
+use crate::openai::types::OpenAiErrorResponse;
+
+use super::runner::{self, SUPPORTED_ENDPOINT};
+use super::store::{Batch, BatchStatus};
+
+/// 上传文件的大小上限
+pub const MAX_FILE_BYTES: usize = 100 * 1024 * 1024;
+
+/// 创建批处理任务请求
+#[derive(Debug, Deserialize)]
+pub struct CreateBatchRequest {
+    pub input_file_id: String,
+    pub endpoint: String,
+    #[serde(default = "default_completion_window")]
+    pub completion_window: String,
+    pub metadata: Option<serde_json::Value>,
+}
+
+fn default_completion_window() -> String {
+    "24h".to_string()
+}
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    let error_type = if status == StatusCode::NOT_FOUND {
+        "not_found_error"
+    } else {
+        "invalid_request_error"
+    };
+    (status, Json(OpenAiErrorResponse::new(error_type, message))).into_response()
+}
+
+/// POST /v1/files
+///
+/// 支持 `multipart/form-data`（OpenAI SDK 的上传方式，字段 `file` 与 `purpose`），
+/// 也可以直接以请求体上传 JSONL 内容
+pub async fn upload_file(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    let (filename, purpose, content) = if content_type.starts_with("multipart/form-data") {
+        match parse_multipart(content_type, &body) {
+            Some(form) => (
+                form.filename.unwrap_or_else(|| "upload.jsonl".to_string()),
+                form.purpose.unwrap_or_else(|| "batch".to_string()),
+                body.slice_ref(form.file),
+            ),
+            None => return error(StatusCode::BAD_REQUEST, "multipart 请求中缺少 file 字段"),
+        }
+    } else {
+        ("upload.jsonl".to_string(), "batch".to_string(), body)
+    };
+
+    let file = state.batches.add_file(filename, purpose, content);
+    tracing::info!("已上传文件 {}（{} 字节）", file.id, file.content.len());
+    Json(file.object()).into_response()
+}
+
+/// GET /v1/files/{id}
+pub async fn get_file(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.batches.file(&id) {
+        Some(file) => Json(file.object()).into_response(),
+        None => error(StatusCode::NOT_FOUND, format!("文件不存在: {}", id)),
+    }
+}
+
+/// GET /v1/files/{id}/content
+pub async fn get_file_content(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.batches.file(&id) {
+        Some(file) => ([(header::CONTENT_TYPE, "application/jsonl")], file.content).into_response(),
+        None => error(StatusCode::NOT_FOUND, format!("文件不存在: {}", id)),
+    }
+}
+
+/// POST /v1/batches
+///
+/// 校验输入文件后在后台执行，立即返回 `in_progress` 状态的任务；
+/// 文件格式错误时任务直接标记为 `failed`，`errors` 中列出出错的行
+pub async fn create_batch(
+    State(state): State<AppState>,
+    Extension(api_key): Extension<ApiKeyLabel>,
+    headers: HeaderMap,
+    OpenAiJson(payload): OpenAiJson<CreateBatchRequest>,
+) -> Response {
+    if payload.endpoint != SUPPORTED_ENDPOINT {
+        return error(
+            StatusCode::BAD_REQUEST,
+            format!("批处理仅支持 {} 端点", SUPPORTED_ENDPOINT),
+        );
+    }
+    let Some(file) = state.batches.file(&payload.input_file_id) else {
+        return error(
+            StatusCode::NOT_FOUND,
+            format!("文件不存在: {}", payload.input_file_id),
+        );
+    };
+
+    let mut batch = Batch::new(
+        payload.endpoint,
+        payload.input_file_id,
+        payload.completion_window,
+        payload.metadata,
+    );
+    match runner::parse_input(&file.content) {
+        Ok(lines) => {
+            batch.status = BatchStatus::InProgress;
+            batch.in_progress_at = Some(chrono::Utc::now().timestamp());
+            batch.request_counts.total = lines.len();
+            let id = batch.id.clone();
+            state.batches.insert_batch(batch.clone());
+            let key = auth::api_key_from_headers(&headers).unwrap_or_default();
+            tokio::spawn(runner::run(state.clone(), id, lines, key, api_key));
+        }
+        Err(errors) => {
+            tracing::warn!("批处理文件 {} 校验失败: {} 处错误", file.id, errors.len());
+            batch.status = BatchStatus::Failed;
+            batch.failed_at = Some(chrono::Utc::now().timestamp());
+            batch.errors = Some(serde_json::json!({ "object": "list", "data": errors }));
+            state.batches.insert_batch(batch.clone());
+        }
+    }
+    Json(batch).into_response()
+}
+
+/// GET /v1/batches
+pub async fn list_batches(State(state): State<AppState>) -> Response {
+    let batches = state.batches.batches();
+    Json(serde_json::json!({
+        "object": "list",
+        "first_id": batches.first().map(|b| &b.id),
+        "last_id": batches.last().map(|b| &b.id),
+        "has_more": false,
+        "data": batches,
+    }))
+    .into_response()
+}
+
+/// GET /v1/batches/{id}
+pub async fn get_batch(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.batches.batch(&id) {
+        Some(batch) => Json(batch).into_response(),
+        None => error(StatusCode::NOT_FOUND, format!("批处理任务不存在: {}", id)),
+    }
+}
+
+/// POST /v1/batches/{id}/cancel
+///
+/// 已开始的请求会继续完成，尚未开始的请求不再执行
+pub async fn cancel_batch(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    let now = chrono::Utc::now().timestamp();
+    let updated = state.batches.update_batch(&id, |b| {
+        if !b.is_finished() {
+            b.status = BatchStatus::Cancelling;
+            b.cancelling_at = Some(now);
+        }
+    });
+    match updated {
+        Some(batch) => Json(batch).into_response(),
+        None => error(StatusCode::NOT_FOUND, format!("批处理任务不存在: {}", id)),
+    }
+}
+
+/// multipart 表单中与上传相关的字段
+struct UploadForm<'a> {
+    file: &'a [u8],
+    filename: Option<String>,
+    purpose: Option<String>,
+}
+
+/// 解析 multipart/form-data 请求体，只提取 `file` 与 `purpose` 字段
+fn parse_multipart<'a>(content_type: &str, body: &'a [u8]) -> Option<UploadForm<'a>> {
+    let boundary = content_type
+        .split(';')
+        .filter_map(|p| p.trim().strip_prefix("boundary="))
+        .next()?
+        .trim_matches('"');
+    let delimiter = format!("--{}", boundary);
+
+    let mut file = None;
+    let mut filename = None;
+    let mut purpose = None;
+
+    let mut rest = body;
+    while let Some(start) = find(rest, delimiter.as_bytes()) {
+        rest = &rest[start + delimiter.len()..];
+        // 结束标记 `--boundary--`
+        if rest.starts_with(b"--") {
+            break;
+        }
+        let part_end = find(rest, delimiter.as_bytes()).unwrap_or(rest.len());
+        let part = &rest[..part_end];
+        let Some(header_end) = find(part, b"\r\n\r\n") else {
+            continue;
+        };
+        let part_headers = String::from_utf8_lossy(&part[..header_end]);
+        // 内容与下一个分隔符之间有一个 CRLF
+        let content = part[header_end + 4..]
+            .strip_suffix(b"\r\n")
+            .unwrap_or(&part[header_end + 4..]);
+
+        match disposition_param(&part_headers, "name").as_deref() {
+            Some("file") => {
+                filename = disposition_param(&part_headers, "filename");
+                file = Some(content);
+            }
+            Some("purpose") => purpose = Some(String::from_utf8_lossy(content).into_owned()),
+            _ => {}
+        }
+    }
+
+    Some(UploadForm {
+        file: file?,
+        filename,
+        purpose,
+    })
+}
+
+/// 提取 Content-Disposition 头中的参数值
+fn disposition_param(headers: &str, key: &str) -> Option<String> {
+    let line = headers
+        .lines()
+        .find(|l| l.to_ascii_lowercase().starts_with("content-disposition"))?;
+    line.split(';').find_map(|p| {
+        let (k, v) = p.trim().split_once('=')?;
+        (k == key).then(|| v.trim_matches('"').to_string())
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_multipart_upload() {
+        let body = b"--XyZ\r\n\
+Content-Disposition: form-data; name=\"purpose\"\r\n\r\n\
+batch\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"file\"; filename=\"requests.jsonl\"\r\n\
+Content-Type: application/octet-stream\r\n\r\n\
+{\"a\":1}\n{\"b\":2}\n\r\n\
+--XyZ--\r\n";
+        let form = parse_multipart("multipart/form-data; boundary=XyZ", body).unwrap();
+
+        assert_eq!(form.file, b"{\"a\":1}\n{\"b\":2}\n");
+        assert_eq!(form.filename.as_deref(), Some("requests.jsonl"));
+        assert_eq!(form.purpose.as_deref(), Some("batch"));
+    }
+
+    #[test]
+    fn test_parse_multipart_without_file() {
+        let body =
+            b"--b\r\nContent-Disposition: form-data; name=\"purpose\"\r\n\r\nbatch\r\n--b--\r\n";
+        assert!(parse_multipart("multipart/form-data; boundary=b", body).is_none());
+    }
+}
@@ -0,0 +1,12 @@
+//! 批处理模块
+//!
+//! 提供 OpenAI 风格的 `/v1/files` 与 `/v1/batches` 端点：上传 JSONL 格式的批处理文件后创建批处理任务，
+//! 任务在后台逐条调用 `/v1/chat/completions` 的处理流程，结果写入输出文件供下载。
+//!
+//! 文件与任务仅保存在内存中，进程重启后丢失。
+
+pub mod handlers;
+mod runner;
+mod store;
+
+pub use store::BatchStore;
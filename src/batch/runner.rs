@@ -0,0 +1,389 @@
+//! 批处理任务执行
+//!
+//! 逐条调用 `/v1/chat/completions` 的处理流程（强制非流式），并发数按凭据池容量计算；
+//! 每条请求同样经过全局限流器、所属 API Key 的并发上限与准入队列（以低优先级排队），不会挤占交互式流量；
+//! 请求体与交互式请求一样经过消息数上限、改写规则、护栏与内容审核（[`check_request_body`]），
+//! 被拒绝的请求写入错误文件。
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    Extension,
+    extract::State,
+    http::{HeaderMap, HeaderValue},
+    response::Response,
+};
+use futures::StreamExt;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::anthropic::middleware::{ApiKeyLabel, AppState, admission_capacity, check_request_body};
+use crate::model::config::Priority;
+use crate::openai::extract::OpenAiJson;
+use crate::openai::handlers::post_chat_completions;
+use crate::openai::types::ChatCompletionRequest;
+
+use super::store::BatchStatus;
+
+/// 批处理支持的端点
+pub const SUPPORTED_ENDPOINT: &str = "/v1/chat/completions";
+
+/// 准入队列拒绝或 API Key 并发已满时的重试间隔
+const ADMISSION_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// 批处理文件中的一行
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchLine {
+    pub custom_id: String,
+    pub method: String,
+    pub url: String,
+    pub body: serde_json::Value,
+}
+
+/// 解析并校验批处理文件，失败时返回 OpenAI 风格的错误列表
+pub fn parse_input(content: &[u8]) -> Result<Vec<BatchLine>, Vec<serde_json::Value>> {
+    let text = String::from_utf8_lossy(content);
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+
+    for (index, raw) in text.lines().enumerate() {
+        if raw.trim().is_empty() {
+            continue;
+        }
+        let line_no = index + 1;
+        let error = |code: &str, message: String| json!({ "code": code, "message": message, "param": null, "line": line_no });
+        match serde_json::from_str::<BatchLine>(raw) {
+            Ok(line) if line.method != "POST" => errors.push(error(
+                "invalid_method",
+                format!("不支持的请求方法: {}", line.method),
+            )),
+            Ok(line) if line.url != SUPPORTED_ENDPOINT => errors.push(error(
+                "invalid_url",
+                format!("仅支持 {}，实际: {}", SUPPORTED_ENDPOINT, line.url),
+            )),
+            Ok(line) => lines.push(line),
+            Err(e) => errors.push(error("invalid_json_line", e.to_string())),
+        }
+    }
+
+    if lines.is_empty() && errors.is_empty() {
+        errors.push(json!({ "code": "empty_file", "message": "批处理文件为空", "param": null, "line": null }));
+    }
+    if errors.is_empty() {
+        Ok(lines)
+    } else {
+        Err(errors)
+    }
+}
+
+/// 单条请求的执行结果
+struct LineResult {
+    index: usize,
+    custom_id: String,
+    status: u16,
+    body: serde_json::Value,
+}
+
+impl LineResult {
+    fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// 输出文件中的一行
+    fn to_line(&self) -> String {
+        let request_id = format!("batch_req_{}", uuid::Uuid::new_v4().simple());
+        let error = if self.is_success() {
+            serde_json::Value::Null
+        } else {
+            self.body["error"].clone()
+        };
+        let line = json!({
+            "id": request_id,
+            "custom_id": self.custom_id,
+            "response": {
+                "status_code": self.status,
+                "request_id": request_id,
+                "body": self.body
+            },
+            "error": error
+        });
+        format!("{}\n", line)
+    }
+}
+
+/// 执行批处理任务，完成后写入输出文件与错误文件
+///
+/// `key` 为创建任务时使用的原始 API Key（用于并发上限与改写规则），`api_key` 为其脱敏形式
+pub async fn run(
+    state: AppState,
+    batch_id: String,
+    lines: Vec<BatchLine>,
+    key: String,
+    api_key: ApiKeyLabel,
+) {
+    let available = state
+        .kiro_provider
+        .as_ref()
+        .map(|p| p.token_manager().available_count())
+        .unwrap_or(0)
+        .max(1);
+    let parallelism = state
+        .config
+        .load()
+        .max_concurrent_per_credential
+        .unwrap_or(1)
+        * available;
+    tracing::info!(
+        "批处理任务 {} 开始执行，共 {} 条请求，并发 {}",
+        batch_id,
+        lines.len(),
+        parallelism
+    );
+
+    let results = Arc::new(Mutex::new(Vec::with_capacity(lines.len())));
+    futures::stream::iter(lines.into_iter().enumerate())
+        .for_each_concurrent(parallelism, |(index, line)| {
+            let state = state.clone();
+            let batch_id = batch_id.clone();
+            let key = key.clone();
+            let api_key = api_key.clone();
+            let results = results.clone();
+            async move {
+                if is_cancelling(&state, &batch_id) {
+                    return;
+                }
+                let (status, body) = execute(&state, &line, &key, api_key).await;
+                let result = LineResult {
+                    index,
+                    custom_id: line.custom_id,
+                    status,
+                    body,
+                };
+                state.batches.update_batch(&batch_id, |b| {
+                    if result.is_success() {
+                        b.request_counts.completed += 1;
+                    } else {
+                        b.request_counts.failed += 1;
+                    }
+                });
+                results.lock().push(result);
+            }
+        })
+        .await;
+
+    let mut results = std::mem::take(&mut *results.lock());
+    results.sort_by_key(|r| r.index);
+    let (succeeded, failed): (Vec<_>, Vec<_>) = results.iter().partition(|r| r.is_success());
+
+    let output_file_id = (!succeeded.is_empty()).then(|| {
+        let content: String = succeeded.iter().map(|r| r.to_line()).collect();
+        let filename = format!("{}_output.jsonl", batch_id);
+        state
+            .batches
+            .add_file(filename, "batch_output", content.into())
+            .id
+    });
+    let error_file_id = (!failed.is_empty()).then(|| {
+        let content: String = failed.iter().map(|r| r.to_line()).collect();
+        let filename = format!("{}_error.jsonl", batch_id);
+        state
+            .batches
+            .add_file(filename, "batch_output", content.into())
+            .id
+    });
+
+    let now = chrono::Utc::now().timestamp();
+    if let Some(batch) = state.batches.update_batch(&batch_id, |b| {
+        b.output_file_id = output_file_id;
+        b.error_file_id = error_file_id;
+        if b.status == BatchStatus::Cancelling {
+            b.status = BatchStatus::Cancelled;
+            b.cancelled_at = Some(now);
+        } else {
+            b.status = BatchStatus::Completed;
+            b.completed_at = Some(now);
+        }
+    }) {
+        tracing::info!(
+            "批处理任务 {} 已结束（{:?}），成功 {} 条，失败 {} 条",
+            batch_id,
+            batch.status,
+            batch.request_counts.completed,
+            batch.request_counts.failed
+        );
+    }
+}
+
+fn is_cancelling(state: &AppState, batch_id: &str) -> bool {
+    state
+        .batches
+        .batch(batch_id)
+        .is_none_or(|b| b.status == BatchStatus::Cancelling)
+}
+
+/// 执行单条请求，返回状态码与响应体
+async fn execute(
+    state: &AppState,
+    line: &BatchLine,
+    key: &str,
+    api_key: ApiKeyLabel,
+) -> (u16, serde_json::Value) {
+    let invalid = |e: serde_json::Error| {
+        (
+            400,
+            json!({ "error": { "message": e.to_string(), "type": "invalid_request_error", "param": null, "code": null } }),
+        )
+    };
+    if let Err(e) = serde_json::from_value::<ChatCompletionRequest>(line.body.clone()) {
+        return invalid(e);
+    }
+
+    // 与交互式请求共享全局限流额度
+    while let Err(wait) = state.rate_limiter.check() {
+        tokio::time::sleep(wait).await;
+    }
+
+    // 与交互式请求共享所属 API Key 的并发上限，已满时稍后重试
+    let _concurrency = loop {
+        match state.concurrency.try_acquire(key) {
+            Some(permit) => break permit,
+            None => tokio::time::sleep(ADMISSION_RETRY_DELAY).await,
+        }
+    };
+
+    // 配置了准入队列时以低优先级排队，队列满或超时后稍后重试
+    let _permit = match admission_capacity(state) {
+        Some(_) => loop {
+            let capacity = admission_capacity(state).unwrap_or(1);
+            match state.admission.acquire(capacity, Priority::Low).await {
+                Ok(permit) => break Some(permit),
+                Err(_) => tokio::time::sleep(ADMISSION_RETRY_DELAY).await,
+            }
+        },
+        None => None,
+    };
+
+    let mut body = line.body.clone();
+    if let Err(response) =
+        check_request_body(state, SUPPORTED_ENDPOINT, key, &api_key.0, &mut body).await
+    {
+        return read_response(response).await;
+    }
+    let mut payload: ChatCompletionRequest = match serde_json::from_value(body) {
+        Ok(payload) => payload,
+        Err(e) => return invalid(e),
+    };
+    payload.stream = false;
+
+    // 携带原始 Key，`n` > 1 时额外的上游调用计入同一个 Key 的并发上限
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(key) {
+        headers.insert("x-api-key", value);
+    }
+    let response = post_chat_completions(
+        State(state.clone()),
+        Extension(api_key),
+        Some(Extension(Priority::Low)),
+        headers,
+        OpenAiJson(payload),
+    )
+    .await;
+    read_response(response).await
+}
+
+/// 读取响应的状态码与 JSON 响应体
+async fn read_response(response: Response) -> (u16, serde_json::Value) {
+    let status = response.status().as_u16();
+    let body = match axum::body::to_bytes(response.into_body(), usize::MAX).await {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null),
+        Err(e) => {
+            json!({ "error": { "message": e.to_string(), "type": "api_error", "param": null, "code": null } })
+        }
+    };
+    (status, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_input_valid_lines() {
+        let content = br#"{"custom_id": "a", "method": "POST", "url": "/v1/chat/completions", "body": {"model": "m", "messages": []}}
+
+{"custom_id": "b", "method": "POST", "url": "/v1/chat/completions", "body": {"model": "m", "messages": []}}
+"#;
+        let lines = parse_input(content).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].custom_id, "b");
+    }
+
+    #[test]
+    fn test_parse_input_reports_line_errors() {
+        let content = br#"{"custom_id": "a", "method": "POST", "url": "/v1/embeddings", "body": {}}
+not json
+"#;
+        let errors = parse_input(content).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0]["code"], "invalid_url");
+        assert_eq!(errors[0]["line"], 1);
+        assert_eq!(errors[1]["code"], "invalid_json_line");
+        assert_eq!(errors[1]["line"], 2);
+
+        assert_eq!(parse_input(b"\n").unwrap_err()[0]["code"], "empty_file");
+    }
+
+    #[tokio::test]
+    async fn test_blocked_line_goes_to_error_file() {
+        let mut config = crate::model::config::Config::default();
+        config.guardrails.blocked_patterns = vec!["(?i)forbidden".to_string()];
+        let state = AppState::new("sk-main-key-000").with_config(config);
+        let line = |id: &str, text: &str| BatchLine {
+            custom_id: id.to_string(),
+            method: "POST".to_string(),
+            url: SUPPORTED_ENDPOINT.to_string(),
+            body: json!({"model": "m", "messages": [{"role": "user", "content": text}]}),
+        };
+        let batch = super::super::store::Batch::new(
+            SUPPORTED_ENDPOINT.to_string(),
+            "file-1".to_string(),
+            "24h".to_string(),
+            None,
+        );
+        let batch_id = batch.id.clone();
+        state.batches.insert_batch(batch);
+
+        run(
+            state.clone(),
+            batch_id.clone(),
+            vec![line("blocked", "a FORBIDDEN prompt")],
+            "sk-main-key-000".to_string(),
+            ApiKeyLabel("sk-m***000".to_string()),
+        )
+        .await;
+
+        let batch = state.batches.batch(&batch_id).unwrap();
+        assert_eq!(batch.request_counts.failed, 1);
+        assert!(batch.output_file_id.is_none());
+        let error_file = state.batches.file(&batch.error_file_id.unwrap()).unwrap();
+        let result: serde_json::Value = serde_json::from_slice(&error_file.content).unwrap();
+        assert_eq!(result["custom_id"], "blocked");
+        assert_eq!(result["response"]["status_code"], 400);
+    }
+
+    #[test]
+    fn test_line_result_error_field() {
+        let failed = LineResult {
+            index: 0,
+            custom_id: "a".to_string(),
+            status: 400,
+            body: json!({"error": {"message": "bad"}}),
+        };
+        let line: serde_json::Value = serde_json::from_str(&failed.to_line()).unwrap();
+        assert_eq!(line["custom_id"], "a");
+        assert_eq!(line["response"]["status_code"], 400);
+        assert_eq!(line["error"]["message"], "bad");
+    }
+}
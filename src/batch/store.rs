@@ -0,0 +1,257 @@
+//! 批处理文件与任务存储
+
+use std::collections::{HashMap, HashSet};
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// 上传的文件
+#[derive(Debug, Clone)]
+pub struct StoredFile {
+    pub id: String,
+    pub filename: String,
+    pub purpose: String,
+    pub created_at: i64,
+    pub content: Bytes,
+}
+
+impl StoredFile {
+    /// OpenAI 文件对象
+    pub fn object(&self) -> serde_json::Value {
+        serde_json::json!({
+            "id": self.id,
+            "object": "file",
+            "bytes": self.content.len(),
+            "created_at": self.created_at,
+            "filename": self.filename,
+            "purpose": self.purpose,
+        })
+    }
+}
+
+/// 批处理任务状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Validating,
+    InProgress,
+    Completed,
+    Failed,
+    Cancelling,
+    Cancelled,
+}
+
+/// 请求计数
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RequestCounts {
+    pub total: usize,
+    pub completed: usize,
+    pub failed: usize,
+}
+
+/// 批处理任务（字段与 OpenAI batch 对象一致）
+#[derive(Debug, Clone, Serialize)]
+pub struct Batch {
+    pub id: String,
+    pub object: &'static str,
+    pub endpoint: String,
+    pub errors: Option<serde_json::Value>,
+    pub input_file_id: String,
+    pub completion_window: String,
+    pub status: BatchStatus,
+    pub output_file_id: Option<String>,
+    pub error_file_id: Option<String>,
+    pub created_at: i64,
+    pub in_progress_at: Option<i64>,
+    pub completed_at: Option<i64>,
+    pub failed_at: Option<i64>,
+    pub cancelling_at: Option<i64>,
+    pub cancelled_at: Option<i64>,
+    pub request_counts: RequestCounts,
+    pub metadata: Option<serde_json::Value>,
+}
+
+impl Batch {
+    pub fn new(
+        endpoint: impl Into<String>,
+        input_file_id: impl Into<String>,
+        completion_window: impl Into<String>,
+        metadata: Option<serde_json::Value>,
+    ) -> Self {
+        Self {
+            id: format!("batch_{}", uuid::Uuid::new_v4().simple()),
+            object: "batch",
+            endpoint: endpoint.into(),
+            errors: None,
+            input_file_id: input_file_id.into(),
+            completion_window: completion_window.into(),
+            status: BatchStatus::Validating,
+            output_file_id: None,
+            error_file_id: None,
+            created_at: chrono::Utc::now().timestamp(),
+            in_progress_at: None,
+            completed_at: None,
+            failed_at: None,
+            cancelling_at: None,
+            cancelled_at: None,
+            request_counts: RequestCounts::default(),
+            metadata,
+        }
+    }
+
+    /// 是否已结束（不会再变化）
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.status,
+            BatchStatus::Completed | BatchStatus::Failed | BatchStatus::Cancelled
+        )
+    }
+
+    /// 结束时间（未结束时为 `None`）
+    fn finished_at(&self) -> Option<i64> {
+        if !self.is_finished() {
+            return None;
+        }
+        Some(
+            self.completed_at
+                .or(self.failed_at)
+                .or(self.cancelled_at)
+                .unwrap_or(self.created_at),
+        )
+    }
+
+    /// 任务引用的输入、结果与错误文件
+    fn file_ids(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.input_file_id.as_str())
+            .chain(self.output_file_id.as_deref())
+            .chain(self.error_file_id.as_deref())
+    }
+}
+
+/// 批处理文件与任务的内存存储
+#[derive(Default)]
+pub struct BatchStore {
+    files: Mutex<HashMap<String, StoredFile>>,
+    batches: Mutex<HashMap<String, Batch>>,
+}
+
+impl BatchStore {
+    /// 保存文件，返回文件对象
+    pub fn add_file(
+        &self,
+        filename: impl Into<String>,
+        purpose: impl Into<String>,
+        content: Bytes,
+    ) -> StoredFile {
+        let file = StoredFile {
+            id: format!("file-{}", uuid::Uuid::new_v4().simple()),
+            filename: filename.into(),
+            purpose: purpose.into(),
+            created_at: chrono::Utc::now().timestamp(),
+            content,
+        };
+        self.files.lock().insert(file.id.clone(), file.clone());
+        file
+    }
+
+    pub fn file(&self, id: &str) -> Option<StoredFile> {
+        self.files.lock().get(id).cloned()
+    }
+
+    pub fn insert_batch(&self, batch: Batch) {
+        self.batches.lock().insert(batch.id.clone(), batch);
+    }
+
+    pub fn batch(&self, id: &str) -> Option<Batch> {
+        self.batches.lock().get(id).cloned()
+    }
+
+    /// 所有任务（最新的在前）
+    pub fn batches(&self) -> Vec<Batch> {
+        let mut batches: Vec<Batch> = self.batches.lock().values().cloned().collect();
+        batches.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+        batches
+    }
+
+    /// 修改任务，返回修改后的快照
+    pub fn update_batch(&self, id: &str, f: impl FnOnce(&mut Batch)) -> Option<Batch> {
+        let mut batches = self.batches.lock();
+        let batch = batches.get_mut(id)?;
+        f(batch);
+        Some(batch.clone())
+    }
+
+    /// 删除在 `before`（Unix 秒）之前结束的任务，以及早于 `before` 且不再被任何任务引用的文件；
+    /// 未结束任务及其文件保留。返回删除的任务与文件总数
+    pub fn prune(&self, before: i64) -> usize {
+        let mut batches = self.batches.lock();
+        let mut files = self.files.lock();
+        let batch_count = batches.len();
+        batches.retain(|_, batch| batch.finished_at().is_none_or(|at| at >= before));
+        let referenced: HashSet<&str> = batches.values().flat_map(Batch::file_ids).collect();
+        let file_count = files.len();
+        files.retain(|id, file| file.created_at >= before || referenced.contains(id.as_str()));
+        (batch_count - batches.len()) + (file_count - files.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_files_and_batches() {
+        let store = BatchStore::default();
+        let file = store.add_file("in.jsonl", "batch", Bytes::from_static(b"{}\n"));
+        assert!(file.id.starts_with("file-"));
+        assert_eq!(store.file(&file.id).unwrap().object()["bytes"], 3);
+
+        let batch = Batch::new("/v1/chat/completions", &file.id, "24h", None);
+        let id = batch.id.clone();
+        store.insert_batch(batch);
+
+        let updated = store
+            .update_batch(&id, |b| b.status = BatchStatus::Completed)
+            .unwrap();
+        assert!(updated.is_finished());
+        assert_eq!(
+            serde_json::to_value(&updated).unwrap()["status"],
+            "completed"
+        );
+        assert_eq!(store.batches().len(), 1);
+        assert!(store.update_batch("batch_missing", |_| {}).is_none());
+    }
+
+    #[test]
+    fn test_prune_keeps_running_batches_and_their_files() {
+        let store = BatchStore::default();
+        let old_input = store.add_file("old.jsonl", "batch", Bytes::from_static(b"{}\n"));
+        let old_output = store.add_file("out.jsonl", "batch_output", Bytes::new());
+        let running_input = store.add_file("run.jsonl", "batch", Bytes::from_static(b"{}\n"));
+        let orphan = store.add_file("orphan.jsonl", "batch", Bytes::new());
+
+        let mut done = Batch::new("/v1/chat/completions", &old_input.id, "24h", None);
+        done.status = BatchStatus::Completed;
+        done.completed_at = Some(done.created_at);
+        done.output_file_id = Some(old_output.id.clone());
+        let done_id = done.id.clone();
+        store.insert_batch(done);
+        let mut running = Batch::new("/v1/chat/completions", &running_input.id, "24h", None);
+        running.status = BatchStatus::InProgress;
+        let running_id = running.id.clone();
+        store.insert_batch(running);
+
+        // 截止时间之前没有任何内容
+        assert_eq!(store.prune(old_input.created_at - 1), 0);
+
+        let removed = store.prune(chrono::Utc::now().timestamp() + 1);
+        assert_eq!(removed, 4);
+        assert!(store.batch(&done_id).is_none());
+        assert!(store.file(&old_input.id).is_none());
+        assert!(store.file(&old_output.id).is_none());
+        assert!(store.file(&orphan.id).is_none());
+        assert!(store.batch(&running_id).is_some());
+        assert!(store.file(&running_input.id).is_some());
+    }
+}
@@ -0,0 +1,287 @@
+//! 压测模式
+//!
+//! 以固定并发向运行中的实例持续发送合成的 `/v1/messages` 请求（按比例混合流式与非流式），
+//! 结束后汇总吞吐、错误率与延迟分位数，用于可重复地衡量代理路径的性能回退。
+//! 流式请求额外统计首字节时间；压测流量与正常流量一样经过认证、限流与上游调用。
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use futures::StreamExt;
+use serde::Serialize;
+use serde_json::json;
+use tokio::time::Instant;
+
+/// 单个请求的超时时间
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// 压测参数
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// 实例地址（含路由前缀），如 `http://127.0.0.1:8080`
+    pub url: String,
+    pub api_key: String,
+    pub concurrency: usize,
+    pub duration: Duration,
+    /// 流式请求占比（0 ~ 1）
+    pub stream_ratio: f64,
+    pub model: String,
+    pub max_tokens: u32,
+}
+
+/// 单个请求的结果
+#[derive(Debug, Clone)]
+struct Sample {
+    stream: bool,
+    /// 成功时为 None，失败时为错误分类（如 `HTTP 503`）
+    error: Option<String>,
+    latency: Duration,
+    /// 流式请求的首字节时间
+    first_byte: Option<Duration>,
+}
+
+/// 延迟分位数（毫秒）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Percentiles {
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+/// 压测报告
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchReport {
+    pub requests: u64,
+    pub streaming_requests: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+    pub requests_per_second: f64,
+    pub elapsed_secs: f64,
+    /// 成功请求的总耗时
+    pub latency_ms: Percentiles,
+    /// 成功的流式请求的首字节时间
+    pub first_byte_ms: Percentiles,
+    /// 按错误分类的次数
+    pub error_kinds: BTreeMap<String, u64>,
+}
+
+impl BenchReport {
+    fn from_samples(samples: &[Sample], elapsed: Duration) -> Self {
+        let requests = samples.len() as u64;
+        let mut error_kinds = BTreeMap::new();
+        for error in samples.iter().filter_map(|s| s.error.as_ref()) {
+            *error_kinds.entry(error.clone()).or_insert(0) += 1;
+        }
+        let errors: u64 = error_kinds.values().sum();
+        let ok = || samples.iter().filter(|s| s.error.is_none());
+        let elapsed_secs = elapsed.as_secs_f64();
+        Self {
+            requests,
+            streaming_requests: samples.iter().filter(|s| s.stream).count() as u64,
+            errors,
+            error_rate: if requests > 0 {
+                errors as f64 / requests as f64
+            } else {
+                0.0
+            },
+            requests_per_second: if elapsed_secs > 0.0 {
+                requests as f64 / elapsed_secs
+            } else {
+                0.0
+            },
+            elapsed_secs,
+            latency_ms: percentiles(ok().map(|s| s.latency).collect()),
+            first_byte_ms: percentiles(ok().filter_map(|s| s.first_byte).collect()),
+            error_kinds,
+        }
+    }
+}
+
+/// 计算分位数（最近秩法），没有样本时全为 0
+fn percentiles(mut values: Vec<Duration>) -> Percentiles {
+    if values.is_empty() {
+        return Percentiles::default();
+    }
+    values.sort_unstable();
+    let at = |q: f64| {
+        let rank = ((q * values.len() as f64).ceil() as usize).clamp(1, values.len());
+        values[rank - 1].as_secs_f64() * 1000.0
+    };
+    Percentiles {
+        p50: at(0.5),
+        p90: at(0.9),
+        p99: at(0.99),
+        max: at(1.0),
+    }
+}
+
+/// 第 `seq` 个请求是否为流式：按占比均匀分布，而非随机抽样，保证每次压测的流量构成一致
+fn is_stream(seq: u64, ratio: f64) -> bool {
+    let ratio = ratio.clamp(0.0, 1.0);
+    ((seq + 1) as f64 * ratio).floor() > (seq as f64 * ratio).floor()
+}
+
+/// 执行压测
+pub async fn run(options: BenchOptions) -> anyhow::Result<BenchReport> {
+    let client = reqwest::Client::builder()
+        .pool_max_idle_per_host(options.concurrency)
+        .timeout(REQUEST_TIMEOUT)
+        .no_proxy()
+        .build()?;
+    let url = format!("{}/v1/messages", options.url.trim_end_matches('/'));
+    let options = Arc::new(options);
+    let seq = Arc::new(AtomicU64::new(0));
+    let started = Instant::now();
+    let deadline = started + options.duration;
+
+    let workers: Vec<_> = (0..options.concurrency.max(1))
+        .map(|_| {
+            let client = client.clone();
+            let url = url.clone();
+            let options = options.clone();
+            let seq = seq.clone();
+            tokio::spawn(async move {
+                let mut samples = Vec::new();
+                while Instant::now() < deadline {
+                    let stream =
+                        is_stream(seq.fetch_add(1, Ordering::Relaxed), options.stream_ratio);
+                    samples.push(send(&client, &url, &options, stream).await);
+                }
+                samples
+            })
+        })
+        .collect();
+
+    let mut samples = Vec::new();
+    for worker in workers {
+        samples.extend(worker.await?);
+    }
+    Ok(BenchReport::from_samples(&samples, started.elapsed()))
+}
+
+/// 发送一个请求并读完响应体
+async fn send(client: &reqwest::Client, url: &str, options: &BenchOptions, stream: bool) -> Sample {
+    let body = json!({
+        "model": options.model,
+        "max_tokens": options.max_tokens,
+        "stream": stream,
+        "messages": [{"role": "user", "content": "Reply with the single word: ok"}],
+    });
+    let started = Instant::now();
+    let mut sample = Sample {
+        stream,
+        error: None,
+        latency: Duration::ZERO,
+        first_byte: None,
+    };
+
+    let result = async {
+        let response = client
+            .post(url)
+            .header("x-api-key", &options.api_key)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| transport_error(&e))?;
+        let status = response.status();
+        let mut chunks = response.bytes_stream();
+        while let Some(chunk) = chunks.next().await {
+            chunk.map_err(|e| transport_error(&e))?;
+            sample.first_byte.get_or_insert_with(|| started.elapsed());
+        }
+        if !status.is_success() {
+            return Err(format!("HTTP {}", status.as_u16()));
+        }
+        Ok(())
+    }
+    .await;
+
+    sample.latency = started.elapsed();
+    if !stream {
+        sample.first_byte = None;
+    }
+    sample.error = result.err();
+    sample
+}
+
+/// 传输错误的分类
+fn transport_error(e: &reqwest::Error) -> String {
+    if e.is_timeout() {
+        "timeout".to_string()
+    } else if e.is_connect() {
+        "connect error".to_string()
+    } else {
+        "transport error".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, http::StatusCode, routing::post};
+
+    #[test]
+    fn test_percentiles() {
+        let values = (1..=100).map(Duration::from_millis).collect();
+        let p = percentiles(values);
+        assert_eq!(p.p50, 50.0);
+        assert_eq!(p.p90, 90.0);
+        assert_eq!(p.p99, 99.0);
+        assert_eq!(p.max, 100.0);
+        assert_eq!(percentiles(Vec::new()), Percentiles::default());
+    }
+
+    #[test]
+    fn test_stream_ratio_is_spread_evenly() {
+        let streams = (0..100).filter(|&i| is_stream(i, 0.3)).count();
+        assert_eq!(streams, 30);
+        assert!(!(0..10).any(|i| is_stream(i, 0.0)));
+        assert!((0..10).all(|i| is_stream(i, 1.0)));
+    }
+
+    #[tokio::test]
+    async fn test_run_against_local_server() {
+        // 偶数序号的请求返回 503
+        let counter = Arc::new(AtomicU64::new(0));
+        let app = Router::new().route(
+            "/v1/messages",
+            post(move || {
+                let n = counter.fetch_add(1, Ordering::Relaxed);
+                async move {
+                    if n.is_multiple_of(2) {
+                        (StatusCode::SERVICE_UNAVAILABLE, "overloaded")
+                    } else {
+                        (StatusCode::OK, "{}")
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let report = run(BenchOptions {
+            url: format!("http://{}", addr),
+            api_key: "key".to_string(),
+            concurrency: 2,
+            duration: Duration::from_millis(200),
+            stream_ratio: 0.5,
+            model: "claude-haiku-4-5".to_string(),
+            max_tokens: 16,
+        })
+        .await
+        .unwrap();
+
+        assert!(report.requests > 0);
+        assert_eq!(
+            report.errors,
+            report.error_kinds.get("HTTP 503").copied().unwrap_or(0)
+        );
+        assert!(report.errors > 0 && report.errors < report.requests);
+        assert!(report.latency_ms.max > 0.0);
+    }
+}
@@ -0,0 +1,618 @@
+//! 运维子命令
+//!
+//! 无需启动服务或调用 Admin API 即可完成的常见操作：生成配置模板、校验配置、哈希 API Key、加密配置值、通过设备授权添加凭据、
+//! 查询凭据余额、导出用量记录、校验审计日志、压测运行中的实例、重放请求与管理 Windows 服务。结果输出到标准输出，失败时以非零状态码退出，便于脚本调用。
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::Utc;
+
+use crate::admin::{AdminService, audit};
+use crate::bench;
+use crate::common::auth;
+use crate::database::{Database, RequestLogQuery};
+use crate::http_client::ProxyConfig;
+use crate::kiro::device_auth::{BUILDER_ID_START_URL, DeviceAuthorization};
+use crate::kiro::model::credentials::CredentialsConfig;
+use crate::kiro::token_manager::MultiTokenManager;
+use crate::model::arg::{Command, ExportFormat, ServiceAction};
+use crate::model::config::{Config, ConfigFormat};
+use crate::model::secret;
+use crate::model::validation::{self, Problem};
+use crate::replay;
+use crate::service;
+use crate::usage::{UsageRecord, UsageStore, timeseries};
+
+/// 执行子命令（`serve`、`init`、`encrypt-value` 与 `service run` 由调用方处理）
+pub async fn run(
+    command: Command,
+    config: Config,
+    config_path: &str,
+    credentials_path: &str,
+) -> anyhow::Result<()> {
+    match command {
+        Command::Serve
+        | Command::Init { .. }
+        | Command::EncryptValue { .. }
+        | Command::Service {
+            action: ServiceAction::Run { .. },
+        } => Ok(()),
+        Command::ValidateConfig => validate_config(&config, config_path, credentials_path),
+        Command::HashKeys { key } => hash_keys(config_path, key.as_deref()),
+        Command::AddCredential {
+            start_url,
+            priority,
+            tags,
+        } => add_credential(config, credentials_path, start_url, priority, tags).await,
+        Command::CheckBalance { id, json } => {
+            check_balance(config, credentials_path, id, json).await
+        }
+        Command::ExportStats {
+            since,
+            format,
+            output,
+        } => export_stats(&config, since.as_deref(), format, output.as_deref()),
+        Command::VerifyAuditLog { path } => verify_audit_log(&config, path.as_deref()),
+        Command::Bench {
+            url,
+            api_key,
+            concurrency,
+            duration,
+            stream_ratio,
+            model,
+            max_tokens,
+            json,
+        } => {
+            let (url, api_key) = target(&config, url, api_key)?;
+            let duration = timeseries::parse_duration(&duration)
+                .and_then(|d| d.to_std().ok())
+                .ok_or_else(|| anyhow::anyhow!("无效的时长: {}", duration))?;
+            if !(0.0..=1.0).contains(&stream_ratio) {
+                anyhow::bail!("--stream-ratio 应在 0 ~ 1 之间");
+            }
+            let options = bench::BenchOptions {
+                url,
+                api_key,
+                concurrency,
+                duration,
+                stream_ratio,
+                model,
+                max_tokens,
+            };
+            run_bench(options, json).await
+        }
+        Command::Replay {
+            database,
+            request_ids,
+            since,
+            failed,
+            limit,
+            url,
+            api_key,
+            dry_run,
+        } => {
+            let since = match since {
+                Some(since) => Some(
+                    Utc::now()
+                        - timeseries::parse_duration(&since)
+                            .ok_or_else(|| anyhow::anyhow!("无效的时长: {}", since))?,
+                ),
+                None => None,
+            };
+            let query = RequestLogQuery {
+                request_ids,
+                since,
+                failed_only: failed,
+                limit: Some(limit),
+            };
+            let database = database
+                .or_else(|| config.database_path.clone())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("未配置 databasePath，请用 --database 指定请求日志所在的数据库")
+                })?;
+            let replayer = if dry_run {
+                None
+            } else {
+                let (url, api_key) = target(&config, url, api_key)?;
+                Some(replay::Replayer::new(&url, api_key)?)
+            };
+            run_replay(&database, &query, replayer.as_ref()).await
+        }
+        Command::Service {
+            action: ServiceAction::Install { name, display_name },
+        } => service::install(&name, &display_name, config_path, credentials_path),
+        Command::Service {
+            action: ServiceAction::Uninstall { name },
+        } => service::uninstall(&name),
+    }
+}
+
+/// 配置文件模板
+const CONFIG_TEMPLATE: &str = include_str!("model/config_template.jsonc");
+
+/// 生成配置文件模板与空的凭证文件（在加载配置之前执行，已有的配置文件无效时也可用 `--force` 覆盖）
+pub fn init(config_path: &str, credentials_path: &str, force: bool) -> anyhow::Result<()> {
+    if ConfigFormat::from_path(Path::new(config_path)) != ConfigFormat::Json {
+        anyhow::bail!("init 只生成 JSON 格式的配置模板，请用 -c 指定 .json 文件");
+    }
+    if !force {
+        for path in [config_path, credentials_path] {
+            if std::path::Path::new(path).exists() {
+                anyhow::bail!("{} 已存在，使用 --force 覆盖", path);
+            }
+        }
+    }
+    let api_key = format!("sk-kiro-{}", uuid::Uuid::new_v4().simple());
+    write_private(
+        config_path,
+        &CONFIG_TEMPLATE.replace("{{API_KEY}}", &auth::hash_api_key(&api_key)),
+    )?;
+    println!("已生成配置文件 {}", config_path);
+    println!("apiKey: {}（配置中只保存哈希，请妥善保存）", api_key);
+    write_private(credentials_path, "[]\n")?;
+    println!("已生成凭证文件 {}", credentials_path);
+    println!("使用 `kiro-rs add-credential` 添加凭据，或在凭证文件中填写 refreshToken");
+    Ok(())
+}
+
+/// 写入仅当前用户可读写的文件（Unix 下权限为 0600）
+fn write_private(path: &str, content: &str) -> anyhow::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // 覆盖已有文件时 mode 不生效，单独设置
+        if let Ok(metadata) = std::fs::metadata(path) {
+            let mut permissions = metadata.permissions();
+            permissions.set_mode(0o600);
+            std::fs::set_permissions(path, permissions)?;
+        }
+    }
+    let mut file = options
+        .open(path)
+        .map_err(|e| anyhow::anyhow!("写入 {} 失败: {}", path, e))?;
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+/// 输出指定 Key 的哈希；未指定时原地替换配置文件中的明文 Key（保留注释与格式）
+/// 用主密钥加密一个配置值（在加载配置之前执行，配置中已有的加密值无需能够解密）
+pub fn encrypt_value(value: Option<String>) -> anyhow::Result<()> {
+    let master_key = secret::master_key()?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "未设置主密钥，请设置 {} 或 {}",
+            secret::MASTER_KEY_ENV,
+            secret::MASTER_KEY_FILE_ENV
+        )
+    })?;
+    let value = match value {
+        Some(value) => value,
+        None => {
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            input.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    if value.is_empty() {
+        anyhow::bail!("要加密的值为空");
+    }
+    println!("{}", secret::encrypt(&master_key, &value)?);
+    Ok(())
+}
+
+fn hash_keys(config_path: &str, key: Option<&str>) -> anyhow::Result<()> {
+    if let Some(key) = key {
+        println!("{}", auth::hash_api_key(key));
+        return Ok(());
+    }
+    if ConfigFormat::from_path(Path::new(config_path)) != ConfigFormat::Json {
+        anyhow::bail!(
+            "hash-keys 只能改写 JSON 配置文件，TOML/YAML 配置请用 `kiro-rs hash-keys <KEY>` 生成哈希后手动替换"
+        );
+    }
+    let text = std::fs::read_to_string(config_path)
+        .map_err(|e| anyhow::anyhow!("读取 {} 失败: {}", config_path, e))?;
+    let (migrated, count) = crate::model::config::hash_plaintext_keys(&text)?;
+    if count == 0 {
+        println!("{} 中没有明文保存的 API Key", config_path);
+        return Ok(());
+    }
+    write_private(config_path, &migrated)?;
+    println!(
+        "已将 {} 个 API Key 替换为哈希，原始 Key 不再保存在 {} 中",
+        count, config_path
+    );
+    println!("环境变量（如 KIRO_API_KEY）中的 Key 需自行替换为 `kiro-rs hash-keys <KEY>` 的输出");
+    Ok(())
+}
+
+fn validate_config(
+    config: &Config,
+    config_path: &str,
+    credentials_path: &str,
+) -> anyhow::Result<()> {
+    // 配置已由调用方加载（语法与类型错误在加载时报告），这里重新读取只为收集警告
+    let mut problems = Config::load_checked(config_path)
+        .map(|(_, warnings)| warnings)
+        .unwrap_or_default();
+    problems.extend(validation::check_config(config));
+
+    match CredentialsConfig::load(credentials_path) {
+        Ok(credentials) => {
+            println!(
+                "凭证文件: {}（{} 个凭据）",
+                credentials_path,
+                credentials.len()
+            );
+            problems.extend(validation::check_credentials(&credentials));
+        }
+        Err(e) => problems.push(Problem::error(
+            "credentials",
+            format!("无法解析凭证文件 {}: {}", credentials_path, e),
+        )),
+    }
+
+    let errors = problems.iter().filter(|p| p.is_error()).count();
+    for problem in &problems {
+        let label = if problem.is_error() {
+            "错误"
+        } else {
+            "警告"
+        };
+        println!("  - [{}] {}", label, problem);
+    }
+    if errors == 0 {
+        let addresses: Vec<String> = config
+            .effective_listeners()
+            .into_iter()
+            .map(|l| l.address)
+            .collect();
+        println!("配置有效，监听地址 {}", addresses.join(", "));
+        return Ok(());
+    }
+    anyhow::bail!("发现 {} 个配置错误", errors)
+}
+
+async fn add_credential(
+    config: Config,
+    credentials_path: &str,
+    start_url: Option<String>,
+    priority: u32,
+    tags: Vec<String>,
+) -> anyhow::Result<()> {
+    let existing = CredentialsConfig::load(credentials_path)?;
+    if !existing.is_multiple() {
+        println!("凭证文件将转换为多凭据（数组）格式");
+    }
+
+    let proxy = ProxyConfig::from_config(&config);
+    let start_url = start_url.as_deref().unwrap_or(BUILDER_ID_START_URL);
+    let authorization = DeviceAuthorization::start(
+        &config.endpoints.oidc(&config.region),
+        start_url,
+        proxy.as_ref(),
+    )
+    .await?;
+    println!("请在浏览器中打开以下地址完成授权：");
+    println!("  {}", authorization.verification_uri());
+    println!("验证码: {}", authorization.user_code());
+    println!("等待授权...");
+    let mut credentials = authorization.wait().await?;
+    credentials.priority = priority;
+    credentials.tags = tags;
+
+    let manager = MultiTokenManager::new(
+        config,
+        existing.into_sorted_credentials(),
+        proxy,
+        Some(credentials_path.into()),
+        true,
+    )?;
+    let id = manager.add_credential(credentials).await?;
+    println!("已添加凭据 #{} 到 {}", id, credentials_path);
+    Ok(())
+}
+
+async fn check_balance(
+    config: Config,
+    credentials_path: &str,
+    id: Option<u64>,
+    json: bool,
+) -> anyhow::Result<()> {
+    let credentials = CredentialsConfig::load(credentials_path)?;
+    let is_multiple = credentials.is_multiple();
+    let proxy = ProxyConfig::from_config(&config);
+    let manager = MultiTokenManager::new(
+        config,
+        credentials.into_sorted_credentials(),
+        proxy,
+        Some(credentials_path.into()),
+        is_multiple,
+    )?;
+    let ids: Vec<u64> = match id {
+        Some(id) => vec![id],
+        None => manager.snapshot().entries.iter().map(|e| e.id).collect(),
+    };
+    let service = AdminService::new(Arc::new(manager), Arc::new(UsageStore::in_memory(0)));
+
+    let mut failed = 0;
+    for id in ids {
+        match service.get_balance(id).await {
+            Ok(balance) if json => println!("{}", serde_json::to_string(&balance)?),
+            Ok(balance) => println!(
+                "#{} {} 已用 {:.2} / {:.2}（{:.1}%），剩余 {:.2}",
+                balance.id,
+                balance.subscription_title.as_deref().unwrap_or("-"),
+                balance.current_usage,
+                balance.usage_limit,
+                balance.usage_percentage,
+                balance.remaining
+            ),
+            Err(e) => {
+                failed += 1;
+                eprintln!("#{} 查询失败: {}", id, e);
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} 个凭据查询余额失败", failed);
+    }
+    Ok(())
+}
+
+fn export_stats(
+    config: &Config,
+    since: Option<&str>,
+    format: ExportFormat,
+    output: Option<&str>,
+) -> anyhow::Result<()> {
+    let start = match since {
+        Some(since) => {
+            let window = timeseries::parse_duration(since)
+                .ok_or_else(|| anyhow::anyhow!("无效的时长: {}", since))?;
+            Utc::now() - window
+        }
+        None => chrono::DateTime::<Utc>::MIN_UTC,
+    };
+    let store = match (&config.database_path, &config.usage_log_path) {
+        (Some(path), _) => UsageStore::with_database(Arc::new(Database::open(path)?), usize::MAX)?,
+        (None, Some(path)) => UsageStore::open(path, usize::MAX)?,
+        (None, None) => {
+            anyhow::bail!("未配置 databasePath 或 usageLogPath，没有可导出的用量记录")
+        }
+    };
+    let records = store.between(start, chrono::DateTime::<Utc>::MAX_UTC);
+
+    let mut out: Box<dyn Write> = match output {
+        Some(output) => Box::new(std::io::BufWriter::new(std::fs::File::create(output)?)),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    match format {
+        ExportFormat::Csv => {
+            writeln!(
+                out,
+                "timestamp,endpoint,model,apiKey,credentialId,inputTokens,outputTokens,latencyMs,status,stream,user,guardrail"
+            )?;
+            for record in &records {
+                writeln!(out, "{}", csv_line(record))?;
+            }
+        }
+        ExportFormat::Jsonl => {
+            for record in &records {
+                writeln!(out, "{}", serde_json::to_string(record)?)?;
+            }
+        }
+    }
+    out.flush()?;
+    if let Some(output) = output {
+        println!("已导出 {} 条用量记录到 {}", records.len(), output);
+    }
+    Ok(())
+}
+
+/// 压测与重放的目标实例地址与 API Key：未指定地址时按配置访问本机（监听所有地址时改用回环地址），
+/// 未指定 Key 时使用配置中的明文 `apiKey`
+fn target(
+    config: &Config,
+    url: Option<String>,
+    api_key: Option<String>,
+) -> anyhow::Result<(String, String)> {
+    let url = url.unwrap_or_else(|| {
+        let host = match config.host.as_str() {
+            "0.0.0.0" => "127.0.0.1",
+            "::" | "[::]" => "[::1]",
+            host => host,
+        };
+        format!("http://{}:{}{}", host, config.port, config.route_prefix())
+    });
+    let api_key = match api_key.or_else(|| config.api_key.clone()) {
+        Some(key) if !auth::is_hashed(&key) => key,
+        _ => anyhow::bail!("配置中没有明文的 apiKey，请通过 --api-key 指定"),
+    };
+    Ok((url, api_key))
+}
+
+async fn run_bench(options: bench::BenchOptions, json: bool) -> anyhow::Result<()> {
+    eprintln!(
+        "压测 {}：并发 {}，时长 {} 秒，流式占比 {:.0}%",
+        options.url,
+        options.concurrency,
+        options.duration.as_secs(),
+        options.stream_ratio * 100.0
+    );
+    let report = bench::run(options).await?;
+    if json {
+        println!("{}", serde_json::to_string(&report)?);
+        return Ok(());
+    }
+    println!(
+        "请求 {}（流式 {}），{:.1} 请求/秒，错误 {}（{:.2}%）",
+        report.requests,
+        report.streaming_requests,
+        report.requests_per_second,
+        report.errors,
+        report.error_rate * 100.0
+    );
+    let latency = report.latency_ms;
+    println!(
+        "延迟 ms：p50 {:.1}  p90 {:.1}  p99 {:.1}  max {:.1}",
+        latency.p50, latency.p90, latency.p99, latency.max
+    );
+    if report.streaming_requests > 0 {
+        let first_byte = report.first_byte_ms;
+        println!(
+            "首字节 ms：p50 {:.1}  p90 {:.1}  p99 {:.1}  max {:.1}",
+            first_byte.p50, first_byte.p90, first_byte.p99, first_byte.max
+        );
+    }
+    for (kind, count) in &report.error_kinds {
+        println!("  {}: {}", kind, count);
+    }
+    Ok(())
+}
+
+async fn run_replay(
+    database: &str,
+    query: &RequestLogQuery,
+    replayer: Option<&replay::Replayer>,
+) -> anyhow::Result<()> {
+    if !Path::new(database).exists() {
+        anyhow::bail!("数据库不存在: {}", database);
+    }
+    let logs = Database::open(database)?.request_logs(query)?;
+    if logs.is_empty() {
+        anyhow::bail!("没有符合条件的请求日志（需要启用 sampling.logBodies）");
+    }
+    let outcomes = replay::run(&logs, replayer, |outcome| {
+        match serde_json::to_string(outcome) {
+            Ok(line) => println!("{}", line),
+            Err(e) => eprintln!("{} 输出结果失败: {}", outcome.request_id, e),
+        }
+    })
+    .await;
+    let failed = outcomes.iter().filter(|o| o.failed()).count();
+    eprintln!("已重放 {} 条请求，{} 条失败", outcomes.len(), failed);
+    if failed > 0 {
+        anyhow::bail!("{} 条请求重放失败", failed);
+    }
+    Ok(())
+}
+
+fn verify_audit_log(config: &Config, path: Option<&str>) -> anyhow::Result<()> {
+    let audit_log = config
+        .audit_log
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("未配置 auditLog，无法获取 hmacKey"))?;
+    let path = path.unwrap_or(&audit_log.path);
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("读取审计日志 {} 失败: {}", path, e))?;
+    let count = audit::verify(&content, &audit_log.hmac_key).map_err(|e| anyhow::anyhow!(e))?;
+    println!("审计日志 {} 校验通过，共 {} 条记录", path, count);
+    Ok(())
+}
+
+fn csv_line(record: &UsageRecord) -> String {
+    [
+        record.timestamp.to_rfc3339(),
+        record.endpoint.clone(),
+        record.model.clone(),
+        record.api_key.clone(),
+        record
+            .credential_id
+            .map(|id| id.to_string())
+            .unwrap_or_default(),
+        record.input_tokens.to_string(),
+        record.output_tokens.to_string(),
+        record.latency_ms.to_string(),
+        record.status.to_string(),
+        record.stream.to_string(),
+        record.user.clone().unwrap_or_default(),
+        record.guardrail.clone().unwrap_or_default(),
+    ]
+    .iter()
+    .map(|field| csv_field(field))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// 按 RFC 4180 转义：含逗号、引号或换行的字段用双引号包裹
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field_escaping() {
+        assert_eq!(csv_field("claude-sonnet-4-5"), "claude-sonnet-4-5");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_validate_config_reports_problems() {
+        let dir = std::env::temp_dir().join(format!("kiro-cli-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("credentials.json");
+        std::fs::write(&path, r#"[{"refreshToken": "short", "authMethod": "idc"}]"#).unwrap();
+
+        let config_path = dir.join("config.json");
+        let result = validate_config(
+            &Config::default(),
+            config_path.to_str().unwrap(),
+            path.to_str().unwrap(),
+        );
+        // apiKey、refreshToken、clientId、clientSecret
+        assert!(result.unwrap_err().to_string().contains("4 个配置错误"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_init_writes_valid_template() {
+        let dir = std::env::temp_dir().join(format!("kiro-init-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("config.json");
+        let credentials_path = dir.join("credentials.json");
+        let (config_path, credentials_path) = (
+            config_path.to_str().unwrap(),
+            credentials_path.to_str().unwrap(),
+        );
+
+        init(config_path, credentials_path, false).unwrap();
+        let (config, warnings) = Config::load_checked(config_path).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert!(auth::is_valid_hash(&config.api_key.unwrap()));
+        assert!(validation::check_config(&Config::load(config_path).unwrap()).is_empty());
+        assert!(
+            CredentialsConfig::load(credentials_path)
+                .unwrap()
+                .is_empty()
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(credentials_path)
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // 已存在时需要 --force
+        assert!(init(config_path, credentials_path, false).is_err());
+        init(config_path, credentials_path, true).unwrap();
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
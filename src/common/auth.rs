@@ -1,41 +1,195 @@
 //! 公共认证工具函数
 
+use std::net::IpAddr;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
 use axum::{
     body::Body,
-    http::{Request, header},
+    http::{HeaderMap, Request, header},
 };
+use sha2::{Digest, Sha256};
 use subtle::ConstantTimeEq;
 
+use crate::limit::AuthLockout;
+use crate::metrics;
+
+/// 哈希形式 API Key 的前缀：`sha256:<盐 hex>:<SHA-256(盐 + Key) hex>`
+pub const HASH_PREFIX: &str = "sha256:";
+
 /// 从请求中提取 API Key
 ///
 /// 支持两种认证方式：
 /// - `x-api-key` header
 /// - `Authorization: Bearer <token>` header
 pub fn extract_api_key(request: &Request<Body>) -> Option<String> {
+    api_key_from_headers(request.headers())
+}
+
+/// 从请求头中提取 API Key（规则同 [`extract_api_key`]）
+pub fn api_key_from_headers(headers: &HeaderMap) -> Option<String> {
     // 优先检查 x-api-key
-    if let Some(key) = request
-        .headers()
-        .get("x-api-key")
-        .and_then(|v| v.to_str().ok())
-    {
+    if let Some(key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
         return Some(key.to_string());
     }
 
     // 其次检查 Authorization: Bearer
-    request
-        .headers()
+    headers
         .get(header::AUTHORIZATION)
         .and_then(|v| v.to_str().ok())
         .and_then(|v| v.strip_prefix("Bearer "))
         .map(|s| s.to_string())
 }
 
+/// API Key 脱敏，仅保留首尾少量字符用于区分
+///
+/// 用于日志与用量记录，避免完整密钥落盘
+pub fn mask_api_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 8 {
+        return "***".to_string();
+    }
+    let prefix: String = chars[..4].iter().collect();
+    let suffix: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}***{}", prefix, suffix)
+}
+
 /// 常量时间字符串比较，防止时序攻击
 ///
 /// 无论字符串内容如何，比较所需的时间都是恒定的，
 /// 这可以防止攻击者通过测量响应时间来猜测 API Key。
+/// 先对两侧取 SHA-256 再比较，长度不同时也不会提前返回，避免泄露 Key 的长度。
 ///
 /// 使用经过安全审计的 `subtle` crate 实现
 pub fn constant_time_eq(a: &str, b: &str) -> bool {
-    a.as_bytes().ct_eq(b.as_bytes()).into()
+    Sha256::digest(a.as_bytes())
+        .ct_eq(&Sha256::digest(b.as_bytes()))
+        .into()
+}
+
+/// 生成 API Key 的加盐哈希，可直接写入配置中的 `apiKey`、`apiKeys[].key` 与 `requestRules[].apiKeys`
+pub fn hash_api_key(key: &str) -> String {
+    let salt = uuid::Uuid::new_v4();
+    format!(
+        "{}{}:{}",
+        HASH_PREFIX,
+        hex::encode(salt.as_bytes()),
+        hex::encode(salted_digest(salt.as_bytes(), key))
+    )
+}
+
+/// 配置中的 Key 是否已是哈希形式
+pub fn is_hashed(stored: &str) -> bool {
+    stored.starts_with(HASH_PREFIX)
+}
+
+/// 校验客户端提供的 Key 是否与配置中的 Key 匹配（配置可为明文或 [`hash_api_key`] 生成的哈希）
+pub fn verify_api_key(stored: &str, presented: &str) -> bool {
+    let Some(hashed) = stored.strip_prefix(HASH_PREFIX) else {
+        return constant_time_eq(stored, presented);
+    };
+    let Some((salt, digest)) = hashed.split_once(':') else {
+        return false;
+    };
+    match (hex::decode(salt), hex::decode(digest)) {
+        (Ok(salt), Ok(digest)) => salted_digest(&salt, presented)
+            .as_slice()
+            .ct_eq(&digest)
+            .into(),
+        _ => false,
+    }
+}
+
+/// 在 `candidates` 中查找与客户端提供的 Key 匹配的第一项
+///
+/// 每个候选都会完整校验一次，匹配的位置不影响耗时
+pub fn find_api_key<'a, T>(
+    candidates: &'a [T],
+    stored: impl Fn(&T) -> &str,
+    presented: &str,
+) -> Option<&'a T> {
+    candidates.iter().fold(None, |found, candidate| {
+        let matched = verify_api_key(stored(candidate), presented);
+        found.or(matched.then_some(candidate))
+    })
+}
+
+/// 记录一次认证失败：计入指标并输出审计日志（`audit` target），失败次数达到上限时锁定该 IP
+pub fn audit_failure(lockout: &AuthLockout, ip: IpAddr, path: &str) {
+    metrics::global()
+        .auth_failures_total
+        .fetch_add(1, Ordering::Relaxed);
+    tracing::warn!(target: "audit", event = "auth_failure", client.address = %ip, path, "认证失败");
+    if let Some(lockout) = lockout.record_failure(ip) {
+        tracing::warn!(
+            target: "audit",
+            event = "auth_lockout",
+            client.address = %ip,
+            lockout_secs = lockout.as_secs(),
+            "认证失败次数过多，锁定 {} 秒",
+            lockout.as_secs()
+        );
+    }
+}
+
+/// 记录一次锁定期内被拒绝的请求，返回建议的 `Retry-After` 秒数
+pub fn audit_locked_out(ip: IpAddr, remaining: Duration) -> u64 {
+    metrics::global()
+        .auth_locked_out_total
+        .fetch_add(1, Ordering::Relaxed);
+    tracing::warn!(target: "audit", event = "auth_locked_out", client.address = %ip, "认证锁定期内的请求被拒绝");
+    remaining.as_secs_f64().ceil().max(1.0) as u64
+}
+
+/// 哈希形式的 Key 格式是否正确
+pub fn is_valid_hash(stored: &str) -> bool {
+    stored
+        .strip_prefix(HASH_PREFIX)
+        .and_then(|hashed| hashed.split_once(':'))
+        .is_some_and(|(salt, digest)| {
+            !salt.is_empty()
+                && hex::decode(salt).is_ok()
+                && hex::decode(digest).is_ok_and(|d| d.len() == 32)
+        })
+}
+
+fn salted_digest(salt: &[u8], key: &str) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(key.as_bytes());
+    hasher.finalize().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_api_key() {
+        assert_eq!(mask_api_key("sk-1234567890abcd"), "sk-1***abcd");
+        assert_eq!(mask_api_key("short"), "***");
+    }
+
+    #[test]
+    fn test_hashed_api_key() {
+        let hashed = hash_api_key("sk-secret");
+        assert!(is_hashed(&hashed) && is_valid_hash(&hashed));
+        assert!(verify_api_key(&hashed, "sk-secret"));
+        assert!(!verify_api_key(&hashed, "sk-other"));
+        // 每次生成的盐不同
+        assert_ne!(hashed, hash_api_key("sk-secret"));
+
+        assert!(verify_api_key("sk-plain", "sk-plain"));
+        assert!(!verify_api_key("sha256:zz:00", "sk-secret"));
+        assert!(!is_valid_hash("sha256:abcd"));
+    }
+
+    #[test]
+    fn test_find_api_key() {
+        let keys = ["sk-a", "sk-b", "sk-b"];
+        assert_eq!(find_api_key(&keys, |k| k, "sk-b"), Some(&keys[1]));
+        assert_eq!(find_api_key(&keys, |k| k, "sk-c"), None);
+        assert!(constant_time_eq("sk-a", "sk-a"));
+        assert!(!constant_time_eq("sk-a", "sk-a-longer"));
+    }
 }
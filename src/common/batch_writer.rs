@@ -0,0 +1,114 @@
+//! 后台批量写入
+//!
+//! 用量记录与请求体日志在请求结束时产生（通常在 tokio 工作线程上的 `Drop` 中），
+//! 直接写文件或数据库会让工作线程阻塞在磁盘 I/O 与数据库锁上。
+//! [`BatchWriter`] 把记录发送给专用线程，线程每次取出队列中已有的记录（最多 [`MAX_BATCH`] 条）一并写入。
+
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::thread::{self, JoinHandle};
+
+/// 单次写入的最大记录数
+pub const MAX_BATCH: usize = 256;
+
+enum Message<T> {
+    Item(T),
+    /// 之前发送的记录全部写入后回复
+    Flush(SyncSender<()>),
+}
+
+/// 后台写入线程，丢弃时写完队列中剩余的记录后退出
+pub struct BatchWriter<T> {
+    sender: Option<Sender<Message<T>>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> BatchWriter<T> {
+    /// 启动名为 `name` 的写入线程，`write` 在该线程上按批调用
+    pub fn spawn(name: &str, write: impl FnMut(Vec<T>) + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let handle = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || run(receiver, write))
+            .expect("创建后台写入线程失败");
+        Self {
+            sender: Some(sender),
+            handle: Some(handle),
+        }
+    }
+
+    /// 提交一条记录（不阻塞）
+    pub fn send(&self, item: T) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(Message::Item(item));
+        }
+    }
+
+    /// 等待此前提交的记录全部写入
+    pub fn flush(&self) {
+        let Some(sender) = &self.sender else { return };
+        let (ack, done) = mpsc::sync_channel(1);
+        if sender.send(Message::Flush(ack)).is_ok() {
+            let _ = done.recv();
+        }
+    }
+}
+
+impl<T> Drop for BatchWriter<T> {
+    fn drop(&mut self) {
+        // 关闭通道后线程写完剩余记录即退出
+        self.sender.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn run<T>(receiver: Receiver<Message<T>>, mut write: impl FnMut(Vec<T>)) {
+    while let Ok(first) = receiver.recv() {
+        let mut batch = Vec::new();
+        let mut acks = Vec::new();
+        let mut next = Some(first);
+        while let Some(message) = next.take() {
+            match message {
+                Message::Item(item) => batch.push(item),
+                Message::Flush(ack) => acks.push(ack),
+            }
+            if batch.len() < MAX_BATCH {
+                next = receiver.try_recv().ok();
+            }
+        }
+        if !batch.is_empty() {
+            write(batch);
+        }
+        for ack in acks {
+            let _ = ack.send(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_flush_and_drop_write_pending_items() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let sink = written.clone();
+        let writer = BatchWriter::spawn("test-writer", move |batch: Vec<usize>| {
+            assert!(batch.len() <= MAX_BATCH);
+            sink.lock().unwrap().extend(batch);
+        });
+
+        for i in 0..1000 {
+            writer.send(i);
+        }
+        writer.flush();
+        assert_eq!(written.lock().unwrap().len(), 1000);
+
+        writer.send(1000);
+        drop(writer);
+        let written = written.lock().unwrap();
+        assert_eq!(*written, (0..=1000).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,312 @@
+//! 客户端 IP
+//!
+//! 只有当直连的对端属于 `trustedProxies` 时才采信 `X-Forwarded-For` / `X-Real-IP`：
+//! 从 `X-Forwarded-For` 最右侧开始跳过受信任的代理，第一个不受信任的地址即为客户端 IP；
+//! 没有 `X-Forwarded-For` 时使用 `X-Real-IP`。未配置受信任代理时始终使用对端地址，请求头无法伪造来源。
+//! Unix 域套接字没有对端 IP，`trustedProxies` 包含 `unix` 时信任经由套接字转发的请求头。
+//! 解析出的客户端 IP 同时用于对话端点的 `ipFilter` 访问规则。
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::extract::connect_info::Connected;
+use axum::http::{HeaderMap, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::serve::IncomingStream;
+use ipnet::IpNet;
+use tokio::net::TcpListener;
+
+use crate::model::config::IpFilterConfig;
+use crate::tls::TlsListener;
+
+/// `trustedProxies` 中代表 Unix 域套接字对端的取值
+const UNIX_PEER: &str = "unix";
+
+tokio::task_local! {
+    static CURRENT: Option<IpAddr>;
+}
+
+static TRUSTED: OnceLock<TrustedProxies> = OnceLock::new();
+
+/// 解析后的请求客户端 IP（作为请求扩展）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// 受信任的反向代理
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    nets: Vec<IpNet>,
+    unix: bool,
+}
+
+impl TrustedProxies {
+    /// 解析 IP、CIDR 或 `unix`
+    pub fn parse(entries: &[String]) -> anyhow::Result<Self> {
+        let mut proxies = Self::default();
+        for entry in entries {
+            let entry = entry.trim();
+            if entry.eq_ignore_ascii_case(UNIX_PEER) {
+                proxies.unix = true;
+            } else if let Some(net) = parse_net(entry) {
+                proxies.nets.push(net);
+            } else {
+                anyhow::bail!("无效的地址 {}（应为 IP、CIDR 或 unix）", entry);
+            }
+        }
+        Ok(proxies)
+    }
+
+    fn trusts(&self, ip: IpAddr) -> bool {
+        contains(&self.nets, ip)
+    }
+
+    /// 根据对端地址（Unix 域套接字为 None）与转发请求头确定客户端 IP
+    pub fn client_ip(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+        let trusted_peer = match peer {
+            Some(ip) => self.trusts(ip),
+            None => self.unix,
+        };
+        if !trusted_peer {
+            return peer;
+        }
+
+        let forwarded: Vec<&str> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .collect();
+        if forwarded.is_empty() {
+            return headers
+                .get("x-real-ip")
+                .and_then(|v| v.to_str().ok())
+                .and_then(parse_addr)
+                .or(peer);
+        }
+
+        let mut nearest = peer;
+        for hop in forwarded.iter().rev() {
+            // 无法解析的地址可能是伪造的，停在最近一个可信的地址
+            let Some(ip) = parse_addr(hop) else {
+                break;
+            };
+            nearest = Some(ip);
+            if !self.trusts(ip) {
+                break;
+            }
+        }
+        nearest
+    }
+}
+
+/// 客户端 IP 访问规则
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+impl IpFilter {
+    /// 解析 `ipFilter` 配置
+    pub fn parse(config: &IpFilterConfig) -> anyhow::Result<Self> {
+        let parse = |entries: &[String]| {
+            entries
+                .iter()
+                .map(|entry| {
+                    parse_net(entry).ok_or_else(|| {
+                        anyhow::anyhow!("无效的地址 {}（应为 IP 或 CIDR）", entry.trim())
+                    })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        };
+        Ok(Self {
+            allow: parse(&config.allow)?,
+            deny: parse(&config.deny)?,
+        })
+    }
+
+    /// 是否放行该客户端（无法确定 IP 的 Unix 域套接字请求不受限制）
+    pub fn allows(&self, ip: Option<IpAddr>) -> bool {
+        let Some(ip) = ip else {
+            return true;
+        };
+        !contains(&self.deny, ip) && (self.allow.is_empty() || contains(&self.allow, ip))
+    }
+}
+
+/// 解析 IP 或 CIDR
+fn parse_net(entry: &str) -> Option<IpNet> {
+    let entry = entry.trim();
+    entry
+        .parse::<IpNet>()
+        .ok()
+        .or_else(|| entry.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+fn contains(nets: &[IpNet], ip: IpAddr) -> bool {
+    let ip = ip.to_canonical();
+    nets.iter().any(|net| net.contains(&ip))
+}
+
+/// 解析 IP，兼容带端口的写法（`1.2.3.4:5678`、`[::1]:80`）
+fn parse_addr(value: &str) -> Option<IpAddr> {
+    let value = value.trim();
+    value
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| value.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// 设置受信任的代理（启动时调用一次，无效的条目由配置校验报告）
+pub fn init(entries: &[String]) {
+    let proxies = TrustedProxies::parse(entries).unwrap_or_else(|e| {
+        tracing::warn!("trustedProxies 无效，不信任任何转发请求头: {}", e);
+        TrustedProxies::default()
+    });
+    let _ = TRUSTED.set(proxies);
+}
+
+/// 当前请求的客户端 IP（在请求处理任务之外调用时为 None）
+pub fn current() -> Option<IpAddr> {
+    CURRENT.try_with(|ip| *ip).ok().flatten()
+}
+
+/// 连接的对端 IP（Unix 域套接字为 None），配合 `into_make_service_with_connect_info::<PeerAddr>()` 使用
+#[derive(Debug, Clone, Copy)]
+pub struct PeerAddr(pub Option<IpAddr>);
+
+impl Connected<IncomingStream<'_, TcpListener>> for PeerAddr {
+    fn connect_info(stream: IncomingStream<'_, TcpListener>) -> Self {
+        PeerAddr(Some(stream.remote_addr().ip()))
+    }
+}
+
+impl Connected<IncomingStream<'_, TlsListener>> for PeerAddr {
+    fn connect_info(stream: IncomingStream<'_, TlsListener>) -> Self {
+        PeerAddr(Some(stream.remote_addr().ip()))
+    }
+}
+
+#[cfg(unix)]
+impl Connected<IncomingStream<'_, tokio::net::UnixListener>> for PeerAddr {
+    fn connect_info(_stream: IncomingStream<'_, tokio::net::UnixListener>) -> Self {
+        PeerAddr(None)
+    }
+}
+
+/// 确定客户端 IP，写入请求扩展并在处理请求期间可通过 [`current`] 获取
+pub async fn middleware(mut request: Request<Body>, next: Next) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<PeerAddr>>()
+        .and_then(|info| info.0.0);
+    let ip = match TRUSTED.get() {
+        Some(trusted) => trusted.client_ip(peer, request.headers()),
+        None => peer,
+    };
+    if let Some(ip) = ip {
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+    CURRENT.scope(ip, next.run(request)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_untrusted_peer_ignores_headers() {
+        let proxies = TrustedProxies::default();
+        let spoofed = headers(&[("x-forwarded-for", "1.2.3.4"), ("x-real-ip", "1.2.3.4")]);
+        assert_eq!(
+            proxies.client_ip(Some(ip("203.0.113.9")), &spoofed),
+            Some(ip("203.0.113.9"))
+        );
+        assert_eq!(proxies.client_ip(None, &spoofed), None);
+    }
+
+    #[test]
+    fn test_trusted_chain() {
+        let proxies =
+            TrustedProxies::parse(&["10.0.0.0/8".to_string(), "127.0.0.1".to_string()]).unwrap();
+        let peer = Some(ip("127.0.0.1"));
+
+        // 客户端伪造的最左侧地址被忽略，取最右侧不受信任的地址
+        let chain = headers(&[
+            ("x-forwarded-for", "6.6.6.6, 198.51.100.7"),
+            ("x-forwarded-for", "10.1.2.3"),
+        ]);
+        assert_eq!(proxies.client_ip(peer, &chain), Some(ip("198.51.100.7")));
+
+        // 全部为受信任代理时取最左侧
+        let internal = headers(&[("x-forwarded-for", "10.0.0.5, 10.0.0.6")]);
+        assert_eq!(proxies.client_ip(peer, &internal), Some(ip("10.0.0.5")));
+
+        let real_ip = headers(&[("x-real-ip", "198.51.100.8:4321")]);
+        assert_eq!(proxies.client_ip(peer, &real_ip), Some(ip("198.51.100.8")));
+
+        // IPv4 映射的 IPv6 对端地址同样匹配
+        assert_eq!(
+            proxies.client_ip(Some(ip("::ffff:127.0.0.1")), &real_ip),
+            Some(ip("198.51.100.8"))
+        );
+    }
+
+    #[test]
+    fn test_unix_peer() {
+        let proxies = TrustedProxies::parse(&["unix".to_string()]).unwrap();
+        let forwarded = headers(&[("x-forwarded-for", "198.51.100.7")]);
+        assert_eq!(
+            proxies.client_ip(None, &forwarded),
+            Some(ip("198.51.100.7"))
+        );
+        assert!(TrustedProxies::parse(&["not-an-ip".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_ip_filter() {
+        let filter = IpFilter::parse(&IpFilterConfig {
+            allow: vec!["10.0.0.0/8".to_string(), "::1".to_string()],
+            deny: vec!["10.0.0.5".to_string()],
+        })
+        .unwrap();
+        assert!(filter.allows(Some(ip("10.1.2.3"))));
+        assert!(filter.allows(Some(ip("::1"))));
+        assert!(!filter.allows(Some(ip("10.0.0.5"))));
+        assert!(!filter.allows(Some(ip("203.0.113.9"))));
+        assert!(filter.allows(None));
+
+        let deny_only = IpFilter::parse(&IpFilterConfig {
+            allow: Vec::new(),
+            deny: vec!["203.0.113.0/24".to_string()],
+        })
+        .unwrap();
+        assert!(!deny_only.allows(Some(ip("::ffff:203.0.113.9"))));
+        assert!(deny_only.allows(Some(ip("198.51.100.7"))));
+
+        assert!(
+            IpFilter::parse(&IpFilterConfig {
+                allow: vec!["unix".to_string()],
+                deny: Vec::new(),
+            })
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! 请求头 / 响应头透传
+
+use axum::http::HeaderMap;
+
+use crate::model::config::wildcard_match;
+
+/// 无论白名单如何配置都不透传的头（认证、连接与消息体相关）
+const NEVER_FORWARD: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "cookie",
+    "set-cookie",
+    "host",
+    "connection",
+    "content-length",
+    "content-type",
+    "content-encoding",
+    "transfer-encoding",
+];
+
+/// 按白名单筛选头，名称不区分大小写，支持 `*` 通配符（如 `x-trace-*`）
+pub fn filter_headers(headers: &HeaderMap, allowlist: &[String]) -> HeaderMap {
+    let mut filtered = HeaderMap::new();
+    if allowlist.is_empty() {
+        return filtered;
+    }
+    for (name, value) in headers {
+        let name_str = name.as_str();
+        if NEVER_FORWARD.contains(&name_str) {
+            continue;
+        }
+        if allowlist
+            .iter()
+            .any(|pattern| wildcard_match(&pattern.to_ascii_lowercase(), name_str))
+        {
+            filtered.append(name.clone(), value.clone());
+        }
+    }
+    filtered
+}
+
+/// 将 `extra` 中的头追加到 `headers`，已存在的头保持不变
+pub fn merge_missing(headers: &mut HeaderMap, extra: &HeaderMap) {
+    for name in extra.keys() {
+        if headers.contains_key(name) {
+            continue;
+        }
+        for value in extra.get_all(name) {
+            headers.append(name.clone(), value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_filter_headers_allowlist_and_denylist() {
+        let mut headers = HeaderMap::new();
+        headers.insert("anthropic-beta", HeaderValue::from_static("tools-2024"));
+        headers.insert("x-trace-id", HeaderValue::from_static("abc"));
+        headers.insert("x-other", HeaderValue::from_static("1"));
+        headers.insert("authorization", HeaderValue::from_static("Bearer secret"));
+
+        let allowlist = vec![
+            "Anthropic-Beta".to_string(),
+            "x-trace-*".to_string(),
+            "authorization".to_string(),
+        ];
+        let filtered = filter_headers(&headers, &allowlist);
+
+        assert_eq!(filtered.len(), 2);
+        assert_eq!(filtered.get("anthropic-beta").unwrap(), "tools-2024");
+        assert_eq!(filtered.get("x-trace-id").unwrap(), "abc");
+        assert!(filtered.get("authorization").is_none());
+        assert!(filter_headers(&headers, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_merge_missing_keeps_existing() {
+        let mut headers = HeaderMap::new();
+        headers.insert("user-agent", HeaderValue::from_static("kiro"));
+        let mut extra = HeaderMap::new();
+        extra.insert("user-agent", HeaderValue::from_static("client"));
+        extra.insert("x-trace-id", HeaderValue::from_static("abc"));
+
+        merge_missing(&mut headers, &extra);
+
+        assert_eq!(headers.get("user-agent").unwrap(), "kiro");
+        assert_eq!(headers.get("x-trace-id").unwrap(), "abc");
+    }
+}
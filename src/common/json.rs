@@ -0,0 +1,26 @@
+//! JSON 请求体提取的公共逻辑
+
+use axum::{extract::rejection::JsonRejection, http::StatusCode};
+
+/// 请求体超出大小限制时的错误码
+pub const REQUEST_TOO_LARGE: &str = "request_too_large";
+
+/// 请求头超出大小限制时的错误码
+pub const HEADERS_TOO_LARGE: &str = "request_header_fields_too_large";
+
+/// 将 axum 的 JSON 提取失败归类为 (状态码, 错误信息)
+///
+/// 请求体过大返回 413；字段缺失 / 类型不匹配（axum 默认 422）与语法错误统一返回 400
+pub fn classify_rejection(rejection: &JsonRejection) -> (StatusCode, String) {
+    let status = match rejection.status() {
+        StatusCode::PAYLOAD_TOO_LARGE => StatusCode::PAYLOAD_TOO_LARGE,
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    let message = if status == StatusCode::PAYLOAD_TOO_LARGE {
+        "Request body is too large".to_string()
+    } else {
+        rejection.body_text()
+    };
+    (status, message)
+}
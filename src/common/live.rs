@@ -0,0 +1,33 @@
+//! 可在运行时整体替换的共享值
+//!
+//! 读取方通过 [`Live::load`] 取得当前值的快照（`Arc`），替换不影响已取得快照的请求。
+
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+/// 可整体替换的共享值，克隆后共享同一份数据
+#[derive(Debug, Default)]
+pub struct Live<T>(Arc<RwLock<Arc<T>>>);
+
+impl<T> Clone for Live<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Live<T> {
+    pub fn new(value: T) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(value))))
+    }
+
+    /// 当前值的快照
+    pub fn load(&self) -> Arc<T> {
+        self.0.read().clone()
+    }
+
+    /// 替换为新值
+    pub fn store(&self, value: T) {
+        *self.0.write() = Arc::new(value);
+    }
+}
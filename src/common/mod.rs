@@ -1,3 +1,9 @@
 //! 公共工具模块
 
 pub mod auth;
+pub mod batch_writer;
+pub mod client_ip;
+pub mod headers;
+pub mod json;
+pub mod live;
+pub mod rewrite;
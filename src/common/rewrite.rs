@@ -0,0 +1,345 @@
+//! 请求改写规则
+//!
+//! 按配置中的 `requestRules` 顺序改写对话端点的原始 JSON 请求体，
+//! 用于去除客户端水印、规范化特殊客户端的请求等场景，无需修改代码。
+//! 规则可以按路由与 API Key 限定生效范围。
+
+use regex_automata::meta::Regex;
+use serde_json::{Value, json};
+
+use crate::common::auth::verify_api_key;
+use crate::model::config::{InjectPosition, RequestRule, RuleAction, wildcard_match};
+
+/// 正则替换作用的顶层字段（消息、系统提示与提示词）
+const TEXT_ROOTS: &[&str] = &["messages", "system", "prompt", "input", "instructions"];
+
+/// 消息内部承载文本的字段
+const TEXT_FIELDS: &[&str] = &["text", "content"];
+
+enum Action {
+    Replace {
+        regex: Regex,
+        replacement: String,
+    },
+    Strip(Vec<Vec<String>>),
+    Inject {
+        message: Value,
+        position: InjectPosition,
+    },
+}
+
+struct Rule {
+    routes: Vec<String>,
+    api_keys: Vec<String>,
+    action: Action,
+}
+
+impl Rule {
+    fn matches(&self, route: &str, api_key: &str) -> bool {
+        (self.routes.is_empty() || self.routes.iter().any(|r| wildcard_match(r, route)))
+            && (self.api_keys.is_empty()
+                || self.api_keys.iter().any(|k| verify_api_key(k, api_key)))
+    }
+}
+
+/// 编译后的请求改写规则集
+#[derive(Default)]
+pub struct RequestRewriter {
+    rules: Vec<Rule>,
+}
+
+impl RequestRewriter {
+    /// 编译配置中的规则，正则无效的规则会被忽略并记录警告
+    pub fn new(rules: &[RequestRule]) -> Self {
+        let rules = rules
+            .iter()
+            .filter_map(|rule| {
+                let action = match &rule.action {
+                    RuleAction::RegexReplace {
+                        pattern,
+                        replacement,
+                    } => match Regex::new(pattern) {
+                        Ok(regex) => Action::Replace {
+                            regex,
+                            replacement: replacement.clone(),
+                        },
+                        Err(e) => {
+                            tracing::warn!("忽略无效的请求改写规则 {:?}: {}", pattern, e);
+                            return None;
+                        }
+                    },
+                    RuleAction::StripFields { fields } => Action::Strip(
+                        fields
+                            .iter()
+                            .map(|f| f.split('.').map(str::to_string).collect())
+                            .collect(),
+                    ),
+                    RuleAction::InjectMessage {
+                        role,
+                        content,
+                        position,
+                    } => Action::Inject {
+                        message: json!({"role": role, "content": content}),
+                        position: *position,
+                    },
+                };
+                Some(Rule {
+                    routes: rule.routes.clone(),
+                    api_keys: rule.api_keys.clone(),
+                    action,
+                })
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// 是否没有任何规则
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 对请求体依次应用匹配的规则，返回生效的规则数
+    pub fn apply(&self, route: &str, api_key: &str, body: &mut Value) -> usize {
+        let mut applied = 0;
+        for rule in self.rules.iter().filter(|r| r.matches(route, api_key)) {
+            match &rule.action {
+                Action::Replace { regex, replacement } => {
+                    for root in TEXT_ROOTS {
+                        if let Some(value) = body.get_mut(*root) {
+                            replace_text(value, regex, replacement);
+                        }
+                    }
+                }
+                Action::Strip(paths) => {
+                    for path in paths {
+                        strip_path(body, path);
+                    }
+                }
+                Action::Inject { message, position } => {
+                    let key = ["messages", "input"]
+                        .into_iter()
+                        .find(|key| body.get(*key).is_some_and(Value::is_array));
+                    let Some(messages) = key.and_then(|k| body[k].as_array_mut()) else {
+                        tracing::debug!("请求体中没有消息列表，跳过消息注入规则");
+                        continue;
+                    };
+                    match position {
+                        InjectPosition::Start => messages.insert(0, message.clone()),
+                        InjectPosition::End => messages.push(message.clone()),
+                    }
+                }
+            }
+            applied += 1;
+        }
+        applied
+    }
+}
+
+/// 替换文本中所有匹配，替换串中的 `$1` / `${name}` 引用捕获分组
+fn replace_all(regex: &Regex, text: &str, replacement: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut last = 0;
+    let mut matched = false;
+    for caps in regex.captures_iter(text) {
+        let Some(m) = caps.get_match() else {
+            continue;
+        };
+        out.push_str(&text[last..m.start()]);
+        caps.interpolate_string_into(text, replacement, &mut out);
+        last = m.end();
+        matched = true;
+    }
+    if !matched {
+        return None;
+    }
+    out.push_str(&text[last..]);
+    Some(out)
+}
+
+/// 递归替换字符串、字符串数组以及对象中 `text` / `content` 字段的文本
+fn replace_text(value: &mut Value, regex: &Regex, replacement: &str) {
+    match value {
+        Value::String(text) => {
+            if let Some(replaced) = replace_all(regex, text, replacement) {
+                *text = replaced;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                replace_text(item, regex, replacement);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                if TEXT_FIELDS.contains(&key.as_str()) {
+                    replace_text(item, regex, replacement);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 依次访问消息、系统提示与提示词中的文本（与正则替换的作用范围一致）
+pub(crate) fn for_each_text(body: &Value, f: &mut impl FnMut(&str)) {
+    fn visit(value: &Value, f: &mut impl FnMut(&str)) {
+        match value {
+            Value::String(text) => f(text),
+            Value::Array(items) => items.iter().for_each(|item| visit(item, f)),
+            Value::Object(map) => map
+                .iter()
+                .filter(|(key, _)| TEXT_FIELDS.contains(&key.as_str()))
+                .for_each(|(_, item)| visit(item, f)),
+            _ => {}
+        }
+    }
+    for root in TEXT_ROOTS {
+        if let Some(value) = body.get(*root) {
+            visit(value, f);
+        }
+    }
+}
+
+/// 按路径删除字段，`*` 匹配数组元素或对象的任意键
+fn strip_path(value: &mut Value, path: &[String]) {
+    let Some((head, rest)) = path.split_first() else {
+        return;
+    };
+    if rest.is_empty() {
+        match value {
+            Value::Object(map) if head == "*" => map.clear(),
+            Value::Object(map) => {
+                map.remove(head);
+            }
+            _ => {}
+        }
+        return;
+    }
+    match (value, head.as_str()) {
+        (Value::Array(items), "*") => items.iter_mut().for_each(|v| strip_path(v, rest)),
+        (Value::Object(map), "*") => map.values_mut().for_each(|v| strip_path(v, rest)),
+        (Value::Object(map), key) => {
+            if let Some(v) = map.get_mut(key) {
+                strip_path(v, rest);
+            }
+        }
+        (Value::Array(items), index) => {
+            if let Some(v) = index.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+                strip_path(v, rest);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(routes: &[&str], api_keys: &[&str], action: RuleAction) -> RequestRule {
+        RequestRule {
+            routes: routes.iter().map(|s| s.to_string()).collect(),
+            api_keys: api_keys.iter().map(|s| s.to_string()).collect(),
+            action,
+        }
+    }
+
+    #[test]
+    fn test_regex_replace_only_touches_text() {
+        let rewriter = RequestRewriter::new(&[rule(
+            &[],
+            &[],
+            RuleAction::RegexReplace {
+                pattern: r"<watermark id=(\d+)/>".to_string(),
+                replacement: "[$1]".to_string(),
+            },
+        )]);
+        let mut body = json!({
+            "model": "<watermark id=0/>",
+            "system": "sys <watermark id=1/>",
+            "messages": [
+                {"role": "user", "content": "hi <watermark id=2/>!"},
+                {"role": "user", "content": [{"type": "text", "text": "<watermark id=3/>"}]}
+            ]
+        });
+
+        assert_eq!(rewriter.apply("/v1/messages", "key", &mut body), 1);
+        assert_eq!(body["model"], "<watermark id=0/>");
+        assert_eq!(body["system"], "sys [1]");
+        assert_eq!(body["messages"][0]["content"], "hi [2]!");
+        assert_eq!(body["messages"][1]["content"][0]["text"], "[3]");
+    }
+
+    #[test]
+    fn test_strip_fields_with_wildcards() {
+        let rewriter = RequestRewriter::new(&[rule(
+            &[],
+            &[],
+            RuleAction::StripFields {
+                fields: vec![
+                    "metadata".to_string(),
+                    "messages.*.content.*.cache_control".to_string(),
+                ],
+            },
+        )]);
+        let mut body = json!({
+            "metadata": {"user_id": "u"},
+            "messages": [{"role": "user", "content": [
+                {"type": "text", "text": "a", "cache_control": {"type": "ephemeral"}}
+            ]}]
+        });
+        rewriter.apply("/v1/messages", "key", &mut body);
+
+        assert!(body.get("metadata").is_none());
+        assert_eq!(
+            body["messages"][0]["content"][0],
+            json!({"type": "text", "text": "a"})
+        );
+    }
+
+    #[test]
+    fn test_inject_message_scoped_by_route_and_key() {
+        let rewriter = RequestRewriter::new(&[rule(
+            &["/v1/chat/*"],
+            &["team-key"],
+            RuleAction::InjectMessage {
+                role: "system".to_string(),
+                content: "Answer in English.".to_string(),
+                position: InjectPosition::Start,
+            },
+        )]);
+        let body = json!({"messages": [{"role": "user", "content": "hi"}]});
+
+        let mut other_key = body.clone();
+        assert_eq!(
+            rewriter.apply("/v1/chat/completions", "other", &mut other_key),
+            0
+        );
+        let mut other_route = body.clone();
+        assert_eq!(
+            rewriter.apply("/v1/messages", "team-key", &mut other_route),
+            0
+        );
+
+        let mut matched = body.clone();
+        assert_eq!(
+            rewriter.apply("/v1/chat/completions", "team-key", &mut matched),
+            1
+        );
+        assert_eq!(matched["messages"][0]["role"], "system");
+        assert_eq!(matched["messages"][1]["content"], "hi");
+    }
+
+    #[test]
+    fn test_invalid_regex_is_skipped() {
+        let rewriter = RequestRewriter::new(&[rule(
+            &[],
+            &[],
+            RuleAction::RegexReplace {
+                pattern: "(".to_string(),
+                replacement: String::new(),
+            },
+        )]);
+        assert!(rewriter.is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+//! 后台运行与 PID 文件
+//!
+//! `--daemon`（仅 Unix）在启动 tokio 运行时之前 fork 到后台：父进程输出子进程 PID 后退出，
+//! 子进程脱离终端（setsid），标准输入重定向到 `/dev/null`，标准输出与标准错误追加写入 `--log-file`。
+//! 工作目录保持不变，配置中的相对路径仍然有效。
+//! `--pid-file` 在启动时写入当前进程 PID，正常退出时删除；文件中的进程仍在运行时拒绝启动。
+
+use std::path::{Path, PathBuf};
+
+/// 默认的后台运行日志文件
+pub const DEFAULT_LOG_FILE: &str = "kiro-rs.log";
+
+/// fork 到后台运行（必须在创建任何线程之前调用）
+#[cfg(unix)]
+pub fn daemonize(log_file: &str) -> anyhow::Result<()> {
+    use std::fs::OpenOptions;
+    use std::os::fd::AsRawFd;
+
+    // 先在父进程中打开文件，失败时错误仍可输出到终端
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .map_err(|e| anyhow::anyhow!("打开日志文件 {} 失败: {}", log_file, e))?;
+    let null = OpenOptions::new().read(true).open("/dev/null")?;
+
+    // SAFETY: 此时进程只有主线程，fork 后子进程可以安全地继续执行
+    match unsafe { libc::fork() } {
+        -1 => return Err(std::io::Error::last_os_error().into()),
+        0 => {}
+        pid => {
+            println!("已在后台启动，PID {}，日志写入 {}", pid, log_file);
+            std::process::exit(0);
+        }
+    }
+
+    // SAFETY: 以下均为对当前进程与有效文件描述符的系统调用
+    unsafe {
+        if libc::setsid() == -1 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        for (from, to) in [
+            (null.as_raw_fd(), libc::STDIN_FILENO),
+            (log.as_raw_fd(), libc::STDOUT_FILENO),
+            (log.as_raw_fd(), libc::STDERR_FILENO),
+        ] {
+            if libc::dup2(from, to) == -1 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn daemonize(_log_file: &str) -> anyhow::Result<()> {
+    anyhow::bail!("--daemon 仅支持 Unix 平台")
+}
+
+/// PID 文件（释放时删除）
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// 写入当前进程 PID；文件中记录的进程仍在运行时返回错误
+    pub fn create(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if let Some(pid) = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| content.trim().parse::<u32>().ok())
+            && pid != std::process::id()
+            && is_running(pid)
+        {
+            anyhow::bail!("进程 {} 仍在运行（PID 文件 {}）", pid, path.display());
+        }
+        std::fs::write(path, format!("{}\n", std::process::id()))
+            .map_err(|e| anyhow::anyhow!("写入 PID 文件 {} 失败: {}", path.display(), e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: 信号 0 只检查进程是否存在，不会发送信号
+    let alive = unsafe { libc::kill(pid, 0) } == 0;
+    // EPERM 表示进程存在但属于其他用户
+    alive || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// 非 Unix 平台无法检查进程是否存在，视为残留文件
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file() {
+        let path = std::env::temp_dir().join(format!("kiro-{}.pid", uuid::Uuid::new_v4()));
+
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap().trim(),
+            std::process::id().to_string()
+        );
+        drop(pid_file);
+        assert!(!path.exists());
+
+        // 残留的 PID 文件（进程已退出）会被覆盖
+        std::fs::write(&path, format!("{}\n", u32::MAX - 1)).unwrap();
+        let pid_file = PidFile::create(&path).unwrap();
+        drop(pid_file);
+
+        // PID 1 始终在运行
+        #[cfg(unix)]
+        {
+            std::fs::write(&path, "1\n").unwrap();
+            assert!(PidFile::create(&path).is_err());
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
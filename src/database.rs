@@ -0,0 +1,537 @@
+//! 嵌入式 SQLite 存储
+//!
+//! 配置 `databasePath` 后，每个请求的用量记录、Admin 审计记录与采样的请求体日志写入同一个 SQLite 数据库，
+//! 重启后从数据库恢复，替代仅保存在内存中的记录。
+//!
+//! 表结构通过 [`MIGRATIONS`] 维护：数据库的 `user_version` 记录已执行的迁移数，
+//! 打开时依次执行尚未执行的迁移，升级版本后无需手动修改表结构。
+
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::Mutex;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, Row, params, params_from_iter};
+
+use crate::admin::AuditEntry;
+use crate::request_log::RequestLogEntry;
+use crate::usage::UsageRecord;
+use crate::usage::timeseries::{BucketSum, SeriesMetric};
+
+/// 表结构迁移，按顺序执行，已发布的迁移不可修改，只能追加
+const MIGRATIONS: &[&str] = &[
+    // 1: 用量记录（即请求日志）与 Admin 审计记录
+    "CREATE TABLE usage (
+        id INTEGER PRIMARY KEY,
+        timestamp_us INTEGER NOT NULL,
+        endpoint TEXT NOT NULL,
+        model TEXT NOT NULL,
+        api_key TEXT NOT NULL,
+        credential_id INTEGER,
+        input_tokens INTEGER NOT NULL,
+        output_tokens INTEGER NOT NULL,
+        latency_ms INTEGER NOT NULL,
+        status INTEGER NOT NULL,
+        stream INTEGER NOT NULL,
+        user TEXT,
+        guardrail TEXT,
+        client_ip TEXT
+    );
+    CREATE INDEX usage_timestamp ON usage (timestamp_us);
+    CREATE TABLE audit (
+        seq INTEGER PRIMARY KEY,
+        timestamp TEXT NOT NULL,
+        method TEXT NOT NULL,
+        path TEXT NOT NULL,
+        status INTEGER NOT NULL,
+        client_ip TEXT,
+        actor TEXT NOT NULL,
+        mac TEXT NOT NULL
+    );",
+    // 2: 请求体与响应体日志
+    "CREATE TABLE request_log (
+        id INTEGER PRIMARY KEY,
+        timestamp_us INTEGER NOT NULL,
+        request_id TEXT NOT NULL,
+        method TEXT NOT NULL,
+        route TEXT NOT NULL,
+        status INTEGER NOT NULL,
+        latency_ms INTEGER NOT NULL,
+        request_body TEXT NOT NULL,
+        request_truncated INTEGER NOT NULL,
+        response_body TEXT NOT NULL,
+        response_truncated INTEGER NOT NULL
+    );
+    CREATE INDEX request_log_timestamp ON request_log (timestamp_us);",
+];
+
+/// 请求体日志的查询条件
+#[derive(Debug, Clone, Default)]
+pub struct RequestLogQuery {
+    /// 只返回这些请求 ID 的记录（为空时不限）
+    pub request_ids: Vec<String>,
+    /// 只返回不早于该时间的记录
+    pub since: Option<DateTime<Utc>>,
+    /// 只返回失败（4xx/5xx）的记录
+    pub failed_only: bool,
+    /// 最多返回的记录数（None 表示不限）
+    pub limit: Option<usize>,
+}
+
+/// SQLite 数据库
+pub struct Database {
+    conn: Mutex<Connection>,
+}
+
+impl Database {
+    /// 打开数据库，文件不存在时自动创建，并执行尚未执行的迁移
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        // WAL 模式下写入不阻塞读取；记录按批提交，NORMAL 足以保证进程崩溃时不丢已提交的数据
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        Self::from_connection(conn)
+    }
+
+    /// 创建仅内存的数据库
+    #[cfg(test)]
+    pub fn in_memory() -> anyhow::Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut conn: Connection) -> anyhow::Result<Self> {
+        migrate(&mut conn)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// 在一个事务中写入一批用量记录
+    pub fn insert_usage(&self, records: &[UsageRecord]) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        {
+            let mut statement = tx.prepare_cached(
+                "INSERT INTO usage (timestamp_us, endpoint, model, api_key, credential_id,
+                    input_tokens, output_tokens, latency_ms, status, stream, user, guardrail, client_ip)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            )?;
+            for record in records {
+                statement.execute(params![
+                    record.timestamp.timestamp_micros(),
+                    record.endpoint,
+                    record.model,
+                    record.api_key,
+                    record.credential_id.map(|id| id as i64),
+                    record.input_tokens,
+                    record.output_tokens,
+                    record.latency_ms as i64,
+                    record.status,
+                    record.stream,
+                    record.user,
+                    record.guardrail,
+                    record.client_ip,
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// 读取最近的 `limit` 条用量记录（按时间顺序）
+    pub fn recent_usage(&self, limit: usize) -> rusqlite::Result<Vec<UsageRecord>> {
+        let conn = self.conn.lock();
+        let mut statement = conn.prepare(
+            "SELECT * FROM (
+                SELECT timestamp_us, endpoint, model, api_key, credential_id, input_tokens,
+                    output_tokens, latency_ms, status, stream, user, guardrail, client_ip, id
+                FROM usage ORDER BY timestamp_us DESC, id DESC LIMIT ?1
+             ) ORDER BY timestamp_us, id",
+        )?;
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        statement.query_map([limit], usage_from_row)?.collect()
+    }
+
+    /// 按步长汇总 `start` 起 `buckets` 个桶内的用量记录
+    pub fn usage_buckets(
+        &self,
+        metric: SeriesMetric,
+        start: DateTime<Utc>,
+        step: Duration,
+        buckets: usize,
+    ) -> rusqlite::Result<Vec<BucketSum>> {
+        let value = match metric {
+            SeriesMetric::Requests => "1",
+            SeriesMetric::Errors => "status NOT BETWEEN 200 AND 299",
+            SeriesMetric::Tokens => "input_tokens + output_tokens",
+            SeriesMetric::Latency => "latency_ms",
+        };
+        let step_us = step.num_seconds().max(1) * 1_000_000;
+        let start_us = start.timestamp_micros();
+        let end_us = start_us.saturating_add(step_us.saturating_mul(buckets as i64));
+
+        let conn = self.conn.lock();
+        let mut statement = conn.prepare(&format!(
+            "SELECT (timestamp_us - ?1) / ?2 AS slot, COUNT(*), TOTAL({value})
+             FROM usage WHERE timestamp_us >= ?1 AND timestamp_us < ?3 GROUP BY slot"
+        ))?;
+        let mut sums = vec![BucketSum::default(); buckets];
+        let rows = statement.query_map(params![start_us, step_us, end_us], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get(2)?))
+        })?;
+        for row in rows {
+            let (slot, count, sum) = row?;
+            if let Some(bucket) = sums.get_mut(slot as usize) {
+                *bucket = BucketSum {
+                    count: count as u64,
+                    sum,
+                };
+            }
+        }
+        Ok(sums)
+    }
+
+    /// 删除早于 `before` 的用量记录，返回删除的记录数
+    pub fn prune_usage(&self, before: DateTime<Utc>) -> rusqlite::Result<usize> {
+        self.conn.lock().execute(
+            "DELETE FROM usage WHERE timestamp_us < ?1",
+            [before.timestamp_micros()],
+        )
+    }
+
+    /// 在一个事务中写入一批请求体日志
+    pub fn insert_request_logs(&self, entries: &[RequestLogEntry]) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        {
+            let mut statement = tx.prepare_cached(
+                "INSERT INTO request_log (timestamp_us, request_id, method, route, status,
+                    latency_ms, request_body, request_truncated, response_body, response_truncated)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+            for entry in entries {
+                statement.execute(params![
+                    entry.timestamp.timestamp_micros(),
+                    entry.request_id,
+                    entry.method,
+                    entry.route,
+                    entry.status,
+                    entry.latency_ms as i64,
+                    entry.request_body,
+                    entry.request_truncated,
+                    entry.response_body,
+                    entry.response_truncated,
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// 查询请求体日志，返回符合条件的最近若干条（按时间顺序）
+    pub fn request_logs(&self, query: &RequestLogQuery) -> rusqlite::Result<Vec<RequestLogEntry>> {
+        let mut conditions = vec!["timestamp_us >= ?".to_string()];
+        let mut values = vec![SqlValue::from(
+            query
+                .since
+                .map_or(i64::MIN, |since| since.timestamp_micros()),
+        )];
+        if query.failed_only {
+            conditions.push("status >= 400".to_string());
+        }
+        if !query.request_ids.is_empty() {
+            conditions.push(format!(
+                "request_id IN ({})",
+                vec!["?"; query.request_ids.len()].join(", ")
+            ));
+            values.extend(query.request_ids.iter().cloned().map(SqlValue::from));
+        }
+        // SQLite 中 LIMIT -1 表示不限制
+        values.push(SqlValue::from(
+            query
+                .limit
+                .map_or(-1, |limit| i64::try_from(limit).unwrap_or(i64::MAX)),
+        ));
+
+        let conn = self.conn.lock();
+        let mut statement = conn.prepare(&format!(
+            "SELECT * FROM (
+                SELECT timestamp_us, request_id, method, route, status, latency_ms, request_body,
+                    request_truncated, response_body, response_truncated, id
+                FROM request_log WHERE {} ORDER BY timestamp_us DESC, id DESC LIMIT ?
+             ) ORDER BY timestamp_us, id",
+            conditions.join(" AND ")
+        ))?;
+        statement
+            .query_map(params_from_iter(values), request_log_from_row)?
+            .collect()
+    }
+
+    /// 删除早于 `before` 的请求体日志，返回删除的记录数
+    pub fn prune_request_logs(&self, before: DateTime<Utc>) -> rusqlite::Result<usize> {
+        self.conn.lock().execute(
+            "DELETE FROM request_log WHERE timestamp_us < ?1",
+            [before.timestamp_micros()],
+        )
+    }
+
+    /// 写入一条审计记录（审计记录以 HMAC 链接，不参与过期清理）
+    pub fn insert_audit(&self, entry: &AuditEntry, mac: &str) -> rusqlite::Result<()> {
+        self.conn.lock().execute(
+            "INSERT OR REPLACE INTO audit (seq, timestamp, method, path, status, client_ip, actor, mac)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                entry.seq as i64,
+                entry.timestamp,
+                entry.method,
+                entry.path,
+                entry.status,
+                entry.client_ip,
+                entry.actor,
+                mac,
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+/// 执行尚未执行的迁移（每个迁移在单独的事务中执行）
+fn migrate(conn: &mut Connection) -> anyhow::Result<()> {
+    let applied: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if applied > MIGRATIONS.len() {
+        anyhow::bail!(
+            "数据库表结构版本 ({}) 高于当前程序支持的版本 ({})，请使用更新版本的程序",
+            applied,
+            MIGRATIONS.len()
+        );
+    }
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn usage_from_row(row: &Row<'_>) -> rusqlite::Result<UsageRecord> {
+    let micros: i64 = row.get(0)?;
+    Ok(UsageRecord {
+        timestamp: DateTime::from_timestamp_micros(micros).unwrap_or_default(),
+        endpoint: row.get(1)?,
+        model: row.get(2)?,
+        api_key: row.get(3)?,
+        credential_id: row.get::<_, Option<i64>>(4)?.map(|id| id as u64),
+        input_tokens: row.get(5)?,
+        output_tokens: row.get(6)?,
+        latency_ms: row.get::<_, i64>(7)? as u64,
+        status: row.get(8)?,
+        stream: row.get(9)?,
+        user: row.get(10)?,
+        guardrail: row.get(11)?,
+        client_ip: row.get(12)?,
+    })
+}
+
+fn request_log_from_row(row: &Row<'_>) -> rusqlite::Result<RequestLogEntry> {
+    let micros: i64 = row.get(0)?;
+    Ok(RequestLogEntry {
+        timestamp: DateTime::from_timestamp_micros(micros).unwrap_or_default(),
+        request_id: row.get(1)?,
+        method: row.get(2)?,
+        route: row.get(3)?,
+        status: row.get(4)?,
+        latency_ms: row.get::<_, i64>(5)? as u64,
+        request_body: row.get(6)?,
+        request_truncated: row.get(7)?,
+        response_body: row.get(8)?,
+        response_truncated: row.get(9)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::OptionalExtension;
+
+    fn record(model: &str, timestamp: DateTime<Utc>) -> UsageRecord {
+        UsageRecord {
+            timestamp,
+            endpoint: "/v1/messages".to_string(),
+            model: model.to_string(),
+            api_key: "sk-***".to_string(),
+            credential_id: Some(3),
+            input_tokens: 10,
+            output_tokens: 20,
+            latency_ms: 100,
+            status: 200,
+            stream: true,
+            user: Some("alice".to_string()),
+            guardrail: None,
+            client_ip: Some("203.0.113.9".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_migrations_are_applied_once() {
+        let path = std::env::temp_dir().join(format!("kiro-db-{}.sqlite", uuid::Uuid::new_v4()));
+        let db = Database::open(&path).unwrap();
+        db.insert_usage(&[record("a", Utc::now())]).unwrap();
+        drop(db);
+
+        // 重新打开时不重复执行迁移，已有数据保留
+        let db = Database::open(&path).unwrap();
+        let version: usize = db
+            .conn
+            .lock()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+        assert_eq!(db.recent_usage(10).unwrap().len(), 1);
+        drop(db);
+
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.clone().into_os_string();
+            file.push(suffix);
+            let _ = fs::remove_file(file);
+        }
+    }
+
+    #[test]
+    fn test_recent_usage_and_prune() {
+        let db = Database::in_memory().unwrap();
+        let now = Utc::now();
+        db.insert_usage(&[record("old", now - chrono::Duration::days(40))])
+            .unwrap();
+        db.insert_usage(&[record("new", now - chrono::Duration::days(1))])
+            .unwrap();
+        db.insert_usage(&[record("newest", now)]).unwrap();
+
+        let recent = db.recent_usage(2).unwrap();
+        let models: Vec<&str> = recent.iter().map(|r| r.model.as_str()).collect();
+        assert_eq!(models, vec!["new", "newest"]);
+        assert_eq!(
+            recent[1].timestamp.timestamp_micros(),
+            now.timestamp_micros()
+        );
+        assert_eq!(recent[1].credential_id, Some(3));
+        assert_eq!(recent[1].user.as_deref(), Some("alice"));
+        assert!(recent[1].stream);
+
+        assert_eq!(db.prune_usage(now - chrono::Duration::days(30)).unwrap(), 1);
+        assert_eq!(db.prune_usage(now - chrono::Duration::days(30)).unwrap(), 0);
+        assert_eq!(db.recent_usage(10).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_request_log_roundtrip_and_prune() {
+        let db = Database::in_memory().unwrap();
+        let now = Utc::now();
+        let entry = |request_id: &str, timestamp: DateTime<Utc>| RequestLogEntry {
+            timestamp,
+            request_id: request_id.to_string(),
+            method: "POST".to_string(),
+            route: "/v1/messages".to_string(),
+            status: 500,
+            latency_ms: 12,
+            request_body: "{}".to_string(),
+            request_truncated: false,
+            response_body: "error".to_string(),
+            response_truncated: true,
+        };
+        db.insert_request_logs(&[entry("req_old", now - chrono::Duration::days(40))])
+            .unwrap();
+        db.insert_request_logs(&[entry("req_new", now)]).unwrap();
+
+        assert_eq!(
+            db.prune_request_logs(now - chrono::Duration::days(30))
+                .unwrap(),
+            1
+        );
+        let logs = db.request_logs(&RequestLogQuery::default()).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].request_id, "req_new");
+        assert!(logs[0].response_truncated);
+        assert_eq!(logs[0].timestamp.timestamp_micros(), now.timestamp_micros());
+    }
+
+    #[test]
+    fn test_request_logs_query() {
+        let db = Database::in_memory().unwrap();
+        let now = Utc::now();
+        for (index, status) in [200, 500, 200, 429].into_iter().enumerate() {
+            db.insert_request_logs(&[RequestLogEntry {
+                timestamp: now - chrono::Duration::hours(4 - index as i64),
+                request_id: format!("req_{}", index),
+                method: "POST".to_string(),
+                route: "/v1/messages".to_string(),
+                status,
+                latency_ms: 1,
+                request_body: "{}".to_string(),
+                request_truncated: false,
+                response_body: String::new(),
+                response_truncated: false,
+            }])
+            .unwrap();
+        }
+        let ids = |query: RequestLogQuery| -> Vec<String> {
+            db.request_logs(&query)
+                .unwrap()
+                .into_iter()
+                .map(|log| log.request_id)
+                .collect()
+        };
+
+        // 最近的若干条，按时间顺序
+        let query = RequestLogQuery {
+            limit: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(ids(query), vec!["req_2", "req_3"]);
+        let query = RequestLogQuery {
+            failed_only: true,
+            ..Default::default()
+        };
+        assert_eq!(ids(query), vec!["req_1", "req_3"]);
+        let query = RequestLogQuery {
+            since: Some(now - chrono::Duration::minutes(150)),
+            ..Default::default()
+        };
+        assert_eq!(ids(query), vec!["req_2", "req_3"]);
+        let query = RequestLogQuery {
+            request_ids: vec!["req_0".to_string(), "req_3".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(ids(query), vec!["req_0", "req_3"]);
+    }
+
+    #[test]
+    fn test_insert_audit() {
+        let db = Database::in_memory().unwrap();
+        let entry = AuditEntry {
+            seq: 1,
+            timestamp: "2026-01-01T00:00:00.000Z".to_string(),
+            method: "POST".to_string(),
+            path: "/api/admin/credentials".to_string(),
+            status: 201,
+            client_ip: None,
+            actor: "admin".to_string(),
+        };
+        db.insert_audit(&entry, "abc").unwrap();
+
+        let (actor, mac): (String, String) = db
+            .conn
+            .lock()
+            .query_row("SELECT actor, mac FROM audit WHERE seq = 1", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .optional()
+            .unwrap()
+            .unwrap();
+        assert_eq!(actor, "admin");
+        assert_eq!(mac, "abc");
+    }
+}
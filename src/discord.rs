@@ -0,0 +1,321 @@
+//! Discord 通知
+//!
+//! 配置 `discord`（频道 Webhook 地址）后，将错误率告警的触发与解除、以及凭据生命周期事件
+//! 以 Embed 卡片发送到频道，卡片字段列出凭据、用量占比、错误等信息。
+//! `events` 可选择推送的事件类型，留空表示全部推送。发送在后台进行，失败只记录警告。
+
+use std::sync::LazyLock;
+
+use chrono::Utc;
+use parking_lot::Mutex;
+use serde_json::{Value, json};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::http_client;
+use crate::kiro::webhook::CredentialEvent;
+use crate::metrics::{
+    self,
+    alerts::{AlertEvent, AlertScope},
+};
+use crate::model::config::DiscordConfig;
+
+/// 可选择的事件类型
+pub const EVENT_KINDS: &[&str] = &[
+    "alert_fired",
+    "alert_resolved",
+    "credential_disabled",
+    "quota_threshold",
+    "refresh_failing",
+    "pool_degraded",
+    "pool_recovered",
+];
+
+/// 发送请求的超时时间（秒）
+const SEND_TIMEOUT_SECS: u64 = 10;
+
+/// Embed 字段值的最大长度（Discord 限制）
+const MAX_FIELD_CHARS: usize = 1024;
+
+/// Embed 颜色
+const COLOR_CRITICAL: u32 = 0xE74C3C;
+const COLOR_WARNING: u32 = 0xF39C12;
+const COLOR_RESOLVED: u32 = 0x2ECC71;
+
+/// 当前配置（None 表示未启用）
+static CONFIG: LazyLock<Mutex<Option<DiscordConfig>>> = LazyLock::new(Mutex::default);
+
+static CLIENT: LazyLock<reqwest::Client> =
+    LazyLock::new(|| http_client::build_integration_client(SEND_TIMEOUT_SECS).unwrap_or_default());
+
+/// 设置 Webhook 地址与事件类型（None 表示关闭）
+pub fn configure(config: Option<DiscordConfig>) {
+    *CONFIG.lock() = config;
+}
+
+/// 是否已启用
+pub fn enabled() -> bool {
+    CONFIG.lock().is_some()
+}
+
+/// 在后台发送凭据生命周期事件
+pub fn send_credential_event(event: &CredentialEvent) {
+    send(event.kind(), credential_embed(event));
+}
+
+/// 启动后台任务，将错误率告警事件转发到 Discord
+pub fn spawn_alert_forwarder() {
+    let mut events = metrics::alerts().subscribe();
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => send(event.kind, alert_embed(&event)),
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Discord 通知跳过了 {} 个告警事件", skipped);
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// 配置是否选择了该事件类型
+fn wants(config: &DiscordConfig, kind: &str) -> bool {
+    config.events.is_empty() || config.events.iter().any(|e| e == kind)
+}
+
+/// 在后台发送一张 Embed 卡片（未启用、未选择该事件或不在 tokio 运行时内时忽略）
+fn send(kind: &str, embed: Value) {
+    let Some(config) = CONFIG.lock().clone() else {
+        return;
+    };
+    if !wants(&config, kind) {
+        return;
+    }
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    let body = json!({ "embeds": [embed] });
+    runtime.spawn(async move {
+        match CLIENT.post(&config.webhook_url).json(&body).send().await {
+            Ok(resp) if !resp.status().is_success() => {
+                tracing::warn!("发送 Discord 通知失败: HTTP {}", resp.status());
+            }
+            Ok(_) => {}
+            // Webhook 地址中含有令牌，不输出到日志
+            Err(e) => tracing::warn!("发送 Discord 通知失败: {}", e.without_url()),
+        }
+    });
+}
+
+/// 构造 Embed（字段均为行内显示）
+fn embed(title: &str, description: String, color: u32, fields: Vec<(&str, String)>) -> Value {
+    let fields: Vec<Value> = fields
+        .into_iter()
+        .map(|(name, value)| {
+            json!({
+                "name": name,
+                "value": value.chars().take(MAX_FIELD_CHARS).collect::<String>(),
+                "inline": true,
+            })
+        })
+        .collect();
+    json!({
+        "title": title,
+        "description": description,
+        "color": color,
+        "fields": fields,
+        "timestamp": Utc::now().to_rfc3339(),
+    })
+}
+
+/// 凭据的显示名称
+fn credential_label(credential_id: u64) -> String {
+    format!("#{}", credential_id)
+}
+
+/// 百分比文本
+fn percent(ratio: f64) -> String {
+    format!("{:.1}%", ratio * 100.0)
+}
+
+/// 凭据生命周期事件的 Embed
+fn credential_embed(event: &CredentialEvent) -> Value {
+    let description = event.summary();
+    match event {
+        CredentialEvent::CredentialDisabled {
+            credential_id,
+            reason,
+        } => embed(
+            "凭据已被自动禁用",
+            description,
+            COLOR_CRITICAL,
+            vec![
+                ("凭据", credential_label(*credential_id)),
+                ("原因", reason.to_string()),
+            ],
+        ),
+        CredentialEvent::QuotaThreshold {
+            credential_id,
+            current_usage,
+            usage_limit,
+            threshold,
+        } => embed(
+            "凭据额度即将用尽",
+            description,
+            COLOR_WARNING,
+            vec![
+                ("凭据", credential_label(*credential_id)),
+                (
+                    "用量",
+                    format!(
+                        "{}（{:.2} / {:.2}）",
+                        percent(current_usage / usage_limit),
+                        current_usage,
+                        usage_limit
+                    ),
+                ),
+                ("阈值", percent(*threshold)),
+            ],
+        ),
+        CredentialEvent::RefreshFailing {
+            credential_id,
+            failures,
+            error,
+        } => embed(
+            "Token 连续刷新失败",
+            description,
+            COLOR_WARNING,
+            vec![
+                ("凭据", credential_label(*credential_id)),
+                ("连续失败", failures.to_string()),
+                ("错误", error.clone()),
+            ],
+        ),
+        CredentialEvent::PoolDegraded {
+            available,
+            total,
+            min_available,
+        } => embed(
+            "可用凭据不足",
+            description,
+            COLOR_CRITICAL,
+            vec![
+                ("可用凭据", format!("{} / {}", available, total)),
+                ("下限", min_available.to_string()),
+            ],
+        ),
+        CredentialEvent::PoolRecovered {
+            available,
+            total,
+            min_available,
+        } => embed(
+            "可用凭据已恢复",
+            description,
+            COLOR_RESOLVED,
+            vec![
+                ("可用凭据", format!("{} / {}", available, total)),
+                ("下限", min_available.to_string()),
+            ],
+        ),
+    }
+}
+
+/// 告警事件的 Embed
+fn alert_embed(event: &AlertEvent) -> Value {
+    let alert = &event.alert;
+    let (scope, mut fields) = match alert.scope {
+        AlertScope::Global => ("全局".to_string(), Vec::new()),
+        AlertScope::Credential { credential_id } => (
+            format!("凭据 {} ", credential_label(credential_id)),
+            vec![("凭据", credential_label(credential_id))],
+        ),
+    };
+    fields.extend([
+        ("错误率", percent(alert.error_rate)),
+        (
+            "失败 / 请求",
+            format!("{} / {}", alert.errors, alert.requests),
+        ),
+        ("阈值", percent(alert.threshold)),
+    ]);
+    let (title, color) = if event.kind == "alert_fired" {
+        ("告警触发", COLOR_CRITICAL)
+    } else {
+        ("告警解除", COLOR_RESOLVED)
+    };
+    embed(
+        title,
+        format!("{}错误率 {}", scope, percent(alert.error_rate)),
+        color,
+        fields,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::alerts::Alert;
+
+    #[test]
+    fn test_event_selection() {
+        let mut config = DiscordConfig {
+            webhook_url: "https://discord.com/api/webhooks/1/token".to_string(),
+            events: Vec::new(),
+        };
+        assert!(EVENT_KINDS.iter().all(|kind| wants(&config, kind)));
+
+        config.events = vec!["pool_degraded".to_string(), "pool_recovered".to_string()];
+        assert!(wants(&config, "pool_degraded"));
+        assert!(!wants(&config, "alert_fired"));
+    }
+
+    #[test]
+    fn test_credential_embed() {
+        let embed = credential_embed(&CredentialEvent::QuotaThreshold {
+            credential_id: 3,
+            current_usage: 95.0,
+            usage_limit: 100.0,
+            threshold: 0.9,
+        });
+        assert_eq!(embed["color"], COLOR_WARNING);
+        assert_eq!(embed["description"], "凭据 #3 额度已使用 95.00 / 100.00");
+        assert_eq!(
+            embed["fields"],
+            json!([
+                {"name": "凭据", "value": "#3", "inline": true},
+                {"name": "用量", "value": "95.0%（95.00 / 100.00）", "inline": true},
+                {"name": "阈值", "value": "90.0%", "inline": true},
+            ])
+        );
+
+        // 过长的错误信息按 Discord 的限制截断
+        let embed = credential_embed(&CredentialEvent::RefreshFailing {
+            credential_id: 1,
+            failures: 3,
+            error: "x".repeat(2000),
+        });
+        assert_eq!(
+            embed["fields"][2]["value"].as_str().unwrap().len(),
+            MAX_FIELD_CHARS
+        );
+    }
+
+    #[test]
+    fn test_alert_embed() {
+        let embed = alert_embed(&AlertEvent {
+            kind: "alert_resolved",
+            alert: Alert {
+                scope: AlertScope::Global,
+                error_rate: 0.05,
+                requests: 100,
+                errors: 5,
+                threshold: 0.25,
+                since: Utc::now(),
+            },
+        });
+        assert_eq!(embed["title"], "告警解除");
+        assert_eq!(embed["color"], COLOR_RESOLVED);
+        assert_eq!(embed["description"], "全局错误率 5.0%");
+        assert_eq!(embed["fields"][1]["value"], "5 / 100");
+    }
+}
@@ -0,0 +1,235 @@
+//! 上游熔断
+//!
+//! 与按凭据的失败计数相互独立，统计滚动窗口内所有上游调用的结果：网络错误、5xx 与 408 的占比超过
+//! `failureRatio` 且调用数不少于 `minRequests` 时熔断。熔断期间请求直接返回 503，不再调用上游，
+//! 也就不会计入任何凭据的失败次数（区域性故障时避免所有凭据被逐个禁用）。
+//! 熔断 `openSecs` 秒后放行一个探测请求：成功则恢复，失败则继续熔断。
+
+use std::sync::LazyLock;
+use std::time::Instant;
+
+use parking_lot::Mutex;
+
+use crate::metrics::alerts::Window;
+use crate::model::config::CircuitBreakerConfig;
+
+/// 全局上游熔断器
+static BREAKER: LazyLock<CircuitBreaker> = LazyLock::new(CircuitBreaker::default);
+
+/// 获取全局上游熔断器
+pub fn breaker() -> &'static CircuitBreaker {
+    &BREAKER
+}
+
+/// 熔断期间拒绝请求
+///
+/// 作为 `anyhow::Error` 返回，调用方可通过 `downcast_ref` 识别并映射为 503
+#[derive(Debug)]
+pub struct CircuitOpen {
+    /// 建议的重试等待时间（秒）
+    pub retry_after_secs: u64,
+}
+
+impl std::fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "上游服务大范围故障，已暂停调用，请约 {} 秒后重试",
+            self.retry_after_secs
+        )
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+/// 熔断状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// 正常调用
+    Closed,
+    /// 熔断中，直到该时刻（秒）
+    Open { until: u64 },
+    /// 已放行探测请求（放行时刻），等待其结果
+    HalfOpen { since: u64 },
+}
+
+#[derive(Debug)]
+struct State {
+    config: Option<CircuitBreakerConfig>,
+    window: Window,
+    phase: Phase,
+}
+
+/// 上游熔断器
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    started: Instant,
+    state: Mutex<State>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            state: Mutex::new(State {
+                config: None,
+                window: Window::default(),
+                phase: Phase::Closed,
+            }),
+        }
+    }
+}
+
+impl CircuitBreaker {
+    /// 设置熔断参数（None 表示关闭熔断），重置统计与状态
+    pub fn configure(&self, config: Option<CircuitBreakerConfig>) {
+        let mut state = self.state.lock();
+        state.config = config;
+        state.window.clear();
+        state.phase = Phase::Closed;
+    }
+
+    /// 调用上游之前检查：熔断中返回 [`CircuitOpen`]
+    pub fn check(&self) -> Result<(), CircuitOpen> {
+        self.check_at(self.now())
+    }
+
+    /// 记录一次上游调用的结果（`None` 表示网络错误）
+    pub fn record(&self, status: Option<u16>) {
+        let failure = status.is_none_or(|s| s >= 500 || s == 408);
+        self.record_at(failure, self.now());
+    }
+
+    fn now(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+
+    fn check_at(&self, now: u64) -> Result<(), CircuitOpen> {
+        let mut state = self.state.lock();
+        let Some(open_secs) = state.config.as_ref().map(|c| c.open_secs) else {
+            return Ok(());
+        };
+        let blocked_until = match state.phase {
+            Phase::Closed => return Ok(()),
+            Phase::Open { until } => until,
+            // 上一个探测请求迟迟没有结果（如客户端断开）时，一个熔断周期后放行新的探测请求
+            Phase::HalfOpen { since } => since + open_secs,
+        };
+        if now < blocked_until {
+            return Err(CircuitOpen {
+                retry_after_secs: (blocked_until - now).max(1),
+            });
+        }
+        tracing::info!("上游熔断到期，放行探测请求");
+        state.phase = Phase::HalfOpen { since: now };
+        Ok(())
+    }
+
+    fn record_at(&self, failure: bool, now: u64) {
+        let mut state = self.state.lock();
+        let Some(config) = state.config.clone() else {
+            return;
+        };
+        match state.phase {
+            Phase::Closed => {
+                state.window.record(now, failure);
+                let (requests, failures) = state.window.totals(now, config.window_secs);
+                let ratio = failures as f64 / requests.max(1) as f64;
+                if requests >= config.min_requests && ratio >= config.failure_ratio {
+                    tracing::error!(
+                        "上游调用失败占比 {:.1}%（{}/{}），熔断 {} 秒",
+                        ratio * 100.0,
+                        failures,
+                        requests,
+                        config.open_secs
+                    );
+                    state.phase = Phase::Open {
+                        until: now + config.open_secs,
+                    };
+                }
+            }
+            Phase::HalfOpen { .. } if failure => {
+                tracing::warn!("探测请求失败，继续熔断 {} 秒", config.open_secs);
+                state.phase = Phase::Open {
+                    until: now + config.open_secs,
+                };
+            }
+            Phase::HalfOpen { .. } => {
+                tracing::info!("探测请求成功，上游熔断解除");
+                state.window.clear();
+                state.phase = Phase::Closed;
+            }
+            // 熔断前发出的调用此时才返回，不影响状态
+            Phase::Open { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker() -> CircuitBreaker {
+        let breaker = CircuitBreaker::default();
+        breaker.configure(Some(CircuitBreakerConfig {
+            failure_ratio: 0.8,
+            window_secs: 60,
+            min_requests: 5,
+            open_secs: 30,
+        }));
+        breaker
+    }
+
+    #[test]
+    fn test_opens_and_recovers() {
+        let breaker = breaker();
+        for _ in 0..4 {
+            breaker.record_at(true, 0);
+        }
+        // 样本不足时不熔断
+        assert!(breaker.check_at(0).is_ok());
+        breaker.record_at(true, 1);
+        assert_eq!(breaker.check_at(10).unwrap_err().retry_after_secs, 21);
+
+        // 到期后只放行一个探测请求
+        assert!(breaker.check_at(31).is_ok());
+        assert!(breaker.check_at(32).is_err());
+        breaker.record_at(true, 33);
+        assert!(breaker.check_at(40).is_err());
+
+        assert!(breaker.check_at(63).is_ok());
+        breaker.record_at(false, 64);
+        assert!(breaker.check_at(64).is_ok());
+        assert!(breaker.check_at(65).is_ok());
+    }
+
+    #[test]
+    fn test_ratio_below_threshold() {
+        let breaker = breaker();
+        for i in 0..10 {
+            breaker.record_at(i % 2 == 0, 0);
+        }
+        assert!(breaker.check_at(0).is_ok());
+    }
+
+    #[test]
+    fn test_stalled_probe_is_replaced() {
+        let breaker = breaker();
+        for _ in 0..5 {
+            breaker.record_at(true, 0);
+        }
+        assert!(breaker.check_at(30).is_ok());
+        // 探测请求没有结果，一个熔断周期后放行新的探测请求
+        assert!(breaker.check_at(59).is_err());
+        assert!(breaker.check_at(60).is_ok());
+    }
+
+    #[test]
+    fn test_disabled() {
+        let breaker = CircuitBreaker::default();
+        for _ in 0..100 {
+            breaker.record(None);
+        }
+        assert!(breaker.check().is_ok());
+    }
+}
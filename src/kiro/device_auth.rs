@@ -0,0 +1,150 @@
+//! IdC / Builder ID 设备授权流程（AWS SSO OIDC）
+//!
+//! 注册公共客户端后发起设备授权，用户在浏览器中确认验证码，
+//! 期间按服务端给出的间隔轮询换取 Token，得到可直接写入凭据文件的 `idc` 凭据。
+
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::http_client::{ProxyConfig, build_client};
+use crate::kiro::model::credentials::KiroCredentials;
+use crate::kiro::model::token_refresh::{
+    DeviceAuthorizationRequest, DeviceAuthorizationResponse, DeviceTokenRequest,
+    IdcRefreshResponse, OidcErrorResponse, RegisterClientRequest, RegisterClientResponse,
+};
+use crate::model::config::UpstreamEndpoint;
+
+/// AWS Builder ID 的起始地址
+pub const BUILDER_ID_START_URL: &str = "https://view.awsapps.com/start";
+
+/// 注册客户端时申请的权限
+const SCOPES: &[&str] = &[
+    "codewhisperer:completions",
+    "codewhisperer:analysis",
+    "codewhisperer:conversations",
+    "codewhisperer:transformations",
+    "codewhisperer:taskassist",
+];
+
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// 进行中的设备授权
+pub struct DeviceAuthorization {
+    client: reqwest::Client,
+    /// AWS SSO OIDC 基础地址
+    base: String,
+    client_id: String,
+    client_secret: String,
+    device: DeviceAuthorizationResponse,
+}
+
+impl DeviceAuthorization {
+    /// 注册客户端并发起设备授权（`base` 为 AWS SSO OIDC 基础地址）
+    pub async fn start(
+        base: &str,
+        start_url: &str,
+        proxy: Option<&ProxyConfig>,
+    ) -> anyhow::Result<Self> {
+        let client = build_client(UpstreamEndpoint::Auth, proxy, 60)?;
+
+        let registered: RegisterClientResponse = client
+            .post(format!("{}/client/register", base))
+            .json(&RegisterClientRequest {
+                client_name: "kiro-rs".to_string(),
+                client_type: "public".to_string(),
+                scopes: SCOPES.iter().map(|s| s.to_string()).collect(),
+            })
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|e| anyhow::anyhow!("注册 OIDC 客户端失败: {}", e))?
+            .json()
+            .await?;
+
+        let device: DeviceAuthorizationResponse = client
+            .post(format!("{}/device_authorization", base))
+            .json(&DeviceAuthorizationRequest {
+                client_id: registered.client_id.clone(),
+                client_secret: registered.client_secret.clone(),
+                start_url: start_url.to_string(),
+            })
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|e| anyhow::anyhow!("发起设备授权失败: {}", e))?
+            .json()
+            .await?;
+
+        Ok(Self {
+            client,
+            base: base.to_string(),
+            client_id: registered.client_id,
+            client_secret: registered.client_secret,
+            device,
+        })
+    }
+
+    /// 用户需要输入的验证码
+    pub fn user_code(&self) -> &str {
+        &self.device.user_code
+    }
+
+    /// 用户需要打开的验证地址（优先使用已带验证码的地址）
+    pub fn verification_uri(&self) -> &str {
+        self.device
+            .verification_uri_complete
+            .as_deref()
+            .unwrap_or(&self.device.verification_uri)
+    }
+
+    /// 轮询直到用户确认授权，返回 `idc` 凭据
+    pub async fn wait(self) -> anyhow::Result<KiroCredentials> {
+        let mut interval = Duration::from_secs(self.device.interval.unwrap_or(5).max(1) as u64);
+        let deadline =
+            tokio::time::Instant::now() + Duration::from_secs(self.device.expires_in.max(0) as u64);
+        let url = format!("{}/token", self.base);
+        let body = DeviceTokenRequest {
+            client_id: self.client_id.clone(),
+            client_secret: self.client_secret.clone(),
+            device_code: self.device.device_code.clone(),
+            grant_type: DEVICE_CODE_GRANT_TYPE.to_string(),
+        };
+
+        loop {
+            if tokio::time::Instant::now() >= deadline {
+                anyhow::bail!("设备授权已过期，请重新执行");
+            }
+            tokio::time::sleep(interval).await;
+
+            let response = self.client.post(&url).json(&body).send().await?;
+            if response.status().is_success() {
+                let data: IdcRefreshResponse = response.json().await?;
+                return Ok(KiroCredentials {
+                    access_token: Some(data.access_token),
+                    refresh_token: data.refresh_token,
+                    expires_at: data
+                        .expires_in
+                        .map(|secs| (Utc::now() + chrono::Duration::seconds(secs)).to_rfc3339()),
+                    auth_method: Some("idc".to_string()),
+                    client_id: Some(self.client_id),
+                    client_secret: Some(self.client_secret),
+                    ..Default::default()
+                });
+            }
+
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            match serde_json::from_str::<OidcErrorResponse>(&text) {
+                Ok(e) if e.error == "authorization_pending" => {}
+                Ok(e) if e.error == "slow_down" => interval += Duration::from_secs(5),
+                Ok(e) => anyhow::bail!(
+                    "设备授权失败: {} {}",
+                    e.error,
+                    e.error_description.unwrap_or_default()
+                ),
+                Err(_) => anyhow::bail!("设备授权失败: {} {}", status, text),
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! 非流式请求对冲
+//!
+//! 记录最近非流式请求的完整耗时（直到读完响应体），以配置的分位数作为耗时预算。
+//! 请求超过预算仍未完成时，在另一个凭据上发起相同请求，取先成功者，以额外的额度消耗换取更低的尾延迟。
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use crate::model::config::HedgingConfig;
+
+/// 保留的耗时样本数
+const MAX_SAMPLES: usize = 256;
+
+/// 最近非流式请求的耗时样本
+#[derive(Debug, Default)]
+pub struct LatencySamples {
+    samples: Mutex<VecDeque<Duration>>,
+}
+
+impl LatencySamples {
+    /// 记录一个耗时样本，超出容量时丢弃最旧的样本
+    pub fn record(&self, latency: Duration) {
+        let mut samples = self.samples.lock();
+        if samples.len() == MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(latency);
+    }
+
+    /// 对冲前的等待时间：样本不足 `minSamples` 时为 None（不对冲）
+    pub fn hedge_delay(&self, config: &HedgingConfig) -> Option<Duration> {
+        let mut sorted: Vec<Duration> = {
+            let samples = self.samples.lock();
+            if samples.len() < config.min_samples.max(1) {
+                return None;
+            }
+            samples.iter().copied().collect()
+        };
+        sorted.sort_unstable();
+        let rank = (config.percentile.clamp(0.0, 1.0) * sorted.len() as f64).ceil() as usize;
+        let budget = sorted[rank.clamp(1, sorted.len()) - 1];
+        Some(budget.max(Duration::from_millis(config.min_delay_ms)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(min_delay_ms: u64) -> HedgingConfig {
+        HedgingConfig {
+            percentile: 0.9,
+            min_delay_ms,
+            min_samples: 10,
+        }
+    }
+
+    #[test]
+    fn test_hedge_delay_percentile() {
+        let samples = LatencySamples::default();
+        for secs in 1..=9 {
+            samples.record(Duration::from_secs(secs));
+        }
+        // 样本不足时不对冲
+        assert_eq!(samples.hedge_delay(&config(0)), None);
+
+        samples.record(Duration::from_secs(10));
+        assert_eq!(
+            samples.hedge_delay(&config(0)),
+            Some(Duration::from_secs(9))
+        );
+        // 不低于预算下限
+        assert_eq!(
+            samples.hedge_delay(&config(15_000)),
+            Some(Duration::from_secs(15))
+        );
+    }
+
+    #[test]
+    fn test_samples_are_bounded() {
+        let samples = LatencySamples::default();
+        for _ in 0..MAX_SAMPLES {
+            samples.record(Duration::from_secs(60));
+        }
+        for _ in 0..MAX_SAMPLES {
+            samples.record(Duration::from_secs(1));
+        }
+        assert_eq!(samples.samples.lock().len(), MAX_SAMPLES);
+        assert_eq!(
+            samples.hedge_delay(&config(0)),
+            Some(Duration::from_secs(1))
+        );
+    }
+}
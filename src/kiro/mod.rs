@@ -1,7 +1,12 @@
 //! Kiro API 客户端模块
 
+pub mod circuit;
+pub mod device_auth;
+pub mod hedge;
 pub mod machine_id;
 pub mod model;
 pub mod parser;
 pub mod provider;
+pub mod retry_budget;
 pub mod token_manager;
+pub mod webhook;
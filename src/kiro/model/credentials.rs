@@ -47,6 +47,15 @@ pub struct KiroCredentials {
     #[serde(default)]
     #[serde(skip_serializing_if = "is_zero")]
     pub priority: u32,
+
+    /// 凭据标签（用于模型路由，如 `pro`）
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// 凭据所在区域（覆盖全局 `region`）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
 }
 
 /// 判断是否为零（用于跳过序列化）
@@ -59,6 +68,8 @@ fn is_zero(value: &u32) -> bool {
 /// 自动识别配置文件格式：
 /// - 单对象格式（旧格式，向后兼容）
 /// - 数组格式（新格式，支持多凭据）
+// 仅在加载凭证文件时短暂存在，不值得为大小差异装箱
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum CredentialsConfig {
@@ -199,6 +210,8 @@ mod tests {
             client_id: None,
             client_secret: None,
             priority: 0,
+            tags: Vec::new(),
+            region: None,
         };
 
         let json = creds.to_pretty_json().unwrap();
@@ -90,6 +90,11 @@ pub enum Event {
 }
 
 impl Event {
+    /// 是否为内容事件（助手文本或工具调用），计费、上下文使用率等元数据事件不算
+    pub fn is_content(&self) -> bool {
+        matches!(self, Self::AssistantResponse(_) | Self::ToolUse(_))
+    }
+
     /// 从帧解析事件
     pub fn from_frame(frame: Frame) -> ParseResult<Self> {
         let message_type = frame.message_type().unwrap_or("event");
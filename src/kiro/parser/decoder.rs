@@ -32,7 +32,7 @@
 
 use super::error::{ParseError, ParseResult};
 use super::frame::{Frame, PRELUDE_SIZE, parse_frame};
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 
 /// 默认最大缓冲区大小 (16 MB)
 pub const DEFAULT_MAX_BUFFER_SIZE: usize = 16 * 1024 * 1024;
@@ -40,7 +40,7 @@ pub const DEFAULT_MAX_BUFFER_SIZE: usize = 16 * 1024 * 1024;
 /// 默认最大连续错误数
 pub const DEFAULT_MAX_ERRORS: usize = 5;
 
-/// 默认初始缓冲区容量
+/// 默认缓冲区容量（拼接跨数据块的帧时一次分配的最小容量）
 pub const DEFAULT_BUFFER_CAPACITY: usize = 8192;
 
 /// 解码器状态
@@ -66,6 +66,9 @@ pub enum DecoderState {
 ///
 /// 用于从字节流中解析 AWS Event Stream 消息帧
 ///
+/// 缓冲区为只读的 [`Bytes`]：没有未解析的剩余数据时直接接管新数据块，
+/// 只在帧跨越数据块边界时才拼接复制；解码出的帧引用缓冲区的切片，不复制负载。
+///
 /// # Example
 ///
 /// ```rust,ignore
@@ -73,7 +76,7 @@ pub enum DecoderState {
 ///
 /// let mut decoder = EventStreamDecoder::new();
 ///
-/// // 提供流数据
+/// // 提供流数据（reqwest 返回的 Bytes 数据块）
 /// decoder.feed(chunk)?;
 ///
 /// // 解码所有可用帧
@@ -85,8 +88,10 @@ pub enum DecoderState {
 /// }
 /// ```
 pub struct EventStreamDecoder {
-    /// 内部缓冲区
-    buffer: BytesMut,
+    /// 内部缓冲区（尚未解析的数据）
+    buffer: Bytes,
+    /// 拼接数据块时一次分配的最小容量
+    capacity: usize,
     /// 当前状态
     state: DecoderState,
     /// 已处理的帧数量
@@ -116,7 +121,8 @@ impl EventStreamDecoder {
     /// 创建具有指定缓冲区大小的解码器
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            buffer: BytesMut::with_capacity(capacity),
+            buffer: Bytes::new(),
+            capacity,
             state: DecoderState::Ready,
             frames_decoded: 0,
             error_count: 0,
@@ -129,7 +135,8 @@ impl EventStreamDecoder {
     /// 创建具有自定义配置的解码器
     pub fn with_config(capacity: usize, max_errors: usize, max_buffer_size: usize) -> Self {
         Self {
-            buffer: BytesMut::with_capacity(capacity),
+            buffer: Bytes::new(),
+            capacity,
             state: DecoderState::Ready,
             frames_decoded: 0,
             error_count: 0,
@@ -144,7 +151,9 @@ impl EventStreamDecoder {
     /// # Returns
     /// - `Ok(())` - 数据已添加到缓冲区
     /// - `Err(BufferOverflow)` - 缓冲区已满
-    pub fn feed(&mut self, data: &[u8]) -> ParseResult<()> {
+    pub fn feed(&mut self, data: impl Into<Bytes>) -> ParseResult<()> {
+        let data = data.into();
+
         // 检查缓冲区大小限制
         let new_size = self.buffer.len() + data.len();
         if new_size > self.max_buffer_size {
@@ -154,7 +163,23 @@ impl EventStreamDecoder {
             });
         }
 
-        self.buffer.extend_from_slice(data);
+        if self.buffer.is_empty() {
+            // 没有剩余数据：直接接管数据块，不复制
+            self.buffer = data;
+        } else {
+            // 帧跨越数据块边界：拼接剩余数据与新数据块。
+            // 之前解码出的帧都已释放时原地追加（容量按需倍增），避免大帧跨越多个数据块时反复复制
+            let mut joined = match std::mem::take(&mut self.buffer).try_into_mut() {
+                Ok(buffer) => buffer,
+                Err(buffer) => {
+                    let mut joined = BytesMut::with_capacity(new_size.max(self.capacity));
+                    joined.extend_from_slice(&buffer);
+                    joined
+                }
+            };
+            joined.extend_from_slice(&data);
+            self.buffer = joined.freeze();
+        }
 
         // 从 Recovering 状态恢复到 Ready
         if self.state == DecoderState::Recovering {
@@ -396,6 +421,7 @@ impl<'a> Iterator for DecodeIter<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::kiro::parser::frame::encode_frame;
 
     #[test]
     fn test_decoder_new() {
@@ -408,31 +434,59 @@ mod tests {
     #[test]
     fn test_decoder_feed() {
         let mut decoder = EventStreamDecoder::new();
-        assert!(decoder.feed(&[1, 2, 3, 4]).is_ok());
+        assert!(decoder.feed(vec![1, 2, 3, 4]).is_ok());
         assert_eq!(decoder.buffer_len(), 4);
     }
 
     #[test]
     fn test_decoder_buffer_overflow() {
         let mut decoder = EventStreamDecoder::with_config(1024, 5, 100);
-        let result = decoder.feed(&[0u8; 101]);
+        let result = decoder.feed(vec![0u8; 101]);
         assert!(matches!(result, Err(ParseError::BufferOverflow { .. })));
     }
 
     #[test]
     fn test_decoder_insufficient_data() {
         let mut decoder = EventStreamDecoder::new();
-        decoder.feed(&[0u8; 10]).unwrap();
+        decoder.feed(vec![0u8; 10]).unwrap();
 
         let result = decoder.decode();
         assert!(matches!(result, Ok(None)));
         assert_eq!(decoder.state(), DecoderState::Ready);
     }
 
+    #[test]
+    fn test_decoder_frames_across_chunks() {
+        let frame = encode_frame(&[(":event-type", "e")], b"{\"content\":\"hi\"}");
+        let body = frame.repeat(3);
+        let mut decoder = EventStreamDecoder::new();
+
+        // 第一个数据块恰好是一个完整帧：负载直接引用该数据块
+        let first = Bytes::copy_from_slice(&body[..frame.len()]);
+        decoder.feed(first.clone()).unwrap();
+        let decoded = decoder.decode().unwrap().unwrap();
+        assert!(first.as_ptr_range().contains(&decoded.payload.as_ptr()));
+
+        // 其余两帧切成不对齐的数据块
+        let mut frames = Vec::new();
+        for chunk in body[frame.len()..].chunks(7) {
+            decoder.feed(chunk.to_vec()).unwrap();
+            frames.extend(decoder.decode_iter().map(|f| f.unwrap()));
+        }
+        assert_eq!(frames.len(), 2);
+        assert!(
+            frames
+                .iter()
+                .all(|f| f.payload == b"{\"content\":\"hi\"}"[..])
+        );
+        assert_eq!(decoder.buffer_len(), 0);
+        assert_eq!(decoder.frames_decoded(), 3);
+    }
+
     #[test]
     fn test_decoder_reset() {
         let mut decoder = EventStreamDecoder::new();
-        decoder.feed(&[1, 2, 3, 4]).unwrap();
+        decoder.feed(vec![1, 2, 3, 4]).unwrap();
 
         decoder.reset();
         assert_eq!(decoder.state(), DecoderState::Ready);
@@ -15,6 +15,10 @@
 //! - Headers: 头部数据
 //! - Payload: 载荷数据（通常是 JSON）
 //! - Message CRC: 整个消息（不含 Message CRC 自身）的 CRC32 校验
+//!
+//! 解析出的帧头部与负载都是输入缓冲区的切片（[`Bytes`] 引用计数），不复制数据。
+
+use bytes::Bytes;
 
 use super::crc::crc32;
 use super::error::{ParseError, ParseResult};
@@ -35,7 +39,7 @@ pub struct Frame {
     /// 消息头部
     pub headers: Headers,
     /// 消息负载
-    pub payload: Vec<u8>,
+    pub payload: Bytes,
 }
 
 impl Frame {
@@ -66,13 +70,13 @@ impl Frame {
 /// 缓冲区管理由上层 `EventStreamDecoder` 负责。
 ///
 /// # Arguments
-/// * `buffer` - 输入缓冲区
+/// * `buffer` - 输入缓冲区（解析出的帧引用其中的切片）
 ///
 /// # Returns
 /// - `Ok(Some((frame, consumed)))` - 成功解析，返回帧和消费的字节数
 /// - `Ok(None)` - 数据不足，需要更多数据
 /// - `Err(e)` - 解析错误
-pub fn parse_frame(buffer: &[u8]) -> ParseResult<Option<(Frame, usize)>> {
+pub fn parse_frame(buffer: &Bytes) -> ParseResult<Option<(Frame, usize)>> {
     // 检查是否有足够的数据读取 prelude
     if buffer.len() < PRELUDE_SIZE {
         return Ok(None);
@@ -143,26 +147,61 @@ pub fn parse_frame(buffer: &[u8]) -> ParseResult<Option<(Frame, usize)>> {
         ));
     }
 
-    let headers = parse_headers(&buffer[headers_start..headers_end], header_length)?;
+    let headers = parse_headers(&buffer.slice(headers_start..headers_end), header_length)?;
 
     // 提取 payload (去除最后4字节的 message_crc)
     let payload_start = headers_end;
     let payload_end = total_length - 4;
-    let payload = buffer[payload_start..payload_end].to_vec();
+    let payload = buffer.slice(payload_start..payload_end);
 
     Ok(Some((Frame { headers, payload }, total_length)))
 }
 
+/// 按 AWS Event Stream 格式编码一个只含字符串头部的帧（仅用于测试）
+#[cfg(test)]
+pub(crate) fn encode_frame(headers: &[(&str, &str)], payload: &[u8]) -> Vec<u8> {
+    let mut header_bytes = Vec::new();
+    for (name, value) in headers {
+        header_bytes.push(name.len() as u8);
+        header_bytes.extend_from_slice(name.as_bytes());
+        header_bytes.push(7);
+        header_bytes.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        header_bytes.extend_from_slice(value.as_bytes());
+    }
+    let total_length = PRELUDE_SIZE + header_bytes.len() + payload.len() + 4;
+    let mut frame = Vec::with_capacity(total_length);
+    frame.extend_from_slice(&(total_length as u32).to_be_bytes());
+    frame.extend_from_slice(&(header_bytes.len() as u32).to_be_bytes());
+    let prelude_crc = crc32(&frame);
+    frame.extend_from_slice(&prelude_crc.to_be_bytes());
+    frame.extend_from_slice(&header_bytes);
+    frame.extend_from_slice(payload);
+    let message_crc = crc32(&frame);
+    frame.extend_from_slice(&message_crc.to_be_bytes());
+    frame
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_frame_insufficient_data() {
-        let buffer = [0u8; 10]; // 小于 PRELUDE_SIZE
+        let buffer = Bytes::from_static(&[0u8; 10]); // 小于 PRELUDE_SIZE
         assert!(matches!(parse_frame(&buffer), Ok(None)));
     }
 
+    #[test]
+    fn test_frame_borrows_buffer() {
+        let buffer = Bytes::from(encode_frame(&[(":event-type", "e")], b"{}"));
+        let (frame, consumed) = parse_frame(&buffer).unwrap().unwrap();
+        assert_eq!(consumed, buffer.len());
+        assert_eq!(frame.event_type(), Some("e"));
+        assert_eq!(frame.payload, &b"{}"[..]);
+        // 负载是输入缓冲区的切片
+        assert!(buffer.as_ptr_range().contains(&frame.payload.as_ptr()));
+    }
+
     #[test]
     fn test_frame_message_too_small() {
         // 构造一个 total_length = 10 的 prelude (小于最小值)
@@ -172,7 +211,7 @@ mod tests {
         let prelude_crc = crc32(&buffer[0..8]);
         buffer[8..12].copy_from_slice(&prelude_crc.to_be_bytes());
 
-        let result = parse_frame(&buffer);
+        let result = parse_frame(&Bytes::from(buffer));
         assert!(matches!(result, Err(ParseError::MessageTooSmall { .. })));
     }
 }
@@ -1,9 +1,11 @@
 //! AWS Event Stream 头部解析
 //!
 //! 实现 AWS Event Stream 协议的头部解析功能
+//!
+//! 头部名称与字符串、字节数组类型的值均为帧数据的切片，解析时不复制
 
 use super::error::{ParseError, ParseResult};
-use std::collections::HashMap;
+use bytes::Bytes;
 
 /// 头部值类型标识
 ///
@@ -53,44 +55,49 @@ pub enum HeaderValue {
     Short(i16),
     Integer(i32),
     Long(i64),
-    ByteArray(Vec<u8>),
-    String(String),
+    ByteArray(Bytes),
+    /// 字符串值（未校验 UTF-8，读取时校验）
+    String(Bytes),
     Timestamp(i64),
     Uuid([u8; 16]),
 }
 
 impl HeaderValue {
-    /// 尝试获取字符串值
+    /// 尝试获取字符串值（不是合法的 UTF-8 时为 None）
     pub fn as_str(&self) -> Option<&str> {
         match self {
-            Self::String(s) => Some(s),
+            Self::String(s) => std::str::from_utf8(s).ok(),
             _ => None,
         }
     }
 }
 
 /// 消息头部集合
+///
+/// 每帧只有少量头部，按顺序保存并线性查找，同名头部以后出现的为准
 #[derive(Debug, Clone, Default)]
 pub struct Headers {
-    inner: HashMap<String, HeaderValue>,
+    inner: Vec<(Bytes, HeaderValue)>,
 }
 
 impl Headers {
     /// 创建空的头部集合
     pub fn new() -> Self {
-        Self {
-            inner: HashMap::new(),
-        }
+        Self { inner: Vec::new() }
     }
 
     /// 插入头部
-    pub fn insert(&mut self, name: String, value: HeaderValue) {
-        self.inner.insert(name, value);
+    pub fn insert(&mut self, name: impl Into<Bytes>, value: HeaderValue) {
+        self.inner.push((name.into(), value));
     }
 
     /// 获取头部值
     pub fn get(&self, name: &str) -> Option<&HeaderValue> {
-        self.inner.get(name)
+        self.inner
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name.as_bytes())
+            .map(|(_, v)| v)
     }
 
     /// 获取字符串类型的头部值
@@ -122,12 +129,12 @@ impl Headers {
 /// 从字节流解析头部
 ///
 /// # Arguments
-/// * `data` - 头部数据切片
+/// * `data` - 头部数据（解析结果引用其中的切片）
 /// * `header_length` - 头部总长度
 ///
 /// # Returns
 /// 解析后的 Headers 结构
-pub fn parse_headers(data: &[u8], header_length: usize) -> ParseResult<Headers> {
+pub fn parse_headers(data: &Bytes, header_length: usize) -> ParseResult<Headers> {
     // 验证数据长度是否足够
     if data.len() < header_length {
         return Err(ParseError::Incomplete {
@@ -161,7 +168,7 @@ pub fn parse_headers(data: &[u8], header_length: usize) -> ParseResult<Headers>
                 available: data.len() - offset,
             });
         }
-        let name = String::from_utf8_lossy(&data[offset..offset + name_len]).to_string();
+        let name = data.slice(offset..offset + name_len);
         offset += name_len;
 
         // 读取值类型 (1 byte)
@@ -175,7 +182,7 @@ pub fn parse_headers(data: &[u8], header_length: usize) -> ParseResult<Headers>
         offset += 1;
 
         // 根据类型解析值
-        let value = parse_header_value(&data[offset..], value_type, &mut offset)?;
+        let value = parse_header_value(&data.slice(offset..), value_type, &mut offset)?;
         headers.insert(name, value);
     }
 
@@ -184,7 +191,7 @@ pub fn parse_headers(data: &[u8], header_length: usize) -> ParseResult<Headers>
 
 /// 解析头部值
 fn parse_header_value(
-    data: &[u8],
+    data: &Bytes,
     value_type: HeaderValueType,
     global_offset: &mut usize,
 ) -> ParseResult<HeaderValue> {
@@ -231,7 +238,7 @@ fn parse_header_value(
             ensure_bytes(data, 2)?;
             let len = u16::from_be_bytes([data[0], data[1]]) as usize;
             ensure_bytes(data, 2 + len)?;
-            let v = data[2..2 + len].to_vec();
+            let v = data.slice(2..2 + len);
             local_offset = 2 + len;
             Ok(HeaderValue::ByteArray(v))
         }
@@ -239,7 +246,7 @@ fn parse_header_value(
             ensure_bytes(data, 2)?;
             let len = u16::from_be_bytes([data[0], data[1]]) as usize;
             ensure_bytes(data, 2 + len)?;
-            let v = String::from_utf8_lossy(&data[2..2 + len]).to_string();
+            let v = data.slice(2..2 + len);
             local_offset = 2 + len;
             Ok(HeaderValue::String(v))
         }
@@ -287,7 +294,7 @@ mod tests {
 
     #[test]
     fn test_header_value_as_str() {
-        let value = HeaderValue::String("test".to_string());
+        let value = HeaderValue::String(Bytes::from_static(b"test"));
         assert_eq!(value.as_str(), Some("test"));
 
         let value = HeaderValue::Bool(true);
@@ -298,8 +305,8 @@ mod tests {
     fn test_headers_get_string() {
         let mut headers = Headers::new();
         headers.insert(
-            ":message-type".to_string(),
-            HeaderValue::String("event".to_string()),
+            ":message-type",
+            HeaderValue::String(Bytes::from_static(b"event")),
         );
         assert_eq!(headers.message_type(), Some("event"));
     }
@@ -310,7 +317,7 @@ mod tests {
         // 头部名: "x" (长度 1)
         // 值类型: 7 (String)
         // 值: "ab" (长度 2)
-        let data = [1u8, b'x', 7, 0, 2, b'a', b'b'];
+        let data = Bytes::from_static(&[1u8, b'x', 7, 0, 2, b'a', b'b']);
         let headers = parse_headers(&data, data.len()).unwrap();
         assert_eq!(headers.get_string("x"), Some("ab"));
     }
@@ -7,16 +7,25 @@
 use reqwest::Client;
 use reqwest::header::{AUTHORIZATION, CONNECTION, CONTENT_TYPE, HOST, HeaderMap, HeaderValue};
 use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
+use tracing::Instrument;
+use tracing::field::Empty;
 use uuid::Uuid;
 
-use crate::http_client::{ProxyConfig, build_client};
+use crate::common::headers;
+use crate::http_client::{ProxyConfig, build_tracked_client, send_timed};
+use crate::kiro::circuit;
+use crate::kiro::hedge::LatencySamples;
 use crate::kiro::machine_id;
-use crate::kiro::token_manager::{CallContext, MultiTokenManager};
-
-#[cfg(test)]
 use crate::kiro::model::credentials::KiroCredentials;
+use crate::kiro::retry_budget;
+use crate::kiro::token_manager::{CallContext, MultiTokenManager};
+use crate::limit;
+use crate::metrics;
+use crate::model::config::{HedgingConfig, UpstreamEndpoint, endpoint_host};
+use crate::timing::{self, Phase};
 
 /// 每个凭据的最大重试次数
 const MAX_RETRIES_PER_CREDENTIAL: usize = 3;
@@ -24,6 +33,67 @@ const MAX_RETRIES_PER_CREDENTIAL: usize = 3;
 /// 总重试次数硬上限（避免无限重试）
 const MAX_TOTAL_RETRIES: usize = 9;
 
+/// 成功响应所使用的凭据 ID
+///
+/// 作为扩展附加在上游返回的 `reqwest::Response` 上，供调用方做用量归属
+#[derive(Debug, Clone, Copy)]
+pub struct CredentialId(pub u64);
+
+/// 成功响应之前的上游调用尝试次数（含本次，首次即成功为 1）
+///
+/// 与 [`CredentialId`] 一样作为扩展附加在上游返回的 `reqwest::Response` 上
+#[derive(Debug, Clone, Copy)]
+pub struct UpstreamAttempts(pub u32);
+
+/// 上游以输入过长为由拒绝了请求
+///
+/// 作为 `anyhow::Error` 返回，调用方可通过 `downcast_ref` 识别并映射为 400
+#[derive(Debug)]
+pub struct ContextLengthExceeded {
+    /// 上游返回的原始响应体
+    pub body: String,
+}
+
+impl std::fmt::Display for ContextLengthExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "输入超出模型上下文长度: {}", self.body)
+    }
+}
+
+impl std::error::Error for ContextLengthExceeded {}
+
+/// 缓冲的上游响应超过 `maxBufferedResponseBytes`
+///
+/// 作为 `anyhow::Error` 返回，调用方可通过 `downcast_ref` 识别
+#[derive(Debug)]
+pub struct ResponseTooLarge {
+    /// 缓冲上限（字节）
+    pub limit: usize,
+}
+
+impl std::fmt::Display for ResponseTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "上游响应超过缓冲上限 {} 字节（maxBufferedResponseBytes），已中止读取",
+            self.limit
+        )
+    }
+}
+
+impl std::error::Error for ResponseTooLarge {}
+
+/// 对冲请求中的一路上游调用
+#[derive(Debug, Clone, Copy)]
+enum Leg<'a> {
+    /// 未对冲的普通调用
+    Single,
+    /// 主调用：记录当前使用的凭据，供对冲调用避开
+    Primary(&'a AtomicU64),
+    /// 对冲调用：避开主调用当前使用的凭据
+    Hedge(&'a AtomicU64),
+}
+
 /// Kiro API Provider
 ///
 /// 核心组件，负责与 Kiro API 通信
@@ -31,6 +101,8 @@ const MAX_TOTAL_RETRIES: usize = 9;
 pub struct KiroProvider {
     token_manager: Arc<MultiTokenManager>,
     client: Client,
+    /// 非流式请求的耗时样本（用于计算对冲的耗时预算）
+    latency: LatencySamples,
 }
 
 impl KiroProvider {
@@ -41,12 +113,14 @@ impl KiroProvider {
 
     /// 创建带代理配置的 KiroProvider 实例
     pub fn with_proxy(token_manager: Arc<MultiTokenManager>, proxy: Option<ProxyConfig>) -> Self {
-        let client = build_client(proxy.as_ref(), 720) // 12 分钟超时
+        // 每个请求按 `timeouts` 单独设置超时，这里只是兜底
+        let client = build_tracked_client(UpstreamEndpoint::Api, proxy.as_ref(), 3600)
             .expect("创建 HTTP 客户端失败");
 
         Self {
             token_manager,
             client,
+            latency: LatencySamples::default(),
         }
     }
 
@@ -55,17 +129,32 @@ impl KiroProvider {
         &self.token_manager
     }
 
-    /// 获取 API 基础 URL
-    pub fn base_url(&self) -> String {
-        format!(
-            "https://q.{}.amazonaws.com/generateAssistantResponse",
-            self.token_manager.config().region
-        )
+    /// 凭据使用的 API 基础地址
+    fn api_base(&self, credentials: &KiroCredentials) -> String {
+        let config = self.token_manager.config();
+        config.endpoints.api(config.region_for(credentials))
+    }
+
+    /// 获取 API URL
+    pub fn base_url(&self, credentials: &KiroCredentials) -> String {
+        format!("{}/generateAssistantResponse", self.api_base(credentials))
+    }
+
+    /// 探测上游端点的网络连通性
+    ///
+    /// 只要收到任何 HTTP 响应（包括 4xx）即视为可达，不消耗凭据额度
+    pub async fn probe_upstream(&self, timeout: Duration) -> anyhow::Result<()> {
+        self.client
+            .get(format!("{}/", self.api_base(&KiroCredentials::default())))
+            .timeout(timeout)
+            .send()
+            .await?;
+        Ok(())
     }
 
     /// 获取 API 基础域名
-    pub fn base_domain(&self) -> String {
-        format!("q.{}.amazonaws.com", self.token_manager.config().region)
+    pub fn base_domain(&self, credentials: &KiroCredentials) -> String {
+        endpoint_host(&self.api_base(credentials))
     }
 
     /// 构建请求头
@@ -105,7 +194,10 @@ impl KiroProvider {
             reqwest::header::USER_AGENT,
             HeaderValue::from_str(&user_agent).unwrap(),
         );
-        headers.insert(HOST, HeaderValue::from_str(&self.base_domain()).unwrap());
+        headers.insert(
+            HOST,
+            HeaderValue::from_str(&self.base_domain(&ctx.credentials))?,
+        );
         headers.insert(
             "amz-sdk-invocation-id",
             HeaderValue::from_str(&Uuid::new_v4().to_string()).unwrap(),
@@ -118,7 +210,9 @@ impl KiroProvider {
             AUTHORIZATION,
             HeaderValue::from_str(&format!("Bearer {}", ctx.token)).unwrap(),
         );
-        headers.insert(CONNECTION, HeaderValue::from_static("close"));
+        if !config.upstream_pool.keeps_alive() {
+            headers.insert(CONNECTION, HeaderValue::from_static("close"));
+        }
 
         Ok(headers)
     }
@@ -132,12 +226,151 @@ impl KiroProvider {
     /// - 429/5xx/网络等瞬态错误: 重试但不禁用或切换凭据（避免误把所有凭据锁死）
     ///
     /// # Arguments
+    /// * `model` - 客户端请求的模型名称（用于匹配凭据路由规则）
     /// * `request_body` - JSON 格式的请求体字符串
+    /// * `extra_headers` - 额外透传给上游的请求头（不覆盖内置请求头）
     ///
     /// # Returns
     /// 返回原始的 HTTP Response，不做解析
-    pub async fn call_api(&self, request_body: &str) -> anyhow::Result<reqwest::Response> {
-        self.call_api_with_retry(request_body, false).await
+    pub async fn call_api(
+        &self,
+        model: &str,
+        request_body: &str,
+        extra_headers: &HeaderMap,
+    ) -> anyhow::Result<reqwest::Response> {
+        self.call_api_with_retry(model, request_body, extra_headers, false, Leg::Single)
+            .await
+    }
+
+    /// 发送非流式 API 请求，启用对冲时超过耗时预算仍未完成则在另一个凭据上发起相同请求
+    ///
+    /// 启用对冲（`hedging` 不为 None）时读完整个响应体后才返回，返回的 Response 带有已缓冲的响应体，
+    /// 响应体超过 `max_body_bytes` 时返回 [`ResponseTooLarge`]；
+    /// 两路调用取先成功者，另一路随即取消。一路失败时等待另一路的结果，都失败时返回主调用的错误。
+    /// 未启用对冲时与 [`call_api`](Self::call_api) 相同。
+    pub async fn call_api_hedged(
+        &self,
+        model: &str,
+        request_body: &str,
+        extra_headers: &HeaderMap,
+        hedging: Option<&HedgingConfig>,
+        max_body_bytes: usize,
+    ) -> anyhow::Result<reqwest::Response> {
+        let Some(hedging) = hedging else {
+            return self.call_api(model, request_body, extra_headers).await;
+        };
+
+        let started = Instant::now();
+        let Some(delay) = self.latency.hedge_delay(hedging) else {
+            // 样本不足时照常调用，只积累耗时样本
+            let response = self
+                .call_api_buffered(
+                    model,
+                    request_body,
+                    extra_headers,
+                    max_body_bytes,
+                    Leg::Single,
+                )
+                .await?;
+            self.latency.record(started.elapsed());
+            return Ok(response);
+        };
+
+        let primary_credential = AtomicU64::new(u64::MAX);
+        let primary = self.call_api_buffered(
+            model,
+            request_body,
+            extra_headers,
+            max_body_bytes,
+            Leg::Primary(&primary_credential),
+        );
+        tokio::pin!(primary);
+        tokio::select! {
+            result = &mut primary => {
+                if result.is_ok() {
+                    self.latency.record(started.elapsed());
+                }
+                return result;
+            }
+            _ = sleep(delay) => {}
+        }
+
+        tracing::info!(
+            "非流式请求超过耗时预算 {} ms 仍未完成，在其他凭据上发起对冲请求",
+            delay.as_millis()
+        );
+        metrics::global()
+            .hedged_requests_total
+            .fetch_add(1, Ordering::Relaxed);
+        let hedge = self.call_api_buffered(
+            model,
+            request_body,
+            extra_headers,
+            max_body_bytes,
+            Leg::Hedge(&primary_credential),
+        );
+        tokio::pin!(hedge);
+        // 对冲请求胜出时，主调用的耗时至少为当前已等待的时间，同样计入样本，避免预算被对冲结果拉低
+        tokio::select! {
+            result = &mut primary => match result {
+                Ok(response) => {
+                    self.latency.record(started.elapsed());
+                    Ok(response)
+                }
+                Err(e) => {
+                    tracing::warn!("主调用失败，等待对冲请求: {}", e);
+                    hedge.await.map_err(|_| e)
+                }
+            },
+            result = &mut hedge => match result {
+                Ok(response) => {
+                    self.latency.record(started.elapsed());
+                    metrics::global()
+                        .hedge_wins_total
+                        .fetch_add(1, Ordering::Relaxed);
+                    Ok(response)
+                }
+                Err(e) => {
+                    tracing::warn!("对冲请求失败，等待主调用: {}", e);
+                    let result = primary.await;
+                    if result.is_ok() {
+                        self.latency.record(started.elapsed());
+                    }
+                    result
+                }
+            },
+        }
+    }
+
+    /// 发送非流式 API 请求并读完响应体，返回带有已缓冲响应体的 Response
+    async fn call_api_buffered(
+        &self,
+        model: &str,
+        request_body: &str,
+        extra_headers: &HeaderMap,
+        max_body_bytes: usize,
+        leg: Leg<'_>,
+    ) -> anyhow::Result<reqwest::Response> {
+        let response = self
+            .call_api_with_retry(model, request_body, extra_headers, false, leg)
+            .await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let credential = response.extensions().get::<CredentialId>().copied();
+        let attempts = response.extensions().get::<UpstreamAttempts>().copied();
+        let body = read_body_limited(response, max_body_bytes).await?;
+
+        let mut buffered = http::Response::new(body);
+        *buffered.status_mut() = status;
+        *buffered.headers_mut() = headers;
+        let mut response = reqwest::Response::from(buffered);
+        if let Some(credential) = credential {
+            response.extensions_mut().insert(credential);
+        }
+        if let Some(attempts) = attempts {
+            response.extensions_mut().insert(attempts);
+        }
+        Ok(response)
     }
 
     /// 发送流式 API 请求
@@ -149,12 +382,20 @@ impl KiroProvider {
     /// - 429/5xx/网络等瞬态错误: 重试但不禁用或切换凭据（避免误把所有凭据锁死）
     ///
     /// # Arguments
+    /// * `model` - 客户端请求的模型名称（用于匹配凭据路由规则）
     /// * `request_body` - JSON 格式的请求体字符串
+    /// * `extra_headers` - 额外透传给上游的请求头（不覆盖内置请求头）
     ///
     /// # Returns
     /// 返回原始的 HTTP Response，调用方负责处理流式数据
-    pub async fn call_api_stream(&self, request_body: &str) -> anyhow::Result<reqwest::Response> {
-        self.call_api_with_retry(request_body, true).await
+    pub async fn call_api_stream(
+        &self,
+        model: &str,
+        request_body: &str,
+        extra_headers: &HeaderMap,
+    ) -> anyhow::Result<reqwest::Response> {
+        self.call_api_with_retry(model, request_body, extra_headers, true, Leg::Single)
+            .await
     }
 
     /// 内部方法：带重试逻辑的 API 调用
@@ -163,10 +404,15 @@ impl KiroProvider {
     /// - 每个凭据最多重试 MAX_RETRIES_PER_CREDENTIAL 次
     /// - 总重试次数 = min(凭据数量 × 每凭据重试次数, MAX_TOTAL_RETRIES)
     /// - 硬上限 9 次，避免无限重试
+    /// - 每次重试（含故障转移）消耗全局重试预算，预算耗尽时不再重试
+    /// - 对冲调用（`leg` 为 [`Leg::Hedge`]）每次尝试都避开主调用当前使用的凭据
     async fn call_api_with_retry(
         &self,
+        model: &str,
         request_body: &str,
+        extra_headers: &HeaderMap,
         is_stream: bool,
+        leg: Leg<'_>,
     ) -> anyhow::Result<reqwest::Response> {
         let total_credentials = self.token_manager.total_count();
         let max_retries = (total_credentials * MAX_RETRIES_PER_CREDENTIAL).min(MAX_TOTAL_RETRIES);
@@ -174,35 +420,86 @@ impl KiroProvider {
         let api_type = if is_stream { "流式" } else { "非流式" };
 
         for attempt in 0..max_retries {
+            // 上游熔断中：直接失败，不计入任何凭据的失败次数
+            circuit::breaker().check()?;
+
+            // 故障转移与重试共用全局重试预算，耗尽时返回最近一次的错误
+            if attempt > 0 && !retry_budget::budget().try_acquire() {
+                break;
+            }
+
             // 获取调用上下文（绑定 index、credentials、token）
-            let ctx = match self.token_manager.acquire_context().await {
-                Ok(c) => c,
+            let selection = tracing::info_span!(
+                "credential_selection",
+                model,
+                credential_id = Empty,
+                otel.status_code = Empty,
+            );
+            let acquired = match leg {
+                Leg::Hedge(primary) => {
+                    self.token_manager
+                        .acquire_context_excluding(model, primary.load(Ordering::Relaxed))
+                        .instrument(selection.clone())
+                        .await
+                }
+                Leg::Single | Leg::Primary(_) => {
+                    self.token_manager
+                        .acquire_context_for(model)
+                        .instrument(selection.clone())
+                        .await
+                }
+            };
+            let ctx = match acquired {
+                Ok(c) => {
+                    selection.record("credential_id", c.id);
+                    if let Leg::Primary(current) = leg {
+                        current.store(c.id, Ordering::Relaxed);
+                    }
+                    c
+                }
                 Err(e) => {
+                    selection.record("otel.status_code", "error");
                     last_error = Some(e);
                     continue;
                 }
             };
 
-            let url = self.base_url();
-            let headers = match self.build_headers(&ctx) {
+            let url = self.base_url(&ctx.credentials);
+            let mut headers = match self.build_headers(&ctx) {
                 Ok(h) => h,
                 Err(e) => {
                     last_error = Some(e);
                     continue;
                 }
             };
+            headers::merge_missing(&mut headers, extra_headers);
 
             // 发送请求
-            let response = match self
+            let call = tracing::info_span!(
+                "upstream_call",
+                otel.kind = "client",
+                stream = is_stream,
+                attempt = attempt + 1,
+                credential_id = ctx.id,
+                http.status_code = Empty,
+                otel.status_code = Empty,
+            );
+            let request = self
                 .client
                 .post(&url)
                 .headers(headers)
-                .body(request_body.to_string())
-                .send()
-                .await
-            {
+                .body(request_body.to_string());
+            let timeouts = self.token_manager.config().timeouts.api(is_stream);
+            metrics::connections().record_request();
+            let send = send_timed(&self.client, request, timeouts).instrument(call.clone());
+            let sent = Instant::now();
+            let response = match timing::measure(Phase::Connect, send).await {
                 Ok(resp) => resp,
                 Err(e) => {
+                    call.record("otel.status_code", "error");
+                    metrics::global().observe_upstream(ctx.id, None);
+                    metrics::alerts().record_upstream(ctx.id, None);
+                    circuit::breaker().record(None);
                     tracing::warn!(
                         "API 请求发送失败（尝试 {}/{}）: {}",
                         attempt + 1,
@@ -211,7 +508,7 @@ impl KiroProvider {
                     );
                     // 网络错误通常是上游/链路瞬态问题，不应导致"禁用凭据"或"切换凭据"
                     // （否则一段时间网络抖动会把所有凭据都误禁用，需要重启才能恢复）
-                    last_error = Some(e.into());
+                    last_error = Some(e);
                     if attempt + 1 < max_retries {
                         sleep(Self::retry_delay(attempt)).await;
                     }
@@ -220,10 +517,23 @@ impl KiroProvider {
             };
 
             let status = response.status();
+            call.record("http.status_code", status.as_u16());
+            metrics::global().observe_upstream(ctx.id, Some(status.as_u16()));
+            metrics::alerts().record_upstream(ctx.id, Some(status.as_u16()));
+            circuit::breaker().record(Some(status.as_u16()));
+            limit::adaptive().record(status.as_u16(), sent.elapsed());
+            if !status.is_success() {
+                call.record("otel.status_code", "error");
+            }
 
             // 成功响应
             if status.is_success() {
                 self.token_manager.report_success(ctx.id);
+                let mut response = response;
+                response.extensions_mut().insert(CredentialId(ctx.id));
+                response
+                    .extensions_mut()
+                    .insert(UpstreamAttempts(attempt as u32 + 1));
                 return Ok(response);
             }
 
@@ -250,12 +560,21 @@ impl KiroProvider {
                     );
                 }
 
-                last_error = Some(anyhow::anyhow!("{} API 请求失败: {} {}", api_type, status, body));
+                last_error = Some(anyhow::anyhow!(
+                    "{} API 请求失败: {} {}",
+                    api_type,
+                    status,
+                    body
+                ));
                 continue;
             }
 
             // 400 Bad Request - 请求问题，重试/切换凭据无意义
             if status.as_u16() == 400 {
+                if Self::is_input_too_long(&body) {
+                    tracing::warn!("{} API 请求失败（输入过长）: {}", api_type, body);
+                    return Err(ContextLengthExceeded { body }.into());
+                }
                 anyhow::bail!("{} API 请求失败: {} {}", api_type, status, body);
             }
 
@@ -279,7 +598,12 @@ impl KiroProvider {
                     );
                 }
 
-                last_error = Some(anyhow::anyhow!("{} API 请求失败: {} {}", api_type, status, body));
+                last_error = Some(anyhow::anyhow!(
+                    "{} API 请求失败: {} {}",
+                    api_type,
+                    status,
+                    body
+                ));
                 continue;
             }
 
@@ -293,7 +617,12 @@ impl KiroProvider {
                     status,
                     body
                 );
-                last_error = Some(anyhow::anyhow!("{} API 请求失败: {} {}", api_type, status, body));
+                last_error = Some(anyhow::anyhow!(
+                    "{} API 请求失败: {} {}",
+                    api_type,
+                    status,
+                    body
+                ));
                 if attempt + 1 < max_retries {
                     sleep(Self::retry_delay(attempt)).await;
                 }
@@ -313,7 +642,12 @@ impl KiroProvider {
                 status,
                 body
             );
-            last_error = Some(anyhow::anyhow!("{} API 请求失败: {} {}", api_type, status, body));
+            last_error = Some(anyhow::anyhow!(
+                "{} API 请求失败: {} {}",
+                api_type,
+                status,
+                body
+            ));
             if attempt + 1 < max_retries {
                 sleep(Self::retry_delay(attempt)).await;
             }
@@ -340,6 +674,11 @@ impl KiroProvider {
         Duration::from_millis(backoff.saturating_add(jitter))
     }
 
+    /// 判断 400 响应是否为输入超出上下文长度
+    fn is_input_too_long(body: &str) -> bool {
+        body.contains("CONTENT_LENGTH_EXCEEDS_THRESHOLD") || body.contains("Input is too long")
+    }
+
     fn is_monthly_request_limit(body: &str) -> bool {
         if body.contains("MONTHLY_REQUEST_COUNT") {
             return true;
@@ -364,6 +703,25 @@ impl KiroProvider {
     }
 }
 
+/// 读完响应体，累计超过 `limit` 字节时立即中止并返回 [`ResponseTooLarge`]
+async fn read_body_limited(
+    mut response: reqwest::Response,
+    limit: usize,
+) -> anyhow::Result<bytes::Bytes> {
+    let mut body = bytes::BytesMut::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| anyhow::anyhow!("读取响应失败: {}", e))?
+    {
+        if body.len() + chunk.len() > limit {
+            return Err(ResponseTooLarge { limit }.into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body.freeze())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -379,9 +737,13 @@ mod tests {
     fn test_base_url() {
         let config = Config::default();
         let credentials = KiroCredentials::default();
-        let provider = create_test_provider(config, credentials);
-        assert!(provider.base_url().contains("amazonaws.com"));
-        assert!(provider.base_url().contains("generateAssistantResponse"));
+        let provider = create_test_provider(config, credentials.clone());
+        assert!(provider.base_url(&credentials).contains("amazonaws.com"));
+        assert!(
+            provider
+                .base_url(&credentials)
+                .contains("generateAssistantResponse")
+        );
     }
 
     #[test]
@@ -389,8 +751,29 @@ mod tests {
         let mut config = Config::default();
         config.region = "us-east-1".to_string();
         let credentials = KiroCredentials::default();
-        let provider = create_test_provider(config, credentials);
-        assert_eq!(provider.base_domain(), "q.us-east-1.amazonaws.com");
+        let provider = create_test_provider(config, credentials.clone());
+        assert_eq!(
+            provider.base_domain(&credentials),
+            "q.us-east-1.amazonaws.com"
+        );
+        let eu = KiroCredentials {
+            region: Some("eu-central-1".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(provider.base_domain(&eu), "q.eu-central-1.amazonaws.com");
+    }
+
+    #[test]
+    fn test_custom_api_endpoint() {
+        let mut config = Config::default();
+        config.endpoints.api = "http://127.0.0.1:9000/{region}/".to_string();
+        let credentials = KiroCredentials::default();
+        let provider = create_test_provider(config, credentials.clone());
+        assert_eq!(
+            provider.base_url(&credentials),
+            "http://127.0.0.1:9000/us-east-1/generateAssistantResponse"
+        );
+        assert_eq!(provider.base_domain(&credentials), "127.0.0.1:9000");
     }
 
     #[test]
@@ -425,6 +808,24 @@ mod tests {
         assert_eq!(headers.get(CONNECTION).unwrap(), "close");
     }
 
+    #[test]
+    fn test_build_headers_keep_alive() {
+        let mut config = Config::default();
+        config.upstream_pool.http2 = true;
+        let credentials = KiroCredentials {
+            refresh_token: Some("a".repeat(150)),
+            ..Default::default()
+        };
+        let provider = create_test_provider(config, credentials.clone());
+        let ctx = CallContext {
+            id: 1,
+            credentials,
+            token: "test_token".to_string(),
+        };
+        let headers = provider.build_headers(&ctx).unwrap();
+        assert!(headers.get(CONNECTION).is_none());
+    }
+
     #[test]
     fn test_is_monthly_request_limit_detects_reason() {
         let body = r#"{"message":"You have reached the limit.","reason":"MONTHLY_REQUEST_COUNT"}"#;
@@ -437,9 +838,33 @@ mod tests {
         assert!(KiroProvider::is_monthly_request_limit(body));
     }
 
+    #[test]
+    fn test_is_input_too_long() {
+        let body = r#"{"message":"Input is too long for requested model.","reason":"CONTENT_LENGTH_EXCEEDS_THRESHOLD"}"#;
+        assert!(KiroProvider::is_input_too_long(body));
+        assert!(!KiroProvider::is_input_too_long(
+            r#"{"message":"Improperly formed request."}"#
+        ));
+    }
+
     #[test]
     fn test_is_monthly_request_limit_false() {
         let body = r#"{"message":"nope","reason":"DAILY_REQUEST_COUNT"}"#;
         assert!(!KiroProvider::is_monthly_request_limit(body));
     }
+
+    #[tokio::test]
+    async fn test_read_body_limited() {
+        let response = |body: &'static str| {
+            reqwest::Response::from(http::Response::new(bytes::Bytes::from_static(
+                body.as_bytes(),
+            )))
+        };
+
+        let body = read_body_limited(response("hello"), 5).await.unwrap();
+        assert_eq!(body, "hello");
+
+        let err = read_body_limited(response("hello!"), 5).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<ResponseTooLarge>().unwrap().limit, 5);
+    }
 }
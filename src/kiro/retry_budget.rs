@@ -0,0 +1,152 @@
+//! 全局重试预算
+//!
+//! 上游调用的故障转移与重试（换凭据、429/5xx 退避重试）以及响应异常后的重新请求共用同一份预算：
+//! 滚动窗口内的重试次数不超过同期客户端对话请求数的 `ratio` 倍（另有 `minRetries` 次保底，低流量时不至于无法重试）。
+//! 上游整体故障时每个请求都会失败，没有预算时每个请求会放大为多次上游调用，加剧限流；
+//! 预算耗尽后不再重试，直接返回最近一次的错误。
+
+use std::sync::LazyLock;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use parking_lot::Mutex;
+
+use crate::metrics::{self, alerts::Window};
+use crate::model::config::RetryBudgetConfig;
+
+/// 全局重试预算
+static BUDGET: LazyLock<RetryBudget> = LazyLock::new(RetryBudget::default);
+
+/// 获取全局重试预算
+pub fn budget() -> &'static RetryBudget {
+    &BUDGET
+}
+
+#[derive(Debug, Default)]
+struct State {
+    config: Option<RetryBudgetConfig>,
+    /// 窗口内的调用数：请求记为成功，重试记为错误
+    window: Window,
+}
+
+/// 重试预算
+#[derive(Debug)]
+pub struct RetryBudget {
+    started: Instant,
+    state: Mutex<State>,
+}
+
+impl Default for RetryBudget {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            state: Mutex::new(State::default()),
+        }
+    }
+}
+
+impl RetryBudget {
+    /// 设置预算参数（None 表示不限制重试），重置统计
+    pub fn configure(&self, config: Option<RetryBudgetConfig>) {
+        let mut state = self.state.lock();
+        state.config = config;
+        state.window.clear();
+    }
+
+    /// 记录一个客户端对话请求（不消耗预算）
+    pub fn record_request(&self) {
+        self.record_request_at(self.now());
+    }
+
+    /// 申请一次重试：预算充足时记录并返回 true，耗尽时返回 false
+    pub fn try_acquire(&self) -> bool {
+        self.try_acquire_at(self.now())
+    }
+
+    fn now(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+
+    fn record_request_at(&self, now: u64) {
+        let mut state = self.state.lock();
+        if state.config.is_some() {
+            state.window.record(now, false);
+        }
+    }
+
+    fn try_acquire_at(&self, now: u64) -> bool {
+        let mut state = self.state.lock();
+        let Some(config) = state.config.clone() else {
+            return true;
+        };
+        let (calls, retries) = state.window.totals(now, config.window_secs);
+        let requests = calls - retries;
+        let allowed = (requests as f64 * config.ratio) as u64 + config.min_retries;
+        if retries >= allowed {
+            metrics::global()
+                .retry_budget_exhausted_total
+                .fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(
+                "重试预算已耗尽（最近 {} 秒 {} 个请求、{} 次重试），不再重试",
+                config.window_secs,
+                requests,
+                retries
+            );
+            return false;
+        }
+        state.window.record(now, true);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget() -> RetryBudget {
+        let budget = RetryBudget::default();
+        budget.configure(Some(RetryBudgetConfig {
+            ratio: 0.2,
+            window_secs: 60,
+            min_retries: 2,
+        }));
+        budget
+    }
+
+    #[test]
+    fn test_retries_limited_by_request_volume() {
+        let budget = budget();
+        for _ in 0..10 {
+            budget.record_request_at(0);
+        }
+        // 10 个请求 × 0.2 + 保底 2 次
+        for _ in 0..4 {
+            assert!(budget.try_acquire_at(1));
+        }
+        assert!(!budget.try_acquire_at(1));
+
+        for _ in 0..5 {
+            budget.record_request_at(2);
+        }
+        assert!(budget.try_acquire_at(2));
+        assert!(!budget.try_acquire_at(2));
+    }
+
+    #[test]
+    fn test_budget_recovers_after_window() {
+        let budget = budget();
+        assert!(budget.try_acquire_at(0));
+        assert!(budget.try_acquire_at(0));
+        assert!(!budget.try_acquire_at(0));
+        // 窗口滑过后之前的重试不再计入
+        assert!(budget.try_acquire_at(80));
+    }
+
+    #[test]
+    fn test_unlimited_when_disabled() {
+        let budget = RetryBudget::default();
+        for _ in 0..100 {
+            assert!(budget.try_acquire());
+        }
+    }
+}
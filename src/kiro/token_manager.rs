@@ -5,20 +5,29 @@
 
 use anyhow::bail;
 use chrono::{DateTime, Duration, Utc};
+use futures::StreamExt;
 use parking_lot::Mutex;
 use serde::Serialize;
 use tokio::sync::Mutex as TokioMutex;
 
+use std::collections::HashMap;
+use std::fmt;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
 
-use crate::http_client::{ProxyConfig, build_client};
+use crate::http_client::{ProxyConfig, TimeoutError, build_client, send_timed};
 use crate::kiro::machine_id;
 use crate::kiro::model::credentials::KiroCredentials;
 use crate::kiro::model::token_refresh::{
     IdcRefreshRequest, IdcRefreshResponse, RefreshRequest, RefreshResponse,
 };
 use crate::kiro::model::usage_limits::UsageLimitsResponse;
-use crate::model::config::Config;
+use crate::kiro::webhook;
+use crate::metrics;
+use crate::model::config::{Config, ModelRoute, UpstreamEndpoint, endpoint_host};
+use crate::redact;
+use crate::timing::{self, Phase};
 
 /// Token 管理器
 ///
@@ -124,22 +133,96 @@ pub(crate) fn validate_refresh_token(credentials: &KiroCredentials) -> anyhow::R
 }
 
 /// 刷新 Token
+#[tracing::instrument(
+    name = "token_refresh",
+    skip_all,
+    fields(auth_method = credentials.auth_method.as_deref().unwrap_or("social"))
+)]
 pub(crate) async fn refresh_token(
     credentials: &KiroCredentials,
     config: &Config,
     proxy: Option<&ProxyConfig>,
 ) -> anyhow::Result<KiroCredentials> {
-    validate_refresh_token(credentials)?;
-
     // 根据 auth_method 选择刷新方式
-    let auth_method = credentials.auth_method.as_deref().unwrap_or("social");
+    let is_idc = matches!(
+        credentials
+            .auth_method
+            .as_deref()
+            .unwrap_or("social")
+            .to_lowercase()
+            .as_str(),
+        "idc" | "builder-id"
+    );
+
+    redact::register_credentials(credentials);
+    let started = Instant::now();
+    let refresh = async {
+        validate_refresh_token(credentials)?;
+        if is_idc {
+            refresh_idc_token(credentials, config, proxy).await
+        } else {
+            refresh_social_token(credentials, config, proxy).await
+        }
+    };
+    let result = timing::measure(Phase::Refresh, refresh).await;
+
+    if let Ok(refreshed) = &result {
+        redact::register_credentials(refreshed);
+    }
+    let outcome = match &result {
+        Ok(_) => "success",
+        Err(e) => refresh_error_class(e),
+    };
+    metrics::global().observe_refresh(
+        if is_idc { "idc" } else { "social" },
+        outcome,
+        started.elapsed(),
+    );
+    result
+}
 
-    match auth_method.to_lowercase().as_str() {
-        "idc" | "builder-id" => refresh_idc_token(credentials, config, proxy).await,
-        _ => refresh_social_token(credentials, config, proxy).await,
+/// Token 刷新接口返回的非成功状态码
+#[derive(Debug)]
+struct RefreshStatusError {
+    status: reqwest::StatusCode,
+    message: String,
+}
+
+impl fmt::Display for RefreshStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
     }
 }
 
+impl std::error::Error for RefreshStatusError {}
+
+/// 刷新失败的错误分类（用作指标标签）
+fn refresh_error_class(error: &anyhow::Error) -> &'static str {
+    if let Some(e) = error.downcast_ref::<RefreshStatusError>() {
+        return match e.status.as_u16() {
+            400 | 401 => "unauthorized",
+            403 => "forbidden",
+            429 => "rate_limited",
+            500..=599 => "server_error",
+            _ => "http_error",
+        };
+    }
+    if error.downcast_ref::<TimeoutError>().is_some() {
+        return "timeout";
+    }
+    if let Some(e) = error.downcast_ref::<reqwest::Error>() {
+        return if e.is_timeout() {
+            "timeout"
+        } else if e.is_decode() {
+            "invalid_response"
+        } else {
+            "network"
+        };
+    }
+    // refreshToken 缺失 / 被截断、缺少 clientId 等本地校验失败
+    "invalid_credentials"
+}
+
 /// 刷新 Social Token
 async fn refresh_social_token(
     credentials: &KiroCredentials,
@@ -149,20 +232,20 @@ async fn refresh_social_token(
     tracing::info!("正在刷新 Social Token...");
 
     let refresh_token = credentials.refresh_token.as_ref().unwrap();
-    let region = &config.region;
+    let base = config.endpoints.social_auth(config.region_for(credentials));
 
-    let refresh_url = format!("https://prod.{}.auth.desktop.kiro.dev/refreshToken", region);
-    let refresh_domain = format!("prod.{}.auth.desktop.kiro.dev", region);
+    let refresh_url = format!("{}/refreshToken", base);
+    let refresh_domain = endpoint_host(&base);
     let machine_id = machine_id::generate_from_credentials(credentials, config)
         .ok_or_else(|| anyhow::anyhow!("无法生成 machineId"))?;
     let kiro_version = &config.kiro_version;
 
-    let client = build_client(proxy, 60)?;
+    let client = build_client(UpstreamEndpoint::Auth, proxy, 60)?;
     let body = RefreshRequest {
         refresh_token: refresh_token.to_string(),
     };
 
-    let response = client
+    let request = client
         .post(&refresh_url)
         .header("Accept", "application/json, text/plain, */*")
         .header("Content-Type", "application/json")
@@ -173,9 +256,8 @@ async fn refresh_social_token(
         .header("Accept-Encoding", "gzip, compress, deflate, br")
         .header("host", &refresh_domain)
         .header("Connection", "close")
-        .json(&body)
-        .send()
-        .await?;
+        .json(&body);
+    let response = send_timed(&client, request, config.timeouts.refresh()).await?;
 
     let status = response.status();
     if !status.is_success() {
@@ -187,7 +269,11 @@ async fn refresh_social_token(
             500..=599 => "服务器错误，AWS OAuth 服务暂时不可用",
             _ => "Token 刷新失败",
         };
-        bail!("{}: {} {}", error_msg, status, body_text);
+        return Err(RefreshStatusError {
+            status,
+            message: format!("{}: {} {}", error_msg, status, body_text),
+        }
+        .into());
     }
 
     let data: RefreshResponse = response.json().await?;
@@ -232,10 +318,10 @@ async fn refresh_idc_token(
         .as_ref()
         .ok_or_else(|| anyhow::anyhow!("IdC 刷新需要 clientSecret"))?;
 
-    let region = &config.region;
-    let refresh_url = format!("https://oidc.{}.amazonaws.com/token", region);
+    let base = config.endpoints.oidc(config.region_for(credentials));
+    let refresh_url = format!("{}/token", base);
 
-    let client = build_client(proxy, 60)?;
+    let client = build_client(UpstreamEndpoint::Auth, proxy, 60)?;
     let body = IdcRefreshRequest {
         client_id: client_id.to_string(),
         client_secret: client_secret.to_string(),
@@ -243,10 +329,10 @@ async fn refresh_idc_token(
         grant_type: "refresh_token".to_string(),
     };
 
-    let response = client
+    let request = client
         .post(&refresh_url)
         .header("Content-Type", "application/json")
-        .header("Host", format!("oidc.{}.amazonaws.com", region))
+        .header("Host", endpoint_host(&base))
         .header("Connection", "keep-alive")
         .header("x-amz-user-agent", IDC_AMZ_USER_AGENT)
         .header("Accept", "*/*")
@@ -254,9 +340,8 @@ async fn refresh_idc_token(
         .header("sec-fetch-mode", "cors")
         .header("User-Agent", "node")
         .header("Accept-Encoding", "br, gzip, deflate")
-        .json(&body)
-        .send()
-        .await?;
+        .json(&body);
+    let response = send_timed(&client, request, config.timeouts.refresh()).await?;
 
     let status = response.status();
     if !status.is_success() {
@@ -268,7 +353,11 @@ async fn refresh_idc_token(
             500..=599 => "服务器错误，AWS OIDC 服务暂时不可用",
             _ => "IdC Token 刷新失败",
         };
-        bail!("{}: {} {}", error_msg, status, body_text);
+        return Err(RefreshStatusError {
+            status,
+            message: format!("{}: {} {}", error_msg, status, body_text),
+        }
+        .into());
     }
 
     let data: IdcRefreshResponse = response.json().await?;
@@ -300,16 +389,16 @@ pub(crate) async fn get_usage_limits(
 ) -> anyhow::Result<UsageLimitsResponse> {
     tracing::debug!("正在获取使用额度信息...");
 
-    let region = &config.region;
-    let host = format!("q.{}.amazonaws.com", region);
+    let base = config.endpoints.api(config.region_for(credentials));
+    let host = endpoint_host(&base);
     let machine_id = machine_id::generate_from_credentials(credentials, config)
         .ok_or_else(|| anyhow::anyhow!("无法生成 machineId"))?;
     let kiro_version = &config.kiro_version;
 
     // 构建 URL
     let mut url = format!(
-        "https://{}/getUsageLimits?origin=AI_EDITOR&resourceType=AGENTIC_REQUEST",
-        host
+        "{}/getUsageLimits?origin=AI_EDITOR&resourceType=AGENTIC_REQUEST",
+        base
     );
 
     // profileArn 是可选的
@@ -328,7 +417,7 @@ pub(crate) async fn get_usage_limits(
         USAGE_LIMITS_AMZ_USER_AGENT_PREFIX, kiro_version, machine_id
     );
 
-    let client = build_client(proxy, 60)?;
+    let client = build_client(UpstreamEndpoint::Api, proxy, 60)?;
 
     let response = client
         .get(&url)
@@ -377,6 +466,12 @@ struct CredentialEntry {
     disabled_reason: Option<DisabledReason>,
 }
 
+/// 将可用凭据数告知凭据生命周期 Webhook（低于下限或恢复时推送）
+fn notify_pool(entries: &[CredentialEntry]) {
+    let available = entries.iter().filter(|e| !e.disabled).count();
+    webhook::notifier().pool_changed(available, entries.len());
+}
+
 /// 禁用原因
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum DisabledReason {
@@ -410,6 +505,8 @@ pub struct CredentialEntrySnapshot {
     pub has_profile_arn: bool,
     /// Token 过期时间
     pub expires_at: Option<String>,
+    /// 凭据标签
+    pub tags: Vec<String>,
 }
 
 /// 凭据管理器状态快照
@@ -426,6 +523,17 @@ pub struct ManagerSnapshot {
     pub available: usize,
 }
 
+/// 启动预热结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WarmUpSummary {
+    /// 取得有效 Token 并查询到额度的凭据数
+    pub ready: usize,
+    /// 额度已用尽而被禁用的凭据数（包含在 `ready` 中）
+    pub exhausted: usize,
+    /// 刷新 Token 或查询额度失败的凭据数
+    pub failed: usize,
+}
+
 /// 多凭据 Token 管理器
 ///
 /// 支持多个凭据的管理，实现固定优先级 + 故障转移策略
@@ -437,12 +545,16 @@ pub struct MultiTokenManager {
     entries: Mutex<Vec<CredentialEntry>>,
     /// 当前活动凭据 ID
     current_id: Mutex<u64>,
-    /// Token 刷新锁，确保同一时间只有一个刷新操作
-    refresh_lock: TokioMutex<()>,
+    /// 按凭据 ID 的 Token 刷新锁，确保同一凭据同一时间只有一个刷新操作
+    refresh_locks: Mutex<HashMap<u64, Arc<TokioMutex<()>>>>,
+    /// 凭据文件回写锁，避免并发刷新时较旧的快照覆盖较新的快照
+    persist_lock: Mutex<()>,
     /// 凭据文件路径（用于回写）
     credentials_path: Option<PathBuf>,
     /// 是否为多凭据格式（数组格式才回写）
     is_multiple_format: bool,
+    /// 模型路由规则（初始值来自配置，可通过 Admin API 修改）
+    routes: Mutex<Vec<ModelRoute>>,
 }
 
 /// 每个凭据最大 API 调用失败次数
@@ -486,6 +598,7 @@ impl MultiTokenManager {
         let entries: Vec<CredentialEntry> = credentials
             .into_iter()
             .map(|mut cred| {
+                redact::register_credentials(&cred);
                 let id = cred.id.unwrap_or_else(|| {
                     let id = next_id;
                     next_id += 1;
@@ -522,14 +635,17 @@ impl MultiTokenManager {
             .map(|e| e.id)
             .unwrap_or(0);
 
+        let routes = Mutex::new(config.model_routes.clone());
         let manager = Self {
             config,
             proxy,
             entries: Mutex::new(entries),
             current_id: Mutex::new(initial_id),
-            refresh_lock: TokioMutex::new(()),
+            refresh_locks: Mutex::new(HashMap::new()),
+            persist_lock: Mutex::new(()),
             credentials_path,
             is_multiple_format,
+            routes,
         };
 
         // 如果有新分配的 ID，立即持久化到配置文件
@@ -620,6 +736,7 @@ impl MultiTokenManager {
                                 e.failure_count = 0;
                             }
                         }
+                        notify_pool(&entries);
                         best = entries
                             .iter()
                             .filter(|e| !e.disabled)
@@ -661,6 +778,102 @@ impl MultiTokenManager {
         }
     }
 
+    /// 获取指定模型的 API 调用上下文
+    ///
+    /// 模型命中路由规则时，只在带有规则标签的凭据中按优先级选择，
+    /// 且不改变当前活动凭据，避免受限流量影响其他请求的凭据选择；
+    /// 未命中任何规则时与 [`acquire_context`](Self::acquire_context) 相同
+    pub async fn acquire_context_for(&self, model: &str) -> anyhow::Result<CallContext> {
+        let Some(tags) = self.route_tags(model) else {
+            return self.acquire_context().await;
+        };
+
+        let candidates: Vec<(u64, KiroCredentials)> = {
+            let entries = self.entries.lock();
+            let current_id = *self.current_id.lock();
+            let mut candidates: Vec<&CredentialEntry> = entries
+                .iter()
+                .filter(|e| !e.disabled && e.credentials.tags.iter().any(|t| tags.contains(t)))
+                .collect();
+            // 当前凭据符合条件时优先使用，其余按优先级排序
+            candidates.sort_by_key(|e| (e.id != current_id, e.credentials.priority));
+            candidates
+                .into_iter()
+                .map(|e| (e.id, e.credentials.clone()))
+                .collect()
+        };
+
+        if candidates.is_empty() {
+            anyhow::bail!(
+                "模型 {} 没有可用的凭据（路由要求标签: {}）",
+                model,
+                tags.join(", ")
+            );
+        }
+
+        for (id, credentials) in candidates {
+            match self.try_ensure_token(id, &credentials).await {
+                Ok(ctx) => return Ok(ctx),
+                Err(e) => {
+                    tracing::warn!("凭据 #{} Token 刷新失败，尝试下一个凭据: {}", id, e);
+                }
+            }
+        }
+
+        anyhow::bail!(
+            "模型 {} 的所有路由凭据均无法获取有效 Token（路由要求标签: {}）",
+            model,
+            tags.join(", ")
+        )
+    }
+
+    /// 获取指定模型的 API 调用上下文，但不使用 `exclude` 凭据（用于对冲请求）
+    ///
+    /// 同样遵循路由规则，按优先级选择，不改变当前活动凭据
+    pub async fn acquire_context_excluding(
+        &self,
+        model: &str,
+        exclude: u64,
+    ) -> anyhow::Result<CallContext> {
+        let tags = self.route_tags(model);
+        let candidates: Vec<(u64, KiroCredentials)> = {
+            let entries = self.entries.lock();
+            let mut candidates: Vec<&CredentialEntry> = entries
+                .iter()
+                .filter(|e| !e.disabled && e.id != exclude)
+                .filter(|e| {
+                    tags.as_ref()
+                        .is_none_or(|tags| e.credentials.tags.iter().any(|t| tags.contains(t)))
+                })
+                .collect();
+            candidates.sort_by_key(|e| e.credentials.priority);
+            candidates
+                .into_iter()
+                .map(|e| (e.id, e.credentials.clone()))
+                .collect()
+        };
+
+        for (id, credentials) in candidates {
+            match self.try_ensure_token(id, &credentials).await {
+                Ok(ctx) => return Ok(ctx),
+                Err(e) => {
+                    tracing::warn!("凭据 #{} Token 刷新失败，尝试下一个凭据: {}", id, e);
+                }
+            }
+        }
+
+        anyhow::bail!("模型 {} 没有凭据 #{} 之外的可用凭据", model, exclude)
+    }
+
+    /// 查找模型命中的第一条路由规则，返回其允许的凭据标签
+    fn route_tags(&self, model: &str) -> Option<Vec<String>> {
+        self.routes
+            .lock()
+            .iter()
+            .find(|r| r.matches(model))
+            .map(|r| r.tags.clone())
+    }
+
     /// 切换到下一个优先级最高的可用凭据（内部方法）
     fn switch_to_next_by_priority(&self) {
         let entries = self.entries.lock();
@@ -723,8 +936,9 @@ impl MultiTokenManager {
         let needs_refresh = is_token_expired(credentials) || is_token_expiring_soon(credentials);
 
         let creds = if needs_refresh {
-            // 获取刷新锁，确保同一时间只有一个刷新操作
-            let _guard = self.refresh_lock.lock().await;
+            // 获取该凭据的刷新锁，确保同一凭据同一时间只有一个刷新操作
+            let lock = self.refresh_lock(id);
+            let _guard = lock.lock().await;
 
             // 第二次检查：获取锁后重新读取凭据，因为其他请求可能已经完成刷新
             let current_creds = {
@@ -738,8 +952,7 @@ impl MultiTokenManager {
 
             if is_token_expired(&current_creds) || is_token_expiring_soon(&current_creds) {
                 // 确实需要刷新
-                let new_creds =
-                    refresh_token(&current_creds, &self.config, self.proxy.as_ref()).await?;
+                let new_creds = self.refresh_credential(id, &current_creds).await?;
 
                 if is_token_expired(&new_creds) {
                     anyhow::bail!("刷新后的 Token 仍然无效或已过期");
@@ -780,6 +993,25 @@ impl MultiTokenManager {
         })
     }
 
+    /// 刷新指定凭据的 Token，并将结果告知凭据生命周期 Webhook（连续失败时推送）
+    async fn refresh_credential(
+        &self,
+        id: u64,
+        credentials: &KiroCredentials,
+    ) -> anyhow::Result<KiroCredentials> {
+        let result = refresh_token(credentials, &self.config, self.proxy.as_ref()).await;
+        match &result {
+            Ok(_) => webhook::notifier().refresh_succeeded(id),
+            Err(e) => webhook::notifier().refresh_failed(id, e),
+        }
+        result
+    }
+
+    /// 获取指定凭据的刷新锁
+    fn refresh_lock(&self, id: u64) -> Arc<TokioMutex<()>> {
+        self.refresh_locks.lock().entry(id).or_default().clone()
+    }
+
     /// 将凭据列表回写到源文件
     ///
     /// 仅在以下条件满足时回写：
@@ -803,7 +1035,8 @@ impl MultiTokenManager {
             None => return Ok(false),
         };
 
-        // 收集所有凭据
+        // 收集所有凭据（持有回写锁直到写入完成）
+        let _guard = self.persist_lock.lock();
         let credentials: Vec<KiroCredentials> = {
             let entries = self.entries.lock();
             entries.iter().map(|e| e.credentials.clone()).collect()
@@ -868,6 +1101,8 @@ impl MultiTokenManager {
             entry.disabled = true;
             entry.disabled_reason = Some(DisabledReason::TooManyFailures);
             tracing::error!("凭据 #{} 已连续失败 {} 次，已被禁用", id, failure_count);
+            webhook::notifier().credential_disabled(id, "too_many_failures");
+            notify_pool(&entries);
 
             // 切换到优先级最高的可用凭据
             if let Some(next) = entries
@@ -915,10 +1150,9 @@ impl MultiTokenManager {
         // 设为阈值，便于在管理面板中直观看到该凭据已不可用
         entry.failure_count = MAX_FAILURES_PER_CREDENTIAL;
 
-        tracing::error!(
-            "凭据 #{} 额度已用尽（MONTHLY_REQUEST_COUNT），已被禁用",
-            id
-        );
+        tracing::error!("凭据 #{} 额度已用尽（MONTHLY_REQUEST_COUNT），已被禁用", id);
+        webhook::notifier().credential_disabled(id, "quota_exceeded");
+        notify_pool(&entries);
 
         // 切换到优先级最高的可用凭据
         if let Some(next) = entries
@@ -977,6 +1211,57 @@ impl MultiTokenManager {
         .await
     }
 
+    /// 启动预热：并发（最多 `concurrency` 个）刷新所有未禁用凭据的 Token 并查询使用额度，
+    /// 额度已用尽的凭据直接禁用，避免首批请求逐个等待冷刷新或落在额度用尽的凭据上
+    ///
+    /// 预热失败的凭据保持原状，之后的请求仍会按正常流程刷新与故障转移
+    pub async fn warm_up(&self, concurrency: usize) -> WarmUpSummary {
+        let credentials: Vec<(u64, KiroCredentials)> = self
+            .entries
+            .lock()
+            .iter()
+            .filter(|e| !e.disabled)
+            .map(|e| (e.id, e.credentials.clone()))
+            .collect();
+
+        let mut summary = WarmUpSummary::default();
+        let mut results = futures::stream::iter(credentials)
+            .map(|(id, credentials)| async move {
+                let result = async {
+                    let ctx = self.try_ensure_token(id, &credentials).await?;
+                    get_usage_limits(
+                        &ctx.credentials,
+                        &self.config,
+                        &ctx.token,
+                        self.proxy.as_ref(),
+                    )
+                    .await
+                }
+                .await;
+                (id, result)
+            })
+            .buffer_unordered(concurrency.max(1));
+
+        while let Some((id, result)) = results.next().await {
+            match result {
+                Ok(usage) => {
+                    summary.ready += 1;
+                    let limit = usage.usage_limit();
+                    webhook::notifier().usage_observed(id, usage.current_usage(), limit);
+                    if limit > 0.0 && usage.current_usage() >= limit {
+                        summary.exhausted += 1;
+                        self.report_quota_exhausted(id);
+                    }
+                }
+                Err(e) => {
+                    summary.failed += 1;
+                    tracing::warn!("凭据 #{} 预热失败: {}", id, e);
+                }
+            }
+        }
+        summary
+    }
+
     // ========================================================================
     // Admin API 方法
     // ========================================================================
@@ -998,6 +1283,7 @@ impl MultiTokenManager {
                     auth_method: e.credentials.auth_method.clone(),
                     has_profile_arn: e.credentials.profile_arn.is_some(),
                     expires_at: e.credentials.expires_at.clone(),
+                    tags: e.credentials.tags.clone(),
                 })
                 .collect(),
             current_id,
@@ -1022,6 +1308,7 @@ impl MultiTokenManager {
             } else {
                 entry.disabled_reason = Some(DisabledReason::Manual);
             }
+            notify_pool(&entries);
         }
         // 持久化更改
         self.persist_credentials()?;
@@ -1048,6 +1335,34 @@ impl MultiTokenManager {
         Ok(())
     }
 
+    /// 设置凭据标签（Admin API）
+    pub fn set_tags(&self, id: u64, tags: Vec<String>) -> anyhow::Result<()> {
+        {
+            let mut entries = self.entries.lock();
+            let entry = entries
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            entry.credentials.tags = tags;
+        }
+        // 持久化更改
+        self.persist_credentials()?;
+        Ok(())
+    }
+
+    /// 获取模型路由规则（Admin API）
+    pub fn routes(&self) -> Vec<ModelRoute> {
+        self.routes.lock().clone()
+    }
+
+    /// 替换模型路由规则（Admin API）
+    ///
+    /// 仅在内存中生效，重启后恢复为配置文件中的 `modelRoutes`
+    pub fn set_routes(&self, routes: Vec<ModelRoute>) {
+        tracing::info!("模型路由规则已更新，共 {} 条", routes.len());
+        *self.routes.lock() = routes;
+    }
+
     /// 重置凭据失败计数并重新启用（Admin API）
     pub fn reset_and_enable(&self, id: u64) -> anyhow::Result<()> {
         {
@@ -1059,6 +1374,7 @@ impl MultiTokenManager {
             entry.failure_count = 0;
             entry.disabled = false;
             entry.disabled_reason = None;
+            notify_pool(&entries);
         }
         // 持久化更改
         self.persist_credentials()?;
@@ -1080,7 +1396,8 @@ impl MultiTokenManager {
         let needs_refresh = is_token_expired(&credentials) || is_token_expiring_soon(&credentials);
 
         let token = if needs_refresh {
-            let _guard = self.refresh_lock.lock().await;
+            let lock = self.refresh_lock(id);
+            let _guard = lock.lock().await;
             let current_creds = {
                 let entries = self.entries.lock();
                 entries
@@ -1091,8 +1408,7 @@ impl MultiTokenManager {
             };
 
             if is_token_expired(&current_creds) || is_token_expiring_soon(&current_creds) {
-                let new_creds =
-                    refresh_token(&current_creds, &self.config, self.proxy.as_ref()).await?;
+                let new_creds = self.refresh_credential(id, &current_creds).await?;
                 {
                     let mut entries = self.entries.lock();
                     if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
@@ -1126,7 +1442,10 @@ impl MultiTokenManager {
                 .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?
         };
 
-        get_usage_limits(&credentials, &self.config, &token, self.proxy.as_ref()).await
+        let usage =
+            get_usage_limits(&credentials, &self.config, &token, self.proxy.as_ref()).await?;
+        webhook::notifier().usage_observed(id, usage.current_usage(), usage.usage_limit());
+        Ok(usage)
     }
 
     /// 添加新凭据（Admin API）
@@ -1161,6 +1480,7 @@ impl MultiTokenManager {
         validated_cred.auth_method = new_cred.auth_method;
         validated_cred.client_id = new_cred.client_id;
         validated_cred.client_secret = new_cred.client_secret;
+        validated_cred.tags = new_cred.tags;
 
         {
             let mut entries = self.entries.lock();
@@ -1171,6 +1491,7 @@ impl MultiTokenManager {
                 disabled: false,
                 disabled_reason: None,
             });
+            notify_pool(&entries);
         }
 
         // 5. 持久化
@@ -1217,6 +1538,7 @@ impl MultiTokenManager {
 
             // 删除凭据
             entries.retain(|e| e.id != id);
+            self.refresh_locks.lock().remove(&id);
 
             was_current
         };
@@ -1242,12 +1564,180 @@ impl MultiTokenManager {
         tracing::info!("已删除凭据 #{}", id);
         Ok(())
     }
+
+    /// 按重新读取的凭据文件同步凭据列表（配置热加载）
+    ///
+    /// 以 ID 匹配：文件中新增的凭据加入列表，文件中已不存在的凭据移除；
+    /// refreshToken 变化的凭据替换为文件中的内容并清除失败计数与禁用状态，
+    /// 其余凭据只同步优先级与标签，保留内存中的 Token 与状态
+    pub fn reload_credentials(
+        &self,
+        credentials: Vec<KiroCredentials>,
+    ) -> anyhow::Result<CredentialChanges> {
+        let mut changes = CredentialChanges::default();
+        let has_new_ids = credentials.iter().any(|c| c.id.is_none());
+        {
+            let mut entries = self.entries.lock();
+            let mut next_id = entries
+                .iter()
+                .map(|e| e.id)
+                .chain(credentials.iter().filter_map(|c| c.id))
+                .max()
+                .unwrap_or(0)
+                + 1;
+
+            let mut seen_ids = std::collections::HashSet::new();
+            let mut reloaded = Vec::with_capacity(credentials.len());
+            for mut cred in credentials {
+                redact::register_credentials(&cred);
+                let id = *cred.id.get_or_insert_with(|| {
+                    next_id += 1;
+                    next_id - 1
+                });
+                if !seen_ids.insert(id) {
+                    anyhow::bail!("检测到重复的凭据 ID: {}", id);
+                }
+                reloaded.push(cred);
+            }
+
+            entries.retain(|e| {
+                let keep = seen_ids.contains(&e.id);
+                if !keep {
+                    changes.removed.push(e.id);
+                }
+                keep
+            });
+            for cred in reloaded {
+                let id = cred.id.unwrap_or_default();
+                match entries.iter_mut().find(|e| e.id == id) {
+                    Some(entry) if entry.credentials.refresh_token != cred.refresh_token => {
+                        entry.credentials = cred;
+                        entry.failure_count = 0;
+                        entry.disabled = false;
+                        entry.disabled_reason = None;
+                        changes.updated.push(id);
+                    }
+                    Some(entry) => {
+                        if entry.credentials.priority != cred.priority
+                            || entry.credentials.tags != cred.tags
+                        {
+                            entry.credentials.priority = cred.priority;
+                            entry.credentials.tags = cred.tags;
+                            changes.updated.push(id);
+                        }
+                    }
+                    None => {
+                        entries.push(CredentialEntry {
+                            id,
+                            credentials: cred,
+                            failure_count: 0,
+                            disabled: false,
+                            disabled_reason: None,
+                        });
+                        changes.added.push(id);
+                    }
+                }
+            }
+            notify_pool(&entries);
+        }
+
+        let current_id = *self.current_id.lock();
+        if changes.removed.contains(&current_id) || current_id == 0 {
+            self.select_highest_priority();
+        }
+        if self.entries.lock().is_empty() {
+            *self.current_id.lock() = 0;
+        }
+
+        if has_new_ids && let Err(e) = self.persist_credentials() {
+            tracing::warn!("新分配 ID 后持久化失败: {}", e);
+        }
+        Ok(changes)
+    }
+}
+
+/// 凭据热加载的变更
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CredentialChanges {
+    pub added: Vec<u64>,
+    pub removed: Vec<u64>,
+    pub updated: Vec<u64>,
+}
+
+impl CredentialChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.updated.is_empty()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_refresh_error_class() {
+        let status = |code: u16| -> anyhow::Error {
+            RefreshStatusError {
+                status: reqwest::StatusCode::from_u16(code).unwrap(),
+                message: "刷新失败".to_string(),
+            }
+            .into()
+        };
+        assert_eq!(refresh_error_class(&status(401)), "unauthorized");
+        assert_eq!(refresh_error_class(&status(429)), "rate_limited");
+        assert_eq!(refresh_error_class(&status(503)), "server_error");
+        assert_eq!(
+            refresh_error_class(&anyhow::anyhow!("缺少 refreshToken")),
+            "invalid_credentials"
+        );
+        // 错误信息保持原样
+        assert_eq!(status(401).to_string(), "刷新失败");
+    }
+
+    #[test]
+    fn test_reload_credentials() {
+        let cred = |id: Option<u64>, token: &str| KiroCredentials {
+            id,
+            refresh_token: Some(token.to_string()),
+            ..Default::default()
+        };
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![cred(Some(1), "a"), cred(Some(2), "b")],
+            None,
+            None,
+            true,
+        )
+        .unwrap();
+
+        let changes = manager
+            .reload_credentials(vec![cred(Some(2), "b2"), cred(None, "c")])
+            .unwrap();
+        assert_eq!(
+            changes,
+            CredentialChanges {
+                added: vec![3],
+                removed: vec![1],
+                updated: vec![2],
+            }
+        );
+        // 当前凭据被移除后切换到剩余的凭据
+        assert_ne!(manager.snapshot().current_id, 1);
+        assert_eq!(manager.total_count(), 2);
+
+        assert!(
+            manager
+                .reload_credentials(vec![cred(Some(2), "b2"), cred(Some(3), "c")])
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            manager
+                .reload_credentials(vec![cred(Some(2), "x"), cred(Some(2), "y")])
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_token_manager_new() {
         let config = Config::default();
@@ -1461,6 +1951,42 @@ mod tests {
         assert_eq!(manager.available_count(), 2);
     }
 
+    #[tokio::test]
+    async fn test_multi_token_manager_warm_up_skips_disabled_and_keeps_failed() {
+        let config = Config::default();
+        let manager = MultiTokenManager::new(
+            config,
+            vec![KiroCredentials::default(), KiroCredentials::default()],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        manager.set_disabled(2, true).unwrap();
+
+        // 缺少 refreshToken 的凭据刷新失败，但不会因预热被禁用
+        let summary = manager.warm_up(4).await;
+        assert_eq!(
+            summary,
+            WarmUpSummary {
+                ready: 0,
+                exhausted: 0,
+                failed: 1,
+            }
+        );
+        assert_eq!(manager.available_count(), 1);
+
+        // 不同凭据使用各自的刷新锁
+        assert!(!Arc::ptr_eq(
+            &manager.refresh_lock(1),
+            &manager.refresh_lock(2)
+        ));
+        assert!(Arc::ptr_eq(
+            &manager.refresh_lock(1),
+            &manager.refresh_lock(1)
+        ));
+    }
+
     #[test]
     fn test_multi_token_manager_report_quota_exhausted() {
         let config = Config::default();
@@ -1501,4 +2027,94 @@ mod tests {
         );
         assert_eq!(manager.available_count(), 0);
     }
+
+    fn route(model: &str, tags: &[&str]) -> ModelRoute {
+        ModelRoute {
+            model: model.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_model_route_wildcard() {
+        let r = route("claude-opus-*", &["pro"]);
+        assert!(r.matches("claude-opus-4-5-20251101"));
+        assert!(!r.matches("claude-sonnet-4-5"));
+        assert!(route("*haiku*", &["x"]).matches("claude-3-5-haiku-latest"));
+        assert!(route("claude-sonnet-4", &["x"]).matches("claude-sonnet-4"));
+        assert!(!route("claude-sonnet-4", &["x"]).matches("claude-sonnet-4-5"));
+    }
+
+    #[tokio::test]
+    async fn test_multi_token_manager_routes_model_to_tagged_credentials() {
+        let config = Config {
+            model_routes: vec![route("claude-opus-*", &["pro"])],
+            ..Default::default()
+        };
+
+        let valid = |token: &str| KiroCredentials {
+            access_token: Some(token.to_string()),
+            expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let cheap = valid("cheap");
+        let pro = KiroCredentials {
+            priority: 1,
+            tags: vec!["pro".to_string()],
+            ..valid("pro")
+        };
+
+        let manager = MultiTokenManager::new(config, vec![cheap, pro], None, None, false).unwrap();
+
+        // 命中规则的模型只使用带标签的凭据，且不改变当前凭据
+        let ctx = manager
+            .acquire_context_for("claude-opus-4-5")
+            .await
+            .unwrap();
+        assert_eq!(ctx.token, "pro");
+        assert_eq!(manager.snapshot().current_id, 1);
+
+        // 未命中规则的模型按原有策略选择
+        let ctx = manager
+            .acquire_context_for("claude-sonnet-4")
+            .await
+            .unwrap();
+        assert_eq!(ctx.token, "cheap");
+
+        // 带标签的凭据全部不可用时直接失败，不回退到其他凭据
+        manager.report_quota_exhausted(2);
+        let err = manager
+            .acquire_context_for("claude-opus-4-5")
+            .await
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("没有可用的凭据"), "实际: {}", err);
+    }
+
+    #[tokio::test]
+    async fn test_multi_token_manager_acquire_excluding() {
+        let valid = |token: &str, priority: u32| KiroCredentials {
+            access_token: Some(token.to_string()),
+            expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+            priority,
+            ..Default::default()
+        };
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![valid("a", 0), valid("b", 1), valid("c", 2)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let ctx = manager.acquire_context_excluding("m", 1).await.unwrap();
+        assert_eq!(ctx.token, "b");
+        assert_eq!(manager.snapshot().current_id, 1);
+
+        manager.report_quota_exhausted(2);
+        manager.report_quota_exhausted(3);
+        assert!(manager.acquire_context_excluding("m", 1).await.is_err());
+    }
 }
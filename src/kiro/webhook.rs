@@ -0,0 +1,425 @@
+//! 凭据生命周期 Webhook
+//!
+//! 凭据被自动禁用、额度用量达到阈值、Token 连续刷新失败，以及可用凭据数低于下限（和恢复）时，
+//! 向配置的地址 POST 一个 JSON 事件，运维可以在用户感知之前发现凭据池退化。
+//! 配置了 `secret` 时请求带 `X-Kiro-Signature: sha256=<hex>`，为以 `secret` 对请求体计算的 HMAC-SHA256。
+//! 推送在后台进行，失败只记录警告，不影响请求处理。
+//!
+//! 配置了 Telegram 或 Discord 时同样的事件也发送到对应渠道；未配置 Webhook 时使用默认阈值。
+
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+use chrono::Utc;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::admin::audit::hmac_sha256;
+use crate::discord;
+use crate::http_client;
+use crate::model::config::{self, CredentialWebhookConfig};
+use crate::telegram;
+
+/// 推送请求的超时时间（秒）
+const WEBHOOK_TIMEOUT_SECS: u64 = 10;
+
+/// 签名请求头
+const SIGNATURE_HEADER: &str = "x-kiro-signature";
+
+/// 全局 Webhook 通知器
+static NOTIFIER: LazyLock<WebhookNotifier> = LazyLock::new(WebhookNotifier::default);
+
+/// 获取全局 Webhook 通知器
+pub fn notifier() -> &'static WebhookNotifier {
+    &NOTIFIER
+}
+
+/// 凭据生命周期事件
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum CredentialEvent {
+    /// 凭据被自动禁用（`reason` 为 `too_many_failures` 或 `quota_exceeded`）
+    #[serde(rename_all = "camelCase")]
+    CredentialDisabled {
+        credential_id: u64,
+        reason: &'static str,
+    },
+    /// 额度用量达到阈值
+    #[serde(rename_all = "camelCase")]
+    QuotaThreshold {
+        credential_id: u64,
+        current_usage: f64,
+        usage_limit: f64,
+        threshold: f64,
+    },
+    /// Token 连续刷新失败
+    #[serde(rename_all = "camelCase")]
+    RefreshFailing {
+        credential_id: u64,
+        failures: u32,
+        error: String,
+    },
+    /// 可用凭据数低于下限
+    #[serde(rename_all = "camelCase")]
+    PoolDegraded {
+        available: usize,
+        total: usize,
+        min_available: usize,
+    },
+    /// 可用凭据数恢复到下限以上
+    #[serde(rename_all = "camelCase")]
+    PoolRecovered {
+        available: usize,
+        total: usize,
+        min_available: usize,
+    },
+}
+
+impl CredentialEvent {
+    /// 事件类型（与 JSON 中的 `event` 字段一致）
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::CredentialDisabled { .. } => "credential_disabled",
+            Self::QuotaThreshold { .. } => "quota_threshold",
+            Self::RefreshFailing { .. } => "refresh_failing",
+            Self::PoolDegraded { .. } => "pool_degraded",
+            Self::PoolRecovered { .. } => "pool_recovered",
+        }
+    }
+
+    /// 一行摘要（便于直接推送到聊天工具）
+    pub fn summary(&self) -> String {
+        match self {
+            Self::CredentialDisabled {
+                credential_id,
+                reason,
+            } => format!("凭据 #{} 已被自动禁用（{}）", credential_id, reason),
+            Self::QuotaThreshold {
+                credential_id,
+                current_usage,
+                usage_limit,
+                ..
+            } => format!(
+                "凭据 #{} 额度已使用 {:.2} / {:.2}",
+                credential_id, current_usage, usage_limit
+            ),
+            Self::RefreshFailing {
+                credential_id,
+                failures,
+                error,
+            } => format!(
+                "凭据 #{} Token 已连续刷新失败 {} 次: {}",
+                credential_id, failures, error
+            ),
+            Self::PoolDegraded {
+                available, total, ..
+            } => format!("可用凭据只剩 {} / {} 个", available, total),
+            Self::PoolRecovered {
+                available, total, ..
+            } => format!("可用凭据已恢复到 {} / {} 个", available, total),
+        }
+    }
+}
+
+/// 事件阈值
+#[derive(Debug, Clone, Copy)]
+struct Thresholds {
+    quota_threshold: f64,
+    refresh_failures: u32,
+    min_available: usize,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    config: Option<CredentialWebhookConfig>,
+    /// 凭据 ID -> 连续刷新失败次数
+    refresh_failures: HashMap<u64, u32>,
+    /// 已推送过额度阈值事件的凭据（用量回落到阈值以下后移除）
+    quota_notified: HashSet<u64>,
+    /// 可用凭据数是否低于下限
+    pool_degraded: bool,
+}
+
+impl State {
+    /// 当前阈值：配置了 Webhook 时取自其配置，只启用 Telegram 或 Discord 时使用默认值，都未启用时为 None
+    fn thresholds(&self) -> Option<Thresholds> {
+        match &self.config {
+            Some(config) => Some(Thresholds {
+                quota_threshold: config.quota_threshold,
+                refresh_failures: config.refresh_failures,
+                min_available: config.min_available,
+            }),
+            None if telegram::enabled() || discord::enabled() => Some(Thresholds {
+                quota_threshold: config::default_webhook_quota_threshold(),
+                refresh_failures: config::default_webhook_refresh_failures(),
+                min_available: config::default_webhook_min_available(),
+            }),
+            None => None,
+        }
+    }
+}
+
+/// 凭据生命周期 Webhook 通知器
+#[derive(Debug)]
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    state: Mutex<State>,
+}
+
+impl Default for WebhookNotifier {
+    fn default() -> Self {
+        Self {
+            client: http_client::build_integration_client(WEBHOOK_TIMEOUT_SECS).unwrap_or_default(),
+            state: Mutex::new(State::default()),
+        }
+    }
+}
+
+impl WebhookNotifier {
+    /// 设置推送地址与阈值（None 表示不推送），重置统计
+    pub fn configure(&self, config: Option<CredentialWebhookConfig>) {
+        *self.state.lock() = State {
+            config,
+            ..State::default()
+        };
+    }
+
+    /// 凭据被自动禁用
+    pub fn credential_disabled(&self, credential_id: u64, reason: &'static str) {
+        let enabled = self.state.lock().thresholds().is_some();
+        if enabled {
+            self.send(CredentialEvent::CredentialDisabled {
+                credential_id,
+                reason,
+            });
+        }
+    }
+
+    /// Token 刷新成功
+    pub fn refresh_succeeded(&self, credential_id: u64) {
+        self.state.lock().refresh_failures.remove(&credential_id);
+    }
+
+    /// Token 刷新失败，连续失败次数达到阈值时推送（之后不再重复推送，直到刷新成功）
+    pub fn refresh_failed(&self, credential_id: u64, error: &anyhow::Error) {
+        if let Some(event) = self.observe_refresh_failure(credential_id, error) {
+            self.send(event);
+        }
+    }
+
+    /// 查询到凭据的额度用量，首次达到阈值时推送
+    pub fn usage_observed(&self, credential_id: u64, current_usage: f64, usage_limit: f64) {
+        if let Some(event) = self.observe_usage(credential_id, current_usage, usage_limit) {
+            self.send(event);
+        }
+    }
+
+    /// 可用凭据数变化，低于下限或恢复时推送
+    pub fn pool_changed(&self, available: usize, total: usize) {
+        if let Some(event) = self.observe_pool(available, total) {
+            self.send(event);
+        }
+    }
+
+    fn observe_refresh_failure(
+        &self,
+        credential_id: u64,
+        error: &anyhow::Error,
+    ) -> Option<CredentialEvent> {
+        let mut state = self.state.lock();
+        let threshold = state.thresholds()?.refresh_failures;
+        let failures = state.refresh_failures.entry(credential_id).or_default();
+        *failures += 1;
+        (*failures == threshold).then(|| CredentialEvent::RefreshFailing {
+            credential_id,
+            failures: *failures,
+            error: error.to_string(),
+        })
+    }
+
+    fn observe_usage(
+        &self,
+        credential_id: u64,
+        current_usage: f64,
+        usage_limit: f64,
+    ) -> Option<CredentialEvent> {
+        let mut state = self.state.lock();
+        let threshold = state.thresholds()?.quota_threshold;
+        if usage_limit <= 0.0 || current_usage / usage_limit < threshold {
+            state.quota_notified.remove(&credential_id);
+            return None;
+        }
+        state
+            .quota_notified
+            .insert(credential_id)
+            .then_some(CredentialEvent::QuotaThreshold {
+                credential_id,
+                current_usage,
+                usage_limit,
+                threshold,
+            })
+    }
+
+    fn observe_pool(&self, available: usize, total: usize) -> Option<CredentialEvent> {
+        let mut state = self.state.lock();
+        let min_available = state.thresholds()?.min_available;
+        let degraded = available < min_available;
+        if degraded == state.pool_degraded {
+            return None;
+        }
+        state.pool_degraded = degraded;
+        Some(if degraded {
+            CredentialEvent::PoolDegraded {
+                available,
+                total,
+                min_available,
+            }
+        } else {
+            CredentialEvent::PoolRecovered {
+                available,
+                total,
+                min_available,
+            }
+        })
+    }
+
+    /// 在后台推送事件到 Webhook、Telegram 与 Discord（不在 tokio 运行时内时只记录日志）
+    fn send(&self, event: CredentialEvent) {
+        tracing::warn!("凭据事件: {}", event.summary());
+        telegram::send(event.summary());
+        discord::send_credential_event(&event);
+        let Some(config) = self.state.lock().config.clone() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        let mut body = json!({
+            "text": event.summary(),
+            "timestamp": Utc::now().to_rfc3339(),
+        });
+        if let (Some(body), Ok(serde_json::Value::Object(fields))) =
+            (body.as_object_mut(), serde_json::to_value(&event))
+        {
+            body.extend(fields);
+        }
+        let body = body.to_string();
+        let mut request = self
+            .client
+            .post(&config.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let Some(secret) = &config.secret {
+            request = request.header(SIGNATURE_HEADER, signature(secret, &body));
+        }
+
+        runtime.spawn(async move {
+            match request.body(body).send().await {
+                Ok(resp) if !resp.status().is_success() => {
+                    tracing::warn!("推送凭据事件失败: HTTP {}", resp.status());
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("推送凭据事件失败: {}", e),
+            }
+        });
+    }
+}
+
+/// 请求体签名：`sha256=` 加 HMAC-SHA256 的十六进制
+fn signature(secret: &str, body: &str) -> String {
+    format!(
+        "sha256={}",
+        hex::encode(hmac_sha256(secret.as_bytes(), &[body.as_bytes()]))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notifier() -> WebhookNotifier {
+        let notifier = WebhookNotifier::default();
+        notifier.configure(Some(CredentialWebhookConfig {
+            url: "http://127.0.0.1:9/hook".to_string(),
+            secret: None,
+            quota_threshold: 0.9,
+            refresh_failures: 2,
+            min_available: 2,
+        }));
+        notifier
+    }
+
+    #[test]
+    fn test_refresh_failures_fire_once_until_success() {
+        let notifier = notifier();
+        let error = anyhow::anyhow!("invalid_grant");
+        assert!(notifier.observe_refresh_failure(1, &error).is_none());
+        assert!(matches!(
+            notifier.observe_refresh_failure(1, &error),
+            Some(CredentialEvent::RefreshFailing { failures: 2, .. })
+        ));
+        assert!(notifier.observe_refresh_failure(1, &error).is_none());
+
+        notifier.refresh_succeeded(1);
+        assert!(notifier.observe_refresh_failure(1, &error).is_none());
+        assert!(notifier.observe_refresh_failure(1, &error).is_some());
+    }
+
+    #[test]
+    fn test_quota_threshold_fires_once_per_crossing() {
+        let notifier = notifier();
+        assert!(notifier.observe_usage(1, 50.0, 100.0).is_none());
+        assert!(notifier.observe_usage(1, 95.0, 100.0).is_some());
+        assert!(notifier.observe_usage(1, 99.0, 100.0).is_none());
+        // 额度重置后再次达到阈值
+        assert!(notifier.observe_usage(1, 0.0, 100.0).is_none());
+        assert!(notifier.observe_usage(1, 90.0, 100.0).is_some());
+        // 没有额度信息时不推送
+        assert!(notifier.observe_usage(2, 10.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn test_pool_degraded_and_recovered() {
+        let notifier = notifier();
+        assert!(notifier.observe_pool(3, 3).is_none());
+        assert_eq!(
+            notifier.observe_pool(1, 3),
+            Some(CredentialEvent::PoolDegraded {
+                available: 1,
+                total: 3,
+                min_available: 2,
+            })
+        );
+        assert!(notifier.observe_pool(0, 3).is_none());
+        assert!(matches!(
+            notifier.observe_pool(2, 3),
+            Some(CredentialEvent::PoolRecovered { .. })
+        ));
+    }
+
+    #[test]
+    fn test_disabled_notifier_ignores_events() {
+        let notifier = WebhookNotifier::default();
+        assert!(notifier.observe_pool(0, 3).is_none());
+        assert!(notifier.observe_usage(1, 100.0, 100.0).is_none());
+    }
+
+    #[test]
+    fn test_event_payload_and_signature() {
+        let event = CredentialEvent::CredentialDisabled {
+            credential_id: 3,
+            reason: "quota_exceeded",
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({"event": "credential_disabled", "credentialId": 3, "reason": "quota_exceeded"})
+        );
+        assert_eq!(event.kind(), "credential_disabled");
+        // RFC 4231 测试用例 2
+        assert_eq!(
+            signature("Jefe", "what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! 自适应并发上限（AIMD）
+//!
+//! 根据上游调用的结果调整全局准入队列的容量：上游返回 429 或响应耗时明显高于基线时按比例降低上限
+//! （每个冷却期最多一次，避免同一波拥塞被重复计算），其余成功调用每次增加 `1 / 上限`，
+//! 即大约每一轮并发增加 1。基线为非拥塞调用耗时（到收到响应头为止）的指数移动平均。
+
+use std::sync::LazyLock;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::metrics;
+use crate::model::config::AdaptiveConcurrencyConfig;
+
+/// 积累多少个耗时样本后才判断耗时突增
+const WARMUP_SAMPLES: u64 = 20;
+
+/// 耗时基线的指数移动平均系数
+const BASELINE_ALPHA: f64 = 0.05;
+
+/// 两次降低上限之间的最短间隔下限（实际取该值与耗时基线中的较大者）
+const MIN_COOLDOWN: Duration = Duration::from_secs(1);
+
+/// 全局自适应并发上限
+static ADAPTIVE: LazyLock<AdaptiveLimit> = LazyLock::new(AdaptiveLimit::default);
+
+/// 获取全局自适应并发上限
+pub fn adaptive() -> &'static AdaptiveLimit {
+    &ADAPTIVE
+}
+
+#[derive(Debug, Default)]
+struct State {
+    config: Option<AdaptiveConcurrencyConfig>,
+    /// 当前上限（小数部分用于累计加性增长）
+    limit: f64,
+    /// 非拥塞调用耗时的移动平均（毫秒）
+    baseline_ms: f64,
+    samples: u64,
+    last_decrease: Option<Instant>,
+}
+
+/// 自适应并发上限
+#[derive(Debug, Default)]
+pub struct AdaptiveLimit {
+    state: Mutex<State>,
+}
+
+impl AdaptiveLimit {
+    /// 设置参数（None 表示关闭），上限重置为 `initialLimit`
+    pub fn configure(&self, config: Option<AdaptiveConcurrencyConfig>) {
+        let mut state = self.state.lock();
+        *state = State {
+            limit: config.as_ref().map_or(0.0, |c| c.initial_limit as f64),
+            config,
+            ..State::default()
+        };
+        set_limit_metric(&state);
+    }
+
+    /// 当前并发上限，未启用时为 None
+    pub fn limit(&self) -> Option<usize> {
+        let state = self.state.lock();
+        state.config.as_ref().map(|_| state.limit as usize)
+    }
+
+    /// 记录一次收到响应的上游调用：状态码与收到响应头的耗时
+    pub fn record(&self, status: u16, latency: Duration) {
+        self.record_at(status, latency, Instant::now());
+    }
+
+    fn record_at(&self, status: u16, latency: Duration, now: Instant) {
+        let mut state = self.state.lock();
+        let Some(config) = state.config.clone() else {
+            return;
+        };
+
+        let latency_ms = latency.as_secs_f64() * 1000.0;
+        let spike = state.samples >= WARMUP_SAMPLES
+            && latency_ms > state.baseline_ms * config.latency_tolerance;
+        if !spike && status != 429 {
+            state.baseline_ms = if state.samples == 0 {
+                latency_ms
+            } else {
+                state.baseline_ms * (1.0 - BASELINE_ALPHA) + latency_ms * BASELINE_ALPHA
+            };
+            state.samples += 1;
+        }
+
+        if spike || status == 429 {
+            let cooldown = MIN_COOLDOWN.max(Duration::from_secs_f64(state.baseline_ms / 1000.0));
+            if state
+                .last_decrease
+                .is_some_and(|last| now.duration_since(last) < cooldown)
+            {
+                return;
+            }
+            state.last_decrease = Some(now);
+            let limit = (state.limit * config.backoff_ratio).max(config.min_limit as f64);
+            tracing::info!(
+                "上游{}，并发上限 {} -> {}",
+                if status == 429 {
+                    "限流"
+                } else {
+                    "耗时突增"
+                },
+                state.limit as usize,
+                limit as usize
+            );
+            state.limit = limit;
+        } else if (200..300).contains(&status) {
+            state.limit = (state.limit + 1.0 / state.limit.max(1.0)).min(config.max_limit as f64);
+        }
+        set_limit_metric(&state);
+    }
+}
+
+fn set_limit_metric(state: &State) {
+    metrics::global()
+        .adaptive_concurrency_limit
+        .store(state.limit as i64, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit() -> AdaptiveLimit {
+        let limit = AdaptiveLimit::default();
+        limit.configure(Some(AdaptiveConcurrencyConfig {
+            initial_limit: 10,
+            min_limit: 2,
+            max_limit: 12,
+            backoff_ratio: 0.5,
+            latency_tolerance: 2.0,
+        }));
+        limit
+    }
+
+    #[test]
+    fn test_additive_increase() {
+        let limit = limit();
+        let now = Instant::now();
+        for _ in 0..11 {
+            limit.record_at(200, Duration::from_millis(100), now);
+        }
+        assert_eq!(limit.limit(), Some(11));
+        for _ in 0..100 {
+            limit.record_at(200, Duration::from_millis(100), now);
+        }
+        assert_eq!(limit.limit(), Some(12));
+    }
+
+    #[test]
+    fn test_backs_off_on_429_once_per_cooldown() {
+        let limit = limit();
+        let now = Instant::now();
+        limit.record_at(429, Duration::from_millis(100), now);
+        assert_eq!(limit.limit(), Some(5));
+        // 同一波拥塞只降低一次
+        limit.record_at(
+            429,
+            Duration::from_millis(100),
+            now + Duration::from_millis(500),
+        );
+        assert_eq!(limit.limit(), Some(5));
+        limit.record_at(
+            429,
+            Duration::from_millis(100),
+            now + Duration::from_secs(2),
+        );
+        assert_eq!(limit.limit(), Some(2));
+        limit.record_at(
+            429,
+            Duration::from_millis(100),
+            now + Duration::from_secs(4),
+        );
+        assert_eq!(limit.limit(), Some(2));
+    }
+
+    #[test]
+    fn test_backs_off_on_latency_spike() {
+        let limit = limit();
+        let now = Instant::now();
+        for _ in 0..WARMUP_SAMPLES {
+            limit.record_at(200, Duration::from_millis(100), now);
+        }
+        let before = limit.limit().unwrap();
+        limit.record_at(200, Duration::from_millis(150), now);
+        assert!(limit.limit().unwrap() >= before);
+        limit.record_at(200, Duration::from_millis(500), now);
+        assert_eq!(limit.limit(), Some(before / 2));
+    }
+
+    #[test]
+    fn test_disabled() {
+        let limit = AdaptiveLimit::default();
+        limit.record(429, Duration::from_secs(1));
+        assert_eq!(limit.limit(), None);
+    }
+}
@@ -0,0 +1,187 @@
+//! 按客户端 IP 的认证失败锁定
+//!
+//! 记录每个客户端 IP 在 `windowSecs` 内提供错误 Key 的次数，达到 `maxFailures` 后在 `lockoutSecs`
+//! 内拒绝该 IP 的全部认证请求（即使 Key 正确），用于抵御在线猜测 Key。
+//! 认证成功会清零该 IP 的失败计数；跟踪的 IP 过多时丢弃窗口已过期且未锁定的记录。
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::model::config::AuthLockoutConfig;
+
+/// 跟踪的 IP 数超过该值时清理过期的记录
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Debug)]
+struct Failures {
+    /// 当前统计窗口的起始时间
+    window_start: Instant,
+    /// 窗口内的失败次数
+    count: u32,
+    locked_until: Option<Instant>,
+}
+
+/// 认证失败锁定器，代理 API 与 Admin API 共用
+#[derive(Debug, Default)]
+pub struct AuthLockout {
+    config: Mutex<Option<AuthLockoutConfig>>,
+    clients: Mutex<HashMap<IpAddr, Failures>>,
+}
+
+impl AuthLockout {
+    /// 创建锁定器，`config` 为 None 时不锁定
+    pub fn new(config: Option<AuthLockoutConfig>) -> Self {
+        Self {
+            config: Mutex::new(config.filter(|c| c.max_failures > 0)),
+            clients: Mutex::default(),
+        }
+    }
+
+    /// 修改锁定配置（已记录的失败次数与锁定状态一并清除）
+    pub fn set_config(&self, config: Option<AuthLockoutConfig>) {
+        *self.config.lock() = config.filter(|c| c.max_failures > 0);
+        self.clients.lock().clear();
+    }
+
+    /// `ip` 是否处于锁定期，锁定时返回剩余时间
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        if self.config.lock().is_none() {
+            return Ok(());
+        }
+        let now = Instant::now();
+        match self.clients.lock().get(&ip.to_canonical()) {
+            Some(Failures {
+                locked_until: Some(until),
+                ..
+            }) if *until > now => Err(*until - now),
+            _ => Ok(()),
+        }
+    }
+
+    /// 记录一次认证失败，本次失败触发锁定时返回锁定时长
+    pub fn record_failure(&self, ip: IpAddr) -> Option<Duration> {
+        let config = self.config.lock().clone()?;
+        let now = Instant::now();
+        let window = Duration::from_secs(config.window_secs);
+        let mut clients = self.clients.lock();
+        if clients.len() >= PRUNE_THRESHOLD {
+            clients.retain(|_, failures| {
+                failures.locked_until.is_some_and(|until| until > now)
+                    || now.saturating_duration_since(failures.window_start) < window
+            });
+        }
+
+        let failures = clients.entry(ip.to_canonical()).or_insert(Failures {
+            window_start: now,
+            count: 0,
+            locked_until: None,
+        });
+        if now.saturating_duration_since(failures.window_start) >= window {
+            failures.window_start = now;
+            failures.count = 0;
+        }
+        failures.count += 1;
+        if failures.count < config.max_failures {
+            return None;
+        }
+        let lockout = Duration::from_secs(config.lockout_secs);
+        failures.locked_until = Some(now + lockout);
+        failures.window_start = now;
+        failures.count = 0;
+        Some(lockout)
+    }
+
+    /// 认证成功，清零 `ip` 的失败计数（锁定期内不清除）
+    pub fn record_success(&self, ip: IpAddr) {
+        if self.config.lock().is_none() {
+            return;
+        }
+        let now = Instant::now();
+        let mut clients = self.clients.lock();
+        let ip = ip.to_canonical();
+        if clients
+            .get(&ip)
+            .is_some_and(|failures| failures.locked_until.is_none_or(|until| until <= now))
+        {
+            clients.remove(&ip);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(max_failures: u32) -> AuthLockoutConfig {
+        AuthLockoutConfig {
+            max_failures,
+            window_secs: 300,
+            lockout_secs: 900,
+        }
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_disabled_lockout_never_locks() {
+        let lockout = AuthLockout::new(None);
+        for _ in 0..1000 {
+            assert_eq!(lockout.record_failure(ip("203.0.113.9")), None);
+        }
+        assert!(lockout.check(ip("203.0.113.9")).is_ok());
+    }
+
+    #[test]
+    fn test_lockout_after_max_failures() {
+        let lockout = AuthLockout::new(Some(config(3)));
+        assert_eq!(lockout.record_failure(ip("203.0.113.9")), None);
+        assert_eq!(lockout.record_failure(ip("203.0.113.9")), None);
+        assert!(lockout.check(ip("203.0.113.9")).is_ok());
+        assert_eq!(
+            lockout.record_failure(ip("203.0.113.9")),
+            Some(Duration::from_secs(900))
+        );
+
+        // 锁定期内认证成功也不解除，IPv4 映射的 IPv6 地址视为同一 IP
+        lockout.record_success(ip("203.0.113.9"));
+        assert!(lockout.check(ip("::ffff:203.0.113.9")).is_err());
+        assert!(lockout.check(ip("198.51.100.7")).is_ok());
+
+        // 修改配置后清除锁定状态
+        lockout.set_config(Some(config(3)));
+        assert!(lockout.check(ip("203.0.113.9")).is_ok());
+    }
+
+    #[test]
+    fn test_success_resets_failures() {
+        let lockout = AuthLockout::new(Some(config(2)));
+        assert_eq!(lockout.record_failure(ip("203.0.113.9")), None);
+        lockout.record_success(ip("203.0.113.9"));
+        assert_eq!(lockout.record_failure(ip("203.0.113.9")), None);
+        assert!(lockout.record_failure(ip("203.0.113.9")).is_some());
+    }
+
+    #[test]
+    fn test_success_after_lockout_expiry_resets_failures() {
+        let lockout = AuthLockout::new(Some(AuthLockoutConfig {
+            max_failures: 2,
+            window_secs: 300,
+            lockout_secs: 0,
+        }));
+        assert_eq!(lockout.record_failure(ip("203.0.113.9")), None);
+        assert!(lockout.record_failure(ip("203.0.113.9")).is_some());
+        // 锁定已到期
+        assert!(lockout.check(ip("203.0.113.9")).is_ok());
+
+        assert_eq!(lockout.record_failure(ip("203.0.113.9")), None);
+        lockout.record_success(ip("203.0.113.9"));
+        // 认证成功后重新计数，不会因到期前累计的失败提前锁定
+        assert_eq!(lockout.record_failure(ip("203.0.113.9")), None);
+        assert!(lockout.record_failure(ip("203.0.113.9")).is_some());
+    }
+}
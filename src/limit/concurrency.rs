@@ -0,0 +1,107 @@
+//! 按 API Key 的并发限制
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+/// 按 API Key 统计进行中的请求数，超过上限时拒绝新请求
+pub struct ConcurrencyLimiter {
+    /// 每个 API Key 允许的最大并发数（None 表示不限制）
+    max_per_key: Mutex<Option<usize>>,
+    /// 每个 API Key 当前进行中的请求数
+    in_flight: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+/// 并发许可，drop 时释放
+///
+/// 需要随响应体一起存活，流式请求在流结束或客户端断开时才释放
+pub struct ConcurrencyPermit {
+    key: String,
+    in_flight: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(max_per_key: Option<usize>) -> Self {
+        Self {
+            max_per_key: Mutex::new(max_per_key),
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// 修改并发上限（进行中的请求不受影响）
+    pub fn set_limit(&self, max_per_key: Option<usize>) {
+        *self.max_per_key.lock() = max_per_key;
+    }
+
+    /// 尝试获取许可，已达上限时返回 None
+    pub fn try_acquire(&self, key: &str) -> Option<ConcurrencyPermit> {
+        let max_per_key = *self.max_per_key.lock();
+        let mut in_flight = self.in_flight.lock();
+        let count = in_flight.entry(key.to_string()).or_insert(0);
+        if max_per_key.is_some_and(|max| *count >= max) {
+            return None;
+        }
+        *count += 1;
+
+        Some(ConcurrencyPermit {
+            key: key.to_string(),
+            in_flight: self.in_flight.clone(),
+        })
+    }
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock();
+        if let Some(count) = in_flight.get_mut(&self.key) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                in_flight.remove(&self.key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_per_key() {
+        let limiter = ConcurrencyLimiter::new(Some(2));
+
+        let a1 = limiter.try_acquire("a").unwrap();
+        let _a2 = limiter.try_acquire("a").unwrap();
+        assert!(limiter.try_acquire("a").is_none());
+        // 其他 key 不受影响
+        assert!(limiter.try_acquire("b").is_some());
+
+        drop(a1);
+        assert!(limiter.try_acquire("a").is_some());
+    }
+
+    #[test]
+    fn test_set_limit() {
+        let limiter = ConcurrencyLimiter::new(Some(1));
+        let _a1 = limiter.try_acquire("a").unwrap();
+        assert!(limiter.try_acquire("a").is_none());
+
+        limiter.set_limit(Some(2));
+        let _a2 = limiter.try_acquire("a").unwrap();
+        assert!(limiter.try_acquire("a").is_none());
+
+        limiter.set_limit(None);
+        assert!(limiter.try_acquire("a").is_some());
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let limiter = ConcurrencyLimiter::new(None);
+        let permits: Vec<_> = (0..100)
+            .map(|_| limiter.try_acquire("a").unwrap())
+            .collect();
+        drop(permits);
+        assert!(limiter.in_flight.lock().is_empty());
+    }
+}
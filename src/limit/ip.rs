@@ -0,0 +1,173 @@
+//! 按客户端 IP 的令牌桶限流
+//!
+//! 每个客户端 IP 独立一个令牌桶，容量为突发请求数，按每分钟额度补充；
+//! 连续被限流达到 `banAfter` 次的 IP 在 `banSecs` 内拒绝全部请求。
+//! 补满且未封禁的桶与新建的桶等价，跟踪的 IP 过多时丢弃这些桶，避免内存随来源地址无限增长。
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use super::rate::Bucket;
+use crate::model::config::IpRateLimitConfig;
+
+/// 跟踪的 IP 数超过该值时清理空闲的桶
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// 被限流的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpRateLimitError {
+    /// 超出额度，附带建议的等待时间
+    Limited(Duration),
+    /// 已被封禁，附带剩余的封禁时间
+    Banned(Duration),
+}
+
+#[derive(Debug)]
+struct Client {
+    bucket: Bucket,
+    /// 连续被限流的次数
+    violations: u32,
+    banned_until: Option<Instant>,
+}
+
+/// 按客户端 IP 的限流器
+#[derive(Debug, Default)]
+pub struct IpRateLimiter {
+    config: Mutex<Option<IpRateLimitConfig>>,
+    clients: Mutex<HashMap<IpAddr, Client>>,
+}
+
+impl IpRateLimiter {
+    /// 创建限流器，`config` 为 None 时不限制
+    pub fn new(config: Option<IpRateLimitConfig>) -> Self {
+        Self {
+            config: Mutex::new(config.filter(|c| c.requests_per_minute > 0)),
+            clients: Mutex::default(),
+        }
+    }
+
+    /// 修改限流配置（已跟踪的桶与封禁状态一并清除）
+    pub fn set_config(&self, config: Option<IpRateLimitConfig>) {
+        *self.config.lock() = config.filter(|c| c.requests_per_minute > 0);
+        self.clients.lock().clear();
+    }
+
+    /// 尝试放行来自 `ip` 的一个请求
+    pub fn check(&self, ip: IpAddr) -> Result<(), IpRateLimitError> {
+        let Some(config) = self.config.lock().clone() else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let mut clients = self.clients.lock();
+        if clients.len() >= PRUNE_THRESHOLD {
+            clients.retain(|_, client| {
+                client.bucket.refill(now);
+                client.banned_until.is_some_and(|until| until > now) || !client.bucket.is_full()
+            });
+        }
+
+        let client = clients.entry(ip.to_canonical()).or_insert_with(|| Client {
+            bucket: Bucket::with_burst(
+                config.requests_per_minute,
+                config.burst.unwrap_or(config.requests_per_minute).max(1),
+                now,
+            ),
+            violations: 0,
+            banned_until: None,
+        });
+        if let Some(until) = client.banned_until {
+            if until > now {
+                return Err(IpRateLimitError::Banned(until - now));
+            }
+            client.banned_until = None;
+        }
+
+        client.bucket.refill(now);
+        match client.bucket.wait_for(1.0) {
+            None => {
+                client.bucket.take(1.0);
+                client.violations = 0;
+                Ok(())
+            }
+            Some(wait) => {
+                client.violations += 1;
+                match config.ban_after {
+                    Some(ban_after) if client.violations >= ban_after => {
+                        let ban = Duration::from_secs(config.ban_secs);
+                        client.banned_until = Some(now + ban);
+                        client.violations = 0;
+                        Err(IpRateLimitError::Banned(ban))
+                    }
+                    _ => Err(IpRateLimitError::Limited(wait)),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(burst: u64, ban_after: Option<u32>) -> IpRateLimitConfig {
+        IpRateLimitConfig {
+            requests_per_minute: 60,
+            burst: Some(burst),
+            ban_after,
+            ban_secs: 600,
+        }
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_disabled_limiter_always_passes() {
+        let limiter = IpRateLimiter::new(None);
+        for _ in 0..1000 {
+            assert!(limiter.check(ip("203.0.113.9")).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_burst_per_ip() {
+        let limiter = IpRateLimiter::new(Some(config(2, None)));
+        assert!(limiter.check(ip("203.0.113.9")).is_ok());
+        assert!(limiter.check(ip("203.0.113.9")).is_ok());
+
+        // 每秒补充一个请求额度
+        match limiter.check(ip("203.0.113.9")) {
+            Err(IpRateLimitError::Limited(wait)) => assert!(wait <= Duration::from_secs(1)),
+            other => panic!("unexpected {:?}", other),
+        }
+        // 其他 IP 不受影响，IPv4 映射的 IPv6 地址视为同一 IP
+        assert!(limiter.check(ip("198.51.100.7")).is_ok());
+        assert!(limiter.check(ip("::ffff:203.0.113.9")).is_err());
+    }
+
+    #[test]
+    fn test_ban_after_repeated_violations() {
+        let limiter = IpRateLimiter::new(Some(config(1, Some(2))));
+        assert!(limiter.check(ip("203.0.113.9")).is_ok());
+        assert!(matches!(
+            limiter.check(ip("203.0.113.9")),
+            Err(IpRateLimitError::Limited(_))
+        ));
+        assert_eq!(
+            limiter.check(ip("203.0.113.9")),
+            Err(IpRateLimitError::Banned(Duration::from_secs(600)))
+        );
+        assert!(matches!(
+            limiter.check(ip("203.0.113.9")),
+            Err(IpRateLimitError::Banned(_))
+        ));
+
+        // 修改配置后清除封禁状态
+        limiter.set_config(Some(config(1, Some(2))));
+        assert!(limiter.check(ip("203.0.113.9")).is_ok());
+    }
+}
@@ -0,0 +1,19 @@
+//! 下游流量限制模块
+//!
+//! 在请求到达上游之前对下游客户端做准入控制，保护凭据池不被单个客户端耗尽。
+
+mod adaptive;
+mod auth;
+mod concurrency;
+mod ip;
+mod queue;
+mod rate;
+mod shed;
+
+pub use adaptive::adaptive;
+pub use auth::AuthLockout;
+pub use concurrency::{ConcurrencyLimiter, ConcurrencyPermit};
+pub use ip::{IpRateLimitError, IpRateLimiter};
+pub use queue::{AdmissionError, AdmissionPermit, AdmissionQueue};
+pub use rate::RateLimiter;
+pub use shed::shedder;
@@ -0,0 +1,308 @@
+//! 全局准入队列
+//!
+//! 限制同时发往上游的请求总数。凭据池饱和时新请求进入有界队列等待，
+//! 有请求完成时优先唤醒优先级最高的请求，同一优先级内先到先得；
+//! 超过等待期限或队列已满时拒绝。
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+
+use crate::metrics;
+use crate::model::config::Priority;
+
+/// 准入失败原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdmissionError {
+    /// 队列已满
+    QueueFull,
+    /// 等待超时
+    Timeout,
+}
+
+impl std::fmt::Display for AdmissionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdmissionError::QueueFull => write!(f, "准入队列已满"),
+            AdmissionError::Timeout => write!(f, "排队等待超时"),
+        }
+    }
+}
+
+impl std::error::Error for AdmissionError {}
+
+/// 排队中的请求
+struct Waiter {
+    id: u64,
+    priority: Priority,
+    notify: oneshot::Sender<()>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// 已准入的请求数
+    in_flight: usize,
+    /// 最近一次计算的容量（释放许可时用于判断能否转交给等待者）
+    capacity: usize,
+    /// 等待中的请求（按到达顺序）
+    waiters: VecDeque<Waiter>,
+    /// 下一个等待者 ID
+    next_id: u64,
+}
+
+impl QueueState {
+    /// 取出下一个应被唤醒的等待者：优先级最高者中最早到达的一个
+    fn pop_next(&mut self) -> Option<Waiter> {
+        let highest = self.waiters.iter().map(|w| w.priority).max()?;
+        let index = self.waiters.iter().position(|w| w.priority == highest)?;
+        self.waiters.remove(index)
+    }
+
+    fn set_depth_metric(&self) {
+        metrics::global()
+            .queue_depth
+            .store(self.waiters.len() as i64, Ordering::Relaxed);
+    }
+}
+
+/// 全局准入队列
+pub struct AdmissionQueue {
+    state: Arc<Mutex<QueueState>>,
+    /// 最大排队数与最长等待时间
+    limits: Mutex<(usize, Duration)>,
+}
+
+/// 准入许可，drop 时释放名额并唤醒下一个等待者
+pub struct AdmissionPermit {
+    state: Arc<Mutex<QueueState>>,
+}
+
+/// 等待者在 future 被取消（客户端断开）或超时时将自己移出队列
+struct WaiterGuard {
+    state: Arc<Mutex<QueueState>>,
+    id: u64,
+}
+
+impl AdmissionQueue {
+    pub fn new(max_depth: usize, timeout: Duration) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(QueueState::default())),
+            limits: Mutex::new((max_depth, timeout)),
+        }
+    }
+
+    /// 修改最大排队数与最长等待时间（已在排队的请求沿用原等待时间）
+    pub fn set_limits(&self, max_depth: usize, timeout: Duration) {
+        *self.limits.lock() = (max_depth, timeout);
+    }
+
+    /// 获取准入许可
+    ///
+    /// `capacity` 为当前允许同时处理的请求数，由调用方按可用凭据数计算，
+    /// 凭据被禁用或恢复时随之变化
+    pub async fn acquire(
+        &self,
+        capacity: usize,
+        priority: Priority,
+    ) -> Result<AdmissionPermit, AdmissionError> {
+        let (max_depth, timeout) = *self.limits.lock();
+        let (id, rx) = {
+            let mut state = self.state.lock();
+            state.capacity = capacity;
+
+            if state.in_flight < capacity && state.waiters.is_empty() {
+                state.in_flight += 1;
+                metrics::global()
+                    .in_flight_requests
+                    .fetch_add(1, Ordering::Relaxed);
+                return Ok(self.permit());
+            }
+
+            if state.waiters.len() >= max_depth {
+                metrics::global()
+                    .queue_rejected_total
+                    .fetch_add(1, Ordering::Relaxed);
+                return Err(AdmissionError::QueueFull);
+            }
+
+            let id = state.next_id;
+            state.next_id += 1;
+            let (tx, rx) = oneshot::channel();
+            state.waiters.push_back(Waiter {
+                id,
+                priority,
+                notify: tx,
+            });
+            state.set_depth_metric();
+            (id, rx)
+        };
+
+        let guard = WaiterGuard {
+            state: self.state.clone(),
+            id,
+        };
+
+        match tokio::time::timeout(timeout, rx).await {
+            // 释放者已将名额转交给本请求
+            Ok(Ok(())) => {
+                std::mem::forget(guard);
+                Ok(self.permit())
+            }
+            _ => {
+                // 超时与名额转交可能同时发生：已被移出队列说明名额已转交
+                let still_waiting = guard.remove();
+                std::mem::forget(guard);
+                if still_waiting {
+                    metrics::global()
+                        .queue_timeout_total
+                        .fetch_add(1, Ordering::Relaxed);
+                    Err(AdmissionError::Timeout)
+                } else {
+                    Ok(self.permit())
+                }
+            }
+        }
+    }
+
+    fn permit(&self) -> AdmissionPermit {
+        AdmissionPermit {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl WaiterGuard {
+    /// 将等待者移出队列，返回是否仍在队列中
+    fn remove(&self) -> bool {
+        let mut state = self.state.lock();
+        let before = state.waiters.len();
+        state.waiters.retain(|w| w.id != self.id);
+        let removed = state.waiters.len() != before;
+        state.set_depth_metric();
+        removed
+    }
+}
+
+impl Drop for WaiterGuard {
+    fn drop(&mut self) {
+        // future 在等待期间被取消：若名额已转交给本请求，需要归还
+        if !self.remove() {
+            release(&self.state);
+        }
+    }
+}
+
+impl Drop for AdmissionPermit {
+    fn drop(&mut self) {
+        release(&self.state);
+    }
+}
+
+/// 释放一个名额：容量允许时直接转交给下一个等待者，否则归还
+fn release(state: &Mutex<QueueState>) {
+    let mut state = state.lock();
+    if state.in_flight <= state.capacity {
+        while let Some(waiter) = state.pop_next() {
+            if waiter.notify.send(()).is_ok() {
+                state.set_depth_metric();
+                return;
+            }
+        }
+    }
+    state.in_flight = state.in_flight.saturating_sub(1);
+    state.set_depth_metric();
+    metrics::global()
+        .in_flight_requests
+        .fetch_sub(1, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_waits_for_release() {
+        let queue = Arc::new(AdmissionQueue::new(10, Duration::from_secs(5)));
+        let first = queue.acquire(1, Priority::Normal).await.unwrap();
+
+        let waiter = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.acquire(1, Priority::Normal).await.is_ok() })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(queue.state.lock().waiters.len(), 1);
+
+        drop(first);
+        assert!(waiter.await.unwrap());
+        assert_eq!(queue.state.lock().in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_dequeued_first() {
+        let queue = Arc::new(AdmissionQueue::new(10, Duration::from_secs(5)));
+        let held = queue.acquire(1, Priority::Normal).await.unwrap();
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut tasks = Vec::new();
+        for priority in [Priority::Low, Priority::Normal, Priority::High] {
+            let queue = queue.clone();
+            let order = order.clone();
+            tasks.push(tokio::spawn(async move {
+                let _permit = queue.acquire(1, priority).await.unwrap();
+                order.lock().push(priority);
+            }));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        drop(held);
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(
+            *order.lock(),
+            vec![Priority::High, Priority::Normal, Priority::Low]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_timeout_and_full() {
+        let queue = Arc::new(AdmissionQueue::new(1, Duration::from_millis(20)));
+        let _held = queue.acquire(1, Priority::Normal).await.unwrap();
+
+        let waiter = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.acquire(1, Priority::Normal).await.err() })
+        };
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(
+            queue.acquire(1, Priority::Normal).await.err(),
+            Some(AdmissionError::QueueFull)
+        );
+        assert_eq!(waiter.await.unwrap(), Some(AdmissionError::Timeout));
+        assert!(queue.state.lock().waiters.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_leaves_queue() {
+        let queue = Arc::new(AdmissionQueue::new(10, Duration::from_secs(5)));
+        let held = queue.acquire(1, Priority::Normal).await.unwrap();
+
+        let waiter = {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                let _ = queue.acquire(1, Priority::Normal).await;
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        waiter.abort();
+        let _ = waiter.await;
+        assert!(queue.state.lock().waiters.is_empty());
+
+        drop(held);
+        assert_eq!(queue.state.lock().in_flight, 0);
+    }
+}
@@ -0,0 +1,178 @@
+//! 全局令牌桶限流
+//!
+//! 对所有下游流量统一限制每分钟请求数（RPM）与每分钟 token 数（TPM），
+//! 用于避免触发上游账号级别的限流。
+//!
+//! TPM 在请求完成后按实际用量扣减，桶可以被扣成负数，
+//! 此时后续请求需要等待桶重新回到正数才能通过。
+
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// 令牌桶
+#[derive(Debug)]
+pub(super) struct Bucket {
+    /// 桶容量（默认为每分钟额度）
+    capacity: f64,
+    /// 当前余量（可能为负）
+    tokens: f64,
+    /// 每秒补充量
+    refill_per_sec: f64,
+    /// 上次补充时间
+    updated: Instant,
+}
+
+impl Bucket {
+    fn per_minute(limit: u64, now: Instant) -> Self {
+        Self::with_burst(limit, limit, now)
+    }
+
+    /// 每分钟补充 `limit`、容量为 `burst` 的桶
+    pub(super) fn with_burst(limit: u64, burst: u64, now: Instant) -> Self {
+        let capacity = burst as f64;
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_sec: limit as f64 / 60.0,
+            updated: now,
+        }
+    }
+
+    pub(super) fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// 桶是否已补满（补满的桶与新建的桶等价，可以丢弃）
+    pub(super) fn is_full(&self) -> bool {
+        self.tokens >= self.capacity
+    }
+
+    /// 扣减余量
+    pub(super) fn take(&mut self, amount: f64) {
+        self.tokens -= amount;
+    }
+
+    /// 余量达到 `amount` 还需等待的时间（已足够时为 None）
+    pub(super) fn wait_for(&self, amount: f64) -> Option<Duration> {
+        if self.tokens >= amount {
+            return None;
+        }
+        Some(Duration::from_secs_f64(
+            (amount - self.tokens) / self.refill_per_sec,
+        ))
+    }
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+}
+
+/// 全局 RPM/TPM 限流器
+#[derive(Debug)]
+pub struct RateLimiter {
+    state: Mutex<LimiterState>,
+}
+
+impl RateLimiter {
+    /// 创建限流器，`rpm` / `tpm` 为 None 时对应维度不限制
+    pub fn new(rpm: Option<u64>, tpm: Option<u64>) -> Self {
+        let now = Instant::now();
+        Self {
+            state: Mutex::new(LimiterState {
+                requests: rpm.filter(|&v| v > 0).map(|v| Bucket::per_minute(v, now)),
+                tokens: tpm.filter(|&v| v > 0).map(|v| Bucket::per_minute(v, now)),
+            }),
+        }
+    }
+
+    /// 修改 RPM / TPM 上限（对应维度的桶以新的额度重新开始）
+    pub fn set_limits(&self, rpm: Option<u64>, tpm: Option<u64>) {
+        *self.state.lock() = Self::new(rpm, tpm).state.into_inner();
+    }
+
+    /// 尝试放行一个请求，被限流时返回建议的等待时间
+    ///
+    /// TPM 只要求余量为正，实际用量在请求完成后通过 [`record_tokens`](Self::record_tokens) 扣减
+    pub fn check(&self) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut state = self.state.lock();
+
+        let mut wait = None;
+        if let Some(bucket) = state.tokens.as_mut() {
+            bucket.refill(now);
+            wait = bucket.wait_for(f64::MIN_POSITIVE);
+        }
+        if let Some(bucket) = state.requests.as_mut() {
+            bucket.refill(now);
+            wait = wait.max(bucket.wait_for(1.0));
+        }
+        if let Some(wait) = wait {
+            return Err(wait);
+        }
+
+        if let Some(bucket) = state.requests.as_mut() {
+            bucket.tokens -= 1.0;
+        }
+        Ok(())
+    }
+
+    /// 扣减已完成请求的 token 用量
+    pub fn record_tokens(&self, tokens: u64) {
+        let mut state = self.state.lock();
+        if let Some(bucket) = state.tokens.as_mut() {
+            bucket.refill(Instant::now());
+            bucket.tokens -= tokens as f64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_limiter_always_passes() {
+        let limiter = RateLimiter::new(None, None);
+        for _ in 0..1000 {
+            assert!(limiter.check().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_rpm_limit() {
+        let limiter = RateLimiter::new(Some(2), None);
+        assert!(limiter.check().is_ok());
+        assert!(limiter.check().is_ok());
+
+        // 每 30 秒补充一个请求额度
+        let wait = limiter.check().unwrap_err();
+        assert!(wait > Duration::from_secs(29) && wait <= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_tpm_debited_after_completion() {
+        let limiter = RateLimiter::new(None, Some(600));
+        assert!(limiter.check().is_ok());
+        limiter.record_tokens(900);
+
+        // 欠 300 tokens，每秒补充 10 个
+        let wait = limiter.check().unwrap_err();
+        assert!(wait > Duration::from_secs(29) && wait <= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_tpm_block_does_not_consume_request_quota() {
+        let limiter = RateLimiter::new(Some(1), Some(60));
+        limiter.record_tokens(120);
+        assert!(limiter.check().is_err());
+
+        // 请求额度未被扣减
+        let state = limiter.state.lock();
+        assert!(state.requests.as_ref().unwrap().tokens > 0.99);
+    }
+}
@@ -0,0 +1,208 @@
+//! 资源压力下的降载
+//!
+//! 后台任务定期采样事件循环延迟（定时器实际唤醒时间比预期晚多少）与进程常驻内存，
+//! 超过阈值时拒绝低优先级的新请求（返回 503），已在处理的请求与流式响应不受影响；
+//! 超过阈值 [`CRITICAL_FACTOR`] 倍时普通优先级的新请求也被拒绝，高优先级请求始终放行。
+
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::metrics;
+use crate::model::config::{LoadSheddingConfig, Priority};
+
+/// 采样间隔
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 超过阈值多少倍时视为严重压力
+const CRITICAL_FACTOR: f64 = 1.5;
+
+/// 全局降载器
+static SHEDDER: LazyLock<LoadShedder> = LazyLock::new(LoadShedder::default);
+
+/// 获取全局降载器
+pub fn shedder() -> &'static LoadShedder {
+    &SHEDDER
+}
+
+/// 资源压力等级
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Pressure {
+    /// 正常
+    None = 0,
+    /// 超过阈值：拒绝低优先级请求
+    Elevated = 1,
+    /// 超过阈值 [`CRITICAL_FACTOR`] 倍：只放行高优先级请求
+    Critical = 2,
+}
+
+impl Pressure {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Elevated,
+            2 => Self::Critical,
+            _ => Self::None,
+        }
+    }
+}
+
+/// 资源压力降载器
+#[derive(Debug, Default)]
+pub struct LoadShedder {
+    config: Mutex<Option<LoadSheddingConfig>>,
+    pressure: AtomicU8,
+}
+
+impl LoadShedder {
+    /// 设置阈值（None 表示关闭降载）
+    pub fn configure(&self, config: Option<LoadSheddingConfig>) {
+        *self.config.lock() = config;
+        self.pressure.store(Pressure::None as u8, Ordering::Relaxed);
+    }
+
+    /// 当前的资源压力等级
+    pub fn pressure(&self) -> Pressure {
+        Pressure::from_u8(self.pressure.load(Ordering::Relaxed))
+    }
+
+    /// 该优先级的新请求是否应被拒绝
+    pub fn should_shed(&self, priority: Priority) -> bool {
+        match self.pressure() {
+            Pressure::None => false,
+            Pressure::Elevated => priority == Priority::Low,
+            Pressure::Critical => priority != Priority::High,
+        }
+    }
+
+    /// 启动后台采样任务（需在 tokio 运行时内调用一次）
+    pub fn spawn_monitor(&'static self) {
+        tokio::spawn(async move {
+            loop {
+                let started = Instant::now();
+                tokio::time::sleep(SAMPLE_INTERVAL).await;
+                let lag = started.elapsed().saturating_sub(SAMPLE_INTERVAL);
+                self.sample(lag, resident_memory_bytes());
+            }
+        });
+    }
+
+    /// 根据一次采样更新压力等级
+    fn sample(&self, lag: Duration, memory: Option<u64>) {
+        let global = metrics::global();
+        global
+            .event_loop_lag_ms
+            .store(lag.as_millis() as i64, Ordering::Relaxed);
+        if let Some(memory) = memory {
+            global
+                .resident_memory_bytes
+                .store(memory as i64, Ordering::Relaxed);
+        }
+
+        let Some(config) = self.config.lock().clone() else {
+            return;
+        };
+        let lag_ratio = lag.as_millis() as f64 / config.max_event_loop_lag_ms.max(1) as f64;
+        let memory_ratio = match (memory, config.max_memory_mb) {
+            (Some(memory), Some(max)) => memory as f64 / (max.max(1) * 1024 * 1024) as f64,
+            _ => 0.0,
+        };
+        let ratio = lag_ratio.max(memory_ratio);
+        let pressure = if ratio >= CRITICAL_FACTOR {
+            Pressure::Critical
+        } else if ratio >= 1.0 {
+            Pressure::Elevated
+        } else {
+            Pressure::None
+        };
+
+        let previous = Pressure::from_u8(self.pressure.swap(pressure as u8, Ordering::Relaxed));
+        if pressure != previous {
+            if pressure == Pressure::None {
+                tracing::info!("资源压力解除，恢复接收所有请求");
+            } else {
+                tracing::warn!(
+                    "资源压力 {:?}（事件循环延迟 {} ms，常驻内存 {} MB），开始拒绝{}优先级的新请求",
+                    pressure,
+                    lag.as_millis(),
+                    memory.map_or(0, |m| m / 1024 / 1024),
+                    if pressure == Pressure::Critical {
+                        "普通及低"
+                    } else {
+                        "低"
+                    }
+                );
+            }
+        }
+    }
+}
+
+/// 进程常驻内存（读取 `/proc/self/status` 的 `VmRSS`，非 Linux 系统为 None）
+fn resident_memory_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    parse_vm_rss(&status)
+}
+
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kb: u64 = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shedder() -> LoadShedder {
+        let shedder = LoadShedder::default();
+        shedder.configure(Some(LoadSheddingConfig {
+            max_event_loop_lag_ms: 100,
+            max_memory_mb: Some(1024),
+        }));
+        shedder
+    }
+
+    #[test]
+    fn test_sheds_by_priority() {
+        let shedder = shedder();
+        shedder.sample(Duration::from_millis(20), Some(100 << 20));
+        assert_eq!(shedder.pressure(), Pressure::None);
+        assert!(!shedder.should_shed(Priority::Low));
+
+        shedder.sample(Duration::from_millis(120), Some(100 << 20));
+        assert_eq!(shedder.pressure(), Pressure::Elevated);
+        assert!(shedder.should_shed(Priority::Low));
+        assert!(!shedder.should_shed(Priority::Normal));
+
+        // 内存超过阈值 1.5 倍
+        shedder.sample(Duration::ZERO, Some(1600 << 20));
+        assert_eq!(shedder.pressure(), Pressure::Critical);
+        assert!(shedder.should_shed(Priority::Normal));
+        assert!(!shedder.should_shed(Priority::High));
+
+        shedder.sample(Duration::ZERO, Some(100 << 20));
+        assert_eq!(shedder.pressure(), Pressure::None);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let shedder = LoadShedder::default();
+        shedder.sample(Duration::from_secs(10), Some(u64::MAX));
+        assert!(!shedder.should_shed(Priority::Low));
+    }
+
+    #[test]
+    fn test_parse_vm_rss() {
+        let status = "Name:\tkiro-rs\nVmPeak:\t  204800 kB\nVmRSS:\t   51200 kB\nThreads:\t8\n";
+        assert_eq!(parse_vm_rss(status), Some(51200 * 1024));
+        assert_eq!(parse_vm_rss("Name:\tkiro-rs\n"), None);
+    }
+}
@@ -0,0 +1,204 @@
+//! 监听地址
+//!
+//! 支持同时监听多个地址，每个地址按配置启用不同的路由组（如管理端只监听本地回环、
+//! API 对外监听）；IPv6 地址默认以双栈方式监听，同时接受 IPv4 连接。
+//! 地址以 `unix:` 开头时监听 Unix 域套接字，供本机反向代理或 sidecar 访问而不开放网络端口。
+
+use std::net::SocketAddr;
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::TcpListener;
+
+use crate::model::config::ListenerConfig;
+
+/// 监听队列长度
+const BACKLOG: i32 = 1024;
+
+/// 已绑定的监听套接字
+pub enum BoundListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+/// 解析地址并绑定监听套接字
+pub async fn bind(config: &ListenerConfig) -> anyhow::Result<BoundListener> {
+    if let Some(path) = config.unix_path() {
+        return bind_unix(path, config.socket_mode.as_deref());
+    }
+    bind_tcp(config).await.map(BoundListener::Tcp)
+}
+
+async fn bind_tcp(config: &ListenerConfig) -> anyhow::Result<TcpListener> {
+    let addr = tokio::net::lookup_host(&config.address)
+        .await?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("无法解析监听地址: {}", config.address))?;
+    bind_addr(addr, config.dual_stack)
+        .map_err(|e| anyhow::anyhow!("监听 {} 失败: {}", config.address, e))
+}
+
+fn bind_addr(addr: SocketAddr, dual_stack: bool) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(BACKLOG)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// 解析八进制的套接字文件权限，如 `"660"`、`"0660"`
+pub fn parse_socket_mode(mode: &str) -> anyhow::Result<u32> {
+    u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+        .ok()
+        .filter(|mode| *mode <= 0o777)
+        .ok_or_else(|| anyhow::anyhow!("无效的 socketMode: {}（应为八进制权限，如 660）", mode))
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &str, mode: Option<&str>) -> anyhow::Result<BoundListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    let mode = mode.map(parse_socket_mode).transpose()?;
+    // 上次未正常退出时残留的套接字文件：无进程监听时删除，否则视为地址被占用
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            anyhow::bail!("监听 unix:{} 失败: 路径已存在且不是套接字文件", path);
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            anyhow::bail!("监听 unix:{} 失败: 已有进程在该套接字上监听", path);
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)
+        .map_err(|e| anyhow::anyhow!("监听 unix:{} 失败: {}", path, e))?;
+    if let Some(mode) = mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .map_err(|e| anyhow::anyhow!("设置 unix:{} 权限失败: {}", path, e))?;
+    }
+    Ok(BoundListener::Unix(listener))
+}
+
+#[cfg(not(unix))]
+fn bind_unix(path: &str, _mode: Option<&str>) -> anyhow::Result<BoundListener> {
+    anyhow::bail!("当前平台不支持 Unix 域套接字: unix:{}", path)
+}
+
+/// 删除 Unix 域套接字文件（服务停止后调用）
+pub fn cleanup(config: &ListenerConfig) {
+    if let Some(path) = config.unix_path()
+        && let Err(e) = std::fs::remove_file(path)
+    {
+        tracing::warn!("删除套接字文件 {} 失败: {}", path, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::config::RouteGroup;
+
+    fn listener_config(address: &str) -> ListenerConfig {
+        ListenerConfig {
+            address: address.to_string(),
+            routes: RouteGroup::all(),
+            dual_stack: true,
+            tls: None,
+            socket_mode: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bind_ephemeral_port() {
+        let BoundListener::Tcp(listener) = bind(&listener_config("127.0.0.1:0")).await.unwrap()
+        else {
+            panic!("应为 TCP 监听");
+        };
+        assert_ne!(listener.local_addr().unwrap().port(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_bind_invalid_address() {
+        assert!(bind(&listener_config("not an address")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sse_over_h2c() {
+        use axum::routing::get;
+        use bytes::Bytes;
+        use futures::stream;
+
+        let BoundListener::Tcp(listener) = bind(&listener_config("127.0.0.1:0")).await.unwrap()
+        else {
+            panic!("应为 TCP 监听");
+        };
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().route(
+            "/events",
+            get(|| async {
+                crate::anthropic::handlers::sse_response(stream::iter(
+                    [
+                        "event: ping\ndata: {}\n\n",
+                        "event: message_stop\ndata: {}\n\n",
+                    ]
+                    .map(|event| Ok(Bytes::from(event))),
+                ))
+            }),
+        );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::builder()
+            .http2_prior_knowledge()
+            .build()
+            .unwrap();
+        // 同一连接上并发多个流式响应
+        let url = format!("http://{}/events", addr);
+        let responses = futures::future::join_all((0..4).map(|_| client.get(&url).send())).await;
+        for response in responses {
+            let response = response.unwrap();
+            assert_eq!(response.version(), reqwest::Version::HTTP_2);
+            assert_eq!(response.headers()["content-type"], "text/event-stream");
+            assert!(response.headers().get("connection").is_none());
+            let body = response.text().await.unwrap();
+            assert!(body.ends_with("event: message_stop\ndata: {}\n\n"));
+        }
+    }
+
+    #[test]
+    fn test_parse_socket_mode() {
+        assert_eq!(parse_socket_mode("660").unwrap(), 0o660);
+        assert_eq!(parse_socket_mode("0600").unwrap(), 0o600);
+        assert!(parse_socket_mode("999").is_err());
+        assert!(parse_socket_mode("7777").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_bind_unix_socket() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("kiro-sock-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("kiro.sock");
+        let mut config = listener_config(&format!("unix:{}", path.display()));
+        config.socket_mode = Some("600".to_string());
+
+        let listener = bind(&config).await.unwrap();
+        assert!(matches!(listener, BoundListener::Unix(_)));
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // 仍在监听时不能重复绑定
+        assert!(bind(&config).await.is_err());
+
+        // 残留的套接字文件会被替换
+        drop(listener);
+        assert!(bind(&config).await.is_ok());
+
+        cleanup(&config);
+        assert!(!path.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,282 @@
+//! 日志输出初始化
+//!
+//! 支持两种格式：
+//! - `pretty`（默认）：tracing-subscriber 的可读文本格式
+//! - `json`：每行一个 JSON 对象，包含时间、级别、消息、事件字段以及所在 span 的字段
+//!   （如请求 span 的 `request_id`、上游调用 span 的 `credential_id`），便于 Loki / ELK 直接采集
+//!
+//! 两种格式的输出都经过 [`redact`] 脱敏后写出。
+
+use std::fmt;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::span::Record;
+use tracing::{Event, Subscriber};
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::{LookupSpan, Registry};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, reload};
+
+use crate::error_report;
+use crate::model::config::LogFormat;
+use crate::redact::{self, Redacting};
+use crate::telemetry;
+
+/// 日志级别过滤器的热更新句柄
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// 初始化全局日志
+///
+/// 日志级别取配置中的 `logLevel`，未配置时使用 `RUST_LOG`（默认 INFO）
+pub fn init(format: LogFormat, level: Option<&str>) {
+    let (pretty, json) = match format {
+        // 输出重定向到文件（如后台运行）时不输出颜色控制字符
+        LogFormat::Pretty => (
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .with_ansi(std::io::stdout().is_terminal())
+                    .with_writer(Redacting(std::io::stdout)),
+            ),
+            None,
+        ),
+        LogFormat::Json => (
+            None,
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .event_format(JsonFormat)
+                    .fmt_fields(JsonFields)
+                    .with_writer(Redacting(std::io::stdout)),
+            ),
+        ),
+    };
+
+    let filter = match level.map(EnvFilter::try_new) {
+        Some(Ok(filter)) => filter,
+        Some(Err(e)) => {
+            eprintln!("logLevel 无效，使用默认日志级别: {}", e);
+            default_filter()
+        }
+        None => default_filter(),
+    };
+    let (filter, handle) = reload::Layer::new(filter);
+    let _ = FILTER.set(handle);
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(pretty)
+        .with(json)
+        .with(telemetry::OtlpLayer)
+        .with(error_report::ErrorReportLayer)
+        .init();
+}
+
+fn default_filter() -> EnvFilter {
+    EnvFilter::from_default_env().add_directive(tracing::Level::INFO.into())
+}
+
+/// 修改日志级别（`None` 时恢复为 `RUST_LOG` / 默认 INFO）
+pub fn set_level(level: Option<&str>) -> anyhow::Result<()> {
+    let filter = match level {
+        Some(level) => EnvFilter::try_new(level)?,
+        None => default_filter(),
+    };
+    let handle = FILTER
+        .get()
+        .ok_or_else(|| anyhow::anyhow!("日志尚未初始化"))?;
+    handle.reload(filter)?;
+    Ok(())
+}
+
+/// 每行一个 JSON 对象的事件格式
+struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut object = Map::new();
+        object.insert(
+            "timestamp".to_string(),
+            Value::from(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)),
+        );
+        object.insert("level".to_string(), Value::from(metadata.level().as_str()));
+        object.insert("target".to_string(), Value::from(metadata.target()));
+
+        // 外层 span 的字段先写入，内层与事件自身的同名字段覆盖之
+        if let Some(scope) = ctx.event_scope() {
+            let mut leaf = None;
+            for span in scope.from_root() {
+                if let Some(fields) = span.extensions().get::<FormattedFields<N>>()
+                    && let Ok(Value::Object(fields)) = serde_json::from_str(&fields.fields)
+                {
+                    object.extend(fields);
+                }
+                leaf = Some(span.name());
+            }
+            if let Some(name) = leaf {
+                object.insert("span".to_string(), Value::from(name));
+            }
+        }
+
+        event.record(&mut JsonVisitor(&mut object));
+        writeln!(writer, "{}", Value::Object(object))
+    }
+}
+
+/// 将 span 字段格式化为 JSON 对象字符串，供 [`JsonFormat`] 合并
+struct JsonFields;
+
+impl<'writer> FormatFields<'writer> for JsonFields {
+    fn format_fields<R: RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        let mut object = Map::new();
+        fields.record(&mut JsonVisitor(&mut object));
+        write!(writer, "{}", Value::Object(object))
+    }
+
+    fn add_fields(
+        &self,
+        current: &'writer mut FormattedFields<Self>,
+        fields: &Record<'_>,
+    ) -> fmt::Result {
+        let mut object = match serde_json::from_str(&current.fields) {
+            Ok(Value::Object(object)) => object,
+            _ => Map::new(),
+        };
+        fields.record(&mut JsonVisitor(&mut object));
+        current.fields = Value::Object(object).to_string();
+        Ok(())
+    }
+}
+
+/// 将字段写入 JSON 对象（跳过链路追踪专用字段，字符串值经过脱敏）
+pub(crate) struct JsonVisitor<'a>(pub(crate) &'a mut Map<String, Value>);
+
+impl JsonVisitor<'_> {
+    fn insert(&mut self, field: &Field, value: Value) {
+        let name = field.name();
+        if name.starts_with("otel.") || name == "traceparent" {
+            return;
+        }
+        self.0.insert(name.to_string(), value);
+    }
+}
+
+impl Visit for JsonVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, Value::from(redact::scrub(value)));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let value = format!("{:?}", value);
+        self.insert(field, Value::from(redact::scrub(&value)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Buffer {
+        type Writer = Buffer;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn test_json_lines_include_span_fields() {
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .event_format(JsonFormat)
+                .fmt_fields(JsonFields)
+                .with_writer(buffer.clone()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!(
+                "request",
+                request_id = "req_1",
+                otel.kind = "server",
+                latency_ms = tracing::field::Empty
+            );
+            let _request = request.enter();
+            let call = tracing::info_span!("upstream_call", credential_id = 3u64);
+            call.in_scope(|| tracing::warn!(error = "timeout", "上游调用失败"));
+            request.record("latency_ms", 12u64);
+            tracing::info!(status = 200u64, "请求完成");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+
+        assert_eq!(lines[0]["level"], "WARN");
+        assert_eq!(lines[0]["message"], "上游调用失败");
+        assert_eq!(lines[0]["request_id"], "req_1");
+        assert_eq!(lines[0]["credential_id"], 3);
+        assert_eq!(lines[0]["error"], "timeout");
+        assert_eq!(lines[0]["span"], "upstream_call");
+        assert!(lines[0].get("otel.kind").is_none());
+
+        assert_eq!(lines[1]["latency_ms"], 12);
+        assert_eq!(lines[1]["status"], 200);
+        assert!(lines[1].get("credential_id").is_none());
+    }
+}
@@ -1,11 +1,34 @@
 mod admin;
 mod admin_ui;
 mod anthropic;
+mod batch;
+mod bench;
+mod cli;
 mod common;
+mod daemon;
+mod database;
+mod discord;
+mod error_report;
 mod http_client;
 mod kiro;
+mod limit;
+mod listener;
+mod logging;
+mod metrics;
 mod model;
+mod openai;
+mod redact;
+mod reload;
+mod replay;
+mod request_log;
+mod service;
+mod systemd;
+mod telegram;
+mod telemetry;
+mod timing;
+mod tls;
 pub mod token;
+mod usage;
 
 use std::sync::Arc;
 
@@ -13,40 +36,171 @@ use clap::Parser;
 use kiro::model::credentials::{CredentialsConfig, KiroCredentials};
 use kiro::provider::KiroProvider;
 use kiro::token_manager::MultiTokenManager;
-use model::arg::Args;
-use model::config::Config;
+use model::arg::{Args, Command, ServiceAction};
+use model::config::{Config, RouteGroup};
+use model::validation;
 
-#[tokio::main]
-async fn main() {
+fn main() {
     // 解析命令行参数
     let args = Args::parse();
 
-    // 初始化日志
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive(tracing::Level::INFO.into()),
-        )
-        .init();
+    // fork 必须在启动 tokio 运行时（创建线程）之前进行
+    if args.daemon
+        && args.is_serve()
+        && let Err(e) = daemon::daemonize(&args.log_file)
+    {
+        eprintln!("后台运行失败: {}", e);
+        std::process::exit(1);
+    }
+    let pid_file = match &args.pid_file {
+        Some(path) if args.is_serve() => Some(daemon::PidFile::create(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })),
+        _ => None,
+    };
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("创建 tokio 运行时失败")
+        .block_on(run(args));
+    drop(pid_file);
+}
 
-    // 加载配置
+async fn run(mut args: Args) {
+    // 加载配置（日志格式取决于配置，加载失败的错误在初始化日志后输出）
     let config_path = args
         .config
+        .clone()
         .unwrap_or_else(|| Config::default_config_path().to_string());
-    let config = Config::load(&config_path).unwrap_or_else(|e| {
+
+    // init 与 encrypt-value 在加载配置之前执行，不要求已有配置有效
+    if let Some(Command::Init { force }) = args.command {
+        let credentials_path = args
+            .credentials
+            .clone()
+            .unwrap_or_else(|| KiroCredentials::default_credentials_path().to_string());
+        if let Err(e) = cli::init(&config_path, &credentials_path, force) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::EncryptValue { value }) = args.command.clone() {
+        if let Err(e) = cli::encrypt_value(value) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(profile) = args.profile.clone() {
+        model::config::select_profile(profile);
+    }
+    let config = Config::load_checked(&config_path);
+
+    // 初始化日志
+    let log_format = args
+        .log_format
+        .or(config.as_ref().ok().map(|(c, _)| c.log_format))
+        .unwrap_or_default();
+    logging::init(
+        log_format,
+        config
+            .as_ref()
+            .ok()
+            .and_then(|(c, _)| c.log_level.as_deref()),
+    );
+
+    let (mut config, warnings) = config.unwrap_or_else(|e| {
         tracing::error!("加载配置失败: {}", e);
         std::process::exit(1);
     });
+    args.apply_overrides(&mut config);
+    redact::register_config(&config);
+
+    if let Err(e) = http_client::init(&config) {
+        tracing::error!("加载上游连接配置失败: {}", e);
+        std::process::exit(1);
+    }
+
+    // 运维子命令执行后直接退出
+    let command = args.command.take();
+    if let Some(Command::Service {
+        action: ServiceAction::Run { name },
+    }) = &command
+    {
+        let stop_timeout = std::time::Duration::from_secs(
+            config.drain_delay_secs + config.shutdown_grace_period_secs,
+        );
+        if let Err(e) = service::start(name, stop_timeout) {
+            tracing::error!("启动服务失败: {}", e);
+            std::process::exit(1);
+        }
+    } else if let Some(command) = command.filter(|c| !matches!(c, Command::Serve)) {
+        let credentials_path = args
+            .credentials
+            .clone()
+            .unwrap_or_else(|| KiroCredentials::default_credentials_path().to_string());
+        if let Err(e) = cli::run(command, config, &config_path, &credentials_path).await {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // 一次性报告全部配置问题，存在错误时拒绝启动
+    let problems: Vec<_> = warnings
+        .into_iter()
+        .chain(validation::check_config(&config))
+        .collect();
+    for problem in &problems {
+        if problem.is_error() {
+            tracing::error!("配置错误: {}", problem);
+        } else {
+            tracing::warn!("配置警告: {}", problem);
+        }
+    }
+    let errors = problems.iter().filter(|p| p.is_error()).count();
+    if errors > 0 {
+        tracing::error!("配置存在 {} 个错误，请修改后重新启动", errors);
+        std::process::exit(1);
+    }
+    if let Some(profile) = &config.profile {
+        tracing::info!("使用配置 profile: {}", profile);
+    }
+
+    telemetry::init(&config);
+    error_report::init(&config);
+    metrics::alerts().configure(config.alerts.clone());
+    kiro::circuit::breaker().configure(config.circuit_breaker.clone());
+    limit::adaptive().configure(config.adaptive_concurrency.clone());
+    kiro::retry_budget::budget().configure(config.retry_budget.clone());
+    kiro::webhook::notifier().configure(config.credential_webhook.clone());
+    telegram::configure(config.telegram.clone());
+    telegram::spawn_alert_forwarder();
+    discord::configure(config.discord.clone());
+    discord::spawn_alert_forwarder();
+    limit::shedder().configure(config.load_shedding.clone());
+    limit::shedder().spawn_monitor();
+    timing::init(config.slow_request_threshold_ms);
+    common::client_ip::init(&config.trusted_proxies);
 
     // 加载凭证（支持单对象或数组格式）
     let credentials_path = args
         .credentials
+        .clone()
         .unwrap_or_else(|| KiroCredentials::default_credentials_path().to_string());
     let credentials_config = CredentialsConfig::load(&credentials_path).unwrap_or_else(|e| {
         tracing::error!("加载凭证失败: {}", e);
         std::process::exit(1);
     });
 
+    // 凭据问题只记录警告：无效的凭据会在使用时被禁用，也可以稍后通过 Admin API 补充
+    for problem in validation::check_credentials(&credentials_config) {
+        tracing::warn!("凭据问题: {}", problem);
+    }
+
     // 判断是否为多凭据格式（用于刷新后回写）
     let is_multiple_format = credentials_config.is_multiple();
 
@@ -65,24 +219,18 @@ async fn main() {
     });
 
     // 构建代理配置
-    let proxy_config = config.proxy_url.as_ref().map(|url| {
-        let mut proxy = http_client::ProxyConfig::new(url);
-        if let (Some(username), Some(password)) = (&config.proxy_username, &config.proxy_password) {
-            proxy = proxy.with_auth(username, password);
-        }
-        proxy
-    });
-
-    if proxy_config.is_some() {
-        tracing::info!("已配置 HTTP 代理: {}", config.proxy_url.as_ref().unwrap());
-    }
+    let proxy_config = http_client::ProxyConfig::from_config(&config);
+    tracing::info!(
+        "上游代理: {}",
+        http_client::describe_proxy(proxy_config.as_ref())
+    );
 
     // 创建 MultiTokenManager 和 KiroProvider
     let token_manager = MultiTokenManager::new(
         config.clone(),
         credentials_list,
         proxy_config.clone(),
-        Some(credentials_path.into()),
+        Some(credentials_path.clone().into()),
         is_multiple_format,
     )
     .unwrap_or_else(|e| {
@@ -100,63 +248,387 @@ async fn main() {
         proxy: proxy_config,
     });
 
+    // 打开嵌入式数据库（执行表结构迁移）
+    let database = config.database_path.as_ref().map(|path| {
+        let database = database::Database::open(path).unwrap_or_else(|e| {
+            tracing::error!("打开数据库失败: {}", e);
+            std::process::exit(1);
+        });
+        tracing::info!("数据库: {}", path);
+        Arc::new(database)
+    });
+    request_log::init(&config, database.as_ref());
+
+    // 初始化用量记录存储
+    let usage_store = match (&database, &config.usage_log_path) {
+        (Some(database), _) => {
+            let store = usage::UsageStore::with_database(
+                database.clone(),
+                anthropic::DEFAULT_USAGE_CAPACITY,
+            )
+            .unwrap_or_else(|e| {
+                tracing::error!("从数据库恢复用量记录失败: {}", e);
+                std::process::exit(1);
+            });
+            tracing::info!("用量记录持久化到数据库（已恢复 {} 条）", store.len());
+            store
+        }
+        (None, Some(path)) => {
+            let store = usage::UsageStore::open(path, anthropic::DEFAULT_USAGE_CAPACITY)
+                .unwrap_or_else(|e| {
+                    tracing::error!("打开用量记录文件失败: {}", e);
+                    std::process::exit(1);
+                });
+            tracing::info!("用量记录持久化到: {}（已恢复 {} 条）", path, store.len());
+            store
+        }
+        (None, None) => usage::UsageStore::in_memory(anthropic::DEFAULT_USAGE_CAPACITY),
+    };
+    let usage_store = match &config.access_log {
+        Some(access_log) => {
+            let log = usage::AccessLog::open(access_log).unwrap_or_else(|e| {
+                tracing::error!("打开访问日志失败: {}", e);
+                std::process::exit(1);
+            });
+            tracing::info!("访问日志写入: {}", access_log.path);
+            usage_store.with_access_log(log)
+        }
+        None => usage_store,
+    };
+    let usage_store = Arc::new(usage_store);
+    // 启动每日用量报告
+    let report_store = config.usage_report.as_ref().map(|report| {
+        let reports = usage::report::ReportStore::open(report.dir.as_deref()).unwrap_or_else(|e| {
+            tracing::error!("打开用量报告目录失败: {}", e);
+            std::process::exit(1);
+        });
+        let reports = Arc::new(reports);
+        usage::report::spawn_daily(report.clone(), usage_store.clone(), reports.clone());
+        reports
+    });
+
     // 构建 Anthropic API 路由（从第一个凭据获取 profile_arn）
-    let anthropic_app = anthropic::create_router_with_provider(
+    let app_state = anthropic::create_app_state(
         &api_key,
         Some(kiro_provider),
         first_credentials.profile_arn.clone(),
+        config.clone(),
+        usage_store.clone(),
     );
 
-    // 构建 Admin API 路由（如果配置了非空的 admin_api_key）
+    // 每小时清理过期的用量记录、请求日志与批处理任务
+    if config.usage_retention_days.is_some() || config.batch_retention_days.is_some() {
+        let usage_retention_days = config.usage_retention_days;
+        let batch_retention_days = config.batch_retention_days;
+        let store = usage_store.clone();
+        let database = database.clone();
+        let batches = app_state.batches.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+            loop {
+                interval.tick().await;
+                if let Some(days) = usage_retention_days {
+                    let before = chrono::Utc::now() - chrono::Duration::days(days as i64);
+                    match store.prune(before) {
+                        Ok(0) => {}
+                        Ok(removed) => tracing::info!("已清理 {} 条过期用量记录", removed),
+                        Err(e) => tracing::warn!("清理过期用量记录失败: {}", e),
+                    }
+                    match database.as_ref().map(|db| db.prune_request_logs(before)) {
+                        None | Some(Ok(0)) => {}
+                        Some(Ok(removed)) => tracing::info!("已清理 {} 条过期请求日志", removed),
+                        Some(Err(e)) => tracing::warn!("清理过期请求日志失败: {}", e),
+                    }
+                }
+                if let Some(days) = batch_retention_days {
+                    let before = chrono::Utc::now() - chrono::Duration::days(days as i64);
+                    match batches.prune(before.timestamp()) {
+                        0 => {}
+                        removed => tracing::info!("已清理 {} 个过期批处理任务与文件", removed),
+                    }
+                }
+            }
+        });
+    }
+
+    // 收到 SIGHUP 时重新加载配置与凭证文件
+    let reloader = Arc::new(reload::Reloader::new(
+        config_path,
+        credentials_path,
+        args,
+        app_state.clone(),
+        token_manager.clone(),
+    ));
+    reloader.clone().spawn_on_sighup();
+
+    // 构建 Admin API 路由（如果配置了非空的 admin_api_key 或 OIDC 单点登录）
     // 安全检查：空字符串被视为未配置，防止空 key 绕过认证
     let admin_key_valid = config
         .admin_api_key
         .as_ref()
         .map(|k| !k.trim().is_empty())
         .unwrap_or(false);
+    let admin_enabled = admin_key_valid || config.admin_oidc.is_some();
 
-    let app = if let Some(admin_key) = &config.admin_api_key {
-        if admin_key.trim().is_empty() {
-            tracing::warn!("admin_api_key 配置为空，Admin API 未启用");
-            anthropic_app
-        } else {
-            let admin_service = admin::AdminService::new(token_manager.clone());
-            let admin_state = admin::AdminState::new(admin_key, admin_service);
-            let admin_app = admin::create_admin_router(admin_state);
+    let admin_app = if admin_enabled {
+        let admin_key = config.admin_api_key.clone().unwrap_or_default();
+        let mut admin_service =
+            admin::AdminService::new(token_manager.clone(), usage_store.clone())
+                .with_reloader(reloader.clone());
+        if let Some(reports) = &report_store {
+            admin_service = admin_service.with_reports(reports.clone());
+        }
+        let mut admin_state = admin::AdminState::new(admin_key, admin_service)
+            .with_config(app_state.config.clone())
+            .with_auth_lockout(app_state.auth_lockout.clone());
+        if let Some(audit_log) = &config.audit_log {
+            let log = admin::AuditLog::open(audit_log).unwrap_or_else(|e| {
+                tracing::error!("打开审计日志失败: {}", e);
+                std::process::exit(1);
+            });
+            tracing::info!("Admin 审计日志写入: {}", audit_log.path);
+            let log = match &database {
+                Some(database) => log.with_database(database.clone()),
+                None => log,
+            };
+            admin_state = admin_state.with_audit_log(log);
+        }
+        if let Some(oidc) = &config.admin_oidc {
+            let login =
+                admin::OidcLogin::new(oidc.clone(), config.public_prefix()).unwrap_or_else(|e| {
+                    tracing::error!("初始化 OIDC 单点登录失败: {}", e);
+                    std::process::exit(1);
+                });
+            tracing::info!("Admin UI 单点登录已启用: {}", oidc.issuer);
+            admin_state = admin_state.with_oidc(login);
+        }
+        let admin_app = admin::create_admin_router(admin_state);
 
-            // 创建 Admin UI 路由
-            let base_path = config.base_path.clone().unwrap_or_default();
-            let admin_ui_app = admin_ui::create_admin_ui_router(base_path);
+        // 创建 Admin UI 路由（前端按外部访问前缀请求 API 与静态资源）
+        let admin_ui_app =
+            admin_ui::create_admin_ui_router(config.public_prefix(), config.admin_oidc.is_some());
 
-            tracing::info!("Admin API 已启用");
-            tracing::info!("Admin UI 已启用: /admin");
-            anthropic_app
+        tracing::info!("Admin API 已启用");
+        tracing::info!("Admin UI 已启用: {}/admin", config.route_prefix());
+        // 未配置 cors 时 Admin API 不允许跨域访问
+        let admin_app = match &config.cors {
+            Some(cors) => admin_app.layer(anthropic::cors_layer(Some(cors))),
+            None => admin_app,
+        };
+        Some(
+            axum::Router::new()
                 .nest("/api/admin", admin_app)
-                .nest("/admin", admin_ui_app)
-        }
+                .nest("/admin", admin_ui_app),
+        )
     } else {
-        anthropic_app
+        if config.admin_api_key.is_some() {
+            tracing::warn!("admin_api_key 配置为空，Admin API 未启用");
+        }
+        None
     };
 
     // 启动服务器
-    let addr = format!("{}:{}", config.host, config.port);
-    tracing::info!("启动 Anthropic API 端点: {}", addr);
-    tracing::info!("API Key: {}***", &api_key[..(api_key.len() / 2)]);
+    let prefix = config.route_prefix();
+    if common::auth::is_hashed(&api_key) {
+        tracing::info!("API Key: 已哈希存储");
+    } else {
+        tracing::info!("API Key: {}***", &api_key[..(api_key.len() / 2)]);
+    }
     tracing::info!("可用 API:");
-    tracing::info!("  GET  /v1/models");
-    tracing::info!("  POST /v1/messages");
-    tracing::info!("  POST /v1/messages/count_tokens");
-    if admin_key_valid {
+    tracing::info!("  GET  {}/v1/models", prefix);
+    tracing::info!("  POST {}/v1/messages", prefix);
+    tracing::info!("  POST {}/v1/messages/count_tokens", prefix);
+    tracing::info!("  POST {}/v1/chat/completions", prefix);
+    tracing::info!("  GET  {}/metrics", prefix);
+    if admin_enabled {
         tracing::info!("Admin API:");
-        tracing::info!("  GET  /api/admin/credentials");
-        tracing::info!("  POST /api/admin/credentials/:index/disabled");
-        tracing::info!("  POST /api/admin/credentials/:index/priority");
-        tracing::info!("  POST /api/admin/credentials/:index/reset");
-        tracing::info!("  GET  /api/admin/credentials/:index/balance");
+        tracing::info!("  GET  {}/api/admin/credentials", prefix);
+        tracing::info!("  POST {}/api/admin/credentials/:index/disabled", prefix);
+        tracing::info!("  POST {}/api/admin/credentials/:index/priority", prefix);
+        tracing::info!("  POST {}/api/admin/credentials/:index/reset", prefix);
+        tracing::info!("  GET  {}/api/admin/credentials/:index/balance", prefix);
+        tracing::info!("  GET  {}/api/admin/usage", prefix);
         tracing::info!("Admin UI:");
-        tracing::info!("  GET  /admin");
+        tracing::info!("  GET  {}/admin", prefix);
+    }
+
+    // 所有监听地址共享同一个停止信号
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let drain_delay_secs = config.drain_delay_secs;
+    tokio::spawn(async move {
+        shutdown_signal(drain_delay_secs).await;
+        let _ = shutdown_tx.send(true);
+    });
+
+    // 预热完成前 /readyz 返回 503，存活检查照常响应
+    let warm_up = config.warm_up_concurrency > 0;
+    anthropic::set_warming_up(warm_up);
+
+    let listener_configs = config.effective_listeners();
+    let mut servers = Vec::new();
+    for listener_config in &listener_configs {
+        let mut app = anthropic::create_router(app_state.clone(), &listener_config.routes);
+        if listener_config.routes.contains(&RouteGroup::Admin)
+            && let Some(admin_app) = &admin_app
+        {
+            app = app.merge(admin_app.clone());
+        }
+        if !prefix.is_empty() {
+            app = axum::Router::new().nest(&prefix, app);
+        }
+        let listener = listener::bind(listener_config).await.unwrap_or_else(|e| {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        });
+        tracing::info!(
+            "监听 {}{}，路由组: {:?}",
+            listener_config.address,
+            if listener_config.tls.is_some() {
+                "（TLS）"
+            } else {
+                ""
+            },
+            listener_config.routes
+        );
+
+        let server = match (listener, &listener_config.tls) {
+            (listener::BoundListener::Tcp(listener), Some(tls_config)) => {
+                let server_config = tls::load_server_config(tls_config).unwrap_or_else(|e| {
+                    tracing::error!("加载 TLS 证书失败: {}", e);
+                    std::process::exit(1);
+                });
+                let server_config = common::live::Live::new(server_config);
+                tls::watch(tls_config.clone(), server_config.clone());
+                let listener = tls::TlsListener::new(listener, server_config).unwrap_or_else(|e| {
+                    tracing::error!("启动 TLS 监听失败: {}", e);
+                    std::process::exit(1);
+                });
+                spawn_server(listener, app, shutdown_rx.clone())
+            }
+            (listener::BoundListener::Tcp(listener), None) => {
+                spawn_server(listener, app, shutdown_rx.clone())
+            }
+            #[cfg(unix)]
+            (listener::BoundListener::Unix(listener), tls_config) => {
+                if tls_config.is_some() {
+                    tracing::warn!(
+                        "Unix 域套接字 {} 不支持 TLS，已忽略 tls 配置",
+                        listener_config.address
+                    );
+                }
+                spawn_server(listener, app, shutdown_rx.clone())
+            }
+        };
+        servers.push(server);
+    }
+    if warm_up {
+        let started = std::time::Instant::now();
+        let summary = token_manager.warm_up(config.warm_up_concurrency).await;
+        tracing::info!(
+            "凭据预热完成（耗时 {} ms）：{} 个可用，其中 {} 个额度已用尽；{} 个失败",
+            started.elapsed().as_millis(),
+            summary.ready,
+            summary.exhausted,
+            summary.failed
+        );
+        anthropic::set_warming_up(false);
+    }
+    systemd::ready();
+    systemd::spawn_watchdog();
+
+    // 停止接受新连接后最多等待宽限期，仍未完成的请求（如长时间的流式生成）随进程退出而中断
+    let grace_period = std::time::Duration::from_secs(config.shutdown_grace_period_secs);
+    let mut stopping = shutdown_rx.clone();
+    let grace_elapsed = async move {
+        let _ = stopping.wait_for(|stopping| *stopping).await;
+        tokio::time::sleep(grace_period).await;
+    };
+    tokio::select! {
+        results = futures::future::join_all(servers) => {
+            for server in results {
+                if let Ok(Err(e)) = server {
+                    tracing::error!("服务异常退出: {}", e);
+                }
+            }
+        }
+        _ = grace_elapsed => {
+            tracing::warn!(
+                "等待 {} 秒后仍有 {} 个请求未完成，强制退出",
+                grace_period.as_secs(),
+                metrics::global()
+                    .in_flight_requests
+                    .load(std::sync::atomic::Ordering::Relaxed)
+            );
+        }
+    }
+
+    if let Err(e) = usage_store.flush() {
+        tracing::warn!("同步用量记录失败: {}", e);
+    }
+    request_log::flush();
+    listener_configs.iter().for_each(listener::cleanup);
+    tracing::info!("服务已停止");
+    service::stopped();
+}
+
+/// 在后台运行 HTTP 服务，收到停止信号后停止接受新连接并等待进行中的请求完成
+fn spawn_server<L>(
+    listener: L,
+    app: axum::Router,
+    mut shutdown_rx: tokio::sync::watch::Receiver<bool>,
+) -> tokio::task::JoinHandle<std::io::Result<()>>
+where
+    L: axum::serve::Listener,
+    L::Addr: std::fmt::Debug,
+    for<'a> common::client_ip::PeerAddr:
+        axum::extract::connect_info::Connected<axum::serve::IncomingStream<'a, L>>,
+{
+    use common::client_ip::{PeerAddr, middleware};
+
+    let app = app.layer(axum::middleware::from_fn(middleware));
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<PeerAddr>(),
+        )
+        .with_graceful_shutdown(async move {
+            let _ = shutdown_rx.wait_for(|stopping| *stopping).await;
+        })
+        .await
+    })
+}
+
+/// 等待 SIGINT / SIGTERM，随后将实例标记为排空中（`/readyz` 返回 503），
+/// 再等待 `drain_delay_secs` 秒让负载均衡器摘除实例，之后停止接受新连接，
+/// 由调用方在 `shutdownGracePeriodSecs` 内等待进行中的请求完成
+async fn shutdown_signal(drain_delay_secs: u64) {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("无法监听 SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+        _ = service::stop_requested() => {}
     }
 
-    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    anthropic::start_draining();
+    systemd::stopping();
+    tracing::info!("收到停止信号，{} 秒后停止接受新连接", drain_delay_secs);
+    tokio::time::sleep(std::time::Duration::from_secs(drain_delay_secs)).await;
+    tracing::info!("停止接受新连接，等待进行中的请求完成");
 }
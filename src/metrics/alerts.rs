@@ -0,0 +1,313 @@
+//! 错误率告警
+//!
+//! 按全局（已结束请求的状态码）与单个凭据（每次上游调用的结果）分别统计滚动窗口内的错误率，
+//! 超过阈值且样本数足够时触发告警，回落到阈值以下（或窗口内样本不足）时解除。
+//! 告警状态通过 Admin API 查询，触发与解除事件通过广播推送给 Admin 事件流。
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::model::config::AlertConfig;
+
+/// 统计桶的时长（秒）
+const BUCKET_SECS: u64 = 10;
+
+/// 事件广播的缓冲区大小
+const EVENT_CAPACITY: usize = 64;
+
+/// 告警范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(tag = "scope", rename_all = "camelCase")]
+pub enum AlertScope {
+    /// 所有请求
+    Global,
+    /// 单个凭据的上游调用
+    #[serde(rename_all = "camelCase")]
+    Credential { credential_id: u64 },
+}
+
+/// 处于触发状态的告警
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    #[serde(flatten)]
+    pub scope: AlertScope,
+    /// 窗口内的错误率
+    pub error_rate: f64,
+    /// 窗口内的请求数
+    pub requests: u64,
+    /// 窗口内的错误数
+    pub errors: u64,
+    /// 告警阈值
+    pub threshold: f64,
+    /// 触发时间
+    pub since: DateTime<Utc>,
+}
+
+/// 告警事件
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertEvent {
+    /// `alert_fired` 或 `alert_resolved`
+    pub kind: &'static str,
+    pub alert: Alert,
+}
+
+/// 滚动窗口内按时间分桶的请求数与错误数（上游熔断同样使用）
+#[derive(Debug, Default)]
+pub(crate) struct Window(VecDeque<(u64, u64, u64)>);
+
+impl Window {
+    pub(crate) fn record(&mut self, now: u64, error: bool) {
+        let start = now - now % BUCKET_SECS;
+        match self.0.back_mut() {
+            Some((bucket, total, errors)) if *bucket == start => {
+                *total += 1;
+                *errors += error as u64;
+            }
+            _ => self.0.push_back((start, 1, error as u64)),
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// 移除窗口外的桶，返回 (请求数, 错误数)
+    pub(crate) fn totals(&mut self, now: u64, window_secs: u64) -> (u64, u64) {
+        while self
+            .0
+            .front()
+            .is_some_and(|(start, _, _)| start + BUCKET_SECS + window_secs <= now)
+        {
+            self.0.pop_front();
+        }
+        self.0
+            .iter()
+            .fold((0, 0), |(total, errors), (_, t, e)| (total + t, errors + e))
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    config: Option<AlertConfig>,
+    windows: BTreeMap<AlertScope, Window>,
+    active: BTreeMap<AlertScope, Alert>,
+}
+
+/// 错误率告警监视器
+#[derive(Debug)]
+pub struct AlertMonitor {
+    started: Instant,
+    state: Mutex<State>,
+    events: broadcast::Sender<AlertEvent>,
+}
+
+impl Default for AlertMonitor {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            state: Mutex::new(State::default()),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+}
+
+impl AlertMonitor {
+    /// 设置告警阈值（None 表示关闭告警）
+    pub fn configure(&self, config: Option<AlertConfig>) {
+        let mut state = self.state.lock();
+        state.config = config;
+        state.windows.clear();
+        state.active.clear();
+    }
+
+    /// 记录一个已结束的请求（5xx 与 429 计为错误，客户端断开不计入）
+    pub fn record_request(&self, status: u16) {
+        if status == 499 {
+            return;
+        }
+        let error = status >= 500 || status == 429;
+        self.record_at(AlertScope::Global, error, self.now());
+    }
+
+    /// 记录一次上游调用（网络错误、5xx、408、429 与凭据相关的 401/402/403 计为错误）
+    pub fn record_upstream(&self, credential_id: u64, status: Option<u16>) {
+        let error = status.is_none_or(|s| s >= 500 || matches!(s, 401 | 402 | 403 | 408 | 429));
+        self.record_at(AlertScope::Credential { credential_id }, error, self.now());
+    }
+
+    /// 当前处于触发状态的告警（先按当前时间重新评估）
+    pub fn active_alerts(&self) -> Vec<Alert> {
+        let now = self.now();
+        let mut state = self.state.lock();
+        let scopes: Vec<AlertScope> = state.windows.keys().copied().collect();
+        for scope in scopes {
+            self.evaluate(&mut state, scope, now);
+        }
+        state.active.values().cloned().collect()
+    }
+
+    /// 订阅告警事件
+    pub fn subscribe(&self) -> broadcast::Receiver<AlertEvent> {
+        self.events.subscribe()
+    }
+
+    fn now(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+
+    fn record_at(&self, scope: AlertScope, error: bool, now: u64) {
+        let mut state = self.state.lock();
+        if state.config.is_none() {
+            return;
+        }
+        state.windows.entry(scope).or_default().record(now, error);
+        self.evaluate(&mut state, scope, now);
+    }
+
+    fn evaluate(&self, state: &mut State, scope: AlertScope, now: u64) {
+        let Some(config) = state.config.clone() else {
+            return;
+        };
+        let Some(window) = state.windows.get_mut(&scope) else {
+            return;
+        };
+        let (requests, errors) = window.totals(now, config.window_secs);
+        if requests == 0 {
+            state.windows.remove(&scope);
+        }
+        let error_rate = if requests == 0 {
+            0.0
+        } else {
+            errors as f64 / requests as f64
+        };
+        let breached = requests >= config.min_requests && error_rate >= config.error_rate_threshold;
+
+        match (breached, state.active.get_mut(&scope)) {
+            (true, Some(alert)) => {
+                alert.error_rate = error_rate;
+                alert.requests = requests;
+                alert.errors = errors;
+            }
+            (true, None) => {
+                let alert = Alert {
+                    scope,
+                    error_rate,
+                    requests,
+                    errors,
+                    threshold: config.error_rate_threshold,
+                    since: Utc::now(),
+                };
+                tracing::warn!(
+                    "错误率告警触发: {:?} 错误率 {:.1}%（{}/{}）",
+                    scope,
+                    error_rate * 100.0,
+                    errors,
+                    requests
+                );
+                state.active.insert(scope, alert.clone());
+                let _ = self.events.send(AlertEvent {
+                    kind: "alert_fired",
+                    alert,
+                });
+            }
+            (false, Some(_)) => {
+                let mut alert = state.active.remove(&scope).expect("alert is active");
+                alert.error_rate = error_rate;
+                alert.requests = requests;
+                alert.errors = errors;
+                tracing::info!(
+                    "错误率告警解除: {:?} 错误率 {:.1}%",
+                    scope,
+                    error_rate * 100.0
+                );
+                let _ = self.events.send(AlertEvent {
+                    kind: "alert_resolved",
+                    alert,
+                });
+            }
+            (false, None) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor() -> AlertMonitor {
+        let monitor = AlertMonitor::default();
+        monitor.configure(Some(AlertConfig {
+            error_rate_threshold: 0.5,
+            window_secs: 60,
+            min_requests: 4,
+        }));
+        monitor
+    }
+
+    #[test]
+    fn test_fires_after_min_requests_and_resolves() {
+        let monitor = monitor();
+        let mut events = monitor.subscribe();
+        let scope = AlertScope::Credential { credential_id: 3 };
+
+        for _ in 0..3 {
+            monitor.record_at(scope, true, 0);
+        }
+        assert!(monitor.active_alerts().is_empty());
+
+        monitor.record_at(scope, false, 1);
+        let alerts = monitor.active_alerts();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].errors, 3);
+        assert_eq!(events.try_recv().unwrap().kind, "alert_fired");
+
+        for _ in 0..4 {
+            monitor.record_at(scope, false, 2);
+        }
+        assert_eq!(events.try_recv().unwrap().kind, "alert_resolved");
+    }
+
+    #[test]
+    fn test_old_buckets_leave_the_window() {
+        let monitor = monitor();
+        for _ in 0..4 {
+            monitor.record_at(AlertScope::Global, true, 0);
+        }
+        assert_eq!(monitor.state.lock().active.len(), 1);
+
+        // 窗口外的错误不再计入，样本不足时解除告警
+        monitor.record_at(AlertScope::Global, false, 200);
+        assert!(monitor.state.lock().active.is_empty());
+    }
+
+    #[test]
+    fn test_disabled_without_config() {
+        let monitor = AlertMonitor::default();
+        monitor.record_request(500);
+        assert!(monitor.active_alerts().is_empty());
+        assert!(monitor.state.lock().windows.is_empty());
+    }
+
+    #[test]
+    fn test_alert_serializes_scope() {
+        let alert = Alert {
+            scope: AlertScope::Credential { credential_id: 2 },
+            error_rate: 0.5,
+            requests: 10,
+            errors: 5,
+            threshold: 0.25,
+            since: Utc::now(),
+        };
+        let value = serde_json::to_value(&alert).unwrap();
+        assert_eq!(value["scope"], "credential");
+        assert_eq!(value["credentialId"], 2);
+        assert_eq!(value["errorRate"], 0.5);
+    }
+}
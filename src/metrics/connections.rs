@@ -0,0 +1,245 @@
+//! 上游连接复用统计
+//!
+//! Kiro API 客户端的连接器层在每次新建连接（TCP、代理隧道与 TLS 握手）时记录耗时，
+//! 生成请求在发送前各记录一次；请求数与新建连接数之差即复用连接池中已有连接的请求数。
+//! 凭据切换到其他区域、连接池空闲超时等情况会引起集中握手，可通过最近一分钟的统计观察。
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use super::write_metric;
+
+/// 最近统计窗口的时长（秒）
+const RECENT_WINDOW_SECS: u64 = 60;
+
+/// 一段时间内的连接统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionCounts {
+    /// 发送的上游请求数
+    pub requests: u64,
+    /// 新建的连接数
+    pub new_connections: u64,
+    /// 复用已有连接的请求数
+    pub reused_requests: u64,
+    /// 复用已有连接的请求占比（没有请求时为 None）
+    pub reuse_ratio: Option<f64>,
+    /// 建立连接失败的次数
+    pub connect_failures: u64,
+    /// 新建连接的平均耗时（毫秒，没有新建连接时为 None）
+    pub avg_connect_ms: Option<f64>,
+}
+
+/// 按秒分桶的计数
+#[derive(Debug, Clone, Copy, Default)]
+struct Bucket {
+    second: u64,
+    requests: u64,
+    connections: u64,
+    failures: u64,
+    connect_micros: u64,
+}
+
+impl Bucket {
+    fn counts(&self) -> ConnectionCounts {
+        let reused_requests = self.requests.saturating_sub(self.connections);
+        ConnectionCounts {
+            requests: self.requests,
+            new_connections: self.connections,
+            reused_requests,
+            reuse_ratio: (self.requests > 0).then(|| reused_requests as f64 / self.requests as f64),
+            connect_failures: self.failures,
+            avg_connect_ms: (self.connections > 0)
+                .then(|| self.connect_micros as f64 / self.connections as f64 / 1000.0),
+        }
+    }
+}
+
+/// 上游连接统计
+#[derive(Debug)]
+pub struct ConnectionStats {
+    started: Instant,
+    requests: AtomicU64,
+    connections: AtomicU64,
+    failures: AtomicU64,
+    connect_micros: AtomicU64,
+    recent: Mutex<VecDeque<Bucket>>,
+}
+
+impl Default for ConnectionStats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: AtomicU64::new(0),
+            connections: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            connect_micros: AtomicU64::new(0),
+            recent: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+impl ConnectionStats {
+    /// 记录一次发送的上游请求
+    pub fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.bump(self.now(), |bucket| bucket.requests += 1);
+    }
+
+    /// 记录一次新建连接的结果与耗时
+    pub fn record_connect(&self, ok: bool, elapsed: Duration) {
+        let micros = elapsed.as_micros() as u64;
+        if ok {
+            self.connections.fetch_add(1, Ordering::Relaxed);
+            self.connect_micros.fetch_add(micros, Ordering::Relaxed);
+        } else {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        self.bump(self.now(), |bucket| {
+            if ok {
+                bucket.connections += 1;
+                bucket.connect_micros += micros;
+            } else {
+                bucket.failures += 1;
+            }
+        });
+    }
+
+    /// 自启动以来的统计
+    pub fn total(&self) -> ConnectionCounts {
+        Bucket {
+            second: 0,
+            requests: self.requests.load(Ordering::Relaxed),
+            connections: self.connections.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            connect_micros: self.connect_micros.load(Ordering::Relaxed),
+        }
+        .counts()
+    }
+
+    /// 最近一分钟的统计
+    pub fn recent(&self) -> ConnectionCounts {
+        self.recent_at(self.now())
+    }
+
+    fn recent_at(&self, now: u64) -> ConnectionCounts {
+        let mut recent = self.recent.lock();
+        prune(&mut recent, now);
+        recent
+            .iter()
+            .fold(Bucket::default(), |sum, bucket| Bucket {
+                second: 0,
+                requests: sum.requests + bucket.requests,
+                connections: sum.connections + bucket.connections,
+                failures: sum.failures + bucket.failures,
+                connect_micros: sum.connect_micros + bucket.connect_micros,
+            })
+            .counts()
+    }
+
+    fn bump(&self, now: u64, update: impl FnOnce(&mut Bucket)) {
+        let mut recent = self.recent.lock();
+        if recent.back().is_none_or(|bucket| bucket.second != now) {
+            recent.push_back(Bucket {
+                second: now,
+                ..Bucket::default()
+            });
+        }
+        if let Some(bucket) = recent.back_mut() {
+            update(bucket);
+        }
+        prune(&mut recent, now);
+    }
+
+    fn now(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+
+    /// 以 Prometheus 文本格式输出累计指标
+    pub(crate) fn render(&self, out: &mut String) {
+        let total = self.total();
+        write_metric(
+            out,
+            "kiro_upstream_requests_sent_total",
+            "counter",
+            "Requests sent to the Kiro API",
+            total.requests,
+        );
+        write_metric(
+            out,
+            "kiro_upstream_connections_total",
+            "counter",
+            "New connections (including TLS handshakes) established to the Kiro API",
+            total.new_connections,
+        );
+        write_metric(
+            out,
+            "kiro_upstream_connect_failures_total",
+            "counter",
+            "Failed attempts to establish a connection to the Kiro API",
+            total.connect_failures,
+        );
+        write_metric(
+            out,
+            "kiro_upstream_connect_seconds_total",
+            "counter",
+            "Time spent establishing new connections to the Kiro API",
+            self.connect_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+        );
+    }
+}
+
+/// 移除统计窗口外的桶
+fn prune(recent: &mut VecDeque<Bucket>, now: u64) {
+    while recent
+        .front()
+        .is_some_and(|bucket| bucket.second + RECENT_WINDOW_SECS <= now)
+    {
+        recent.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reuse_counts() {
+        let stats = ConnectionStats::default();
+        assert_eq!(stats.total().reuse_ratio, None);
+
+        for _ in 0..4 {
+            stats.record_request();
+        }
+        stats.record_connect(true, Duration::from_millis(30));
+        stats.record_connect(false, Duration::from_millis(5));
+
+        let total = stats.total();
+        assert_eq!(total.requests, 4);
+        assert_eq!(total.new_connections, 1);
+        assert_eq!(total.reused_requests, 3);
+        assert_eq!(total.reuse_ratio, Some(0.75));
+        assert_eq!(total.connect_failures, 1);
+        assert_eq!(total.avg_connect_ms, Some(30.0));
+        assert_eq!(stats.recent(), total);
+    }
+
+    #[test]
+    fn test_recent_window_expires() {
+        let stats = ConnectionStats::default();
+        stats.record_request();
+        stats.record_connect(true, Duration::from_millis(10));
+
+        assert_eq!(stats.recent_at(RECENT_WINDOW_SECS - 1).requests, 1);
+        assert_eq!(
+            stats.recent_at(RECENT_WINDOW_SECS),
+            ConnectionCounts::default()
+        );
+        // 累计值不受窗口影响
+        assert_eq!(stats.total().new_connections, 1);
+    }
+}
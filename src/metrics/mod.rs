@@ -0,0 +1,732 @@
+//! 运行指标模块
+//!
+//! 以原子计数器维护进程级指标，通过 `GET /metrics` 以 Prometheus 文本格式导出。
+//! 请求数、耗时与 token 指标带有 `credential_index` 与 `model` 标签，
+//! 每个标签最多保留 [`MAX_LABEL_VALUES`] 个不同取值，超出的归入 `other`，避免客户端传入任意模型名导致序列膨胀。
+//! Token 刷新的次数、结果与耗时按认证方式（`social` / `idc`）分组。
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+use alerts::AlertMonitor;
+use axum::http::header;
+use axum::response::IntoResponse;
+use connections::ConnectionStats;
+use parking_lot::Mutex;
+
+/// 每个标签保留的最大不同取值数
+pub const MAX_LABEL_VALUES: usize = 64;
+
+/// 超出取值上限时使用的标签值
+const OVERFLOW_LABEL: &str = "other";
+
+/// 请求耗时直方图的桶上界（秒）
+const LATENCY_BUCKETS: &[f64] = &[
+    0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+
+/// 首 token 耗时直方图的桶上界（秒）
+const FIRST_TOKEN_BUCKETS: &[f64] = &[0.25, 0.5, 1.0, 2.0, 3.0, 5.0, 10.0, 20.0, 30.0, 60.0];
+
+/// Token 刷新耗时直方图的桶上界（秒）
+const REFRESH_BUCKETS: &[f64] = &[0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0, 60.0];
+
+/// 输出速度直方图的桶上界（tokens/秒）
+const THROUGHPUT_BUCKETS: &[f64] = &[
+    5.0, 10.0, 20.0, 30.0, 50.0, 75.0, 100.0, 150.0, 200.0, 300.0,
+];
+
+pub mod alerts;
+pub mod connections;
+
+/// 全局指标实例
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+/// 全局告警监视器
+static ALERTS: LazyLock<AlertMonitor> = LazyLock::new(AlertMonitor::default);
+
+/// 全局上游连接统计
+static CONNECTIONS: LazyLock<ConnectionStats> = LazyLock::new(ConnectionStats::default);
+
+/// 获取全局指标
+pub fn global() -> &'static Metrics {
+    &METRICS
+}
+
+/// 获取全局告警监视器
+pub fn alerts() -> &'static AlertMonitor {
+    &ALERTS
+}
+
+/// 获取全局上游连接统计
+pub fn connections() -> &'static ConnectionStats {
+    &CONNECTIONS
+}
+
+/// 进程级运行指标
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// 准入队列中等待的请求数
+    pub queue_depth: AtomicI64,
+    /// 已通过准入、正在处理的请求数
+    pub in_flight_requests: AtomicI64,
+    /// 自适应并发上限的当前值（未启用时为 0）
+    pub adaptive_concurrency_limit: AtomicI64,
+    /// 最近一次采样的事件循环延迟（毫秒）
+    pub event_loop_lag_ms: AtomicI64,
+    /// 最近一次采样的进程常驻内存（字节，非 Linux 系统为 0）
+    pub resident_memory_bytes: AtomicI64,
+    /// 因队列已满被拒绝的请求数
+    pub queue_rejected_total: AtomicU64,
+    /// 排队超时的请求数
+    pub queue_timeout_total: AtomicU64,
+    /// 被全局 RPM/TPM 限流拒绝的请求数
+    pub rate_limited_total: AtomicU64,
+    /// 被 `ipFilter` 拒绝的请求数
+    pub ip_denied_total: AtomicU64,
+    /// 被按 IP 限流拒绝（含封禁期内）的请求数
+    pub ip_rate_limited_total: AtomicU64,
+    /// 提供了错误 Key 的认证请求数（含 Admin API）
+    pub auth_failures_total: AtomicU64,
+    /// 因认证失败次数过多被锁定拒绝的请求数
+    pub auth_locked_out_total: AtomicU64,
+    /// 发起的非流式对冲请求数
+    pub hedged_requests_total: AtomicU64,
+    /// 对冲请求先于主调用完成的次数
+    pub hedge_wins_total: AtomicU64,
+    /// 因重试预算耗尽而放弃的重试次数
+    pub retry_budget_exhausted_total: AtomicU64,
+    /// 因资源压力被降载拒绝的请求数
+    pub load_shed_total: AtomicU64,
+    /// 按凭据与模型分组的请求指标
+    labeled: Mutex<LabeledMetrics>,
+}
+
+/// 带标签的指标
+#[derive(Debug, Default)]
+struct LabeledMetrics {
+    credentials: LabelGuard,
+    models: LabelGuard,
+    /// (credential_index, model, status) -> 请求数
+    requests: BTreeMap<(String, String, u16), u64>,
+    /// (credential_index, model) -> 耗时直方图
+    latency: BTreeMap<(String, String), Histogram>,
+    /// (credential_index, model) -> 首 token 耗时直方图
+    first_token: BTreeMap<(String, String), Histogram>,
+    /// (credential_index, model) -> 输出速度直方图
+    throughput: BTreeMap<(String, String), Histogram>,
+    /// (credential_index, model) -> (输入 tokens, 输出 tokens)
+    tokens: BTreeMap<(String, String), (u64, u64)>,
+    /// (credential_index, status) -> 上游调用次数（status 为 HTTP 状态码或 `network`）
+    upstream: BTreeMap<(String, String), u64>,
+    /// (auth_method, outcome) -> Token 刷新次数（outcome 为 `success` 或错误分类）
+    refresh: BTreeMap<(&'static str, &'static str), u64>,
+    /// auth_method -> Token 刷新耗时直方图
+    refresh_duration: BTreeMap<&'static str, Histogram>,
+}
+
+/// 限制标签的不同取值数
+#[derive(Debug, Default)]
+struct LabelGuard(HashSet<String>);
+
+impl LabelGuard {
+    fn label(&mut self, value: &str) -> String {
+        if self.0.contains(value) {
+            return value.to_string();
+        }
+        if self.0.len() < MAX_LABEL_VALUES {
+            self.0.insert(value.to_string());
+            return value.to_string();
+        }
+        OVERFLOW_LABEL.to_string()
+    }
+}
+
+/// 累计直方图
+#[derive(Debug, Clone)]
+struct Histogram {
+    bounds: &'static [f64],
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(self.bounds) {
+            if value <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// 凭据标签值（未到达上游时为 `none`）
+fn credential_label(credential_id: Option<u64>) -> String {
+    credential_id.map_or_else(|| "none".to_string(), |id| id.to_string())
+}
+
+impl Metrics {
+    /// 记录一个已结束的请求
+    pub fn observe_request(
+        &self,
+        credential_id: Option<u64>,
+        model: &str,
+        status: u16,
+        latency: Duration,
+        input_tokens: i32,
+        output_tokens: i32,
+    ) {
+        let mut labeled = self.labeled.lock();
+        let credential = labeled.credentials.label(&credential_label(credential_id));
+        let model = labeled.models.label(model);
+
+        *labeled
+            .requests
+            .entry((credential.clone(), model.clone(), status))
+            .or_default() += 1;
+        labeled
+            .latency
+            .entry((credential.clone(), model.clone()))
+            .or_insert_with(|| Histogram::new(LATENCY_BUCKETS))
+            .observe(latency.as_secs_f64());
+        let tokens = labeled.tokens.entry((credential, model)).or_default();
+        tokens.0 += input_tokens.max(0) as u64;
+        tokens.1 += output_tokens.max(0) as u64;
+    }
+
+    /// 记录请求的首 token 耗时与输出速度（tokens/秒，输出过短无法计算时为 None）
+    pub fn observe_generation(
+        &self,
+        credential_id: Option<u64>,
+        model: &str,
+        first_token: Duration,
+        tokens_per_second: Option<f64>,
+    ) {
+        let mut labeled = self.labeled.lock();
+        let credential = labeled.credentials.label(&credential_label(credential_id));
+        let model = labeled.models.label(model);
+
+        labeled
+            .first_token
+            .entry((credential.clone(), model.clone()))
+            .or_insert_with(|| Histogram::new(FIRST_TOKEN_BUCKETS))
+            .observe(first_token.as_secs_f64());
+        if let Some(rate) = tokens_per_second {
+            labeled
+                .throughput
+                .entry((credential, model))
+                .or_insert_with(|| Histogram::new(THROUGHPUT_BUCKETS))
+                .observe(rate);
+        }
+    }
+
+    /// 记录一次上游调用的结果（`status` 为 None 表示网络错误）
+    pub fn observe_upstream(&self, credential_id: u64, status: Option<u16>) {
+        let mut labeled = self.labeled.lock();
+        let credential = labeled.credentials.label(&credential_id.to_string());
+        let status = status.map_or_else(|| "network".to_string(), |s| s.to_string());
+        *labeled.upstream.entry((credential, status)).or_default() += 1;
+    }
+
+    /// 单个凭据的上游调用次数，按状态码（或 `network`）分组
+    pub fn upstream_statuses(&self, credential_id: u64) -> BTreeMap<String, u64> {
+        let credential = credential_id.to_string();
+        self.labeled
+            .lock()
+            .upstream
+            .iter()
+            .filter(|((c, _), _)| *c == credential)
+            .map(|((_, status), count)| (status.clone(), *count))
+            .collect()
+    }
+
+    /// 记录一次 Token 刷新（`outcome` 为 `success` 或失败的错误分类）
+    pub fn observe_refresh(
+        &self,
+        auth_method: &'static str,
+        outcome: &'static str,
+        duration: Duration,
+    ) {
+        let mut labeled = self.labeled.lock();
+        *labeled.refresh.entry((auth_method, outcome)).or_default() += 1;
+        labeled
+            .refresh_duration
+            .entry(auth_method)
+            .or_insert_with(|| Histogram::new(REFRESH_BUCKETS))
+            .observe(duration.as_secs_f64());
+    }
+
+    /// 以 Prometheus 文本格式输出所有指标
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        write_metric(
+            &mut out,
+            "kiro_queue_depth",
+            "gauge",
+            "Requests waiting in the admission queue",
+            self.queue_depth.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "kiro_in_flight_requests",
+            "gauge",
+            "Admitted requests currently being processed",
+            self.in_flight_requests.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "kiro_adaptive_concurrency_limit",
+            "gauge",
+            "Current adaptive limit on concurrently admitted requests (0 when disabled)",
+            self.adaptive_concurrency_limit.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "kiro_event_loop_lag_ms",
+            "gauge",
+            "Most recently sampled delay of the async runtime in waking a timer",
+            self.event_loop_lag_ms.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "kiro_resident_memory_bytes",
+            "gauge",
+            "Most recently sampled resident memory of the process (0 when unavailable)",
+            self.resident_memory_bytes.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "kiro_queue_rejected_total",
+            "counter",
+            "Requests rejected because the admission queue was full",
+            self.queue_rejected_total.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "kiro_queue_timeout_total",
+            "counter",
+            "Requests that timed out waiting in the admission queue",
+            self.queue_timeout_total.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "kiro_rate_limited_total",
+            "counter",
+            "Requests rejected by the global RPM/TPM limiter",
+            self.rate_limited_total.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "kiro_ip_denied_total",
+            "counter",
+            "Requests rejected by the client IP filter",
+            self.ip_denied_total.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "kiro_ip_rate_limited_total",
+            "counter",
+            "Requests rejected by the per-client-IP limiter, including banned clients",
+            self.ip_rate_limited_total.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "kiro_auth_failures_total",
+            "counter",
+            "Requests that presented an invalid API key, including the admin API",
+            self.auth_failures_total.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "kiro_auth_locked_out_total",
+            "counter",
+            "Requests rejected because the client IP is locked out after repeated auth failures",
+            self.auth_locked_out_total.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "kiro_hedged_requests_total",
+            "counter",
+            "Non-streaming requests that fired a hedge on another credential",
+            self.hedged_requests_total.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "kiro_hedge_wins_total",
+            "counter",
+            "Hedged requests where the hedge finished before the original call",
+            self.hedge_wins_total.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "kiro_retry_budget_exhausted_total",
+            "counter",
+            "Retries skipped because the global retry budget was exhausted",
+            self.retry_budget_exhausted_total.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "kiro_load_shed_total",
+            "counter",
+            "New requests rejected because the process was under resource pressure",
+            self.load_shed_total.load(Ordering::Relaxed),
+        );
+        connections().render(&mut out);
+        self.labeled.lock().render(&mut out);
+        out
+    }
+}
+
+impl LabeledMetrics {
+    fn render(&self, out: &mut String) {
+        write_header(
+            out,
+            "kiro_requests_total",
+            "counter",
+            "Finished requests by credential, model and HTTP status",
+        );
+        for ((credential, model, status), count) in &self.requests {
+            let _ = writeln!(
+                out,
+                "kiro_requests_total{{credential_index=\"{}\",model=\"{}\",status=\"{}\"}} {}",
+                escape(credential),
+                escape(model),
+                status,
+                count
+            );
+        }
+
+        write_histograms(
+            out,
+            "kiro_request_duration_seconds",
+            "Request latency by credential and model",
+            &self.latency,
+        );
+        write_histograms(
+            out,
+            "kiro_time_to_first_token_seconds",
+            "Time from request start to the first generated token by credential and model",
+            &self.first_token,
+        );
+        write_histograms(
+            out,
+            "kiro_output_tokens_per_second",
+            "Output tokens per second after the first token by credential and model",
+            &self.throughput,
+        );
+
+        write_header(
+            out,
+            "kiro_tokens_total",
+            "counter",
+            "Tokens by credential, model and direction",
+        );
+        for ((credential, model), (input, output)) in &self.tokens {
+            for (direction, value) in [("input", input), ("output", output)] {
+                let _ = writeln!(
+                    out,
+                    "kiro_tokens_total{{credential_index=\"{}\",model=\"{}\",direction=\"{}\"}} {}",
+                    escape(credential),
+                    escape(model),
+                    direction,
+                    value
+                );
+            }
+        }
+
+        write_header(
+            out,
+            "kiro_upstream_requests_total",
+            "counter",
+            "Upstream call attempts by credential, upstream status and error class",
+        );
+        for ((credential, status), count) in &self.upstream {
+            let _ = writeln!(
+                out,
+                "kiro_upstream_requests_total{{credential_index=\"{}\",status=\"{}\",class=\"{}\"}} {}",
+                escape(credential),
+                status,
+                upstream_class(status),
+                count
+            );
+        }
+
+        let mut attempts: BTreeMap<&str, u64> = BTreeMap::new();
+        for ((method, _), count) in &self.refresh {
+            *attempts.entry(method).or_default() += count;
+        }
+        write_header(
+            out,
+            "kiro_token_refresh_attempts_total",
+            "counter",
+            "Token refresh attempts by auth method",
+        );
+        for (method, count) in &attempts {
+            let _ = writeln!(
+                out,
+                "kiro_token_refresh_attempts_total{{auth_method=\"{}\"}} {}",
+                method, count
+            );
+        }
+        write_header(
+            out,
+            "kiro_token_refresh_total",
+            "counter",
+            "Token refresh results by auth method and outcome (success or error class)",
+        );
+        for ((method, outcome), count) in &self.refresh {
+            let _ = writeln!(
+                out,
+                "kiro_token_refresh_total{{auth_method=\"{}\",outcome=\"{}\"}} {}",
+                method, outcome, count
+            );
+        }
+        write_header(
+            out,
+            "kiro_token_refresh_duration_seconds",
+            "histogram",
+            "Token refresh duration by auth method",
+        );
+        for (method, histogram) in &self.refresh_duration {
+            write_histogram(
+                out,
+                "kiro_token_refresh_duration_seconds",
+                &format!("auth_method=\"{}\"", method),
+                histogram,
+            );
+        }
+    }
+}
+
+/// 上游状态对应的错误分类（`status` 为状态码或 `network`）
+fn upstream_class(status: &str) -> &'static str {
+    match status.parse::<u16>() {
+        Err(_) => "network",
+        Ok(200..=299) => "success",
+        Ok(400) => "bad_request",
+        Ok(401 | 403) => "auth",
+        Ok(402) => "quota",
+        Ok(408) => "timeout",
+        Ok(429) => "rate_limited",
+        Ok(500..=599) => "server_error",
+        Ok(_) => "client_error",
+    }
+}
+
+/// 写入按 (credential_index, model) 分组的直方图
+fn write_histograms(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    histograms: &BTreeMap<(String, String), Histogram>,
+) {
+    write_header(out, name, "histogram", help);
+    for ((credential, model), histogram) in histograms {
+        let labels = format!(
+            "credential_index=\"{}\",model=\"{}\"",
+            escape(credential),
+            escape(model)
+        );
+        write_histogram(out, name, &labels, histogram);
+    }
+}
+
+/// 写入单个直方图序列（`labels` 为已格式化的标签）
+fn write_histogram(out: &mut String, name: &str, labels: &str, histogram: &Histogram) {
+    for (bound, count) in histogram.bounds.iter().zip(&histogram.buckets) {
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{},le=\"{}\"}} {}",
+            name, labels, bound, count
+        );
+    }
+    let _ = writeln!(
+        out,
+        "{}_bucket{{{},le=\"+Inf\"}} {}",
+        name, labels, histogram.count
+    );
+    let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, histogram.sum);
+    let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, histogram.count);
+}
+
+/// 转义 Prometheus 标签值
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// 写入指标的 HELP 与 TYPE 行
+fn write_header(out: &mut String, name: &str, metric_type: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, metric_type);
+}
+
+/// 写入单个无标签指标
+fn write_metric(
+    out: &mut String,
+    name: &str,
+    metric_type: &str,
+    help: &str,
+    value: impl std::fmt::Display,
+) {
+    write_header(out, name, metric_type, help);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// GET /metrics
+///
+/// 以 Prometheus 文本格式返回运行指标
+pub async fn get_metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        global().render(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prometheus_format() {
+        let metrics = Metrics::default();
+        metrics.queue_depth.store(3, Ordering::Relaxed);
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE kiro_queue_depth gauge\nkiro_queue_depth 3\n"));
+        assert!(text.contains("kiro_queue_timeout_total 0\n"));
+    }
+
+    #[test]
+    fn test_render_labeled_request_metrics() {
+        let metrics = Metrics::default();
+        metrics.observe_request(
+            Some(2),
+            "claude-sonnet-4-5",
+            200,
+            Duration::from_millis(300),
+            10,
+            20,
+        );
+        metrics.observe_request(
+            None,
+            "claude-sonnet-4-5",
+            429,
+            Duration::from_millis(5),
+            0,
+            0,
+        );
+        metrics.observe_upstream(2, Some(502));
+        metrics.observe_upstream(2, None);
+
+        let text = metrics.render();
+        assert!(text.contains(
+            "kiro_requests_total{credential_index=\"2\",model=\"claude-sonnet-4-5\",status=\"200\"} 1\n"
+        ));
+        assert!(text.contains(
+            "kiro_requests_total{credential_index=\"none\",model=\"claude-sonnet-4-5\",status=\"429\"} 1\n"
+        ));
+        assert!(text.contains(
+            "kiro_request_duration_seconds_bucket{credential_index=\"2\",model=\"claude-sonnet-4-5\",le=\"0.25\"} 0\n"
+        ));
+        assert!(text.contains(
+            "kiro_request_duration_seconds_bucket{credential_index=\"2\",model=\"claude-sonnet-4-5\",le=\"0.5\"} 1\n"
+        ));
+        assert!(text.contains(
+            "kiro_tokens_total{credential_index=\"2\",model=\"claude-sonnet-4-5\",direction=\"output\"} 20\n"
+        ));
+        assert!(text.contains(
+            "kiro_upstream_requests_total{credential_index=\"2\",status=\"network\",class=\"network\"} 1\n"
+        ));
+        assert!(text.contains(
+            "kiro_upstream_requests_total{credential_index=\"2\",status=\"502\",class=\"server_error\"} 1\n"
+        ));
+    }
+
+    #[test]
+    fn test_upstream_statuses_per_credential() {
+        let metrics = Metrics::default();
+        metrics.observe_upstream(1, Some(403));
+        metrics.observe_upstream(1, Some(403));
+        metrics.observe_upstream(1, Some(200));
+        metrics.observe_upstream(2, Some(429));
+
+        let statuses = metrics.upstream_statuses(1);
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(statuses["403"], 2);
+        assert_eq!(upstream_class("403"), "auth");
+        assert_eq!(upstream_class("429"), "rate_limited");
+        assert!(metrics.upstream_statuses(3).is_empty());
+    }
+
+    #[test]
+    fn test_render_generation_histograms() {
+        let metrics = Metrics::default();
+        metrics.observe_generation(Some(1), "m", Duration::from_millis(800), Some(42.0));
+        metrics.observe_generation(Some(1), "m", Duration::from_secs(4), None);
+
+        let text = metrics.render();
+        assert!(text.contains(
+            "kiro_time_to_first_token_seconds_bucket{credential_index=\"1\",model=\"m\",le=\"1\"} 1\n"
+        ));
+        assert!(text.contains(
+            "kiro_time_to_first_token_seconds_count{credential_index=\"1\",model=\"m\"} 2\n"
+        ));
+        assert!(text.contains(
+            "kiro_output_tokens_per_second_bucket{credential_index=\"1\",model=\"m\",le=\"50\"} 1\n"
+        ));
+        assert!(text.contains(
+            "kiro_output_tokens_per_second_count{credential_index=\"1\",model=\"m\"} 1\n"
+        ));
+    }
+
+    #[test]
+    fn test_render_refresh_metrics() {
+        let metrics = Metrics::default();
+        metrics.observe_refresh("social", "success", Duration::from_millis(300));
+        metrics.observe_refresh("social", "unauthorized", Duration::from_millis(200));
+        metrics.observe_refresh("idc", "network", Duration::from_secs(3));
+
+        let text = metrics.render();
+        assert!(text.contains("kiro_token_refresh_attempts_total{auth_method=\"social\"} 2\n"));
+        assert!(text.contains(
+            "kiro_token_refresh_total{auth_method=\"social\",outcome=\"unauthorized\"} 1\n"
+        ));
+        assert!(text.contains(
+            "kiro_token_refresh_duration_seconds_bucket{auth_method=\"idc\",le=\"5\"} 1\n"
+        ));
+        assert!(
+            text.contains("kiro_token_refresh_duration_seconds_count{auth_method=\"social\"} 2\n")
+        );
+    }
+
+    #[test]
+    fn test_label_values_are_capped() {
+        let metrics = Metrics::default();
+        for i in 0..MAX_LABEL_VALUES + 5 {
+            metrics.observe_request(Some(1), &format!("model-{}", i), 200, Duration::ZERO, 0, 0);
+        }
+        metrics.observe_request(Some(1), "model-0", 200, Duration::ZERO, 0, 0);
+
+        let text = metrics.render();
+        assert!(text.contains("model=\"other\",status=\"200\"} 5\n"));
+        assert!(text.contains("model=\"model-0\",status=\"200\"} 2\n"));
+    }
+}
@@ -1,14 +1,263 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+
+use super::config::{Config, LogFormat};
+use crate::daemon::DEFAULT_LOG_FILE;
+use crate::service::DEFAULT_SERVICE_NAME;
 
 /// Anthropic <-> Kiro API 客户端
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
 pub struct Args {
     /// 配置文件路径
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub config: Option<String>,
 
     /// 凭证文件路径
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub credentials: Option<String>,
+
+    /// 使用配置文件 `profiles` 中的指定 profile（覆盖环境变量 KIRO_PROFILE）
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// 监听地址（覆盖配置文件与环境变量中的 host）
+    #[arg(long)]
+    pub host: Option<String>,
+
+    /// 监听端口（覆盖配置文件与环境变量中的 port）
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// 日志输出格式（覆盖配置文件中的 logFormat）
+    #[arg(long, value_enum, global = true)]
+    pub log_format: Option<LogFormat>,
+
+    /// 在后台运行（仅 Unix），标准输出与标准错误写入 --log-file
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// 后台运行时的日志文件
+    #[arg(long, default_value = DEFAULT_LOG_FILE)]
+    pub log_file: String,
+
+    /// 启动时写入进程 PID 的文件，退出时删除
+    #[arg(long)]
+    pub pid_file: Option<String>,
+
+    /// 子命令（省略时启动服务）
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Args {
+    /// 是否启动服务（未指定子命令、`serve` 或 `service run`）
+    pub fn is_serve(&self) -> bool {
+        matches!(
+            self.command,
+            None | Some(Command::Serve)
+                | Some(Command::Service {
+                    action: ServiceAction::Run { .. }
+                })
+        )
+    }
+
+    /// 将命令行参数覆盖到配置上
+    pub fn apply_overrides(&self, config: &mut Config) {
+        if let Some(host) = &self.host {
+            config.host = host.clone();
+        }
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+    }
+}
+
+/// 子命令
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// 启动服务（默认）
+    Serve,
+
+    /// 生成带注释的配置文件模板与空的凭证文件
+    Init {
+        /// 覆盖已存在的文件
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// 校验配置文件与凭证文件
+    ValidateConfig,
+
+    /// 将配置文件中明文保存的下游 API Key 替换为加盐哈希
+    HashKeys {
+        /// 只输出该 Key 的哈希，不修改配置文件
+        key: Option<String>,
+    },
+
+    /// 加密配置值，输出可写入配置文件的 `enc:` 密文（主密钥取自 KIRO_MASTER_KEY 或 KIRO_MASTER_KEY_FILE）
+    EncryptValue {
+        /// 要加密的值（省略时从标准输入读取，避免留在 shell 历史中）
+        value: Option<String>,
+    },
+
+    /// 通过设备授权（IdC / Builder ID）添加凭据并写入凭证文件
+    AddCredential {
+        /// IdC 起始地址（默认 AWS Builder ID）
+        #[arg(long)]
+        start_url: Option<String>,
+
+        /// 凭据优先级（数字越小优先级越高）
+        #[arg(long, default_value_t = 0)]
+        priority: u32,
+
+        /// 凭据标签（可重复指定）
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+    },
+
+    /// 查询凭据余额
+    CheckBalance {
+        /// 只查询指定凭据
+        #[arg(long)]
+        id: Option<u64>,
+
+        /// 以 JSON 输出
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// 导出持久化的用量记录
+    ExportStats {
+        /// 只导出最近一段时间的记录，如 `24h`、`7d`
+        #[arg(long)]
+        since: Option<String>,
+
+        /// 输出格式
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+
+        /// 输出文件（默认标准输出）
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// 校验 Admin 审计日志的 HMAC 链
+    VerifyAuditLog {
+        /// 审计日志文件（默认 `auditLog.path`）
+        #[arg(long)]
+        path: Option<String>,
+    },
+
+    /// 向运行中的实例发送合成的对话请求进行压测，输出延迟分位数与错误率
+    Bench {
+        /// 实例地址（默认按配置的 host、port 与 pathPrefix 访问本机）
+        #[arg(long)]
+        url: Option<String>,
+
+        /// 下游 API Key（默认取配置中的 apiKey，已哈希存储时必须指定）
+        #[arg(long)]
+        api_key: Option<String>,
+
+        /// 并发请求数
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+
+        /// 压测时长，如 `30s`、`5m`
+        #[arg(long, default_value = "30s")]
+        duration: String,
+
+        /// 流式请求占比（0 ~ 1）
+        #[arg(long, default_value_t = 0.5)]
+        stream_ratio: f64,
+
+        /// 请求的模型
+        #[arg(long, default_value = "claude-haiku-4-5")]
+        model: String,
+
+        /// 每个请求的 max_tokens
+        #[arg(long, default_value_t = 16)]
+        max_tokens: u32,
+
+        /// 以 JSON 输出报告
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// 按时间顺序重放请求日志（`sampling.logBodies`）中的请求，输出每条请求的状态码与响应
+    Replay {
+        /// 请求日志所在的数据库（默认取配置中的 databasePath）
+        #[arg(long)]
+        database: Option<String>,
+
+        /// 只重放指定请求 ID 的记录（可重复指定）
+        #[arg(long = "request-id")]
+        request_ids: Vec<String>,
+
+        /// 只重放最近一段时间的记录，如 `24h`、`7d`
+        #[arg(long)]
+        since: Option<String>,
+
+        /// 只重放失败（4xx/5xx）的记录
+        #[arg(long)]
+        failed: bool,
+
+        /// 最多重放的记录数（取最近的记录）
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+
+        /// 实例地址（默认按配置的 host、port 与 pathPrefix 访问本机）
+        #[arg(long)]
+        url: Option<String>,
+
+        /// 下游 API Key（默认取配置中的 apiKey，已哈希存储时必须指定）
+        #[arg(long)]
+        api_key: Option<String>,
+
+        /// 只在本地执行请求转换，输出发送给上游的 conversationState，不发送请求
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// 管理 Windows 服务
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+}
+
+/// Windows 服务操作
+#[derive(Subcommand, Debug, Clone)]
+pub enum ServiceAction {
+    /// 注册开机自动启动的服务（使用当前的配置文件与凭证文件路径）
+    Install {
+        /// 服务名
+        #[arg(long, default_value = DEFAULT_SERVICE_NAME)]
+        name: String,
+
+        /// 服务显示名称
+        #[arg(long, default_value = "Kiro API Proxy")]
+        display_name: String,
+    },
+
+    /// 停止并删除服务
+    Uninstall {
+        /// 服务名
+        #[arg(long, default_value = DEFAULT_SERVICE_NAME)]
+        name: String,
+    },
+
+    /// 以服务方式运行（由服务控制管理器调用）
+    Run {
+        /// 服务名
+        #[arg(long, default_value = DEFAULT_SERVICE_NAME)]
+        name: String,
+    },
+}
+
+/// 用量导出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    /// 每行一个 JSON 对象
+    Jsonl,
 }
@@ -1,6 +1,14 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use super::secret;
+use super::validation::{self, Problem};
+use crate::common::auth;
+use crate::kiro::model::credentials::KiroCredentials;
 
 /// KNA 应用配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,9 +20,21 @@ pub struct Config {
     #[serde(default = "default_port")]
     pub port: u16,
 
+    /// 监听地址列表（可选，配置后忽略 `host` / `port` / `tls`），每个地址可启用不同的路由组
+    #[serde(default)]
+    pub listeners: Vec<ListenerConfig>,
+
+    /// `host:port` 上的 TLS 终止（可选，配置了 `listeners` 时在各监听地址上单独配置）
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+
     #[serde(default = "default_region")]
     pub region: String,
 
+    /// 上游端点地址模板（`{region}` 替换为凭据或全局的区域，用于新区域或测试环境）
+    #[serde(default)]
+    pub endpoints: EndpointsConfig,
+
     #[serde(default = "default_kiro_version")]
     pub kiro_version: String,
 
@@ -24,6 +44,10 @@ pub struct Config {
     #[serde(default)]
     pub api_key: Option<String>,
 
+    /// 额外的下游 API Key（可选，可为每个 Key 指定调度优先级）
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+
     #[serde(default = "default_system_version")]
     pub system_version: String,
 
@@ -59,80 +83,2274 @@ pub struct Config {
     #[serde(default)]
     pub admin_api_key: Option<String>,
 
+    /// 额外的 Admin API 密钥（可选，明文或加盐哈希，与 `adminApiKey` 同时有效，用于不停机轮换）
+    #[serde(default)]
+    pub admin_api_keys: Vec<String>,
+
+    /// Admin UI 的 OIDC 单点登录（可选，授权码 + PKCE，按 ID Token 中的声明授予 Admin 权限）
+    #[serde(default)]
+    pub admin_oidc: Option<AdminOidcConfig>,
+
     /// 外部访问路径前缀（用于反向代理场景）
     /// 例如："/kiro-rs" 表示通过 /kiro-rs/admin 访问
     #[serde(default)]
     pub base_path: Option<String>,
+
+    /// 服务自身挂载的路径前缀（代理 API 与 Admin 路由均在其下）
+    /// 例如："/ai" 表示通过 /ai/v1/messages、/ai/admin 访问
+    #[serde(default)]
+    pub path_prefix: Option<String>,
+
+    /// 是否从响应中移除推理内容（thinking 块 / reasoning_content）
+    /// 用于无法处理未知字段的客户端
+    #[serde(default)]
+    pub strip_reasoning: bool,
+
+    /// 用量记录持久化文件路径（JSON Lines，可选，未配置时仅保存在内存中）
+    #[serde(default)]
+    pub usage_log_path: Option<String>,
+
+    /// 嵌入式 SQLite 数据库路径（可选）；配置后用量记录与 Admin 审计记录持久化到数据库，
+    /// 表结构在启动时自动迁移。与 `usage_log_path` 二选一
+    #[serde(default)]
+    pub database_path: Option<String>,
+
+    /// 用量记录保留天数（可选，未配置时不清理）；过期记录每小时从内存与持久化文件或数据库中删除
+    #[serde(default)]
+    pub usage_retention_days: Option<u64>,
+
+    /// 批处理任务与文件保留天数（可选，未配置时不清理）；已结束的任务连同其输入、结果与错误文件，
+    /// 以及未被任务引用的过期文件，每小时从内存中删除
+    #[serde(default)]
+    pub batch_retention_days: Option<u64>,
+
+    /// 每日用量报告（可选，未配置时不生成）
+    #[serde(default)]
+    pub usage_report: Option<UsageReportConfig>,
+
+    /// 慢请求阈值（毫秒，可选），总耗时超过该值的请求以 WARN 级别输出耗时分解
+    #[serde(default)]
+    pub slow_request_threshold_ms: Option<u64>,
+
+    /// 启动时并发预热（刷新 Token、查询额度）的凭据数，预热完成前 `/readyz` 返回 503；0 表示不预热
+    #[serde(default = "default_warm_up_concurrency")]
+    pub warm_up_concurrency: usize,
+
+    /// 收到停止信号后、停止接受新连接前的等待秒数（期间 `/readyz` 返回 503）
+    #[serde(default = "default_drain_delay_secs")]
+    pub drain_delay_secs: u64,
+
+    /// 停止接受新连接后等待进行中的请求（含流式响应）完成的最长秒数，超时后强制退出
+    #[serde(default = "default_shutdown_grace_period_secs")]
+    pub shutdown_grace_period_secs: u64,
+
+    /// 错误率告警（全局与按凭据，可选，未配置时不启用）
+    #[serde(default)]
+    pub alerts: Option<AlertConfig>,
+
+    /// 上游熔断（可选）：所有上游调用中失败的占比过高时（如区域性故障）直接返回 503，不再调用上游
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+
+    /// 非流式请求的对冲（可选）：超过耗时预算仍未完成时在另一个凭据上发起相同请求，取先完成者
+    #[serde(default)]
+    pub hedging: Option<HedgingConfig>,
+
+    /// 自适应并发上限（可选）：按上游 429 与响应耗时自动调整全局准入队列的容量
+    #[serde(default)]
+    pub adaptive_concurrency: Option<AdaptiveConcurrencyConfig>,
+
+    /// 全局重试预算（可选）：重试次数不超过近期请求数的一定比例，上游整体故障时避免重试放大流量
+    #[serde(default)]
+    pub retry_budget: Option<RetryBudgetConfig>,
+
+    /// 凭据生命周期 Webhook（可选）：凭据被自动禁用、额度达到阈值、连续刷新失败或可用凭据不足时推送事件
+    #[serde(default)]
+    pub credential_webhook: Option<CredentialWebhookConfig>,
+
+    /// Telegram 通知（可选）：错误率告警与凭据生命周期事件以消息发送到指定会话
+    #[serde(default)]
+    pub telegram: Option<TelegramConfig>,
+
+    /// Discord 通知（可选）：错误率告警与凭据生命周期事件以 Embed 卡片发送到频道 Webhook
+    #[serde(default)]
+    pub discord: Option<DiscordConfig>,
+
+    /// 资源压力降载（可选）：事件循环延迟或内存超过阈值时按优先级拒绝新请求
+    #[serde(default)]
+    pub load_shedding: Option<LoadSheddingConfig>,
+
+    /// 上游 HTTPS 连接的证书选项（额外的根证书、按端点跳过证书校验，可选）
+    #[serde(default)]
+    pub upstream_tls: Option<UpstreamTlsConfig>,
+
+    /// 上游主机名到固定 IP 的映射（跳过 DNS 解析，端口仍取自 URL）
+    #[serde(default)]
+    pub dns_overrides: HashMap<String, Vec<std::net::IpAddr>>,
+
+    /// 上游连接池（高并发流式请求时调优）
+    #[serde(default)]
+    pub upstream_pool: UpstreamPoolConfig,
+
+    /// 上游请求的分阶段超时
+    #[serde(default)]
+    pub timeouts: TimeoutsConfig,
+
+    /// 访问日志（每个请求一行，按大小 / 时间轮转，可选）
+    #[serde(default)]
+    pub access_log: Option<AccessLogConfig>,
+
+    /// Admin 操作审计日志（可选，每行以 HMAC 链接，可检测事后篡改）
+    #[serde(default)]
+    pub audit_log: Option<AuditLogConfig>,
+
+    /// 输入上下文窗口上限（tokens），各模型的上下文窗口不超过该值（`modelLimits` 显式覆盖的除外），
+    /// 超出时按 `compaction_strategy` 处理历史消息
+    #[serde(default = "default_context_window_tokens")]
+    pub context_window_tokens: u64,
+
+    /// 按模型覆盖内置的最大输出 tokens 与上下文窗口（按顺序匹配）
+    #[serde(default)]
+    pub model_limits: Vec<ModelLimit>,
+
+    /// 上下文超限时的历史压缩策略（默认关闭，由上游返回错误）
+    #[serde(default)]
+    pub compaction_strategy: CompactionStrategy,
+
+    /// 每个 API Key 允许同时进行的对话请求数（可选，未配置时不限制）
+    #[serde(default)]
+    pub max_concurrent_per_key: Option<usize>,
+
+    /// 每个可用凭据允许同时处理的请求数（可选，配置后启用全局准入队列）
+    #[serde(default)]
+    pub max_concurrent_per_credential: Option<usize>,
+
+    /// 准入队列的最大排队数
+    #[serde(default = "default_max_queue_depth")]
+    pub max_queue_depth: usize,
+
+    /// 请求在准入队列中的最长等待时间（秒）
+    #[serde(default = "default_queue_timeout_secs")]
+    pub queue_timeout_secs: u64,
+
+    /// 全局每分钟请求数上限（可选，所有 API Key 共享）
+    #[serde(default)]
+    pub global_rpm: Option<u64>,
+
+    /// 全局每分钟 token 数上限（可选，按输入 + 输出 tokens 计算，所有 API Key 共享）
+    #[serde(default)]
+    pub global_tpm: Option<u64>,
+
+    /// 是否合并同时进行的相同非流式请求（只调用一次上游并共享结果）
+    #[serde(default)]
+    pub dedupe_concurrent_requests: bool,
+
+    /// 日志输出格式（命令行参数 `--log-format` 优先）
+    #[serde(default)]
+    pub log_format: LogFormat,
+
+    /// 日志级别过滤规则（如 `info,kiro_rs=debug`，可选，未配置时使用 `RUST_LOG`）
+    #[serde(default)]
+    pub log_level: Option<String>,
+
+    /// OTLP/HTTP 链路追踪导出地址（如 `http://localhost:4318`，可选，环境变量
+    /// `OTEL_EXPORTER_OTLP_ENDPOINT` 优先）
+    #[serde(default)]
+    pub otlp_endpoint: Option<String>,
+
+    /// 链路追踪中的服务名（环境变量 `OTEL_SERVICE_NAME` 优先）
+    #[serde(default = "default_otlp_service_name")]
+    pub otlp_service_name: String,
+
+    /// Sentry DSN（可选），配置后将 ERROR 级别日志与 panic 上报到 Sentry；环境变量 `SENTRY_DSN` 优先
+    #[serde(default)]
+    pub sentry_dsn: Option<String>,
+
+    /// 上报到 Sentry 的环境名
+    #[serde(default = "default_sentry_environment")]
+    pub sentry_environment: String,
+
+    /// 链路追踪与日志采样
+    #[serde(default)]
+    pub sampling: SamplingConfig,
+
+    /// 是否在响应头中返回凭据、上游耗时、首 token 耗时与重试次数
+    #[serde(default)]
+    pub performance_headers: bool,
+
+    /// 模型到凭据标签的路由规则（按顺序匹配第一条，未匹配的模型可使用任意凭据）
+    #[serde(default)]
+    pub model_routes: Vec<ModelRoute>,
+
+    /// 转发给上游的客户端请求头白名单（支持 `*` 通配符）
+    #[serde(default)]
+    pub forward_request_headers: Vec<String>,
+
+    /// 返回给客户端的上游响应头白名单（支持 `*` 通配符）
+    #[serde(default)]
+    pub expose_response_headers: Vec<String>,
+
+    /// 是否将终端用户标识的哈希值通过 `x-kiro-end-user` 请求头转发给上游
+    #[serde(default)]
+    pub forward_end_user_hash: bool,
+
+    /// 服务端提示词预设（名称 -> 预设），通过模型名 `kiro:<名称>` 或请求头 `x-kiro-preset` 选择
+    #[serde(default)]
+    pub presets: HashMap<String, PromptPreset>,
+
+    /// 请求改写规则，按顺序作用于对话端点的原始 JSON 请求体
+    #[serde(default)]
+    pub request_rules: Vec<RequestRule>,
+
+    /// 关键词护栏（拒绝命中屏蔽规则的请求，可选检查模型输出）
+    #[serde(default)]
+    pub guardrails: GuardrailConfig,
+
+    /// 外部内容审核接口（可选）
+    #[serde(default)]
+    pub moderation: Option<ModerationConfig>,
+
+    /// 对话请求体的最大字节数，超出时返回 413
+    #[serde(default = "default_max_request_body_bytes")]
+    pub max_request_body_bytes: usize,
+
+    /// 请求头的最大总字节数（名称与值之和），超出时返回 431
+    #[serde(default = "default_max_request_header_bytes")]
+    pub max_request_header_bytes: usize,
+
+    /// 非流式请求缓冲上游响应的最大字节数，超出时中止并返回 502
+    #[serde(default = "default_max_buffered_response_bytes")]
+    pub max_buffered_response_bytes: usize,
+
+    /// 对话请求中消息数组的最大长度（可选），超出时返回 413
+    #[serde(default)]
+    pub max_messages: Option<usize>,
+
+    /// 流式增量合并窗口（毫秒，可选，未配置时逐条转发上游增量）
+    #[serde(default)]
+    pub stream_coalesce_ms: Option<u64>,
+
+    /// 合并后的增量达到该字符数时立即输出
+    #[serde(default = "default_stream_coalesce_chars")]
+    pub stream_coalesce_chars: usize,
+
+    /// 受信任的反向代理（IP、CIDR 或 `unix`），只有来自这些地址的请求才采信
+    /// `X-Forwarded-For` / `X-Real-IP`
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+
+    /// 对话端点的客户端 IP 访问规则
+    #[serde(default)]
+    pub ip_filter: IpFilterConfig,
+
+    /// 按客户端 IP 的限流（可选，作用于 `/v1` 路径）
+    #[serde(default)]
+    pub ip_rate_limit: Option<IpRateLimitConfig>,
+
+    /// 按客户端 IP 的认证失败锁定（可选，同时作用于代理 API 与 Admin API）
+    #[serde(default)]
+    pub auth_lockout: Option<AuthLockoutConfig>,
+
+    /// CORS 策略（可选，同时作用于代理 API 与 Admin API；未配置时代理 API 允许任何来源，Admin API 不返回 CORS 头）
+    #[serde(default)]
+    pub cors: Option<CorsConfig>,
+
+    /// 当前使用的 profile（由 `--profile` 或 `KIRO_PROFILE` 选择，不从配置文件读取）
+    #[serde(skip)]
+    pub profile: Option<String>,
 }
 
-fn default_host() -> String {
-    "127.0.0.1".to_string()
+/// 模型路由规则
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelRoute {
+    /// 模型名称模式，支持 `*` 通配符（如 `claude-opus-*`）
+    pub model: String,
+    /// 允许使用的凭据标签，凭据带有其中任一标签即可
+    pub tags: Vec<String>,
 }
 
-fn default_port() -> u16 {
-    8080
+impl ModelRoute {
+    /// 判断模型名称是否匹配本规则
+    pub fn matches(&self, model: &str) -> bool {
+        wildcard_match(&self.model, model)
+    }
 }
 
-fn default_region() -> String {
-    "us-east-1".to_string()
+/// 模型限制覆盖
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelLimit {
+    /// 模型名称模式，支持 `*` 通配符
+    pub model: String,
+    /// 最大输出 tokens
+    #[serde(default)]
+    pub max_output_tokens: Option<i32>,
+    /// 上下文窗口（tokens）
+    #[serde(default)]
+    pub context_window: Option<u64>,
 }
 
-fn default_kiro_version() -> String {
-    "0.8.0".to_string()
+/// 简单通配符匹配（仅支持 `*`，匹配任意长度字符）
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // 无通配符，需完全相等
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
 }
 
-fn default_system_version() -> String {
-    const SYSTEM_VERSIONS: &[&str] = &["darwin#24.6.0", "win32#10.0.22631"];
-    SYSTEM_VERSIONS[fastrand::usize(..SYSTEM_VERSIONS.len())].to_string()
+/// 请求改写规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestRule {
+    /// 生效的路由（如 `/v1/messages`，支持 `*` 通配），为空时对所有对话端点生效
+    #[serde(default)]
+    pub routes: Vec<String>,
+    /// 生效的 API Key，为空时对所有 Key 生效
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+    /// 改写动作
+    #[serde(flatten)]
+    pub action: RuleAction,
 }
 
-fn default_node_version() -> String {
-    "22.21.1".to_string()
+/// 请求改写动作
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum RuleAction {
+    /// 对消息与系统提示中的文本做正则替换（替换串支持 `$1`、`${name}` 引用分组）
+    RegexReplace {
+        pattern: String,
+        replacement: String,
+    },
+    /// 删除字段，路径以 `.` 分隔，`*` 匹配数组元素或对象的任意键
+    StripFields { fields: Vec<String> },
+    /// 向 `messages`（Responses API 为数组形式的 `input`）注入一条消息
+    InjectMessage {
+        role: String,
+        content: String,
+        #[serde(default)]
+        position: InjectPosition,
+    },
 }
 
-fn default_count_tokens_auth_type() -> String {
-    "x-api-key".to_string()
+/// 注入消息的位置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum InjectPosition {
+    /// 插入到最前面
+    Start,
+    /// 追加到末尾
+    #[default]
+    End,
 }
 
-impl Default for Config {
+/// 关键词护栏配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GuardrailConfig {
+    /// 屏蔽规则（正则表达式，可用 `(?i)` 忽略大小写），为空时不启用护栏
+    #[serde(default)]
+    pub blocked_patterns: Vec<String>,
+    /// 是否同时检查模型输出
+    #[serde(default)]
+    pub scan_output: bool,
+    /// 输出命中屏蔽规则时的处理方式
+    #[serde(default)]
+    pub output_action: GuardrailAction,
+}
+
+/// 客户端 IP 访问规则（IP 或 CIDR），先检查拒绝列表，允许列表非空时只放行其中的地址
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpFilterConfig {
+    /// 允许的地址（为空时不限）
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// 拒绝的地址
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+/// 按客户端 IP 的限流配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpRateLimitConfig {
+    /// 每个客户端 IP 每分钟的请求数
+    pub requests_per_minute: u64,
+    /// 突发容量（未配置时等于 `requestsPerMinute`）
+    #[serde(default)]
+    pub burst: Option<u64>,
+    /// 连续被限流达到该次数后封禁（未配置时不封禁）
+    #[serde(default)]
+    pub ban_after: Option<u32>,
+    /// 封禁时长（秒）
+    #[serde(default = "default_ip_ban_secs")]
+    pub ban_secs: u64,
+}
+
+/// 认证失败锁定配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthLockoutConfig {
+    /// 统计窗口内允许的失败次数，达到后锁定
+    pub max_failures: u32,
+    /// 统计窗口（秒）
+    #[serde(default = "default_auth_window_secs")]
+    pub window_secs: u64,
+    /// 锁定时长（秒）
+    #[serde(default = "default_auth_lockout_secs")]
+    pub lockout_secs: u64,
+}
+
+/// CORS 策略
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorsConfig {
+    /// 允许的来源（如 `https://app.example.com`），支持 `*` 通配符（如 `https://*.example.com`），
+    /// 为空时不允许跨域请求
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// 允许的请求头（为空时允许任何请求头）
+    #[serde(default)]
+    pub allowed_headers: Vec<String>,
+    /// 是否允许携带凭证（Cookie、HTTP 认证）
+    #[serde(default)]
+    pub allow_credentials: bool,
+    /// 预检请求结果的缓存时间（秒）
+    #[serde(default)]
+    pub max_age_secs: Option<u64>,
+}
+
+/// 输出命中护栏时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GuardrailAction {
+    /// 截断输出，以 `refusal` 结束消息
+    #[default]
+    Halt,
+    /// 仅记录，不修改输出
+    Flag,
+}
+
+/// 外部内容审核接口配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModerationConfig {
+    /// 审核接口地址
+    pub url: String,
+    /// 审核接口密钥（可选，以 `Authorization: Bearer` 发送）
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// 单次审核的超时时间（毫秒）
+    #[serde(default = "default_moderation_timeout_ms")]
+    pub timeout_ms: u64,
+    /// 审核接口超时或出错时是否拒绝请求（默认放行）
+    #[serde(default)]
+    pub fail_closed: bool,
+    /// 是否同时审核非流式响应的模型输出
+    #[serde(default)]
+    pub scan_output: bool,
+}
+
+/// 链路追踪与日志采样配置（失败的请求始终导出与记录）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SamplingConfig {
+    /// 成功请求导出链路的比例（0 ~ 1）
+    #[serde(default = "default_sample_rate")]
+    pub trace_rate: f64,
+    /// 成功请求输出请求完成日志的比例（0 ~ 1）
+    #[serde(default = "default_sample_rate")]
+    pub log_rate: f64,
+    /// 是否记录请求体与响应体（与请求完成日志使用同一采样决定，写入 `databasePath` 数据库）
+    #[serde(default)]
+    pub log_bodies: bool,
+    /// 请求体与响应体各自记录的最大字节数，超出部分截断
+    #[serde(default = "default_max_logged_body_bytes")]
+    pub max_body_bytes: usize,
+}
+
+impl Default for SamplingConfig {
     fn default() -> Self {
         Self {
-            host: default_host(),
-            port: default_port(),
-            region: default_region(),
-            kiro_version: default_kiro_version(),
-            machine_id: None,
-            api_key: None,
-            system_version: default_system_version(),
-            node_version: default_node_version(),
-            count_tokens_api_url: None,
-            count_tokens_api_key: None,
-            count_tokens_auth_type: default_count_tokens_auth_type(),
-            proxy_url: None,
-            proxy_username: None,
-            proxy_password: None,
-            admin_api_key: None,
-            base_path: None,
+            trace_rate: default_sample_rate(),
+            log_rate: default_sample_rate(),
+            log_bodies: false,
+            max_body_bytes: default_max_logged_body_bytes(),
         }
     }
 }
 
-impl Config {
-    /// 获取默认配置文件路径
-    pub fn default_config_path() -> &'static str {
-        "config.json"
-    }
+/// 每日用量报告配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageReportConfig {
+    /// 每天生成前一天报告的时刻（UTC 小时，0 ~ 23）
+    #[serde(default)]
+    pub hour_utc: u32,
+    /// 报告保存目录（可选，未配置时仅保存在内存中）
+    #[serde(default)]
+    pub dir: Option<String>,
+    /// 报告生成后推送的 Webhook 地址（可选），请求体为 `{"text": 摘要, "report": 报告}`
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+}
 
-    /// 从文件加载配置
-    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
-        let path = path.as_ref();
-        if !path.exists() {
-            // 配置文件不存在，返回默认配置
-            return Ok(Self::default());
-        }
+/// 错误率告警配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertConfig {
+    /// 触发告警的错误率（0 ~ 1）
+    #[serde(default = "default_alert_error_rate_threshold")]
+    pub error_rate_threshold: f64,
+    /// 滚动窗口时长（秒）
+    #[serde(default = "default_alert_window_secs")]
+    pub window_secs: u64,
+    /// 窗口内至少有多少个请求才评估错误率
+    #[serde(default = "default_alert_min_requests")]
+    pub min_requests: u64,
+}
+
+/// 上游熔断配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CircuitBreakerConfig {
+    /// 触发熔断的失败占比（0 ~ 1，网络错误、5xx 与 408 计为失败）
+    #[serde(default = "default_circuit_failure_ratio")]
+    pub failure_ratio: f64,
+    /// 滚动窗口时长（秒）
+    #[serde(default = "default_circuit_window_secs")]
+    pub window_secs: u64,
+    /// 窗口内至少有多少次上游调用才评估失败占比
+    #[serde(default = "default_circuit_min_requests")]
+    pub min_requests: u64,
+    /// 熔断持续时间（秒），之后放行一个探测请求
+    #[serde(default = "default_circuit_open_secs")]
+    pub open_secs: u64,
+}
+
+/// 非流式请求对冲配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HedgingConfig {
+    /// 以最近非流式请求耗时的该分位数作为耗时预算（0 ~ 1）
+    #[serde(default = "default_hedging_percentile")]
+    pub percentile: f64,
+    /// 耗时预算下限（毫秒）
+    #[serde(default = "default_hedging_min_delay_ms")]
+    pub min_delay_ms: u64,
+    /// 至少积累多少个耗时样本才开始对冲
+    #[serde(default = "default_hedging_min_samples")]
+    pub min_samples: usize,
+}
+
+/// 自适应并发上限配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdaptiveConcurrencyConfig {
+    /// 初始并发上限
+    #[serde(default = "default_adaptive_initial_limit")]
+    pub initial_limit: usize,
+    /// 并发上限的下限
+    #[serde(default = "default_adaptive_min_limit")]
+    pub min_limit: usize,
+    /// 并发上限的上限
+    #[serde(default = "default_adaptive_max_limit")]
+    pub max_limit: usize,
+    /// 拥塞时上限乘以的系数（0 ~ 1）
+    #[serde(default = "default_adaptive_backoff_ratio")]
+    pub backoff_ratio: f64,
+    /// 响应耗时超过基线的多少倍视为耗时突增
+    #[serde(default = "default_adaptive_latency_tolerance")]
+    pub latency_tolerance: f64,
+}
+
+/// 全局重试预算配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryBudgetConfig {
+    /// 窗口内允许的重试次数占请求数的比例
+    #[serde(default = "default_retry_budget_ratio")]
+    pub ratio: f64,
+    /// 滚动窗口时长（秒）
+    #[serde(default = "default_retry_budget_window_secs")]
+    pub window_secs: u64,
+    /// 窗口内保底允许的重试次数（低流量时仍可重试）
+    #[serde(default = "default_retry_budget_min_retries")]
+    pub min_retries: u64,
+}
+
+/// 凭据生命周期 Webhook 配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialWebhookConfig {
+    /// 推送地址
+    pub url: String,
+    /// 请求体签名密钥（可选）
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// 额度用量占比达到该值时推送（0 ~ 1）
+    #[serde(default = "default_webhook_quota_threshold")]
+    pub quota_threshold: f64,
+    /// Token 连续刷新失败多少次时推送
+    #[serde(default = "default_webhook_refresh_failures")]
+    pub refresh_failures: u32,
+    /// 可用凭据数低于该值时推送
+    #[serde(default = "default_webhook_min_available")]
+    pub min_available: usize,
+}
+
+/// Telegram 通知配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelegramConfig {
+    /// Bot Token（由 @BotFather 创建机器人时获得）
+    pub bot_token: String,
+    /// 接收消息的会话 ID（用户、群组或频道）
+    pub chat_id: String,
+}
+
+/// Discord 通知配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscordConfig {
+    /// 频道 Webhook 地址（频道设置 → 整合 → Webhook）
+    pub webhook_url: String,
+    /// 推送的事件类型（为空表示全部），如 `["credential_disabled", "pool_degraded"]`
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+/// 资源压力降载配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadSheddingConfig {
+    /// 事件循环延迟阈值（毫秒）
+    #[serde(default = "default_max_event_loop_lag_ms")]
+    pub max_event_loop_lag_ms: u64,
+    /// 进程常驻内存阈值（MB，不配置时不按内存降载）
+    #[serde(default)]
+    pub max_memory_mb: Option<u64>,
+}
+
+/// Unix 域套接字监听地址的前缀
+pub const UNIX_ADDRESS_PREFIX: &str = "unix:";
+
+/// 监听地址配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListenerConfig {
+    /// 监听地址，如 `127.0.0.1:8080`、`0.0.0.0:8443`、`[::]:8080`，
+    /// 或以 `unix:` 开头的 Unix 域套接字路径，如 `unix:/run/kiro-rs/kiro.sock`
+    pub address: String,
+    /// 启用的路由组（默认全部）
+    #[serde(default = "RouteGroup::all")]
+    pub routes: Vec<RouteGroup>,
+    /// IPv6 地址是否同时接受 IPv4 连接（双栈）
+    #[serde(default = "default_dual_stack")]
+    pub dual_stack: bool,
+    /// TLS 终止（可选，未配置时为明文 HTTP）
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Unix 域套接字文件权限（八进制，如 `"660"`，未配置时由 umask 决定）
+    #[serde(default)]
+    pub socket_mode: Option<String>,
+}
+
+impl ListenerConfig {
+    /// Unix 域套接字路径（地址不以 `unix:` 开头时为 `None`）
+    pub fn unix_path(&self) -> Option<&str> {
+        self.address.strip_prefix(UNIX_ADDRESS_PREFIX)
+    }
+}
+
+/// TLS 证书配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsConfig {
+    /// 证书链文件（PEM）
+    pub cert_path: String,
+    /// 私钥文件（PEM，支持 PKCS#8 / PKCS#1 / SEC1）
+    pub key_path: String,
+    /// 检查证书文件变化的间隔（秒），0 表示不自动重新加载
+    #[serde(default = "default_tls_reload_interval_secs")]
+    pub reload_interval_secs: u64,
+    /// 要求客户端证书（可选，未配置时不校验客户端身份）
+    #[serde(default)]
+    pub client_auth: Option<ClientAuthConfig>,
+}
+
+/// 客户端证书认证（mTLS）配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientAuthConfig {
+    /// 签发客户端证书的 CA 证书文件（PEM，可包含多个证书）
+    pub ca_path: String,
+    /// 允许的客户端证书主体（匹配 CN 或 SAN 中的 DNS 名称，支持 `*` 通配符；为空时接受 CA 签发的任何证书）
+    #[serde(default)]
+    pub allowed_subjects: Vec<String>,
+}
+
+/// 上游端点地址模板
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointsConfig {
+    /// Kiro API（生成请求与额度查询）
+    #[serde(default = "default_api_endpoint")]
+    pub api: String,
+    /// Social 凭据的 Token 刷新
+    #[serde(default = "default_social_auth_endpoint")]
+    pub social_auth: String,
+    /// AWS SSO OIDC（IdC 凭据的 Token 刷新与设备授权）
+    #[serde(default = "default_oidc_endpoint")]
+    pub oidc: String,
+}
+
+impl Default for EndpointsConfig {
+    fn default() -> Self {
+        Self {
+            api: default_api_endpoint(),
+            social_auth: default_social_auth_endpoint(),
+            oidc: default_oidc_endpoint(),
+        }
+    }
+}
+
+impl EndpointsConfig {
+    /// Kiro API 基础地址
+    pub fn api(&self, region: &str) -> String {
+        expand_endpoint(&self.api, region)
+    }
+
+    /// Social Token 刷新基础地址
+    pub fn social_auth(&self, region: &str) -> String {
+        expand_endpoint(&self.social_auth, region)
+    }
+
+    /// AWS SSO OIDC 基础地址
+    pub fn oidc(&self, region: &str) -> String {
+        expand_endpoint(&self.oidc, region)
+    }
+}
+
+fn expand_endpoint(template: &str, region: &str) -> String {
+    template
+        .replace("{region}", region)
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// 将路径前缀规范化为 `/a/b` 形式（空前缀或 `/` 为空字符串）
+fn normalize_prefix(prefix: &str) -> String {
+    let prefix = prefix.trim().trim_matches('/');
+    if prefix.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", prefix)
+    }
+}
+
+/// 基础地址的 `host[:port]`（用于 Host 请求头）
+pub fn endpoint_host(base: &str) -> String {
+    match reqwest::Url::parse(base) {
+        Ok(url) => match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            _ => String::new(),
+        },
+        Err(_) => String::new(),
+    }
+}
+
+/// 上游连接池选项
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpstreamPoolConfig {
+    /// 每个主机保留的最大空闲连接数（不配置时不限制）
+    #[serde(default)]
+    pub max_idle_per_host: Option<usize>,
+    /// 空闲连接的保留时间（秒，0 表示不超时）
+    #[serde(default = "default_pool_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+    /// TCP keepalive 间隔（秒，0 表示关闭）
+    #[serde(default = "default_tcp_keepalive_secs")]
+    pub tcp_keepalive_secs: u64,
+    /// 复用 Kiro API 连接（默认每个请求携带 `Connection: close`，与 Kiro IDE 一致）
+    #[serde(default)]
+    pub reuse_connections: bool,
+    /// 通过 ALPN 协商 HTTP/2（多个请求复用同一连接，隐含 `reuseConnections`）
+    #[serde(default)]
+    pub http2: bool,
+}
+
+impl Default for UpstreamPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: None,
+            idle_timeout_secs: default_pool_idle_timeout_secs(),
+            tcp_keepalive_secs: default_tcp_keepalive_secs(),
+            reuse_connections: false,
+            http2: false,
+        }
+    }
+}
+
+impl UpstreamPoolConfig {
+    /// Kiro API 请求是否保持连接
+    pub fn keeps_alive(&self) -> bool {
+        self.reuse_connections || self.http2
+    }
+}
+
+/// 上游请求的分阶段超时（秒）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeoutsConfig {
+    /// 建立 TCP / TLS 连接
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_secs: u64,
+    /// 从开始请求到写完请求体（含建立连接）
+    #[serde(default = "default_write_timeout_secs")]
+    pub write_secs: u64,
+    /// 流式请求：写完请求体后等待响应头
+    #[serde(default = "default_stream_first_byte_timeout_secs")]
+    pub stream_first_byte_secs: u64,
+    /// 流式请求：整个请求（含读取完整响应流）
+    #[serde(default = "default_stream_total_timeout_secs")]
+    pub stream_total_secs: u64,
+    /// 非流式请求：写完请求体后等待响应头
+    #[serde(default = "default_non_stream_first_byte_timeout_secs")]
+    pub non_stream_first_byte_secs: u64,
+    /// 非流式请求：整个请求
+    #[serde(default = "default_non_stream_total_timeout_secs")]
+    pub non_stream_total_secs: u64,
+    /// Token 刷新：写完请求体后等待响应头
+    #[serde(default = "default_refresh_first_byte_timeout_secs")]
+    pub refresh_first_byte_secs: u64,
+    /// Token 刷新：整个请求
+    #[serde(default = "default_refresh_total_timeout_secs")]
+    pub refresh_total_secs: u64,
+}
+
+impl Default for TimeoutsConfig {
+    fn default() -> Self {
+        Self {
+            connect_secs: default_connect_timeout_secs(),
+            write_secs: default_write_timeout_secs(),
+            stream_first_byte_secs: default_stream_first_byte_timeout_secs(),
+            stream_total_secs: default_stream_total_timeout_secs(),
+            non_stream_first_byte_secs: default_non_stream_first_byte_timeout_secs(),
+            non_stream_total_secs: default_non_stream_total_timeout_secs(),
+            refresh_first_byte_secs: default_refresh_first_byte_timeout_secs(),
+            refresh_total_secs: default_refresh_total_timeout_secs(),
+        }
+    }
+}
+
+impl TimeoutsConfig {
+    /// Kiro API 请求的超时
+    pub fn api(&self, is_stream: bool) -> RequestTimeouts {
+        if is_stream {
+            RequestTimeouts::from_secs(
+                self.write_secs,
+                self.stream_first_byte_secs,
+                self.stream_total_secs,
+            )
+        } else {
+            RequestTimeouts::from_secs(
+                self.write_secs,
+                self.non_stream_first_byte_secs,
+                self.non_stream_total_secs,
+            )
+        }
+    }
+
+    /// Token 刷新请求的超时
+    pub fn refresh(&self) -> RequestTimeouts {
+        RequestTimeouts::from_secs(
+            self.write_secs,
+            self.refresh_first_byte_secs,
+            self.refresh_total_secs,
+        )
+    }
+}
+
+/// 单个请求的分阶段超时
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestTimeouts {
+    pub write: Duration,
+    pub first_byte: Duration,
+    pub total: Duration,
+}
+
+impl RequestTimeouts {
+    fn from_secs(write: u64, first_byte: u64, total: u64) -> Self {
+        Self {
+            write: Duration::from_secs(write),
+            first_byte: Duration::from_secs(first_byte),
+            total: Duration::from_secs(total),
+        }
+    }
+}
+
+/// 上游 HTTPS 连接的证书选项
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpstreamTlsConfig {
+    /// 额外信任的根证书（PEM，可包含多个证书），如企业代理的 CA
+    #[serde(default)]
+    pub ca_cert_paths: Vec<String>,
+    /// 跳过证书校验的上游端点（不安全，仅用于排查问题）
+    #[serde(default)]
+    pub insecure_skip_verify: Vec<UpstreamEndpoint>,
+}
+
+/// 上游端点
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UpstreamEndpoint {
+    /// Kiro API（生成请求与额度查询）
+    Api,
+    /// Token 刷新与设备授权（Kiro 认证服务与 AWS SSO OIDC）
+    Auth,
+    /// 外部 count_tokens API
+    CountTokens,
+    /// 外部集成（OIDC、Webhook、Telegram/Discord 通知、错误上报、内容审核、遥测导出与用量报告推送）
+    Integrations,
+}
+
+/// 可按监听地址启用的路由组
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RouteGroup {
+    /// `/v1` 下的 API 端点
+    Api,
+    /// Admin API 与 Admin UI
+    Admin,
+    /// `/metrics`
+    Metrics,
+    /// `/health`、`/livez`、`/readyz`、`/health/deep`
+    Health,
+}
+
+impl RouteGroup {
+    pub fn all() -> Vec<RouteGroup> {
+        vec![Self::Api, Self::Admin, Self::Metrics, Self::Health]
+    }
+}
+
+/// 访问日志配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessLogConfig {
+    /// 访问日志文件路径
+    pub path: String,
+    /// 单个文件的大小上限（MB），超出时轮转，0 表示不限制
+    #[serde(default = "default_access_log_max_size_mb")]
+    pub max_size_mb: u64,
+    /// 按时间轮转的周期
+    #[serde(default)]
+    pub rotation: AccessLogRotation,
+    /// 保留的历史文件数
+    #[serde(default = "default_access_log_max_files")]
+    pub max_files: usize,
+}
+
+/// Admin 操作审计日志配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogConfig {
+    /// 审计日志文件路径（JSON Lines）
+    pub path: String,
+    /// 计算链式 HMAC 的密钥，校验时需使用同一密钥
+    pub hmac_key: String,
+}
+
+/// Admin UI 的 OIDC 单点登录配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminOidcConfig {
+    /// 身份提供方的 Issuer（从 `{issuer}/.well-known/openid-configuration` 发现各端点）
+    pub issuer: String,
+    pub client_id: String,
+    /// 机密客户端的 Client Secret（公共客户端只使用 PKCE，可不填）
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    /// 在身份提供方登记的回调地址，指向 `{basePath}{pathPrefix}/api/admin/oidc/callback`
+    pub redirect_url: String,
+    /// 请求的 scope（需要包含 `claim` 所在的 scope）
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+    /// 用于授权的 ID Token 声明，支持以 `.` 访问嵌套字段（如 `realm_access.roles`）
+    #[serde(default = "default_oidc_claim")]
+    pub claim: String,
+    /// 声明取值（字符串或字符串数组）包含其中任一项时授予 Admin 权限
+    pub allowed_values: Vec<String>,
+    /// 会话的空闲超时（秒），超过该时间未使用需重新登录
+    #[serde(default = "default_session_idle_timeout_secs")]
+    pub session_idle_timeout_secs: u64,
+    /// 会话自登录起的最长有效期（秒），到期后即使一直在使用也需重新登录
+    #[serde(default = "default_session_max_age_secs")]
+    pub session_max_age_secs: u64,
+}
+
+/// 访问日志按时间轮转的周期
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AccessLogRotation {
+    /// 每天
+    #[default]
+    Daily,
+    /// 每小时
+    Hourly,
+    /// 仅按大小轮转
+    Never,
+}
+
+/// 服务端提示词预设
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptPreset {
+    /// 系统提示词（放在客户端系统提示之前）
+    #[serde(default)]
+    pub system: Option<String>,
+    /// 实际使用的模型（通过模型名选择预设时必填）
+    #[serde(default)]
+    pub model: Option<String>,
+    /// 覆盖客户端的 max_tokens
+    #[serde(default)]
+    pub max_tokens: Option<i32>,
+    /// 覆盖客户端的 temperature（0~1）
+    #[serde(default)]
+    pub temperature: Option<f32>,
+}
+
+/// 额外的下游 API Key 配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyConfig {
+    /// API Key
+    pub key: String,
+    /// 调度优先级（凭据池饱和时高优先级请求先出队）
+    #[serde(default)]
+    pub priority: Priority,
+    /// 允许使用的模型，支持 `*` 通配符（为空时不限）
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// 允许访问的端点（如 `/v1/messages`、`/v1/batches*`），支持 `*` 通配符（为空时不限）
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+    /// 是否允许流式请求
+    #[serde(default = "default_allow_stream")]
+    pub stream: bool,
+    /// 是否允许以只读方式访问 Admin API（仅 GET 请求）
+    #[serde(default)]
+    pub admin_read_only: bool,
+}
+
+impl ApiKeyConfig {
+    /// 是否允许访问该端点（不含 `pathPrefix`）
+    pub fn allows_endpoint(&self, route: &str) -> bool {
+        self.endpoints.is_empty() || self.endpoints.iter().any(|e| wildcard_match(e, route))
+    }
+
+    /// 是否允许使用该模型
+    pub fn allows_model(&self, model: &str) -> bool {
+        self.models.is_empty() || self.models.iter().any(|m| wildcard_match(m, model))
+    }
+
+    /// 是否需要检查请求体中的模型与流式参数
+    pub fn restricts_body(&self) -> bool {
+        !self.models.is_empty() || !self.stream
+    }
+}
+
+/// 请求调度优先级
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Priority {
+    /// 批处理任务
+    Low,
+    /// 默认
+    #[default]
+    Normal,
+    /// 交互式用户
+    High,
+}
+
+/// 日志输出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum LogFormat {
+    /// 可读文本
+    #[default]
+    Pretty,
+    /// 每行一个 JSON 对象
+    Json,
+}
+
+/// 历史消息压缩策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CompactionStrategy {
+    /// 不压缩
+    #[default]
+    Off,
+    /// 丢弃最早的对话轮次
+    DropOldest,
+    /// 丢弃最早的对话轮次，并以摘录形式保留其要点
+    Summarize,
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    ["openid", "email", "profile"].map(String::from).to_vec()
+}
+
+fn default_oidc_claim() -> String {
+    "groups".to_string()
+}
+
+pub(crate) fn default_session_idle_timeout_secs() -> u64 {
+    3600
+}
+
+pub(crate) fn default_session_max_age_secs() -> u64 {
+    12 * 3600
+}
+
+fn default_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_port() -> u16 {
+    8080
+}
+
+fn default_dual_stack() -> bool {
+    true
+}
+
+fn default_tls_reload_interval_secs() -> u64 {
+    30
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_pool_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_tcp_keepalive_secs() -> u64 {
+    15
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    10
+}
+
+fn default_write_timeout_secs() -> u64 {
+    30
+}
+
+fn default_stream_first_byte_timeout_secs() -> u64 {
+    120
+}
+
+fn default_stream_total_timeout_secs() -> u64 {
+    1800
+}
+
+fn default_non_stream_first_byte_timeout_secs() -> u64 {
+    300
+}
+
+fn default_non_stream_total_timeout_secs() -> u64 {
+    720
+}
+
+fn default_refresh_first_byte_timeout_secs() -> u64 {
+    30
+}
+
+fn default_refresh_total_timeout_secs() -> u64 {
+    60
+}
+
+fn default_api_endpoint() -> String {
+    "https://q.{region}.amazonaws.com".to_string()
+}
+
+fn default_social_auth_endpoint() -> String {
+    "https://prod.{region}.auth.desktop.kiro.dev".to_string()
+}
+
+fn default_oidc_endpoint() -> String {
+    "https://oidc.{region}.amazonaws.com".to_string()
+}
+
+fn default_kiro_version() -> String {
+    "0.8.0".to_string()
+}
+
+fn default_system_version() -> String {
+    const SYSTEM_VERSIONS: &[&str] = &["darwin#24.6.0", "win32#10.0.22631"];
+    SYSTEM_VERSIONS[fastrand::usize(..SYSTEM_VERSIONS.len())].to_string()
+}
+
+fn default_node_version() -> String {
+    "22.21.1".to_string()
+}
+
+fn default_count_tokens_auth_type() -> String {
+    "x-api-key".to_string()
+}
+
+fn default_context_window_tokens() -> u64 {
+    200_000
+}
+
+fn default_max_queue_depth() -> usize {
+    100
+}
+
+fn default_queue_timeout_secs() -> u64 {
+    30
+}
+
+fn default_shutdown_grace_period_secs() -> u64 {
+    30
+}
+
+fn default_drain_delay_secs() -> u64 {
+    5
+}
+
+fn default_warm_up_concurrency() -> usize {
+    8
+}
+
+fn default_sentry_environment() -> String {
+    "production".to_string()
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+fn default_max_logged_body_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_otlp_service_name() -> String {
+    "kiro-rs".to_string()
+}
+
+fn default_max_request_body_bytes() -> usize {
+    10 * 1024 * 1024
+}
+
+fn default_max_request_header_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_max_buffered_response_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_alert_error_rate_threshold() -> f64 {
+    0.25
+}
+
+fn default_alert_window_secs() -> u64 {
+    300
+}
+
+fn default_alert_min_requests() -> u64 {
+    20
+}
+
+fn default_circuit_failure_ratio() -> f64 {
+    0.9
+}
+
+fn default_circuit_window_secs() -> u64 {
+    60
+}
+
+fn default_circuit_min_requests() -> u64 {
+    20
+}
+
+fn default_circuit_open_secs() -> u64 {
+    30
+}
+
+fn default_hedging_percentile() -> f64 {
+    0.95
+}
+
+fn default_hedging_min_delay_ms() -> u64 {
+    2000
+}
+
+fn default_hedging_min_samples() -> usize {
+    20
+}
+
+fn default_adaptive_initial_limit() -> usize {
+    16
+}
+
+fn default_adaptive_min_limit() -> usize {
+    1
+}
+
+fn default_adaptive_max_limit() -> usize {
+    256
+}
+
+fn default_adaptive_backoff_ratio() -> f64 {
+    0.7
+}
+
+fn default_retry_budget_ratio() -> f64 {
+    0.2
+}
+
+fn default_retry_budget_window_secs() -> u64 {
+    60
+}
+
+fn default_retry_budget_min_retries() -> u64 {
+    10
+}
+
+pub(crate) fn default_webhook_quota_threshold() -> f64 {
+    0.9
+}
+
+pub(crate) fn default_webhook_refresh_failures() -> u32 {
+    3
+}
+
+pub(crate) fn default_webhook_min_available() -> usize {
+    1
+}
+
+fn default_max_event_loop_lag_ms() -> u64 {
+    200
+}
+
+fn default_adaptive_latency_tolerance() -> f64 {
+    3.0
+}
+
+fn default_access_log_max_size_mb() -> u64 {
+    100
+}
+
+fn default_access_log_max_files() -> usize {
+    7
+}
+
+fn default_moderation_timeout_ms() -> u64 {
+    3000
+}
+
+fn default_ip_ban_secs() -> u64 {
+    600
+}
+
+fn default_auth_window_secs() -> u64 {
+    300
+}
+
+fn default_auth_lockout_secs() -> u64 {
+    900
+}
+
+fn default_allow_stream() -> bool {
+    true
+}
+
+fn default_stream_coalesce_chars() -> usize {
+    256
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: default_host(),
+            port: default_port(),
+            listeners: Vec::new(),
+            tls: None,
+            region: default_region(),
+            endpoints: EndpointsConfig::default(),
+            kiro_version: default_kiro_version(),
+            machine_id: None,
+            api_key: None,
+            api_keys: Vec::new(),
+            system_version: default_system_version(),
+            node_version: default_node_version(),
+            count_tokens_api_url: None,
+            count_tokens_api_key: None,
+            count_tokens_auth_type: default_count_tokens_auth_type(),
+            proxy_url: None,
+            proxy_username: None,
+            proxy_password: None,
+            admin_api_key: None,
+            admin_api_keys: Vec::new(),
+            admin_oidc: None,
+            base_path: None,
+            path_prefix: None,
+            strip_reasoning: false,
+            usage_log_path: None,
+            database_path: None,
+            usage_retention_days: None,
+            batch_retention_days: None,
+            usage_report: None,
+            slow_request_threshold_ms: None,
+            warm_up_concurrency: default_warm_up_concurrency(),
+            drain_delay_secs: default_drain_delay_secs(),
+            shutdown_grace_period_secs: default_shutdown_grace_period_secs(),
+            alerts: None,
+            circuit_breaker: None,
+            hedging: None,
+            adaptive_concurrency: None,
+            retry_budget: None,
+            credential_webhook: None,
+            telegram: None,
+            discord: None,
+            load_shedding: None,
+            upstream_tls: None,
+            dns_overrides: HashMap::new(),
+            upstream_pool: UpstreamPoolConfig::default(),
+            timeouts: TimeoutsConfig::default(),
+            access_log: None,
+            audit_log: None,
+            context_window_tokens: default_context_window_tokens(),
+            model_limits: Vec::new(),
+            compaction_strategy: CompactionStrategy::default(),
+            max_concurrent_per_key: None,
+            max_concurrent_per_credential: None,
+            max_queue_depth: default_max_queue_depth(),
+            queue_timeout_secs: default_queue_timeout_secs(),
+            global_rpm: None,
+            global_tpm: None,
+            dedupe_concurrent_requests: false,
+            log_format: LogFormat::default(),
+            log_level: None,
+            otlp_endpoint: None,
+            otlp_service_name: default_otlp_service_name(),
+            sentry_dsn: None,
+            sentry_environment: default_sentry_environment(),
+            sampling: SamplingConfig::default(),
+            performance_headers: false,
+            model_routes: Vec::new(),
+            forward_request_headers: Vec::new(),
+            expose_response_headers: Vec::new(),
+            forward_end_user_hash: false,
+            presets: HashMap::new(),
+            request_rules: Vec::new(),
+            guardrails: GuardrailConfig::default(),
+            moderation: None,
+            max_request_body_bytes: default_max_request_body_bytes(),
+            max_request_header_bytes: default_max_request_header_bytes(),
+            max_buffered_response_bytes: default_max_buffered_response_bytes(),
+            max_messages: None,
+            stream_coalesce_ms: None,
+            stream_coalesce_chars: default_stream_coalesce_chars(),
+            trusted_proxies: Vec::new(),
+            ip_filter: IpFilterConfig::default(),
+            ip_rate_limit: None,
+            auth_lockout: None,
+            cors: None,
+            profile: None,
+        }
+    }
+}
+
+impl Config {
+    /// 获取默认配置文件路径：依次查找 `config.json`、`config.toml`、`config.yaml`、`config.yml`，
+    /// 都不存在时为 `config.json`
+    pub fn default_config_path() -> &'static str {
+        DEFAULT_CONFIG_PATHS
+            .iter()
+            .copied()
+            .find(|path| Path::new(path).exists())
+            .unwrap_or(DEFAULT_CONFIG_PATHS[0])
+    }
+
+    /// 生效的监听地址：未配置 `listeners` 时为 `host:port`（`host` 以 `unix:` 开头时为
+    /// Unix 域套接字，忽略 `port`），启用全部路由组
+    pub fn effective_listeners(&self) -> Vec<ListenerConfig> {
+        if !self.listeners.is_empty() {
+            return self.listeners.clone();
+        }
+        let address = if self.host.starts_with(UNIX_ADDRESS_PREFIX) {
+            self.host.clone()
+        } else if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        };
+        vec![ListenerConfig {
+            address,
+            routes: RouteGroup::all(),
+            dual_stack: default_dual_stack(),
+            tls: self.tls.clone(),
+            socket_mode: None,
+        }]
+    }
+
+    /// 规范化的路由前缀：以 `/` 开头、不以 `/` 结尾，未配置时为空字符串
+    pub fn route_prefix(&self) -> String {
+        normalize_prefix(self.path_prefix.as_deref().unwrap_or_default())
+    }
+
+    /// Admin UI 在浏览器中的访问前缀（外部前缀 `basePath` 加上 `pathPrefix`）
+    pub fn public_prefix(&self) -> String {
+        let base_path = normalize_prefix(self.base_path.as_deref().unwrap_or_default());
+        format!("{}{}", base_path, self.route_prefix())
+    }
+
+    /// 凭据实际使用的区域（凭据未配置 `region` 时使用全局区域）
+    pub fn region_for<'a>(&'a self, credentials: &'a KiroCredentials) -> &'a str {
+        credentials.region.as_deref().unwrap_or(&self.region)
+    }
+
+    /// 从文件加载配置（按扩展名解析 JSON、TOML 或 YAML），并应用 `KIRO_` 前缀的环境变量覆盖
+    ///
+    /// 优先级：命令行参数 > 环境变量 > 配置文件 > 默认值
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::load_checked(path).map(|(config, _)| config)
+    }
+
+    /// 同 [`Config::load`]，同时返回配置文件中的警告（如拼错而被忽略的配置项）
+    ///
+    /// 语法或类型错误时一次性报告全部问题及其字段路径
+    pub fn load_checked<P: AsRef<Path>>(path: P) -> anyhow::Result<(Self, Vec<Problem>)> {
+        let path = path.as_ref();
+        let value = if path.exists() {
+            ConfigFormat::from_path(path)
+                .parse(&path.display().to_string(), &fs::read_to_string(path)?)?
+        } else {
+            // 配置文件不存在，使用默认配置
+            serde_json::Value::Object(Default::default())
+        };
+        let profile = active_profile();
+        let (value, warnings) = apply_profile(value, profile.as_deref())?;
+        let mut config = Self::from_value_with_env(value, std::env::vars())?;
+        config.profile = profile;
+        Ok((config, warnings))
+    }
+
+    /// 将环境变量覆盖合并到配置文件内容后反序列化
+    fn from_value_with_env(
+        mut value: serde_json::Value,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> anyhow::Result<Self> {
+        let defaults = serde_json::to_value(Self::default())?;
+        for (name, raw) in vars {
+            let Some(path) = env_key_path(&name) else {
+                continue;
+            };
+            let current = lookup(&value, &path).or_else(|| lookup(&defaults, &path));
+            let parsed = match current {
+                // 字符串字段原样使用，避免 `KIRO_API_KEY=123` 被解析为数字
+                Some(serde_json::Value::String(_)) => serde_json::Value::String(raw),
+                _ => serde_json::from_str(&raw).unwrap_or(serde_json::Value::String(raw)),
+            };
+            set_path(&mut value, &path, parsed);
+        }
+        if secret::contains_encrypted(&value) {
+            let master_key = secret::master_key()?.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "配置中含有 enc: 加密值，但未设置 {} 或 {}",
+                    secret::MASTER_KEY_ENV,
+                    secret::MASTER_KEY_FILE_ENV
+                )
+            })?;
+            secret::decrypt_values(&mut value, &master_key)
+                .map_err(|e| anyhow::anyhow!("解密配置失败: {}", e))?;
+        }
+        let problems = validation::type_errors(&value);
+        if !problems.is_empty() {
+            anyhow::bail!(
+                "配置无效（含环境变量覆盖），共 {} 个问题:\n{}",
+                problems.len(),
+                validation::format_problems(&problems)
+            );
+        }
+        serde_json::from_value(value)
+            .map_err(|e| anyhow::anyhow!("配置无效（含环境变量覆盖）: {}", e))
+    }
+}
+
+/// 未指定 `-c` 时依次查找的配置文件
+const DEFAULT_CONFIG_PATHS: &[&str] = &["config.json", "config.toml", "config.yaml", "config.yml"];
+
+/// 配置文件格式（按扩展名选择，其他扩展名视为 JSON）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// JSON（支持 `//` 与 `/* */` 注释）
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            Some("toml") => Self::Toml,
+            Some("yaml" | "yml") => Self::Yaml,
+            _ => Self::Json,
+        }
+    }
+
+    /// 将配置文件内容解析为与 JSON 配置相同的结构（空的 YAML 文件视为空配置）
+    fn parse(self, file: &str, content: &str) -> anyhow::Result<serde_json::Value> {
+        let result = match self {
+            Self::Json => serde_json::from_str(&strip_comments(content))
+                .map_err(|e| validation::syntax_error(file, &e)),
+            Self::Toml => toml::from_str(content)
+                .map_err(|e| Problem::error(file, format!("TOML 语法错误: {}", e))),
+            Self::Yaml => serde_yaml::from_str(content)
+                .map_err(|e| Problem::error(file, format!("YAML 语法错误: {}", e))),
+        };
+        match result {
+            Ok(serde_json::Value::Null) => Ok(serde_json::Value::Object(Default::default())),
+            Ok(value) => Ok(value),
+            Err(problem) => Err(anyhow::anyhow!(
+                "配置文件无效:\n{}",
+                validation::format_problems(&[problem])
+            )),
+        }
+    }
+}
+
+/// 将配置文件中明文保存的下游 API Key（`apiKey`、`apiKeys[].key`、`requestRules[].apiKeys`，
+/// 含各 profile）替换为加盐哈希，保留注释与格式；返回新的文件内容与替换的 Key 数
+///
+/// 只按字节位置改写上述字段的值，其他位置出现的相同字符串（注释、其他配置项）保持不变；
+/// 同一个 Key 出现在多个字段时使用同一个哈希。
+pub fn hash_plaintext_keys(text: &str) -> anyhow::Result<(String, usize)> {
+    let stripped = strip_comments(text);
+    serde_json::from_str::<serde_json::Value>(&stripped)?;
+
+    let mut hashes: HashMap<String, String> = HashMap::new();
+    let mut migrated = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (path, span) in string_spans(&stripped) {
+        let path: Vec<&str> = path.iter().map(String::as_str).collect();
+        if !is_key_field(&path) {
+            continue;
+        }
+        let key: String = serde_json::from_str(&stripped[span.clone()])?;
+        if key.trim().is_empty() || auth::is_hashed(&key) || secret::is_encrypted(&key) {
+            continue;
+        }
+        if !hashes.contains_key(&key) {
+            let hashed = serde_json::to_string(&auth::hash_api_key(&key))?;
+            hashes.insert(key.clone(), hashed);
+        }
+        migrated.push_str(&text[copied..span.start]);
+        migrated.push_str(&hashes[&key]);
+        copied = span.end;
+    }
+    migrated.push_str(&text[copied..]);
+    Ok((migrated, hashes.len()))
+}
+
+/// 字段路径是否为下游 API Key（数组元素记为 `*`）
+fn is_key_field(path: &[&str]) -> bool {
+    match path {
+        ["apiKey"] | ["apiKeys", "*", "key"] | ["requestRules", "*", "apiKeys", "*"] => true,
+        [PROFILES_KEY, _, rest @ ..] => is_key_field(rest),
+        _ => false,
+    }
+}
+
+/// 列出合法 JSON 文本中每个字符串值的字段路径（数组元素记为 `*`）与字节范围（含引号）
+fn string_spans(text: &str) -> Vec<(Vec<String>, std::ops::Range<usize>)> {
+    struct Scanner<'a> {
+        text: &'a str,
+        pos: usize,
+        path: Vec<String>,
+        spans: Vec<(Vec<String>, std::ops::Range<usize>)>,
+    }
+
+    impl Scanner<'_> {
+        fn peek(&self) -> Option<u8> {
+            self.text.as_bytes().get(self.pos).copied()
+        }
+
+        fn skip_whitespace(&mut self) {
+            while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+                self.pos += 1;
+            }
+        }
+
+        fn string(&mut self) -> std::ops::Range<usize> {
+            let start = self.pos;
+            self.pos += 1;
+            while let Some(byte) = self.peek() {
+                self.pos += 1;
+                match byte {
+                    b'\\' => self.pos += 1,
+                    b'"' => break,
+                    _ => {}
+                }
+            }
+            start..self.pos
+        }
+
+        fn value(&mut self) {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b'{') => {
+                    self.pos += 1;
+                    loop {
+                        self.skip_whitespace();
+                        match self.peek() {
+                            Some(b',') => self.pos += 1,
+                            Some(b'"') => {
+                                let span = self.string();
+                                let name =
+                                    serde_json::from_str(&self.text[span]).unwrap_or_default();
+                                self.skip_whitespace();
+                                // 跳过 `:`
+                                self.pos += 1;
+                                self.path.push(name);
+                                self.value();
+                                self.path.pop();
+                            }
+                            Some(b'}') => {
+                                self.pos += 1;
+                                break;
+                            }
+                            _ => break,
+                        }
+                    }
+                }
+                Some(b'[') => {
+                    self.pos += 1;
+                    loop {
+                        self.skip_whitespace();
+                        match self.peek() {
+                            Some(b',') => self.pos += 1,
+                            Some(b']') => {
+                                self.pos += 1;
+                                break;
+                            }
+                            Some(_) => {
+                                self.path.push("*".to_string());
+                                self.value();
+                                self.path.pop();
+                            }
+                            None => break,
+                        }
+                    }
+                }
+                Some(b'"') => {
+                    let span = self.string();
+                    self.spans.push((self.path.clone(), span));
+                }
+                // 数字、true、false、null
+                Some(_) => {
+                    while !matches!(
+                        self.peek(),
+                        None | Some(b',' | b']' | b'}' | b' ' | b'\t' | b'\n' | b'\r')
+                    ) {
+                        self.pos += 1;
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+
+    let mut scanner = Scanner {
+        text,
+        pos: 0,
+        path: Vec::new(),
+        spans: Vec::new(),
+    };
+    scanner.value();
+    scanner.spans
+}
+
+/// 去掉 `//` 与 `/* */` 注释（字符串内的除外），注释按字节替换为空格，
+/// 保留错误位置的行列号以及其余内容的字节位置
+fn strip_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => {
+                while let Some(&next) = chars.peek() {
+                    if next == '\n' {
+                        break;
+                    }
+                    out.extend(std::iter::repeat_n(' ', next.len_utf8()));
+                    chars.next();
+                }
+                out.push(' ');
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                out.push_str("  ");
+                let mut prev = ' ';
+                for next in chars.by_ref() {
+                    match next {
+                        '\n' => out.push('\n'),
+                        _ => out.extend(std::iter::repeat_n(' ', next.len_utf8())),
+                    }
+                    if prev == '*' && next == '/' {
+                        break;
+                    }
+                    prev = next;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// 环境变量覆盖的前缀
+const ENV_PREFIX: &str = "KIRO_";
+
+/// 选择 profile 的环境变量（不作为配置项覆盖）
+pub const PROFILE_ENV: &str = "KIRO_PROFILE";
+
+/// 命令行 `--profile` 选择的 profile（启动时设置）
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// 设置 `--profile`（优先于环境变量 `KIRO_PROFILE`，之后的加载与热加载均使用该 profile）
+pub fn select_profile(name: String) {
+    let _ = PROFILE.set(name);
+}
+
+fn active_profile() -> Option<String> {
+    PROFILE
+        .get()
+        .cloned()
+        .or_else(|| std::env::var(PROFILE_ENV).ok())
+        .filter(|name| !name.is_empty())
+}
+
+/// 取出 `profiles`，将选中的 profile 深度合并到公共配置上（对象逐字段合并，其余值整体替换）
+///
+/// 返回合并后的配置与未知配置项的警告（包括各 profile 中的配置项）
+fn apply_profile(
+    mut value: serde_json::Value,
+    profile: Option<&str>,
+) -> anyhow::Result<(serde_json::Value, Vec<Problem>)> {
+    let profiles = match value.as_object_mut().and_then(|o| o.remove(PROFILES_KEY)) {
+        None => serde_json::Map::new(),
+        Some(serde_json::Value::Object(profiles)) => profiles,
+        Some(_) => anyhow::bail!(
+            "配置无效:\n  - {}: 必须是对象，键为 profile 名",
+            PROFILES_KEY
+        ),
+    };
+
+    let mut warnings = validation::unknown_keys(&value);
+    for (name, overrides) in &profiles {
+        if !overrides.is_object() {
+            anyhow::bail!("配置无效:\n  - {}.{}: 必须是对象", PROFILES_KEY, name);
+        }
+        warnings.extend(
+            validation::unknown_keys(overrides)
+                .into_iter()
+                .map(|mut p| {
+                    p.path = format!("{}.{}.{}", PROFILES_KEY, name, p.path);
+                    p
+                }),
+        );
+    }
+
+    if let Some(name) = profile {
+        let Some(overrides) = profiles.get(name) else {
+            let mut names: Vec<&str> = profiles.keys().map(String::as_str).collect();
+            names.sort_unstable();
+            anyhow::bail!(
+                "未知的 profile {}（可选: {}）",
+                name,
+                if names.is_empty() {
+                    "无".to_string()
+                } else {
+                    names.join(", ")
+                }
+            );
+        };
+        merge(&mut value, overrides.clone());
+    }
+    Ok((value, warnings))
+}
+
+/// 配置文件中保存各 profile 覆盖项的键
+const PROFILES_KEY: &str = "profiles";
+
+fn merge(base: &mut serde_json::Value, overrides: serde_json::Value) {
+    match (base, overrides) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// 将 `KIRO_ADMIN_API_KEY`、`KIRO_ALERTS__WINDOW_SECS` 形式的变量名转换为配置键路径
+/// （`__` 分隔嵌套层级，各段由大写下划线转为 camelCase）
+fn env_key_path(name: &str) -> Option<Vec<String>> {
+    let rest = name.strip_prefix(ENV_PREFIX)?;
+    if rest.is_empty()
+        || [
+            PROFILE_ENV,
+            secret::MASTER_KEY_ENV,
+            secret::MASTER_KEY_FILE_ENV,
+        ]
+        .contains(&name)
+    {
+        return None;
+    }
+    rest.split("__")
+        .map(|segment| {
+            let mut key = String::new();
+            for (i, word) in segment.split('_').filter(|w| !w.is_empty()).enumerate() {
+                let word = word.to_ascii_lowercase();
+                if i == 0 {
+                    key.push_str(&word);
+                } else {
+                    let mut chars = word.chars();
+                    key.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+                    key.push_str(chars.as_str());
+                }
+            }
+            (!key.is_empty()).then_some(key)
+        })
+        .collect()
+}
+
+fn lookup<'a>(value: &'a serde_json::Value, path: &[String]) -> Option<&'a serde_json::Value> {
+    path.iter().try_fold(value, |v, key| v.get(key))
+}
+
+fn set_path(value: &mut serde_json::Value, path: &[String], new: serde_json::Value) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut current = value;
+    for key in parents {
+        if !current.get(key).is_some_and(|v| v.is_object()) {
+            current[key] = serde_json::Value::Object(Default::default());
+        }
+        current = &mut current[key];
+    }
+    if current.is_object() {
+        current[last] = new;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_api_key_scopes() {
+        let unrestricted: ApiKeyConfig =
+            serde_json::from_value(serde_json::json!({"key": "sk-a"})).unwrap();
+        assert!(unrestricted.stream && !unrestricted.admin_read_only);
+        assert!(!unrestricted.restricts_body());
+        assert!(unrestricted.allows_endpoint("/v1/batches") && unrestricted.allows_model("any"));
+
+        let scoped: ApiKeyConfig = serde_json::from_value(serde_json::json!({
+            "key": "sk-b",
+            "models": ["claude-haiku-*"],
+            "endpoints": ["/v1/messages*", "/v1/models"],
+            "stream": false
+        }))
+        .unwrap();
+        assert!(scoped.restricts_body());
+        assert!(scoped.allows_endpoint("/v1/messages/count_tokens"));
+        assert!(!scoped.allows_endpoint("/v1/chat/completions"));
+        assert!(scoped.allows_model("claude-haiku-4-5"));
+        assert!(!scoped.allows_model("claude-opus-4-5"));
+    }
+
+    #[test]
+    fn test_path_prefixes() {
+        let mut config = Config::default();
+        assert_eq!(config.route_prefix(), "");
+        assert_eq!(config.public_prefix(), "");
+
+        config.path_prefix = Some("ai/".to_string());
+        assert_eq!(config.route_prefix(), "/ai");
+        config.base_path = Some("/kiro-rs".to_string());
+        assert_eq!(config.public_prefix(), "/kiro-rs/ai");
+
+        config.path_prefix = Some("/".to_string());
+        assert_eq!(config.route_prefix(), "");
+        assert_eq!(config.public_prefix(), "/kiro-rs");
+    }
+
+    #[test]
+    fn test_env_key_path() {
+        assert_eq!(
+            env_key_path("KIRO_ADMIN_API_KEY"),
+            Some(vec!["adminApiKey".to_string()])
+        );
+        assert_eq!(
+            env_key_path("KIRO_ALERTS__WINDOW_SECS"),
+            Some(vec!["alerts".to_string(), "windowSecs".to_string()])
+        );
+        assert_eq!(env_key_path("PATH"), None);
+        assert_eq!(env_key_path("KIRO_"), None);
+        assert_eq!(env_key_path("KIRO_MASTER_KEY"), None);
+    }
+
+    #[test]
+    fn test_env_overrides_config_file() {
+        let file = serde_json::json!({ "host": "127.0.0.1", "port": 8080, "apiKey": "from-file" });
+        let config = Config::from_value_with_env(
+            file,
+            vars(&[
+                ("KIRO_PORT", "9000"),
+                ("KIRO_API_KEY", "123456"),
+                ("KIRO_HOST", "0.0.0.0"),
+                ("KIRO_ALERTS__WINDOW_SECS", "60"),
+                ("HOME", "/root"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.host, "0.0.0.0");
+        // 字符串字段不会被解析为数字
+        assert_eq!(config.api_key.as_deref(), Some("123456"));
+        assert_eq!(config.alerts.unwrap().window_secs, 60);
+    }
+
+    #[test]
+    fn test_parse_toml_and_yaml_config() {
+        let toml = r#"
+host = "0.0.0.0"
+port = 9000
+apiKey = "sk-toml"
+
+[alerts]
+windowSecs = 60
+
+[[modelLimits]]
+model = "claude-opus-4-5"
+maxOutputTokens = 64000
+"#;
+        let yaml = r#"
+host: 0.0.0.0
+port: 9000
+apiKey: sk-toml
+alerts:
+  windowSecs: 60
+modelLimits:
+  - model: claude-opus-4-5
+    maxOutputTokens: 64000
+"#;
+        let from_toml = ConfigFormat::Toml.parse("config.toml", toml).unwrap();
+        let from_yaml = ConfigFormat::Yaml.parse("config.yaml", yaml).unwrap();
+        assert_eq!(from_toml, from_yaml);
+
+        // 与 JSON 配置走相同的环境变量覆盖
+        let config =
+            Config::from_value_with_env(from_yaml, vars(&[("KIRO_PORT", "9100")])).unwrap();
+        assert_eq!(config.host, "0.0.0.0");
+        assert_eq!(config.port, 9100);
+        assert_eq!(config.api_key.as_deref(), Some("sk-toml"));
+        assert_eq!(config.alerts.unwrap().window_secs, 60);
+        assert_eq!(config.model_limits.len(), 1);
+
+        assert_eq!(
+            ConfigFormat::Yaml.parse("config.yaml", "").unwrap(),
+            serde_json::json!({})
+        );
+        assert!(ConfigFormat::Toml.parse("config.toml", "port = ").is_err());
+        assert!(ConfigFormat::Yaml.parse("config.yaml", "port: [").is_err());
+    }
+
+    #[test]
+    fn test_config_format_from_path() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("/etc/kiro/config.TOML")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("config.yml")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("config.jsonc")),
+            ConfigFormat::Json
+        );
+    }
+
+    #[test]
+    fn test_apply_profile() {
+        let file = serde_json::json!({
+            "port": 8080,
+            "apiKey": "shared",
+            "alerts": {"windowSecs": 60, "minRequests": 10},
+            "profiles": {
+                "prod": {"port": 443, "alerts": {"windowSecs": 300}, "hostt": "0.0.0.0"},
+                "dev": {"logLevel": "debug"}
+            }
+        });
+
+        let (value, warnings) = apply_profile(file.clone(), None).unwrap();
+        assert_eq!(value["port"], 8080);
+        assert!(value.get("profiles").is_none());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, "profiles.prod.hostt");
+
+        let (value, _) = apply_profile(file.clone(), Some("prod")).unwrap();
+        let config = Config::from_value_with_env(value, Vec::new()).unwrap();
+        assert_eq!(config.port, 443);
+        assert_eq!(config.api_key.as_deref(), Some("shared"));
+        let alerts = config.alerts.unwrap();
+        assert_eq!(alerts.window_secs, 300);
+        assert_eq!(alerts.min_requests, 10);
+
+        let error = apply_profile(file, Some("staging")).unwrap_err();
+        assert!(error.to_string().contains("可选: dev, prod"));
+    }
+
+    #[test]
+    fn test_hash_plaintext_keys() {
+        let text = r#"{
+  // 主 Key
+  "apiKey": "sk-main",
+  "apiKeys": [{"key": "sk-team", "priority": "high"}],
+  "requestRules": [{"apiKeys": ["sk-team"], "type": "stripFields", "fields": ["metadata"]}],
+  "profiles": {"prod": {"apiKey": "sk-prod"}}
+}"#;
+        let (migrated, count) = hash_plaintext_keys(text).unwrap();
+        assert_eq!(count, 3);
+        assert!(migrated.contains("// 主 Key"));
+        assert!(!migrated.contains("sk-main") && !migrated.contains("sk-team"));
+
+        let value: serde_json::Value = serde_json::from_str(&strip_comments(&migrated)).unwrap();
+        assert!(auth::verify_api_key(
+            value["apiKey"].as_str().unwrap(),
+            "sk-main"
+        ));
+        let team = value["apiKeys"][0]["key"].as_str().unwrap();
+        assert!(auth::verify_api_key(team, "sk-team"));
+        assert_eq!(value["requestRules"][0]["apiKeys"][0], team);
+        assert!(auth::verify_api_key(
+            value["profiles"]["prod"]["apiKey"].as_str().unwrap(),
+            "sk-prod"
+        ));
+
+        // 已是哈希的 Key 不再处理
+        assert_eq!(hash_plaintext_keys(&migrated).unwrap(), (migrated, 0));
+    }
+
+    #[test]
+    fn test_hash_plaintext_keys_only_rewrites_key_fields() {
+        let text = r#"{
+  // 旧 Key: "sk-shared"，注释中的中文不影响位置
+  "apiKey": "sk-shared",
+  "defaultProfile": "sk-shared",
+  "modelAliases": {"sk-shared": "claude-sonnet-4.5"},
+  "profiles": {"sk-shared": {"apiKeys": [{"key": "sk-shared", "label": "sk-shared"}]}}
+}"#;
+        let (migrated, count) = hash_plaintext_keys(text).unwrap();
+        assert_eq!(count, 1);
+        assert!(migrated.contains(r#"// 旧 Key: "sk-shared"，注释中的中文不影响位置"#));
+
+        let value: serde_json::Value = serde_json::from_str(&strip_comments(&migrated)).unwrap();
+        assert!(auth::verify_api_key(
+            value["apiKey"].as_str().unwrap(),
+            "sk-shared"
+        ));
+        assert_eq!(value["defaultProfile"], "sk-shared");
+        assert_eq!(value["modelAliases"]["sk-shared"], "claude-sonnet-4.5");
+        let profile = &value["profiles"]["sk-shared"]["apiKeys"][0];
+        assert_eq!(profile["key"], value["apiKey"]);
+        assert_eq!(profile["label"], "sk-shared");
+    }
+
+    #[test]
+    fn test_strip_comments() {
+        let text = "{\n  // 注释\n  \"url\": \"http://a/b\", /* 块\n注释 */ \"s\": \"\\\"//\"\n}";
+        let stripped = strip_comments(text);
+        assert_eq!(stripped.lines().count(), text.lines().count());
+        let value: serde_json::Value = serde_json::from_str(&stripped).unwrap();
+        assert_eq!(value["url"], "http://a/b");
+        assert_eq!(value["s"], "\"//");
+    }
 
-        let content = fs::read_to_string(path)?;
-        let config: Config = serde_json::from_str(&content)?;
-        Ok(config)
+    #[test]
+    fn test_invalid_env_override_is_error() {
+        let result = Config::from_value_with_env(
+            serde_json::json!({}),
+            vars(&[("KIRO_PORT", "not-a-port")]),
+        );
+        assert!(result.is_err());
     }
 }
@@ -0,0 +1,174 @@
+//! 配置文件中的加密值
+//!
+//! 以 `enc:` 开头的字符串配置值（如 Admin Key、Webhook 密钥）在加载配置时解密，配置文件因此可以不含明文密钥地
+//! 提交到私有仓库。密文为 `enc:` 加上 Base64(12 字节随机 nonce + AES-256-GCM 密文与认证标签)，AES 密钥为主密钥的 SHA-256。
+//! 主密钥从环境变量 `KIRO_MASTER_KEY` 或 `KIRO_MASTER_KEY_FILE` 指向的文件读取，密文由 `encrypt-value` 子命令生成。
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::redact;
+
+/// 加密值的前缀
+pub const ENCRYPTED_PREFIX: &str = "enc:";
+
+/// 主密钥的环境变量（不作为配置项覆盖）
+pub const MASTER_KEY_ENV: &str = "KIRO_MASTER_KEY";
+
+/// 保存主密钥的文件路径的环境变量（不作为配置项覆盖）
+pub const MASTER_KEY_FILE_ENV: &str = "KIRO_MASTER_KEY_FILE";
+
+/// 是否为加密值
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// 读取主密钥：`KIRO_MASTER_KEY` 优先，其次为 `KIRO_MASTER_KEY_FILE` 指向的文件（去掉首尾空白）
+pub fn master_key() -> anyhow::Result<Option<String>> {
+    if let Ok(key) = std::env::var(MASTER_KEY_ENV)
+        && !key.trim().is_empty()
+    {
+        return Ok(Some(key.trim().to_string()));
+    }
+    let Ok(path) = std::env::var(MASTER_KEY_FILE_ENV) else {
+        return Ok(None);
+    };
+    let key = std::fs::read_to_string(&path)
+        .map_err(|e| anyhow::anyhow!("读取主密钥文件 {} 失败: {}", path, e))?;
+    let key = key.trim();
+    if key.is_empty() {
+        anyhow::bail!("主密钥文件 {} 为空", path);
+    }
+    Ok(Some(key.to_string()))
+}
+
+fn cipher(master_key: &str) -> LessSafeKey {
+    let key = Sha256::digest(master_key.as_bytes());
+    LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &key).expect("AES-256 密钥长度固定为 32 字节"))
+}
+
+/// 加密一个配置值，返回 `enc:` 开头的密文
+pub fn encrypt(master_key: &str, plaintext: &str) -> anyhow::Result<String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| anyhow::anyhow!("生成随机数失败"))?;
+    let mut sealed = plaintext.as_bytes().to_vec();
+    cipher(master_key)
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::empty(),
+            &mut sealed,
+        )
+        .map_err(|_| anyhow::anyhow!("加密失败"))?;
+    Ok(format!(
+        "{}{}",
+        ENCRYPTED_PREFIX,
+        STANDARD.encode([nonce.as_slice(), &sealed].concat())
+    ))
+}
+
+/// 解密 `enc:` 开头的密文
+pub fn decrypt(master_key: &str, value: &str) -> anyhow::Result<String> {
+    let encoded = value
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .ok_or_else(|| anyhow::anyhow!("不是加密值"))?;
+    let mut bytes = STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| anyhow::anyhow!("密文不是有效的 Base64: {}", e))?;
+    if bytes.len() < NONCE_LEN + AES_256_GCM.tag_len() {
+        anyhow::bail!("密文过短");
+    }
+    let (nonce, sealed) = bytes.split_at_mut(NONCE_LEN);
+    let nonce =
+        Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow::anyhow!("nonce 无效"))?;
+    let plaintext = cipher(master_key)
+        .open_in_place(nonce, Aad::empty(), sealed)
+        .map_err(|_| anyhow::anyhow!("解密失败，主密钥不正确或密文已损坏"))?;
+    Ok(String::from_utf8(plaintext.to_vec())?)
+}
+
+/// 配置中是否含有加密值
+pub fn contains_encrypted(value: &Value) -> bool {
+    match value {
+        Value::String(s) => is_encrypted(s),
+        Value::Array(items) => items.iter().any(contains_encrypted),
+        Value::Object(map) => map.values().any(contains_encrypted),
+        _ => false,
+    }
+}
+
+/// 就地解密配置中所有的加密值，解密后的值登记到日志脱敏；失败时报告字段路径
+pub fn decrypt_values(value: &mut Value, master_key: &str) -> anyhow::Result<()> {
+    decrypt_at(value, master_key, &mut String::new())
+}
+
+fn decrypt_at(value: &mut Value, master_key: &str, path: &mut String) -> anyhow::Result<()> {
+    let len = path.len();
+    match value {
+        Value::String(s) if is_encrypted(s) => {
+            let plaintext =
+                decrypt(master_key, s).map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+            redact::register(&plaintext);
+            *s = plaintext;
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                path.push_str(&format!("[{}]", index));
+                decrypt_at(item, master_key, path)?;
+                path.truncate(len);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+                decrypt_at(item, master_key, path)?;
+                path.truncate(len);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_encrypt_roundtrip() {
+        let encrypted = encrypt("master-key", "admin-secret").unwrap();
+        assert!(is_encrypted(&encrypted));
+        // 每次加密使用不同的 nonce
+        assert_ne!(encrypted, encrypt("master-key", "admin-secret").unwrap());
+        assert_eq!(decrypt("master-key", &encrypted).unwrap(), "admin-secret");
+        assert!(decrypt("wrong-key", &encrypted).is_err());
+        assert!(decrypt("master-key", "enc:AAAA").is_err());
+    }
+
+    #[test]
+    fn test_decrypt_values() {
+        let mut value = json!({
+            "adminApiKey": encrypt("master-key", "admin-secret").unwrap(),
+            "apiKeys": [{"key": encrypt("master-key", "sk-downstream").unwrap()}],
+            "region": "us-east-1",
+        });
+        assert!(contains_encrypted(&value));
+        decrypt_values(&mut value, "master-key").unwrap();
+        assert!(!contains_encrypted(&value));
+        assert_eq!(value["adminApiKey"], "admin-secret");
+        assert_eq!(value["apiKeys"][0]["key"], "sk-downstream");
+
+        let mut value = json!({"apiKeys": [{"key": "enc:not-base64!"}]});
+        let error = decrypt_values(&mut value, "master-key").unwrap_err();
+        assert!(error.to_string().starts_with("apiKeys[0].key: "));
+    }
+}
@@ -0,0 +1,1072 @@
+//! 配置校验
+//!
+//! 一次性报告配置文件与凭证文件中的全部问题，每个问题带有字段路径（如 `listeners[0].routes[1]`、
+//! `credentials[2].refreshToken`）与修改建议，而不是在第一个解析错误处以难以理解的信息失败。
+//! 启动时错误级别的问题会阻止启动，警告只记录日志；`validate-config` 子命令输出全部问题。
+
+use std::collections::HashSet;
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+use super::config::{AdminOidcConfig, Config, IpFilterConfig, TlsConfig};
+use crate::common::auth;
+use crate::common::client_ip::IpFilter;
+use crate::discord;
+use crate::kiro::model::credentials::{CredentialsConfig, KiroCredentials};
+use crate::kiro::token_manager::validate_refresh_token;
+use crate::listener::parse_socket_mode;
+
+/// 问题级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// 配置无法使用
+    Error,
+    /// 配置可以使用，但很可能不符合预期（如拼错的配置项被忽略）
+    Warning,
+}
+
+/// 配置问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub severity: Severity,
+    /// 字段路径
+    pub path: String,
+    pub message: String,
+    /// 修改建议
+    pub suggestion: Option<String>,
+}
+
+impl Problem {
+    pub fn error(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            path: path.into(),
+            message: message.into(),
+            suggestion: None,
+        }
+    }
+
+    fn warning(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(path, message)
+        }
+    }
+
+    fn suggest(mut self, suggestion: impl Into<String>) -> Self {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)?;
+        } else {
+            write!(f, "{}: {}", self.path, self.message)?;
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "（{}）", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// 将问题列表格式化为多行文本
+pub fn format_problems(problems: &[Problem]) -> String {
+    problems
+        .iter()
+        .map(|p| format!("  - {}", p))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// JSON 语法错误
+pub fn syntax_error(file: &str, e: &serde_json::Error) -> Problem {
+    Problem::error(
+        file,
+        format!(
+            "第 {} 行第 {} 列: JSON 语法错误: {}",
+            e.line(),
+            e.column(),
+            e
+        ),
+    )
+    .suggest("检查是否缺少逗号、引号或多了结尾逗号")
+}
+
+/// 检查配置文件中的未知配置项（通常是拼写错误，会被静默忽略）
+pub fn unknown_keys(value: &Value) -> Vec<Problem> {
+    let (Some(file), Ok(Value::Object(known))) =
+        (value.as_object(), serde_json::to_value(Config::default()))
+    else {
+        return Vec::new();
+    };
+    file.keys()
+        .filter(|key| !known.contains_key(*key))
+        .map(|key| {
+            let problem = Problem::warning(key.as_str(), "未知的配置项，将被忽略");
+            match closest(key, known.keys().map(String::as_str)) {
+                Some(candidate) => problem.suggest(format!("是否应为 `{}`？", candidate)),
+                None => problem,
+            }
+        })
+        .collect()
+}
+
+/// 逐个配置项反序列化，报告全部类型错误（而非只报告第一个）
+pub fn type_errors(value: &Value) -> Vec<Problem> {
+    let Some(object) = value.as_object() else {
+        return vec![
+            Problem::error("", "配置文件顶层必须是 JSON 对象").suggest("使用 `{ ... }` 包裹配置"),
+        ];
+    };
+    let mut problems = Vec::new();
+    for (key, field) in object {
+        let single = Value::Object(Map::from_iter([(key.clone(), field.clone())]));
+        if let Err(e) = serde_path_to_error::deserialize::<_, Config>(single) {
+            let path = e.path().to_string();
+            let message = e.inner().to_string();
+            let mut problem =
+                Problem::error(if path == "." { key.clone() } else { path }, &message);
+            if let Some(suggestion) = type_hint(&message) {
+                problem = problem.suggest(suggestion);
+            }
+            problems.push(problem);
+        }
+    }
+    problems
+}
+
+/// 常见类型错误的修改建议
+fn type_hint(message: &str) -> Option<&'static str> {
+    if !message.starts_with("invalid type: string") {
+        return None;
+    }
+    let expected = message.rsplit("expected ").next().unwrap_or_default();
+    let numeric = expected.contains("integer")
+        || expected.contains("number")
+        || (expected.starts_with(['u', 'i', 'f'])
+            && expected[1..].starts_with(|c: char| c.is_ascii_digit()));
+    if numeric {
+        Some("数值不要加引号")
+    } else if expected.contains("boolean") {
+        Some("布尔值应为不加引号的 true 或 false")
+    } else if expected.contains("sequence") {
+        Some("应为数组，如 [\"a\", \"b\"]")
+    } else {
+        None
+    }
+}
+
+/// 检查配置项取值之间的约束（类型已正确）
+pub fn check_config(config: &Config) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    if config
+        .api_key
+        .as_deref()
+        .is_none_or(|k| k.trim().is_empty())
+    {
+        problems.push(
+            Problem::error("apiKey", "未设置 apiKey")
+                .suggest("在配置文件或环境变量 KIRO_API_KEY 中设置"),
+        );
+    }
+    for (index, api_key) in config.api_keys.iter().enumerate() {
+        if api_key.key.trim().is_empty() {
+            problems.push(Problem::error(
+                format!("apiKeys[{}].key", index),
+                "API Key 为空",
+            ));
+        }
+        for (i, endpoint) in api_key.endpoints.iter().enumerate() {
+            if !endpoint.starts_with('/') {
+                problems.push(
+                    Problem::warning(
+                        format!("apiKeys[{}].endpoints[{}]", index, i),
+                        format!("端点 {} 不以 / 开头，不会匹配任何请求", endpoint),
+                    )
+                    .suggest("如 /v1/messages 或 /v1/*（不含 pathPrefix）"),
+                );
+            }
+        }
+        if api_key.admin_read_only
+            && config
+                .admin_api_key
+                .as_deref()
+                .is_none_or(|k| k.trim().is_empty())
+        {
+            problems.push(
+                Problem::warning(
+                    format!("apiKeys[{}].adminReadOnly", index),
+                    "未配置 adminApiKey，Admin API 未启用",
+                )
+                .suggest("配置 adminApiKey 后才能只读访问 Admin API"),
+            );
+        }
+    }
+    let keys = config
+        .api_key
+        .iter()
+        .map(|key| ("apiKey".to_string(), key))
+        .chain(
+            config
+                .api_keys
+                .iter()
+                .enumerate()
+                .map(|(index, k)| (format!("apiKeys[{}].key", index), &k.key)),
+        );
+    for (path, key) in keys {
+        if key.trim().is_empty() {
+            continue;
+        }
+        if !auth::is_hashed(key) {
+            problems.push(
+                Problem::warning(path, "API Key 以明文保存")
+                    .suggest("运行 `kiro-rs hash-keys` 替换为加盐哈希"),
+            );
+        } else if !auth::is_valid_hash(key) {
+            problems.push(
+                Problem::error(path, "无效的 API Key 哈希")
+                    .suggest("使用 `kiro-rs hash-keys <KEY>` 重新生成"),
+            );
+        }
+    }
+
+    for (index, key) in config.admin_api_keys.iter().enumerate() {
+        let path = format!("adminApiKeys[{}]", index);
+        if key.trim().is_empty() {
+            problems.push(Problem::error(path, "Admin Key 为空").suggest("删除该项"));
+        } else if auth::is_hashed(key) && !auth::is_valid_hash(key) {
+            problems.push(
+                Problem::error(path, "无效的 Admin Key 哈希")
+                    .suggest("使用 `kiro-rs hash-keys <KEY>` 重新生成"),
+            );
+        }
+    }
+    let admin_enabled = config.admin_oidc.is_some()
+        || config
+            .admin_api_key
+            .as_deref()
+            .is_some_and(|k| !k.trim().is_empty());
+    if !config.admin_api_keys.is_empty() && !admin_enabled {
+        problems.push(Problem::warning(
+            "adminApiKeys",
+            "未配置 adminApiKey，Admin API 未启用，adminApiKeys 不会生效",
+        ));
+    }
+    if let Some(oidc) = &config.admin_oidc {
+        check_admin_oidc(oidc, &mut problems);
+    }
+
+    if let Some(tls) = &config.tls {
+        check_tls("tls", tls, &mut problems);
+    }
+    let mut addresses = HashSet::new();
+    for (index, listener) in config.listeners.iter().enumerate() {
+        let path = format!("listeners[{}]", index);
+        if listener.address.trim().is_empty() {
+            problems.push(Problem::error(format!("{}.address", path), "监听地址为空"));
+        } else if !addresses.insert(listener.address.as_str()) {
+            problems.push(
+                Problem::error(
+                    format!("{}.address", path),
+                    format!("重复的监听地址 {}", listener.address),
+                )
+                .suggest("每个地址只能监听一次，可在同一项中配置多个路由组"),
+            );
+        }
+        if listener.routes.is_empty() {
+            problems.push(
+                Problem::warning(format!("{}.routes", path), "未启用任何路由组")
+                    .suggest("省略 routes 以启用全部路由组"),
+            );
+        }
+        if let Some(mode) = &listener.socket_mode {
+            if let Err(e) = parse_socket_mode(mode) {
+                problems.push(Problem::error(
+                    format!("{}.socketMode", path),
+                    e.to_string(),
+                ));
+            }
+            if listener.unix_path().is_none() {
+                problems.push(
+                    Problem::warning(format!("{}.socketMode", path), "仅对 unix: 地址生效")
+                        .suggest("TCP 地址请删除 socketMode"),
+                );
+            }
+        }
+        if let Some(tls) = &listener.tls {
+            check_tls(&format!("{}.tls", path), tls, &mut problems);
+        }
+    }
+
+    if let Some(level) = &config.log_level
+        && let Err(e) = tracing_subscriber::EnvFilter::try_new(level)
+    {
+        problems.push(
+            Problem::error("logLevel", format!("无效的日志级别: {}", e))
+                .suggest("语法同 RUST_LOG，如 info 或 info,kiro_rs=debug"),
+        );
+    }
+
+    for (path, url) in [
+        ("proxyUrl", &config.proxy_url),
+        ("countTokensApiUrl", &config.count_tokens_api_url),
+    ] {
+        if let Some(url) = url
+            && !(path == "proxyUrl" && url.eq_ignore_ascii_case(crate::http_client::DIRECT))
+            && let Err(e) = reqwest::Url::parse(url)
+        {
+            problems.push(
+                Problem::error(path, format!("无效的 URL {}: {}", url, e))
+                    .suggest("需包含协议，如 http://127.0.0.1:7890"),
+            );
+        }
+    }
+    for (path, template) in [
+        ("endpoints.api", &config.endpoints.api),
+        ("endpoints.socialAuth", &config.endpoints.social_auth),
+        ("endpoints.oidc", &config.endpoints.oidc),
+    ] {
+        let url = template.replace("{region}", &config.region);
+        match reqwest::Url::parse(&url) {
+            Ok(parsed) if parsed.host_str().is_some() => {}
+            Ok(_) => problems.push(Problem::error(path, format!("缺少主机名: {}", template))),
+            Err(e) => problems.push(
+                Problem::error(path, format!("无效的 URL {}: {}", template, e))
+                    .suggest("如 https://q.{region}.amazonaws.com"),
+            ),
+        }
+    }
+    if let Some(prefix) = &config.path_prefix
+        && prefix
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '{' | '}' | '*' | '?' | '#' | ':'))
+    {
+        problems.push(
+            Problem::error("pathPrefix", format!("无效的路径前缀 {}", prefix))
+                .suggest("只包含路径字符，如 /ai 或 /services/kiro"),
+        );
+    }
+    if config.max_request_header_bytes == 0 {
+        problems.push(Problem::error(
+            "maxRequestHeaderBytes",
+            "必须大于 0，否则所有请求都会被拒绝",
+        ));
+    }
+    if config.max_buffered_response_bytes == 0 {
+        problems.push(Problem::error(
+            "maxBufferedResponseBytes",
+            "必须大于 0，否则所有非流式请求都会失败",
+        ));
+    }
+    for (name, preset) in &config.presets {
+        if let Some(temperature) = preset.temperature
+            && !(0.0..=1.0).contains(&temperature)
+        {
+            problems.push(
+                Problem::error(
+                    format!("presets.{}.temperature", name),
+                    format!("temperature 必须在 0 到 1 之间，实际为 {}", temperature),
+                )
+                .suggest("如 0.2"),
+            );
+        }
+    }
+    if config.max_messages == Some(0) {
+        problems.push(
+            Problem::error("maxMessages", "必须大于 0，否则所有对话请求都会被拒绝")
+                .suggest("删除 maxMessages 以不限制消息数"),
+        );
+    }
+    if let Some(limit) = &config.ip_rate_limit {
+        if limit.requests_per_minute == 0 {
+            problems.push(
+                Problem::warning("ipRateLimit.requestsPerMinute", "为 0 时不限流")
+                    .suggest("删除 ipRateLimit 以关闭按 IP 限流"),
+            );
+        }
+        if limit.burst == Some(0) {
+            problems.push(Problem::error("ipRateLimit.burst", "突发容量必须大于 0"));
+        }
+        if limit.ban_after.is_some() && limit.ban_secs == 0 {
+            problems.push(
+                Problem::warning("ipRateLimit.banSecs", "封禁时长为 0，banAfter 不会生效")
+                    .suggest("如 600"),
+            );
+        }
+    }
+    if let Some(lockout) = &config.auth_lockout {
+        if lockout.max_failures == 0 {
+            problems.push(
+                Problem::warning("authLockout.maxFailures", "为 0 时不锁定")
+                    .suggest("删除 authLockout 以关闭认证失败锁定"),
+            );
+        }
+        if lockout.window_secs == 0 {
+            problems.push(
+                Problem::error("authLockout.windowSecs", "统计窗口必须大于 0").suggest("如 300"),
+            );
+        }
+        if lockout.lockout_secs == 0 {
+            problems.push(
+                Problem::warning(
+                    "authLockout.lockoutSecs",
+                    "锁定时长为 0，失败次数达到上限后不会锁定",
+                )
+                .suggest("如 900"),
+            );
+        }
+    }
+    if let Some(database_path) = &config.database_path {
+        if database_path.trim().is_empty() {
+            problems.push(
+                Problem::error("databasePath", "数据库路径为空").suggest("如 \"data/kiro.db\""),
+            );
+        }
+        if config.usage_log_path.is_some() {
+            problems.push(
+                Problem::error(
+                    "usageLogPath",
+                    "已配置 databasePath，用量记录写入数据库，不能同时配置 usageLogPath",
+                )
+                .suggest("删除 usageLogPath，或删除 databasePath 继续使用 JSON Lines 文件"),
+            );
+        }
+    }
+    if config.sampling.log_bodies {
+        if config.database_path.is_none() {
+            problems.push(
+                Problem::error(
+                    "sampling.logBodies",
+                    "请求体日志写入数据库，需要配置 databasePath",
+                )
+                .suggest("设置 databasePath，如 \"data/kiro.db\""),
+            );
+        }
+        if config.sampling.max_body_bytes == 0 {
+            problems
+                .push(Problem::error("sampling.maxBodyBytes", "必须大于 0").suggest("如 65536"));
+        }
+    }
+    if let Some(audit_log) = &config.audit_log {
+        if audit_log.hmac_key.len() < 16 {
+            problems.push(
+                Problem::error("auditLog.hmacKey", "密钥过短，至少 16 个字符")
+                    .suggest("使用随机生成的长密钥，如 `openssl rand -hex 32`"),
+            );
+        }
+        if !admin_enabled {
+            problems.push(Problem::warning(
+                "auditLog",
+                "未配置 adminApiKey，Admin API 未启用，审计日志不会有记录",
+            ));
+        }
+    }
+    if let Some(cors) = &config.cors {
+        if cors.allowed_origins.is_empty() {
+            problems.push(
+                Problem::warning(
+                    "cors.allowedOrigins",
+                    "未配置允许的来源，跨域请求均会被拒绝",
+                )
+                .suggest("如 [\"https://app.example.com\"]，或 [\"*\"] 允许任何来源"),
+            );
+        }
+        for (index, name) in cors.allowed_headers.iter().enumerate() {
+            if name.parse::<axum::http::HeaderName>().is_err() {
+                problems.push(Problem::error(
+                    format!("cors.allowedHeaders[{}]", index),
+                    format!("无效的请求头名称 {}", name),
+                ));
+            }
+        }
+    }
+    for (list, entries) in [
+        ("allow", &config.ip_filter.allow),
+        ("deny", &config.ip_filter.deny),
+    ] {
+        for (index, entry) in entries.iter().enumerate() {
+            let filter = IpFilterConfig {
+                allow: vec![entry.clone()],
+                deny: Vec::new(),
+            };
+            if let Err(e) = IpFilter::parse(&filter) {
+                problems.push(
+                    Problem::error(format!("ipFilter.{}[{}]", list, index), e.to_string())
+                        .suggest("如 10.0.0.0/8 或 203.0.113.7"),
+                );
+            }
+        }
+    }
+    for (index, entry) in config.trusted_proxies.iter().enumerate() {
+        if let Err(e) = crate::common::client_ip::TrustedProxies::parse(std::slice::from_ref(entry))
+        {
+            problems.push(
+                Problem::error(format!("trustedProxies[{}]", index), e.to_string())
+                    .suggest("如 127.0.0.1、10.0.0.0/8、::1 或 unix"),
+            );
+        }
+    }
+    let timeouts = &config.timeouts;
+    for (path, secs) in [
+        ("timeouts.connectSecs", timeouts.connect_secs),
+        ("timeouts.writeSecs", timeouts.write_secs),
+        (
+            "timeouts.streamFirstByteSecs",
+            timeouts.stream_first_byte_secs,
+        ),
+        ("timeouts.streamTotalSecs", timeouts.stream_total_secs),
+        (
+            "timeouts.nonStreamFirstByteSecs",
+            timeouts.non_stream_first_byte_secs,
+        ),
+        (
+            "timeouts.nonStreamTotalSecs",
+            timeouts.non_stream_total_secs,
+        ),
+        (
+            "timeouts.refreshFirstByteSecs",
+            timeouts.refresh_first_byte_secs,
+        ),
+        ("timeouts.refreshTotalSecs", timeouts.refresh_total_secs),
+    ] {
+        if secs == 0 {
+            problems.push(Problem::error(path, "超时必须大于 0"));
+        }
+    }
+    if let Some(breaker) = &config.circuit_breaker {
+        if !(breaker.failure_ratio > 0.0 && breaker.failure_ratio <= 1.0) {
+            problems.push(Problem::error(
+                "circuitBreaker.failureRatio",
+                "失败占比应在 (0, 1] 之间",
+            ));
+        }
+        for (path, value) in [
+            ("circuitBreaker.windowSecs", breaker.window_secs),
+            ("circuitBreaker.minRequests", breaker.min_requests),
+            ("circuitBreaker.openSecs", breaker.open_secs),
+        ] {
+            if value == 0 {
+                problems.push(Problem::error(path, "必须大于 0"));
+            }
+        }
+    }
+    if let Some(hedging) = &config.hedging
+        && !(0.0..=1.0).contains(&hedging.percentile)
+    {
+        problems.push(
+            Problem::error("hedging.percentile", "分位数应在 [0, 1] 之间")
+                .suggest("如 0.95 表示以 P95 耗时作为预算"),
+        );
+    }
+    if let Some(adaptive) = &config.adaptive_concurrency {
+        if adaptive.min_limit == 0 {
+            problems.push(Problem::error("adaptiveConcurrency.minLimit", "必须大于 0"));
+        }
+        if !(adaptive.min_limit <= adaptive.initial_limit
+            && adaptive.initial_limit <= adaptive.max_limit)
+        {
+            problems.push(Problem::error(
+                "adaptiveConcurrency",
+                "需满足 minLimit ≤ initialLimit ≤ maxLimit",
+            ));
+        }
+        if !(adaptive.backoff_ratio > 0.0 && adaptive.backoff_ratio < 1.0) {
+            problems.push(Problem::error(
+                "adaptiveConcurrency.backoffRatio",
+                "应在 (0, 1) 之间",
+            ));
+        }
+        if adaptive.latency_tolerance <= 1.0 {
+            problems.push(Problem::error(
+                "adaptiveConcurrency.latencyTolerance",
+                "应大于 1",
+            ));
+        }
+    }
+    if let Some(budget) = &config.retry_budget {
+        if !(budget.ratio >= 0.0 && budget.ratio.is_finite()) {
+            problems.push(Problem::error("retryBudget.ratio", "不能为负数"));
+        }
+        if budget.window_secs == 0 {
+            problems.push(Problem::error("retryBudget.windowSecs", "必须大于 0"));
+        }
+    }
+    if let Some(webhook) = &config.credential_webhook {
+        if let Err(e) = reqwest::Url::parse(&webhook.url) {
+            problems.push(
+                Problem::error(
+                    "credentialWebhook.url",
+                    format!("无效的 URL {}: {}", webhook.url, e),
+                )
+                .suggest("需包含协议，如 https://hooks.example.com/kiro"),
+            );
+        }
+        if !(webhook.quota_threshold > 0.0 && webhook.quota_threshold <= 1.0) {
+            problems.push(Problem::error(
+                "credentialWebhook.quotaThreshold",
+                "应在 (0, 1] 之间",
+            ));
+        }
+        if webhook.refresh_failures == 0 {
+            problems.push(Problem::error(
+                "credentialWebhook.refreshFailures",
+                "必须大于 0",
+            ));
+        }
+    }
+    if let Some(telegram) = &config.telegram {
+        if telegram.bot_token.trim().is_empty() {
+            problems.push(
+                Problem::error("telegram.botToken", "不能为空")
+                    .suggest("在 Telegram 中通过 @BotFather 创建机器人获取"),
+            );
+        }
+        if telegram.chat_id.trim().is_empty() {
+            problems.push(Problem::error("telegram.chatId", "不能为空"));
+        }
+    }
+    if let Some(discord) = &config.discord {
+        if let Err(e) = reqwest::Url::parse(&discord.webhook_url) {
+            problems.push(
+                Problem::error("discord.webhookUrl", format!("无效的 URL: {}", e))
+                    .suggest("如 https://discord.com/api/webhooks/<id>/<token>"),
+            );
+        }
+        for (i, event) in discord.events.iter().enumerate() {
+            if !discord::EVENT_KINDS.contains(&event.as_str()) {
+                problems.push(
+                    Problem::error(
+                        format!("discord.events[{}]", i),
+                        format!("未知的事件类型 {}", event),
+                    )
+                    .suggest(format!("可选值: {}", discord::EVENT_KINDS.join(", "))),
+                );
+            }
+        }
+    }
+    if let Some(shedding) = &config.load_shedding {
+        if shedding.max_event_loop_lag_ms == 0 {
+            problems.push(Problem::error(
+                "loadShedding.maxEventLoopLagMs",
+                "必须大于 0",
+            ));
+        }
+        if shedding.max_memory_mb == Some(0) {
+            problems.push(Problem::error("loadShedding.maxMemoryMb", "必须大于 0"));
+        }
+    }
+    if config.proxy_username.is_some() != config.proxy_password.is_some() {
+        problems.push(Problem::warning(
+            "proxyUsername",
+            "proxyUsername 与 proxyPassword 需同时配置，代理认证未启用",
+        ));
+    }
+    if !matches!(
+        config.count_tokens_auth_type.as_str(),
+        "x-api-key" | "bearer"
+    ) {
+        problems.push(
+            Problem::error(
+                "countTokensAuthType",
+                format!("未知的认证类型 {}", config.count_tokens_auth_type),
+            )
+            .suggest("可选 x-api-key 或 bearer"),
+        );
+    }
+    for (host, addrs) in &config.dns_overrides {
+        if addrs.is_empty() {
+            problems.push(Problem::error(
+                format!("dnsOverrides.{}", host),
+                "至少需要一个 IP 地址",
+            ));
+        }
+        if host.contains(':') || host.contains('/') {
+            problems.push(
+                Problem::error(format!("dnsOverrides.{}", host), "键应为主机名")
+                    .suggest("不含协议与端口，如 q.us-east-1.amazonaws.com"),
+            );
+        }
+    }
+    if let Some(upstream_tls) = &config.upstream_tls {
+        for (index, file) in upstream_tls.ca_cert_paths.iter().enumerate() {
+            if !std::path::Path::new(file).is_file() {
+                problems.push(
+                    Problem::error(
+                        format!("upstreamTls.caCertPaths[{}]", index),
+                        format!("文件不存在: {}", file),
+                    )
+                    .suggest("相对路径基于启动时的工作目录"),
+                );
+            }
+        }
+        if !upstream_tls.insecure_skip_verify.is_empty() {
+            problems.push(Problem::warning(
+                "upstreamTls.insecureSkipVerify",
+                format!(
+                    "已关闭 {:?} 的 TLS 证书校验，连接可被中间人窃听与篡改",
+                    upstream_tls.insecure_skip_verify
+                ),
+            ));
+        }
+    }
+
+    problems
+}
+
+fn check_tls(path: &str, tls: &TlsConfig, problems: &mut Vec<Problem>) {
+    for (field, file) in [("certPath", &tls.cert_path), ("keyPath", &tls.key_path)] {
+        if !std::path::Path::new(file).is_file() {
+            problems.push(
+                Problem::error(
+                    format!("{}.{}", path, field),
+                    format!("文件不存在: {}", file),
+                )
+                .suggest("相对路径基于启动时的工作目录"),
+            );
+        }
+    }
+    if let Some(client_auth) = &tls.client_auth
+        && !std::path::Path::new(&client_auth.ca_path).is_file()
+    {
+        problems.push(
+            Problem::error(
+                format!("{}.clientAuth.caPath", path),
+                format!("文件不存在: {}", client_auth.ca_path),
+            )
+            .suggest("相对路径基于启动时的工作目录"),
+        );
+    }
+}
+
+fn check_admin_oidc(oidc: &AdminOidcConfig, problems: &mut Vec<Problem>) {
+    for (field, url) in [
+        ("issuer", &oidc.issuer),
+        ("redirectUrl", &oidc.redirect_url),
+    ] {
+        let valid = reqwest::Url::parse(url)
+            .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.has_host());
+        if !valid {
+            problems.push(
+                Problem::error(
+                    format!("adminOidc.{}", field),
+                    format!("无效的地址 {}", url),
+                )
+                .suggest("使用完整的 http(s) 地址"),
+            );
+        }
+    }
+    if !oidc
+        .redirect_url
+        .trim_end_matches('/')
+        .ends_with("/api/admin/oidc/callback")
+    {
+        problems.push(
+            Problem::warning("adminOidc.redirectUrl", "回调地址不是 Admin API 的回调端点").suggest(
+                "如 https://kiro.example.com/api/admin/oidc/callback（含 basePath 与 pathPrefix）",
+            ),
+        );
+    }
+    if oidc.client_id.trim().is_empty() {
+        problems.push(Problem::error("adminOidc.clientId", "Client ID 为空"));
+    }
+    if !oidc.scopes.iter().any(|scope| scope == "openid") {
+        problems.push(
+            Problem::error(
+                "adminOidc.scopes",
+                "缺少 openid，身份提供方不会返回 ID Token",
+            )
+            .suggest("如 [\"openid\", \"email\", \"profile\"]"),
+        );
+    }
+    if oidc.allowed_values.is_empty() {
+        problems.push(
+            Problem::error(
+                "adminOidc.allowedValues",
+                "未配置允许的声明取值，任何用户都无法登录",
+            )
+            .suggest(format!(
+                "填写可访问 Admin UI 的 {}，如 [\"kiro-admins\"]",
+                oidc.claim
+            )),
+        );
+    }
+    for (field, secs) in [
+        ("sessionIdleTimeoutSecs", oidc.session_idle_timeout_secs),
+        ("sessionMaxAgeSecs", oidc.session_max_age_secs),
+    ] {
+        if secs == 0 {
+            problems.push(
+                Problem::error(
+                    format!("adminOidc.{}", field),
+                    "必须大于 0，否则登录后立即失效",
+                )
+                .suggest("如 3600"),
+            );
+        }
+    }
+    if oidc.session_idle_timeout_secs > oidc.session_max_age_secs {
+        problems.push(Problem::warning(
+            "adminOidc.sessionIdleTimeoutSecs",
+            "空闲超时大于最长有效期，空闲超时不会生效",
+        ));
+    }
+}
+
+/// 检查凭证文件中的每个凭据（路径按文件中的顺序编号）
+pub fn check_credentials(credentials: &CredentialsConfig) -> Vec<Problem> {
+    let entries: Vec<(String, &KiroCredentials)> = match credentials {
+        CredentialsConfig::Single(cred) => vec![("credentials".to_string(), cred)],
+        CredentialsConfig::Multiple(creds) => creds
+            .iter()
+            .enumerate()
+            .map(|(index, cred)| (format!("credentials[{}]", index), cred))
+            .collect(),
+    };
+    if entries.is_empty() {
+        return vec![
+            Problem::error("credentials", "凭证文件中没有凭据")
+                .suggest("使用 add-credential 子命令添加凭据"),
+        ];
+    }
+
+    let mut problems = Vec::new();
+    let mut ids = HashSet::new();
+    for (path, cred) in entries {
+        if let Some(id) = cred.id
+            && !ids.insert(id)
+        {
+            problems.push(
+                Problem::error(format!("{}.id", path), format!("重复的凭据 ID {}", id))
+                    .suggest("删除 id 字段，启动时会自动分配"),
+            );
+        }
+        if let Err(e) = validate_refresh_token(cred) {
+            let message = e.to_string();
+            let mut problem = Problem::error(
+                format!("{}.refreshToken", path),
+                message.lines().next().unwrap_or_default(),
+            );
+            if message.contains("截断") {
+                problem = problem.suggest(
+                    "从 ~/.aws/sso/cache/kiro-auth-token.json 复制完整的 refreshToken，或使用 add-credential 重新授权",
+                );
+            }
+            problems.push(problem);
+        }
+        let auth_method = cred.auth_method.as_deref().map(str::to_lowercase);
+        match auth_method.as_deref() {
+            None | Some("social") => {}
+            Some("idc" | "builder-id") => {
+                for (field, value) in [
+                    ("clientId", &cred.client_id),
+                    ("clientSecret", &cred.client_secret),
+                ] {
+                    if value.is_none() {
+                        problems.push(
+                            Problem::error(
+                                format!("{}.{}", path, field),
+                                "IdC 凭据需要 clientId 与 clientSecret",
+                            )
+                            .suggest("从 ~/.aws/sso/cache 中对应的客户端注册文件复制"),
+                        );
+                    }
+                }
+            }
+            Some(other) => problems.push(
+                Problem::warning(
+                    format!("{}.authMethod", path),
+                    format!("未知的认证方式 {}", other),
+                )
+                .suggest("可选 social、idc 或 builder-id"),
+            ),
+        }
+    }
+    problems
+}
+
+/// 在候选项中找出与 `input` 最相近的一项
+fn closest<'a>(input: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .map(|candidate| (strsim::jaro_winkler(input, candidate), candidate))
+        .filter(|(score, _)| *score > 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::config::{CorsConfig, EndpointsConfig, PromptPreset};
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_unknown_keys_suggest_closest() {
+        let problems = unknown_keys(&json!({"apiKye": "x", "port": 8080, "zzz": 1}));
+        assert_eq!(problems.len(), 2);
+        assert_eq!(problems[0].path, "apiKye");
+        assert_eq!(
+            problems[0].suggestion.as_deref(),
+            Some("是否应为 `apiKey`？")
+        );
+        assert_eq!(problems[1].path, "zzz");
+        assert!(problems[1].suggestion.is_none());
+        assert!(!problems[0].is_error());
+    }
+
+    #[test]
+    fn test_type_errors_report_all_with_paths() {
+        let problems = type_errors(&json!({
+            "port": "8080",
+            "stripReasoning": "yes",
+            "listeners": [{"address": "127.0.0.1:8080", "routes": ["api", "apii"]}],
+            "host": "0.0.0.0"
+        }));
+        let paths: Vec<&str> = problems.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec!["listeners[0].routes[1]", "port", "stripReasoning"]
+        );
+        assert_eq!(problems[1].suggestion.as_deref(), Some("数值不要加引号"));
+        assert!(problems.iter().all(Problem::is_error));
+    }
+
+    #[test]
+    fn test_check_config() {
+        let config = Config {
+            api_key: Some(auth::hash_api_key("sk-test")),
+            log_level: Some("info,[".to_string()),
+            proxy_url: Some("127.0.0.1:7890".to_string()),
+            path_prefix: Some("/v1/{model}".to_string()),
+            endpoints: EndpointsConfig {
+                api: "q.{region}.amazonaws.com".to_string(),
+                ..Default::default()
+            },
+            ..Config::default()
+        };
+        let paths: Vec<String> = check_config(&config).into_iter().map(|p| p.path).collect();
+        assert_eq!(
+            paths,
+            vec!["logLevel", "proxyUrl", "endpoints.api", "pathPrefix"]
+        );
+
+        let config = Config {
+            api_key: Some(auth::hash_api_key("sk-test")),
+            ..Config::default()
+        };
+        assert!(check_config(&config).is_empty());
+
+        // 明文 Key 仍可使用，只提示迁移
+        let config = Config {
+            api_key: Some("sk-test".to_string()),
+            ..Config::default()
+        };
+        let problems = check_config(&config);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].path, "apiKey");
+        assert!(!problems[0].is_error());
+    }
+
+    #[test]
+    fn test_check_preset_temperature() {
+        let config = Config {
+            api_key: Some(auth::hash_api_key("sk-test")),
+            presets: HashMap::from([(
+                "hot".to_string(),
+                PromptPreset {
+                    temperature: Some(1.5),
+                    ..Default::default()
+                },
+            )]),
+            ..Config::default()
+        };
+        let problems = check_config(&config);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].path, "presets.hot.temperature");
+        assert!(problems[0].is_error());
+    }
+
+    #[test]
+    fn test_check_cors() {
+        let config = Config {
+            api_key: Some(auth::hash_api_key("sk-test")),
+            cors: Some(CorsConfig {
+                allowed_headers: vec!["x-api-key".to_string(), "bad header".to_string()],
+                ..Default::default()
+            }),
+            ..Config::default()
+        };
+        let problems = check_config(&config);
+        let paths: Vec<&str> = problems.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(paths, vec!["cors.allowedOrigins", "cors.allowedHeaders[1]"]);
+        assert!(!problems[0].is_error() && problems[1].is_error());
+    }
+
+    #[test]
+    fn test_check_admin_oidc() {
+        let config = Config {
+            api_key: Some(auth::hash_api_key("sk-test")),
+            admin_oidc: Some(AdminOidcConfig {
+                issuer: "idp.example.com".to_string(),
+                client_id: "kiro".to_string(),
+                client_secret: None,
+                redirect_url: "https://kiro.example.com/admin".to_string(),
+                scopes: vec!["email".to_string()],
+                claim: "groups".to_string(),
+                allowed_values: Vec::new(),
+                session_idle_timeout_secs: 0,
+                session_max_age_secs: 3600,
+            }),
+            ..Config::default()
+        };
+        let problems = check_config(&config);
+        let paths: Vec<&str> = problems.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "adminOidc.issuer",
+                "adminOidc.redirectUrl",
+                "adminOidc.scopes",
+                "adminOidc.allowedValues",
+                "adminOidc.sessionIdleTimeoutSecs"
+            ]
+        );
+        assert!(!problems[1].is_error());
+    }
+
+    #[test]
+    fn test_check_credentials() {
+        let credentials: CredentialsConfig = serde_json::from_value(json!([
+            {"id": 1, "refreshToken": "a".repeat(120)},
+            {"id": 1, "refreshToken": "truncated..."},
+            {"refreshToken": "b".repeat(120), "authMethod": "idc", "clientId": "c"}
+        ]))
+        .unwrap();
+        let problems = check_credentials(&credentials);
+        let paths: Vec<&str> = problems.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "credentials[1].id",
+                "credentials[1].refreshToken",
+                "credentials[2].clientSecret"
+            ]
+        );
+        assert!(problems[1].suggestion.is_some());
+        assert_eq!(
+            problems[1].to_string().lines().count(),
+            1,
+            "问题描述应为单行"
+        );
+    }
+}
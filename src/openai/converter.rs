@@ -0,0 +1,761 @@
+//! OpenAI ↔ Anthropic 协议转换器
+//!
+//! OpenAI 请求先转换为 Anthropic Messages 请求，复用现有的 Anthropic → Kiro 转换链路；
+//! 响应再由 Anthropic 消息映射回 OpenAI 格式。
+
+use std::collections::HashMap;
+
+use serde_json::json;
+
+use crate::anthropic::types::{
+    MAX_BUDGET_TOKENS, Message, MessagesRequest, Metadata, SystemMessage, Thinking, Tool,
+};
+
+use super::structured::StructuredMode;
+use super::types::{ChatCompletionRequest, ChatMessage, ChatTool, CompletionRequest};
+
+/// 未指定 max_tokens 时的默认值
+pub(super) const DEFAULT_MAX_TOKENS: i32 = 4096;
+
+/// 单次请求允许的最大候选数（n）
+pub const MAX_CHOICES: u32 = 8;
+
+/// 转换错误
+#[derive(Debug)]
+pub enum ConversionError {
+    /// 不支持的消息角色
+    UnsupportedRole(String),
+    /// tool 消息缺少 tool_call_id
+    MissingToolCallId,
+    /// 不支持的 prompt 格式（多个 prompt 或 token 数组）
+    UnsupportedPrompt,
+    /// 不支持的输入格式
+    UnsupportedInput(&'static str),
+    /// 不支持 previous_response_id（服务端不保存历史响应）
+    PreviousResponseUnsupported,
+    /// n 超出允许范围
+    InvalidChoiceCount(u32),
+}
+
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConversionError::UnsupportedRole(role) => write!(f, "不支持的消息角色: {}", role),
+            ConversionError::MissingToolCallId => write!(f, "tool 消息缺少 tool_call_id"),
+            ConversionError::UnsupportedPrompt => {
+                write!(f, "prompt 仅支持字符串或只包含一个字符串的数组")
+            }
+            ConversionError::UnsupportedInput(reason) => write!(f, "不支持的输入: {}", reason),
+            ConversionError::PreviousResponseUnsupported => {
+                write!(f, "不支持 previous_response_id，请在 input 中传入完整对话")
+            }
+            ConversionError::InvalidChoiceCount(n) => {
+                write!(f, "n 必须在 1 到 {} 之间，当前为 {}", MAX_CHOICES, n)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// 将 OpenAI Chat Completions 请求转换为 Anthropic Messages 请求
+pub fn convert_request(req: &ChatCompletionRequest) -> Result<MessagesRequest, ConversionError> {
+    if let Some(n) = req.n
+        && !(1..=MAX_CHOICES).contains(&n)
+    {
+        return Err(ConversionError::InvalidChoiceCount(n));
+    }
+
+    let mut system = Vec::new();
+    let mut messages: Vec<Message> = Vec::new();
+    // 上一条消息是否为由 tool 消息生成的 user 消息（用于合并并行工具结果）
+    let mut last_is_tool_result = false;
+
+    for msg in &req.messages {
+        match msg.role.as_str() {
+            "system" | "developer" => {
+                let text = content_to_text(&msg.content);
+                if !text.is_empty() {
+                    system.push(SystemMessage { text });
+                }
+                last_is_tool_result = false;
+            }
+            "user" => {
+                messages.push(Message {
+                    role: "user".to_string(),
+                    content: convert_user_content(&msg.content),
+                });
+                last_is_tool_result = false;
+            }
+            "assistant" => {
+                messages.push(Message {
+                    role: "assistant".to_string(),
+                    content: convert_assistant_content(msg),
+                });
+                last_is_tool_result = false;
+            }
+            "tool" => {
+                let tool_use_id = msg
+                    .tool_call_id
+                    .clone()
+                    .ok_or(ConversionError::MissingToolCallId)?;
+                let block = json!({
+                    "type": "tool_result",
+                    "tool_use_id": tool_use_id,
+                    "content": convert_user_content(&msg.content)
+                });
+
+                // 并行工具调用的多个结果合并到同一条 user 消息中
+                match messages.last_mut() {
+                    Some(last) if last_is_tool_result => {
+                        if let serde_json::Value::Array(blocks) = &mut last.content {
+                            blocks.push(block);
+                        }
+                    }
+                    _ => messages.push(Message {
+                        role: "user".to_string(),
+                        content: json!([block]),
+                    }),
+                }
+                last_is_tool_result = true;
+            }
+            other => return Err(ConversionError::UnsupportedRole(other.to_string())),
+        }
+    }
+
+    // 上游不支持 response_format，通过系统提示词注入格式要求
+    if let Some(mode) = req
+        .response_format
+        .as_ref()
+        .and_then(StructuredMode::from_response_format)
+    {
+        system.push(SystemMessage {
+            text: mode.instruction(),
+        });
+    }
+
+    Ok(MessagesRequest {
+        model: req.model.clone(),
+        max_tokens: req
+            .max_completion_tokens
+            .or(req.max_tokens)
+            .unwrap_or(DEFAULT_MAX_TOKENS),
+        temperature: None,
+        messages,
+        stream: req.stream,
+        system: if system.is_empty() {
+            None
+        } else {
+            Some(system)
+        },
+        tools: req.tools.as_ref().map(|tools| convert_tools(tools)),
+        tool_choice: req.tool_choice.as_ref().map(convert_tool_choice),
+        thinking: req
+            .reasoning_effort
+            .as_deref()
+            .or_else(|| req.reasoning.as_ref().and_then(|r| r.effort.as_deref()))
+            .and_then(convert_reasoning_effort),
+        metadata: user_metadata(&req.user),
+    })
+}
+
+/// 将旧版 Completions 请求转换为只有一条 user 消息的 Anthropic Messages 请求
+pub fn convert_completion_request(
+    req: &CompletionRequest,
+) -> Result<MessagesRequest, ConversionError> {
+    let prompt = completion_prompt(&req.prompt)?;
+    Ok(MessagesRequest {
+        model: req.model.clone(),
+        max_tokens: req.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        temperature: None,
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: json!(prompt),
+        }],
+        stream: req.stream,
+        system: None,
+        tools: None,
+        tool_choice: None,
+        thinking: None,
+        metadata: user_metadata(&req.user),
+    })
+}
+
+/// 将 OpenAI 的 `user` 字段映射为 Anthropic `metadata.user_id`
+pub(super) fn user_metadata(user: &Option<String>) -> Option<Metadata> {
+    user.as_ref().map(|user| Metadata {
+        user_id: Some(user.clone()),
+    })
+}
+
+/// 提取 Completions 请求中的 prompt 文本
+pub fn completion_prompt(prompt: &serde_json::Value) -> Result<&str, ConversionError> {
+    match prompt {
+        serde_json::Value::String(s) => Ok(s),
+        serde_json::Value::Array(items) if items.len() == 1 => {
+            items[0].as_str().ok_or(ConversionError::UnsupportedPrompt)
+        }
+        _ => Err(ConversionError::UnsupportedPrompt),
+    }
+}
+
+/// 将 OpenAI reasoning_effort 映射为 thinking 预算
+///
+/// `minimal` / `none` 或未知取值表示不启用 thinking
+pub(super) fn convert_reasoning_effort(effort: &str) -> Option<Thinking> {
+    let budget_tokens = match effort {
+        "low" => 4096,
+        "medium" => 12288,
+        "high" => MAX_BUDGET_TOKENS,
+        _ => return None,
+    };
+    Some(Thinking::enabled(budget_tokens))
+}
+
+/// 提取消息中的纯文本内容
+fn content_to_text(content: &Option<serde_json::Value>) -> String {
+    match content {
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(serde_json::Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|p| p.get("text").and_then(|v| v.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// 转换 user / tool 消息内容（支持文本和 base64 data URL 图片）
+fn convert_user_content(content: &Option<serde_json::Value>) -> serde_json::Value {
+    let Some(serde_json::Value::Array(parts)) = content else {
+        return json!(content_to_text(content));
+    };
+
+    let blocks: Vec<serde_json::Value> = parts
+        .iter()
+        .filter_map(|part| match part.get("type").and_then(|v| v.as_str()) {
+            Some("text") => Some(json!({
+                "type": "text",
+                "text": part.get("text").and_then(|v| v.as_str()).unwrap_or_default()
+            })),
+            Some("image_url") => {
+                let url = part
+                    .pointer("/image_url/url")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default();
+                let image = parse_data_url(url);
+                if image.is_none() {
+                    tracing::warn!("跳过不支持的图片地址（仅支持 base64 data URL）");
+                }
+                image
+            }
+            _ => None,
+        })
+        .collect();
+
+    json!(blocks)
+}
+
+/// 解析 `data:image/png;base64,xxx` 格式的图片为 Anthropic image 块
+pub(super) fn parse_data_url(url: &str) -> Option<serde_json::Value> {
+    let rest = url.strip_prefix("data:")?;
+    let (meta, data) = rest.split_once(',')?;
+    let media_type = meta.strip_suffix(";base64")?;
+    Some(json!({
+        "type": "image",
+        "source": {
+            "type": "base64",
+            "media_type": media_type,
+            "data": data
+        }
+    }))
+}
+
+/// 转换 assistant 消息内容（文本 + tool_calls）
+fn convert_assistant_content(msg: &ChatMessage) -> serde_json::Value {
+    let text = content_to_text(&msg.content);
+    let Some(tool_calls) = msg.tool_calls.as_ref().filter(|c| !c.is_empty()) else {
+        return json!(text);
+    };
+
+    let mut blocks = Vec::new();
+    if !text.is_empty() {
+        blocks.push(json!({ "type": "text", "text": text }));
+    }
+    for call in tool_calls {
+        let input: serde_json::Value = if call.function.arguments.trim().is_empty() {
+            json!({})
+        } else {
+            serde_json::from_str(&call.function.arguments).unwrap_or_else(|e| {
+                tracing::warn!("tool_calls 参数 JSON 解析失败: {}, id: {}", e, call.id);
+                json!({})
+            })
+        };
+        blocks.push(json!({
+            "type": "tool_use",
+            "id": call.id,
+            "name": call.function.name,
+            "input": input
+        }));
+    }
+
+    json!(blocks)
+}
+
+/// 转换工具定义
+fn convert_tools(tools: &[ChatTool]) -> Vec<Tool> {
+    tools
+        .iter()
+        .filter(|t| t.tool_type == "function")
+        .map(|t| {
+            let input_schema: HashMap<String, serde_json::Value> = t
+                .function
+                .parameters
+                .as_ref()
+                .and_then(|p| serde_json::from_value(p.clone()).ok())
+                .unwrap_or_else(|| {
+                    HashMap::from([
+                        ("type".to_string(), json!("object")),
+                        ("properties".to_string(), json!({})),
+                    ])
+                });
+            Tool {
+                name: t.function.name.clone(),
+                description: t.function.description.clone().unwrap_or_default(),
+                input_schema,
+            }
+        })
+        .collect()
+}
+
+/// 转换 tool_choice
+///
+/// - `"auto"` / `"none"` → `{"type": "auto"}` / `{"type": "none"}`
+/// - `"required"` → `{"type": "any"}`
+/// - `{"type": "function", "function": {"name": ...}}` → `{"type": "tool", "name": ...}`
+/// - `{"type": "function", "name": ...}`（Responses 格式）→ `{"type": "tool", "name": ...}`
+pub(super) fn convert_tool_choice(choice: &serde_json::Value) -> serde_json::Value {
+    match choice {
+        serde_json::Value::String(s) if s == "required" => json!({ "type": "any" }),
+        serde_json::Value::String(s) => json!({ "type": s }),
+        other => match other
+            .pointer("/function/name")
+            .or_else(|| other.get("name"))
+            .and_then(|v| v.as_str())
+        {
+            Some(name) => json!({ "type": "tool", "name": name }),
+            None => json!({ "type": "auto" }),
+        },
+    }
+}
+
+/// 将 Anthropic stop_reason 映射为 OpenAI finish_reason
+pub fn map_finish_reason(stop_reason: &str) -> &'static str {
+    match stop_reason {
+        "tool_use" => "tool_calls",
+        "max_tokens" => "length",
+        "refusal" => "content_filter",
+        _ => "stop",
+    }
+}
+
+/// 将 Anthropic 消息转换为 OpenAI chat.completion 响应
+pub fn convert_response(message: &serde_json::Value, created: i64) -> serde_json::Value {
+    let mut text = String::new();
+    let mut reasoning = String::new();
+    let mut tool_calls = Vec::new();
+
+    if let Some(blocks) = message["content"].as_array() {
+        for block in blocks {
+            match block["type"].as_str() {
+                Some("text") => text.push_str(block["text"].as_str().unwrap_or_default()),
+                Some("thinking") => {
+                    reasoning.push_str(block["thinking"].as_str().unwrap_or_default())
+                }
+                Some("tool_use") => tool_calls.push(json!({
+                    "index": tool_calls.len(),
+                    "id": block["id"],
+                    "type": "function",
+                    "function": {
+                        "name": block["name"],
+                        "arguments": block["input"].to_string()
+                    }
+                })),
+                _ => {}
+            }
+        }
+    }
+
+    let mut response_message = json!({
+        "role": "assistant",
+        "content": if text.is_empty() && !tool_calls.is_empty() { serde_json::Value::Null } else { json!(text) }
+    });
+    if !reasoning.is_empty() {
+        response_message["reasoning_content"] = json!(reasoning);
+    }
+    if !tool_calls.is_empty() {
+        response_message["tool_calls"] = json!(tool_calls);
+    }
+
+    let prompt_tokens = message["usage"]["input_tokens"].as_i64().unwrap_or(0);
+    let completion_tokens = message["usage"]["output_tokens"].as_i64().unwrap_or(0);
+
+    json!({
+        "id": chat_completion_id(message["id"].as_str().unwrap_or_default()),
+        "object": "chat.completion",
+        "created": created,
+        "model": message["model"],
+        "choices": [{
+            "index": 0,
+            "message": response_message,
+            "finish_reason": map_finish_reason(message["stop_reason"].as_str().unwrap_or_default())
+        }],
+        "usage": {
+            "prompt_tokens": prompt_tokens,
+            "completion_tokens": completion_tokens,
+            "total_tokens": prompt_tokens + completion_tokens
+        }
+    })
+}
+
+/// 将多个单候选的 chat.completion 响应合并为一个（n > 1）
+///
+/// 按顺序重新编号 choices，usage 为各次上游调用之和，ID 沿用第一个响应
+pub fn merge_choices(responses: Vec<serde_json::Value>) -> serde_json::Value {
+    let mut responses = responses.into_iter();
+    let Some(mut merged) = responses.next() else {
+        return serde_json::Value::Null;
+    };
+
+    let mut choices = merged["choices"].as_array().cloned().unwrap_or_default();
+    let mut prompt_tokens = merged["usage"]["prompt_tokens"].as_i64().unwrap_or(0);
+    let mut completion_tokens = merged["usage"]["completion_tokens"].as_i64().unwrap_or(0);
+    for response in responses {
+        choices.extend(response["choices"].as_array().cloned().unwrap_or_default());
+        prompt_tokens += response["usage"]["prompt_tokens"].as_i64().unwrap_or(0);
+        completion_tokens += response["usage"]["completion_tokens"].as_i64().unwrap_or(0);
+    }
+    for (index, choice) in choices.iter_mut().enumerate() {
+        choice["index"] = json!(index);
+    }
+
+    merged["choices"] = json!(choices);
+    merged["usage"] = json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": prompt_tokens + completion_tokens
+    });
+    merged
+}
+
+/// 将 Anthropic 消息转换为 OpenAI text_completion 响应
+///
+/// `echo` 为 Some 时在输出文本前附带原始 prompt
+pub fn convert_completion_response(
+    message: &serde_json::Value,
+    created: i64,
+    echo: Option<&str>,
+) -> serde_json::Value {
+    let mut text = echo.unwrap_or_default().to_string();
+    for block in message["content"].as_array().into_iter().flatten() {
+        if block["type"] == "text" {
+            text.push_str(block["text"].as_str().unwrap_or_default());
+        }
+    }
+
+    let prompt_tokens = message["usage"]["input_tokens"].as_i64().unwrap_or(0);
+    let completion_tokens = message["usage"]["output_tokens"].as_i64().unwrap_or(0);
+
+    json!({
+        "id": completion_id(message["id"].as_str().unwrap_or_default()),
+        "object": "text_completion",
+        "created": created,
+        "model": message["model"],
+        "choices": [{
+            "index": 0,
+            "text": text,
+            "logprobs": null,
+            "finish_reason": map_finish_reason(message["stop_reason"].as_str().unwrap_or_default())
+        }],
+        "usage": {
+            "prompt_tokens": prompt_tokens,
+            "completion_tokens": completion_tokens,
+            "total_tokens": prompt_tokens + completion_tokens
+        }
+    })
+}
+
+/// 由 Anthropic 消息 ID 生成 text completion ID
+pub fn completion_id(message_id: &str) -> String {
+    format!(
+        "cmpl-{}",
+        message_id.strip_prefix("msg_").unwrap_or(message_id)
+    )
+}
+
+/// 由 Anthropic 消息 ID 生成 chat completion ID
+pub fn chat_completion_id(message_id: &str) -> String {
+    format!(
+        "chatcmpl-{}",
+        message_id.strip_prefix("msg_").unwrap_or(message_id)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openai::types::ReasoningOptions;
+
+    fn request(messages: serde_json::Value) -> ChatCompletionRequest {
+        serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": messages
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_convert_request_system_and_user() {
+        let req = request(json!([
+            {"role": "system", "content": "be brief"},
+            {"role": "user", "content": "hi"}
+        ]));
+        let converted = convert_request(&req).unwrap();
+
+        assert_eq!(converted.system.unwrap()[0].text, "be brief");
+        assert_eq!(converted.messages.len(), 1);
+        assert_eq!(converted.messages[0].content, json!("hi"));
+        assert_eq!(converted.max_tokens, DEFAULT_MAX_TOKENS);
+    }
+
+    #[test]
+    fn test_convert_request_injects_response_format_instruction() {
+        let req: ChatCompletionRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "hi"}],
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": {"name": "answer", "schema": {"type": "object"}}
+            }
+        }))
+        .unwrap();
+        let system = convert_request(&req).unwrap().system.unwrap();
+
+        assert_eq!(system.len(), 1);
+        assert!(system[0].text.contains("\"answer\""));
+        assert!(system[0].text.contains("{\"type\":\"object\"}"));
+    }
+
+    #[test]
+    fn test_convert_request_maps_reasoning_effort() {
+        let mut req = request(json!([{"role": "user", "content": "hi"}]));
+        req.reasoning_effort = Some("low".to_string());
+        let thinking = convert_request(&req).unwrap().thinking.unwrap();
+        assert_eq!(thinking.thinking_type, "enabled");
+        assert_eq!(thinking.budget_tokens, 4096);
+
+        req.reasoning_effort = Some("minimal".to_string());
+        assert!(convert_request(&req).unwrap().thinking.is_none());
+
+        req.reasoning_effort = None;
+        req.reasoning = Some(ReasoningOptions {
+            effort: Some("high".to_string()),
+        });
+        let thinking = convert_request(&req).unwrap().thinking.unwrap();
+        assert_eq!(thinking.budget_tokens, MAX_BUDGET_TOKENS);
+    }
+
+    #[test]
+    fn test_convert_request_parallel_tool_calls_and_results() {
+        let req = request(json!([
+            {"role": "user", "content": "weather in Paris and Rome?"},
+            {"role": "assistant", "content": null, "tool_calls": [
+                {"id": "call_1", "type": "function", "function": {"name": "weather", "arguments": "{\"city\":\"Paris\"}"}},
+                {"id": "call_2", "type": "function", "function": {"name": "weather", "arguments": "{\"city\":\"Rome\"}"}}
+            ]},
+            {"role": "tool", "tool_call_id": "call_1", "content": "sunny"},
+            {"role": "tool", "tool_call_id": "call_2", "content": "rainy"}
+        ]));
+        let converted = convert_request(&req).unwrap();
+
+        assert_eq!(converted.messages.len(), 3);
+        let tool_uses = converted.messages[1].content.as_array().unwrap();
+        assert_eq!(tool_uses.len(), 2);
+        assert_eq!(tool_uses[1]["input"]["city"], "Rome");
+
+        // 两个工具结果应合并到同一条 user 消息
+        let results = converted.messages[2].content.as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["tool_use_id"], "call_1");
+        assert_eq!(results[1]["tool_use_id"], "call_2");
+    }
+
+    #[test]
+    fn test_convert_request_rejects_unknown_role() {
+        let req = request(json!([{"role": "narrator", "content": "x"}]));
+        assert!(matches!(
+            convert_request(&req),
+            Err(ConversionError::UnsupportedRole(_))
+        ));
+    }
+
+    #[test]
+    fn test_convert_user_content_data_url_image() {
+        let content = Some(json!([
+            {"type": "text", "text": "what is this"},
+            {"type": "image_url", "image_url": {"url": "data:image/png;base64,AAAA"}}
+        ]));
+        let converted = convert_user_content(&content);
+        assert_eq!(converted[1]["type"], "image");
+        assert_eq!(converted[1]["source"]["media_type"], "image/png");
+        assert_eq!(converted[1]["source"]["data"], "AAAA");
+    }
+
+    #[test]
+    fn test_convert_tool_choice() {
+        assert_eq!(
+            convert_tool_choice(&json!("required")),
+            json!({"type": "any"})
+        );
+        assert_eq!(
+            convert_tool_choice(&json!({"type": "function", "function": {"name": "f"}})),
+            json!({"type": "tool", "name": "f"})
+        );
+    }
+
+    #[test]
+    fn test_convert_response_multiple_tool_calls() {
+        let message = json!({
+            "id": "msg_abc",
+            "model": "claude-sonnet-4-5",
+            "content": [
+                {"type": "tool_use", "id": "t1", "name": "a", "input": {"x": 1}},
+                {"type": "tool_use", "id": "t2", "name": "b", "input": {}}
+            ],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 10, "output_tokens": 5}
+        });
+        let response = convert_response(&message, 0);
+
+        assert_eq!(response["id"], "chatcmpl-abc");
+        let choice = &response["choices"][0];
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        assert!(choice["message"]["content"].is_null());
+        assert_eq!(choice["message"]["tool_calls"][1]["index"], 1);
+        assert_eq!(choice["message"]["tool_calls"][1]["id"], "t2");
+        assert_eq!(
+            choice["message"]["tool_calls"][0]["function"]["arguments"],
+            "{\"x\":1}"
+        );
+        assert_eq!(response["usage"]["total_tokens"], 15);
+    }
+
+    #[test]
+    fn test_convert_completion_request_single_user_message() {
+        let req: CompletionRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "prompt": ["Once upon a time"],
+            "max_tokens": 32
+        }))
+        .unwrap();
+        let converted = convert_completion_request(&req).unwrap();
+
+        assert_eq!(converted.messages.len(), 1);
+        assert_eq!(converted.messages[0].role, "user");
+        assert_eq!(converted.messages[0].content, json!("Once upon a time"));
+        assert_eq!(converted.max_tokens, 32);
+
+        let multiple = json!(["a", "b"]);
+        assert!(matches!(
+            completion_prompt(&multiple),
+            Err(ConversionError::UnsupportedPrompt)
+        ));
+        assert!(completion_prompt(&json!([[1, 2, 3]])).is_err());
+    }
+
+    #[test]
+    fn test_convert_completion_response_with_echo() {
+        let message = json!({
+            "id": "msg_xyz",
+            "model": "claude-sonnet-4-5",
+            "content": [{"type": "text", "text": " there was"}],
+            "stop_reason": "max_tokens",
+            "usage": {"input_tokens": 4, "output_tokens": 3}
+        });
+        let response = convert_completion_response(&message, 0, Some("Once"));
+
+        assert_eq!(response["id"], "cmpl-xyz");
+        assert_eq!(response["object"], "text_completion");
+        assert_eq!(response["choices"][0]["text"], "Once there was");
+        assert_eq!(response["choices"][0]["finish_reason"], "length");
+        assert_eq!(response["usage"]["total_tokens"], 7);
+    }
+
+    #[test]
+    fn test_merge_choices_reindexes_and_sums_usage() {
+        let message = |id: &str, text: &str| {
+            json!({
+                "id": id,
+                "model": "m",
+                "content": [{"type": "text", "text": text}],
+                "stop_reason": "end_turn",
+                "usage": {"input_tokens": 10, "output_tokens": 3}
+            })
+        };
+        let merged = merge_choices(vec![
+            convert_response(&message("msg_a", "one"), 0),
+            convert_response(&message("msg_b", "two"), 0),
+            convert_response(&message("msg_c", "three"), 0),
+        ]);
+
+        assert_eq!(merged["id"], "chatcmpl-a");
+        let choices = merged["choices"].as_array().unwrap();
+        assert_eq!(choices.len(), 3);
+        for (i, choice) in choices.iter().enumerate() {
+            assert_eq!(choice["index"], i);
+        }
+        assert_eq!(choices[2]["message"]["content"], "three");
+        assert_eq!(merged["usage"]["prompt_tokens"], 30);
+        assert_eq!(merged["usage"]["completion_tokens"], 9);
+        assert_eq!(merged["usage"]["total_tokens"], 39);
+    }
+
+    #[test]
+    fn test_convert_request_rejects_invalid_n() {
+        let req: ChatCompletionRequest = serde_json::from_value(json!({
+            "model": "m",
+            "n": 0,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+        assert!(matches!(
+            convert_request(&req),
+            Err(ConversionError::InvalidChoiceCount(0))
+        ));
+    }
+
+    #[test]
+    fn test_convert_tool_message_with_image() {
+        let req: ChatCompletionRequest = serde_json::from_value(json!({
+            "model": "m",
+            "messages": [
+                {"role": "user", "content": "take a screenshot"},
+                {"role": "assistant", "content": null, "tool_calls": [
+                    {"id": "call_1", "type": "function", "function": {"name": "screenshot", "arguments": "{}"}}
+                ]},
+                {"role": "tool", "tool_call_id": "call_1", "content": [
+                    {"type": "text", "text": "done"},
+                    {"type": "image_url", "image_url": {"url": "data:image/png;base64,AAAA"}}
+                ]}
+            ]
+        }))
+        .unwrap();
+        let converted = convert_request(&req).unwrap();
+
+        let result = &converted.messages[2].content[0];
+        assert_eq!(result["type"], "tool_result");
+        assert_eq!(result["content"][0]["text"], "done");
+        assert_eq!(result["content"][1]["type"], "image");
+        assert_eq!(result["content"][1]["source"]["data"], "AAAA");
+    }
+}
@@ -0,0 +1,39 @@
+//! 返回 OpenAI 格式错误的 JSON 请求体提取器
+
+use axum::{
+    Json,
+    extract::{FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::de::DeserializeOwned;
+
+use crate::common::json::{REQUEST_TOO_LARGE, classify_rejection};
+
+use super::types::OpenAiErrorResponse;
+
+/// 与 `axum::Json` 相同，但解析失败时返回 OpenAI 格式的 400 / 413 错误
+pub struct OpenAiJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for OpenAiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(Self(value)),
+            Err(rejection) => {
+                let (status, message) = classify_rejection(&rejection);
+                tracing::warn!("请求体解析失败 ({}): {}", status, message);
+                let mut error = OpenAiErrorResponse::new("invalid_request_error", message);
+                if status == StatusCode::PAYLOAD_TOO_LARGE {
+                    error = error.with_code(REQUEST_TOO_LARGE);
+                }
+                Err((status, Json(error)).into_response())
+            }
+        }
+    }
+}
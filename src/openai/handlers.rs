@@ -0,0 +1,505 @@
+//! OpenAI Chat Completions / Completions / Responses 端点处理器
+
+use axum::{
+    Extension,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use bytes::Bytes;
+use futures::{StreamExt, future::join_all, stream};
+
+use crate::anthropic::handlers::{
+    HandlerError, PreparedRequest, aggregate_message, complete_message, open_event_stream,
+    prepare_request, sse_response, with_response_headers,
+};
+use crate::anthropic::middleware::{
+    ApiKeyLabel, AppState, acquire_extra_permits, hold_until_body_done,
+};
+use crate::anthropic::pipeline::sse_stream;
+use crate::anthropic::types::{Message, MessagesRequest};
+use crate::common::auth;
+use crate::kiro::retry_budget;
+use crate::model::config::Priority;
+
+use crate::usage::UsageRecorder;
+
+use super::converter::{
+    completion_prompt, convert_completion_request, convert_completion_response, convert_request,
+    convert_response, merge_choices,
+};
+use super::extract::OpenAiJson;
+use super::responses::{self, ResponsesStreamEncoder};
+use super::stream::{ChatStreamEncoder, ChoicesStreamEncoder, CompletionStreamEncoder};
+use super::structured::{StructuredMode, retry_prompt};
+use super::types::{
+    ChatCompletionRequest, CompletionRequest, OpenAiErrorResponse, ResponsesRequest,
+};
+
+/// 将 Anthropic 错误类型映射为 OpenAI 错误响应
+fn error_response((status, error): HandlerError) -> Response {
+    let error_type = match error.error.error_type.as_str() {
+        "invalid_request_error" => "invalid_request_error",
+        "authentication_error" => "authentication_error",
+        _ => "api_error",
+    };
+    let mut body = OpenAiErrorResponse::new(error_type, error.error.message);
+    if let Some(code) = error.error.code {
+        if code == "context_length_exceeded" {
+            body = body.with_param("messages");
+        }
+        body = body.with_code(code);
+    }
+    (status, Json(body)).into_response()
+}
+
+/// 转换 OpenAI 请求，失败时返回 400 响应
+#[allow(clippy::result_large_err)]
+fn convert_or_bad_request(payload: &ChatCompletionRequest) -> Result<MessagesRequest, Response> {
+    convert_request(payload).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(OpenAiErrorResponse::new(
+                "invalid_request_error",
+                e.to_string(),
+            )),
+        )
+            .into_response()
+    })
+}
+
+/// POST /v1/chat/completions
+///
+/// OpenAI 兼容的对话接口，内部转换为 Anthropic 请求后复用 /v1/messages 的处理流程
+pub async fn post_chat_completions(
+    State(state): State<AppState>,
+    Extension(api_key): Extension<ApiKeyLabel>,
+    priority: Option<Extension<Priority>>,
+    request_headers: HeaderMap,
+    OpenAiJson(payload): OpenAiJson<ChatCompletionRequest>,
+) -> Response {
+    tracing::info!(
+        model = %payload.model,
+        stream = %payload.stream,
+        message_count = %payload.messages.len(),
+        "Received POST /v1/chat/completions request"
+    );
+
+    let mut usage = UsageRecorder::new(
+        state.usage_store.clone(),
+        "/v1/chat/completions",
+        api_key.0,
+        &payload.model,
+        payload.stream,
+    )
+    .with_rate_limiter(state.rate_limiter.clone());
+
+    let messages_request = match convert_or_bad_request(&payload) {
+        Ok(req) => req,
+        Err(resp) => {
+            usage.set_status(resp.status().as_u16());
+            return resp;
+        }
+    };
+
+    let prepared = match prepare_request(&state, messages_request, &request_headers) {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            usage.set_status(e.0.as_u16());
+            return error_response(e);
+        }
+    };
+    retry_budget::budget().record_request();
+
+    let priority = priority.map(|Extension(p)| p).unwrap_or_default();
+    let response = complete(
+        &state,
+        &payload,
+        &request_headers,
+        priority,
+        &prepared,
+        usage,
+    )
+    .await;
+    with_response_headers(response, &prepared)
+}
+
+/// POST /v1/completions
+///
+/// 旧版文本补全接口，prompt 作为单条 user 消息发送，响应映射回 text_completion 格式
+pub async fn post_completions(
+    State(state): State<AppState>,
+    Extension(api_key): Extension<ApiKeyLabel>,
+    request_headers: HeaderMap,
+    OpenAiJson(payload): OpenAiJson<CompletionRequest>,
+) -> Response {
+    tracing::info!(
+        model = %payload.model,
+        stream = %payload.stream,
+        "Received POST /v1/completions request"
+    );
+
+    let mut usage = UsageRecorder::new(
+        state.usage_store.clone(),
+        "/v1/completions",
+        api_key.0,
+        &payload.model,
+        payload.stream,
+    )
+    .with_rate_limiter(state.rate_limiter.clone());
+
+    let messages_request = match convert_completion_request(&payload) {
+        Ok(req) => req,
+        Err(e) => {
+            usage.set_status(StatusCode::BAD_REQUEST.as_u16());
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    OpenAiErrorResponse::new("invalid_request_error", e.to_string())
+                        .with_param("prompt"),
+                ),
+            )
+                .into_response();
+        }
+    };
+
+    let prepared = match prepare_request(&state, messages_request, &request_headers) {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            usage.set_status(e.0.as_u16());
+            return error_response(e);
+        }
+    };
+    retry_budget::budget().record_request();
+
+    let created = chrono::Utc::now().timestamp();
+    let echo = payload.echo.then(|| {
+        completion_prompt(&payload.prompt)
+            .unwrap_or_default()
+            .to_string()
+    });
+
+    let response = if payload.stream {
+        let include_usage = payload
+            .stream_options
+            .as_ref()
+            .is_some_and(|o| o.include_usage);
+        let encoder = CompletionStreamEncoder::new(&payload.model, created, include_usage, echo);
+
+        match open_event_stream(&prepared, usage).await {
+            Ok(events) => sse_stream(events, encoder),
+            Err(e) => error_response(e),
+        }
+    } else {
+        match aggregate_message(&prepared, &mut usage).await {
+            Ok(message) => (
+                StatusCode::OK,
+                Json(convert_completion_response(
+                    &message,
+                    created,
+                    echo.as_deref(),
+                )),
+            )
+                .into_response(),
+            Err(e) => error_response(e),
+        }
+    };
+    with_response_headers(response, &prepared)
+}
+
+/// POST /v1/responses
+///
+/// OpenAI Responses 接口，输入项转换为 Anthropic 消息后复用 /v1/messages 的处理流程
+pub async fn post_responses(
+    State(state): State<AppState>,
+    Extension(api_key): Extension<ApiKeyLabel>,
+    request_headers: HeaderMap,
+    OpenAiJson(payload): OpenAiJson<ResponsesRequest>,
+) -> Response {
+    tracing::info!(
+        model = %payload.model,
+        stream = %payload.stream,
+        "Received POST /v1/responses request"
+    );
+
+    let mut usage = UsageRecorder::new(
+        state.usage_store.clone(),
+        "/v1/responses",
+        api_key.0,
+        &payload.model,
+        payload.stream,
+    )
+    .with_rate_limiter(state.rate_limiter.clone());
+
+    let messages_request = match responses::convert_request(&payload) {
+        Ok(req) => req,
+        Err(e) => {
+            usage.set_status(StatusCode::BAD_REQUEST.as_u16());
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(OpenAiErrorResponse::new(
+                    "invalid_request_error",
+                    e.to_string(),
+                )),
+            )
+                .into_response();
+        }
+    };
+
+    let prepared = match prepare_request(&state, messages_request, &request_headers) {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            usage.set_status(e.0.as_u16());
+            return error_response(e);
+        }
+    };
+    retry_budget::budget().record_request();
+
+    let created = chrono::Utc::now().timestamp();
+    let response = if payload.stream {
+        let encoder = ResponsesStreamEncoder::new(&payload.model, created);
+
+        match open_event_stream(&prepared, usage).await {
+            Ok(events) => sse_stream(events, encoder),
+            Err(e) => error_response(e),
+        }
+    } else {
+        match aggregate_message(&prepared, &mut usage).await {
+            Ok(message) => (
+                StatusCode::OK,
+                Json(responses::convert_response(&message, created)),
+            )
+                .into_response(),
+            Err(e) => error_response(e),
+        }
+    };
+    with_response_headers(response, &prepared)
+}
+
+/// 调用上游并按请求的格式（流式 / 非流式 / 结构化输出）生成响应
+async fn complete(
+    state: &AppState,
+    payload: &ChatCompletionRequest,
+    request_headers: &HeaderMap,
+    priority: Priority,
+    prepared: &PreparedRequest,
+    mut usage: UsageRecorder,
+) -> Response {
+    let created = chrono::Utc::now().timestamp();
+    let include_usage = payload
+        .stream_options
+        .as_ref()
+        .is_some_and(|o| o.include_usage);
+
+    // 结构化输出需要先拿到完整文本校验，流式请求在校验通过后一次性回放
+    if let Some(mode) = payload
+        .response_format
+        .as_ref()
+        .and_then(StructuredMode::from_response_format)
+    {
+        let mut message = match aggregate_message(prepared, &mut usage).await {
+            Ok(message) => message,
+            Err(e) => return error_response(e),
+        };
+
+        if let Err(error) = apply_structured_output(&mode, &mut message) {
+            if !retry_budget::budget().try_acquire() {
+                tracing::warn!(
+                    "结构化输出校验失败，重试预算已耗尽，不再重新请求: {}",
+                    error
+                );
+                usage.set_status(StatusCode::BAD_GATEWAY.as_u16());
+                return structured_output_error(&error);
+            }
+            tracing::warn!("结构化输出校验失败，重新请求一次: {}", error);
+            message = match reask(
+                state,
+                payload,
+                request_headers,
+                &message,
+                &error,
+                &mut usage,
+            )
+            .await
+            {
+                Ok(retried) => retried,
+                Err(resp) => return resp,
+            };
+            if let Err(error) = apply_structured_output(&mode, &mut message) {
+                tracing::warn!("重试后结构化输出仍校验失败: {}", error);
+                usage.set_status(StatusCode::BAD_GATEWAY.as_u16());
+                return structured_output_error(&error);
+            }
+        }
+
+        return if payload.stream {
+            let mut encoder = ChatStreamEncoder::new(&payload.model, created, include_usage);
+            let chunks = encoder.encode_message(&message);
+            usage.set_status(StatusCode::OK.as_u16());
+            sse_response(stream::iter(chunks.into_iter().map(|c| Ok(Bytes::from(c)))))
+        } else {
+            (StatusCode::OK, Json(convert_response(&message, created))).into_response()
+        };
+    }
+
+    let n = payload.n.unwrap_or(1) as usize;
+    if n > 1 {
+        // 每个候选都是一次独立的上游调用，额外的调用同样占用并发与准入名额
+        let key = auth::api_key_from_headers(request_headers).unwrap_or_default();
+        let permits = match acquire_extra_permits(state, &key, priority, n - 1).await {
+            Ok(permits) => permits,
+            Err(response) => {
+                usage.set_status(response.status().as_u16());
+                return response;
+            }
+        };
+        let response = fan_out(payload, prepared, usage, n, created, include_usage).await;
+        return hold_until_body_done(response, permits);
+    }
+
+    if payload.stream {
+        let encoder = ChatStreamEncoder::new(&payload.model, created, include_usage);
+
+        match open_event_stream(prepared, usage).await {
+            Ok(events) => sse_stream(events, encoder),
+            Err(e) => error_response(e),
+        }
+    } else {
+        match aggregate_message(prepared, &mut usage).await {
+            Ok(message) => {
+                (StatusCode::OK, Json(convert_response(&message, created))).into_response()
+            }
+            Err(e) => error_response(e),
+        }
+    }
+}
+
+/// n > 1：并行发起 n 次上游请求（可能落在不同凭据上），合并为一个多候选响应
+///
+/// 每次上游调用使用独立的用量采集器；任一请求失败时整体返回该错误
+async fn fan_out(
+    payload: &ChatCompletionRequest,
+    prepared: &PreparedRequest,
+    usage: UsageRecorder,
+    n: usize,
+    created: i64,
+    include_usage: bool,
+) -> Response {
+    let mut recorders: Vec<UsageRecorder> = (1..n).map(|_| usage.sibling()).collect();
+    recorders.insert(0, usage);
+
+    if payload.stream {
+        let opened = join_all(
+            recorders
+                .into_iter()
+                .map(|usage| open_event_stream(prepared, usage)),
+        )
+        .await;
+
+        let mut streams = Vec::with_capacity(n);
+        for (index, result) in opened.into_iter().enumerate() {
+            match result {
+                Ok(events) => streams.push(events.map(move |event| (index, event)).boxed()),
+                Err(e) => return error_response(e),
+            }
+        }
+
+        let encoder = ChoicesStreamEncoder::new(&payload.model, created, n, include_usage);
+        sse_stream(stream::select_all(streams), encoder)
+    } else {
+        // 各候选需要独立的上游调用，不参与相同请求合并
+        let results = join_all(
+            recorders
+                .iter_mut()
+                .map(|usage| complete_message(prepared, usage)),
+        )
+        .await;
+
+        let mut responses = Vec::with_capacity(n);
+        for result in results {
+            match result {
+                Ok(message) => responses.push(convert_response(&message, created)),
+                Err(e) => return error_response(e),
+            }
+        }
+        (StatusCode::OK, Json(merge_choices(responses))).into_response()
+    }
+}
+
+/// 校验消息中的文本输出，通过后用规范化的 JSON 替换文本块
+///
+/// 包含工具调用的响应不做校验（模型选择了调用工具而非直接回答）
+fn apply_structured_output(
+    mode: &StructuredMode,
+    message: &mut serde_json::Value,
+) -> Result<(), String> {
+    let Some(blocks) = message["content"].as_array_mut() else {
+        return Err("响应中没有内容".to_string());
+    };
+    if blocks.iter().any(|b| b["type"] == "tool_use") {
+        return Ok(());
+    }
+
+    let text: String = blocks
+        .iter()
+        .filter(|b| b["type"] == "text")
+        .filter_map(|b| b["text"].as_str())
+        .collect();
+    let normalized = mode.check(&text)?;
+
+    blocks.retain(|b| b["type"] != "text");
+    blocks.push(serde_json::json!({ "type": "text", "text": normalized }));
+    Ok(())
+}
+
+/// 模型输出不符合 `response_format` 时的 502 响应
+fn structured_output_error(error: &str) -> Response {
+    (
+        StatusCode::BAD_GATEWAY,
+        Json(
+            OpenAiErrorResponse::new(
+                "api_error",
+                format!("模型输出不符合 response_format: {}", error),
+            )
+            .with_param("response_format")
+            .with_code("invalid_structured_output"),
+        ),
+    )
+        .into_response()
+}
+
+/// 将不合格的输出和校验错误追加到对话中，重新请求一次（沿用原请求头，保持预设与路由选择一致）
+async fn reask(
+    state: &AppState,
+    payload: &ChatCompletionRequest,
+    request_headers: &HeaderMap,
+    failed: &serde_json::Value,
+    error: &str,
+    usage: &mut UsageRecorder,
+) -> Result<serde_json::Value, Response> {
+    let mut messages_request = convert_or_bad_request(payload)?;
+
+    let previous: String = failed["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|b| b["type"] == "text")
+        .filter_map(|b| b["text"].as_str())
+        .collect();
+    messages_request.messages.push(Message {
+        role: "assistant".to_string(),
+        content: serde_json::json!(previous),
+    });
+    messages_request.messages.push(Message {
+        role: "user".to_string(),
+        content: serde_json::json!(retry_prompt(error)),
+    });
+
+    let prepared = prepare_request(state, messages_request, request_headers).map_err(|e| {
+        usage.set_status(e.0.as_u16());
+        error_response(e)
+    })?;
+    aggregate_message(&prepared, usage)
+        .await
+        .map_err(error_response)
+}
@@ -0,0 +1,34 @@
+//! OpenAI API 兼容模块
+//!
+//! 将 OpenAI Chat Completions / Completions / Responses 请求转换为 Anthropic 请求，
+//! 复用 Anthropic 模块的上游调用与事件解析逻辑，再将结果转换回 OpenAI 格式。
+//!
+//! # 支持的端点
+//! - `POST /v1/chat/completions` - 创建对话补全（支持流式、并行工具调用与结构化输出）
+//! - `POST /v1/completions` - 旧版文本补全（prompt 转换为单条 user 消息）
+//! - `POST /v1/responses` - Responses API（支持输入项数组、函数调用与流式事件）
+
+mod converter;
+pub(crate) mod extract;
+pub mod handlers;
+mod responses;
+mod stream;
+mod structured;
+pub(crate) mod types;
+
+use crate::anthropic::types::MessagesRequest;
+
+/// 将 OpenAI 端点（`/v1/chat/completions`、`/v1/completions`、`/v1/responses`）的请求体
+/// 转换为 Anthropic Messages 请求，供 `replay --dry-run` 在本地检查转换结果
+pub fn to_messages_request(
+    route: &str,
+    body: serde_json::Value,
+) -> anyhow::Result<MessagesRequest> {
+    let request = match route {
+        "/v1/chat/completions" => converter::convert_request(&serde_json::from_value(body)?),
+        "/v1/completions" => converter::convert_completion_request(&serde_json::from_value(body)?),
+        "/v1/responses" => responses::convert_request(&serde_json::from_value(body)?),
+        _ => anyhow::bail!("不支持的路由: {}", route),
+    };
+    request.map_err(|e| anyhow::anyhow!("{}", e))
+}
@@ -0,0 +1,778 @@
+//! OpenAI Responses API 兼容模块
+//!
+//! 将 Responses 请求的输入项（消息、函数调用、函数结果）转换为 Anthropic Messages 请求，
+//! 再将 Anthropic 消息 / SSE 事件映射回 Responses 的输出项与流式事件。
+
+use std::collections::HashMap;
+
+use serde_json::json;
+
+use crate::anthropic::pipeline::SseEncoder;
+use crate::anthropic::stream::SseEvent;
+use crate::anthropic::types::{Message, MessagesRequest, SystemMessage, Tool};
+
+use super::converter::{
+    ConversionError, DEFAULT_MAX_TOKENS, convert_reasoning_effort, convert_tool_choice,
+    parse_data_url, user_metadata,
+};
+use super::types::{ResponsesRequest, ResponsesTool};
+
+/// 将 Responses 请求转换为 Anthropic Messages 请求
+pub fn convert_request(req: &ResponsesRequest) -> Result<MessagesRequest, ConversionError> {
+    if req.previous_response_id.is_some() {
+        return Err(ConversionError::PreviousResponseUnsupported);
+    }
+
+    let mut system = Vec::new();
+    if let Some(instructions) = req.instructions.as_ref().filter(|s| !s.is_empty()) {
+        system.push(SystemMessage {
+            text: instructions.clone(),
+        });
+    }
+
+    let mut messages = Vec::new();
+    match &req.input {
+        serde_json::Value::String(text) => push_block(
+            &mut messages,
+            "user",
+            json!({ "type": "text", "text": text }),
+        ),
+        serde_json::Value::Array(items) => {
+            for item in items {
+                convert_item(item, &mut system, &mut messages)?;
+            }
+        }
+        _ => {
+            return Err(ConversionError::UnsupportedInput(
+                "input 必须是字符串或输入项数组",
+            ));
+        }
+    }
+
+    Ok(MessagesRequest {
+        model: req.model.clone(),
+        max_tokens: req.max_output_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        temperature: None,
+        messages,
+        stream: req.stream,
+        system: if system.is_empty() {
+            None
+        } else {
+            Some(system)
+        },
+        tools: req.tools.as_ref().map(|tools| convert_tools(tools)),
+        tool_choice: req.tool_choice.as_ref().map(convert_tool_choice),
+        thinking: req
+            .reasoning
+            .as_ref()
+            .and_then(|r| r.effort.as_deref())
+            .and_then(convert_reasoning_effort),
+        metadata: user_metadata(&req.user),
+    })
+}
+
+/// 转换单个输入项，追加到系统提示或消息列表
+fn convert_item(
+    item: &serde_json::Value,
+    system: &mut Vec<SystemMessage>,
+    messages: &mut Vec<Message>,
+) -> Result<(), ConversionError> {
+    // 简写形式 `{"role": "user", "content": "..."}` 没有 type 字段
+    match item["type"].as_str().unwrap_or("message") {
+        "message" => match item["role"].as_str().unwrap_or("user") {
+            "system" | "developer" => {
+                let text: Vec<String> = content_blocks(&item["content"])
+                    .iter()
+                    .filter_map(|b| b["text"].as_str().map(str::to_string))
+                    .collect();
+                if !text.is_empty() {
+                    system.push(SystemMessage {
+                        text: text.join("\n"),
+                    });
+                }
+            }
+            role @ ("user" | "assistant") => {
+                for block in content_blocks(&item["content"]) {
+                    push_block(messages, role, block);
+                }
+            }
+            other => return Err(ConversionError::UnsupportedRole(other.to_string())),
+        },
+        "function_call" => {
+            let call_id = item["call_id"]
+                .as_str()
+                .ok_or(ConversionError::UnsupportedInput(
+                    "function_call 缺少 call_id",
+                ))?;
+            let arguments = item["arguments"].as_str().unwrap_or_default();
+            let input: serde_json::Value = if arguments.trim().is_empty() {
+                json!({})
+            } else {
+                serde_json::from_str(arguments).unwrap_or_else(|e| {
+                    tracing::warn!("function_call 参数 JSON 解析失败: {}, id: {}", e, call_id);
+                    json!({})
+                })
+            };
+            push_block(
+                messages,
+                "assistant",
+                json!({
+                    "type": "tool_use",
+                    "id": call_id,
+                    "name": item["name"],
+                    "input": input
+                }),
+            );
+        }
+        "function_call_output" => {
+            let call_id = item["call_id"]
+                .as_str()
+                .ok_or(ConversionError::UnsupportedInput(
+                    "function_call_output 缺少 call_id",
+                ))?;
+            // output 可以是字符串，也可以是 input_text / input_image 内容数组
+            let output = match &item["output"] {
+                serde_json::Value::String(s) => json!(s),
+                parts @ serde_json::Value::Array(_) => json!(content_blocks(parts)),
+                other => json!(other.to_string()),
+            };
+            push_block(
+                messages,
+                "user",
+                json!({
+                    "type": "tool_result",
+                    "tool_use_id": call_id,
+                    "content": output
+                }),
+            );
+        }
+        // 推理内容无法回传给上游
+        "reasoning" => {}
+        other => tracing::warn!("忽略不支持的输入项类型: {}", other),
+    }
+    Ok(())
+}
+
+/// 将消息内容转换为 Anthropic 内容块（支持文本和 base64 data URL 图片）
+fn content_blocks(content: &serde_json::Value) -> Vec<serde_json::Value> {
+    let text_block = |text: &str| json!({ "type": "text", "text": text });
+    match content {
+        serde_json::Value::String(s) if !s.is_empty() => vec![text_block(s)],
+        serde_json::Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part["type"].as_str() {
+                Some("input_text" | "output_text" | "text") => part["text"]
+                    .as_str()
+                    .filter(|t| !t.is_empty())
+                    .map(text_block),
+                Some("refusal") => part["refusal"].as_str().map(text_block),
+                Some("input_image") => {
+                    let image = part["image_url"].as_str().and_then(parse_data_url);
+                    if image.is_none() {
+                        tracing::warn!("跳过不支持的图片地址（仅支持 base64 data URL）");
+                    }
+                    image
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// 追加内容块，与上一条同角色的消息合并（如并行的多个函数调用 / 函数结果）
+fn push_block(messages: &mut Vec<Message>, role: &str, block: serde_json::Value) {
+    if let Some(last) = messages.last_mut().filter(|m| m.role == role)
+        && let serde_json::Value::Array(blocks) = &mut last.content
+    {
+        blocks.push(block);
+        return;
+    }
+    messages.push(Message {
+        role: role.to_string(),
+        content: json!([block]),
+    });
+}
+
+/// 转换工具定义（仅支持 function 类型）
+fn convert_tools(tools: &[ResponsesTool]) -> Vec<Tool> {
+    tools
+        .iter()
+        .filter_map(|t| {
+            if t.tool_type != "function" {
+                tracing::warn!("忽略不支持的工具类型: {}", t.tool_type);
+                return None;
+            }
+            let input_schema: HashMap<String, serde_json::Value> = t
+                .parameters
+                .as_ref()
+                .and_then(|p| serde_json::from_value(p.clone()).ok())
+                .unwrap_or_else(|| {
+                    HashMap::from([
+                        ("type".to_string(), json!("object")),
+                        ("properties".to_string(), json!({})),
+                    ])
+                });
+            Some(Tool {
+                name: t.name.clone()?,
+                description: t.description.clone().unwrap_or_default(),
+                input_schema,
+            })
+        })
+        .collect()
+}
+
+/// 由 Anthropic 消息 ID 生成 response ID
+fn response_id(message_id: &str) -> String {
+    format!(
+        "resp_{}",
+        message_id.strip_prefix("msg_").unwrap_or(message_id)
+    )
+}
+
+/// 生成输出项 ID（`msg_` / `rs_` / `fc_` 前缀 + response ID + 序号）
+fn item_id(prefix: &str, response_id: &str, index: usize) -> String {
+    format!(
+        "{}_{}_{}",
+        prefix,
+        response_id.strip_prefix("resp_").unwrap_or(response_id),
+        index
+    )
+}
+
+fn output_text(text: &str) -> serde_json::Value {
+    json!({ "type": "output_text", "text": text, "annotations": [] })
+}
+
+fn summary_text(text: &str) -> serde_json::Value {
+    json!({ "type": "summary_text", "text": text })
+}
+
+/// 构建 response 对象
+fn response_object(
+    id: &str,
+    model: &str,
+    created: i64,
+    output: &[serde_json::Value],
+    stop_reason: Option<&str>,
+    input_tokens: i64,
+    output_tokens: i64,
+) -> serde_json::Value {
+    let (status, incomplete_details) = match stop_reason {
+        None => ("in_progress", serde_json::Value::Null),
+        Some("max_tokens") => ("incomplete", json!({ "reason": "max_output_tokens" })),
+        Some("refusal") => ("incomplete", json!({ "reason": "content_filter" })),
+        Some(_) => ("completed", serde_json::Value::Null),
+    };
+    json!({
+        "id": id,
+        "object": "response",
+        "created_at": created,
+        "status": status,
+        "incomplete_details": incomplete_details,
+        "model": model,
+        "output": output,
+        "usage": {
+            "input_tokens": input_tokens,
+            "output_tokens": output_tokens,
+            "total_tokens": input_tokens + output_tokens
+        }
+    })
+}
+
+/// 将 Anthropic 消息转换为 Responses 响应
+pub fn convert_response(message: &serde_json::Value, created: i64) -> serde_json::Value {
+    let id = response_id(message["id"].as_str().unwrap_or_default());
+    let output: Vec<serde_json::Value> = message["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .filter_map(|(index, block)| match block["type"].as_str() {
+            Some("text") => Some(json!({
+                "id": item_id("msg", &id, index),
+                "type": "message",
+                "status": "completed",
+                "role": "assistant",
+                "content": [output_text(block["text"].as_str().unwrap_or_default())]
+            })),
+            Some("thinking") => Some(json!({
+                "id": item_id("rs", &id, index),
+                "type": "reasoning",
+                "summary": [summary_text(block["thinking"].as_str().unwrap_or_default())]
+            })),
+            Some("tool_use") => Some(json!({
+                "id": item_id("fc", &id, index),
+                "type": "function_call",
+                "status": "completed",
+                "call_id": block["id"],
+                "name": block["name"],
+                "arguments": block["input"].to_string()
+            })),
+            _ => None,
+        })
+        .collect();
+
+    response_object(
+        &id,
+        message["model"].as_str().unwrap_or_default(),
+        created,
+        &output,
+        Some(message["stop_reason"].as_str().unwrap_or("end_turn")),
+        message["usage"]["input_tokens"].as_i64().unwrap_or(0),
+        message["usage"]["output_tokens"].as_i64().unwrap_or(0),
+    )
+}
+
+/// Responses 流式编码器
+///
+/// 每个 Anthropic 内容块对应一个输出项，块内增量累积后在 `content_block_stop`
+/// 时输出对应的 `*.done` 事件，`message_stop` 时输出包含完整结果的 `response.completed`
+pub struct ResponsesStreamEncoder {
+    /// response ID
+    id: String,
+    /// 模型名称
+    model: String,
+    /// 创建时间（Unix 秒）
+    created: i64,
+    /// 下一个事件序号
+    sequence_number: u64,
+    /// 已开始的输出项
+    output: Vec<serde_json::Value>,
+    /// 各输出项累积的文本 / 参数
+    buffers: Vec<String>,
+    /// Anthropic 块索引 -> 输出项索引
+    block_outputs: HashMap<i64, usize>,
+    input_tokens: i64,
+    output_tokens: i64,
+    stop_reason: Option<String>,
+}
+
+impl ResponsesStreamEncoder {
+    pub fn new(model: impl Into<String>, created: i64) -> Self {
+        Self {
+            id: String::new(),
+            model: model.into(),
+            created,
+            sequence_number: 0,
+            output: Vec::new(),
+            buffers: Vec::new(),
+            block_outputs: HashMap::new(),
+            input_tokens: 0,
+            output_tokens: 0,
+            stop_reason: None,
+        }
+    }
+
+    /// 将一个 Anthropic SSE 事件编码为零个或多个 Responses SSE 字符串
+    pub fn encode(&mut self, event: &SseEvent) -> Vec<String> {
+        let data = &event.data;
+        match event.event.as_str() {
+            "message_start" => {
+                self.id = response_id(data["message"]["id"].as_str().unwrap_or_default());
+                self.input_tokens = data["message"]["usage"]["input_tokens"]
+                    .as_i64()
+                    .unwrap_or(0);
+                let response = self.response();
+                vec![
+                    self.event("response.created", json!({ "response": response })),
+                    self.event("response.in_progress", json!({ "response": response })),
+                ]
+            }
+            "content_block_start" => {
+                let Some(block_index) = data["index"].as_i64() else {
+                    return Vec::new();
+                };
+                self.start_item(block_index, &data["content_block"])
+            }
+            "content_block_delta" => {
+                let Some(output_index) = self.output_index(data) else {
+                    return Vec::new();
+                };
+                let delta = &data["delta"];
+                let (event_type, text) = match delta["type"].as_str() {
+                    Some("text_delta") => ("response.output_text.delta", &delta["text"]),
+                    Some("thinking_delta") => {
+                        ("response.reasoning_summary_text.delta", &delta["thinking"])
+                    }
+                    Some("input_json_delta") => (
+                        "response.function_call_arguments.delta",
+                        &delta["partial_json"],
+                    ),
+                    _ => return Vec::new(),
+                };
+                let text = text.as_str().unwrap_or_default();
+                if text.is_empty() {
+                    return Vec::new();
+                }
+                self.buffers[output_index].push_str(text);
+
+                let mut payload = self.item_ref(output_index);
+                payload["delta"] = json!(text);
+                vec![self.event(event_type, payload)]
+            }
+            "content_block_stop" => match self.output_index(data) {
+                Some(output_index) => self.finish_item(output_index),
+                None => Vec::new(),
+            },
+            "message_delta" => {
+                self.stop_reason = data["delta"]["stop_reason"].as_str().map(str::to_string);
+                if let Some(input_tokens) = data["usage"]["input_tokens"].as_i64() {
+                    self.input_tokens = input_tokens;
+                }
+                self.output_tokens = data["usage"]["output_tokens"].as_i64().unwrap_or(0);
+                Vec::new()
+            }
+            "message_stop" => {
+                if self.stop_reason.is_none() {
+                    self.stop_reason = Some("end_turn".to_string());
+                }
+                let response = self.response();
+                let event_type = if response["status"] == "incomplete" {
+                    "response.incomplete"
+                } else {
+                    "response.completed"
+                };
+                vec![self.event(event_type, json!({ "response": response }))]
+            }
+            // SSE 注释行，客户端会忽略，仅用于保活
+            "ping" => vec![": ping\n\n".to_string()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// 开始一个新的输出项
+    fn start_item(&mut self, block_index: i64, block: &serde_json::Value) -> Vec<String> {
+        let output_index = self.output.len();
+        let item = match block["type"].as_str() {
+            Some("text") => json!({
+                "id": item_id("msg", &self.id, output_index),
+                "type": "message",
+                "status": "in_progress",
+                "role": "assistant",
+                "content": []
+            }),
+            Some("thinking") => json!({
+                "id": item_id("rs", &self.id, output_index),
+                "type": "reasoning",
+                "summary": []
+            }),
+            Some("tool_use") => json!({
+                "id": item_id("fc", &self.id, output_index),
+                "type": "function_call",
+                "status": "in_progress",
+                "call_id": block["id"],
+                "name": block["name"],
+                "arguments": ""
+            }),
+            _ => return Vec::new(),
+        };
+        self.block_outputs.insert(block_index, output_index);
+        self.output.push(item.clone());
+        self.buffers.push(String::new());
+
+        let mut events = vec![self.event(
+            "response.output_item.added",
+            json!({ "output_index": output_index, "item": item }),
+        )];
+        let mut part = self.item_ref(output_index);
+        match item["type"].as_str() {
+            Some("message") => {
+                part["part"] = output_text("");
+                events.push(self.event("response.content_part.added", part));
+            }
+            Some("reasoning") => {
+                part["part"] = summary_text("");
+                events.push(self.event("response.reasoning_summary_part.added", part));
+            }
+            _ => {}
+        }
+        events
+    }
+
+    /// 结束输出项，输出累积的完整内容
+    fn finish_item(&mut self, output_index: usize) -> Vec<String> {
+        let text = std::mem::take(&mut self.buffers[output_index]);
+        let mut done = self.item_ref(output_index);
+        let mut item = self.output[output_index].take();
+        let mut events = Vec::new();
+
+        match item["type"].as_str() {
+            Some("message") => {
+                item["status"] = json!("completed");
+                item["content"] = json!([output_text(&text)]);
+                let mut text_done = done.clone();
+                text_done["text"] = json!(text);
+                events.push(self.event("response.output_text.done", text_done));
+                done["part"] = output_text(&text);
+                events.push(self.event("response.content_part.done", done));
+            }
+            Some("reasoning") => {
+                item["summary"] = json!([summary_text(&text)]);
+                let mut text_done = done.clone();
+                text_done["text"] = json!(text);
+                events.push(self.event("response.reasoning_summary_text.done", text_done));
+                done["part"] = summary_text(&text);
+                events.push(self.event("response.reasoning_summary_part.done", done));
+            }
+            Some("function_call") => {
+                let arguments = if text.is_empty() {
+                    "{}".to_string()
+                } else {
+                    text
+                };
+                item["status"] = json!("completed");
+                item["arguments"] = json!(arguments);
+                done["arguments"] = json!(arguments);
+                events.push(self.event("response.function_call_arguments.done", done));
+            }
+            _ => {}
+        }
+
+        self.output[output_index] = item.clone();
+        events.push(self.event(
+            "response.output_item.done",
+            json!({ "output_index": output_index, "item": item }),
+        ));
+        events
+    }
+
+    fn output_index(&self, data: &serde_json::Value) -> Option<usize> {
+        data["index"]
+            .as_i64()
+            .and_then(|i| self.block_outputs.get(&i))
+            .copied()
+    }
+
+    /// 增量事件中定位输出项的公共字段
+    fn item_ref(&self, output_index: usize) -> serde_json::Value {
+        let item = &self.output[output_index];
+        let mut payload = json!({ "item_id": item["id"], "output_index": output_index });
+        match item["type"].as_str() {
+            Some("message") => payload["content_index"] = json!(0),
+            Some("reasoning") => payload["summary_index"] = json!(0),
+            _ => {}
+        }
+        payload
+    }
+
+    fn response(&self) -> serde_json::Value {
+        response_object(
+            &self.id,
+            &self.model,
+            self.created,
+            &self.output,
+            self.stop_reason.as_deref(),
+            self.input_tokens,
+            self.output_tokens,
+        )
+    }
+
+    /// 构建带类型与序号的 SSE 事件
+    fn event(&mut self, event_type: &str, mut data: serde_json::Value) -> String {
+        data["type"] = json!(event_type);
+        data["sequence_number"] = json!(self.sequence_number);
+        self.sequence_number += 1;
+        SseEvent::new(event_type, data).to_sse_string()
+    }
+}
+
+impl SseEncoder for ResponsesStreamEncoder {
+    fn encode(&mut self, event: &SseEvent) -> Vec<String> {
+        ResponsesStreamEncoder::encode(self, event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(body: serde_json::Value) -> ResponsesRequest {
+        serde_json::from_value(body).unwrap()
+    }
+
+    /// 解析 SSE 字符串为 (事件类型, 数据)
+    fn parse(sse: &str) -> (String, serde_json::Value) {
+        let mut lines = sse.lines();
+        let event = lines.next().unwrap().strip_prefix("event: ").unwrap();
+        let data = lines.next().unwrap().strip_prefix("data: ").unwrap();
+        (event.to_string(), serde_json::from_str(data).unwrap())
+    }
+
+    #[test]
+    fn test_convert_request_string_input_and_instructions() {
+        let req = request(json!({
+            "model": "claude-sonnet-4-5",
+            "instructions": "be brief",
+            "input": "hi",
+            "max_output_tokens": 64
+        }));
+        let converted = convert_request(&req).unwrap();
+
+        assert_eq!(converted.system.unwrap()[0].text, "be brief");
+        assert_eq!(converted.messages.len(), 1);
+        assert_eq!(converted.messages[0].content[0]["text"], "hi");
+        assert_eq!(converted.max_tokens, 64);
+    }
+
+    #[test]
+    fn test_convert_request_input_items_with_function_calls() {
+        let req = request(json!({
+            "model": "claude-sonnet-4-5",
+            "input": [
+                {"role": "developer", "content": "use tools"},
+                {"type": "message", "role": "user", "content": [
+                    {"type": "input_text", "text": "weather in Paris and Rome?"},
+                    {"type": "input_image", "image_url": "data:image/png;base64,AAAA"}
+                ]},
+                {"type": "reasoning", "id": "rs_1", "summary": []},
+                {"type": "function_call", "call_id": "call_1", "name": "weather", "arguments": "{\"city\":\"Paris\"}"},
+                {"type": "function_call", "call_id": "call_2", "name": "weather", "arguments": "{\"city\":\"Rome\"}"},
+                {"type": "function_call_output", "call_id": "call_1", "output": "sunny"},
+                {"type": "function_call_output", "call_id": "call_2", "output": "rainy"}
+            ],
+            "tools": [
+                {"type": "function", "name": "weather", "parameters": {"type": "object", "properties": {}}},
+                {"type": "web_search"}
+            ],
+            "tool_choice": {"type": "function", "name": "weather"}
+        }));
+        let converted = convert_request(&req).unwrap();
+
+        assert_eq!(converted.system.unwrap()[0].text, "use tools");
+        assert_eq!(converted.messages.len(), 3);
+        assert_eq!(converted.messages[0].content[1]["type"], "image");
+
+        // 并行函数调用与结果分别合并到同一条消息
+        let calls = converted.messages[1].content.as_array().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1]["input"]["city"], "Rome");
+        let results = converted.messages[2].content.as_array().unwrap();
+        assert_eq!(results[0]["tool_use_id"], "call_1");
+        assert_eq!(results[1]["content"], "rainy");
+
+        assert_eq!(converted.tools.unwrap().len(), 1);
+        assert_eq!(
+            converted.tool_choice.unwrap(),
+            json!({"type": "tool", "name": "weather"})
+        );
+    }
+
+    #[test]
+    fn test_convert_request_rejects_previous_response_id() {
+        let req = request(json!({
+            "model": "claude-sonnet-4-5",
+            "input": "continue",
+            "previous_response_id": "resp_1"
+        }));
+        assert!(matches!(
+            convert_request(&req),
+            Err(ConversionError::PreviousResponseUnsupported)
+        ));
+    }
+
+    #[test]
+    fn test_convert_response_output_items() {
+        let message = json!({
+            "id": "msg_abc",
+            "model": "claude-sonnet-4-5",
+            "content": [
+                {"type": "thinking", "thinking": "hmm"},
+                {"type": "text", "text": "calling"},
+                {"type": "tool_use", "id": "t1", "name": "a", "input": {"x": 1}}
+            ],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 10, "output_tokens": 5}
+        });
+        let response = convert_response(&message, 0);
+
+        assert_eq!(response["id"], "resp_abc");
+        assert_eq!(response["status"], "completed");
+        let output = response["output"].as_array().unwrap();
+        assert_eq!(output[0]["type"], "reasoning");
+        assert_eq!(output[0]["summary"][0]["text"], "hmm");
+        assert_eq!(output[1]["content"][0]["text"], "calling");
+        assert_eq!(output[2]["call_id"], "t1");
+        assert_eq!(output[2]["arguments"], "{\"x\":1}");
+        assert_eq!(response["usage"]["total_tokens"], 15);
+    }
+
+    #[test]
+    fn test_stream_encoder_event_sequence() {
+        let mut encoder = ResponsesStreamEncoder::new("m", 0);
+        let events = [
+            SseEvent::new(
+                "message_start",
+                json!({"message": {"id": "msg_1", "usage": {"input_tokens": 3}}}),
+            ),
+            SseEvent::new(
+                "content_block_start",
+                json!({"index": 0, "content_block": {"type": "text", "text": ""}}),
+            ),
+            SseEvent::new(
+                "content_block_delta",
+                json!({"index": 0, "delta": {"type": "text_delta", "text": "Hel"}}),
+            ),
+            SseEvent::new(
+                "content_block_delta",
+                json!({"index": 0, "delta": {"type": "text_delta", "text": "lo"}}),
+            ),
+            SseEvent::new("content_block_stop", json!({"index": 0})),
+            SseEvent::new(
+                "content_block_start",
+                json!({"index": 1, "content_block": {"type": "tool_use", "id": "t1", "name": "a"}}),
+            ),
+            SseEvent::new(
+                "content_block_delta",
+                json!({"index": 1, "delta": {"type": "input_json_delta", "partial_json": "{}"}}),
+            ),
+            SseEvent::new("content_block_stop", json!({"index": 1})),
+            SseEvent::new(
+                "message_delta",
+                json!({"delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 4}}),
+            ),
+            SseEvent::new("message_stop", json!({})),
+        ];
+        let parsed: Vec<(String, serde_json::Value)> = events
+            .iter()
+            .flat_map(|e| encoder.encode(e))
+            .map(|s| parse(&s))
+            .collect();
+
+        let types: Vec<&str> = parsed.iter().map(|(t, _)| t.as_str()).collect();
+        assert_eq!(
+            types,
+            vec![
+                "response.created",
+                "response.in_progress",
+                "response.output_item.added",
+                "response.content_part.added",
+                "response.output_text.delta",
+                "response.output_text.delta",
+                "response.output_text.done",
+                "response.content_part.done",
+                "response.output_item.done",
+                "response.output_item.added",
+                "response.function_call_arguments.delta",
+                "response.function_call_arguments.done",
+                "response.output_item.done",
+                "response.completed",
+            ]
+        );
+        for (index, (event_type, data)) in parsed.iter().enumerate() {
+            assert_eq!(data["type"], event_type.as_str());
+            assert_eq!(data["sequence_number"], index);
+        }
+
+        assert_eq!(parsed[6].1["text"], "Hello");
+        assert_eq!(parsed[6].1["item_id"], "msg_1_0");
+        assert_eq!(parsed[11].1["arguments"], "{}");
+
+        let response = &parsed[13].1["response"];
+        assert_eq!(response["id"], "resp_1");
+        assert_eq!(response["status"], "completed");
+        assert_eq!(response["output"][0]["content"][0]["text"], "Hello");
+        assert_eq!(response["output"][1]["status"], "completed");
+        assert_eq!(response["usage"]["total_tokens"], 7);
+    }
+}
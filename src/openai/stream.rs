@@ -0,0 +1,709 @@
+//! OpenAI 流式响应编码模块
+//!
+//! 将 Anthropic SSE 事件序列转换为 OpenAI `chat.completion.chunk` 流，
+//! 或旧版 Completions 接口的 `text_completion` 流
+
+use std::collections::{HashMap, HashSet};
+
+use serde_json::json;
+
+use crate::anthropic::pipeline::SseEncoder;
+use crate::anthropic::stream::SseEvent;
+
+use super::converter::{chat_completion_id, completion_id, map_finish_reason};
+
+/// OpenAI 流式编码器
+///
+/// 维护 Anthropic 内容块索引到 OpenAI tool_calls 索引的映射，
+/// 保证同一个工具调用的所有增量使用稳定的 index。
+pub struct ChatStreamEncoder {
+    /// chat completion ID
+    id: String,
+    /// 模型名称
+    model: String,
+    /// 创建时间（Unix 秒）
+    created: i64,
+    /// 是否在末尾输出 usage chunk
+    include_usage: bool,
+    /// Anthropic 块索引 -> tool_calls 索引
+    tool_call_indices: HashMap<i64, usize>,
+    /// 下一个 tool_calls 索引
+    next_tool_call_index: usize,
+    /// 尚未输出过参数的工具块（块结束时补发 `{}`）
+    tool_calls_without_arguments: HashSet<i64>,
+    /// message_start 中的输入 tokens（message_delta 中有更准确的值时覆盖）
+    input_tokens: i64,
+    /// choices 中的索引（n > 1 时区分不同候选）
+    choice_index: usize,
+}
+
+impl ChatStreamEncoder {
+    pub fn new(model: impl Into<String>, created: i64, include_usage: bool) -> Self {
+        Self {
+            id: String::new(),
+            model: model.into(),
+            created,
+            include_usage,
+            tool_call_indices: HashMap::new(),
+            next_tool_call_index: 0,
+            tool_calls_without_arguments: HashSet::new(),
+            input_tokens: 0,
+            choice_index: 0,
+        }
+    }
+
+    /// 设置 choices 中的索引
+    pub fn with_choice_index(mut self, index: usize) -> Self {
+        self.choice_index = index;
+        self
+    }
+
+    /// 将一个 Anthropic SSE 事件编码为零个或多个 OpenAI SSE 字符串
+    pub fn encode(&mut self, event: &SseEvent) -> Vec<String> {
+        let data = &event.data;
+        match event.event.as_str() {
+            "message_start" => {
+                if self.id.is_empty() {
+                    self.id =
+                        chat_completion_id(data["message"]["id"].as_str().unwrap_or_default());
+                }
+                self.input_tokens = data["message"]["usage"]["input_tokens"]
+                    .as_i64()
+                    .unwrap_or(0);
+                vec![self.chunk(json!({ "role": "assistant", "content": "" }), None)]
+            }
+            "content_block_start" => {
+                let block = &data["content_block"];
+                if block["type"] != "tool_use" {
+                    return Vec::new();
+                }
+                let Some(block_index) = data["index"].as_i64() else {
+                    return Vec::new();
+                };
+                let tool_call_index = self.next_tool_call_index;
+                self.next_tool_call_index += 1;
+                self.tool_call_indices.insert(block_index, tool_call_index);
+                self.tool_calls_without_arguments.insert(block_index);
+
+                vec![self.chunk(
+                    json!({
+                        "tool_calls": [{
+                            "index": tool_call_index,
+                            "id": block["id"],
+                            "type": "function",
+                            "function": { "name": block["name"], "arguments": "" }
+                        }]
+                    }),
+                    None,
+                )]
+            }
+            "content_block_delta" => {
+                let delta = &data["delta"];
+                match delta["type"].as_str() {
+                    Some("text_delta") => {
+                        vec![self.chunk(json!({ "content": delta["text"] }), None)]
+                    }
+                    Some("thinking_delta") => {
+                        vec![self.chunk(json!({ "reasoning_content": delta["thinking"] }), None)]
+                    }
+                    Some("input_json_delta") => {
+                        let Some(block_index) = data["index"].as_i64() else {
+                            return Vec::new();
+                        };
+                        let partial_json = delta["partial_json"].as_str().unwrap_or_default();
+                        if partial_json.is_empty() {
+                            return Vec::new();
+                        }
+                        self.tool_calls_without_arguments.remove(&block_index);
+                        split_arguments(partial_json)
+                            .filter_map(|part| self.arguments_chunk(block_index, part))
+                            .collect()
+                    }
+                    _ => Vec::new(),
+                }
+            }
+            // 无参数的工具调用补发 `{}`，避免客户端解析空字符串失败
+            "content_block_stop" => match data["index"].as_i64() {
+                Some(block_index) if self.tool_calls_without_arguments.remove(&block_index) => self
+                    .arguments_chunk(block_index, "{}")
+                    .into_iter()
+                    .collect(),
+                _ => Vec::new(),
+            },
+            "message_delta" => {
+                let finish_reason =
+                    map_finish_reason(data["delta"]["stop_reason"].as_str().unwrap_or_default());
+                let mut chunks = vec![self.chunk(json!({}), Some(finish_reason))];
+
+                if self.include_usage {
+                    let (prompt_tokens, completion_tokens) = self.usage_of(data);
+                    chunks.push(self.usage_chunk(prompt_tokens, completion_tokens));
+                }
+                chunks
+            }
+            "message_stop" => vec!["data: [DONE]\n\n".to_string()],
+            // SSE 注释行，客户端会忽略，仅用于保活
+            "ping" => vec![": ping\n\n".to_string()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// 将完整的 Anthropic 消息编码为完整的 OpenAI 流
+    ///
+    /// 用于结构化输出：需要先聚合校验，再以流式格式一次性回放给客户端
+    pub fn encode_message(&mut self, message: &serde_json::Value) -> Vec<String> {
+        let mut events = vec![SseEvent::new(
+            "message_start",
+            json!({ "type": "message_start", "message": message }),
+        )];
+        if let Some(blocks) = message["content"].as_array() {
+            for (index, block) in blocks.iter().enumerate() {
+                let delta = match block["type"].as_str() {
+                    Some("text") => json!({ "type": "text_delta", "text": block["text"] }),
+                    Some("thinking") => {
+                        json!({ "type": "thinking_delta", "thinking": block["thinking"] })
+                    }
+                    Some("tool_use") => json!({
+                        "type": "input_json_delta",
+                        "partial_json": block["input"].to_string()
+                    }),
+                    _ => continue,
+                };
+                events.push(SseEvent::new(
+                    "content_block_start",
+                    json!({ "index": index, "content_block": block }),
+                ));
+                events.push(SseEvent::new(
+                    "content_block_delta",
+                    json!({ "index": index, "delta": delta }),
+                ));
+            }
+        }
+        events.push(SseEvent::new(
+            "message_delta",
+            json!({
+                "delta": { "stop_reason": message["stop_reason"] },
+                "usage": message["usage"]
+            }),
+        ));
+        events.push(SseEvent::new("message_stop", json!({})));
+
+        events.iter().flat_map(|e| self.encode(e)).collect()
+    }
+
+    /// 构建只包含 index 与参数片段的 tool_calls 增量（与 OpenAI 一致，id / name 只在首个 chunk 中出现）
+    fn arguments_chunk(&self, block_index: i64, arguments: &str) -> Option<String> {
+        let tool_call_index = *self.tool_call_indices.get(&block_index)?;
+        Some(self.chunk(
+            json!({
+                "tool_calls": [{
+                    "index": tool_call_index,
+                    "function": { "arguments": arguments }
+                }]
+            }),
+            None,
+        ))
+    }
+
+    /// 从 message_delta 中提取 (prompt_tokens, completion_tokens)
+    fn usage_of(&self, data: &serde_json::Value) -> (i64, i64) {
+        let prompt_tokens = data["usage"]["input_tokens"]
+            .as_i64()
+            .unwrap_or(self.input_tokens);
+        let completion_tokens = data["usage"]["output_tokens"].as_i64().unwrap_or(0);
+        (prompt_tokens, completion_tokens)
+    }
+
+    /// 构建末尾的 usage chunk
+    fn usage_chunk(&self, prompt_tokens: i64, completion_tokens: i64) -> String {
+        Self::to_sse(&json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [],
+            "usage": {
+                "prompt_tokens": prompt_tokens,
+                "completion_tokens": completion_tokens,
+                "total_tokens": prompt_tokens + completion_tokens
+            }
+        }))
+    }
+
+    /// 构建单个 chat.completion.chunk
+    fn chunk(&self, delta: serde_json::Value, finish_reason: Option<&str>) -> String {
+        Self::to_sse(&json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "index": self.choice_index,
+                "delta": delta,
+                "finish_reason": finish_reason
+            }]
+        }))
+    }
+
+    fn to_sse(data: &serde_json::Value) -> String {
+        format!(
+            "data: {}\n\n",
+            serde_json::to_string(data).unwrap_or_default()
+        )
+    }
+}
+
+impl SseEncoder for ChatStreamEncoder {
+    fn encode(&mut self, event: &SseEvent) -> Vec<String> {
+        ChatStreamEncoder::encode(self, event)
+    }
+}
+
+/// 单个 tool_calls 参数增量的最大字符数
+///
+/// 上游可能一次性返回完整的工具参数（结构化输出回放时也是如此），
+/// 拆分后客户端可以像对接 OpenAI 一样边接收边解析
+const TOOL_ARGUMENTS_CHUNK_CHARS: usize = 128;
+
+/// 按字符边界将工具参数拆分为不超过 [`TOOL_ARGUMENTS_CHUNK_CHARS`] 个字符的片段
+fn split_arguments(arguments: &str) -> impl Iterator<Item = &str> {
+    let mut rest = arguments;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest
+            .char_indices()
+            .nth(TOOL_ARGUMENTS_CHUNK_CHARS)
+            .map_or(rest.len(), |(i, _)| i);
+        let (part, tail) = rest.split_at(end);
+        rest = tail;
+        Some(part)
+    })
+}
+
+/// n > 1 时的多候选流式编码器
+///
+/// 多个上游事件流交错到达，每个候选使用独立的 [`ChatStreamEncoder`]（共享同一个 ID），
+/// 所有候选结束后再输出合计的 usage 与 `[DONE]`
+pub struct ChoicesStreamEncoder {
+    encoders: Vec<ChatStreamEncoder>,
+    include_usage: bool,
+    /// 尚未结束的候选数
+    pending: usize,
+    prompt_tokens: i64,
+    completion_tokens: i64,
+}
+
+impl ChoicesStreamEncoder {
+    pub fn new(model: &str, created: i64, n: usize, include_usage: bool) -> Self {
+        Self {
+            encoders: (0..n)
+                .map(|i| ChatStreamEncoder::new(model, created, false).with_choice_index(i))
+                .collect(),
+            include_usage,
+            pending: n,
+            prompt_tokens: 0,
+            completion_tokens: 0,
+        }
+    }
+
+    /// 编码第 `index` 个候选的事件
+    pub fn encode(&mut self, index: usize, event: &SseEvent) -> Vec<String> {
+        match event.event.as_str() {
+            // 以最先到达的消息 ID 作为所有候选共享的 chunk ID
+            "message_start" if self.encoders[index].id.is_empty() => {
+                let id =
+                    chat_completion_id(event.data["message"]["id"].as_str().unwrap_or_default());
+                for encoder in &mut self.encoders {
+                    if encoder.id.is_empty() {
+                        encoder.id = id.clone();
+                    }
+                }
+            }
+            "message_delta" => {
+                let (prompt_tokens, completion_tokens) = self.encoders[index].usage_of(&event.data);
+                self.prompt_tokens += prompt_tokens;
+                self.completion_tokens += completion_tokens;
+            }
+            "message_stop" => {
+                self.pending = self.pending.saturating_sub(1);
+                if self.pending > 0 {
+                    return Vec::new();
+                }
+                let mut chunks = Vec::new();
+                if self.include_usage {
+                    chunks.push(
+                        self.encoders[index]
+                            .usage_chunk(self.prompt_tokens, self.completion_tokens),
+                    );
+                }
+                chunks.push("data: [DONE]\n\n".to_string());
+                return chunks;
+            }
+            _ => {}
+        }
+        self.encoders[index].encode(event)
+    }
+}
+
+impl SseEncoder<(usize, SseEvent)> for ChoicesStreamEncoder {
+    fn encode(&mut self, (index, event): &(usize, SseEvent)) -> Vec<String> {
+        ChoicesStreamEncoder::encode(self, *index, event)
+    }
+}
+
+/// 旧版 Completions 流式编码器
+///
+/// 只输出文本增量，thinking 与工具调用不在该接口的响应格式中
+pub struct CompletionStreamEncoder {
+    /// completion ID
+    id: String,
+    /// 模型名称
+    model: String,
+    /// 创建时间（Unix 秒）
+    created: i64,
+    /// 是否在末尾输出 usage chunk
+    include_usage: bool,
+    /// 需要在第一个 chunk 中回显的 prompt
+    echo: Option<String>,
+    /// message_start 中的输入 tokens（message_delta 中有更准确的值时覆盖）
+    input_tokens: i64,
+}
+
+impl CompletionStreamEncoder {
+    pub fn new(
+        model: impl Into<String>,
+        created: i64,
+        include_usage: bool,
+        echo: Option<String>,
+    ) -> Self {
+        Self {
+            id: String::new(),
+            model: model.into(),
+            created,
+            include_usage,
+            echo,
+            input_tokens: 0,
+        }
+    }
+
+    /// 将一个 Anthropic SSE 事件编码为零个或多个 OpenAI SSE 字符串
+    pub fn encode(&mut self, event: &SseEvent) -> Vec<String> {
+        let data = &event.data;
+        match event.event.as_str() {
+            "message_start" => {
+                self.id = completion_id(data["message"]["id"].as_str().unwrap_or_default());
+                self.input_tokens = data["message"]["usage"]["input_tokens"]
+                    .as_i64()
+                    .unwrap_or(0);
+                match self.echo.take() {
+                    Some(prompt) => vec![self.chunk(&prompt, None)],
+                    None => Vec::new(),
+                }
+            }
+            "content_block_delta" if data["delta"]["type"] == "text_delta" => {
+                vec![self.chunk(data["delta"]["text"].as_str().unwrap_or_default(), None)]
+            }
+            "message_delta" => {
+                let finish_reason =
+                    map_finish_reason(data["delta"]["stop_reason"].as_str().unwrap_or_default());
+                let mut chunks = vec![self.chunk("", Some(finish_reason))];
+
+                if self.include_usage {
+                    let prompt_tokens = data["usage"]["input_tokens"]
+                        .as_i64()
+                        .unwrap_or(self.input_tokens);
+                    let completion_tokens = data["usage"]["output_tokens"].as_i64().unwrap_or(0);
+                    chunks.push(ChatStreamEncoder::to_sse(&json!({
+                        "id": self.id,
+                        "object": "text_completion",
+                        "created": self.created,
+                        "model": self.model,
+                        "choices": [],
+                        "usage": {
+                            "prompt_tokens": prompt_tokens,
+                            "completion_tokens": completion_tokens,
+                            "total_tokens": prompt_tokens + completion_tokens
+                        }
+                    })));
+                }
+                chunks
+            }
+            "message_stop" => vec!["data: [DONE]\n\n".to_string()],
+            "ping" => vec![": ping\n\n".to_string()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// 构建单个 text_completion chunk
+    fn chunk(&self, text: &str, finish_reason: Option<&str>) -> String {
+        ChatStreamEncoder::to_sse(&json!({
+            "id": self.id,
+            "object": "text_completion",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "index": 0,
+                "text": text,
+                "logprobs": null,
+                "finish_reason": finish_reason
+            }]
+        }))
+    }
+}
+
+impl SseEncoder for CompletionStreamEncoder {
+    fn encode(&mut self, event: &SseEvent) -> Vec<String> {
+        CompletionStreamEncoder::encode(self, event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(chunk: &str) -> serde_json::Value {
+        serde_json::from_str(chunk.trim().strip_prefix("data: ").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_encode_parallel_tool_calls_with_stable_indices() {
+        let mut encoder = ChatStreamEncoder::new("claude-sonnet-4-5", 0, false);
+        let events = [
+            SseEvent::new(
+                "message_start",
+                json!({"type": "message_start", "message": {"id": "msg_1", "usage": {"input_tokens": 3}}}),
+            ),
+            SseEvent::new(
+                "content_block_start",
+                json!({"index": 1, "content_block": {"type": "tool_use", "id": "t1", "name": "a"}}),
+            ),
+            SseEvent::new(
+                "content_block_delta",
+                json!({"index": 1, "delta": {"type": "input_json_delta", "partial_json": "{}"}}),
+            ),
+            SseEvent::new(
+                "content_block_start",
+                json!({"index": 2, "content_block": {"type": "tool_use", "id": "t2", "name": "b"}}),
+            ),
+            SseEvent::new(
+                "content_block_delta",
+                json!({"index": 2, "delta": {"type": "input_json_delta", "partial_json": "{\"k\":"}}),
+            ),
+        ];
+
+        let chunks: Vec<serde_json::Value> = events
+            .iter()
+            .flat_map(|e| encoder.encode(e))
+            .map(|c| parse(&c))
+            .collect();
+
+        assert_eq!(chunks[0]["id"], "chatcmpl-1");
+        assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+        assert_eq!(
+            chunks[1]["choices"][0]["delta"]["tool_calls"][0]["index"],
+            0
+        );
+        assert_eq!(
+            chunks[1]["choices"][0]["delta"]["tool_calls"][0]["id"],
+            "t1"
+        );
+        assert_eq!(
+            chunks[3]["choices"][0]["delta"]["tool_calls"][0]["index"],
+            1
+        );
+        assert_eq!(
+            chunks[4]["choices"][0]["delta"]["tool_calls"][0]["index"],
+            1
+        );
+        assert_eq!(
+            chunks[4]["choices"][0]["delta"]["tool_calls"][0]["function"]["arguments"],
+            "{\"k\":"
+        );
+    }
+
+    #[test]
+    fn test_encode_finish_with_usage_and_done() {
+        let mut encoder = ChatStreamEncoder::new("m", 0, true);
+        let chunks = encoder.encode(&SseEvent::new(
+            "message_delta",
+            json!({"delta": {"stop_reason": "tool_use"}, "usage": {"input_tokens": 4, "output_tokens": 6}}),
+        ));
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(
+            parse(&chunks[0])["choices"][0]["finish_reason"],
+            "tool_calls"
+        );
+        assert_eq!(parse(&chunks[1])["usage"]["total_tokens"], 10);
+
+        let done = encoder.encode(&SseEvent::new("message_stop", json!({})));
+        assert_eq!(done, vec!["data: [DONE]\n\n".to_string()]);
+    }
+
+    #[test]
+    fn test_encode_message_replays_full_stream() {
+        let mut encoder = ChatStreamEncoder::new("m", 0, false);
+        let chunks = encoder.encode_message(&json!({
+            "id": "msg_2",
+            "content": [{"type": "text", "text": "{\"a\":1}"}],
+            "stop_reason": "end_turn",
+            "usage": {"input_tokens": 1, "output_tokens": 2}
+        }));
+
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(
+            parse(&chunks[1])["choices"][0]["delta"]["content"],
+            "{\"a\":1}"
+        );
+        assert_eq!(parse(&chunks[2])["choices"][0]["finish_reason"], "stop");
+        assert_eq!(chunks[3], "data: [DONE]\n\n");
+    }
+
+    #[test]
+    fn test_encode_thinking_as_reasoning_content() {
+        let mut encoder = ChatStreamEncoder::new("m", 0, false);
+        let chunks = encoder.encode(&SseEvent::new(
+            "content_block_delta",
+            json!({"index": 0, "delta": {"type": "thinking_delta", "thinking": "let me see"}}),
+        ));
+        let delta = &parse(&chunks[0])["choices"][0]["delta"];
+        assert_eq!(delta["reasoning_content"], "let me see");
+        assert!(delta.get("content").is_none());
+    }
+
+    #[test]
+    fn test_completion_encoder_text_only_with_echo() {
+        let mut encoder = CompletionStreamEncoder::new("m", 0, false, Some("Hi".to_string()));
+        let events = [
+            SseEvent::new(
+                "message_start",
+                json!({"type": "message_start", "message": {"id": "msg_3", "usage": {"input_tokens": 1}}}),
+            ),
+            SseEvent::new(
+                "content_block_delta",
+                json!({"index": 0, "delta": {"type": "thinking_delta", "thinking": "hmm"}}),
+            ),
+            SseEvent::new(
+                "content_block_delta",
+                json!({"index": 1, "delta": {"type": "text_delta", "text": " there"}}),
+            ),
+            SseEvent::new(
+                "message_delta",
+                json!({"delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 1}}),
+            ),
+            SseEvent::new("message_stop", json!({})),
+        ];
+        let chunks: Vec<String> = events.iter().flat_map(|e| encoder.encode(e)).collect();
+
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(parse(&chunks[0])["id"], "cmpl-3");
+        assert_eq!(parse(&chunks[0])["choices"][0]["text"], "Hi");
+        assert_eq!(parse(&chunks[1])["choices"][0]["text"], " there");
+        assert_eq!(parse(&chunks[2])["choices"][0]["finish_reason"], "stop");
+        assert_eq!(chunks[3], "data: [DONE]\n\n");
+    }
+
+    #[test]
+    fn test_choices_encoder_interleaves_and_finishes_once() {
+        let mut encoder = ChoicesStreamEncoder::new("m", 0, 2, true);
+        let start = |id: &str| {
+            SseEvent::new(
+                "message_start",
+                json!({"message": {"id": id, "usage": {"input_tokens": 3}}}),
+            )
+        };
+        let text = |t: &str| {
+            SseEvent::new(
+                "content_block_delta",
+                json!({"index": 0, "delta": {"type": "text_delta", "text": t}}),
+            )
+        };
+        let delta = SseEvent::new(
+            "message_delta",
+            json!({"delta": {"stop_reason": "end_turn"}, "usage": {"input_tokens": 3, "output_tokens": 2}}),
+        );
+        let stop = SseEvent::new("message_stop", json!({}));
+
+        let mut chunks = Vec::new();
+        chunks.extend(encoder.encode(1, &start("msg_b")));
+        chunks.extend(encoder.encode(0, &start("msg_a")));
+        chunks.extend(encoder.encode(0, &text("x")));
+        chunks.extend(encoder.encode(1, &text("y")));
+        chunks.extend(encoder.encode(0, &delta));
+        chunks.extend(encoder.encode(0, &stop));
+        chunks.extend(encoder.encode(1, &delta));
+        chunks.extend(encoder.encode(1, &stop));
+
+        let done = chunks.iter().filter(|c| c.contains("[DONE]")).count();
+        assert_eq!(done, 1);
+        assert_eq!(chunks.last().unwrap(), "data: [DONE]\n\n");
+
+        let parsed: Vec<serde_json::Value> = chunks[..chunks.len() - 1]
+            .iter()
+            .map(|c| parse(c))
+            .collect();
+        assert!(parsed.iter().all(|c| c["id"] == "chatcmpl-b"));
+        assert_eq!(parsed[0]["choices"][0]["index"], 1);
+        assert_eq!(parsed[2]["choices"][0]["delta"]["content"], "x");
+        assert_eq!(parsed[3]["choices"][0]["index"], 1);
+
+        let usage = &parsed.last().unwrap()["usage"];
+        assert_eq!(usage["prompt_tokens"], 6);
+        assert_eq!(usage["completion_tokens"], 4);
+    }
+
+    #[test]
+    fn test_encode_tool_arguments_incrementally() {
+        let mut encoder = ChatStreamEncoder::new("m", 0, false);
+        let arguments = format!("{{\"text\":\"{}\"}}", "é".repeat(300));
+        let events = [
+            SseEvent::new(
+                "content_block_start",
+                json!({"index": 0, "content_block": {"type": "tool_use", "id": "t1", "name": "a"}}),
+            ),
+            SseEvent::new(
+                "content_block_delta",
+                json!({"index": 0, "delta": {"type": "input_json_delta", "partial_json": arguments}}),
+            ),
+            SseEvent::new("content_block_stop", json!({"index": 0})),
+            SseEvent::new(
+                "content_block_start",
+                json!({"index": 1, "content_block": {"type": "tool_use", "id": "t2", "name": "b"}}),
+            ),
+            SseEvent::new("content_block_stop", json!({"index": 1})),
+        ];
+
+        let chunks: Vec<serde_json::Value> = events
+            .iter()
+            .flat_map(|e| encoder.encode(e))
+            .map(|c| parse(&c))
+            .collect();
+        let calls: Vec<&serde_json::Value> = chunks
+            .iter()
+            .map(|c| &c["choices"][0]["delta"]["tool_calls"][0])
+            .collect();
+
+        // 首个 chunk 带 id / name，其余只带 index 与参数片段
+        assert_eq!(calls[0]["id"], "t1");
+        assert_eq!(calls[0]["function"]["arguments"], "");
+        let deltas: Vec<&serde_json::Value> = calls
+            .iter()
+            .skip(1)
+            .take_while(|c| c["index"] == 0)
+            .copied()
+            .collect();
+        assert!(deltas.len() > 1);
+        assert!(deltas.iter().all(|c| c.get("id").is_none()));
+        let joined: String = deltas
+            .iter()
+            .map(|c| c["function"]["arguments"].as_str().unwrap())
+            .collect();
+        assert_eq!(joined, arguments);
+
+        // 无参数的工具调用在块结束时补发 {}
+        let last = calls.last().unwrap();
+        assert_eq!(last["index"], 1);
+        assert_eq!(last["function"]["arguments"], "{}");
+    }
+}
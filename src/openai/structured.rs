@@ -0,0 +1,205 @@
+//! 结构化输出（JSON mode / JSON Schema）支持
+//!
+//! Kiro 上游没有原生的 response_format 参数，这里通过系统提示词注入格式要求，
+//! 并在返回后校验文本能否解析为 JSON（json_schema 模式下额外校验 schema 的常用子集）。
+
+use serde_json::Value;
+
+use super::types::ResponseFormat;
+
+/// 结构化输出模式
+#[derive(Debug, Clone, PartialEq)]
+pub enum StructuredMode {
+    /// 任意 JSON 对象
+    JsonObject,
+    /// 按 schema 约束的 JSON
+    JsonSchema { name: String, schema: Option<Value> },
+}
+
+impl StructuredMode {
+    /// 从 response_format 解析模式，`text` 或未知类型返回 None
+    pub fn from_response_format(format: &ResponseFormat) -> Option<Self> {
+        match format.format_type.as_str() {
+            "json_object" => Some(Self::JsonObject),
+            "json_schema" => {
+                let spec = format.json_schema.as_ref();
+                Some(Self::JsonSchema {
+                    name: spec
+                        .map(|s| s.name.clone())
+                        .unwrap_or_else(|| "response".to_string()),
+                    schema: spec.and_then(|s| s.schema.clone()),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// 注入到系统提示词中的格式要求
+    pub fn instruction(&self) -> String {
+        match self {
+            Self::JsonObject => "You must respond with a single valid JSON object only. \
+                Do not wrap it in markdown code fences and do not add any text before or after it."
+                .to_string(),
+            Self::JsonSchema { name, schema } => {
+                let mut text = format!(
+                    "You must respond with a single valid JSON value named \"{}\" only. \
+                     Do not wrap it in markdown code fences and do not add any text before or after it.",
+                    name
+                );
+                if let Some(schema) = schema {
+                    text.push_str(
+                        "\nThe JSON must strictly conform to the following JSON Schema:\n",
+                    );
+                    text.push_str(&schema.to_string());
+                }
+                text
+            }
+        }
+    }
+
+    /// 校验模型输出，成功时返回规范化后的 JSON 文本
+    pub fn check(&self, text: &str) -> Result<String, String> {
+        let value = extract_json(text)?;
+        match self {
+            Self::JsonObject => {
+                if !value.is_object() {
+                    return Err("输出不是 JSON 对象".to_string());
+                }
+            }
+            Self::JsonSchema { schema, .. } => {
+                if let Some(schema) = schema {
+                    validate(&value, schema, "$")?;
+                }
+            }
+        }
+        Ok(value.to_string())
+    }
+}
+
+/// 校验失败后追加的重试提示
+pub fn retry_prompt(error: &str) -> String {
+    format!(
+        "Your previous response was not valid for the required format: {}. \
+         Respond again with only the corrected JSON.",
+        error
+    )
+}
+
+/// 从模型输出中提取 JSON（容忍 markdown 代码块包裹）
+fn extract_json(text: &str) -> Result<Value, String> {
+    let trimmed = text.trim();
+    let body = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|s| s.strip_suffix("```"))
+        .unwrap_or(trimmed)
+        .trim();
+    serde_json::from_str(body).map_err(|e| format!("JSON 解析失败: {}", e))
+}
+
+/// 按 JSON Schema 常用子集校验（type / properties / required / items / enum）
+fn validate(value: &Value, schema: &Value, path: &str) -> Result<(), String> {
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| type_matches(value, t)) {
+            return Err(format!("{} 类型应为 {}", path, types.join("|")));
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(|e| e.as_array())
+        && !options.contains(value)
+    {
+        return Err(format!("{} 不在 enum 允许的取值中", path));
+    }
+
+    if let Value::Object(obj) = value {
+        if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+            for key in required.iter().filter_map(|k| k.as_str()) {
+                if !obj.contains_key(key) {
+                    return Err(format!("{} 缺少必需字段 {}", path, key));
+                }
+            }
+        }
+        if let Some(properties) = schema.get("properties").and_then(|p| p.as_object()) {
+            for (key, sub_schema) in properties {
+                if let Some(sub_value) = obj.get(key) {
+                    validate(sub_value, sub_schema, &format!("{}.{}", path, key))?;
+                }
+            }
+            if schema.get("additionalProperties") == Some(&Value::Bool(false))
+                && let Some(extra) = obj.keys().find(|k| !properties.contains_key(*k))
+            {
+                return Err(format!("{} 包含未声明的字段 {}", path, extra));
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (i, item) in items.iter().enumerate() {
+            validate(item, item_schema, &format!("{}[{}]", path, i))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn type_matches(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema_mode(schema: Value) -> StructuredMode {
+        StructuredMode::JsonSchema {
+            name: "result".to_string(),
+            schema: Some(schema),
+        }
+    }
+
+    #[test]
+    fn test_json_object_strips_code_fence() {
+        let mode = StructuredMode::JsonObject;
+        assert_eq!(mode.check("```json\n{\"a\": 1}\n```").unwrap(), "{\"a\":1}");
+        assert!(mode.check("[1, 2]").is_err());
+        assert!(mode.check("Sure! {\"a\": 1}").is_err());
+    }
+
+    #[test]
+    fn test_json_schema_validation() {
+        let mode = schema_mode(json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "tags": {"type": "array", "items": {"type": "string"}},
+                "level": {"enum": ["low", "high"]}
+            },
+            "required": ["name"],
+            "additionalProperties": false
+        }));
+
+        assert!(
+            mode.check(r#"{"name": "x", "tags": ["a"], "level": "low"}"#)
+                .is_ok()
+        );
+        assert!(mode.check(r#"{"tags": []}"#).unwrap_err().contains("name"));
+        assert!(mode.check(r#"{"name": "x", "tags": [1]}"#).is_err());
+        assert!(mode.check(r#"{"name": "x", "level": "mid"}"#).is_err());
+        assert!(mode.check(r#"{"name": "x", "extra": 1}"#).is_err());
+    }
+}
@@ -0,0 +1,210 @@
+//! OpenAI Chat Completions / Completions / Responses API 类型定义
+
+use serde::{Deserialize, Serialize};
+
+// === 错误响应 ===
+
+/// OpenAI 风格的错误响应
+#[derive(Debug, Serialize)]
+pub struct OpenAiErrorResponse {
+    pub error: OpenAiError,
+}
+
+/// 错误详情
+#[derive(Debug, Serialize)]
+pub struct OpenAiError {
+    pub message: String,
+    #[serde(rename = "type")]
+    pub error_type: String,
+    pub param: Option<String>,
+    pub code: Option<String>,
+}
+
+impl OpenAiErrorResponse {
+    /// 创建新的错误响应
+    pub fn new(error_type: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            error: OpenAiError {
+                message: message.into(),
+                error_type: error_type.into(),
+                param: None,
+                code: None,
+            },
+        }
+    }
+
+    /// 设置错误码
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.error.code = Some(code.into());
+        self
+    }
+
+    /// 设置出错的参数名
+    pub fn with_param(mut self, param: impl Into<String>) -> Self {
+        self.error.param = Some(param.into());
+        self
+    }
+}
+
+// === Chat Completions 请求 ===
+
+/// Chat Completions 请求体
+///
+/// 仅声明实际使用的字段，其他字段（temperature、top_p 等）会被忽略
+#[derive(Debug, Deserialize)]
+pub struct ChatCompletionRequest {
+    pub model: String,
+    /// 终端用户标识（记录到用量日志）
+    pub user: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    /// 生成的候选数（> 1 时并行发起多次上游请求）
+    pub n: Option<u32>,
+    #[serde(default)]
+    pub stream: bool,
+    pub stream_options: Option<StreamOptions>,
+    pub max_tokens: Option<i32>,
+    pub max_completion_tokens: Option<i32>,
+    pub tools: Option<Vec<ChatTool>>,
+    pub tool_choice: Option<serde_json::Value>,
+    pub response_format: Option<ResponseFormat>,
+    /// 推理强度：`minimal`、`low`、`medium`、`high`
+    pub reasoning_effort: Option<String>,
+    /// 部分客户端使用嵌套形式 `{"reasoning": {"effort": "high"}}`
+    pub reasoning: Option<ReasoningOptions>,
+}
+
+// === Completions（旧版文本补全）请求 ===
+
+/// Completions 请求体
+///
+/// 仅声明实际使用的字段，其他字段（temperature、logprobs 等）会被忽略
+#[derive(Debug, Deserialize)]
+pub struct CompletionRequest {
+    pub model: String,
+    /// 终端用户标识（记录到用量日志）
+    pub user: Option<String>,
+    /// 可以是字符串或只包含一个字符串的数组
+    pub prompt: serde_json::Value,
+    #[serde(default)]
+    pub stream: bool,
+    pub stream_options: Option<StreamOptions>,
+    pub max_tokens: Option<i32>,
+    /// 是否在输出文本前附带原始 prompt
+    #[serde(default)]
+    pub echo: bool,
+}
+
+// === Responses 请求 ===
+
+/// Responses 请求体
+///
+/// 仅声明实际使用的字段，其他字段（temperature、store 等）会被忽略
+#[derive(Debug, Deserialize)]
+pub struct ResponsesRequest {
+    pub model: String,
+    /// 终端用户标识（记录到用量日志）
+    pub user: Option<String>,
+    /// 可以是字符串或输入项数组
+    pub input: serde_json::Value,
+    /// 系统指令
+    pub instructions: Option<String>,
+    #[serde(default)]
+    pub stream: bool,
+    pub max_output_tokens: Option<i32>,
+    pub tools: Option<Vec<ResponsesTool>>,
+    pub tool_choice: Option<serde_json::Value>,
+    pub reasoning: Option<ReasoningOptions>,
+    /// 服务端会话续接（不支持，需在 input 中传入完整对话）
+    pub previous_response_id: Option<String>,
+}
+
+/// Responses 工具定义（函数定义与 type 平铺在同一层）
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResponsesTool {
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub parameters: Option<serde_json::Value>,
+}
+
+/// 嵌套的推理选项
+#[derive(Debug, Deserialize)]
+pub struct ReasoningOptions {
+    pub effort: Option<String>,
+}
+
+/// 响应格式（JSON mode / structured output）
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResponseFormat {
+    /// `text`、`json_object` 或 `json_schema`
+    #[serde(rename = "type")]
+    pub format_type: String,
+    pub json_schema: Option<JsonSchemaSpec>,
+}
+
+/// json_schema 格式定义
+#[derive(Debug, Clone, Deserialize)]
+pub struct JsonSchemaSpec {
+    pub name: String,
+    pub schema: Option<serde_json::Value>,
+}
+
+/// 流式选项
+#[derive(Debug, Deserialize)]
+pub struct StreamOptions {
+    /// 是否在流末尾附带 usage
+    #[serde(default)]
+    pub include_usage: bool,
+}
+
+/// 对话消息
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    /// 可以是 string、内容片段数组或 null
+    #[serde(default)]
+    pub content: Option<serde_json::Value>,
+    pub tool_calls: Option<Vec<ToolCall>>,
+    pub tool_call_id: Option<String>,
+}
+
+/// 工具调用
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type", default = "default_tool_type")]
+    pub call_type: String,
+    pub function: FunctionCall,
+}
+
+/// 函数调用
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FunctionCall {
+    pub name: String,
+    /// JSON 编码的参数字符串
+    #[serde(default)]
+    pub arguments: String,
+}
+
+/// 工具定义
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatTool {
+    #[serde(rename = "type", default = "default_tool_type")]
+    pub tool_type: String,
+    pub function: FunctionDefinition,
+}
+
+/// 函数定义
+#[derive(Debug, Clone, Deserialize)]
+pub struct FunctionDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub parameters: Option<serde_json::Value>,
+}
+
+fn default_tool_type() -> String {
+    "function".to_string()
+}
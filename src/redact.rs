@@ -0,0 +1,211 @@
+//! 日志脱敏
+//!
+//! 在日志写出之前替换其中的密钥，防止刷新令牌、访问令牌与 API Key 经由 anyhow 错误链、
+//! 上游响应体或 `{:?}` 输出泄露到日志、错误上报与链路追踪中。
+//! 两种方式同时生效：
+//! - 已知密钥：配置中的 API Key / Admin Key / 代理密码以及凭据中的令牌在加载与刷新时登记，出现在任何位置都会被替换
+//! - 模式匹配：`Bearer <token>` 以及 `refreshToken` / `access_token` / `clientSecret` / `password` 等字段的值
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io;
+use std::sync::LazyLock;
+
+use parking_lot::RwLock;
+use regex_automata::meta::Regex;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::kiro::model::credentials::KiroCredentials;
+use crate::model::config::Config;
+
+/// 替换密钥的占位文本
+const REDACTED: &str = "[REDACTED]";
+
+/// 短于该长度的值不登记，避免把常见单词当作密钥替换
+const MIN_SECRET_LEN: usize = 8;
+
+/// 最多登记的密钥数，超出时丢弃最早登记的（访问令牌每次刷新都会更换）
+const MAX_SECRETS: usize = 4096;
+
+/// 已登记的密钥
+static SECRETS: RwLock<VecDeque<String>> = RwLock::new(VecDeque::new());
+
+/// 按字段名与 `Bearer` 前缀识别的密钥，第 1 个捕获组为保留的前缀
+static PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?i)(bearer\s+|(?:access_?token|refresh_?token|client_?secret|password)\\?"?\s*[:=]\s*(?:Some\()?\\?"?)[^\s"\\,;&)}]+"#,
+    )
+    .expect("脱敏正则无效")
+});
+
+/// 登记一个需要在日志中隐藏的值
+pub fn register(secret: &str) {
+    let secret = secret.trim();
+    if secret.len() < MIN_SECRET_LEN {
+        return;
+    }
+    let mut secrets = SECRETS.write();
+    if secrets.iter().any(|s| s == secret) {
+        return;
+    }
+    if secrets.len() >= MAX_SECRETS {
+        secrets.pop_front();
+    }
+    secrets.push_back(secret.to_string());
+}
+
+/// 登记配置中的密钥（API Key、Admin Key、count_tokens Key、代理密码、审计日志密钥与 OIDC Client Secret）
+pub fn register_config(config: &Config) {
+    let secrets = [
+        &config.api_key,
+        &config.admin_api_key,
+        &config.count_tokens_api_key,
+        &config.proxy_password,
+    ];
+    for secret in secrets.into_iter().flatten() {
+        register(secret);
+    }
+    for key in &config.api_keys {
+        register(&key.key);
+    }
+    for key in &config.admin_api_keys {
+        register(key);
+    }
+    if let Some(audit_log) = &config.audit_log {
+        register(&audit_log.hmac_key);
+    }
+    if let Some(secret) = config
+        .credential_webhook
+        .as_ref()
+        .and_then(|webhook| webhook.secret.as_ref())
+    {
+        register(secret);
+    }
+    if let Some(telegram) = &config.telegram {
+        register(&telegram.bot_token);
+    }
+    if let Some(discord) = &config.discord {
+        register(&discord.webhook_url);
+    }
+    if let Some(secret) = config
+        .admin_oidc
+        .as_ref()
+        .and_then(|oidc| oidc.client_secret.as_ref())
+    {
+        register(secret);
+    }
+}
+
+/// 登记凭据中的令牌与 Client Secret
+pub fn register_credentials(credentials: &KiroCredentials) {
+    let secrets = [
+        &credentials.access_token,
+        &credentials.refresh_token,
+        &credentials.client_secret,
+    ];
+    for secret in secrets.into_iter().flatten() {
+        register(secret);
+    }
+}
+
+/// 替换文本中的密钥，没有密钥时不分配内存
+pub fn scrub(text: &str) -> Cow<'_, str> {
+    let mut result = Cow::Borrowed(text);
+    {
+        let secrets = SECRETS.read();
+        for secret in secrets.iter() {
+            if result.contains(secret.as_str()) {
+                result = Cow::Owned(result.replace(secret.as_str(), REDACTED));
+            }
+        }
+    }
+
+    if !PATTERN.is_match(result.as_ref()) {
+        return result;
+    }
+    let mut out = String::with_capacity(result.len());
+    let mut last = 0;
+    for caps in PATTERN.captures_iter(result.as_ref()) {
+        let (Some(matched), Some(prefix)) = (caps.get_match(), caps.get_group(1)) else {
+            continue;
+        };
+        out.push_str(&result[last..prefix.end]);
+        out.push_str(REDACTED);
+        last = matched.end();
+    }
+    out.push_str(&result[last..]);
+    Cow::Owned(out)
+}
+
+/// 写出前脱敏的 [`MakeWriter`]，包装日志输出目标
+pub struct Redacting<M>(pub M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Redacting<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(self.0.make_writer())
+    }
+
+    fn make_writer_for(&'a self, meta: &tracing::Metadata<'_>) -> Self::Writer {
+        RedactingWriter(self.0.make_writer_for(meta))
+    }
+}
+
+/// 脱敏后写入内部输出
+///
+/// tracing-subscriber 将每条日志格式化完成后一次写出，因此按单次写入处理即可
+pub struct RedactingWriter<W>(W);
+
+impl<W: io::Write> io::Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match std::str::from_utf8(buf).map(scrub) {
+            Ok(Cow::Owned(text)) => {
+                self.0.write_all(text.as_bytes())?;
+                Ok(buf.len())
+            }
+            _ => self.0.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrub_registered_secrets() {
+        register("aorAAAAAtest-refresh-token-value");
+        register("short");
+        assert_eq!(
+            scrub("刷新失败: token aorAAAAAtest-refresh-token-value 无效"),
+            "刷新失败: token [REDACTED] 无效"
+        );
+        assert_eq!(scrub("short"), "short");
+        assert!(matches!(scrub("没有密钥"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_scrub_patterns() {
+        assert_eq!(
+            scrub("Authorization: Bearer eyJhbGciOi.abc-def"),
+            "Authorization: Bearer [REDACTED]"
+        );
+        assert_eq!(
+            scrub(r#"400 {"refreshToken":"abc123","error":"invalid_grant"}"#),
+            r#"400 {"refreshToken":"[REDACTED]","error":"invalid_grant"}"#
+        );
+        assert_eq!(
+            scrub(r#"KiroCredentials { id: Some(1), access_token: Some("xyz") }"#),
+            r#"KiroCredentials { id: Some(1), access_token: Some("[REDACTED]") }"#
+        );
+        assert_eq!(
+            scrub(r#"{\"access_token\":\"xyz\"} password=hunter2&user=a"#),
+            r#"{\"access_token\":\"[REDACTED]\"} password=[REDACTED]&user=a"#
+        );
+    }
+}
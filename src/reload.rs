@@ -0,0 +1,270 @@
+//! 配置热加载
+//!
+//! 收到 SIGHUP 或 Admin API 的 `POST /reload` 时重新读取配置文件与凭证文件：运行时可安全调整的配置项
+//! （限流与排队参数、模型规格与路由、请求处理开关、IP 访问规则、Admin Key、告警、日志级别）立即生效，
+//! 凭据列表按 ID 同步；其余配置项（监听地址、主 API Key、代理、持久化路径等）只记录变化，需重启后生效。
+
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::anthropic::AppState;
+use crate::discord;
+use crate::kiro::model::credentials::CredentialsConfig;
+use crate::kiro::token_manager::MultiTokenManager;
+use crate::kiro::{circuit, retry_budget, webhook};
+use crate::limit;
+use crate::logging;
+use crate::metrics;
+use crate::model::arg::Args;
+use crate::model::config::Config;
+use crate::redact;
+use crate::systemd;
+use crate::telegram;
+
+/// 可在运行时生效的配置项
+const RUNTIME_KEYS: &[&str] = &[
+    "apiKeys",
+    "adminApiKey",
+    "adminApiKeys",
+    "maxConcurrentPerKey",
+    "maxConcurrentPerCredential",
+    "maxQueueDepth",
+    "queueTimeoutSecs",
+    "globalRpm",
+    "globalTpm",
+    "modelLimits",
+    "contextWindowTokens",
+    "modelRoutes",
+    "presets",
+    "compactionStrategy",
+    "dedupeConcurrentRequests",
+    "stripReasoning",
+    "performanceHeaders",
+    "streamCoalesceMs",
+    "streamCoalesceChars",
+    "forwardRequestHeaders",
+    "exposeResponseHeaders",
+    "forwardEndUserHash",
+    "ipFilter",
+    "ipRateLimit",
+    "authLockout",
+    "maxRequestHeaderBytes",
+    "maxBufferedResponseBytes",
+    "maxMessages",
+    "alerts",
+    "circuitBreaker",
+    "hedging",
+    "adaptiveConcurrency",
+    "retryBudget",
+    "credentialWebhook",
+    "telegram",
+    "discord",
+    "loadShedding",
+    "logLevel",
+];
+
+/// 配置变化
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigDiff {
+    /// 已在运行时生效的配置项
+    pub applied: Vec<String>,
+    /// 需重启后生效的配置项
+    pub restart_required: Vec<String>,
+}
+
+/// 比较两份配置，按是否可在运行时生效划分变化的配置项
+pub fn diff(old: &Config, new: &Config) -> ConfigDiff {
+    let (Ok(Value::Object(old)), Ok(Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return ConfigDiff::default();
+    };
+
+    let mut diff = ConfigDiff::default();
+    let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        if old.get(key) == new.get(key) {
+            continue;
+        }
+        if RUNTIME_KEYS.contains(&key.as_str()) {
+            diff.applied.push(key.clone());
+        } else {
+            diff.restart_required.push(key.clone());
+        }
+    }
+    diff
+}
+
+/// 在 `old` 的基础上取 `new` 中可在运行时生效的配置项
+fn merge(old: &Config, new: &Config) -> anyhow::Result<Config> {
+    let mut merged = serde_json::to_value(old)?;
+    let new = serde_json::to_value(new)?;
+    for key in RUNTIME_KEYS {
+        if let Some(value) = new.get(key) {
+            merged[*key] = value.clone();
+        }
+    }
+    Ok(serde_json::from_value(merged)?)
+}
+
+/// 配置热加载器
+pub struct Reloader {
+    config_path: String,
+    credentials_path: String,
+    /// 命令行参数（重新加载后同样覆盖配置文件）
+    args: Args,
+    state: AppState,
+    token_manager: Arc<MultiTokenManager>,
+    /// 串行化 SIGHUP 与 Admin API 触发的重新加载
+    lock: Mutex<()>,
+}
+
+impl Reloader {
+    pub fn new(
+        config_path: String,
+        credentials_path: String,
+        args: Args,
+        state: AppState,
+        token_manager: Arc<MultiTokenManager>,
+    ) -> Self {
+        Self {
+            config_path,
+            credentials_path,
+            args,
+            state,
+            token_manager,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// 重新读取配置与凭证文件并应用可在运行时生效的变化，返回配置项的变化
+    pub fn reload(&self) -> anyhow::Result<ConfigDiff> {
+        let _guard = self.lock.lock();
+        let mut config = Config::load(&self.config_path)?;
+        self.args.apply_overrides(&mut config);
+        redact::register_config(&config);
+        let credentials = CredentialsConfig::load(&self.credentials_path)?;
+
+        let current = self.state.config.load();
+        let changes = diff(&current, &config);
+        if changes.applied.iter().any(|k| k == "logLevel") {
+            logging::set_level(config.log_level.as_deref())?;
+        }
+        if changes.applied.iter().any(|k| k == "modelRoutes") {
+            self.token_manager.set_routes(config.model_routes.clone());
+        }
+        if changes.applied.iter().any(|k| k == "alerts") {
+            metrics::alerts().configure(config.alerts.clone());
+        }
+        if changes.applied.iter().any(|k| k == "circuitBreaker") {
+            circuit::breaker().configure(config.circuit_breaker.clone());
+        }
+        if changes.applied.iter().any(|k| k == "adaptiveConcurrency") {
+            limit::adaptive().configure(config.adaptive_concurrency.clone());
+        }
+        if changes.applied.iter().any(|k| k == "retryBudget") {
+            retry_budget::budget().configure(config.retry_budget.clone());
+        }
+        if changes.applied.iter().any(|k| k == "credentialWebhook") {
+            webhook::notifier().configure(config.credential_webhook.clone());
+        }
+        if changes.applied.iter().any(|k| k == "telegram") {
+            telegram::configure(config.telegram.clone());
+        }
+        if changes.applied.iter().any(|k| k == "discord") {
+            discord::configure(config.discord.clone());
+        }
+        if changes.applied.iter().any(|k| k == "loadShedding") {
+            limit::shedder().configure(config.load_shedding.clone());
+        }
+        self.state.reload_config(merge(&current, &config)?);
+
+        let credential_changes = self
+            .token_manager
+            .reload_credentials(credentials.into_sorted_credentials())?;
+
+        if changes.applied.is_empty() && credential_changes.is_empty() {
+            tracing::info!("配置已重新加载，无可应用的变化");
+        } else {
+            tracing::info!(
+                applied = ?changes.applied,
+                credentials_added = ?credential_changes.added,
+                credentials_removed = ?credential_changes.removed,
+                credentials_updated = ?credential_changes.updated,
+                "配置已重新加载"
+            );
+        }
+        if !changes.restart_required.is_empty() {
+            tracing::warn!(
+                keys = ?changes.restart_required,
+                "以下配置项的变化需重启后生效"
+            );
+        }
+        Ok(changes)
+    }
+
+    /// 在后台监听 SIGHUP 并重新加载配置（非 Unix 平台不做任何处理）
+    pub fn spawn_on_sighup(self: Arc<Self>) {
+        #[cfg(unix)]
+        tokio::spawn(async move {
+            use tokio::signal::unix::{SignalKind, signal};
+
+            let mut hangup = match signal(SignalKind::hangup()) {
+                Ok(signal) => signal,
+                Err(e) => {
+                    tracing::warn!("无法监听 SIGHUP，配置热加载未启用: {}", e);
+                    return;
+                }
+            };
+            while hangup.recv().await.is_some() {
+                tracing::info!("收到 SIGHUP，重新加载配置");
+                systemd::reloading();
+                if let Err(e) = self.reload() {
+                    tracing::error!("重新加载配置失败，继续使用当前配置: {}", e);
+                }
+                systemd::reloaded();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_keys_exist() {
+        let value = serde_json::to_value(Config::default()).unwrap();
+        for key in RUNTIME_KEYS {
+            assert!(value.get(key).is_some(), "未知的配置项: {}", key);
+        }
+    }
+
+    #[test]
+    fn test_diff_and_merge() {
+        let old = Config::default();
+        let new = Config {
+            global_rpm: Some(60),
+            port: 9000,
+            strip_reasoning: true,
+            ..old.clone()
+        };
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes.applied, vec!["globalRpm", "stripReasoning"]);
+        assert_eq!(changes.restart_required, vec!["port"]);
+
+        let merged = merge(&old, &new).unwrap();
+        assert_eq!(merged.global_rpm, Some(60));
+        assert!(merged.strip_reasoning);
+        // 需重启的配置项保持不变
+        assert_eq!(merged.port, old.port);
+        assert!(diff(&merged, &new).applied.is_empty());
+    }
+}
@@ -0,0 +1,259 @@
+//! 请求重放
+//!
+//! 读取请求日志（`sampling.logBodies` 写入数据库 `request_log` 表的记录），按时间顺序逐条发送到运行中的实例，
+//! 输出状态码、耗时与响应，用于复现用户报告的转换问题（可按错误响应中的 `request_id` 选取记录）。
+//! 请求体被截断的记录无法重放，直接报告失败。
+//! 试运行时只在本地执行请求转换，输出将发送给上游的 `conversationState`，不发送任何请求。
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::anthropic::{self, types::MessagesRequest};
+use crate::openai;
+use crate::request_log::RequestLogEntry;
+
+/// 单个请求的超时时间
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Anthropic Messages API 的路由，其余路由按 OpenAI 兼容接口转换
+const MESSAGES_ROUTE: &str = "/v1/messages";
+
+/// 一条待重放的请求
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayEntry {
+    pub request_id: String,
+    pub route: String,
+    pub body: Value,
+}
+
+impl ReplayEntry {
+    /// 从请求日志还原请求，请求体被截断或不是 JSON 时返回错误
+    pub fn from_log(log: &RequestLogEntry) -> anyhow::Result<Self> {
+        if log.request_truncated {
+            anyhow::bail!("请求体超过 sampling.maxBodyBytes 被截断，无法重放");
+        }
+        Ok(Self {
+            request_id: log.request_id.clone(),
+            route: log.route.clone(),
+            body: serde_json::from_str(&log.request_body)?,
+        })
+    }
+}
+
+/// 单条记录的重放结果（以 JSON 行输出）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayOutcome {
+    /// 原请求的请求 ID
+    pub request_id: String,
+    pub route: String,
+    /// 原请求的状态码
+    pub original_status: u16,
+    /// HTTP 状态码（试运行时为 None）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    /// 响应体（JSON 响应解析为对象，流式响应保留原始 SSE 文本）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<Value>,
+    /// 试运行时转换得到的 Kiro `conversationState`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conversation_state: Option<Value>,
+    /// 还原、转换或发送失败的原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ReplayOutcome {
+    fn new(log: &RequestLogEntry) -> Self {
+        Self {
+            request_id: log.request_id.clone(),
+            route: log.route.clone(),
+            original_status: log.status,
+            status: None,
+            latency_ms: None,
+            response: None,
+            conversation_state: None,
+            error: None,
+        }
+    }
+
+    /// 是否失败（转换出错、发送失败或非 2xx 响应）
+    pub fn failed(&self) -> bool {
+        self.error.is_some() || self.status.is_some_and(|s| !(200..300).contains(&s))
+    }
+}
+
+/// 在本地执行请求转换，返回 Kiro `conversationState`
+pub fn translate(entry: &ReplayEntry) -> anyhow::Result<Value> {
+    let request: MessagesRequest = if entry.route == MESSAGES_ROUTE {
+        serde_json::from_value(entry.body.clone())?
+    } else {
+        openai::to_messages_request(&entry.route, entry.body.clone())?
+    };
+    let result = anthropic::convert_request(&request)?;
+    Ok(serde_json::to_value(result.conversation_state)?)
+}
+
+/// 按顺序重放请求记录
+pub struct Replayer {
+    client: reqwest::Client,
+    /// 实例地址（含路由前缀）
+    url: String,
+    api_key: String,
+}
+
+impl Replayer {
+    pub fn new(url: &str, api_key: String) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .no_proxy()
+            .build()?;
+        Ok(Self {
+            client,
+            url: url.trim_end_matches('/').to_string(),
+            api_key,
+        })
+    }
+
+    /// 发送一条请求并读完响应
+    pub async fn send(&self, entry: &ReplayEntry, outcome: &mut ReplayOutcome) {
+        let started = Instant::now();
+        let result = async {
+            let response = self
+                .client
+                .post(format!("{}{}", self.url, entry.route))
+                .header("x-api-key", &self.api_key)
+                .json(&entry.body)
+                .send()
+                .await?;
+            let status = response.status().as_u16();
+            let text = response.text().await?;
+            anyhow::Ok((status, text))
+        }
+        .await;
+        outcome.latency_ms = Some(started.elapsed().as_millis() as u64);
+        match result {
+            Ok((status, text)) => {
+                outcome.status = Some(status);
+                outcome.response = Some(serde_json::from_str(&text).unwrap_or(Value::String(text)));
+            }
+            Err(e) => outcome.error = Some(e.to_string()),
+        }
+    }
+}
+
+/// 按顺序重放（或试运行）请求日志，每条结果交给 `emit`
+pub async fn run(
+    logs: &[RequestLogEntry],
+    replayer: Option<&Replayer>,
+    mut emit: impl FnMut(&ReplayOutcome),
+) -> Vec<ReplayOutcome> {
+    let mut outcomes = Vec::new();
+    for log in logs {
+        let mut outcome = ReplayOutcome::new(log);
+        match ReplayEntry::from_log(log) {
+            Err(e) => outcome.error = Some(format!("无法还原请求: {}", e)),
+            Ok(entry) => match replayer {
+                Some(replayer) => replayer.send(&entry, &mut outcome).await,
+                None => match translate(&entry) {
+                    Ok(state) => outcome.conversation_state = Some(state),
+                    Err(e) => outcome.error = Some(e.to_string()),
+                },
+            },
+        }
+        emit(&outcome);
+        outcomes.push(outcome);
+    }
+    outcomes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde_json::json;
+
+    fn log(request_id: &str, route: &str, body: Value) -> RequestLogEntry {
+        RequestLogEntry {
+            timestamp: Utc::now(),
+            request_id: request_id.to_string(),
+            method: "POST".to_string(),
+            route: route.to_string(),
+            status: 500,
+            latency_ms: 10,
+            request_body: body.to_string(),
+            request_truncated: false,
+            response_body: String::new(),
+            response_truncated: false,
+        }
+    }
+
+    #[test]
+    fn test_entry_from_log() {
+        let entry =
+            ReplayEntry::from_log(&log("req_1", "/v1/responses", json!({"model": "m"}))).unwrap();
+        assert_eq!(entry.request_id, "req_1");
+        assert_eq!(entry.route, "/v1/responses");
+        assert_eq!(entry.body, json!({"model": "m"}));
+
+        // 截断的请求体无法重放
+        let truncated = RequestLogEntry {
+            request_truncated: true,
+            ..log("req_2", MESSAGES_ROUTE, json!({}))
+        };
+        assert!(ReplayEntry::from_log(&truncated).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_translates_each_route() {
+        let logs = [
+            log(
+                "req_1",
+                MESSAGES_ROUTE,
+                json!({
+                    "model": "claude-sonnet-4-5",
+                    "max_tokens": 16,
+                    "messages": [{"role": "user", "content": "hello"}],
+                }),
+            ),
+            log(
+                "req_2",
+                "/v1/chat/completions",
+                json!({
+                    "model": "claude-sonnet-4-5",
+                    "messages": [{"role": "user", "content": "hi"}],
+                }),
+            ),
+            log(
+                "req_3",
+                MESSAGES_ROUTE,
+                json!({"model": "gpt-4", "max_tokens": 1, "messages": []}),
+            ),
+            RequestLogEntry {
+                request_body: "{\"model\": \"trunc".to_string(),
+                request_truncated: true,
+                ..log("req_4", MESSAGES_ROUTE, json!({}))
+            },
+        ];
+
+        let mut emitted = 0;
+        let outcomes = run(&logs, None, |_| emitted += 1).await;
+        assert_eq!(emitted, 4);
+        assert_eq!(outcomes[0].request_id, "req_1");
+        assert_eq!(outcomes[0].original_status, 500);
+        assert!(!outcomes[0].failed());
+        assert_eq!(
+            outcomes[0].conversation_state.as_ref().unwrap()["currentMessage"]["userInputMessage"]
+                ["content"],
+            "hello"
+        );
+        assert_eq!(outcomes[1].route, "/v1/chat/completions");
+        assert!(outcomes[1].conversation_state.is_some());
+        assert!(outcomes[2].failed());
+        assert!(outcomes[3].error.as_deref().unwrap().contains("截断"));
+    }
+}
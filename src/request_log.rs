@@ -0,0 +1,267 @@
+//! 请求体与响应体日志
+//!
+//! 配置 `sampling.logBodies` 后，POST 请求的请求体与响应体（各保留前 `sampling.maxBodyBytes` 字节，
+//! 经 [`redact::scrub`] 脱敏）写入数据库的 `request_log` 表，可由 `replay` 子命令重放。
+//!
+//! 是否记录沿用请求的采样决定（[`telemetry::sample`] 的 `log`）：选中的请求与失败（4xx/5xx）的请求记录，
+//! 其余丢弃。请求体在转发给处理函数的同时复制，响应体在发送给客户端的同时复制，
+//! 响应流结束（或客户端断开）时提交一条记录，由后台线程按批写入数据库，不额外缓冲或延迟响应。
+//!
+//! [`telemetry::sample`]: crate::telemetry::sample
+
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+use axum::body::{Body, Bytes};
+use axum::http::{Method, Request};
+use axum::response::Response;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::common::batch_writer::BatchWriter;
+use crate::database::Database;
+use crate::model::config::Config;
+use crate::redact;
+
+/// 启用后设置
+static SINK: OnceLock<Sink> = OnceLock::new();
+
+struct Sink {
+    writer: BatchWriter<RequestLogEntry>,
+    max_body_bytes: usize,
+    /// `pathPrefix`，记录的路由不含该前缀
+    prefix: String,
+}
+
+/// 一条请求记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestLogEntry {
+    /// 请求开始时间
+    pub timestamp: DateTime<Utc>,
+    pub request_id: String,
+    pub method: String,
+    /// 请求路由（不含 `pathPrefix`），如 `/v1/messages`
+    pub route: String,
+    pub status: u16,
+    /// 从收到请求到响应流结束的耗时（毫秒）
+    pub latency_ms: u64,
+    /// 脱敏后的请求体
+    pub request_body: String,
+    /// 请求体是否超过 `maxBodyBytes` 被截断
+    pub request_truncated: bool,
+    /// 脱敏后的响应体（流式响应为原始 SSE 文本）
+    pub response_body: String,
+    pub response_truncated: bool,
+}
+
+/// 按配置启用请求体日志（需要已打开数据库）
+pub fn init(config: &Config, database: Option<&Arc<Database>>) {
+    if !config.sampling.log_bodies {
+        return;
+    }
+    let Some(database) = database else { return };
+    let database = database.clone();
+    let _ = SINK.set(Sink {
+        writer: BatchWriter::spawn("request-log-writer", move |entries| {
+            if let Err(e) = database.insert_request_logs(&entries) {
+                tracing::warn!("写入请求日志失败: {}", e);
+            }
+        }),
+        max_body_bytes: config.sampling.max_body_bytes,
+        prefix: config.route_prefix(),
+    });
+}
+
+/// 等待已提交的记录写入数据库（退出前调用）
+pub fn flush() {
+    if let Some(sink) = SINK.get() {
+        sink.writer.flush();
+    }
+}
+
+/// 截取前若干字节的缓冲区
+#[derive(Default)]
+struct Captured {
+    data: Vec<u8>,
+    truncated: bool,
+}
+
+impl Captured {
+    fn push(&mut self, chunk: &Bytes, limit: usize) {
+        let remaining = limit.saturating_sub(self.data.len());
+        if chunk.len() > remaining {
+            self.truncated = true;
+        }
+        self.data
+            .extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+    }
+
+    fn text(&self) -> String {
+        redact::scrub(&String::from_utf8_lossy(&self.data)).into_owned()
+    }
+}
+
+/// 正在记录的请求
+pub struct Capture {
+    timestamp: DateTime<Utc>,
+    started: Instant,
+    request_id: String,
+    method: String,
+    route: String,
+    request: Arc<Mutex<Captured>>,
+}
+
+/// 开始记录请求：未启用或不是 POST 请求时原样返回
+pub fn begin(request: Request<Body>, request_id: &str) -> (Request<Body>, Option<Capture>) {
+    let Some(sink) = SINK.get() else {
+        return (request, None);
+    };
+    if request.method() != Method::POST {
+        return (request, None);
+    }
+    let path = request.uri().path();
+    let capture = Capture {
+        timestamp: Utc::now(),
+        started: Instant::now(),
+        request_id: request_id.to_string(),
+        method: request.method().to_string(),
+        route: path.strip_prefix(&sink.prefix).unwrap_or(path).to_string(),
+        request: Arc::default(),
+    };
+
+    let (parts, body) = request.into_parts();
+    let buffer = capture.request.clone();
+    let limit = sink.max_body_bytes;
+    let body = Body::from_stream(body.into_data_stream().map(move |chunk| {
+        if let Ok(bytes) = &chunk {
+            buffer.lock().push(bytes, limit);
+        }
+        chunk
+    }));
+    (Request::from_parts(parts, body), Some(capture))
+}
+
+impl Capture {
+    /// 复制响应体，响应流结束时写入记录（只对需要记录的请求调用）
+    pub fn finish(self, response: Response) -> Response {
+        let Some(sink) = SINK.get() else {
+            return response;
+        };
+        let (parts, body) = response.into_parts();
+        let mut pending = Pending {
+            capture: self,
+            status: parts.status.as_u16(),
+            response: Captured::default(),
+            sink,
+        };
+        let body = Body::from_stream(body.into_data_stream().map(move |chunk| {
+            if let Ok(bytes) = &chunk {
+                pending.response.push(bytes, pending.sink.max_body_bytes);
+            }
+            chunk
+        }));
+        Response::from_parts(parts, body)
+    }
+}
+
+/// 等待响应流结束的记录，丢弃时提交给后台写入线程
+struct Pending {
+    capture: Capture,
+    status: u16,
+    response: Captured,
+    sink: &'static Sink,
+}
+
+impl Pending {
+    fn entry(&self) -> RequestLogEntry {
+        let request = self.capture.request.lock();
+        RequestLogEntry {
+            timestamp: self.capture.timestamp,
+            request_id: self.capture.request_id.clone(),
+            method: self.capture.method.clone(),
+            route: self.capture.route.clone(),
+            status: self.status,
+            latency_ms: self.capture.started.elapsed().as_millis() as u64,
+            request_body: request.text(),
+            request_truncated: request.truncated,
+            response_body: self.response.text(),
+            response_truncated: self.response.truncated,
+        }
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.sink.writer.send(self.entry());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::RequestLogQuery;
+
+    #[test]
+    fn test_captured_truncates_and_redacts() {
+        let mut captured = Captured::default();
+        captured.push(
+            &Bytes::from_static(b"{\"password\": \"hunter2hunter2\"}"),
+            64,
+        );
+        assert!(!captured.truncated);
+        assert_eq!(captured.text(), "{\"password\": \"[REDACTED]\"}");
+
+        let mut captured = Captured::default();
+        captured.push(&Bytes::from_static(b"abcd"), 6);
+        captured.push(&Bytes::from_static(b"efgh"), 6);
+        captured.push(&Bytes::from_static(b"ijkl"), 6);
+        assert!(captured.truncated);
+        assert_eq!(captured.text(), "abcdef");
+    }
+
+    #[tokio::test]
+    async fn test_capture_records_request_and_response_bodies() {
+        let database = Arc::new(Database::in_memory().unwrap());
+        let mut config = Config {
+            path_prefix: Some("/ai".to_string()),
+            ..Config::default()
+        };
+        config.sampling.log_bodies = true;
+        init(&config, Some(&database));
+
+        let request = Request::post("/ai/v1/messages")
+            .body(Body::from("{\"model\":\"claude\"}"))
+            .unwrap();
+        let (request, capture) = begin(request, "req_1");
+        let capture = capture.unwrap();
+        // 处理函数读取请求体时复制
+        axum::body::to_bytes(request.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        let response = capture.finish(Response::new(Body::from("data: ok\n\n")));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"data: ok\n\n");
+        flush();
+
+        let logs = database.request_logs(&RequestLogQuery::default()).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].request_id, "req_1");
+        assert_eq!(logs[0].route, "/v1/messages");
+        assert_eq!(logs[0].status, 200);
+        assert_eq!(logs[0].request_body, "{\"model\":\"claude\"}");
+        assert_eq!(logs[0].response_body, "data: ok\n\n");
+
+        // 非 POST 请求不记录
+        let (_, capture) = begin(
+            Request::get("/ai/health").body(Body::empty()).unwrap(),
+            "req_2",
+        );
+        assert!(capture.is_none());
+    }
+}
@@ -0,0 +1,250 @@
+//! Windows 服务
+//!
+//! `service install` 通过 `sc.exe` 注册开机自动启动的服务，启动命令为
+//! `kiro-rs --config <配置> --credentials <凭证> service run`（路径在安装时转换为绝对路径，
+//! 服务进程的工作目录为 System32）；`service uninstall` 停止并删除服务。
+//! `service run` 由服务控制管理器（SCM）启动：连接 SCM 后照常启动服务，
+//! 收到停止 / 关机控制时与 Ctrl+C 一样排空连接后退出。非 Windows 平台不支持。
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+/// 默认服务名
+pub const DEFAULT_SERVICE_NAME: &str = "kiro-rs";
+
+/// SCM 发出的停止请求
+static STOP: Notify = Notify::const_new();
+
+/// 等待 SCM 的停止 / 关机控制（非服务模式下永不返回）
+pub async fn stop_requested() {
+    STOP.notified().await;
+}
+
+/// 服务的启动命令行
+fn command_line(exe: &Path, config: &Path, credentials: &Path, name: &str) -> String {
+    format!(
+        "\"{}\" --config \"{}\" --credentials \"{}\" service run --name \"{}\"",
+        exe.display(),
+        config.display(),
+        credentials.display(),
+        name
+    )
+}
+
+/// 转换为绝对路径（文件需已存在）
+fn absolute(path: &str) -> anyhow::Result<PathBuf> {
+    let path = std::fs::canonicalize(path).map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+    // 去掉 canonicalize 在 Windows 上添加的 `\\?\` 前缀，sc.exe 与服务启动命令不识别
+    Ok(match path.to_str().and_then(|p| p.strip_prefix(r"\\?\")) {
+        Some(stripped) => PathBuf::from(stripped),
+        None => path,
+    })
+}
+
+/// 注册开机自动启动的服务
+pub fn install(
+    name: &str,
+    display_name: &str,
+    config_path: &str,
+    credentials_path: &str,
+) -> anyhow::Result<()> {
+    let exe = std::env::current_exe()?;
+    let command = command_line(
+        &exe,
+        &absolute(config_path)?,
+        &absolute(credentials_path)?,
+        name,
+    );
+    sc(&[
+        "create",
+        name,
+        "binPath=",
+        &command,
+        "start=",
+        "auto",
+        "DisplayName=",
+        display_name,
+    ])?;
+    sc(&[
+        "description",
+        name,
+        "Anthropic Claude API compatible proxy for Kiro",
+    ])?;
+    // 异常退出后自动重启
+    sc(&[
+        "failure",
+        name,
+        "reset=",
+        "86400",
+        "actions=",
+        "restart/5000/restart/5000/restart/30000",
+    ])?;
+    println!("已安装服务 {}，启动命令: {}", name, command);
+    println!("使用 `sc.exe start {}` 启动服务", name);
+    Ok(())
+}
+
+/// 停止并删除服务
+pub fn uninstall(name: &str) -> anyhow::Result<()> {
+    // 服务未运行时停止会失败，忽略
+    let _ = sc(&["stop", name]);
+    sc(&["delete", name])?;
+    println!("已删除服务 {}", name);
+    Ok(())
+}
+
+#[cfg(windows)]
+fn sc(args: &[&str]) -> anyhow::Result<()> {
+    let output = std::process::Command::new("sc.exe").args(args).output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "sc.exe {} 失败: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stdout).trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn sc(_args: &[&str]) -> anyhow::Result<()> {
+    anyhow::bail!("仅 Windows 支持服务模式")
+}
+
+/// 连接服务控制管理器，报告服务已启动（`stop_timeout` 用于告知 SCM 停止所需的最长时间）
+#[cfg(windows)]
+pub fn start(name: &str, stop_timeout: Duration) -> anyhow::Result<()> {
+    scm::start(name, stop_timeout)
+}
+
+#[cfg(not(windows))]
+pub fn start(_name: &str, _stop_timeout: Duration) -> anyhow::Result<()> {
+    anyhow::bail!("仅 Windows 支持服务模式")
+}
+
+/// 向 SCM 报告服务已停止（非服务模式下为空操作）
+pub fn stopped() {
+    #[cfg(windows)]
+    scm::set_status(windows_service::service::ServiceState::Stopped);
+}
+
+/// 服务控制管理器接口（windows-service）
+#[cfg(windows)]
+mod scm {
+    use std::ffi::OsString;
+    use std::sync::OnceLock;
+    use std::time::Duration;
+
+    use windows_service::service::{
+        ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus,
+        ServiceType,
+    };
+    use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+    use windows_service::{define_windows_service, service_dispatcher};
+
+    use windows_service::service_control_handler::ServiceStatusHandle;
+
+    /// 服务名
+    static NAME: OnceLock<String> = OnceLock::new();
+    /// 注册控制处理函数后得到的状态句柄
+    static HANDLE: OnceLock<ServiceStatusHandle> = OnceLock::new();
+    /// 停止所需的最长时间
+    static STOP_WAIT_HINT: OnceLock<Duration> = OnceLock::new();
+
+    define_windows_service!(ffi_service_main, service_main);
+
+    pub fn start(name: &str, stop_timeout: Duration) -> anyhow::Result<()> {
+        NAME.set(name.to_string())
+            .map_err(|_| anyhow::anyhow!("服务已启动"))?;
+        let _ = STOP_WAIT_HINT.set(stop_timeout);
+        // service_dispatcher::start 阻塞到服务停止，在独立线程中调用
+        std::thread::spawn(|| {
+            let name = NAME.get().map(String::as_str).unwrap_or_default();
+            if let Err(e) = service_dispatcher::start(name, ffi_service_main) {
+                tracing::error!(
+                    "连接服务控制管理器失败（`service run` 只能由 Windows 服务启动）: {}",
+                    e
+                );
+                std::process::exit(1);
+            }
+        });
+        Ok(())
+    }
+
+    fn service_main(_arguments: Vec<OsString>) {
+        let Some(name) = NAME.get() else {
+            return;
+        };
+        let handler = |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                set_status(ServiceState::StopPending);
+                super::STOP.notify_one();
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        };
+        match service_control_handler::register(name, handler) {
+            Ok(handle) => {
+                let _ = HANDLE.set(handle);
+                set_status(ServiceState::Running);
+                tracing::info!("已作为 Windows 服务运行");
+            }
+            Err(e) => tracing::error!("注册服务控制处理函数失败: {}", e),
+        }
+    }
+
+    pub fn set_status(state: ServiceState) {
+        let Some(handle) = HANDLE.get() else {
+            return;
+        };
+        let status = ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted: if state == ServiceState::Running {
+                ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+            } else {
+                ServiceControlAccept::empty()
+            },
+            exit_code: ServiceExitCode::Win32(0),
+            checkpoint: 0,
+            wait_hint: if state == ServiceState::StopPending {
+                STOP_WAIT_HINT.get().copied().unwrap_or_default()
+            } else {
+                Duration::ZERO
+            },
+            process_id: None,
+        };
+        if let Err(e) = handle.set_service_status(status) {
+            tracing::warn!("更新服务状态失败: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line_quotes_paths() {
+        let line = command_line(
+            Path::new(r"C:\Program Files\kiro-rs\kiro-rs.exe"),
+            Path::new(r"C:\ProgramData\kiro-rs\config.json"),
+            Path::new(r"C:\ProgramData\kiro-rs\credentials.json"),
+            DEFAULT_SERVICE_NAME,
+        );
+        assert_eq!(
+            line,
+            r#""C:\Program Files\kiro-rs\kiro-rs.exe" --config "C:\ProgramData\kiro-rs\config.json" --credentials "C:\ProgramData\kiro-rs\credentials.json" service run --name "kiro-rs""#
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_unsupported_platform() {
+        assert!(start(DEFAULT_SERVICE_NAME, Duration::from_secs(1)).is_err());
+        assert!(uninstall(DEFAULT_SERVICE_NAME).is_err());
+    }
+}
@@ -0,0 +1,143 @@
+//! systemd 服务通知
+//!
+//! 以 `Type=notify` 运行时（systemd 设置了 `NOTIFY_SOCKET`），所有监听地址就绪后发送 `READY=1`，
+//! 收到停止信号时发送 `STOPPING=1`，热加载期间发送 `RELOADING=1`。
+//! 单元配置了 `WatchdogSec=` 时按一半间隔发送 `WATCHDOG=1`：心跳由 tokio 运行时中的任务发出，
+//! 运行时卡死时心跳随之停止，systemd 会在超时后重启服务。
+//! 未由 systemd 启动或非 Unix 平台时所有通知均为空操作。
+
+use std::time::Duration;
+
+/// 发送通知（`NOTIFY_SOCKET` 未设置时忽略）
+pub fn notify(state: &str) {
+    #[cfg(unix)]
+    if let Some(socket) = std::env::var_os("NOTIFY_SOCKET")
+        && let Err(e) = send(&socket, state)
+    {
+        tracing::warn!("发送 systemd 通知失败: {}", e);
+    }
+    #[cfg(not(unix))]
+    let _ = state;
+}
+
+#[cfg(unix)]
+fn send(socket: &std::ffi::OsStr, state: &str) -> std::io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::UnixDatagram;
+
+    let datagram = UnixDatagram::unbound()?;
+    let path = socket.as_bytes();
+    // `@` 开头为 Linux 抽象命名空间地址
+    #[cfg(target_os = "linux")]
+    if let Some(name) = path.strip_prefix(b"@") {
+        use std::os::linux::net::SocketAddrExt;
+
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+        datagram.send_to_addr(state.as_bytes(), &addr)?;
+        return Ok(());
+    }
+    datagram.send_to(state.as_bytes(), std::ffi::OsStr::from_bytes(path))?;
+    Ok(())
+}
+
+/// 所有监听地址已就绪
+pub fn ready() {
+    notify(&format!("READY=1\nMAINPID={}", std::process::id()));
+}
+
+/// 开始停止服务
+pub fn stopping() {
+    notify("STOPPING=1");
+}
+
+/// 开始重新加载配置
+pub fn reloading() {
+    notify(&format!("RELOADING=1\nMONOTONIC_USEC={}", monotonic_usec()));
+}
+
+/// 配置重新加载完成
+pub fn reloaded() {
+    notify("READY=1");
+}
+
+/// `Type=notify-reload` 要求 `RELOADING=1` 附带 CLOCK_MONOTONIC 时间戳（微秒）
+fn monotonic_usec() -> i64 {
+    #[cfg(unix)]
+    {
+        let mut now = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: `now` 为有效的可写 timespec，CLOCK_MONOTONIC 在所有 Unix 平台上可用
+        if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) } == 0 {
+            // 32 位平台上 time_t / c_long 可能为 i32
+            #[allow(clippy::useless_conversion)]
+            return i64::from(now.tv_sec) * 1_000_000 + i64::from(now.tv_nsec) / 1_000;
+        }
+    }
+    0
+}
+
+/// 解析 systemd 设置的看门狗间隔（`WATCHDOG_PID` 指向其他进程时不启用）
+fn watchdog_interval(usec: Option<&str>, pid: Option<&str>) -> Option<Duration> {
+    if let Some(pid) = pid
+        && pid.parse::<u32>().ok() != Some(std::process::id())
+    {
+        return None;
+    }
+    let usec = usec?.parse::<u64>().ok().filter(|usec| *usec > 0)?;
+    Some(Duration::from_micros(usec))
+}
+
+/// 配置了 `WatchdogSec=` 时在后台按一半间隔发送看门狗心跳
+pub fn spawn_watchdog() {
+    let Some(interval) = watchdog_interval(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+    ) else {
+        return;
+    };
+    tracing::info!("已启用 systemd 看门狗，间隔 {:?}", interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval / 2);
+        loop {
+            ticker.tick().await;
+            notify("WATCHDOG=1");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchdog_interval() {
+        let pid = std::process::id().to_string();
+        assert_eq!(
+            watchdog_interval(Some("30000000"), None),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            watchdog_interval(Some("30000000"), Some(&pid)),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(watchdog_interval(Some("30000000"), Some("1")), None);
+        assert_eq!(watchdog_interval(Some("0"), None), None);
+        assert_eq!(watchdog_interval(None, None), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_send_to_socket_path() {
+        let path = std::env::temp_dir().join(format!("kiro-notify-{}.sock", uuid::Uuid::new_v4()));
+        let receiver = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        send(path.as_os_str(), "READY=1").unwrap();
+        let mut buf = [0u8; 64];
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
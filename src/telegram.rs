@@ -0,0 +1,134 @@
+//! Telegram 通知
+//!
+//! 配置 `telegram`（Bot Token 与 Chat ID）后，将错误率告警的触发与解除、以及凭据生命周期事件
+//! （自动禁用、额度达到阈值、连续刷新失败、可用凭据不足与恢复）以文本消息发送到指定会话，
+//! 适合没有其他告警渠道的自托管部署。发送在后台进行，失败只记录警告。
+
+use std::sync::LazyLock;
+
+use parking_lot::Mutex;
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::http_client;
+use crate::metrics::{
+    self,
+    alerts::{AlertEvent, AlertScope},
+};
+use crate::model::config::TelegramConfig;
+
+/// Bot API 地址
+const API_BASE: &str = "https://api.telegram.org";
+
+/// 发送请求的超时时间（秒）
+const SEND_TIMEOUT_SECS: u64 = 10;
+
+/// 当前配置（None 表示未启用）
+static CONFIG: LazyLock<Mutex<Option<TelegramConfig>>> = LazyLock::new(Mutex::default);
+
+static CLIENT: LazyLock<reqwest::Client> =
+    LazyLock::new(|| http_client::build_integration_client(SEND_TIMEOUT_SECS).unwrap_or_default());
+
+/// 设置 Bot Token 与 Chat ID（None 表示关闭）
+pub fn configure(config: Option<TelegramConfig>) {
+    *CONFIG.lock() = config;
+}
+
+/// 是否已启用
+pub fn enabled() -> bool {
+    CONFIG.lock().is_some()
+}
+
+/// 在后台发送一条消息（未启用或不在 tokio 运行时内时忽略）
+pub fn send(text: String) {
+    let Some(config) = CONFIG.lock().clone() else {
+        return;
+    };
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    let url = format!("{}/bot{}/sendMessage", API_BASE, config.bot_token);
+    let body = json!({
+        "chat_id": config.chat_id,
+        "text": text,
+        "disable_web_page_preview": true,
+    });
+    runtime.spawn(async move {
+        match CLIENT.post(url).json(&body).send().await {
+            Ok(resp) if !resp.status().is_success() => {
+                tracing::warn!("发送 Telegram 通知失败: HTTP {}", resp.status());
+            }
+            Ok(_) => {}
+            // 请求 URL 中含有 Bot Token，不输出到日志
+            Err(e) => tracing::warn!("发送 Telegram 通知失败: {}", e.without_url()),
+        }
+    });
+}
+
+/// 启动后台任务，将错误率告警事件转发到 Telegram
+pub fn spawn_alert_forwarder() {
+    let mut events = metrics::alerts().subscribe();
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if enabled() {
+                        send(alert_text(&event));
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Telegram 通知跳过了 {} 个告警事件", skipped);
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// 告警事件的消息文本
+fn alert_text(event: &AlertEvent) -> String {
+    let alert = &event.alert;
+    let scope = match alert.scope {
+        AlertScope::Global => "全局".to_string(),
+        AlertScope::Credential { credential_id } => format!("凭据 #{} ", credential_id),
+    };
+    let title = if event.kind == "alert_fired" {
+        "⚠️ 告警触发"
+    } else {
+        "✅ 告警解除"
+    };
+    format!(
+        "{}：{}错误率 {:.1}%（{} 个请求中 {} 个失败，阈值 {:.1}%）",
+        title,
+        scope,
+        alert.error_rate * 100.0,
+        alert.requests,
+        alert.errors,
+        alert.threshold * 100.0
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::alerts::Alert;
+
+    #[test]
+    fn test_alert_text() {
+        let event = AlertEvent {
+            kind: "alert_fired",
+            alert: Alert {
+                scope: AlertScope::Credential { credential_id: 2 },
+                error_rate: 0.35,
+                requests: 40,
+                errors: 14,
+                threshold: 0.25,
+                since: chrono::Utc::now(),
+            },
+        };
+        assert_eq!(
+            alert_text(&event),
+            "⚠️ 告警触发：凭据 #2 错误率 35.0%（40 个请求中 14 个失败，阈值 25.0%）"
+        );
+    }
+}
@@ -0,0 +1,443 @@
+//! 链路追踪导出（OTLP/HTTP JSON）
+//!
+//! 将 `kiro_rs` 内的 tracing span（请求 → 凭据选择 → Token 刷新 → 上游调用 → 流式响应）
+//! 转换为 OpenTelemetry span，批量以 OTLP/HTTP JSON 格式发送到 `{otlpEndpoint}/v1/traces`，
+//! 可直接接入 Jaeger、Tempo 或 OpenTelemetry Collector。
+//!
+//! span 字段约定：`otel.kind`（server / client）设置 span 类型，`otel.status_code = "error"`
+//! 标记失败，`traceparent` 字段携带客户端传入的 W3C Trace Context 以延续其链路，
+//! 其余字段作为 span 属性导出。
+//!
+//! 采样在链路结束（根 span 关闭）时决定：链路中任一 span 失败则完整导出，
+//! 否则按根 span 的 `otel.sampled` 字段（请求 span 由 [`sample`] 按 `sampling.traceRate` 决定）导出或丢弃。
+
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{Value, json};
+use tokio::sync::mpsc;
+use tracing::Subscriber;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::http_client;
+use crate::model::config::{Config, SamplingConfig};
+use crate::redact;
+
+/// 只导出本 crate 内的 span
+const TARGET_PREFIX: &str = "kiro_rs";
+
+/// 单次发送的最大 span 数
+const MAX_BATCH: usize = 512;
+
+/// 发送间隔
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 已结束的 span，启用导出后才会设置
+static EXPORTER: OnceLock<mpsc::UnboundedSender<Value>> = OnceLock::new();
+
+/// 采样率配置（未初始化时全部采样）
+static SAMPLING: OnceLock<SamplingConfig> = OnceLock::new();
+
+/// 单个请求的采样决定
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    /// 成功时是否导出链路
+    pub trace: bool,
+    /// 成功时是否输出请求完成日志（启用 `sampling.logBodies` 时同时记录请求体与响应体）
+    pub log: bool,
+}
+
+/// 为新请求做采样决定（失败的请求不受采样影响，始终导出与记录）
+///
+/// 两个采样率使用同一个随机数，因此采样率较低一方选中的请求一定也被另一方选中。
+pub fn sample() -> Sample {
+    let Some(config) = SAMPLING.get() else {
+        return Sample {
+            trace: true,
+            log: true,
+        };
+    };
+    let draw = fastrand::f64();
+    Sample {
+        trace: draw < config.trace_rate,
+        log: draw < config.log_rate,
+    }
+}
+
+/// 将 span 转换为 OTLP span 的 tracing 层（未启用导出时不做任何处理）
+pub struct OtlpLayer;
+
+/// 挂在 span 扩展中的 OTLP 数据
+struct SpanData {
+    trace_id: String,
+    span_id: String,
+    parent_span_id: Option<String>,
+    start: SystemTime,
+    fields: SpanFields,
+    /// 已结束的子孙 span，等待根 span 结束时决定是否导出
+    pending: Vec<Value>,
+    /// 子孙 span 中是否有失败
+    descendant_error: bool,
+}
+
+/// span 字段
+#[derive(Default)]
+struct SpanFields {
+    kind: Option<String>,
+    error: bool,
+    traceparent: Option<String>,
+    /// 采样决定（`otel.sampled`），未设置时按 `sampling.traceRate` 随机决定
+    sampled: Option<bool>,
+    attributes: Vec<Value>,
+}
+
+impl Visit for SpanFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "otel.kind" => self.kind = Some(value.to_string()),
+            "otel.status_code" => self.error = value.eq_ignore_ascii_case("error"),
+            "traceparent" => self.traceparent = Some(value.to_string()),
+            name => self.push(name, json!({ "stringValue": redact::scrub(value) })),
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field.name(), json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push(field.name(), json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        match field.name() {
+            "otel.sampled" => self.sampled = Some(value),
+            name => self.push(name, json!({ "boolValue": value })),
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.push(field.name(), json!({ "doubleValue": value }));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+}
+
+impl SpanFields {
+    fn push(&mut self, key: &str, value: Value) {
+        // 同名字段以最后一次记录为准
+        self.attributes.retain(|a| a["key"] != key);
+        self.attributes.push(json!({ "key": key, "value": value }));
+    }
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if EXPORTER.get().is_none() {
+            return;
+        }
+        let Some(span) = ctx.span(id) else { return };
+        if !span.metadata().target().starts_with(TARGET_PREFIX) {
+            return;
+        }
+
+        let mut fields = SpanFields::default();
+        attrs.record(&mut fields);
+
+        let parent = span.scope().skip(1).find_map(|ancestor| {
+            ancestor
+                .extensions()
+                .get::<SpanData>()
+                .map(|p| (p.trace_id.clone(), p.span_id.clone()))
+        });
+        let (trace_id, parent_span_id) = match parent {
+            Some((trace_id, span_id)) => (trace_id, Some(span_id)),
+            None => match fields.traceparent.as_deref().and_then(parse_traceparent) {
+                Some((trace_id, span_id)) => (trace_id, Some(span_id)),
+                None => (hex::encode(random_id::<16>()), None),
+            },
+        };
+
+        span.extensions_mut().insert(SpanData {
+            trace_id,
+            span_id: hex::encode(random_id::<8>()),
+            parent_span_id,
+            start: SystemTime::now(),
+            fields,
+            pending: Vec::new(),
+            descendant_error: false,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id)
+            && let Some(data) = span.extensions_mut().get_mut::<SpanData>()
+        {
+            values.record(&mut data.fields);
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(sender) = EXPORTER.get() else { return };
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(mut data) = span.extensions_mut().remove::<SpanData>() else {
+            return;
+        };
+        let mut pending = std::mem::take(&mut data.pending);
+        let error = data.fields.error || data.descendant_error;
+        let sampled = data.fields.sampled;
+        pending.push(to_otlp_span(span.name(), data, SystemTime::now()));
+
+        // 非根 span 交给最外层的导出 span，由其结束时统一决定
+        let root = span
+            .scope()
+            .skip(1)
+            .filter(|ancestor| ancestor.extensions().get::<SpanData>().is_some())
+            .last();
+        if let Some(root) = root
+            && let Some(root_data) = root.extensions_mut().get_mut::<SpanData>()
+        {
+            root_data.descendant_error |= error;
+            root_data.pending.append(&mut pending);
+            return;
+        }
+
+        if should_export(error, sampled) {
+            for otlp_span in pending {
+                let _ = sender.send(otlp_span);
+            }
+        }
+    }
+}
+
+/// 链路结束时是否导出：失败的链路始终导出，其余按采样决定
+fn should_export(error: bool, sampled: Option<bool>) -> bool {
+    error
+        || sampled.unwrap_or_else(|| {
+            SAMPLING
+                .get()
+                .is_none_or(|config| fastrand::f64() < config.trace_rate)
+        })
+}
+
+/// 转换为 OTLP JSON span
+fn to_otlp_span(name: &str, data: SpanData, end: SystemTime) -> Value {
+    let kind = match data.fields.kind.as_deref() {
+        Some("server") => 2,
+        Some("client") => 3,
+        _ => 1,
+    };
+    let mut span = json!({
+        "traceId": data.trace_id,
+        "spanId": data.span_id,
+        "name": name,
+        "kind": kind,
+        "startTimeUnixNano": unix_nanos(data.start).to_string(),
+        "endTimeUnixNano": unix_nanos(end).to_string(),
+        "attributes": data.fields.attributes,
+        "status": { "code": if data.fields.error { 2 } else { 0 } },
+    });
+    if let Some(parent) = data.parent_span_id {
+        span["parentSpanId"] = json!(parent);
+    }
+    span
+}
+
+/// 解析 W3C `traceparent`（`00-<trace-id>-<parent-id>-<flags>`），返回 trace ID 与父 span ID
+fn parse_traceparent(value: &str) -> Option<(String, String)> {
+    let mut parts = value.trim().split('-');
+    let (_version, trace_id, span_id) = (parts.next()?, parts.next()?, parts.next()?);
+    let valid = |id: &str, len: usize| {
+        id.len() == len
+            && id.bytes().all(|b| b.is_ascii_hexdigit())
+            && id.bytes().any(|b| b != b'0')
+    };
+    (valid(trace_id, 32) && valid(span_id, 16))
+        .then(|| (trace_id.to_ascii_lowercase(), span_id.to_ascii_lowercase()))
+}
+
+fn random_id<const N: usize>() -> [u8; N] {
+    let mut id = [0u8; N];
+    while id.iter().all(|b| *b == 0) {
+        id.iter_mut().for_each(|b| *b = fastrand::u8(..));
+    }
+    id
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+/// 按配置启用 OTLP 导出（环境变量 `OTEL_EXPORTER_OTLP_ENDPOINT` / `OTEL_SERVICE_NAME` 优先）
+///
+/// 需要在 tokio 运行时内调用。
+pub fn init(config: &Config) {
+    let _ = SAMPLING.set(config.sampling.clone());
+    let endpoint = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .or_else(|| config.otlp_endpoint.clone());
+    let Some(endpoint) = endpoint else { return };
+    let service_name = std::env::var("OTEL_SERVICE_NAME")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| config.otlp_service_name.clone());
+
+    let url = traces_url(&endpoint);
+    let (sender, receiver) = mpsc::unbounded_channel();
+    if EXPORTER.set(sender).is_err() {
+        return;
+    }
+    tracing::info!(
+        "已启用 OTLP 链路追踪导出: {}（服务名 {}）",
+        url,
+        service_name
+    );
+    tokio::spawn(export_loop(receiver, url, service_name));
+}
+
+/// OTLP/HTTP 的 traces 接收地址
+fn traces_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    }
+}
+
+/// 批量发送已结束的 span：攒满一批或每隔 `FLUSH_INTERVAL` 发送一次
+async fn export_loop(mut receiver: mpsc::UnboundedReceiver<Value>, url: String, service: String) {
+    let client = match http_client::build_integration_client(30) {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::warn!(
+                "创建 OTLP 导出的 HTTP Client 失败，链路追踪导出未启动: {}",
+                e
+            );
+            return;
+        }
+    };
+    let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+    let mut batch = Vec::with_capacity(MAX_BATCH);
+    loop {
+        let limit = MAX_BATCH - batch.len();
+        let (flush, closed) = tokio::select! {
+            received = receiver.recv_many(&mut batch, limit) => {
+                (batch.len() >= MAX_BATCH || received == 0, received == 0)
+            }
+            _ = ticker.tick() => (!batch.is_empty(), false),
+        };
+        if flush && !batch.is_empty() {
+            let body = export_request(&service, std::mem::take(&mut batch));
+            match client.post(&url).json(&body).send().await {
+                Ok(response) if !response.status().is_success() => {
+                    tracing::warn!("OTLP 导出失败: {}", response.status());
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("OTLP 导出失败: {}", e),
+            }
+        }
+        if closed {
+            return;
+        }
+    }
+}
+
+/// OTLP `ExportTraceServiceRequest`
+fn export_request(service: &str, spans: Vec<Value>) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    { "key": "service.name", "value": { "stringValue": service } },
+                    { "key": "service.version", "value": { "stringValue": env!("CARGO_PKG_VERSION") } },
+                ],
+            },
+            "scopeSpans": [{
+                "scope": { "name": TARGET_PREFIX },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_traceparent() {
+        let parsed =
+            parse_traceparent("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01").unwrap();
+        assert_eq!(parsed.0, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(parsed.1, "00f067aa0ba902b7");
+
+        assert!(
+            parse_traceparent("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_none()
+        );
+        assert!(parse_traceparent("00-abc-00f067aa0ba902b7-01").is_none());
+        assert!(parse_traceparent("garbage").is_none());
+    }
+
+    #[test]
+    fn test_traces_url() {
+        assert_eq!(
+            traces_url("http://localhost:4318"),
+            "http://localhost:4318/v1/traces"
+        );
+        assert_eq!(
+            traces_url("http://localhost:4318/"),
+            "http://localhost:4318/v1/traces"
+        );
+        assert_eq!(
+            traces_url("http://collector/v1/traces"),
+            "http://collector/v1/traces"
+        );
+    }
+
+    #[test]
+    fn test_should_export_errors_regardless_of_sampling() {
+        assert!(should_export(true, Some(false)));
+        assert!(should_export(false, Some(true)));
+        assert!(!should_export(false, Some(false)));
+    }
+
+    #[test]
+    fn test_to_otlp_span() {
+        let mut fields = SpanFields {
+            kind: Some("client".to_string()),
+            error: true,
+            ..Default::default()
+        };
+        fields.push("attempt", json!({ "intValue": "1" }));
+        fields.push("attempt", json!({ "intValue": "2" }));
+        let data = SpanData {
+            trace_id: "t".repeat(32),
+            span_id: "s".repeat(16),
+            parent_span_id: Some("p".repeat(16)),
+            start: UNIX_EPOCH + Duration::from_secs(1),
+            fields,
+            pending: Vec::new(),
+            descendant_error: false,
+        };
+
+        let span = to_otlp_span("upstream_call", data, UNIX_EPOCH + Duration::from_secs(2));
+        assert_eq!(span["kind"], 3);
+        assert_eq!(span["status"]["code"], 2);
+        assert_eq!(span["startTimeUnixNano"], "1000000000");
+        assert_eq!(span["parentSpanId"], "p".repeat(16));
+        assert_eq!(span["attributes"].as_array().unwrap().len(), 1);
+        assert_eq!(span["attributes"][0]["value"]["intValue"], "2");
+    }
+}
@@ -0,0 +1,125 @@
+//! 慢请求耗时分解
+//!
+//! 配置了 `slowRequestThresholdMs` 时，每个请求在 [`scope`] 中执行，
+//! 排队、Token 刷新与上游连接（发出请求到收到响应头）的耗时通过 [`measure`] 累加到当前请求；
+//! 请求结束时总耗时超过阈值则由用量采集器以 WARN 级别输出完整的耗时分解。
+
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// 慢请求阈值（未配置时不采集耗时分解）
+static THRESHOLD: OnceLock<Duration> = OnceLock::new();
+
+tokio::task_local! {
+    static CURRENT: RequestTiming;
+}
+
+/// 请求处理中的阶段
+#[derive(Debug, Clone, Copy)]
+pub enum Phase {
+    /// 在准入队列中等待
+    Queue,
+    /// 刷新凭据 Token
+    Refresh,
+    /// 发出上游请求到收到响应头（每次重试累加）
+    Connect,
+}
+
+/// 各阶段累计耗时
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Phases {
+    pub queue: Duration,
+    pub refresh: Duration,
+    pub connect: Duration,
+    /// 上游请求次数（含重试）
+    pub attempts: u32,
+}
+
+/// 单个请求的耗时分解，可在同一请求的各处共享
+#[derive(Clone, Default)]
+pub struct RequestTiming(Arc<Mutex<Phases>>);
+
+impl RequestTiming {
+    /// 当前累计的各阶段耗时
+    pub fn phases(&self) -> Phases {
+        *self.0.lock()
+    }
+
+    fn add(&self, phase: Phase, elapsed: Duration) {
+        let mut phases = self.0.lock();
+        match phase {
+            Phase::Queue => phases.queue += elapsed,
+            Phase::Refresh => phases.refresh += elapsed,
+            Phase::Connect => {
+                phases.connect += elapsed;
+                phases.attempts += 1;
+            }
+        }
+    }
+}
+
+/// 设置慢请求阈值（毫秒）
+pub fn init(threshold_ms: Option<u64>) {
+    if let Some(ms) = threshold_ms {
+        let _ = THRESHOLD.set(Duration::from_millis(ms));
+    }
+}
+
+/// 慢请求阈值
+pub fn threshold() -> Option<Duration> {
+    THRESHOLD.get().copied()
+}
+
+/// 在新的请求耗时上下文中执行（未配置阈值时直接执行）
+pub async fn scope<F: Future>(future: F) -> F::Output {
+    if threshold().is_some() {
+        CURRENT.scope(RequestTiming::default(), future).await
+    } else {
+        future.await
+    }
+}
+
+/// 当前请求的耗时分解（不在请求上下文中时为 None）
+pub fn current() -> Option<RequestTiming> {
+    CURRENT.try_with(RequestTiming::clone).ok()
+}
+
+/// 执行 `future` 并将耗时计入当前请求的 `phase` 阶段
+pub async fn measure<F: Future>(phase: Phase, future: F) -> F::Output {
+    let started = Instant::now();
+    let output = future.await;
+    let _ = CURRENT.try_with(|timing| timing.add(phase, started.elapsed()));
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_measure_accumulates_in_scope() {
+        let timing = RequestTiming::default();
+        let phases = CURRENT
+            .scope(timing.clone(), async {
+                measure(Phase::Connect, async {}).await;
+                measure(Phase::Connect, async {}).await;
+                measure(Phase::Queue, tokio::time::sleep(Duration::from_millis(5))).await;
+                current().map(|t| t.phases())
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(phases.attempts, 2);
+        assert!(phases.queue >= Duration::from_millis(5));
+        assert_eq!(timing.phases().attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_measure_outside_scope_is_noop() {
+        assert_eq!(measure(Phase::Refresh, async { 1 }).await, 1);
+        assert!(current().is_none());
+    }
+}
@@ -11,6 +11,7 @@ use crate::anthropic::types::{
     CountTokensRequest, CountTokensResponse, Message, SystemMessage, Tool,
 };
 use crate::http_client::{ProxyConfig, build_client};
+use crate::model::config::UpstreamEndpoint;
 use std::sync::OnceLock;
 
 /// Count Tokens API 配置
@@ -143,7 +144,7 @@ async fn call_remote_count_tokens(
     messages: &Vec<Message>,
     tools: &Option<Vec<Tool>>,
 ) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
-    let client = build_client(config.proxy.as_ref(), 300)?;
+    let client = build_client(UpstreamEndpoint::CountTokens, config.proxy.as_ref(), 300)?;
 
     // 构建请求体
     let request = CountTokensRequest {
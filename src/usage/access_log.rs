@@ -0,0 +1,282 @@
+//! 访问日志
+//!
+//! 每个请求结束时以 logfmt 格式追加一行（时间、端点、API Key、模型、凭据、状态码、耗时、tokens），
+//! 与应用日志相互独立。文件超过大小上限或跨越轮转周期（按天 / 按小时）时，
+//! 重命名为 `<文件名>.<时间戳>` 并新建文件，只保留最近的若干个历史文件。
+
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+
+use super::UsageRecord;
+use crate::model::config::{AccessLogConfig, AccessLogRotation};
+
+/// 访问日志文件
+pub struct AccessLog {
+    path: PathBuf,
+    max_bytes: u64,
+    rotation: AccessLogRotation,
+    max_files: usize,
+    state: Mutex<State>,
+}
+
+/// 当前文件状态
+struct State {
+    file: File,
+    size: u64,
+    /// 当前文件所属的轮转周期
+    period: String,
+}
+
+impl AccessLog {
+    /// 打开访问日志，文件不存在时自动创建
+    pub fn open(config: &AccessLogConfig) -> anyhow::Result<Self> {
+        let path = PathBuf::from(&config.path);
+        let (file, size, modified) = open_append(&path)?;
+        Ok(Self {
+            path,
+            max_bytes: config.max_size_mb * 1024 * 1024,
+            rotation: config.rotation,
+            max_files: config.max_files,
+            state: Mutex::new(State {
+                file,
+                size,
+                period: period(config.rotation, modified),
+            }),
+        })
+    }
+
+    /// 追加一条请求记录
+    pub fn write(&self, record: &UsageRecord) {
+        let line = format_line(record);
+        if let Err(e) = self.write_line(&line, Utc::now()) {
+            tracing::warn!("写入访问日志失败 ({}): {}", self.path.display(), e);
+        }
+    }
+
+    /// 将当前文件同步到磁盘
+    pub fn flush(&self) -> anyhow::Result<()> {
+        let mut state = self.state.lock();
+        state.file.flush()?;
+        state.file.sync_data()?;
+        Ok(())
+    }
+
+    fn write_line(&self, line: &str, now: DateTime<Utc>) -> anyhow::Result<()> {
+        let mut state = self.state.lock();
+        let period = period(self.rotation, now);
+        let oversized = self.max_bytes > 0 && state.size + line.len() as u64 > self.max_bytes;
+        if state.size > 0 && (oversized || period != state.period) {
+            self.rotate(&mut state, now)?;
+        }
+        state.period = period;
+
+        state.file.write_all(line.as_bytes())?;
+        state.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// 将当前文件重命名为历史文件并新建文件，清理超出保留数量的历史文件
+    fn rotate(&self, state: &mut State, now: DateTime<Utc>) -> anyhow::Result<()> {
+        state.file.flush()?;
+        let stamp = now.format("%Y%m%d-%H%M%S").to_string();
+        let mut target = self.rotated_path(&stamp);
+        let mut suffix = 1;
+        while target.exists() {
+            target = self.rotated_path(&format!("{}.{}", stamp, suffix));
+            suffix += 1;
+        }
+        fs::rename(&self.path, &target)?;
+
+        let (file, size, _) = open_append(&self.path)?;
+        state.file = file;
+        state.size = size;
+
+        let mut rotated = self.rotated_files()?;
+        if rotated.len() > self.max_files {
+            rotated.sort();
+            for old in &rotated[..rotated.len() - self.max_files] {
+                if let Err(e) = fs::remove_file(old) {
+                    tracing::warn!("删除过期访问日志失败 ({}): {}", old.display(), e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn rotated_path(&self, stamp: &str) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(stamp);
+        self.path.with_file_name(name)
+    }
+
+    /// 已轮转的历史文件
+    fn rotated_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let prefix = format!(
+            "{}.",
+            self.path.file_name().unwrap_or_default().to_string_lossy()
+        );
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        Ok(fs::read_dir(dir)?
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+            .map(|entry| entry.path())
+            .collect())
+    }
+}
+
+/// 以追加模式打开文件，返回文件、当前大小与最后修改时间
+fn open_append(path: &Path) -> anyhow::Result<(File, u64, DateTime<Utc>)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let metadata = file.metadata()?;
+    let modified = metadata
+        .modified()
+        .map(DateTime::<Utc>::from)
+        .unwrap_or_else(|_| Utc::now());
+    Ok((file, metadata.len(), modified))
+}
+
+/// 时间所属的轮转周期
+fn period(rotation: AccessLogRotation, time: DateTime<Utc>) -> String {
+    match rotation {
+        AccessLogRotation::Daily => time.format("%Y%m%d").to_string(),
+        AccessLogRotation::Hourly => time.format("%Y%m%d%H").to_string(),
+        AccessLogRotation::Never => String::new(),
+    }
+}
+
+/// 格式化为一行 logfmt
+fn format_line(record: &UsageRecord) -> String {
+    let mut line = String::new();
+    let mut field = |key: &str, value: &str| {
+        let quoted = value.is_empty() || value.contains([' ', '"', '=']);
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        if quoted {
+            let _ = write!(line, "{}={:?}", key, value);
+        } else {
+            let _ = write!(line, "{}={}", key, value);
+        }
+    };
+
+    field("time", &record.timestamp.to_rfc3339());
+    field("endpoint", &record.endpoint);
+    field("key", &record.api_key);
+    field("model", &record.model);
+    field(
+        "credential",
+        &record
+            .credential_id
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| "-".to_string()),
+    );
+    field("status", &record.status.to_string());
+    field("latency_ms", &record.latency_ms.to_string());
+    field("input_tokens", &record.input_tokens.to_string());
+    field("output_tokens", &record.output_tokens.to_string());
+    field("stream", &record.stream.to_string());
+    if let Some(user) = &record.user {
+        field("user", user);
+    }
+    if let Some(ip) = &record.client_ip {
+        field("ip", ip);
+    }
+    line.push('\n');
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn record() -> UsageRecord {
+        UsageRecord {
+            timestamp: Utc.with_ymd_and_hms(2026, 1, 2, 3, 4, 5).unwrap(),
+            endpoint: "/v1/messages".to_string(),
+            model: "claude-sonnet-4-5".to_string(),
+            api_key: "sk-***".to_string(),
+            credential_id: Some(2),
+            input_tokens: 10,
+            output_tokens: 20,
+            latency_ms: 150,
+            status: 200,
+            stream: true,
+            user: Some("alice smith".to_string()),
+            guardrail: None,
+            client_ip: Some("198.51.100.7".to_string()),
+        }
+    }
+
+    fn config(dir: &Path, max_size_mb: u64, rotation: AccessLogRotation) -> AccessLogConfig {
+        AccessLogConfig {
+            path: dir.join("access.log").to_string_lossy().into_owned(),
+            max_size_mb,
+            rotation,
+            max_files: 2,
+        }
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kiro-access-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_format_line() {
+        assert_eq!(
+            format_line(&record()),
+            "time=2026-01-02T03:04:05+00:00 endpoint=/v1/messages key=sk-*** \
+             model=claude-sonnet-4-5 credential=2 status=200 latency_ms=150 \
+             input_tokens=10 output_tokens=20 stream=true user=\"alice smith\" ip=198.51.100.7\n"
+        );
+    }
+
+    #[test]
+    fn test_rotates_on_period_change_and_keeps_max_files() {
+        let dir = temp_dir();
+        let log = AccessLog::open(&config(&dir, 0, AccessLogRotation::Hourly)).unwrap();
+        let start = Utc::now();
+        for hour in 0..4 {
+            log.write_line("line\n", start + chrono::Duration::hours(hour))
+                .unwrap();
+        }
+
+        let rotated = log.rotated_files().unwrap();
+        assert_eq!(rotated.len(), 2);
+        assert_eq!(
+            fs::read_to_string(dir.join("access.log")).unwrap(),
+            "line\n"
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_rotates_when_size_exceeded() {
+        let dir = temp_dir();
+        let mut log = AccessLog::open(&config(&dir, 1, AccessLogRotation::Never)).unwrap();
+        log.max_bytes = 10;
+        let now = Utc::now();
+        log.write_line("12345678\n", now).unwrap();
+        log.write_line("abcdefgh\n", now).unwrap();
+
+        assert_eq!(log.rotated_files().unwrap().len(), 1);
+        assert_eq!(
+            fs::read_to_string(dir.join("access.log")).unwrap(),
+            "abcdefgh\n"
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,15 @@
+//! 请求用量统计模块
+//!
+//! 每个代理请求结束时生成一条 [`UsageRecord`]，写入 [`UsageStore`]，
+//! 作为统计接口与配额功能的数据来源；配置了访问日志时同时写入 [`AccessLog`]。
+//! 配置了每日报告时，[`report`] 定时汇总前一天的记录；[`timeseries`] 将记录分桶供图表使用。
+
+mod access_log;
+mod recorder;
+pub mod report;
+mod store;
+pub mod timeseries;
+
+pub use access_log::AccessLog;
+pub use recorder::{GuardrailFlag, UsageRecorder};
+pub use store::{KeyUsageSummary, UsageRecord, UsageStore};
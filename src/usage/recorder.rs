@@ -0,0 +1,251 @@
+//! 单请求用量采集器
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use parking_lot::Mutex;
+
+use crate::common::client_ip;
+use crate::limit::RateLimiter;
+use crate::metrics;
+use crate::timing::{self, RequestTiming};
+
+use super::store::{UsageRecord, UsageStore};
+
+/// 客户端中途断开时记录的状态码（沿用 nginx 的约定）
+pub const STATUS_CLIENT_CLOSED: u16 = 499;
+
+/// 护栏命中标记
+///
+/// 可交给流处理中的其他阶段写入，采集器写入存储时读取（仅保留第一次命中的规则）
+#[derive(Clone, Default)]
+pub struct GuardrailFlag(Arc<Mutex<Option<String>>>);
+
+impl GuardrailFlag {
+    /// 记录命中的屏蔽规则
+    pub fn set(&self, pattern: &str) {
+        self.0.lock().get_or_insert_with(|| pattern.to_string());
+    }
+
+    /// 获取命中的屏蔽规则
+    pub fn get(&self) -> Option<String> {
+        self.0.lock().clone()
+    }
+}
+
+/// 单请求用量采集器
+///
+/// 在请求处理过程中逐步填充 token、凭据和状态码，
+/// 被 drop 时（请求完成或客户端断开）计算耗时并写入存储。
+///
+/// 客户端断开时 axum 会丢弃 handler future 或响应体流，采集器随之被 drop；
+/// 此时上游响应也一并被丢弃、连接关闭，状态码保持为 499。
+pub struct UsageRecorder {
+    store: Arc<UsageStore>,
+    record: UsageRecord,
+    started: Instant,
+    /// 请求结束时扣减 TPM 额度的全局限流器
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// 护栏命中标记
+    guardrail: GuardrailFlag,
+    /// 收到第一个生成 token 的时间
+    first_token: Option<Instant>,
+    /// 请求的耗时分解（配置了慢请求阈值时）
+    timing: Option<RequestTiming>,
+}
+
+impl UsageRecorder {
+    pub fn new(
+        store: Arc<UsageStore>,
+        endpoint: impl Into<String>,
+        api_key: impl Into<String>,
+        model: impl Into<String>,
+        stream: bool,
+    ) -> Self {
+        Self {
+            store,
+            record: UsageRecord {
+                timestamp: Utc::now(),
+                endpoint: endpoint.into(),
+                model: model.into(),
+                api_key: api_key.into(),
+                credential_id: None,
+                input_tokens: 0,
+                output_tokens: 0,
+                latency_ms: 0,
+                // 在处理流程显式设置状态码之前被 drop，说明客户端已断开
+                status: STATUS_CLIENT_CLOSED,
+                stream,
+                user: None,
+                guardrail: None,
+                client_ip: client_ip::current().map(|ip| ip.to_string()),
+            },
+            started: Instant::now(),
+            rate_limiter: None,
+            guardrail: GuardrailFlag::default(),
+            first_token: None,
+            timing: timing::current(),
+        }
+    }
+
+    /// 为同一请求的另一次上游调用创建独立的采集器（如 n > 1 时的并行请求）
+    pub fn sibling(&self) -> Self {
+        let mut recorder = Self::new(
+            self.store.clone(),
+            self.record.endpoint.clone(),
+            self.record.api_key.clone(),
+            self.record.model.clone(),
+            self.record.stream,
+        );
+        recorder.rate_limiter = self.rate_limiter.clone();
+        recorder.timing = self.timing.clone();
+        recorder
+    }
+
+    /// 请求结束时将 token 用量计入全局限流器
+    pub fn with_rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// 设置实际使用的凭据 ID
+    pub fn set_credential_id(&mut self, id: Option<u64>) {
+        self.record.credential_id = id;
+    }
+
+    /// 设置终端用户标识
+    pub fn set_user(&mut self, user: Option<String>) {
+        self.record.user = user;
+    }
+
+    /// 记录命中的护栏屏蔽规则
+    pub fn set_guardrail(&mut self, pattern: &str) {
+        self.guardrail.set(pattern);
+    }
+
+    /// 获取护栏命中标记，供流处理中的其他阶段写入
+    pub fn guardrail_flag(&self) -> GuardrailFlag {
+        self.guardrail.clone()
+    }
+
+    /// 累加 token 用量（同一请求可能多次调用上游，如结构化输出重试）
+    pub fn add_tokens(&mut self, input_tokens: i32, output_tokens: i32) {
+        self.record.input_tokens += input_tokens;
+        self.record.output_tokens += output_tokens;
+    }
+
+    /// 标记收到第一个生成 token（只记录第一次）
+    pub fn mark_first_token(&mut self) {
+        self.first_token.get_or_insert_with(Instant::now);
+    }
+
+    /// 设置 HTTP 状态码
+    pub fn set_status(&mut self, status: u16) {
+        self.record.status = status;
+    }
+}
+
+impl Drop for UsageRecorder {
+    fn drop(&mut self) {
+        self.record.timestamp = Utc::now();
+        self.record.latency_ms = self.started.elapsed().as_millis() as u64;
+        self.record.guardrail = self.guardrail.get();
+        if self.record.status == STATUS_CLIENT_CLOSED {
+            tracing::info!(
+                endpoint = %self.record.endpoint,
+                model = %self.record.model,
+                credential_id = ?self.record.credential_id,
+                latency_ms = self.record.latency_ms,
+                "客户端已断开，取消上游请求"
+            );
+        }
+        if let Some(threshold) = timing::threshold()
+            && self.started.elapsed() >= threshold
+        {
+            let phases = self.timing.as_ref().map(|t| t.phases()).unwrap_or_default();
+            tracing::warn!(
+                endpoint = %self.record.endpoint,
+                model = %self.record.model,
+                credential_id = ?self.record.credential_id,
+                status = self.record.status,
+                queue_ms = phases.queue.as_millis() as u64,
+                refresh_ms = phases.refresh.as_millis() as u64,
+                connect_ms = phases.connect.as_millis() as u64,
+                upstream_attempts = phases.attempts,
+                first_token_ms = self.first_token.map(|t| (t - self.started).as_millis() as u64),
+                total_ms = self.record.latency_ms,
+                "慢请求"
+            );
+        }
+        metrics::global().observe_request(
+            self.record.credential_id,
+            &self.record.model,
+            self.record.status,
+            self.started.elapsed(),
+            self.record.input_tokens,
+            self.record.output_tokens,
+        );
+        metrics::alerts().record_request(self.record.status);
+        if self.record.status == 200
+            && let Some(first_token) = self.first_token
+        {
+            metrics::global().observe_generation(
+                self.record.credential_id,
+                &self.record.model,
+                first_token - self.started,
+                tokens_per_second(self.record.output_tokens, first_token.elapsed()),
+            );
+        }
+        if let Some(limiter) = &self.rate_limiter {
+            let tokens = self.record.input_tokens.max(0) + self.record.output_tokens.max(0);
+            limiter.record_tokens(tokens as u64);
+        }
+        self.store.record(self.record.clone());
+    }
+}
+
+/// 首 token 之后的输出速度，输出过短（不足 2 个 token 或耗时过短）时无法计算
+fn tokens_per_second(output_tokens: i32, generation: Duration) -> Option<f64> {
+    let secs = generation.as_secs_f64();
+    (output_tokens > 1 && secs >= 0.001).then(|| output_tokens as f64 / secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorder_writes_on_drop() {
+        let store = Arc::new(UsageStore::in_memory(10));
+
+        let mut recorder =
+            UsageRecorder::new(store.clone(), "/v1/messages", "sk-***", "claude", true);
+        recorder.set_credential_id(Some(3));
+        recorder.add_tokens(5, 7);
+        drop(recorder);
+
+        let recent = store.recent(1);
+        assert_eq!(recent[0].credential_id, Some(3));
+        assert_eq!(recent[0].output_tokens, 7);
+        // 未标记完成的流式请求记为客户端断开
+        assert_eq!(recent[0].status, STATUS_CLIENT_CLOSED);
+    }
+
+    #[test]
+    fn test_non_stream_recorder_dropped_early_is_cancelled() {
+        let store = Arc::new(UsageStore::in_memory(10));
+
+        let recorder = UsageRecorder::new(store.clone(), "/v1/messages", "sk-***", "claude", false);
+        drop(recorder);
+
+        assert_eq!(store.recent(1)[0].status, STATUS_CLIENT_CLOSED);
+    }
+
+    #[test]
+    fn test_tokens_per_second() {
+        assert_eq!(tokens_per_second(100, Duration::from_secs(2)), Some(50.0));
+        assert_eq!(tokens_per_second(1, Duration::from_secs(2)), None);
+        assert_eq!(tokens_per_second(100, Duration::ZERO), None);
+    }
+}
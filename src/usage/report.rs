@@ -0,0 +1,320 @@
+//! 每日用量报告
+//!
+//! 每天在配置的时刻（UTC）汇总前一天的用量记录：请求数、tokens、各凭据的消耗与错误分布，
+//! 保存在内存中（配置了目录时同时写入 `<目录>/<日期>.json`，启动时恢复），
+//! 可通过 Admin API 查询，配置了 Webhook 时推送一份摘要。
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chrono::{Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::{UsageRecord, UsageStore};
+use crate::http_client;
+use crate::model::config::UsageReportConfig;
+
+/// 报告中列出的错误状态码数量上限
+const MAX_ERROR_HIGHLIGHTS: usize = 5;
+
+/// 内存中保留的报告数量上限
+const MAX_REPORTS: usize = 90;
+
+/// 单日用量报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyReport {
+    /// 统计日期（UTC）
+    pub date: NaiveDate,
+    /// 请求数
+    pub requests: u64,
+    /// 失败请求数（状态码非 2xx）
+    pub errors: u64,
+    /// 输入 tokens
+    pub input_tokens: i64,
+    /// 输出 tokens
+    pub output_tokens: i64,
+    /// 各凭据的用量（输出 tokens 从多到少，未到达上游的请求不计入）
+    pub credentials: Vec<CredentialUsage>,
+    /// 出现最多的错误状态码
+    pub top_errors: Vec<StatusCount>,
+}
+
+/// 单个凭据的用量
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialUsage {
+    pub credential_id: u64,
+    pub requests: u64,
+    pub errors: u64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+}
+
+/// 状态码计数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusCount {
+    pub status: u16,
+    pub count: u64,
+}
+
+impl DailyReport {
+    /// 由指定日期的记录生成报告
+    pub fn build(date: NaiveDate, records: &[UsageRecord]) -> Self {
+        let mut report = Self {
+            date,
+            requests: 0,
+            errors: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+            credentials: Vec::new(),
+            top_errors: Vec::new(),
+        };
+        let mut credentials: BTreeMap<u64, CredentialUsage> = BTreeMap::new();
+        let mut statuses: BTreeMap<u16, u64> = BTreeMap::new();
+
+        for record in records {
+            let error = !(200..300).contains(&record.status);
+            report.requests += 1;
+            report.errors += error as u64;
+            report.input_tokens += record.input_tokens as i64;
+            report.output_tokens += record.output_tokens as i64;
+            if error {
+                *statuses.entry(record.status).or_default() += 1;
+            }
+            if let Some(id) = record.credential_id {
+                let usage = credentials.entry(id).or_insert_with(|| CredentialUsage {
+                    credential_id: id,
+                    ..Default::default()
+                });
+                usage.requests += 1;
+                usage.errors += error as u64;
+                usage.input_tokens += record.input_tokens as i64;
+                usage.output_tokens += record.output_tokens as i64;
+            }
+        }
+
+        report.credentials = credentials.into_values().collect();
+        report.credentials.sort_by_key(|c| Reverse(c.output_tokens));
+        report.top_errors = statuses
+            .into_iter()
+            .map(|(status, count)| StatusCount { status, count })
+            .collect();
+        report.top_errors.sort_by_key(|s| Reverse(s.count));
+        report.top_errors.truncate(MAX_ERROR_HIGHLIGHTS);
+        report
+    }
+
+    /// 一段适合推送到聊天工具的文字摘要
+    pub fn summary_text(&self) -> String {
+        let mut text = format!(
+            "kiro-rs 用量日报 {}：请求 {}（失败 {}），输入 {} tokens，输出 {} tokens",
+            self.date, self.requests, self.errors, self.input_tokens, self.output_tokens
+        );
+        for usage in &self.credentials {
+            text.push_str(&format!(
+                "\n凭据 #{}：请求 {}（失败 {}），输出 {} tokens",
+                usage.credential_id, usage.requests, usage.errors, usage.output_tokens
+            ));
+        }
+        if !self.top_errors.is_empty() {
+            let errors: Vec<String> = self
+                .top_errors
+                .iter()
+                .map(|e| format!("{} × {}", e.status, e.count))
+                .collect();
+            text.push_str(&format!("\n主要错误：{}", errors.join("，")));
+        }
+        text
+    }
+}
+
+/// 已生成的报告
+pub struct ReportStore {
+    reports: Mutex<BTreeMap<NaiveDate, DailyReport>>,
+    /// 报告保存目录
+    dir: Option<PathBuf>,
+}
+
+impl ReportStore {
+    /// 创建报告存储，配置了目录时从中恢复已有报告
+    pub fn open(dir: Option<&str>) -> anyhow::Result<Self> {
+        let mut reports = BTreeMap::new();
+        let dir = dir.map(PathBuf::from);
+        if let Some(dir) = &dir {
+            fs::create_dir_all(dir)?;
+            for entry in fs::read_dir(dir)?.filter_map(Result::ok) {
+                let path = entry.path();
+                if path.extension().is_none_or(|ext| ext != "json") {
+                    continue;
+                }
+                match fs::read_to_string(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|s| Ok(serde_json::from_str::<DailyReport>(&s)?))
+                {
+                    Ok(report) => {
+                        reports.insert(report.date, report);
+                    }
+                    Err(e) => tracing::warn!("跳过无法解析的用量报告 ({}): {}", path.display(), e),
+                }
+            }
+        }
+        let store = Self {
+            reports: Mutex::new(reports),
+            dir,
+        };
+        store.trim();
+        Ok(store)
+    }
+
+    /// 保存一份报告（同一日期的报告会被覆盖）
+    pub fn insert(&self, report: DailyReport) {
+        if let Some(dir) = &self.dir {
+            let path = dir.join(format!("{}.json", report.date));
+            let result = serde_json::to_vec_pretty(&report)
+                .map_err(anyhow::Error::from)
+                .and_then(|bytes| Ok(fs::write(&path, bytes)?));
+            if let Err(e) = result {
+                tracing::warn!("保存用量报告失败 ({}): {}", path.display(), e);
+            }
+        }
+        self.reports.lock().insert(report.date, report);
+        self.trim();
+    }
+
+    /// 所有报告（最新的在前）
+    pub fn list(&self) -> Vec<DailyReport> {
+        self.reports.lock().values().rev().cloned().collect()
+    }
+
+    fn trim(&self) {
+        let mut reports = self.reports.lock();
+        while reports.len() > MAX_REPORTS {
+            reports.pop_first();
+        }
+    }
+}
+
+/// 启动每日报告任务
+pub fn spawn_daily(config: UsageReportConfig, usage: Arc<UsageStore>, reports: Arc<ReportStore>) {
+    let time = NaiveTime::from_hms_opt(config.hour_utc.min(23), 0, 0).unwrap_or_default();
+    tokio::spawn(async move {
+        let client = match http_client::build_integration_client(30) {
+            Ok(client) => client,
+            Err(e) => {
+                tracing::error!("创建用量报告的 HTTP Client 失败，每日报告未启动: {}", e);
+                return;
+            }
+        };
+        loop {
+            let now = Utc::now();
+            let mut next = now.date_naive().and_time(time);
+            if next <= now.naive_utc() {
+                next += Duration::days(1);
+            }
+            let wait = (next - now.naive_utc()).to_std().unwrap_or_default();
+            tokio::time::sleep(wait).await;
+
+            let date = next.date() - Duration::days(1);
+            let start = Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN));
+            let records = usage.between(start, start + Duration::days(1));
+            let report = DailyReport::build(date, &records);
+            tracing::info!(
+                "已生成 {} 的用量报告：请求 {}，失败 {}",
+                date,
+                report.requests,
+                report.errors
+            );
+
+            if let Some(url) = &config.webhook_url {
+                let body = json!({ "text": report.summary_text(), "report": &report });
+                match client.post(url).json(&body).send().await {
+                    Ok(resp) if !resp.status().is_success() => {
+                        tracing::warn!("推送用量报告失败: HTTP {}", resp.status());
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("推送用量报告失败: {}", e),
+                }
+            }
+            reports.insert(report);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(credential_id: Option<u64>, status: u16, output_tokens: i32) -> UsageRecord {
+        UsageRecord {
+            timestamp: Utc::now(),
+            endpoint: "/v1/messages".to_string(),
+            model: "claude-sonnet-4-5".to_string(),
+            api_key: "sk-***".to_string(),
+            credential_id,
+            input_tokens: 10,
+            output_tokens,
+            latency_ms: 100,
+            status,
+            stream: false,
+            user: None,
+            guardrail: None,
+            client_ip: None,
+        }
+    }
+
+    #[test]
+    fn test_build_report() {
+        let date = NaiveDate::from_ymd_opt(2026, 1, 2).unwrap();
+        let report = DailyReport::build(
+            date,
+            &[
+                record(Some(1), 200, 5),
+                record(Some(2), 200, 50),
+                record(Some(2), 502, 0),
+                record(None, 429, 0),
+                record(None, 429, 0),
+            ],
+        );
+
+        assert_eq!(report.requests, 5);
+        assert_eq!(report.errors, 3);
+        assert_eq!(report.input_tokens, 50);
+        assert_eq!(report.output_tokens, 55);
+        assert_eq!(report.credentials.len(), 2);
+        assert_eq!(report.credentials[0].credential_id, 2);
+        assert_eq!(report.credentials[0].errors, 1);
+        assert_eq!(report.top_errors[0].status, 429);
+        assert_eq!(report.top_errors[0].count, 2);
+        assert!(report.summary_text().contains("429 × 2"));
+    }
+
+    #[test]
+    fn test_report_store_persists_reports() {
+        let dir = std::env::temp_dir().join(format!("kiro-reports-{}", uuid::Uuid::new_v4()));
+        let dir_str = dir.to_string_lossy().into_owned();
+
+        let store = ReportStore::open(Some(&dir_str)).unwrap();
+        for day in 1..=2 {
+            let date = NaiveDate::from_ymd_opt(2026, 1, day).unwrap();
+            store.insert(DailyReport::build(date, &[record(Some(1), 200, 1)]));
+        }
+        drop(store);
+
+        let reopened = ReportStore::open(Some(&dir_str)).unwrap();
+        let reports = reopened.list();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(
+            reports[0].date,
+            NaiveDate::from_ymd_opt(2026, 1, 2).unwrap()
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,579 @@
+//! 用量记录存储
+//!
+//! 内存中保留最近的记录，配置了 `usageLogPath` 时同时以 JSON Lines 格式追加写入文件，
+//! 配置了 `databasePath` 时写入 SQLite 数据库，启动时从文件或数据库恢复最近的记录。
+//! 配置了保留天数时定期清理过期记录（并压缩文件）。
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use super::AccessLog;
+use super::timeseries::{self, SeriesMetric, SeriesPoint};
+use crate::common::batch_writer::BatchWriter;
+use crate::database::Database;
+
+/// 单个请求的用量记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageRecord {
+    /// 请求完成时间
+    pub timestamp: DateTime<Utc>,
+    /// 请求端点，如 `/v1/messages`
+    pub endpoint: String,
+    /// 客户端请求的模型
+    pub model: String,
+    /// 脱敏后的 API Key
+    pub api_key: String,
+    /// 实际使用的凭据 ID（未到达上游时为空）
+    pub credential_id: Option<u64>,
+    /// 输入 tokens
+    pub input_tokens: i32,
+    /// 输出 tokens
+    pub output_tokens: i32,
+    /// 请求耗时（毫秒）
+    pub latency_ms: u64,
+    /// HTTP 状态码（客户端中途断开记为 499）
+    pub status: u16,
+    /// 是否为流式请求
+    pub stream: bool,
+    /// 终端用户标识（OpenAI `user` / Anthropic `metadata.user_id`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// 命中的护栏屏蔽规则或外部审核结论
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guardrail: Option<String>,
+    /// 客户端 IP（经受信任代理转发时取自转发请求头）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+}
+
+/// 按 API Key 汇总的用量
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyUsageSummary {
+    /// 脱敏后的 API Key
+    pub api_key: String,
+    /// 请求数
+    pub requests: u64,
+    /// 输入 tokens
+    pub input_tokens: i64,
+    /// 输出 tokens
+    pub output_tokens: i64,
+    /// 按终端用户的明细（请求数从多到少，未携带用户标识的请求不计入）
+    pub users: Vec<UserUsageSummary>,
+}
+
+/// 单个终端用户的用量
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserUsageSummary {
+    /// 终端用户标识
+    pub user: String,
+    /// 请求数
+    pub requests: u64,
+    /// 输入 tokens
+    pub input_tokens: i64,
+    /// 输出 tokens
+    pub output_tokens: i64,
+}
+
+/// 用量存储
+pub struct UsageStore {
+    /// 最近的记录（按时间顺序）
+    records: Mutex<VecDeque<UsageRecord>>,
+    /// 内存中保留的最大记录数
+    capacity: usize,
+    /// 持久化文件
+    file: Option<Arc<Mutex<File>>>,
+    /// 持久化文件路径（仅用于日志）
+    path: Option<PathBuf>,
+    /// 持久化数据库
+    database: Option<Arc<Database>>,
+    /// 访问日志（可选）
+    access_log: Option<Arc<AccessLog>>,
+    /// 后台写入线程（首次记录时启动，仅内存存储时为 None）
+    writer: OnceLock<Option<BatchWriter<UsageRecord>>>,
+}
+
+impl UsageStore {
+    /// 创建仅内存的存储
+    pub fn in_memory(capacity: usize) -> Self {
+        Self {
+            records: Mutex::new(VecDeque::with_capacity(capacity.min(1024))),
+            capacity,
+            file: None,
+            path: None,
+            database: None,
+            access_log: None,
+            writer: OnceLock::new(),
+        }
+    }
+
+    /// 使用 SQLite 数据库持久化，从数据库恢复最近的 `capacity` 条记录
+    pub fn with_database(database: Arc<Database>, capacity: usize) -> anyhow::Result<Self> {
+        let records = database.recent_usage(capacity)?;
+        Ok(Self {
+            records: Mutex::new(records.into()),
+            capacity,
+            file: None,
+            path: None,
+            database: Some(database),
+            access_log: None,
+            writer: OnceLock::new(),
+        })
+    }
+
+    /// 打开持久化存储，文件不存在时自动创建
+    pub fn open(path: impl AsRef<Path>, capacity: usize) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut records = VecDeque::new();
+
+        if path.exists() {
+            let reader = BufReader::new(File::open(path)?);
+            for line in reader.lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<UsageRecord>(&line) {
+                    Ok(record) => {
+                        if records.len() == capacity {
+                            records.pop_front();
+                        }
+                        records.push_back(record);
+                    }
+                    Err(e) => tracing::warn!("跳过无法解析的用量记录: {}", e),
+                }
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            records: Mutex::new(records),
+            capacity,
+            file: Some(Arc::new(Mutex::new(file))),
+            path: Some(path.to_path_buf()),
+            database: None,
+            access_log: None,
+            writer: OnceLock::new(),
+        })
+    }
+
+    /// 删除早于 `before` 的记录（内存与持久化文件或数据库），返回删除的记录数
+    ///
+    /// 持久化时以文件或数据库中删除的记录数为准（内存只保留其中最近的部分）。
+    pub fn prune(&self, before: DateTime<Utc>) -> anyhow::Result<usize> {
+        // 先写完队列中的记录，避免清理后再写入早于 `before` 的记录
+        self.flush_writer();
+        let in_memory = {
+            let mut records = self.records.lock();
+            let len = records.len();
+            records.retain(|r| r.timestamp >= before);
+            len - records.len()
+        };
+
+        if let Some(database) = &self.database {
+            return Ok(database.prune_usage(before)?);
+        }
+        match &self.file {
+            Some(_) => self.prune_file(before),
+            None => Ok(in_memory),
+        }
+    }
+
+    /// 删除持久化文件中早于 `before` 的记录，返回删除的记录数
+    ///
+    /// 文件通过写入临时文件后重命名的方式整体重写，期间暂停追加写入。
+    fn prune_file(&self, before: DateTime<Utc>) -> anyhow::Result<usize> {
+        let (Some(file), Some(path)) = (&self.file, &self.path) else {
+            return Ok(0);
+        };
+        let mut file = file.lock();
+        file.flush()?;
+
+        let mut kept = Vec::new();
+        let mut removed = 0;
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // 无法解析的行原样保留，避免清理时丢失数据
+            match serde_json::from_str::<UsageRecord>(&line) {
+                Ok(record) if record.timestamp < before => removed += 1,
+                _ => kept.push(line),
+            }
+        }
+        if removed == 0 {
+            return Ok(0);
+        }
+
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp = path.with_file_name(tmp_name);
+        {
+            let mut out = File::create(&tmp)?;
+            for line in &kept {
+                writeln!(out, "{}", line)?;
+            }
+            out.sync_all()?;
+        }
+        fs::rename(&tmp, path)?;
+        *file = OpenOptions::new().append(true).open(path)?;
+        Ok(removed)
+    }
+
+    /// 同时将每条记录写入访问日志
+    pub fn with_access_log(mut self, access_log: AccessLog) -> Self {
+        self.access_log = Some(Arc::new(access_log));
+        self
+    }
+
+    /// 追加一条记录
+    ///
+    /// 内存中的记录立即更新；持久化文件、数据库与访问日志交给后台线程按批写入，不阻塞调用方
+    pub fn record(&self, record: UsageRecord) {
+        if let Some(writer) = self.writer() {
+            writer.send(record.clone());
+        }
+
+        let mut records = self.records.lock();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// 后台写入线程，首次调用时启动
+    fn writer(&self) -> Option<&BatchWriter<UsageRecord>> {
+        self.writer
+            .get_or_init(|| {
+                if self.file.is_none() && self.database.is_none() && self.access_log.is_none() {
+                    return None;
+                }
+                let file = self.file.clone();
+                let path = self.path.clone().unwrap_or_default();
+                let database = self.database.clone();
+                let access_log = self.access_log.clone();
+                Some(BatchWriter::spawn("usage-writer", move |records| {
+                    persist(
+                        &records,
+                        file.as_deref(),
+                        &path,
+                        database.as_deref(),
+                        access_log.as_deref(),
+                    )
+                }))
+            })
+            .as_ref()
+    }
+
+    /// 等待已提交的记录写入完成
+    fn flush_writer(&self) {
+        if let Some(Some(writer)) = self.writer.get() {
+            writer.flush();
+        }
+    }
+
+    /// 将已写入的记录同步到磁盘（退出前调用）
+    pub fn flush(&self) -> anyhow::Result<()> {
+        self.flush_writer();
+        if let Some(file) = &self.file {
+            let mut file = file.lock();
+            file.flush()?;
+            file.sync_data()?;
+        }
+        if let Some(access_log) = &self.access_log {
+            access_log.flush()?;
+        }
+        Ok(())
+    }
+
+    /// 获取最近的记录（最新的在前）
+    pub fn recent(&self, limit: usize) -> Vec<UsageRecord> {
+        self.records
+            .lock()
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// 获取时间在 `[start, end)` 内的记录（按时间顺序）
+    pub fn between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<UsageRecord> {
+        self.records
+            .lock()
+            .iter()
+            .filter(|r| r.timestamp >= start && r.timestamp < end)
+            .cloned()
+            .collect()
+    }
+
+    /// 按步长汇总 `start` 起 `buckets` 个桶内的记录
+    ///
+    /// 配置了数据库时在数据库中汇总（不受内存容量限制），查询失败时退回内存中的记录
+    pub fn series(
+        &self,
+        metric: SeriesMetric,
+        start: DateTime<Utc>,
+        step: Duration,
+        buckets: usize,
+    ) -> Vec<SeriesPoint> {
+        if let Some(database) = &self.database {
+            match database.usage_buckets(metric, start, step, buckets) {
+                Ok(sums) => return timeseries::points(sums, metric, start, step),
+                Err(e) => tracing::warn!("从数据库汇总用量时间序列失败: {}", e),
+            }
+        }
+        let records = self.between(start, start + step * buckets as i32);
+        timeseries::bucket(&records, metric, start, step, buckets)
+    }
+
+    /// 内存中的记录数
+    pub fn len(&self) -> usize {
+        self.records.lock().len()
+    }
+
+    /// 按 API Key 与终端用户汇总内存中的记录（请求数从多到少）
+    pub fn summary_by_key(&self) -> Vec<KeyUsageSummary> {
+        let mut keys: BTreeMap<String, (KeyUsageSummary, BTreeMap<String, UserUsageSummary>)> =
+            BTreeMap::new();
+        for record in self.records.lock().iter() {
+            let (key, users) = keys.entry(record.api_key.clone()).or_default();
+            key.requests += 1;
+            key.input_tokens += record.input_tokens as i64;
+            key.output_tokens += record.output_tokens as i64;
+            if let Some(user) = &record.user {
+                let entry = users.entry(user.clone()).or_default();
+                entry.requests += 1;
+                entry.input_tokens += record.input_tokens as i64;
+                entry.output_tokens += record.output_tokens as i64;
+            }
+        }
+
+        let mut summaries: Vec<KeyUsageSummary> = keys
+            .into_iter()
+            .map(|(api_key, (mut summary, users))| {
+                summary.api_key = api_key;
+                summary.users = users
+                    .into_iter()
+                    .map(|(user, mut usage)| {
+                        usage.user = user;
+                        usage
+                    })
+                    .collect();
+                summary.users.sort_by_key(|u| Reverse(u.requests));
+                summary
+            })
+            .collect();
+        summaries.sort_by_key(|s| Reverse(s.requests));
+        summaries
+    }
+}
+
+/// 在后台线程上写入一批记录
+fn persist(
+    records: &[UsageRecord],
+    file: Option<&Mutex<File>>,
+    path: &Path,
+    database: Option<&Database>,
+    access_log: Option<&AccessLog>,
+) {
+    if let Some(access_log) = access_log {
+        records.iter().for_each(|record| access_log.write(record));
+    }
+    if let Some(file) = file {
+        let mut lines = String::new();
+        for record in records {
+            match serde_json::to_string(record) {
+                Ok(line) => {
+                    lines.push_str(&line);
+                    lines.push('\n');
+                }
+                Err(e) => tracing::warn!("序列化用量记录失败: {}", e),
+            }
+        }
+        if let Err(e) = file.lock().write_all(lines.as_bytes()) {
+            tracing::warn!("写入用量记录失败 ({}): {}", path.display(), e);
+        }
+    }
+    if let Some(database) = database
+        && let Err(e) = database.insert_usage(records)
+    {
+        tracing::warn!("写入用量记录到数据库失败: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(model: &str) -> UsageRecord {
+        UsageRecord {
+            timestamp: Utc::now(),
+            endpoint: "/v1/messages".to_string(),
+            model: model.to_string(),
+            api_key: "sk-***".to_string(),
+            credential_id: Some(1),
+            input_tokens: 10,
+            output_tokens: 20,
+            latency_ms: 100,
+            status: 200,
+            stream: false,
+            user: None,
+            guardrail: None,
+            client_ip: None,
+        }
+    }
+
+    #[test]
+    fn test_in_memory_store_evicts_oldest() {
+        let store = UsageStore::in_memory(2);
+        store.record(record("a"));
+        store.record(record("b"));
+        store.record(record("c"));
+
+        let recent = store.recent(10);
+        assert_eq!(store.len(), 2);
+        assert_eq!(recent[0].model, "c");
+        assert_eq!(recent[1].model, "b");
+    }
+
+    #[test]
+    fn test_persistent_store_reloads_records() {
+        let path = std::env::temp_dir().join(format!("kiro-usage-{}.jsonl", uuid::Uuid::new_v4()));
+
+        let store = UsageStore::open(&path, 10).unwrap();
+        store.record(record("a"));
+        store.record(record("b"));
+        store.flush().unwrap();
+        drop(store);
+
+        let reopened = UsageStore::open(&path, 10).unwrap();
+        let recent = reopened.recent(10);
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].model, "b");
+        assert_eq!(recent[0].credential_id, Some(1));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_prune_removes_old_records_from_memory_and_file() {
+        let path = std::env::temp_dir().join(format!("kiro-usage-{}.jsonl", uuid::Uuid::new_v4()));
+        let now = Utc::now();
+        let aged = |model: &str, days: i64| UsageRecord {
+            timestamp: now - chrono::Duration::days(days),
+            ..record(model)
+        };
+
+        let store = UsageStore::open(&path, 10).unwrap();
+        store.record(aged("old", 40));
+        store.record(aged("new", 1));
+        let removed = store.prune(now - chrono::Duration::days(30)).unwrap();
+        assert_eq!(removed, 1);
+        assert_eq!(store.len(), 1);
+
+        // 清理后仍可继续追加
+        store.record(aged("newer", 0));
+        drop(store);
+
+        let reopened = UsageStore::open(&path, 10).unwrap();
+        let models: Vec<String> = reopened.recent(10).into_iter().map(|r| r.model).collect();
+        assert_eq!(models, vec!["newer", "new"]);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_prune_counts_in_memory_and_database_records() {
+        let now = Utc::now();
+        let aged = |model: &str, days: i64| UsageRecord {
+            timestamp: now - chrono::Duration::days(days),
+            ..record(model)
+        };
+        let before = now - chrono::Duration::days(30);
+
+        let store = UsageStore::in_memory(10);
+        store.record(aged("old", 40));
+        store.record(aged("older", 50));
+        store.record(aged("new", 1));
+        assert_eq!(store.prune(before).unwrap(), 2);
+        assert_eq!(store.len(), 1);
+
+        // 数据库中的记录超出内存容量时，以数据库中删除的数量为准
+        let database = Arc::new(Database::in_memory().unwrap());
+        let store = UsageStore::with_database(database.clone(), 1).unwrap();
+        store.record(aged("old", 40));
+        store.record(aged("older", 50));
+        store.record(aged("new", 1));
+        assert_eq!(store.prune(before).unwrap(), 2);
+
+        let reopened = UsageStore::with_database(database, 10).unwrap();
+        let models: Vec<String> = reopened.recent(10).into_iter().map(|r| r.model).collect();
+        assert_eq!(models, vec!["new"]);
+    }
+
+    #[test]
+    fn test_series_uses_database_beyond_memory_capacity() {
+        let start = DateTime::from_timestamp(1_000_000_000, 0).unwrap();
+        let at = |secs: i64, status: u16| UsageRecord {
+            timestamp: start + Duration::seconds(secs),
+            status,
+            ..record("m")
+        };
+        let database = Arc::new(Database::in_memory().unwrap());
+        let store = UsageStore::with_database(database, 1).unwrap();
+        store.record(at(0, 200));
+        store.record(at(30, 502));
+        store.record(at(90, 200));
+        store.record(at(600, 200));
+        store.flush().unwrap();
+
+        let values = |metric| -> Vec<f64> {
+            store
+                .series(metric, start, Duration::seconds(60), 3)
+                .into_iter()
+                .map(|p| p.value)
+                .collect()
+        };
+        assert_eq!(values(SeriesMetric::Requests), vec![2.0, 1.0, 0.0]);
+        assert_eq!(values(SeriesMetric::Errors), vec![1.0, 0.0, 0.0]);
+        assert_eq!(values(SeriesMetric::Tokens), vec![60.0, 30.0, 0.0]);
+    }
+
+    #[test]
+    fn test_summary_by_key_groups_users() {
+        let store = UsageStore::in_memory(10);
+        let with_user = |key: &str, user: Option<&str>| UsageRecord {
+            api_key: key.to_string(),
+            user: user.map(str::to_string),
+            ..record("m")
+        };
+        store.record(with_user("sk-a", Some("alice")));
+        store.record(with_user("sk-a", Some("alice")));
+        store.record(with_user("sk-a", Some("bob")));
+        store.record(with_user("sk-a", None));
+        store.record(with_user("sk-b", None));
+
+        let summary = store.summary_by_key();
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0].api_key, "sk-a");
+        assert_eq!(summary[0].requests, 4);
+        assert_eq!(summary[0].input_tokens, 40);
+        assert_eq!(summary[0].users.len(), 2);
+        assert_eq!(summary[0].users[0].user, "alice");
+        assert_eq!(summary[0].users[0].requests, 2);
+        assert!(summary[1].users.is_empty());
+    }
+}
@@ -0,0 +1,188 @@
+//! 用量时间序列
+//!
+//! 将用量记录按固定步长分桶，供 Admin UI 绘制请求数、错误数、tokens 与延迟曲线，
+//! 无需额外部署 Prometheus。配置了数据库时在 SQLite 中分桶汇总，否则使用内存中的记录。
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::UsageRecord;
+
+/// 单个序列的最大点数
+pub const MAX_POINTS: i64 = 2000;
+
+/// 时间窗口的上限
+pub const MAX_WINDOW: Duration = Duration::days(366);
+
+/// 时间序列指标
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeriesMetric {
+    /// 请求数
+    #[default]
+    Requests,
+    /// 失败请求数（状态码非 2xx）
+    Errors,
+    /// 输入 + 输出 tokens
+    Tokens,
+    /// 平均延迟（毫秒）
+    Latency,
+}
+
+/// 序列中的一个点
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeriesPoint {
+    /// 桶的起始时间
+    pub timestamp: DateTime<Utc>,
+    pub value: f64,
+}
+
+/// 解析 `30s` / `5m` / `24h` / `7d` 形式的时长（超出可表示范围时返回 None）
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let unit = s.chars().last()?;
+    let value: i64 = s[..s.len() - unit.len_utf8()].parse().ok()?;
+    if value <= 0 {
+        return None;
+    }
+    match unit {
+        's' => Duration::try_seconds(value),
+        'm' => Duration::try_minutes(value),
+        'h' => Duration::try_hours(value),
+        'd' => Duration::try_days(value),
+        _ => None,
+    }
+}
+
+/// 起止时间：结束时间对齐到 `now` 所在桶的末尾，起始时间向前覆盖整个窗口
+pub fn range(now: DateTime<Utc>, window: Duration, step: Duration) -> (DateTime<Utc>, usize) {
+    let step_secs = step.num_seconds().max(1);
+    let buckets = (window.num_seconds() + step_secs - 1) / step_secs;
+    let end = now.timestamp() - now.timestamp().rem_euclid(step_secs) + step_secs;
+    let start = DateTime::from_timestamp(end - buckets * step_secs, 0).unwrap_or(now);
+    (start, buckets as usize)
+}
+
+/// 将记录按步长分桶（`records` 中超出范围的记录会被忽略）
+pub fn bucket(
+    records: &[UsageRecord],
+    metric: SeriesMetric,
+    start: DateTime<Utc>,
+    step: Duration,
+    buckets: usize,
+) -> Vec<SeriesPoint> {
+    let step_secs = step.num_seconds().max(1);
+    let mut sums = vec![BucketSum::default(); buckets];
+    for record in records {
+        let offset = (record.timestamp - start).num_seconds();
+        if offset < 0 {
+            continue;
+        }
+        let Some(slot) = sums.get_mut((offset / step_secs) as usize) else {
+            continue;
+        };
+        slot.count += 1;
+        slot.sum += match metric {
+            SeriesMetric::Requests => 1.0,
+            SeriesMetric::Errors => !(200..300).contains(&record.status) as u8 as f64,
+            SeriesMetric::Tokens => (record.input_tokens + record.output_tokens) as f64,
+            SeriesMetric::Latency => record.latency_ms as f64,
+        };
+    }
+    points(sums, metric, start, step)
+}
+
+/// 一个桶内的请求数与指标累计值
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BucketSum {
+    pub count: u64,
+    pub sum: f64,
+}
+
+/// 将各桶的累计值转换为序列中的点
+pub fn points(
+    sums: Vec<BucketSum>,
+    metric: SeriesMetric,
+    start: DateTime<Utc>,
+    step: Duration,
+) -> Vec<SeriesPoint> {
+    let step_secs = step.num_seconds().max(1);
+    sums.into_iter()
+        .enumerate()
+        .map(|(i, slot)| SeriesPoint {
+            timestamp: start + Duration::seconds(i as i64 * step_secs),
+            value: match metric {
+                SeriesMetric::Latency if slot.count > 0 => slot.sum / slot.count as f64,
+                SeriesMetric::Latency => 0.0,
+                _ => slot.sum,
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(timestamp: DateTime<Utc>, status: u16, latency_ms: u64) -> UsageRecord {
+        UsageRecord {
+            timestamp,
+            endpoint: "/v1/messages".to_string(),
+            model: "claude-sonnet-4-5".to_string(),
+            api_key: "sk-***".to_string(),
+            credential_id: Some(1),
+            input_tokens: 10,
+            output_tokens: 5,
+            latency_ms,
+            status,
+            stream: false,
+            user: None,
+            guardrail: None,
+            client_ip: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("24h"), Some(Duration::hours(24)));
+        assert_eq!(parse_duration("5m"), Some(Duration::minutes(5)));
+        assert_eq!(parse_duration("30s"), Some(Duration::seconds(30)));
+        assert_eq!(parse_duration("7d"), Some(Duration::days(7)));
+        assert_eq!(parse_duration("0m"), None);
+        assert_eq!(parse_duration("9999999999999d"), None);
+        assert_eq!(parse_duration("5x"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn test_range_aligns_to_step() {
+        let now = DateTime::from_timestamp(1_000_000_130, 0).unwrap();
+        let (start, buckets) = range(now, Duration::minutes(10), Duration::minutes(1));
+        assert_eq!(buckets, 10);
+        assert_eq!(start.timestamp(), 1_000_000_140 - 600);
+    }
+
+    #[test]
+    fn test_bucket_metrics() {
+        let start = DateTime::from_timestamp(1_000_000_000, 0).unwrap();
+        let step = Duration::seconds(60);
+        let records = [
+            record(start, 200, 100),
+            record(start + Duration::seconds(30), 502, 300),
+            record(start + Duration::seconds(90), 200, 50),
+            record(start + Duration::seconds(600), 200, 50),
+        ];
+
+        let values = |metric| -> Vec<f64> {
+            bucket(&records, metric, start, step, 3)
+                .into_iter()
+                .map(|p| p.value)
+                .collect()
+        };
+        assert_eq!(values(SeriesMetric::Requests), vec![2.0, 1.0, 0.0]);
+        assert_eq!(values(SeriesMetric::Errors), vec![1.0, 0.0, 0.0]);
+        assert_eq!(values(SeriesMetric::Tokens), vec![30.0, 15.0, 0.0]);
+        assert_eq!(values(SeriesMetric::Latency), vec![200.0, 50.0, 0.0]);
+    }
+}